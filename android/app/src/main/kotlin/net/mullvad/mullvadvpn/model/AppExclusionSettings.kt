@@ -0,0 +1,10 @@
+package net.mullvad.mullvadvpn.model
+
+import android.os.Parcelable
+import kotlinx.parcelize.Parcelize
+
+@Parcelize
+data class AppExclusionSettings(
+    val enabled: Boolean,
+    val excludedPackages: ArrayList<String>
+) : Parcelable
@@ -9,5 +9,6 @@ data class Settings(
     val allowLan: Boolean,
     val autoConnect: Boolean,
     val tunnelOptions: TunnelOptions,
-    val showBetaReleases: Boolean
+    val showBetaReleases: Boolean,
+    val appExclusions: AppExclusionSettings
 ) : Parcelable
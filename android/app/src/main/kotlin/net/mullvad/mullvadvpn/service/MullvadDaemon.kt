@@ -2,6 +2,7 @@ package net.mullvad.mullvadvpn.service
 
 import kotlinx.coroutines.flow.MutableSharedFlow
 import kotlinx.coroutines.flow.asSharedFlow
+import net.mullvad.mullvadvpn.model.AppExclusionSettings
 import net.mullvad.mullvadvpn.model.AppVersionInfo
 import net.mullvad.mullvadvpn.model.Device
 import net.mullvad.mullvadvpn.model.DeviceEvent
@@ -138,6 +139,10 @@ class MullvadDaemon(vpnService: MullvadVpnService) {
         setAllowLan(daemonInterfaceAddress, allowLan)
     }
 
+    fun setAppExclusions(appExclusions: AppExclusionSettings) {
+        setAppExclusions(daemonInterfaceAddress, appExclusions)
+    }
+
     fun setAutoConnect(autoConnect: Boolean) {
         setAutoConnect(daemonInterfaceAddress, autoConnect)
     }
@@ -219,6 +224,10 @@ class MullvadDaemon(vpnService: MullvadVpnService) {
     ): RemoveDeviceResult
 
     private external fun setAllowLan(daemonInterfaceAddress: Long, allowLan: Boolean)
+    private external fun setAppExclusions(
+        daemonInterfaceAddress: Long,
+        appExclusions: AppExclusionSettings
+    )
     private external fun setAutoConnect(daemonInterfaceAddress: Long, alwaysOn: Boolean)
     private external fun setDnsOptions(daemonInterfaceAddress: Long, dnsOptions: DnsOptions)
     private external fun setWireguardMtu(daemonInterfaceAddress: Long, wireguardMtu: Int?)
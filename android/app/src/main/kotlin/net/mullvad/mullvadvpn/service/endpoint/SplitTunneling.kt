@@ -1,58 +1,122 @@
 package net.mullvad.mullvadvpn.service.endpoint
 
-import kotlin.properties.Delegates.observable
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.GlobalScope
+import kotlinx.coroutines.channels.Channel
+import kotlinx.coroutines.channels.ClosedReceiveChannelException
+import kotlinx.coroutines.channels.actor
+import kotlinx.coroutines.channels.sendBlocking
 import net.mullvad.mullvadvpn.ipc.Event
 import net.mullvad.mullvadvpn.ipc.Request
+import net.mullvad.mullvadvpn.model.AppExclusionSettings
+import net.mullvad.mullvadvpn.model.Settings
 import net.mullvad.mullvadvpn.service.persistence.SplitTunnelingPersistence
 import net.mullvad.talpid.util.EventNotifier
 
-class SplitTunneling(persistence: SplitTunnelingPersistence, endpoint: ServiceEndpoint) {
-    private val excludedApps = persistence.excludedApps.toMutableSet()
+class SplitTunneling(
+    private val persistence: SplitTunnelingPersistence,
+    endpoint: ServiceEndpoint
+) {
+    // Only the latest exclusions need to reach the daemon
+    private val updateChannel = spawnActor()
+    private val daemon = endpoint.intermittentDaemon
+    private val excludedApps = mutableSetOf<String>()
 
-    private var enabled by observable(persistence.enabled) { _, wasEnabled, isEnabled ->
-        if (wasEnabled != isEnabled) {
-            persistence.enabled = isEnabled
-            update()
-        }
-    }
+    private var enabled = false
 
-    val onChange = EventNotifier<List<String>?>(excludedApps.toList())
+    val onChange = EventNotifier<List<String>?>(null)
 
     init {
         onChange.subscribe(this) { excludedApps ->
             endpoint.sendEvent(Event.SplitTunnelingUpdate(excludedApps))
         }
 
+        endpoint.settingsListener.subscribe(this, ::handleNewSettings)
+
         endpoint.dispatcher.apply {
             registerHandler(Request.IncludeApp::class) { request ->
-                excludedApps.remove(request.packageName)
-                update()
+                synchronized(this@SplitTunneling) {
+                    excludedApps.remove(request.packageName)
+                    update()
+                }
             }
 
             registerHandler(Request.ExcludeApp::class) { request ->
-                excludedApps.add(request.packageName)
-                update()
+                synchronized(this@SplitTunneling) {
+                    excludedApps.add(request.packageName)
+                    update()
+                }
             }
 
             registerHandler(Request.SetEnableSplitTunneling::class) { request ->
-                enabled = request.enable
+                synchronized(this@SplitTunneling) {
+                    if (enabled != request.enable) {
+                        enabled = request.enable
+                        update()
+                    }
+                }
             }
 
-            registerHandler(Request.PersistExcludedApps::class) { _ ->
-                persistence.excludedApps = excludedApps
-            }
+            // Every change is saved by the daemon as soon as it is made
+            registerHandler(Request.PersistExcludedApps::class) { _ -> }
         }
     }
 
     fun onDestroy() {
+        updateChannel.close()
         onChange.unsubscribeAll()
     }
 
+    private fun handleNewSettings(settings: Settings) {
+        synchronized(this) {
+            val appExclusions = settings.appExclusions
+
+            // Apps used to be stored by the app itself rather than in the daemon settings
+            if (persistence.hasStoredSettings) {
+                if (!appExclusions.enabled && appExclusions.excludedPackages.isEmpty()) {
+                    enabled = persistence.enabled
+                    excludedApps.clear()
+                    excludedApps.addAll(persistence.excludedApps)
+                    persistence.clear()
+                    update()
+                    return
+                }
+
+                persistence.clear()
+            }
+
+            if (appExclusions.enabled != enabled ||
+                appExclusions.excludedPackages.toSet() != excludedApps
+            ) {
+                enabled = appExclusions.enabled
+                excludedApps.clear()
+                excludedApps.addAll(appExclusions.excludedPackages)
+                notifyChange()
+            }
+        }
+    }
+
     private fun update() {
+        notifyChange()
+        updateChannel.sendBlocking(AppExclusionSettings(enabled, ArrayList(excludedApps)))
+    }
+
+    private fun notifyChange() {
         if (enabled) {
             onChange.notify(excludedApps.toList())
         } else {
             onChange.notify(null)
         }
     }
+
+    private fun spawnActor() =
+        GlobalScope.actor<AppExclusionSettings>(Dispatchers.Default, Channel.CONFLATED) {
+            try {
+                for (appExclusions in channel) {
+                    daemon.await().setAppExclusions(appExclusions)
+                }
+            } catch (exception: ClosedReceiveChannelException) {
+                // Closed sender, so stop the actor
+            }
+        }
 }
@@ -3,5 +3,6 @@ set -eu
 
 chmod u+s "/usr/bin/mullvad-exclude"
 
+systemctl enable "/opt/Mullvad VPN/resources/mullvad-early-boot-blocking.service"
 systemctl enable "/opt/Mullvad VPN/resources/mullvad-daemon.service"
 systemctl start mullvad-daemon.service
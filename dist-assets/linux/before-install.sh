@@ -6,6 +6,7 @@ if which systemctl &> /dev/null; then
         /opt/Mullvad\ VPN/resources/mullvad-setup prepare-restart || true
         systemctl stop mullvad-daemon.service
         systemctl disable mullvad-daemon.service
+        systemctl disable mullvad-early-boot-blocking.service || true
         cp /var/log/mullvad-vpn/daemon.log /var/log/mullvad-vpn/old-install-daemon.log \
             || echo "Failed to copy old daemon log"
     fi
@@ -17,6 +17,7 @@ fi
 # the user might've disabled or stopped the service themselves already
 systemctl stop mullvad-daemon.service || true
 systemctl disable mullvad-daemon.service || true
+systemctl disable mullvad-early-boot-blocking.service || true
 
 pkill -x "mullvad-gui" || true
 
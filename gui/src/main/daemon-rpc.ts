@@ -10,6 +10,7 @@ import { promisify } from 'util';
 import {
   AccountToken,
   AfterDisconnect,
+  AutoConnectScope,
   BridgeSettings,
   BridgeState,
   ConnectionConfig,
@@ -63,6 +64,7 @@ import {
   InvalidAccountError,
   ListDevicesError,
   TooManyDevicesError,
+  TunnelBusyError,
 } from './errors';
 import { ManagementServiceClient } from './management_interface/management_interface_grpc_pb';
 import * as grpcTypes from './management_interface/management_interface_pb';
@@ -404,11 +406,16 @@ export class DaemonRpc {
   }
 
   public async disconnectTunnel(): Promise<void> {
-    await this.callEmpty(this.client.disconnectTunnel);
+    await this.callInterruptingTunnel(this.client.disconnectTunnel);
   }
 
   public async reconnectTunnel(): Promise<void> {
-    await this.callEmpty(this.client.reconnectTunnel);
+    await this.callInterruptingTunnel(this.client.reconnectTunnel);
+  }
+
+  // Carries out the disconnect or reconnect that was held back with a TunnelBusyError.
+  public async confirmDisconnect(): Promise<void> {
+    await this.callEmpty(this.client.confirmDisconnect);
   }
 
   public async getLocation(): Promise<ILocation> {
@@ -570,6 +577,20 @@ export class DaemonRpc {
     return this.call<Empty, R>(fn, new Empty());
   }
 
+  // The daemon holds back disconnects and reconnects while the tunnel is busy, if the user has
+  // asked it to.
+  private async callInterruptingTunnel<R>(fn: CallFunctionArgument<Empty, R>): Promise<R> {
+    try {
+      return await this.callEmpty(fn);
+    } catch (e) {
+      const error = e as grpc.ServiceError;
+      if (error.code === grpc.status.FAILED_PRECONDITION) {
+        throw new TunnelBusyError(error.details);
+      }
+      throw error;
+    }
+  }
+
   private callString<R>(fn: CallFunctionArgument<StringValue, R>, value?: string): Promise<R> {
     const googleString = new StringValue();
 
@@ -879,6 +900,8 @@ function convertFromTunnelStateErrorCause(
     }
     case grpcTypes.ErrorState.Cause.SPLIT_TUNNEL_ERROR:
       return { reason: 'split_tunnel_error' };
+    case grpcTypes.ErrorState.Cause.STRICT_ENFORCEMENT:
+      return { reason: 'strict_enforcement', details: state.strictEnforcementFailure };
     case grpcTypes.ErrorState.Cause.VPN_PERMISSION_DENIED:
       // VPN_PERMISSION_DENIED is only ever created on Android
       throw invalidErrorStateCause;
@@ -949,6 +972,8 @@ function convertFromObfuscationEndpoint(
 ): IObfuscationEndpoint {
   const obfuscationTypes: Record<grpcTypes.ObfuscationType, ObfuscationType> = {
     [grpcTypes.ObfuscationType.UDP2TCP]: 'udp2tcp',
+    [grpcTypes.ObfuscationType.EXTERNAL]: 'external',
+    [grpcTypes.ObfuscationType.WSS]: 'wss',
   };
 
   return {
@@ -972,8 +997,10 @@ function convertFromSettings(settings: grpcTypes.Settings): ISettings | undefine
   const bridgeSettings = convertFromBridgeSettings(settingsObject.bridgeSettings!);
   const tunnelOptions = convertFromTunnelOptions(settingsObject.tunnelOptions!);
   const splitTunnel = settingsObject.splitTunnel ?? { enableExclusions: false, appsList: [] };
+  const autoConnectScope = convertFromAutoConnectScope(settingsObject.autoConnectScope?.scope);
   return {
     ...settings.toObject(),
+    autoConnectScope,
     bridgeState,
     relaySettings,
     bridgeSettings,
@@ -982,6 +1009,12 @@ function convertFromSettings(settings: grpcTypes.Settings): ISettings | undefine
   };
 }
 
+function convertFromAutoConnectScope(
+  scope?: grpcTypes.AutoConnectScope.Scope,
+): AutoConnectScope {
+  return scope === grpcTypes.AutoConnectScope.Scope.MACHINE ? 'machine' : 'user';
+}
+
 function convertFromBridgeState(bridgeState: grpcTypes.BridgeState.State): BridgeState {
   const bridgeStateMap: Record<grpcTypes.BridgeState.State, BridgeState> = {
     [grpcTypes.BridgeState.State.AUTO]: 'auto',
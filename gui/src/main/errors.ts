@@ -22,6 +22,12 @@ export class TooManyDevicesError extends Error {
   }
 }
 
+export class TunnelBusyError extends Error {
+  constructor(message: string) {
+    super(message);
+  }
+}
+
 export class ListDevicesError extends Error {
   constructor() {
     super('Failed to fetch list of devices');
@@ -64,7 +64,7 @@ import AccountDataCache from './account-data-cache';
 import { getOpenAtLogin, setOpenAtLogin } from './autostart';
 import { readChangelog } from './changelog';
 import { ConnectionObserver, DaemonRpc, SubscriptionListener } from './daemon-rpc';
-import { InvalidAccountError } from './errors';
+import { InvalidAccountError, TunnelBusyError } from './errors';
 import Expectation from './expectation';
 import GuiSettings from './gui-settings';
 import { IpcMainEventChannel } from './ipc-event-channel';
@@ -155,6 +155,7 @@ class ApplicationMain {
   private settings: ISettings = {
     allowLan: false,
     autoConnect: false,
+    autoConnectScope: 'user',
     blockWhenDisconnected: false,
     showBetaReleases: false,
     splitTunnel: {
@@ -441,10 +442,21 @@ class ApplicationMain {
   private async prepareToQuit() {
     if (this.quitWithoutDisconnect) {
       log.info('Not disconnecting tunnel on quit');
+    } else if (this.settings.autoConnectScope === 'machine') {
+      log.info('Not disconnecting tunnel on quit since auto-connect is a machine-wide setting');
     } else {
       if (this.connectedToDaemon) {
         try {
-          await this.daemonRpc.disconnectTunnel();
+          try {
+            await this.daemonRpc.disconnectTunnel();
+          } catch (e) {
+            if (!(e instanceof TunnelBusyError)) {
+              throw e;
+            }
+            // Quitting is an explicit request to disconnect, so the warning is not shown.
+            log.info(`Disconnecting although the tunnel is busy: ${e.message}`);
+            await this.daemonRpc.confirmDisconnect();
+          }
           log.info('Disconnected the tunnel');
         } catch (e) {
           const error = e as Error;
@@ -875,17 +887,53 @@ class ApplicationMain {
   private reconnectTunnel = async (): Promise<void> => {
     if (reconnectEnabled(this.connectedToDaemon, this.isLoggedIn(), this.tunnelState.state)) {
       this.setOptimisticTunnelState('connecting');
-      await this.daemonRpc.reconnectTunnel();
+      try {
+        await this.daemonRpc.reconnectTunnel();
+      } catch (e) {
+        await this.confirmTunnelInterruption(e);
+      }
     }
   };
 
   private disconnectTunnel = async (): Promise<void> => {
     if (disconnectEnabled(this.connectedToDaemon, this.tunnelState.state)) {
       this.setOptimisticTunnelState('disconnecting');
-      await this.daemonRpc.disconnectTunnel();
+      try {
+        await this.daemonRpc.disconnectTunnel();
+      } catch (e) {
+        await this.confirmTunnelInterruption(e);
+      }
     }
   };
 
+  // Asks the user whether to go ahead with a disconnect or reconnect that the daemon held back
+  // because the tunnel is busy.
+  private async confirmTunnelInterruption(error: unknown): Promise<void> {
+    if (!(error instanceof TunnelBusyError)) {
+      throw error;
+    }
+
+    const { response } = await dialog.showMessageBox({
+      type: 'warning',
+      message: messages.pgettext('tunnel-busy-dialog', 'The tunnel is busy transferring data'),
+      detail: error.message,
+      buttons: [
+        messages.gettext('Cancel'),
+        messages.pgettext('tunnel-busy-dialog', 'Interrupt anyway'),
+      ],
+      defaultId: 0,
+      cancelId: 0,
+    });
+
+    if (response === 1) {
+      await this.daemonRpc.confirmDisconnect();
+    } else if (this.tunnelStateFallback) {
+      this.tunnelStateFallbackScheduler.cancel();
+      this.setTunnelStateImpl(this.tunnelStateFallback);
+      this.tunnelStateFallback = undefined;
+    }
+  }
+
   private setAccountHistory(accountHistory?: AccountToken) {
     this.accountHistory = accountHistory;
 
@@ -1643,6 +1691,12 @@ class ApplicationMain {
   }
 
   private updateDaemonsAutoConnect() {
+    // A machine-wide auto-connect setting is managed by an administrator and must not be overridden
+    // by the preferences of whichever user happens to be logged on.
+    if (this.settings.autoConnectScope === 'machine') {
+      return;
+    }
+
     const daemonAutoConnect = this.guiSettings.autoConnect && getOpenAtLogin();
     if (daemonAutoConnect !== this.settings.autoConnect) {
       void this.daemonRpc.setAutoConnect(daemonAutoConnect);
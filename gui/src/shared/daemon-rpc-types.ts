@@ -44,7 +44,8 @@ export type ErrorStateCause =
     }
   | { reason: 'set_firewall_policy_error'; details: FirewallPolicyError }
   | { reason: 'tunnel_parameter_error'; details: TunnelParameterError }
-  | { reason: 'auth_failed'; details?: string };
+  | { reason: 'auth_failed'; details?: string }
+  | { reason: 'strict_enforcement'; details: string };
 
 export type AfterDisconnect = 'nothing' | 'block' | 'reconnect';
 
@@ -61,7 +62,7 @@ export function tunnelTypeToString(tunnel: TunnelType): string {
 }
 
 export type RelayProtocol = 'tcp' | 'udp';
-export type ObfuscationType = 'udp2tcp';
+export type ObfuscationType = 'udp2tcp' | 'wss' | 'external';
 
 export type Constraint<T> = 'any' | { only: T };
 export type LiftedConstraint<T> = 'any' | T;
@@ -371,6 +372,7 @@ export interface IDeviceRemoval {
 export interface ISettings {
   allowLan: boolean;
   autoConnect: boolean;
+  autoConnectScope: AutoConnectScope;
   blockWhenDisconnected: boolean;
   showBetaReleases: boolean;
   relaySettings: RelaySettings;
@@ -382,6 +384,8 @@ export interface ISettings {
 
 export type BridgeState = 'auto' | 'on' | 'off';
 
+export type AutoConnectScope = 'user' | 'machine';
+
 export type SplitTunnelSettings = {
   enableExclusions: boolean;
   appsList: string[];
@@ -152,6 +152,16 @@ function getMessage(errorDetails: IErrorState, accountExpiry?: string): string {
           'notifications',
           'Unable to communicate with Mullvad kernel driver. Try reconnecting or contact support.',
         );
+      case 'strict_enforcement':
+        return sprintf(
+          // TRANSLATORS: Available placeholders:
+          // TRANSLATORS: %(failure)s - a technical description of what failed, in English
+          messages.pgettext(
+            'notifications',
+            'Strict enforcement is enabled and the connection could not be fully set up: %(failure)s',
+          ),
+          { failure: errorDetails.cause.details },
+        );
     }
   }
 }
@@ -156,6 +156,7 @@ const config = {
       { from: distAssets('libtalpid_openvpn_plugin.so'), to: '.' },
       { from: distAssets('binaries/x86_64-unknown-linux-gnu/openvpn'), to: '.' },
       { from: distAssets('linux/mullvad-daemon.service'), to: '.' },
+      { from: distAssets('linux/mullvad-early-boot-blocking.service'), to: '.' },
     ],
   },
 
@@ -170,9 +171,11 @@ const config = {
       distAssets('linux/before-remove.sh'),
       '--config-files',
       '/opt/Mullvad VPN/resources/mullvad-daemon.service',
+      '/opt/Mullvad VPN/resources/mullvad-early-boot-blocking.service',
       distAssets('mullvad') + '=/usr/bin/',
       distAssets('mullvad-exclude') + '=/usr/bin/',
       distAssets('linux/problem-report-link') + '=/usr/bin/mullvad-problem-report',
+      distAssets('linux/net.mullvad.VPN.conf') + '=/usr/share/dbus-1/system.d/',
       distAssets('shell-completions/mullvad.bash') +
         '=/usr/share/bash-completion/completions/mullvad',
       distAssets('shell-completions/_mullvad') + '=/usr/local/share/zsh/site-functions/_mullvad',
@@ -193,9 +196,11 @@ const config = {
       distAssets('linux/post-transaction.sh'),
       '--config-files',
       '/opt/Mullvad VPN/resources/mullvad-daemon.service',
+      '/opt/Mullvad VPN/resources/mullvad-early-boot-blocking.service',
       distAssets('mullvad') + '=/usr/bin/',
       distAssets('mullvad-exclude') + '=/usr/bin/',
       distAssets('linux/problem-report-link') + '=/usr/bin/mullvad-problem-report',
+      distAssets('linux/net.mullvad.VPN.conf') + '=/usr/share/dbus-1/system.d/',
       distAssets('shell-completions/mullvad.bash') +
         '=/usr/share/bash-completion/completions/mullvad',
       distAssets('shell-completions/_mullvad') + '=/usr/share/zsh/site-functions/_mullvad',
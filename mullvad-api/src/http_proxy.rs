@@ -0,0 +1,122 @@
+//! A minimal HTTP proxy client, which only supports the `CONNECT` method and, optionally, basic
+//! authentication (RFC 7617).
+
+use std::{io, net::SocketAddr};
+use talpid_types::net::openvpn::ProxyAuth;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Responses with longer headers than this are rejected.
+const MAX_RESPONSE_HEADER_SIZE: usize = 8 * 1024;
+
+/// Asks the HTTP proxy at the other end of `stream` to connect to `target`. Once this returns,
+/// `stream` carries the traffic to and from `target`.
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    target: SocketAddr,
+    auth: Option<&ProxyAuth>,
+) -> io::Result<()> {
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(auth) = auth {
+        let credentials = base64::encode(format!("{}:{}", auth.username, auth.password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read one byte at a time, so that nothing after the header is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_HEADER_SIZE {
+            return Err(protocol_error("the proxy response header is too long"));
+        }
+        response.push(stream.read_u8().await?);
+    }
+
+    let status_line = String::from_utf8_lossy(&response);
+    let mut parts = status_line.split_whitespace();
+    if !parts
+        .next()
+        .map(|version| version.starts_with("HTTP/1."))
+        .unwrap_or(false)
+    {
+        return Err(protocol_error("unexpected HTTP version"));
+    }
+    let status: u16 = parts
+        .next()
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| protocol_error("invalid status code"))?;
+
+    match status {
+        200..=299 => Ok(()),
+        407 => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            if auth.is_some() {
+                "the proxy rejected the username or password"
+            } else {
+                "the proxy requires authentication"
+            },
+        )),
+        status => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("the proxy failed to connect, status code {}", status),
+        )),
+    }
+}
+
+fn protocol_error(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const REQUEST: &[u8] = b"CONNECT 1.2.3.4:443 HTTP/1.1\r\nHost: 1.2.3.4:443\r\n\
+        Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n";
+
+    #[tokio::test]
+    async fn test_connect_with_auth() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let auth = ProxyAuth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        let target: SocketAddr = "1.2.3.4:443".parse().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut request = vec![0u8; REQUEST.len()];
+            server.read_exact(&mut request).await.unwrap();
+            assert_eq!(request, REQUEST);
+            server
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\ntunneled")
+                .await
+                .unwrap();
+        });
+
+        connect(&mut client, target, Some(&auth)).await.unwrap();
+        server.await.unwrap();
+
+        // Data that follows the response belongs to the tunneled connection
+        let mut tunneled = [0u8; 8];
+        client.read_exact(&mut tunneled).await.unwrap();
+        assert_eq!(&tunneled, b"tunneled");
+    }
+
+    #[tokio::test]
+    async fn test_connect_refused() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let target: SocketAddr = "1.2.3.4:443".parse().unwrap();
+
+        tokio::spawn(async move {
+            let mut request = [0u8; 1];
+            server.read_exact(&mut request).await.unwrap();
+            server
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let error = connect(&mut client, target, None).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
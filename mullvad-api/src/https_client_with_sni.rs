@@ -1,6 +1,8 @@
 use crate::{
     abortable_stream::{AbortableStream, AbortableStreamHandle},
+    http_proxy,
     proxy::{ApiConnection, ApiConnectionMode, ProxyConfig},
+    socks5,
     tls_stream::TlsStream,
     AddressCache,
 };
@@ -33,7 +35,7 @@ use std::{
     task::{Context, Poll},
     time::Duration,
 };
-use talpid_types::ErrorExt;
+use talpid_types::{net::openvpn::RemoteProxySettings, ErrorExt};
 
 use tokio::{
     net::{TcpSocket, TcpStream},
@@ -72,6 +74,10 @@ enum InnerConnectionMode {
     Direct,
     /// Connect to the destination via a proxy.
     Proxied(ParsedShadowsocksConfig),
+    /// Connect to the destination via a SOCKS5 proxy.
+    Socks5(RemoteProxySettings),
+    /// Connect to the destination via an HTTP proxy.
+    Http(RemoteProxySettings),
 }
 
 #[derive(Clone)]
@@ -107,6 +113,12 @@ impl TryFrom<ApiConnectionMode> for InnerConnectionMode {
                         .map_err(|_| ProxyConfigError::InvalidCipher(config.cipher))?,
                 })
             }
+            ApiConnectionMode::Proxied(ProxyConfig::Socks5(config)) => {
+                InnerConnectionMode::Socks5(config)
+            }
+            ApiConnectionMode::Proxied(ProxyConfig::Http(config)) => {
+                InnerConnectionMode::Http(config)
+            }
         })
     }
 }
@@ -322,6 +334,38 @@ impl Service<Uri> for HttpsConnectorWithSni {
                             let tls_stream = TlsStream::connect_https(proxy, &hostname).await?;
                             Ok(ApiConnection::Proxied(Box::new(tls_stream)))
                         }
+                        InnerConnectionMode::Socks5(proxy_config) => {
+                            let mut socket = Self::open_socket(
+                                proxy_config.address,
+                                #[cfg(target_os = "android")]
+                                socket_bypass_tx.clone(),
+                            )
+                            .await?;
+                            timeout(
+                                CONNECT_TIMEOUT,
+                                socks5::connect(&mut socket, addr, proxy_config.auth.as_ref()),
+                            )
+                            .await
+                            .map_err(|err| io::Error::new(io::ErrorKind::TimedOut, err))??;
+                            let tls_stream = TlsStream::connect_https(socket, &hostname).await?;
+                            Ok(ApiConnection::Direct(Box::new(tls_stream)))
+                        }
+                        InnerConnectionMode::Http(proxy_config) => {
+                            let mut socket = Self::open_socket(
+                                proxy_config.address,
+                                #[cfg(target_os = "android")]
+                                socket_bypass_tx.clone(),
+                            )
+                            .await?;
+                            timeout(
+                                CONNECT_TIMEOUT,
+                                http_proxy::connect(&mut socket, addr, proxy_config.auth.as_ref()),
+                            )
+                            .await
+                            .map_err(|err| io::Error::new(io::ErrorKind::TimedOut, err))??;
+                            let tls_stream = TlsStream::connect_https(socket, &hostname).await?;
+                            Ok(ApiConnection::Direct(Box::new(tls_stream)))
+                        }
                     }
                 };
 
@@ -0,0 +1,290 @@
+//! Checks for signs that the network blocks or tampers with connections to the API. The checks
+//! connect directly to the API, so they go through the firewall only while it allows the API.
+
+use crate::{tls_stream::read_cert_store, API};
+use mullvad_types::interference::{CheckKind, CheckOutcome, InterferenceCheck};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_rustls::{
+    rustls::{
+        self,
+        client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+        Certificate, ClientConfig, ServerName,
+    },
+    TlsConnector,
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs the DNS, TCP and TLS checks against the API at `address`, which should be the address
+/// that the app uses to reach it.
+pub async fn check_api(address: SocketAddr) -> Vec<InterferenceCheck> {
+    let mut checks = vec![check_dns(address.ip()).await];
+
+    match timeout(CHECK_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(stream)) => {
+            checks.push(check(
+                CheckKind::ApiTcp,
+                CheckOutcome::Passed,
+                format!("Connected to {}", address),
+            ));
+            checks.push(check_tls(stream).await);
+        }
+        Ok(Err(error)) => {
+            checks.push(check(
+                CheckKind::ApiTcp,
+                CheckOutcome::Failed,
+                format!("Could not connect to {}: {}", address, error),
+            ));
+            checks.push(tls_skipped());
+        }
+        Err(_) => {
+            checks.push(check(
+                CheckKind::ApiTcp,
+                CheckOutcome::Failed,
+                format!("Timed out connecting to {}", address),
+            ));
+            checks.push(tls_skipped());
+        }
+    }
+
+    checks
+}
+
+/// Resolves the API hostname with the system resolver and compares the result with the address
+/// that the app knows about.
+async fn check_dns(known_address: IpAddr) -> InterferenceCheck {
+    let resolved = match timeout(
+        CHECK_TIMEOUT,
+        tokio::net::lookup_host((API.host.as_str(), 443)),
+    )
+    .await
+    {
+        Ok(Ok(addresses)) => addresses.map(|address| address.ip()).collect::<Vec<_>>(),
+        Ok(Err(error)) => {
+            return check(
+                CheckKind::ApiDns,
+                CheckOutcome::Inconclusive,
+                format!("Could not resolve {}: {}", API.host, error),
+            )
+        }
+        Err(_) => {
+            return check(
+                CheckKind::ApiDns,
+                CheckOutcome::Inconclusive,
+                format!("Timed out resolving {}", API.host),
+            )
+        }
+    };
+    let resolved_list = resolved
+        .iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if resolved.iter().any(|address| !is_public(address)) {
+        check(
+            CheckKind::ApiDns,
+            CheckOutcome::Failed,
+            format!(
+                "{} resolves to {}, which is not a public address",
+                API.host, resolved_list
+            ),
+        )
+    } else if resolved.contains(&known_address) {
+        check(
+            CheckKind::ApiDns,
+            CheckOutcome::Passed,
+            format!("{} resolves to {}", API.host, resolved_list),
+        )
+    } else {
+        check(
+            CheckKind::ApiDns,
+            CheckOutcome::Inconclusive,
+            format!(
+                "{} resolves to {}, but the app uses {}. The API may have moved, or the \
+                 responses may be tampered with",
+                API.host, resolved_list, known_address
+            ),
+        )
+    }
+}
+
+/// Performs a TLS handshake over `stream` and checks that the certificate is signed by the
+/// authority that the API uses. The fingerprint of the certificate is included in the details,
+/// so that an intercepting proxy can be identified.
+async fn check_tls(stream: TcpStream) -> InterferenceCheck {
+    let verifier = Arc::new(RecordingVerifier {
+        inner: WebPkiVerifier::new(read_cert_store(), None),
+        fingerprint: Mutex::new(None),
+    });
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    let server_name = match ServerName::try_from(API.host.as_str()) {
+        Ok(server_name) => server_name,
+        Err(_) => {
+            return check(
+                CheckKind::ApiTls,
+                CheckOutcome::Inconclusive,
+                format!("Invalid hostname \"{}\"", API.host),
+            )
+        }
+    };
+
+    let result = timeout(
+        CHECK_TIMEOUT,
+        TlsConnector::from(Arc::new(config)).connect(server_name, stream),
+    )
+    .await;
+    let fingerprint = verifier.fingerprint.lock().unwrap().take();
+
+    match (result, fingerprint) {
+        (Ok(Ok(_)), Some(fingerprint)) => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Passed,
+            format!(
+                "The certificate is signed by the expected authority (SHA-256 {})",
+                fingerprint
+            ),
+        ),
+        (Ok(Err(error)), Some(fingerprint)) => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Failed,
+            format!(
+                "The certificate (SHA-256 {}) was rejected: {}",
+                fingerprint, error
+            ),
+        ),
+        (Ok(Err(error)), None) if error.kind() == io::ErrorKind::ConnectionReset => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Failed,
+            "The connection was reset before a certificate was presented, which suggests that \
+             it is blocked based on the server name"
+                .to_owned(),
+        ),
+        (Ok(Err(error)), None) => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Inconclusive,
+            format!("The handshake failed: {}", error),
+        ),
+        (Ok(Ok(_)), None) => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Inconclusive,
+            "No certificate was presented".to_owned(),
+        ),
+        (Err(_), _) => check(
+            CheckKind::ApiTls,
+            CheckOutcome::Inconclusive,
+            "Timed out during the handshake".to_owned(),
+        ),
+    }
+}
+
+fn tls_skipped() -> InterferenceCheck {
+    check(
+        CheckKind::ApiTls,
+        CheckOutcome::Inconclusive,
+        "Skipped, since no TCP connection could be made".to_owned(),
+    )
+}
+
+fn check(kind: CheckKind, outcome: CheckOutcome, details: String) -> InterferenceCheck {
+    InterferenceCheck {
+        kind,
+        outcome,
+        details,
+    }
+}
+
+/// Returns whether `address` can be reached on the internet. Poisoned DNS responses often point
+/// at local or reserved addresses.
+pub fn is_public(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            !(address.is_private()
+                || address.is_loopback()
+                || address.is_link_local()
+                || address.is_unspecified()
+                || address.is_broadcast())
+        }
+        IpAddr::V6(address) => {
+            !(address.is_loopback()
+                || address.is_unspecified()
+                // Unique local addresses
+                || (address.segments()[0] & 0xfe00) == 0xfc00
+                // Link-local addresses
+                || (address.segments()[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Verifies certificates in the same way as the API client, but also remembers the fingerprint
+/// of the most recently presented certificate.
+struct RecordingVerifier {
+    inner: WebPkiVerifier,
+    fingerprint: Mutex<Option<String>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.fingerprint.lock().unwrap() = Some(fingerprint(&end_entity.0));
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/// Formats the SHA-256 digest of `certificate` as colon-separated hex bytes.
+fn fingerprint(certificate: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, certificate)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        assert!(is_public(&"45.83.222.100".parse().unwrap()));
+        assert!(is_public(&"2a03:1b20:1:f011::a01f".parse().unwrap()));
+        assert!(!is_public(&"127.0.0.1".parse().unwrap()));
+        assert!(!is_public(&"10.1.2.3".parse().unwrap()));
+        assert!(!is_public(&"0.0.0.0".parse().unwrap()));
+        assert!(!is_public(&"fd00::1".parse().unwrap()));
+        assert!(!is_public(&"fe80::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(
+            fingerprint(b""),
+            "E3:B0:C4:42:98:FC:1C:14:9A:FB:F4:C8:99:6F:B9:24:\
+             27:AE:41:E4:64:9B:93:4C:A4:95:99:1B:78:52:B8:55"
+        );
+    }
+}
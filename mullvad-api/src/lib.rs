@@ -6,7 +6,7 @@ use futures::channel::mpsc;
 use futures::Stream;
 use hyper::Method;
 use mullvad_types::{
-    account::{AccountToken, VoucherSubmission},
+    account::{AccountToken, LoginLink, VoucherSubmission},
     version::AppVersion,
 };
 use proxy::ApiConnectionMode;
@@ -23,8 +23,10 @@ use availability::{ApiAvailability, ApiAvailabilityHandle};
 pub mod rest;
 
 mod abortable_stream;
+mod http_proxy;
 mod https_client_with_sni;
 pub mod proxy;
+mod socks5;
 mod tls_stream;
 #[cfg(target_os = "android")]
 pub use crate::https_client_with_sni::SocketBypassRequest;
@@ -33,7 +35,9 @@ mod access;
 mod address_cache;
 pub mod device;
 mod fs;
+pub mod interference;
 mod relay_list;
+pub mod stats;
 pub use address_cache::AddressCache;
 pub use device::DevicesProxy;
 pub use hyper::StatusCode;
@@ -129,6 +133,7 @@ pub struct Runtime {
     handle: tokio::runtime::Handle,
     pub address_cache: AddressCache,
     api_availability: availability::ApiAvailability,
+    stats: stats::ApiStats,
     #[cfg(target_os = "android")]
     socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
 }
@@ -176,6 +181,7 @@ impl Runtime {
             handle,
             address_cache: AddressCache::new(None)?,
             api_availability: ApiAvailability::new(availability::State::default()),
+            stats: stats::ApiStats::default(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         })
@@ -223,6 +229,7 @@ impl Runtime {
             handle,
             address_cache,
             api_availability: ApiAvailability::new(availability::State::default()),
+            stats: stats::ApiStats::default(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         })
@@ -242,6 +249,7 @@ impl Runtime {
             self.address_cache.clone(),
             proxy_provider,
             new_address_callback,
+            self.stats.clone(),
             #[cfg(target_os = "android")]
             socket_bypass_tx,
         )
@@ -288,6 +296,11 @@ impl Runtime {
         .await
     }
 
+    /// Returns the counters of the requests made by all request services of this runtime.
+    pub fn stats(&self) -> stats::ApiStats {
+        self.stats.clone()
+    }
+
     pub fn handle(&mut self) -> &mut tokio::runtime::Handle {
         &mut self.handle
     }
@@ -387,6 +400,48 @@ impl AccountsProxy {
         }
     }
 
+    /// Requests a short-lived code that can be approved in a browser to log in without entering
+    /// the account number on this device.
+    pub fn create_login_link(&self) -> impl Future<Output = Result<LoginLink, rest::Error>> {
+        let service = self.handle.service.clone();
+        let response = rest::send_request(
+            &self.handle.factory,
+            service,
+            &format!("{}/login-links", ACCOUNTS_URL_PREFIX),
+            Method::POST,
+            None,
+            &[StatusCode::CREATED],
+        );
+
+        async move { rest::deserialize_body(response.await?).await }
+    }
+
+    /// Returns the account that approved the login link, or `None` if it is still pending.
+    pub fn poll_login_link(
+        &self,
+        code: String,
+    ) -> impl Future<Output = Result<Option<AccountToken>, rest::Error>> {
+        #[derive(serde::Deserialize)]
+        struct LoginLinkResponse {
+            account_number: Option<AccountToken>,
+        }
+
+        let service = self.handle.service.clone();
+        let response = rest::send_request(
+            &self.handle.factory,
+            service,
+            &format!("{}/login-links/{}", ACCOUNTS_URL_PREFIX, code),
+            Method::GET,
+            None,
+            &[StatusCode::OK],
+        );
+
+        async move {
+            let response: LoginLinkResponse = rest::deserialize_body(response.await?).await?;
+            Ok(response.account_number)
+        }
+    }
+
     pub fn get_www_auth_token(
         &self,
         account: AccountToken,
@@ -10,7 +10,10 @@ use std::{
     pin::Pin,
     task::{self, Poll},
 };
-use talpid_types::{net::openvpn::ShadowsocksProxySettings, ErrorExt};
+use talpid_types::{
+    net::openvpn::{RemoteProxySettings, ShadowsocksProxySettings},
+    ErrorExt,
+};
 use tokio::{
     fs,
     io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
@@ -39,6 +42,8 @@ impl fmt::Display for ApiConnectionMode {
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum ProxyConfig {
     Shadowsocks(ShadowsocksProxySettings),
+    Socks5(RemoteProxySettings),
+    Http(RemoteProxySettings),
 }
 
 impl fmt::Display for ProxyConfig {
@@ -46,6 +51,8 @@ impl fmt::Display for ProxyConfig {
         match self {
             // TODO: Do not hardcode TCP
             ProxyConfig::Shadowsocks(ss) => write!(f, "Shadowsocks {}/TCP", ss.peer),
+            ProxyConfig::Socks5(socks) => write!(f, "SOCKS5 {}/TCP", socks.address),
+            ProxyConfig::Http(http) => write!(f, "HTTP {}/TCP", http.address),
         }
     }
 }
@@ -115,6 +122,8 @@ impl ApiConnectionMode {
     pub fn get_endpoint(&self) -> Option<SocketAddr> {
         match self {
             ApiConnectionMode::Proxied(ProxyConfig::Shadowsocks(ss)) => Some(ss.peer),
+            ApiConnectionMode::Proxied(ProxyConfig::Socks5(socks)) => Some(socks.address),
+            ApiConnectionMode::Proxied(ProxyConfig::Http(http)) => Some(http.address),
             ApiConnectionMode::Direct => None,
         }
     }
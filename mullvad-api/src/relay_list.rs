@@ -21,6 +21,9 @@ pub struct RelayListProxy {
 
 const RELAY_LIST_TIMEOUT: Duration = Duration::from_secs(15);
 
+/// Appended to the URL of a mirrored relay list to get the URL of its signature.
+const SIGNATURE_URL_SUFFIX: &str = ".sig";
+
 impl RelayListProxy {
     /// Construct a new relay list rest client
     pub fn new(handle: rest::MullvadRestHandle) -> Self {
@@ -51,16 +54,7 @@ impl RelayListProxy {
                 return rest::handle_error_response(response).await;
             }
 
-            let etag = response
-                .headers()
-                .get(header::ETAG)
-                .and_then(|tag| match tag.to_str() {
-                    Ok(tag) => Some(tag.to_string()),
-                    Err(_) => {
-                        log::error!("Ignoring invalid tag from server: {:?}", tag.as_bytes());
-                        None
-                    }
-                });
+            let etag = response_etag(&response);
 
             Ok(Some(
                 rest::deserialize_body::<ServerRelayList>(response)
@@ -69,6 +63,73 @@ impl RelayListProxy {
             ))
         }
     }
+
+    /// Fetch the relay list from a mirror instead of the API. The raw Ed25519 signature of the
+    /// list is fetched from the same URL with `.sig` appended, and the list is rejected unless
+    /// it has been signed with the key of the mirror.
+    ///
+    /// `service` must not be bound to the API hostname, since the mirror is likely to be hosted
+    /// elsewhere.
+    pub fn relay_list_from_mirror(
+        service: rest::RequestServiceHandle,
+        mirror: relay_list::RelayListMirror,
+        etag: Option<String>,
+    ) -> impl Future<Output = Result<Option<relay_list::RelayList>, rest::Error>> {
+        async move {
+            let mut request = rest::RestRequest::get(&mirror.url)?;
+            request.set_timeout(RELAY_LIST_TIMEOUT);
+            if let Some(ref tag) = etag {
+                request.add_header(header::IF_NONE_MATCH, tag)?;
+            }
+
+            let response = service.request(request).await?;
+            if etag.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+            if response.status() != StatusCode::OK {
+                return rest::handle_error_response(response).await;
+            }
+            let etag = response_etag(&response);
+            let relay_list = rest::read_body(response).await?;
+
+            let mut request =
+                rest::RestRequest::get(&format!("{}{}", mirror.url, SIGNATURE_URL_SUFFIX))?;
+            request.set_timeout(RELAY_LIST_TIMEOUT);
+            let response = service.request(request).await?;
+            if response.status() != StatusCode::OK {
+                return rest::handle_error_response(response).await;
+            }
+            let signature = rest::read_body(response).await?;
+            verify_signature(&mirror.public_key, &relay_list, &signature)?;
+
+            let relay_list: ServerRelayList =
+                serde_json::from_slice(&relay_list).map_err(rest::Error::DeserializeError)?;
+            Ok(Some(relay_list.into_relay_list(etag)))
+        }
+    }
+}
+
+fn response_etag(response: &rest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|tag| match tag.to_str() {
+            Ok(tag) => Some(tag.to_string()),
+            Err(_) => {
+                log::error!("Ignoring invalid tag from server: {:?}", tag.as_bytes());
+                None
+            }
+        })
+}
+
+fn verify_signature(
+    public_key: &[u8; 32],
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<(), rest::Error> {
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(payload, signature)
+        .map_err(|_| rest::Error::InvalidSignature)
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -207,15 +268,19 @@ impl ServerRelayList {
                             .iter_mut()
                             .find(|r| r.hostname == wireguard_relay.relay.hostname)
                         {
-                            Some(relay) => relay
-                                .tunnels
-                                .wireguard
-                                .push(wireguard_endpoint_data(wireguard_relay.public_key)),
+                            Some(relay) => {
+                                relay
+                                    .tunnels
+                                    .wireguard
+                                    .push(wireguard_endpoint_data(wireguard_relay.public_key));
+                                relay.obfuscators.wss = wireguard_relay.wss;
+                            }
                             None => {
                                 let mut relay = relay(wireguard_relay.relay, location);
                                 relay.ipv6_addr_in = Some(wireguard_relay.ipv6_addr_in);
                                 relay.tunnels.wireguard =
                                     vec![wireguard_endpoint_data(wireguard_relay.public_key)];
+                                relay.obfuscators.wss = wireguard_relay.wss;
                                 city.relays.push(relay);
                             }
                         };
@@ -310,6 +375,8 @@ fn relay(relay: Relay, location: location::Location) -> relay_list::Relay {
         active: relay.active,
         owned: relay.owned,
         provider: relay.provider,
+        provider_name: relay.provider_name,
+        datacenter: relay.datacenter,
         weight: relay.weight,
         tunnels: Default::default(),
         bridges: Default::default(),
@@ -339,6 +406,10 @@ struct Relay {
     owned: bool,
     location: String,
     provider: String,
+    #[serde(default)]
+    provider_name: Option<String>,
+    #[serde(default)]
+    datacenter: Option<String>,
     ipv4_addr_in: Ipv4Addr,
     weight: u64,
     include_in_country: bool,
@@ -348,6 +419,9 @@ impl Relay {
     fn convert_to_lowercase(&mut self) {
         self.hostname = self.hostname.to_lowercase();
         self.location = self.location.to_lowercase();
+        if let Some(datacenter) = &mut self.datacenter {
+            *datacenter = datacenter.to_lowercase();
+        }
     }
 }
 
@@ -365,6 +439,9 @@ struct WireGuardRelay {
     relay: Relay,
     ipv6_addr_in: Ipv6Addr,
     public_key: wireguard::PublicKey,
+    /// WebSocket over TLS endpoints, if the relay has any.
+    #[serde(default)]
+    wss: Vec<relay_list::WssEndpointData>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -372,3 +449,30 @@ struct Bridges {
     shadowsocks: Vec<relay_list::ShadowsocksEndpointData>,
     relays: Vec<Relay>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_verify_signature() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(key_pair.public_key().as_ref());
+
+        let payload = br#"{"locations": {}}"#;
+        let signature = key_pair.sign(payload);
+
+        assert!(verify_signature(&public_key, payload, signature.as_ref()).is_ok());
+        assert!(matches!(
+            verify_signature(&public_key, br#"{"locations": []}"#, signature.as_ref()),
+            Err(rest::Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_signature(&[0u8; 32], payload, signature.as_ref()),
+            Err(rest::Error::InvalidSignature)
+        ));
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     availability::ApiAvailabilityHandle,
     https_client_with_sni::{HttpsConnectorWithSni, HttpsConnectorWithSniHandle},
     proxy::ApiConnectionMode,
+    stats::{self, ApiStats, RequestOutcome},
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -74,6 +75,10 @@ pub enum Error {
     /// The string given was not a valid URI.
     #[error(display = "Not a valid URI")]
     UriError(#[error(source)] http::uri::InvalidUri),
+
+    /// The response was not signed by the expected key.
+    #[error(display = "The response has an invalid signature")]
+    InvalidSignature,
 }
 
 impl Error {
@@ -122,6 +127,9 @@ pub(crate) struct RequestService<
     new_address_callback: F,
     address_cache: AddressCache,
     api_availability: ApiAvailabilityHandle,
+    /// Connection mode used by new requests.
+    connection_mode: ApiConnectionMode,
+    stats: ApiStats,
 }
 
 impl<
@@ -136,6 +144,7 @@ impl<
         address_cache: AddressCache,
         mut proxy_config_provider: T,
         new_address_callback: F,
+        stats: ApiStats,
         #[cfg(target_os = "android")] socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
     ) -> RequestServiceHandle {
         let (connector, connector_handle) = HttpsConnectorWithSni::new(
@@ -145,8 +154,10 @@ impl<
             socket_bypass_tx.clone(),
         );
 
+        let mut connection_mode = ApiConnectionMode::Direct;
         if let Some(config) = proxy_config_provider.next().await {
-            connector_handle.set_connection_mode(config);
+            connector_handle.set_connection_mode(config.clone());
+            connection_mode = config;
         }
 
         let (command_tx, command_rx) = mpsc::unbounded();
@@ -163,6 +174,8 @@ impl<
             new_address_callback,
             address_cache,
             api_availability,
+            connection_mode,
+            stats,
         };
         let handle = RequestServiceHandle { tx: command_tx };
         tokio::spawn(service.into_future());
@@ -176,6 +189,9 @@ impl<
                 let timeout = request.timeout();
 
                 let hyper_request = request.into_request();
+                let endpoint = stats::endpoint_name(hyper_request.method(), hyper_request.uri());
+                let access_method = self.connection_mode.to_string();
+                let stats = self.stats.clone();
 
                 let api_availability = self.api_availability.clone();
                 let suspend_fut = api_availability.wait_for_unsuspend();
@@ -193,6 +209,13 @@ impl<
 
                     let response = flatten_result(response).map_err(|error| error.map_aborted());
 
+                    let outcome = match &response {
+                        Ok(response) => RequestOutcome::Response(response.status()),
+                        Err(error) if error.is_aborted() => RequestOutcome::Aborted,
+                        Err(_) => RequestOutcome::Failed,
+                    };
+                    stats.record(endpoint, access_method, outcome);
+
                     if let Err(err) = &response {
                         if err.is_network_error() && !api_availability.get_state().is_offline() {
                             log::error!("{}", err.display_chain_with_msg("HTTP request failed"));
@@ -221,7 +244,9 @@ impl<
                     };
                     // Switch to new connection mode unless rejected by address change callback
                     if (self.new_address_callback)(endpoint).await {
-                        self.connector_handle.set_connection_mode(new_config);
+                        self.connector_handle
+                            .set_connection_mode(new_config.clone());
+                        self.connection_mode = new_config;
                     }
                 }
             }
@@ -539,15 +564,25 @@ pub async fn deserialize_body<T: serde::de::DeserializeOwned>(response: Response
 }
 
 async fn deserialize_body_inner<T: serde::de::DeserializeOwned>(
-    mut response: Response,
+    response: Response,
     body_length: usize,
 ) -> Result<T> {
+    let body = read_body_inner(response, body_length).await?;
+    serde_json::from_slice(&body).map_err(Error::DeserializeError)
+}
+
+/// Reads the entire body of a response.
+pub async fn read_body(response: Response) -> Result<Vec<u8>> {
+    let body_length = get_body_length(&response);
+    read_body_inner(response, body_length).await
+}
+
+async fn read_body_inner(mut response: Response, body_length: usize) -> Result<Vec<u8>> {
     let mut body: Vec<u8> = Vec::with_capacity(body_length);
     while let Some(chunk) = response.body_mut().next().await {
         body.extend(&chunk?);
     }
-
-    serde_json::from_slice(&body).map_err(Error::DeserializeError)
+    Ok(body)
 }
 
 fn get_body_length(response: &Response) -> usize {
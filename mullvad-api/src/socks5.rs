@@ -0,0 +1,167 @@
+//! A minimal SOCKS5 client (RFC 1928), which only supports the `CONNECT` command and, optionally,
+//! username/password authentication (RFC 1929).
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+};
+use talpid_types::net::openvpn::ProxyAuth;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xff;
+
+const CMD_CONNECT: u8 = 0x01;
+
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Asks the SOCKS5 server at the other end of `stream` to connect to `target`. Once this returns,
+/// `stream` carries the traffic to and from `target`.
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    target: SocketAddr,
+    auth: Option<&ProxyAuth>,
+) -> io::Result<()> {
+    let method = if auth.is_some() {
+        METHOD_USERNAME_PASSWORD
+    } else {
+        METHOD_NO_AUTH
+    };
+    stream.write_all(&[VERSION, 1, method]).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(protocol_error("unexpected SOCKS version"));
+    }
+    match (reply[1], auth) {
+        (METHOD_NO_AUTH, _) => (),
+        (METHOD_USERNAME_PASSWORD, Some(auth)) => authenticate(stream, auth).await?,
+        (METHOD_NO_ACCEPTABLE, _) => {
+            return Err(protocol_error(
+                "the proxy does not accept the authentication method",
+            ))
+        }
+        _ => return Err(protocol_error("unexpected authentication method")),
+    }
+
+    let mut request = vec![VERSION, CMD_CONNECT, 0x00];
+    match target.ip() {
+        IpAddr::V4(ip) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(protocol_error("unexpected SOCKS version"));
+    }
+    if reply[1] != 0x00 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("the proxy failed to connect, reply code {}", reply[1]),
+        ));
+    }
+
+    // Skip the address that the proxy bound to
+    let address_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => usize::from(stream.read_u8().await?),
+        _ => return Err(protocol_error("unexpected address type")),
+    };
+    let mut bound_address = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound_address).await?;
+
+    Ok(())
+}
+
+async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    auth: &ProxyAuth,
+) -> io::Result<()> {
+    let username = auth.username.as_bytes();
+    let password = auth.password.as_bytes();
+    let username_len = u8::try_from(username.len())
+        .map_err(|_| protocol_error("the proxy username is too long"))?;
+    let password_len = u8::try_from(password.len())
+        .map_err(|_| protocol_error("the proxy password is too long"))?;
+
+    let mut request = vec![AUTH_VERSION, username_len];
+    request.extend_from_slice(username);
+    request.push(password_len);
+    request.extend_from_slice(password);
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0x00 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the proxy rejected the username or password",
+        ));
+    }
+    Ok(())
+}
+
+fn protocol_error(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_with_auth() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let auth = ProxyAuth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        let target: SocketAddr = "1.2.3.4:443".parse().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut greeting = [0u8; 3];
+            server.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [VERSION, 1, METHOD_USERNAME_PASSWORD]);
+            server
+                .write_all(&[VERSION, METHOD_USERNAME_PASSWORD])
+                .await
+                .unwrap();
+
+            let mut auth_request = [0u8; 11];
+            server.read_exact(&mut auth_request).await.unwrap();
+            assert_eq!(&auth_request, b"\x01\x04user\x04pass");
+            server.write_all(&[AUTH_VERSION, 0x00]).await.unwrap();
+
+            let mut request = [0u8; 10];
+            server.read_exact(&mut request).await.unwrap();
+            assert_eq!(
+                request,
+                [VERSION, CMD_CONNECT, 0, ATYP_IPV4, 1, 2, 3, 4, 0x01, 0xbb]
+            );
+            server
+                .write_all(&[VERSION, 0, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        connect(&mut client, target, Some(&auth)).await.unwrap();
+        server.await.unwrap();
+    }
+}
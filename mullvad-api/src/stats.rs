@@ -0,0 +1,124 @@
+//! Counts the requests made by the request services, so that it is possible to see how often each
+//! endpoint is used, how often requests fail and which access methods are used to reach them.
+
+use hyper::{Method, StatusCode, Uri};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// Path segments at least this long are assumed to be identifiers, such as account numbers and
+/// device IDs, and are not included in endpoint names.
+const MIN_ID_LENGTH: usize = 16;
+
+/// Counters for the requests made to a single endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointStats {
+    /// Number of requests that have been sent.
+    pub requests: u64,
+    /// Requests that got no response, because of a network error or a timeout.
+    pub failures: u64,
+    /// Requests that were answered with an error status code. This includes `rate_limited`.
+    pub error_responses: u64,
+    /// Requests that were rejected with `429 Too Many Requests`.
+    pub rate_limited: u64,
+    /// Number of requests that were sent using each access method.
+    pub access_methods: BTreeMap<String, u64>,
+}
+
+/// How a request ended.
+pub(crate) enum RequestOutcome {
+    Response(StatusCode),
+    Failed,
+    /// The request was cancelled before it finished, e.g. because the connection was reset.
+    Aborted,
+}
+
+/// Request counters that are shared by all request services of a runtime. They are kept in
+/// memory only, so they cover the requests made since the runtime was created.
+#[derive(Debug, Clone, Default)]
+pub struct ApiStats {
+    endpoints: Arc<Mutex<BTreeMap<String, EndpointStats>>>,
+}
+
+impl ApiStats {
+    pub(crate) fn record(&self, endpoint: String, access_method: String, outcome: RequestOutcome) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint).or_default();
+        stats.requests += 1;
+        *stats.access_methods.entry(access_method).or_default() += 1;
+        match outcome {
+            RequestOutcome::Response(status) => {
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    stats.rate_limited += 1;
+                }
+                if status.is_client_error() || status.is_server_error() {
+                    stats.error_responses += 1;
+                }
+            }
+            RequestOutcome::Failed => stats.failures += 1,
+            RequestOutcome::Aborted => (),
+        }
+    }
+
+    /// Returns the counters of every endpoint that has been used, ordered by endpoint name.
+    pub fn snapshot(&self) -> BTreeMap<String, EndpointStats> {
+        self.endpoints.lock().unwrap().clone()
+    }
+}
+
+/// Returns a name such as `GET api.mullvad.net/app/v1/relays` that identifies an endpoint.
+/// Identifiers in the path are replaced with `*`, and the query is left out.
+pub(crate) fn endpoint_name(method: &Method, uri: &Uri) -> String {
+    let path = uri
+        .path()
+        .split('/')
+        .map(|segment| {
+            if segment.len() >= MIN_ID_LENGTH {
+                "*"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{} {}{}", method, uri.host().unwrap_or_default(), path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_name() {
+        let uri: Uri =
+            "https://api.mullvad.net/accounts/v1/devices/7c2e3a9b-1d4f-4c1e-9b0a-5f6d7e8a9b0c?x=1"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            endpoint_name(&Method::DELETE, &uri),
+            "DELETE api.mullvad.net/accounts/v1/devices/*"
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let stats = ApiStats::default();
+        let status = |code| RequestOutcome::Response(StatusCode::from_u16(code).unwrap());
+        stats.record("GET a".to_owned(), "unproxied".to_owned(), status(200));
+        stats.record("GET a".to_owned(), "unproxied".to_owned(), status(429));
+        stats.record(
+            "GET a".to_owned(),
+            "Shadowsocks 1.2.3.4:443/TCP".to_owned(),
+            RequestOutcome::Failed,
+        );
+
+        let snapshot = stats.snapshot();
+        let endpoint = &snapshot["GET a"];
+        assert_eq!(endpoint.requests, 3);
+        assert_eq!(endpoint.failures, 1);
+        assert_eq!(endpoint.error_responses, 1);
+        assert_eq!(endpoint.rate_limited, 1);
+        assert_eq!(endpoint.access_methods["unproxied"], 2);
+    }
+}
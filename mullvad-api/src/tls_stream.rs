@@ -55,7 +55,7 @@ where
     }
 }
 
-fn read_cert_store() -> rustls::RootCertStore {
+pub(crate) fn read_cert_store() -> rustls::RootCertStore {
     let mut cert_store = rustls::RootCertStore::empty();
 
     let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(LE_ROOT_CERT))
@@ -12,9 +12,14 @@ const REVOKED_MESSAGE: &str = "The current device has been revoked";
 const DEVICE_NOT_FOUND_ERROR: &str = "There is no such device";
 const INVALID_ACCOUNT_ERROR: &str = "The account does not exist";
 const TOO_MANY_DEVICES_ERROR: &str =
-    "There are too many devices on this account. Revoke one to log in";
+    "There are too many devices on this account. Revoke one with `mullvad account devices revoke` \
+    to log in";
 const ALREADY_LOGGED_IN_ERROR: &str =
     "You are already logged in. Please log out before creating a new account";
+const WEB_LOGIN_EXPIRED_ERROR: &str = "The code expired before it was approved";
+
+/// How often to check whether the daemon has completed a web login.
+const WEB_LOGIN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 pub struct Account;
 
@@ -30,11 +35,22 @@ impl Command for Account {
             .setting(clap::AppSettings::SubcommandRequiredElseHelp)
             .subcommand(clap::App::new("create").about("Create and log in to a new account"))
             .subcommand(
-                clap::App::new("login").about("Log in to an account").arg(
-                    clap::Arg::new("account")
-                        .help("The Mullvad account token to configure the client with")
-                        .required(false),
-                ),
+                clap::App::new("login")
+                    .about("Log in to an account")
+                    .arg(
+                        clap::Arg::new("account")
+                            .help("The Mullvad account token to configure the client with")
+                            .required(false),
+                    )
+                    .arg(
+                        clap::Arg::new("web")
+                            .long("web")
+                            .help(
+                                "Log in by approving a one-time code in a browser instead of \
+                                entering the account number on this device",
+                            )
+                            .conflicts_with("account"),
+                    ),
             )
             .subcommand(clap::App::new("logout").about("Log out of the current account"))
             .subcommand(
@@ -47,36 +63,23 @@ impl Command for Account {
                             .help("Enables verbose output"),
                     ),
             )
+            .subcommand(with_list_devices_args(
+                clap::App::new("list-devices").about("List devices associated with an account"),
+            ))
+            .subcommand(with_revoke_device_args(
+                clap::App::new("revoke-device").about("Revoke a device associated with an account"),
+            ))
             .subcommand(
-                clap::App::new("list-devices")
-                    .about("List devices associated with an account")
-                    .arg(
-                        clap::Arg::new("account")
-                            .help("Mullvad account number")
-                            .long("account")
-                            .takes_value(true),
-                    )
-                    .arg(
-                        clap::Arg::new("verbose")
-                            .long("verbose")
-                            .short('v')
-                            .help("Enables verbose output"),
-                    ),
-            )
-            .subcommand(
-                clap::App::new("revoke-device")
-                    .about("Revoke a device associated with an account")
-                    .arg(
-                        clap::Arg::new("account")
-                            .help("Mullvad account number")
-                            .long("account")
-                            .takes_value(true),
-                    )
-                    .arg(
-                        clap::Arg::new("device")
-                            .help("Name or ID of the device to revoke")
-                            .required(true),
-                    ),
+                clap::App::new("devices")
+                    .about("Manage the devices associated with an account")
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(with_list_devices_args(
+                        clap::App::new("list").about("List devices associated with an account"),
+                    ))
+                    .subcommand(with_revoke_device_args(
+                        clap::App::new("revoke")
+                            .about("Revoke a device associated with an account"),
+                    )),
             )
             .subcommand(
                 clap::App::new("redeem").about("Redeems a voucher").arg(
@@ -91,7 +94,11 @@ impl Command for Account {
         if let Some(_matches) = matches.subcommand_matches("create") {
             self.create().await
         } else if let Some(set_matches) = matches.subcommand_matches("login") {
-            self.login(parse_token_else_stdin(set_matches)).await
+            if set_matches.is_present("web") {
+                self.web_login().await
+            } else {
+                self.login(parse_token_else_stdin(set_matches)).await
+            }
         } else if let Some(_matches) = matches.subcommand_matches("logout") {
             self.logout().await
         } else if let Some(set_matches) = matches.subcommand_matches("get") {
@@ -101,6 +108,12 @@ impl Command for Account {
             self.list_devices(set_matches).await
         } else if let Some(set_matches) = matches.subcommand_matches("revoke-device") {
             self.revoke_device(set_matches).await
+        } else if let Some(devices_matches) = matches.subcommand_matches("devices") {
+            match devices_matches.subcommand() {
+                Some(("list", matches)) => self.list_devices(matches).await,
+                Some(("revoke", matches)) => self.revoke_device(matches).await,
+                _ => unreachable!("No devices command given"),
+            }
         } else if let Some(matches) = matches.subcommand_matches("redeem") {
             let voucher = matches.value_of_t_or_exit("voucher");
             self.redeem_voucher(voucher).await
@@ -127,6 +140,40 @@ impl Account {
         Ok(())
     }
 
+    async fn web_login(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let link = rpc
+            .start_web_login(())
+            .await
+            .map_err(map_device_error)?
+            .into_inner();
+        let expiry = link.expiry.unwrap();
+
+        println!(
+            "Open {} in a browser and approve the code {}",
+            link.url, link.code
+        );
+        println!("The code expires at {}", Self::format_expiry(&expiry));
+        println!("Waiting for approval. The login completes even if this command is interrupted.");
+
+        loop {
+            tokio::time::sleep(WEB_LOGIN_POLL_INTERVAL).await;
+            let state = rpc
+                .get_device(())
+                .await
+                .map_err(map_device_error)?
+                .into_inner();
+            if state.state == types::device_state::State::LoggedIn as i32 {
+                let device = state.device.expect("Device must be provided if logged in");
+                println!("Mullvad account \"{}\" set", device.account_token);
+                return Ok(());
+            }
+            if chrono::Utc::now().timestamp() >= expiry.seconds {
+                return Err(Error::Other(WEB_LOGIN_EXPIRED_ERROR));
+            }
+        }
+    }
+
     async fn logout(&self) -> Result<()> {
         let mut rpc = new_rpc_client().await?;
         rpc.logout_account(()).await?;
@@ -323,6 +370,35 @@ impl Account {
     }
 }
 
+fn with_list_devices_args(app: clap::App<'static>) -> clap::App<'static> {
+    app.arg(
+        clap::Arg::new("account")
+            .help("Mullvad account number")
+            .long("account")
+            .takes_value(true),
+    )
+    .arg(
+        clap::Arg::new("verbose")
+            .long("verbose")
+            .short('v')
+            .help("Enables verbose output"),
+    )
+}
+
+fn with_revoke_device_args(app: clap::App<'static>) -> clap::App<'static> {
+    app.arg(
+        clap::Arg::new("account")
+            .help("Mullvad account number")
+            .long("account")
+            .takes_value(true),
+    )
+    .arg(
+        clap::Arg::new("device")
+            .help("Name or ID of the device to revoke")
+            .required(true),
+    )
+}
+
 fn map_device_error(error: Status) -> Error {
     match error.code() {
         Code::ResourceExhausted => Error::Other(TOO_MANY_DEVICES_ERROR),
@@ -0,0 +1,297 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use mullvad_types::access_method::{AccessMethod, ApiAccessMethod, ApiAccessMethodSettings};
+use std::{convert::TryFrom, net::SocketAddr};
+use talpid_types::net::openvpn::{self, SHADOWSOCKS_CIPHERS};
+
+pub struct ApiAccess;
+
+#[mullvad_management_interface::async_trait]
+impl Command for ApiAccess {
+    fn name(&self) -> &'static str {
+        "api-access"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Manage how the daemon reaches the Mullvad API. When the API cannot be reached, \
+                the next enabled method in the list is tried",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(clap::App::new("list").about("List the API access methods"))
+            .subcommand(create_add_subcommand())
+            .subcommand(
+                clap::App::new("remove")
+                    .about("Remove a custom API access method")
+                    .arg(name_arg()),
+            )
+            .subcommand(
+                clap::App::new("enable")
+                    .about("Enable an API access method")
+                    .arg(name_arg()),
+            )
+            .subcommand(
+                clap::App::new("disable")
+                    .about("Disable an API access method")
+                    .arg(name_arg()),
+            )
+            .subcommand(
+                clap::App::new("use")
+                    .about(
+                        "Only reach the API through this access method, and disable all others. \
+                        Use this when the API must always be reached through a proxy",
+                    )
+                    .arg(name_arg()),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("list", _)) => Self::list().await,
+            Some(("add", matches)) => Self::add(matches).await,
+            Some(("remove", matches)) => Self::remove(matches.value_of("name").unwrap()).await,
+            Some(("enable", matches)) => {
+                Self::set_enabled(matches.value_of("name").unwrap(), true).await
+            }
+            Some(("disable", matches)) => {
+                Self::set_enabled(matches.value_of("name").unwrap(), false).await
+            }
+            Some(("use", matches)) => Self::use_only(matches.value_of("name").unwrap()).await,
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+fn name_arg() -> clap::Arg<'static> {
+    clap::Arg::new("name")
+        .help("Name of the access method")
+        .required(true)
+}
+
+fn create_add_subcommand() -> clap::App<'static> {
+    clap::App::new("add")
+        .about("Add a custom API access method")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            clap::App::new("shadowsocks")
+                .about("Reach the API through a Shadowsocks server")
+                .arg(name_arg().index(1))
+                .arg(
+                    clap::Arg::new("remote-ip")
+                        .help("Specifies the IP of the remote Shadowsocks server")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    clap::Arg::new("remote-port")
+                        .help("Specifies the port of the remote Shadowsocks server")
+                        .required(true)
+                        .index(3),
+                )
+                .arg(
+                    clap::Arg::new("password")
+                        .help("Specifies the password on the remote Shadowsocks server")
+                        .required(true)
+                        .index(4),
+                )
+                .arg(
+                    clap::Arg::new("cipher")
+                        .help("Specifies the cipher to use")
+                        .default_value("aes-256-gcm")
+                        .possible_values(SHADOWSOCKS_CIPHERS)
+                        .index(5),
+                ),
+        )
+        .subcommand(
+            create_remote_proxy_subcommand("socks5").about("Reach the API through a SOCKS5 proxy"),
+        )
+        .subcommand(
+            create_remote_proxy_subcommand("http")
+                .about("Reach the API through an HTTP proxy that supports the CONNECT method"),
+        )
+}
+
+fn create_remote_proxy_subcommand(name: &'static str) -> clap::App<'static> {
+    clap::App::new(name)
+        .arg(name_arg().index(1))
+        .arg(
+            clap::Arg::new("remote-ip")
+                .help("Specifies the IP of the remote proxy server")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            clap::Arg::new("remote-port")
+                .help("Specifies the port the remote proxy server is listening on")
+                .required(true)
+                .index(3),
+        )
+        .arg(
+            clap::Arg::new("username")
+                .help("Specifies the username for remote authentication")
+                .long("username")
+                .takes_value(true)
+                .requires("password"),
+        )
+        .arg(
+            clap::Arg::new("password")
+                .help("Specifies the password for remote authentication")
+                .long("password")
+                .takes_value(true)
+                .requires("username"),
+        )
+}
+
+fn parse_remote_proxy_settings(matches: &clap::ArgMatches) -> openvpn::RemoteProxySettings {
+    let remote_ip = matches.value_of_t_or_exit("remote-ip");
+    let remote_port = matches.value_of_t_or_exit("remote-port");
+    let auth = match (matches.value_of("username"), matches.value_of("password")) {
+        (Some(username), Some(password)) => Some(openvpn::ProxyAuth {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }),
+        _ => None,
+    };
+    openvpn::RemoteProxySettings {
+        address: SocketAddr::new(remote_ip, remote_port),
+        auth,
+    }
+}
+
+impl ApiAccess {
+    async fn list() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let access_methods = Self::get_access_methods(&mut rpc).await?;
+        if access_methods.methods.is_empty() {
+            println!("No access methods have been added");
+        }
+        for method in access_methods.methods {
+            println!(
+                "{} ({}): {}",
+                method.name,
+                if method.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                method.access_method
+            );
+        }
+        Ok(())
+    }
+
+    async fn add(matches: &clap::ArgMatches) -> Result<()> {
+        let (name, access_method) = match matches.subcommand() {
+            Some(("shadowsocks", matches)) => {
+                let remote_ip = matches.value_of_t_or_exit("remote-ip");
+                let remote_port = matches.value_of_t_or_exit("remote-port");
+                let settings = openvpn::ShadowsocksProxySettings {
+                    peer: SocketAddr::new(remote_ip, remote_port),
+                    password: matches.value_of_t_or_exit("password"),
+                    cipher: matches.value_of_t_or_exit("cipher"),
+                };
+                (
+                    matches.value_of("name").unwrap(),
+                    AccessMethod::Shadowsocks(settings),
+                )
+            }
+            Some(("socks5", matches)) => (
+                matches.value_of("name").unwrap(),
+                AccessMethod::Socks5(parse_remote_proxy_settings(matches)),
+            ),
+            Some(("http", matches)) => (
+                matches.value_of("name").unwrap(),
+                AccessMethod::Http(parse_remote_proxy_settings(matches)),
+            ),
+            _ => unreachable!("unhandled access method"),
+        };
+
+        let mut rpc = new_rpc_client().await?;
+        let mut access_methods = Self::get_access_methods(&mut rpc).await?;
+        if access_methods.get(name).is_some() {
+            return Err(Error::InvalidCommand(
+                "an access method with that name already exists",
+            ));
+        }
+        access_methods.methods.push(ApiAccessMethod {
+            name: name.to_owned(),
+            enabled: true,
+            access_method,
+        });
+        Self::set_access_methods(&mut rpc, &access_methods).await?;
+        println!("Added access method \"{}\"", name);
+        Ok(())
+    }
+
+    async fn remove(name: &str) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut access_methods = Self::get_access_methods(&mut rpc).await?;
+        match access_methods.get(name) {
+            None => return Err(Error::InvalidCommand("no such access method")),
+            Some(ApiAccessMethod {
+                access_method: AccessMethod::Direct | AccessMethod::Bridges,
+                ..
+            }) => {
+                return Err(Error::InvalidCommand(
+                    "built-in access methods cannot be removed, but they can be disabled",
+                ))
+            }
+            Some(_) => (),
+        }
+        access_methods.methods.retain(|method| method.name != name);
+        Self::set_access_methods(&mut rpc, &access_methods).await?;
+        println!("Removed access method \"{}\"", name);
+        Ok(())
+    }
+
+    async fn set_enabled(name: &str, enabled: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut access_methods = Self::get_access_methods(&mut rpc).await?;
+        access_methods
+            .get_mut(name)
+            .ok_or(Error::InvalidCommand("no such access method"))?
+            .enabled = enabled;
+        Self::set_access_methods(&mut rpc, &access_methods).await?;
+        if enabled {
+            println!("Enabled access method \"{}\"", name);
+        } else {
+            println!("Disabled access method \"{}\"", name);
+        }
+        Ok(())
+    }
+
+    async fn use_only(name: &str) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut access_methods = Self::get_access_methods(&mut rpc).await?;
+        if !access_methods.use_only(name) {
+            return Err(Error::InvalidCommand("no such access method"));
+        }
+        Self::set_access_methods(&mut rpc, &access_methods).await?;
+        println!("The API is now only reached using \"{}\"", name);
+        Ok(())
+    }
+
+    async fn get_access_methods(
+        rpc: &mut ManagementServiceClient,
+    ) -> Result<ApiAccessMethodSettings> {
+        let access_methods = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .api_access_methods
+            .ok_or(Error::Other("the daemon did not report any access methods"))?;
+        ApiAccessMethodSettings::try_from(access_methods)
+            .map_err(|_| Error::Other("the daemon reported invalid access methods"))
+    }
+
+    async fn set_access_methods(
+        rpc: &mut ManagementServiceClient,
+        access_methods: &ApiAccessMethodSettings,
+    ) -> Result<()> {
+        rpc.set_api_access_methods(types::ApiAccessMethods::from(access_methods))
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to set API access methods", error))?;
+        Ok(())
+    }
+}
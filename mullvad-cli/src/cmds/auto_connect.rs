@@ -1,4 +1,5 @@
 use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types::{auto_connect_scope::Scope, AutoConnectScope};
 
 pub struct AutoConnect;
 
@@ -19,6 +20,17 @@ impl Command for AutoConnect {
                         clap::Arg::new("policy")
                             .required(true)
                             .possible_values(&["on", "off"]),
+                    )
+                    .arg(
+                        clap::Arg::new("scope")
+                            .long("scope")
+                            .takes_value(true)
+                            .possible_values(&["user", "machine"])
+                            .help(
+                                "Whether the setting follows the user's app preferences, or is a \
+                                machine-wide policy that connects the tunnel before anyone logs on, \
+                                keeps retrying until it is up, and keeps it up when the app quits",
+                            ),
                     ),
             )
             .subcommand(clap::App::new("get").about("Display the current auto-connect setting"))
@@ -27,7 +39,12 @@ impl Command for AutoConnect {
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
         if let Some(set_matches) = matches.subcommand_matches("set") {
             let auto_connect = set_matches.value_of("policy").expect("missing policy");
-            self.set(auto_connect == "on").await
+            let scope = set_matches.value_of("scope").map(|scope| match scope {
+                "user" => Scope::User,
+                "machine" => Scope::Machine,
+                _ => unreachable!("invalid scope"),
+            });
+            self.set(auto_connect == "on", scope).await
         } else if let Some(_matches) = matches.subcommand_matches("get") {
             self.get().await
         } else {
@@ -37,8 +54,14 @@ impl Command for AutoConnect {
 }
 
 impl AutoConnect {
-    async fn set(&self, auto_connect: bool) -> Result<()> {
+    async fn set(&self, auto_connect: bool, scope: Option<Scope>) -> Result<()> {
         let mut rpc = new_rpc_client().await?;
+        if let Some(scope) = scope {
+            rpc.set_auto_connect_scope(AutoConnectScope {
+                scope: i32::from(scope),
+            })
+            .await?;
+        }
         rpc.set_auto_connect(auto_connect).await?;
         println!("Changed auto-connect setting");
         Ok(())
@@ -46,8 +69,22 @@ impl AutoConnect {
 
     async fn get(&self) -> Result<()> {
         let mut rpc = new_rpc_client().await?;
-        let auto_connect = rpc.get_settings(()).await?.into_inner().auto_connect;
-        println!("Autoconnect: {}", if auto_connect { "on" } else { "off" });
+        let settings = rpc.get_settings(()).await?.into_inner();
+        let scope = settings
+            .auto_connect_scope
+            .and_then(|scope| Scope::from_i32(scope.scope))
+            .unwrap_or(Scope::User);
+        println!(
+            "Autoconnect: {}",
+            if settings.auto_connect { "on" } else { "off" }
+        );
+        println!(
+            "Scope      : {}",
+            match scope {
+                Scope::User => "user",
+                Scope::Machine => "machine",
+            }
+        );
         Ok(())
     }
 }
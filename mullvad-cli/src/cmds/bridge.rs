@@ -72,6 +72,29 @@ fn create_bridge_set_subcommand() -> clap::App<'static> {
                         .required(true),
                 ),
         )
+        .subcommand(
+            clap::App::new("port")
+                .about(
+                    "Set the port of the Shadowsocks server on the bridge. The 'list' \
+                       command shows the ports that each bridge relay accepts.",
+                )
+                .arg(
+                    clap::Arg::new("port")
+                        .help("Port number, or 'any' for no preference.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            clap::App::new("cipher")
+                .about("Set the cipher of the Shadowsocks server on the bridge")
+                .arg(
+                    clap::Arg::new("cipher")
+                        .help("Cipher, or 'any' for no preference.")
+                        .possible_value("any")
+                        .possible_values(SHADOWSOCKS_CIPHERS)
+                        .required(true),
+                ),
+        )
         .subcommand(location::get_subcommand().about(
             "Set country or city to select bridge relays from. Use the 'list' \
              command to show available alternatives.",
@@ -105,8 +128,9 @@ fn create_set_custom_settings_subcommand() -> clap::App<'static> {
     {
         local_subcommand = local_subcommand.about(
             "Registers a local SOCKS5 proxy. The server must be excluded using \
-           'mullvad-exclude', or `SO_MARK` must be set to '0x6d6f6c65', in order \
-           to bypass firewall restrictions",
+           'mullvad-exclude', or `SO_MARK` must be set to the firewall mark shown by \
+           'mullvad policy-routing get' (by default '0x6d6f6c65'), in order to bypass \
+           firewall restrictions",
         );
     }
     #[cfg(target_os = "macos")]
@@ -123,7 +147,10 @@ fn create_set_custom_settings_subcommand() -> clap::App<'static> {
         .subcommand(local_subcommand)
         .subcommand(
             clap::App::new("remote")
-                .about("Registers a remote SOCKS5 proxy")
+                .about(
+                    "Registers a remote SOCKS5 proxy. The username and password are only needed \
+                    if the server requires authentication",
+                )
                 .arg(
                     clap::Arg::new("remote-ip")
                         .help("Specifies the IP of the remote proxy server")
@@ -139,13 +166,12 @@ fn create_set_custom_settings_subcommand() -> clap::App<'static> {
                 .arg(
                     clap::Arg::new("username")
                         .help("Specifies the username for remote authentication")
-                        .required(true)
+                        .requires("password")
                         .index(3),
                 )
                 .arg(
                     clap::Arg::new("password")
                         .help("Specifies the password for remote authentication")
-                        .required(true)
                         .index(4),
                 ),
         )
@@ -202,6 +228,10 @@ impl Bridge {
             Some(("ownership", ownership_matches)) => {
                 Self::handle_set_bridge_ownership(ownership_matches).await
             }
+            Some(("port", port_matches)) => Self::handle_set_bridge_port(port_matches).await,
+            Some(("cipher", cipher_matches)) => {
+                Self::handle_set_bridge_cipher(cipher_matches).await
+            }
             Some(("custom", custom_matches)) => {
                 Self::handle_bridge_set_custom_settings(custom_matches).await
             }
@@ -236,11 +266,10 @@ impl Bridge {
     }
 
     async fn handle_set_bridge_location(matches: &clap::ArgMatches) -> Result<()> {
-        Self::update_bridge_settings(
-            Some(location::get_constraint_from_args(matches)),
-            None,
-            None,
-        )
+        let location = location::get_constraint_from_args(matches);
+        Self::update_bridge_settings(|constraints| {
+            constraints.location = Constraint::<LocationConstraint>::from(location);
+        })
         .await
     }
 
@@ -252,54 +281,50 @@ impl Bridge {
             providers
         };
 
-        Self::update_bridge_settings(None, Some(providers), None).await
+        let providers = types::try_providers_constraint_from_proto(&providers).unwrap();
+        Self::update_bridge_settings(|constraints| constraints.providers = providers).await
     }
 
     async fn handle_set_bridge_ownership(matches: &clap::ArgMatches) -> Result<()> {
-        let ownership =
-            super::relay::parse_ownership_constraint(matches.value_of("ownership").unwrap());
-        Self::update_bridge_settings(None, None, Some(ownership)).await
+        let ownership = types::ownership_constraint_from_proto(
+            super::relay::parse_ownership_constraint(matches.value_of("ownership").unwrap()),
+        );
+        Self::update_bridge_settings(|constraints| constraints.ownership = ownership).await
+    }
+
+    async fn handle_set_bridge_port(matches: &clap::ArgMatches) -> Result<()> {
+        let port = match matches.value_of("port").unwrap() {
+            "any" => Constraint::Any,
+            port => Constraint::Only(
+                port.parse()
+                    .ok()
+                    .filter(|port| *port != 0)
+                    .ok_or(Error::InvalidCommand("Invalid port"))?,
+            ),
+        };
+        Self::update_bridge_settings(|constraints| constraints.port = port).await
     }
 
-    async fn update_bridge_settings(
-        location: Option<types::RelayLocation>,
-        providers: Option<Vec<String>>,
-        ownership: Option<types::Ownership>,
-    ) -> Result<()> {
+    async fn handle_set_bridge_cipher(matches: &clap::ArgMatches) -> Result<()> {
+        let cipher = match matches.value_of("cipher").unwrap() {
+            "any" => Constraint::Any,
+            cipher => Constraint::Only(cipher.to_owned()),
+        };
+        Self::update_bridge_settings(|constraints| constraints.cipher = cipher).await
+    }
+
+    /// Changes the bridge constraints. Custom bridge settings are replaced by the default
+    /// constraints before `update` is applied.
+    async fn update_bridge_settings(update: impl FnOnce(&mut BridgeConstraints)) -> Result<()> {
         let mut rpc = new_rpc_client().await?;
         let settings = rpc.get_settings(()).await?.into_inner();
 
         let bridge_settings = BridgeSettings::try_from(settings.bridge_settings.unwrap()).unwrap();
-        let constraints = match bridge_settings {
-            BridgeSettings::Normal(mut constraints) => {
-                if let Some(new_location) = location {
-                    constraints.location = Constraint::<LocationConstraint>::from(new_location);
-                }
-                if let Some(new_providers) = providers {
-                    constraints.providers =
-                        types::try_providers_constraint_from_proto(&new_providers).unwrap();
-                }
-                if let Some(new_ownership) = ownership {
-                    constraints.ownership = types::ownership_constraint_from_proto(new_ownership);
-                }
-                constraints
-            }
-            _ => {
-                let location = Constraint::<LocationConstraint>::from(location.unwrap_or_default());
-                let providers =
-                    types::try_providers_constraint_from_proto(&providers.unwrap_or_default())
-                        .unwrap();
-                let ownership = ownership
-                    .map(types::ownership_constraint_from_proto)
-                    .unwrap_or_default();
-
-                BridgeConstraints {
-                    location,
-                    providers,
-                    ownership,
-                }
-            }
+        let mut constraints = match bridge_settings {
+            BridgeSettings::Normal(constraints) => constraints,
+            _ => BridgeConstraints::default(),
         };
+        update(&mut constraints);
 
         rpc.set_bridge_settings(
             types::BridgeSettings::try_from(BridgeSettings::Normal(constraints)).unwrap(),
@@ -332,15 +357,7 @@ impl Bridge {
                 peer: SocketAddr::new(remote_ip, remote_port),
             };
             let packed_proxy = openvpn::ProxySettings::Local(local_proxy);
-            if let Err(error) = openvpn::validate_proxy_settings(&packed_proxy) {
-                panic!("{}", error);
-            }
-
-            let mut rpc = new_rpc_client().await?;
-            rpc.set_bridge_settings(types::BridgeSettings::from(BridgeSettings::Custom(
-                packed_proxy,
-            )))
-            .await?;
+            Self::set_custom_bridge(packed_proxy).await?;
         } else if let Some(args) = matches.subcommand_matches("remote") {
             let remote_ip = args.value_of_t_or_exit("remote-ip");
             let remote_port = args.value_of_t_or_exit("remote-port");
@@ -359,15 +376,7 @@ impl Bridge {
                 auth,
             };
             let packed_proxy = openvpn::ProxySettings::Remote(proxy);
-            if let Err(error) = openvpn::validate_proxy_settings(&packed_proxy) {
-                panic!("{}", error);
-            }
-
-            let mut rpc = new_rpc_client().await?;
-            rpc.set_bridge_settings(types::BridgeSettings::from(BridgeSettings::Custom(
-                packed_proxy,
-            )))
-            .await?;
+            Self::set_custom_bridge(packed_proxy).await?;
         } else if let Some(args) = matches.subcommand_matches("shadowsocks") {
             let remote_ip = args.value_of_t_or_exit("remote-ip");
             let remote_port = args.value_of_t_or_exit("remote-port");
@@ -380,15 +389,7 @@ impl Bridge {
                 cipher,
             };
             let packed_proxy = openvpn::ProxySettings::Shadowsocks(proxy);
-            if let Err(error) = openvpn::validate_proxy_settings(&packed_proxy) {
-                panic!("{}", error);
-            }
-
-            let mut rpc = new_rpc_client().await?;
-            rpc.set_bridge_settings(types::BridgeSettings::from(BridgeSettings::Custom(
-                packed_proxy,
-            )))
-            .await?;
+            Self::set_custom_bridge(packed_proxy).await?;
         } else {
             unreachable!("unhandled proxy type");
         }
@@ -397,6 +398,14 @@ impl Bridge {
         Ok(())
     }
 
+    async fn set_custom_bridge(proxy: openvpn::ProxySettings) -> Result<()> {
+        openvpn::validate_proxy_settings(&proxy).map_err(Error::InvalidProxySettings)?;
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_bridge_settings(types::BridgeSettings::from(BridgeSettings::Custom(proxy)))
+            .await?;
+        Ok(())
+    }
+
     fn print_local_proxy(proxy: &openvpn::LocalProxySettings) {
         println!("proxy: local");
         println!("  local port: {}", proxy.port);
@@ -473,8 +482,22 @@ impl Bridge {
                     } else {
                         "rented"
                     };
+                    let endpoints = relay
+                        .bridges
+                        .as_ref()
+                        .unwrap()
+                        .shadowsocks
+                        .iter()
+                        .map(|endpoint| {
+                            let protocol = types::TransportProtocol::from_i32(endpoint.protocol)
+                                .map(|protocol| format!("{:?}", protocol).to_lowercase())
+                                .unwrap_or_default();
+                            format!("{}/{} ({})", endpoint.port, protocol, endpoint.cipher)
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
                     println!(
-                        "\t\t{} ({}) - hosted by {} ({ownership})",
+                        "\t\t{} ({}) - hosted by {} ({ownership}), Shadowsocks on {endpoints}",
                         relay.hostname, relay.ipv4_addr_in, relay.provider
                     );
                 }
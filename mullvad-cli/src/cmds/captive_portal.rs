@@ -0,0 +1,134 @@
+use super::disconnect::parse_duration;
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::{net::IpAddr, time::Duration};
+
+/// How long traffic to a captive portal is permitted unless `--for` is given.
+const DEFAULT_DURATION: Duration = Duration::from_secs(5 * 60);
+
+pub struct CaptivePortal;
+
+#[mullvad_management_interface::async_trait]
+impl Command for CaptivePortal {
+    fn name(&self) -> &'static str {
+        "captive-portal"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Sign in to a network that requires it, such as hotel Wi-Fi")
+            .long_about(
+                "Sign in to a network that requires it, such as hotel Wi-Fi. Permits traffic \
+                outside the tunnel to the sign-in page for a while, without disabling lockdown \
+                mode. DNS remains blocked, so the page must be reached by its IP address.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("open")
+                    .about("Permit traffic to the captive portal")
+                    .arg(
+                        clap::Arg::new("address")
+                            .help("IP address of the portal. All traffic is permitted if omitted"),
+                    )
+                    .arg(
+                        clap::Arg::new("for")
+                            .long("for")
+                            .takes_value(true)
+                            .value_name("DURATION")
+                            .help("How long to permit traffic for, e.g. 2m. At most 10 minutes"),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("close").about("Stop permitting traffic to the captive portal"),
+            )
+            .subcommand(
+                clap::App::new("status")
+                    .about("Display whether traffic to a captive portal is permitted"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("open", matches)) => {
+                let address = match matches.value_of("address") {
+                    Some(address) => address
+                        .parse::<IpAddr>()
+                        .map_err(|_| Error::InvalidCommand("invalid IP address"))?
+                        .to_string(),
+                    None => String::new(),
+                };
+                let duration = match matches.value_of("for") {
+                    Some(duration) => parse_duration(duration)
+                        .ok_or(Error::InvalidCommand("invalid duration, expected e.g. 2m"))?,
+                    None => DEFAULT_DURATION,
+                };
+                Self::open(address, duration).await
+            }
+            Some(("close", _)) => Self::close().await,
+            Some(("status", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                if !Self::print_status(&mut rpc).await? {
+                    println!("No traffic is permitted for a captive portal");
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled subcommand"),
+        }
+    }
+}
+
+impl CaptivePortal {
+    async fn open(address: String, duration: Duration) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_captive_portal(types::CaptivePortalAccess {
+            portal: Some(types::CaptivePortal { address }),
+            duration: Some(types::Duration {
+                seconds: duration.as_secs() as i64,
+                nanos: 0,
+            }),
+        })
+        .await
+        .map_err(|error| Error::RpcFailedExt("Failed to permit the captive portal", error))?;
+        Self::print_status(&mut rpc).await?;
+        println!("Run 'mullvad captive-portal close' once you have signed in");
+        Ok(())
+    }
+
+    async fn close() -> Result<()> {
+        let was_active = new_rpc_client()
+            .await?
+            .end_captive_portal(())
+            .await?
+            .into_inner();
+        if was_active {
+            println!("Stopped permitting traffic to the captive portal");
+        } else {
+            println!("No traffic was permitted for a captive portal");
+        }
+        Ok(())
+    }
+
+    /// Prints what is permitted for a captive portal, and until when. Returns whether anything
+    /// is permitted.
+    async fn print_status(rpc: &mut ManagementServiceClient) -> Result<bool> {
+        let mode = rpc.get_captive_portal(()).await?.into_inner();
+        match (mode.portal, mode.ends_at) {
+            (Some(portal), Some(ends_at)) => {
+                let ends_at = chrono::NaiveDateTime::from_timestamp(ends_at.seconds, 0);
+                let ends_at = chrono::DateTime::<chrono::Utc>::from_utc(ends_at, chrono::Utc);
+                let permitted = if portal.address.is_empty() {
+                    "All traffic".to_owned()
+                } else {
+                    format!("Traffic to {}", portal.address)
+                };
+                println!(
+                    "{} is permitted outside the tunnel until {}",
+                    permitted,
+                    ends_at.with_timezone(&chrono::Local).format("%X")
+                );
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
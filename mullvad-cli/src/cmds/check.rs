@@ -0,0 +1,33 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_types::leak_check::LeakReport;
+use std::convert::TryFrom;
+
+pub struct Check;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Check {
+    fn name(&self) -> &'static str {
+        "check"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name()).about(
+            "Check that traffic exits from a Mullvad relay and that DNS queries, IPv6 traffic \
+             and local addresses do not leak outside of the tunnel",
+        )
+    }
+
+    async fn run(&self, _: &clap::ArgMatches) -> Result<()> {
+        println!("Checking for leaks. This may take a few seconds.\n");
+        let report = new_rpc_client().await?.check_leaks(()).await?.into_inner();
+        let report = LeakReport::try_from(report)
+            .map_err(|_| Error::Other("the daemon sent an invalid report"))?;
+        print!("{}", report);
+        if report.passed() {
+            println!("\nAll checks passed");
+        } else {
+            println!("\nNot all checks passed");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types;
+
+pub struct Circumvention;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Circumvention {
+    fn name(&self) -> &'static str {
+        "circumvention"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Control the assistant that changes circumvention settings when connecting keeps \
+                failing because of network interference",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Change whether settings are changed automatically")
+                    .arg(
+                        clap::Arg::new("policy")
+                            .required(true)
+                            .possible_values(&["on", "off"]),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("get")
+                    .about("Display the current setting and the changes that have been made"),
+            )
+            .subcommand(
+                clap::App::new("revert")
+                    .about("Restore the settings that were changed by the assistant"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("set", matches)) => {
+                let enable_str = matches.value_of("policy").expect("missing policy");
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_circumvention_assistant(enable_str == "on").await?;
+                println!("Circumvention assistant: {}", enable_str);
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = rpc.get_settings(()).await?.into_inner();
+                let enabled_str = if settings.circumvention_assistant {
+                    "on"
+                } else {
+                    "off"
+                };
+                println!("Circumvention assistant: {}", enabled_str);
+
+                let changes = rpc
+                    .get_circumvention_changes(())
+                    .await?
+                    .into_inner()
+                    .changes;
+                if changes.is_empty() {
+                    println!("No settings have been changed");
+                } else {
+                    println!("Changes:");
+                    for change in &changes {
+                        print_change(change);
+                    }
+                    println!("Run `mullvad circumvention revert` to undo them");
+                }
+                Ok(())
+            }
+            Some(("revert", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let changes = rpc
+                    .revert_circumvention_changes(())
+                    .await?
+                    .into_inner()
+                    .changes;
+                if changes.is_empty() {
+                    println!("No settings have been changed");
+                } else {
+                    println!("Reverted:");
+                    for change in &changes {
+                        print_change(change);
+                    }
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+fn print_change(change: &types::CircumventionChange) {
+    let applied_at = change
+        .applied_at
+        .as_ref()
+        .map(|applied_at| {
+            let applied_at = chrono::NaiveDateTime::from_timestamp(applied_at.seconds, 0);
+            chrono::DateTime::<chrono::Utc>::from_utc(applied_at, chrono::Utc)
+                .with_timezone(&chrono::Local)
+                .format("%c")
+                .to_string()
+        })
+        .unwrap_or_default();
+    println!(
+        "  {}: {} ({})",
+        applied_at, change.description, change.reason
+    );
+}
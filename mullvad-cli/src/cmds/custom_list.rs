@@ -0,0 +1,167 @@
+use super::relay::parse_location_set_item;
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use mullvad_types::{custom_list::CustomListsSettings, relay_constraints::Constraint};
+use std::convert::TryFrom;
+
+pub struct CustomList;
+
+#[mullvad_management_interface::async_trait]
+impl Command for CustomList {
+    fn name(&self) -> &'static str {
+        "custom-list"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Manage named lists of locations. Select relays from a list with \
+                'mullvad relay set location --custom-list <name>'",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(clap::App::new("list").about("List the custom lists and their locations"))
+            .subcommand(
+                clap::App::new("new")
+                    .about("Create a custom list")
+                    .arg(name_arg())
+                    .arg(locations_arg().required(false)),
+            )
+            .subcommand(
+                clap::App::new("add")
+                    .about("Add locations to a custom list")
+                    .arg(name_arg())
+                    .arg(locations_arg()),
+            )
+            .subcommand(
+                clap::App::new("remove")
+                    .about("Remove locations from a custom list")
+                    .arg(name_arg())
+                    .arg(locations_arg()),
+            )
+            .subcommand(
+                clap::App::new("delete")
+                    .about(
+                        "Delete a custom list. Lists used by the relay settings cannot be deleted",
+                    )
+                    .arg(name_arg()),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("list", _)) => Self::list().await,
+            Some(("new", matches)) => Self::create(matches).await,
+            Some(("add", matches)) => Self::edit(matches, true).await,
+            Some(("remove", matches)) => Self::edit(matches, false).await,
+            Some(("delete", matches)) => Self::delete(matches.value_of("name").unwrap()).await,
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+fn name_arg() -> clap::Arg<'static> {
+    clap::Arg::new("name")
+        .help("Name of the custom list")
+        .required(true)
+        .index(1)
+}
+
+fn locations_arg() -> clap::Arg<'static> {
+    clap::Arg::new("locations")
+        .help("Locations given as <country>[,<city>[,<hostname>]], such as 'se,sto'")
+        .required(true)
+        .multiple_values(true)
+        .index(2)
+}
+
+fn parse_locations(matches: &clap::ArgMatches) -> Result<Vec<types::RelayLocation>> {
+    matches
+        .values_of("locations")
+        .map(|values| values.map(parse_location_set_item).collect())
+        .unwrap_or_else(|| Ok(vec![]))
+}
+
+impl CustomList {
+    async fn list() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let custom_lists = Self::get_custom_lists(&mut rpc).await?;
+        if custom_lists.custom_lists.is_empty() {
+            println!("No custom lists have been created");
+        }
+        for list in &custom_lists.custom_lists {
+            println!("{}", list.name);
+            if list.locations.is_empty() {
+                println!("\t(empty)");
+            }
+            for location in &list.locations {
+                println!("\t{}", location);
+            }
+        }
+        Ok(())
+    }
+
+    async fn create(matches: &clap::ArgMatches) -> Result<()> {
+        let name = matches.value_of("name").unwrap();
+        let list = types::CustomList {
+            name: name.to_owned(),
+            locations: parse_locations(matches)?,
+        };
+        let mut rpc = new_rpc_client().await?;
+        rpc.create_custom_list(list)
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to create custom list", error))?;
+        println!("Created custom list \"{}\"", name);
+        Ok(())
+    }
+
+    async fn edit(matches: &clap::ArgMatches, add: bool) -> Result<()> {
+        let name = matches.value_of("name").unwrap();
+        let locations: Vec<_> = parse_locations(matches)?
+            .into_iter()
+            .filter_map(|location| Constraint::from(location).option())
+            .collect();
+
+        let mut rpc = new_rpc_client().await?;
+        let custom_lists = Self::get_custom_lists(&mut rpc).await?;
+        let mut list = custom_lists
+            .get(name)
+            .cloned()
+            .ok_or(Error::InvalidCommand("no such custom list"))?;
+        if add {
+            for location in locations {
+                if !list.locations.contains(&location) {
+                    list.locations.push(location);
+                }
+            }
+        } else {
+            list.locations
+                .retain(|location| !locations.contains(location));
+        }
+
+        rpc.update_custom_list(types::CustomList::from(list))
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to update custom list", error))?;
+        println!("Updated custom list \"{}\"", name);
+        Ok(())
+    }
+
+    async fn delete(name: &str) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.delete_custom_list(name.to_owned())
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to delete custom list", error))?;
+        println!("Deleted custom list \"{}\"", name);
+        Ok(())
+    }
+
+    async fn get_custom_lists(rpc: &mut ManagementServiceClient) -> Result<CustomListsSettings> {
+        let custom_lists = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .custom_lists
+            .unwrap_or_default();
+        CustomListsSettings::try_from(custom_lists)
+            .map_err(|_| Error::Other("the daemon reported invalid custom lists"))
+    }
+}
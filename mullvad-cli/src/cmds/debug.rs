@@ -0,0 +1,195 @@
+use super::disconnect::{format_duration, parse_duration};
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::types;
+use mullvad_types::{failure_capsule::FailureCapsule, interference::InterferenceReport};
+use std::{convert::TryFrom, fs, path::PathBuf, time::Duration};
+
+pub struct Debug;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Debug {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Inspect what the daemon is doing, for troubleshooting")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(clap::App::new("api-stats").about(
+                "Display how many requests have been made to each API endpoint since the \
+                 daemon started, how many of them failed and which access methods were used",
+            ))
+            .subcommand(clap::App::new("network-report").about(
+                "Check the network for signs that it blocks or tampers with VPN and API \
+                 traffic, and suggest settings that may get around it. The report can be \
+                 shared with support",
+            ))
+            .subcommand(clap::App::new("last-failure").about(
+                "Display the log lines and parameters of the most recent connection attempt that \
+                 failed. Sensitive information is redacted, so it can be attached to bug reports",
+            ))
+            .subcommand(
+                clap::App::new("snapshot")
+                    .about(
+                        "Save the settings, relay list and target state of the daemon to a file. \
+                         Credentials are removed. Developers can start a daemon from the file \
+                         with --load-snapshot to reproduce a problem",
+                    )
+                    .arg(
+                        clap::Arg::new("file")
+                            .required(true)
+                            .allow_invalid_utf8(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("permissive")
+                    .about(
+                        "Recovery mode for when the tunnel stack is broken. Disconnects and stops \
+                         enforcing the firewall, so that traffic flows as if the app was not \
+                         installed, e.g. to download a fix. The previous state is restored when \
+                         the duration has elapsed. Shows whether it is active if no option is \
+                         given",
+                    )
+                    .arg(
+                        clap::Arg::new("for")
+                            .long("for")
+                            .takes_value(true)
+                            .value_name("DURATION")
+                            .help(
+                                "Enter permissive mode for this long, e.g. 10m. At most 24 hours",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("off")
+                            .long("off")
+                            .conflicts_with("for")
+                            .help("End permissive mode now"),
+                    ),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("api-stats", _)) => Self::api_stats().await,
+            Some(("network-report", _)) => Self::network_report().await,
+            Some(("last-failure", _)) => Self::last_failure().await,
+            Some(("snapshot", matches)) => {
+                Self::snapshot(PathBuf::from(matches.value_of_os("file").unwrap())).await
+            }
+            Some(("permissive", matches)) => {
+                if let Some(duration) = matches.value_of("for") {
+                    let duration = parse_duration(duration)
+                        .ok_or(Error::InvalidCommand("invalid duration, expected e.g. 10m"))?;
+                    Self::enter_permissive_mode(duration).await
+                } else if matches.is_present("off") {
+                    Self::end_permissive_mode().await
+                } else {
+                    Self::print_permissive_mode().await
+                }
+            }
+            _ => unreachable!("unhandled subcommand"),
+        }
+    }
+}
+
+impl Debug {
+    async fn api_stats() -> Result<()> {
+        let stats = new_rpc_client()
+            .await?
+            .get_api_stats(())
+            .await?
+            .into_inner();
+        if stats.endpoints.is_empty() {
+            println!("No API requests have been made");
+            return Ok(());
+        }
+        for endpoint in stats.endpoints {
+            println!("{}", endpoint.endpoint);
+            println!("    Requests        : {}", endpoint.requests);
+            println!("    No response     : {}", endpoint.failures);
+            println!("    Error responses : {}", endpoint.error_responses);
+            println!("    Rate limited    : {}", endpoint.rate_limited);
+            for method in endpoint.access_methods {
+                println!("    Via {}: {}", method.access_method, method.requests);
+            }
+        }
+        Ok(())
+    }
+
+    async fn network_report() -> Result<()> {
+        println!("Checking the network. This may take a few seconds.\n");
+        let report = new_rpc_client()
+            .await?
+            .check_network_interference(())
+            .await?
+            .into_inner();
+        let report = InterferenceReport::try_from(report)
+            .map_err(|_| Error::Other("the daemon sent an invalid report"))?;
+        print!("{}", report);
+        Ok(())
+    }
+
+    async fn last_failure() -> Result<()> {
+        let failure = new_rpc_client()
+            .await?
+            .get_last_connection_failure(())
+            .await?
+            .into_inner();
+        match failure.capsule {
+            Some(capsule) => {
+                let capsule = FailureCapsule::try_from(capsule)
+                    .map_err(|_| Error::Other("the daemon sent an invalid capsule"))?;
+                print!("{}", capsule);
+            }
+            None => println!("No connection attempt has failed since the daemon started"),
+        }
+        Ok(())
+    }
+
+    async fn snapshot(path: PathBuf) -> Result<()> {
+        let snapshot = new_rpc_client().await?.get_snapshot(()).await?.into_inner();
+        fs::write(&path, snapshot).map_err(Error::SnapshotIo)?;
+        println!("Wrote snapshot to {}", path.display());
+        Ok(())
+    }
+
+    async fn enter_permissive_mode(duration: Duration) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .set_permissive_mode(types::Duration {
+                seconds: duration.as_secs() as i64,
+                nanos: 0,
+            })
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to enter permissive mode", error))?;
+        println!(
+            "PERMISSIVE MODE: the firewall is not enforced and traffic does not go through the \
+             tunnel for the next {}. Run 'mullvad debug permissive --off' to end it early",
+            format_duration(duration)
+        );
+        Ok(())
+    }
+
+    async fn end_permissive_mode() -> Result<()> {
+        let was_active = new_rpc_client()
+            .await?
+            .end_permissive_mode(())
+            .await?
+            .into_inner();
+        if was_active {
+            println!("Ended permissive mode. The firewall is enforced again");
+        } else {
+            println!("Permissive mode is not active");
+        }
+        Ok(())
+    }
+
+    async fn print_permissive_mode() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        if !super::status::print_permissive_mode(&mut rpc).await? {
+            println!("Permissive mode is not active");
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{format, new_rpc_client, state, Command, Error, Result};
 use futures::StreamExt;
-use mullvad_management_interface::types::tunnel_state::State::Disconnected;
+use mullvad_management_interface::{
+    types::{self, tunnel_state::State::Disconnected},
+    Code, ManagementServiceClient, Response, Status,
+};
+use std::time::Duration;
 
 pub struct Disconnect;
 
@@ -19,6 +23,23 @@ impl Command for Disconnect {
                     .short('w')
                     .help("Wait until disconnected before exiting"),
             )
+            .arg(
+                clap::Arg::new("for")
+                    .long("for")
+                    .takes_value(true)
+                    .value_name("DURATION")
+                    .help(
+                        "Disconnect temporarily, e.g. for 90s, 10m or 1h. Blocking while \
+                        disconnected is suspended for this long, after which the tunnel is \
+                        connected again. At most 24 hours",
+                    ),
+            )
+            .arg(
+                clap::Arg::new("force")
+                    .long("force")
+                    .short('f')
+                    .help("Disconnect even if the tunnel is busy transferring data"),
+            )
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -30,7 +51,25 @@ impl Command for Disconnect {
             None
         };
 
-        if rpc.disconnect_tunnel(()).await?.into_inner() {
+        let disconnect_issued = match matches.value_of("for") {
+            Some(duration) => {
+                let duration = parse_duration(duration)
+                    .ok_or(Error::InvalidCommand("invalid duration, expected e.g. 10m"))?;
+                rpc.pause_tunnel(types::Duration {
+                    seconds: duration.as_secs() as i64,
+                    nanos: 0,
+                })
+                .await
+                .map_err(|error| Error::RpcFailedExt("Failed to pause the tunnel", error))?
+                .into_inner()
+            }
+            None => {
+                let response = rpc.disconnect_tunnel(()).await;
+                confirm_if_forced(&mut rpc, response, matches.is_present("force")).await?
+            }
+        };
+
+        if disconnect_issued {
             if let Some(mut receiver) = receiver_option {
                 while let Some(state) = receiver.next().await {
                     let state = state?;
@@ -46,3 +85,50 @@ impl Command for Disconnect {
         Ok(())
     }
 }
+
+/// Returns whether a disconnect or reconnect was issued. If the daemon held it back because the
+/// tunnel is busy, it is confirmed if `force` is set.
+pub async fn confirm_if_forced(
+    rpc: &mut ManagementServiceClient,
+    response: std::result::Result<Response<bool>, Status>,
+    force: bool,
+) -> Result<bool> {
+    match response {
+        Ok(response) => Ok(response.into_inner()),
+        Err(status) if status.code() == Code::FailedPrecondition => {
+            if force {
+                return Ok(rpc.confirm_disconnect(()).await?.into_inner());
+            }
+            eprintln!("{}", status.message());
+            Err(Error::Other(
+                "Not interrupting the tunnel. Use --force to do it anyway",
+            ))
+        }
+        Err(status) => Err(Error::RpcFailed(status)),
+    }
+}
+
+const DURATION_UNITS: &[(&str, u64)] = &[("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)];
+
+/// Parses a duration given as a number followed by `s`, `m`, `h` or `d`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let unit_index = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_index);
+    let amount: u64 = amount.parse().ok()?;
+    let (_, multiplier) = DURATION_UNITS.iter().find(|(name, _)| *name == unit)?;
+    match amount.checked_mul(*multiplier)? {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    }
+}
+
+/// Formats a duration in the largest unit accepted by [`parse_duration`] that represents it
+/// exactly.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    DURATION_UNITS
+        .iter()
+        .find(|(_, multiplier)| seconds % multiplier == 0)
+        .map(|(name, multiplier)| format!("{}{}", seconds / multiplier, name))
+        .unwrap_or_else(|| format!("{}s", seconds))
+}
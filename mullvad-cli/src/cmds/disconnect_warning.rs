@@ -0,0 +1,105 @@
+use crate::{new_rpc_client, Command, Error, Result};
+
+pub struct DisconnectWarning;
+
+#[mullvad_management_interface::async_trait]
+impl Command for DisconnectWarning {
+    fn name(&self) -> &'static str {
+        "disconnect-warning"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Warn before interrupting the tunnel while it is busy transferring data")
+            .long_about(
+                "Warn before interrupting the tunnel while it is busy transferring data. While \
+                more than the threshold passes through the tunnel, disconnecting and reconnecting \
+                is held back until it is confirmed, e.g. with `mullvad disconnect --force`.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Set the throughput above which to warn")
+                    .arg(
+                        clap::Arg::new("threshold")
+                            .help(
+                                "Bytes per second in both directions, e.g. 500k or 2M. Use \"off\" \
+                                to never warn",
+                            )
+                            .required(true),
+                    ),
+            )
+            .subcommand(clap::App::new("get").about("Display the current threshold"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("set", matches)) => {
+                let value = matches.value_of("threshold").unwrap();
+                let threshold = if value == "off" {
+                    0
+                } else {
+                    parse_rate(value).ok_or(Error::InvalidCommand(
+                        "invalid threshold, expected e.g. 500k or 2M",
+                    ))?
+                };
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_disconnect_warning_threshold(threshold).await?;
+                println!("Changed disconnect warning threshold");
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let threshold = rpc
+                    .get_settings(())
+                    .await?
+                    .into_inner()
+                    .disconnect_warning_threshold;
+                if threshold == 0 {
+                    println!("Disconnect warning: off");
+                } else {
+                    println!("Disconnect warning: above {}", format_rate(threshold));
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+const RATE_UNITS: &[(&str, u64)] = &[("G", 1_000_000_000), ("M", 1_000_000), ("k", 1_000)];
+
+/// Parses a positive number of bytes or bits per second, optionally followed by `k`, `M` or `G`.
+pub fn parse_rate(value: &str) -> Option<u64> {
+    let (amount, multiplier) = match RATE_UNITS.iter().find(|(unit, _)| value.ends_with(unit)) {
+        Some((unit, multiplier)) => (&value[..value.len() - unit.len()], *multiplier),
+        None => (value, 1),
+    };
+    match amount.parse::<u64>().ok()?.checked_mul(multiplier)? {
+        0 => None,
+        bytes => Some(bytes),
+    }
+}
+
+/// Formats a number of bytes per second in the largest unit that is not larger than it.
+pub fn format_rate(bytes: u64) -> String {
+    format!("{}/s", format_bytes(bytes))
+}
+
+/// Formats a number of bits per second in the largest unit that is not larger than it.
+pub fn format_bitrate(bits: u64) -> String {
+    RATE_UNITS
+        .iter()
+        .find(|(_, multiplier)| bits >= *multiplier)
+        .map(|(unit, multiplier)| format!("{:.1} {}bit/s", bits as f64 / *multiplier as f64, unit))
+        .unwrap_or_else(|| format!("{} bit/s", bits))
+}
+
+/// Formats a number of bytes in the largest unit that is not larger than it.
+pub fn format_bytes(bytes: u64) -> String {
+    RATE_UNITS
+        .iter()
+        .find(|(_, multiplier)| bytes >= *multiplier)
+        .map(|(unit, multiplier)| format!("{:.1} {}B", bytes as f64 / *multiplier as f64, unit))
+        .unwrap_or_else(|| format!("{} B", bytes))
+}
@@ -1,7 +1,11 @@
-use crate::{new_rpc_client, Command, Result};
-use mullvad_management_interface::types;
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
 use mullvad_types::settings::{DnsOptions, DnsState};
 use std::{convert::TryInto, net::IpAddr};
+use talpid_types::net::dns::{is_valid_domain, DnsFilterAction, DnsFilterRule, SplitDnsRule};
+
+/// How often `mullvad dns log tail --follow` asks the daemon for new queries.
+const QUERY_LOG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 pub struct Dns;
 
@@ -12,7 +16,7 @@ impl Command for Dns {
     }
 
     fn clap_subcommand(&self) -> clap::App<'static> {
-        clap::App::new(self.name())
+        let app = clap::App::new(self.name())
             .about("Configure DNS servers to use when connected")
             .setting(clap::AppSettings::SubcommandRequiredElseHelp)
             .subcommand(clap::App::new("get").about("Display the current DNS settings"))
@@ -60,11 +64,205 @@ impl Command for Dns {
                             .arg(
                                 clap::Arg::new("servers")
                                     .multiple_occurrences(true)
-                                    .help("One or more IP addresses pointing to DNS resolvers.")
+                                    .help(
+                                        "One or more IP addresses pointing to DNS resolvers, in \
+                                        order of preference. Resolvers that do not respond are \
+                                        tried last while connected.",
+                                    )
+                                    .required(true),
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("split")
+                    .about("Send queries for some domains to other DNS servers")
+                    .long_about(
+                        "Send queries for some domains to other DNS servers, such as those of a \
+                        corporate network. Servers on the local network are reached outside the \
+                        tunnel, and all other servers through it. The queries are forwarded by a \
+                        local resolver while connected.",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(clap::App::new("list").about("List the split DNS rules"))
+                    .subcommand(
+                        clap::App::new("add")
+                            .about("Add or replace the rule for a domain")
+                            .arg(
+                                clap::Arg::new("domain")
+                                    .help(
+                                        "Domain, such as corp.example. A leading '*.' only \
+                                        matches subdomains",
+                                    )
+                                    .required(true),
+                            )
+                            .arg(
+                                clap::Arg::new("servers")
+                                    .multiple_occurrences(true)
+                                    .help("One or more IP addresses of DNS resolvers")
                                     .required(true),
                             ),
+                    )
+                    .subcommand(
+                        clap::App::new("remove")
+                            .about("Remove the rule for a domain")
+                            .arg(clap::Arg::new("domain").required(true)),
                     ),
             )
+            .subcommand(
+                clap::App::new("filter")
+                    .about("Block or rewrite queries for some domains")
+                    .long_about(
+                        "Block or rewrite queries for some domains. The queries are answered by a \
+                        local resolver while connected, and are never sent to a DNS server.",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(clap::App::new("list").about("List the DNS filter rules"))
+                    .subcommand(
+                        clap::App::new("block")
+                            .about("Respond that a domain does not exist")
+                            .arg(
+                                clap::Arg::new("domain")
+                                    .help(
+                                        "Domain, such as ads.example. A leading '*.' only \
+                                        matches subdomains",
+                                    )
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("rewrite")
+                            .about("Respond with other addresses for a domain")
+                            .arg(
+                                clap::Arg::new("domain")
+                                    .help(
+                                        "Domain, such as router.home. A leading '*.' only \
+                                        matches subdomains",
+                                    )
+                                    .required(true),
+                            )
+                            .arg(
+                                clap::Arg::new("addresses")
+                                    .multiple_occurrences(true)
+                                    .help("One or more IP addresses to respond with")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("remove")
+                            .about("Remove the rule for a domain")
+                            .arg(clap::Arg::new("domain").required(true)),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("log")
+                    .about("Record DNS queries while connected")
+                    .long_about(
+                        "Record DNS queries while connected, with how each of them was answered. \
+                        This is useful for checking that content blockers and split DNS rules \
+                        work as expected. The log is only kept in memory by the daemon, holds \
+                        the last 1000 queries and is not included in problem reports.",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Enable or disable the DNS query log")
+                            .arg(
+                                clap::Arg::new("policy")
+                                    .required(true)
+                                    .possible_values(&["on", "off"]),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("tail")
+                            .about("Display the most recent DNS queries")
+                            .arg(
+                                clap::Arg::new("lines")
+                                    .short('n')
+                                    .long("lines")
+                                    .takes_value(true)
+                                    .default_value("20")
+                                    .help("Number of queries to display"),
+                            )
+                            .arg(
+                                clap::Arg::new("follow")
+                                    .short('f')
+                                    .long("follow")
+                                    .takes_value(false)
+                                    .help("Keep displaying new queries as they are made"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("search")
+                    .about("Configure the domains that are appended to short names")
+                    .long_about(
+                        "Configure the domains that are appended to short names while connected, \
+                        so that a name such as 'intranet' can resolve to 'intranet.corp.example'.",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Set the search domains, in the order they are tried")
+                            .arg(
+                                clap::Arg::new("domains")
+                                    .multiple_occurrences(true)
+                                    .help("One or more domains, such as corp.example")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(clap::App::new("clear").about("Remove all search domains")),
+            )
+            .subcommand(
+                clap::App::new("leak-prevention")
+                    .about("Control which DNS traffic is blocked while connected")
+                    .long_about(
+                        "Control which DNS traffic is blocked while connected. Plain DNS to other \
+                        servers than the ones in use is always blocked. 'strict' also blocks DNS \
+                        over TLS, and DNS over HTTPS to well-known public resolvers, which stops \
+                        programs with their own DNS servers from bypassing them.",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Set the DNS leak prevention mode")
+                            .arg(
+                                clap::Arg::new("mode")
+                                    .required(true)
+                                    .possible_values(&["default", "strict"]),
+                            ),
+                    ),
+            );
+        #[cfg(target_os = "linux")]
+        let app = app.subcommand(
+            clap::App::new("backend")
+                .about("Control how DNS is configured on this host")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(clap::App::new("get").about("Display the current DNS backend"))
+                .subcommand(
+                    clap::App::new("set")
+                        .about("Set the DNS backend")
+                        .long_about(
+                            "Set the DNS backend. 'auto' detects how DNS is managed on this \
+                            host. 'systemd-resolved' configures DNS for the tunnel interface \
+                            through systemd-resolved, without modifying /etc/resolv.conf. Use it \
+                            when another program keeps rewriting /etc/resolv.conf.",
+                        )
+                        .arg(
+                            clap::Arg::new("backend")
+                                .required(true)
+                                .possible_values(&["auto", "systemd-resolved"]),
+                        )
+                        .arg(
+                            clap::Arg::new("dnssec")
+                                .long("dnssec")
+                                .help("DNSSEC mode to use with systemd-resolved")
+                                .takes_value(true)
+                                .default_value("global")
+                                .possible_values(&["global", "no", "allow-downgrade", "yes"]),
+                        ),
+                ),
+        );
+        app
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -93,6 +291,87 @@ impl Command for Dns {
                 _ => unreachable!("No custom-dns server command given"),
             },
             Some(("get", _)) => self.get().await,
+            Some(("split", matches)) => match matches.subcommand() {
+                Some(("list", _)) => self.list_split_rules().await,
+                Some(("add", matches)) => {
+                    let domain = matches.value_of("domain").unwrap().to_owned();
+                    let servers = matches.values_of_t_or_exit::<IpAddr>("servers");
+                    self.add_split_rule(SplitDnsRule { domain, servers }).await
+                }
+                Some(("remove", matches)) => {
+                    self.remove_split_rule(matches.value_of("domain").unwrap())
+                        .await
+                }
+                _ => unreachable!("No split DNS command given"),
+            },
+            Some(("filter", matches)) => match matches.subcommand() {
+                Some(("list", _)) => self.list_filter_rules().await,
+                Some(("block", matches)) => {
+                    let domain = matches.value_of("domain").unwrap().to_owned();
+                    self.add_filter_rule(DnsFilterRule {
+                        domain,
+                        action: DnsFilterAction::Block,
+                    })
+                    .await
+                }
+                Some(("rewrite", matches)) => {
+                    let domain = matches.value_of("domain").unwrap().to_owned();
+                    let addresses = matches.values_of_t_or_exit::<IpAddr>("addresses");
+                    self.add_filter_rule(DnsFilterRule {
+                        domain,
+                        action: DnsFilterAction::Rewrite(addresses),
+                    })
+                    .await
+                }
+                Some(("remove", matches)) => {
+                    self.remove_filter_rule(matches.value_of("domain").unwrap())
+                        .await
+                }
+                _ => unreachable!("No DNS filter command given"),
+            },
+            Some(("log", matches)) => match matches.subcommand() {
+                Some(("set", matches)) => {
+                    self.set_query_log(matches.value_of("policy").unwrap() == "on")
+                        .await
+                }
+                Some(("tail", matches)) => {
+                    let lines = matches.value_of_t_or_exit::<usize>("lines");
+                    self.tail_query_log(lines, matches.is_present("follow"))
+                        .await
+                }
+                _ => unreachable!("No DNS query log command given"),
+            },
+            Some(("search", matches)) => match matches.subcommand() {
+                Some(("set", matches)) => {
+                    let domains = matches
+                        .values_of("domains")
+                        .unwrap()
+                        .map(str::to_owned)
+                        .collect();
+                    self.set_search_domains(domains).await
+                }
+                Some(("clear", _)) => self.set_search_domains(vec![]).await,
+                _ => unreachable!("No DNS search domain command given"),
+            },
+            Some(("leak-prevention", matches)) => match matches.subcommand() {
+                Some(("set", matches)) => {
+                    self.set_strict_leak_prevention(matches.value_of("mode").unwrap() == "strict")
+                        .await
+                }
+                _ => unreachable!("No DNS leak prevention command given"),
+            },
+            #[cfg(target_os = "linux")]
+            Some(("backend", matches)) => match matches.subcommand() {
+                Some(("set", matches)) => {
+                    self.set_backend(
+                        matches.value_of("backend").unwrap(),
+                        matches.value_of("dnssec").unwrap(),
+                    )
+                    .await
+                }
+                Some(("get", _)) => self.get_backend().await,
+                _ => unreachable!("No DNS backend command given"),
+            },
             _ => unreachable!("No custom-dns command given"),
         }
     }
@@ -176,7 +455,282 @@ impl Dns {
                 }
             }
         }
+        if !options.split_rules.is_empty() {
+            println!("Split DNS rules:");
+            for rule in &options.split_rules {
+                println!("{}", rule);
+            }
+        }
+        if !options.filter_rules.is_empty() {
+            println!("DNS filter rules:");
+            for rule in &options.filter_rules {
+                println!("{}", rule);
+            }
+        }
+        if !options.search_domains.is_empty() {
+            println!("Search domains: {}", options.search_domains.join(", "));
+        }
+        println!(
+            "Strict leak prevention: {}",
+            if options.strict_leak_prevention {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+        println!(
+            "Query log: {}",
+            if options.query_log { "on" } else { "off" }
+        );
+
+        Ok(())
+    }
+
+    async fn list_split_rules(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let options = Self::get_options(&mut rpc).await?;
+        for rule in options.split_rules {
+            let rule = SplitDnsRule::try_from(rule)
+                .map_err(|_| Error::Other("Received invalid split DNS rule"))?;
+            println!("{}", rule);
+        }
+        Ok(())
+    }
+
+    async fn add_split_rule(&self, rule: SplitDnsRule) -> Result<()> {
+        if !rule.is_valid() {
+            return Err(Error::InvalidCommand("invalid domain"));
+        }
+        let mut rpc = new_rpc_client().await?;
+        let mut options = Self::get_options(&mut rpc).await?;
+        options
+            .split_rules
+            .retain(|existing| !existing.domain.eq_ignore_ascii_case(&rule.domain));
+        options.split_rules.push(types::SplitDnsRule::from(&rule));
+        rpc.set_dns_options(options).await?;
+        println!("Added split DNS rule: {}", rule);
+        Ok(())
+    }
+
+    async fn remove_split_rule(&self, domain: &str) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut options = Self::get_options(&mut rpc).await?;
+        let num_rules = options.split_rules.len();
+        options
+            .split_rules
+            .retain(|existing| !existing.domain.eq_ignore_ascii_case(domain));
+        if options.split_rules.len() == num_rules {
+            return Err(Error::InvalidCommand("no rule exists for the domain"));
+        }
+        rpc.set_dns_options(options).await?;
+        println!("Removed split DNS rule for {}", domain);
+        Ok(())
+    }
+
+    async fn list_filter_rules(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let options = Self::get_options(&mut rpc).await?;
+        for rule in options.filter_rules {
+            let rule = DnsFilterRule::try_from(rule)
+                .map_err(|_| Error::Other("Received invalid DNS filter rule"))?;
+            println!("{}", rule);
+        }
+        Ok(())
+    }
+
+    async fn add_filter_rule(&self, rule: DnsFilterRule) -> Result<()> {
+        if !rule.is_valid() {
+            return Err(Error::InvalidCommand("invalid domain"));
+        }
+        let mut rpc = new_rpc_client().await?;
+        let mut options = Self::get_options(&mut rpc).await?;
+        options
+            .filter_rules
+            .retain(|existing| !existing.domain.eq_ignore_ascii_case(&rule.domain));
+        options.filter_rules.push(types::DnsFilterRule::from(&rule));
+        rpc.set_dns_options(options).await?;
+        println!("Added DNS filter rule: {}", rule);
+        Ok(())
+    }
+
+    async fn remove_filter_rule(&self, domain: &str) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut options = Self::get_options(&mut rpc).await?;
+        let num_rules = options.filter_rules.len();
+        options
+            .filter_rules
+            .retain(|existing| !existing.domain.eq_ignore_ascii_case(domain));
+        if options.filter_rules.len() == num_rules {
+            return Err(Error::InvalidCommand("no rule exists for the domain"));
+        }
+        rpc.set_dns_options(options).await?;
+        println!("Removed DNS filter rule for {}", domain);
+        Ok(())
+    }
+
+    async fn set_query_log(&self, enabled: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let options = Self::get_options(&mut rpc).await?;
+        rpc.set_dns_options(types::DnsOptions {
+            query_log: enabled,
+            ..options
+        })
+        .await?;
+        if enabled {
+            println!("Enabled the DNS query log");
+        } else {
+            println!("Disabled and cleared the DNS query log");
+        }
+        Ok(())
+    }
+
+    async fn tail_query_log(&self, lines: usize, follow: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        if !Self::get_options(&mut rpc).await?.query_log {
+            return Err(Error::CommandFailed(
+                "The DNS query log is disabled. Enable it with 'mullvad dns log set on'",
+            ));
+        }
 
+        let entries = rpc.get_dns_query_log(0).await?.into_inner().entries;
+        let mut last_sequence = entries.last().map(|entry| entry.sequence).unwrap_or(0);
+        for entry in &entries[entries.len().saturating_sub(lines)..] {
+            print_query_log_entry(entry);
+        }
+        while follow {
+            tokio::time::sleep(QUERY_LOG_POLL_INTERVAL).await;
+            let entries = rpc
+                .get_dns_query_log(last_sequence)
+                .await?
+                .into_inner()
+                .entries;
+            for entry in &entries {
+                print_query_log_entry(entry);
+            }
+            if let Some(entry) = entries.last() {
+                last_sequence = entry.sequence;
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_search_domains(&self, domains: Vec<String>) -> Result<()> {
+        if !domains.iter().all(|domain| is_valid_domain(domain)) {
+            return Err(Error::InvalidCommand("invalid domain"));
+        }
+        let mut rpc = new_rpc_client().await?;
+        let options = Self::get_options(&mut rpc).await?;
+        rpc.set_dns_options(types::DnsOptions {
+            search_domains: domains,
+            ..options
+        })
+        .await?;
+        println!("Updated DNS search domains");
+        Ok(())
+    }
+
+    async fn set_strict_leak_prevention(&self, enabled: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let options = Self::get_options(&mut rpc).await?;
+        rpc.set_dns_options(types::DnsOptions {
+            strict_leak_prevention: enabled,
+            ..options
+        })
+        .await?;
+        println!("Updated DNS leak prevention");
+        Ok(())
+    }
+
+    async fn get_options(rpc: &mut ManagementServiceClient) -> Result<types::DnsOptions> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .tunnel_options
+            .unwrap()
+            .dns_options
+            .unwrap())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_backend(&self, backend: &str, dnssec: &str) -> Result<()> {
+        use types::dns_backend::{Backend, Dnssec};
+
+        let backend = match backend {
+            "auto" => Backend::Auto,
+            "systemd-resolved" => Backend::SystemdResolved,
+            _ => unreachable!("unknown DNS backend"),
+        };
+        let dnssec = match dnssec {
+            "global" => Dnssec::Global,
+            "no" => Dnssec::No,
+            "allow-downgrade" => Dnssec::AllowDowngrade,
+            "yes" => Dnssec::Yes,
+            _ => unreachable!("unknown DNSSEC mode"),
+        };
+        if backend == Backend::Auto && dnssec != Dnssec::Global {
+            return Err(Error::InvalidCommand(
+                "--dnssec can only be used with systemd-resolved",
+            ));
+        }
+
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_dns_backend(types::DnsBackend {
+            backend: backend as i32,
+            dnssec: dnssec as i32,
+        })
+        .await?;
+        println!("Updated DNS backend");
         Ok(())
     }
+
+    #[cfg(target_os = "linux")]
+    async fn get_backend(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let backend = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .dns_backend
+            .unwrap_or_default();
+        let backend = talpid_types::net::DnsBackend::try_from(backend)
+            .map_err(|_| Error::Other("Received invalid DNS backend"))?;
+        println!("DNS backend: {}", backend);
+        Ok(())
+    }
+}
+
+fn print_query_log_entry(entry: &types::DnsQueryLogEntry) {
+    use types::dns_query_log_entry::Outcome;
+
+    let time = entry.time.as_ref().map(|time| {
+        let time = chrono::NaiveDateTime::from_timestamp(time.seconds, 0);
+        chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc).with_timezone(&chrono::Local)
+    });
+    let query_type = match entry.query_type {
+        1 => "A".to_owned(),
+        5 => "CNAME".to_owned(),
+        12 => "PTR".to_owned(),
+        15 => "MX".to_owned(),
+        16 => "TXT".to_owned(),
+        28 => "AAAA".to_owned(),
+        33 => "SRV".to_owned(),
+        65 => "HTTPS".to_owned(),
+        other => format!("TYPE{}", other),
+    };
+    let outcome = match Outcome::from_i32(entry.outcome) {
+        Some(Outcome::Resolved) => format!("resolved by {}", entry.server),
+        Some(Outcome::Blocked) => "blocked".to_owned(),
+        Some(Outcome::Rewritten) => "rewritten".to_owned(),
+        Some(Outcome::NoResponse) => "no response".to_owned(),
+        None => "unknown".to_owned(),
+    };
+    println!(
+        "{} {} {} {}",
+        time.map(|time| time.format("%X").to_string())
+            .unwrap_or_default(),
+        query_type,
+        entry.name,
+        outcome
+    );
 }
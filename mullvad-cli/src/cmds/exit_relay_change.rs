@@ -0,0 +1,72 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types::{
+    exit_relay_change_policy::Policy, ExitRelayChangePolicy,
+};
+
+pub struct ExitRelayChange;
+
+#[mullvad_management_interface::async_trait]
+impl Command for ExitRelayChange {
+    fn name(&self) -> &'static str {
+        "exit-relay-change"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Control what happens when a relay list update shows that the current exit relay \
+                    has moved to another location or been reclassified",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Change the exit relay change policy")
+                    .arg(
+                        clap::Arg::new("policy")
+                            .help(
+                                "Whether to only notify clients, or to also reconnect so that a \
+                                relay matching the constraints is selected",
+                            )
+                            .required(true)
+                            .possible_values(&["notify", "reconnect"]),
+                    ),
+            )
+            .subcommand(clap::App::new("get").about("Display the current exit relay change policy"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("set", matches)) => {
+                let policy = match matches.value_of("policy").unwrap() {
+                    "notify" => Policy::Notify,
+                    "reconnect" => Policy::Reconnect,
+                    _ => unreachable!("invalid policy"),
+                };
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_exit_relay_change_policy(ExitRelayChangePolicy {
+                    policy: i32::from(policy),
+                })
+                .await?;
+                println!("Changed exit relay change policy");
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = rpc.get_settings(()).await?.into_inner();
+                let policy = settings
+                    .exit_relay_change_policy
+                    .and_then(|policy| Policy::from_i32(policy.policy))
+                    .unwrap_or(Policy::Notify);
+                println!(
+                    "Exit relay change policy: {}",
+                    match policy {
+                        Policy::Notify => "notify",
+                        Policy::Reconnect => "reconnect",
+                    }
+                );
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
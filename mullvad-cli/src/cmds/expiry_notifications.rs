@@ -0,0 +1,126 @@
+use super::disconnect::{format_duration, parse_duration};
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::time::Duration;
+
+pub struct ExpiryNotifications;
+
+#[mullvad_management_interface::async_trait]
+impl Command for ExpiryNotifications {
+    fn name(&self) -> &'static str {
+        "expiry-notifications"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Configure notifications about the account running out of time")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("thresholds")
+                    .about("Set how long before the expiry to notify")
+                    .arg(
+                        clap::Arg::new("threshold")
+                            .help(
+                                "Notify once when the time left drops below each of these, \
+                                e.g. 7d 1d. Use \"none\" to disable the notifications",
+                            )
+                            .required(true)
+                            .multiple_values(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("script")
+                    .about("Set a program to run for each notification")
+                    .long_about(
+                        "Set a program to run for each notification. The program gets the expiry \
+                        date in MULLVAD_ACCOUNT_EXPIRY and the crossed threshold, in seconds, in \
+                        MULLVAD_EXPIRY_THRESHOLD. On Linux, it runs as the user nobody.",
+                    )
+                    .arg(
+                        clap::Arg::new("path")
+                            .help("Absolute path to the program, or \"none\" to not run anything")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("get").about("Display the current expiry notification settings"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("thresholds", matches)) => {
+                let values: Vec<&str> = matches.values_of("threshold").unwrap().collect();
+                let thresholds = if values == ["none"] {
+                    vec![]
+                } else {
+                    values
+                        .into_iter()
+                        .map(|value| {
+                            parse_duration(value).ok_or(Error::InvalidCommand(
+                                "invalid threshold, expected e.g. 7d or 12h",
+                            ))
+                        })
+                        .collect::<Result<Vec<Duration>>>()?
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.thresholds = thresholds.into_iter().map(types::Duration::from).collect();
+                rpc.set_expiry_notifications(settings).await?;
+                println!("Changed expiry notification thresholds");
+                Ok(())
+            }
+            Some(("script", matches)) => {
+                let path = matches.value_of("path").unwrap();
+                let script = if path == "none" {
+                    String::new()
+                } else if std::path::Path::new(path).is_absolute() {
+                    path.to_owned()
+                } else {
+                    return Err(Error::InvalidCommand("the script path must be absolute"));
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.script = script;
+                rpc.set_expiry_notifications(settings).await?;
+                println!("Changed expiry notification script");
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = Self::get_settings(&mut rpc).await?;
+                let thresholds: Vec<String> = settings
+                    .thresholds
+                    .into_iter()
+                    .filter_map(|threshold| Duration::try_from(threshold).ok())
+                    .map(format_duration)
+                    .collect();
+                if thresholds.is_empty() {
+                    println!("Thresholds: none");
+                } else {
+                    println!("Thresholds: {}", thresholds.join(" "));
+                }
+                if settings.script.is_empty() {
+                    println!("Script    : none");
+                } else {
+                    println!("Script    : {}", settings.script);
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl ExpiryNotifications {
+    async fn get_settings(
+        rpc: &mut ManagementServiceClient,
+    ) -> Result<types::ExpiryNotificationSettings> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .expiry_notifications
+            .unwrap_or_default())
+    }
+}
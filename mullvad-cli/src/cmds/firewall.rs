@@ -0,0 +1,147 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::convert::TryFrom;
+use talpid_types::net::{FirewallException, FirewallExceptionDirection, TransportProtocol};
+
+pub struct Firewall;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Firewall {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Manage ports that are kept open towards the local network, \
+                    even while the firewall is blocking traffic",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(with_exception_args(
+                clap::App::new("add").about("Keep a port open towards the local network"),
+            ))
+            .subcommand(with_exception_args(
+                clap::App::new("delete").about("Remove a firewall exception"),
+            ))
+            .subcommand(clap::App::new("clear").about("Remove all firewall exceptions"))
+            .subcommand(clap::App::new("list").about("Display all firewall exceptions"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("add", matches)) => {
+                let exception = parse_exception(matches);
+                let mut rpc = new_rpc_client().await?;
+                let mut exceptions = Self::get_exceptions(&mut rpc).await?;
+                if exceptions.contains(&exception) {
+                    println!("The firewall exception already exists");
+                    return Ok(());
+                }
+                exceptions.push(exception);
+                Self::set_exceptions(&mut rpc, exceptions).await?;
+                println!("Added firewall exception: {}", exception);
+                Ok(())
+            }
+            Some(("delete", matches)) => {
+                let exception = parse_exception(matches);
+                let mut rpc = new_rpc_client().await?;
+                let mut exceptions = Self::get_exceptions(&mut rpc).await?;
+                let num_exceptions = exceptions.len();
+                exceptions.retain(|existing| *existing != exception);
+                if exceptions.len() == num_exceptions {
+                    println!("No such firewall exception");
+                    return Ok(());
+                }
+                Self::set_exceptions(&mut rpc, exceptions).await?;
+                println!("Removed firewall exception: {}", exception);
+                Ok(())
+            }
+            Some(("clear", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                Self::set_exceptions(&mut rpc, vec![]).await?;
+                println!("Removed all firewall exceptions");
+                Ok(())
+            }
+            Some(("list", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let exceptions = Self::get_exceptions(&mut rpc).await?;
+                if exceptions.is_empty() {
+                    println!("No firewall exceptions");
+                }
+                for exception in exceptions {
+                    println!("{}", exception);
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl Firewall {
+    async fn get_exceptions(rpc: &mut ManagementServiceClient) -> Result<Vec<FirewallException>> {
+        let exceptions = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .firewall_exceptions
+            .unwrap_or_default()
+            .exceptions;
+        Ok(exceptions
+            .into_iter()
+            .filter_map(|exception| FirewallException::try_from(exception).ok())
+            .collect())
+    }
+
+    async fn set_exceptions(
+        rpc: &mut ManagementServiceClient,
+        exceptions: Vec<FirewallException>,
+    ) -> Result<()> {
+        rpc.set_firewall_exceptions(types::FirewallExceptions {
+            exceptions: exceptions
+                .into_iter()
+                .map(types::FirewallException::from)
+                .collect(),
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+fn with_exception_args(app: clap::App<'static>) -> clap::App<'static> {
+    app.arg(
+        clap::Arg::new("direction")
+            .help("Whether to permit connections from, or to, the local network")
+            .required(true)
+            .possible_values(&["inbound", "outbound"]),
+    )
+    .arg(
+        clap::Arg::new("protocol")
+            .required(true)
+            .possible_values(&["tcp", "udp"]),
+    )
+    .arg(
+        clap::Arg::new("port")
+            .help("The local port of inbound connections, or the remote port of outbound ones")
+            .required(true),
+    )
+}
+
+fn parse_exception(matches: &clap::ArgMatches) -> FirewallException {
+    let direction = match matches.value_of("direction").unwrap() {
+        "inbound" => FirewallExceptionDirection::Inbound,
+        "outbound" => FirewallExceptionDirection::Outbound,
+        _ => unreachable!("invalid direction"),
+    };
+    let protocol = match matches.value_of("protocol").unwrap() {
+        "tcp" => TransportProtocol::Tcp,
+        "udp" => TransportProtocol::Udp,
+        _ => unreachable!("invalid protocol"),
+    };
+    FirewallException {
+        direction,
+        protocol,
+        port: matches.value_of_t_or_exit("port"),
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+
+pub struct Hooks;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Hooks {
+    fn name(&self) -> &'static str {
+        "hooks"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        let path_arg = || {
+            clap::Arg::new("path")
+                .help("Absolute path to the program, or \"none\" to not run anything")
+                .required(true)
+        };
+        clap::App::new(self.name())
+            .about("Configure programs to run when the tunnel state changes")
+            .long_about(
+                "Configure programs to run when the tunnel state changes. The programs run with \
+                the privileges of the daemon, so they must be placed in the hooks directory of \
+                the settings directory, e.g. /etc/mullvad-vpn/hooks on Linux and macOS, which \
+                only administrators can write to.\n\n\
+                The programs get an empty environment apart from PATH and these variables, when \
+                they are known: MULLVAD_TUNNEL_STATE, MULLVAD_TUNNEL_TYPE, \
+                MULLVAD_RELAY_HOSTNAME, MULLVAD_ENTRY_HOSTNAME, MULLVAD_ENDPOINT_IP, \
+                MULLVAD_ENDPOINT_PORT, MULLVAD_ENDPOINT_PROTOCOL, MULLVAD_INTERFACE, \
+                MULLVAD_TUNNEL_IPV4, MULLVAD_TUNNEL_IPV6, MULLVAD_COUNTRY and MULLVAD_CITY. The \
+                disconnect and error hooks get the variables of the connection that went away. \
+                The error hook also gets MULLVAD_ERROR and MULLVAD_BLOCKING.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("connected")
+                    .about("Set a program to run when the tunnel has connected")
+                    .arg(path_arg()),
+            )
+            .subcommand(
+                clap::App::new("disconnected")
+                    .about("Set a program to run when the tunnel has disconnected")
+                    .arg(path_arg()),
+            )
+            .subcommand(
+                clap::App::new("error")
+                    .about("Set a program to run when the tunnel has entered the error state")
+                    .arg(path_arg()),
+            )
+            .subcommand(clap::App::new("get").about("Display the current hook programs"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some((hook @ ("connected" | "disconnected" | "error"), matches)) => {
+                let path = matches.value_of("path").unwrap();
+                let script = if path == "none" {
+                    String::new()
+                } else if std::path::Path::new(path).is_absolute() {
+                    path.to_owned()
+                } else {
+                    return Err(Error::InvalidCommand("the program path must be absolute"));
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                match hook {
+                    "connected" => settings.connected = script,
+                    "disconnected" => settings.disconnected = script,
+                    _ => settings.error = script,
+                }
+                rpc.set_tunnel_hooks(settings).await?;
+                println!("Changed the {} hook", hook);
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = Self::get_settings(&mut rpc).await?;
+                for (hook, script) in [
+                    ("Connected   ", settings.connected),
+                    ("Disconnected", settings.disconnected),
+                    ("Error       ", settings.error),
+                ] {
+                    if script.is_empty() {
+                        println!("{}: none", hook);
+                    } else {
+                        println!("{}: {}", hook, script);
+                    }
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl Hooks {
+    async fn get_settings(rpc: &mut ManagementServiceClient) -> Result<types::TunnelHookSettings> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .tunnel_hooks
+            .unwrap_or_default())
+    }
+}
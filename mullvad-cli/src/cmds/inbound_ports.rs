@@ -0,0 +1,135 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::convert::TryFrom;
+use talpid_types::net::{InboundTunnelPort, TransportProtocol};
+
+pub struct InboundPorts;
+
+#[mullvad_management_interface::async_trait]
+impl Command for InboundPorts {
+    fn name(&self) -> &'static str {
+        "inbound-ports"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Manage local ports that accept inbound connections from inside the tunnel, \
+                    such as ports forwarded by the relay",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(with_port_args(
+                clap::App::new("add").about("Accept inbound connections to a port in the tunnel"),
+            ))
+            .subcommand(with_port_args(
+                clap::App::new("delete").about("Stop accepting inbound connections to a port"),
+            ))
+            .subcommand(clap::App::new("clear").about("Remove all inbound tunnel ports"))
+            .subcommand(clap::App::new("list").about("Display all inbound tunnel ports"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("add", matches)) => {
+                let port = parse_port(matches);
+                let mut rpc = new_rpc_client().await?;
+                let mut ports = Self::get_ports(&mut rpc).await?;
+                if ports.contains(&port) {
+                    println!("The inbound tunnel port already exists");
+                    return Ok(());
+                }
+                ports.push(port);
+                Self::set_ports(&mut rpc, ports).await?;
+                println!("Added inbound tunnel port: {}", port);
+                Ok(())
+            }
+            Some(("delete", matches)) => {
+                let port = parse_port(matches);
+                let mut rpc = new_rpc_client().await?;
+                let mut ports = Self::get_ports(&mut rpc).await?;
+                let num_ports = ports.len();
+                ports.retain(|existing| *existing != port);
+                if ports.len() == num_ports {
+                    println!("No such inbound tunnel port");
+                    return Ok(());
+                }
+                Self::set_ports(&mut rpc, ports).await?;
+                println!("Removed inbound tunnel port: {}", port);
+                Ok(())
+            }
+            Some(("clear", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                Self::set_ports(&mut rpc, vec![]).await?;
+                println!("Removed all inbound tunnel ports");
+                Ok(())
+            }
+            Some(("list", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let ports = Self::get_ports(&mut rpc).await?;
+                if ports.is_empty() {
+                    println!("No inbound tunnel ports");
+                }
+                for port in ports {
+                    println!("{}", port);
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl InboundPorts {
+    async fn get_ports(rpc: &mut ManagementServiceClient) -> Result<Vec<InboundTunnelPort>> {
+        let ports = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .inbound_tunnel_ports
+            .unwrap_or_default()
+            .ports;
+        Ok(ports
+            .into_iter()
+            .filter_map(|port| InboundTunnelPort::try_from(port).ok())
+            .collect())
+    }
+
+    async fn set_ports(
+        rpc: &mut ManagementServiceClient,
+        ports: Vec<InboundTunnelPort>,
+    ) -> Result<()> {
+        rpc.set_inbound_tunnel_ports(types::InboundTunnelPorts {
+            ports: ports
+                .into_iter()
+                .map(types::InboundTunnelPort::from)
+                .collect(),
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+fn with_port_args(app: clap::App<'static>) -> clap::App<'static> {
+    app.arg(
+        clap::Arg::new("protocol")
+            .required(true)
+            .possible_values(&["tcp", "udp"]),
+    )
+    .arg(
+        clap::Arg::new("port")
+            .help("The local port that connections are made to")
+            .required(true),
+    )
+}
+
+fn parse_port(matches: &clap::ArgMatches) -> InboundTunnelPort {
+    let protocol = match matches.value_of("protocol").unwrap() {
+        "tcp" => TransportProtocol::Tcp,
+        "udp" => TransportProtocol::Udp,
+        _ => unreachable!("invalid protocol"),
+    };
+    InboundTunnelPort {
+        protocol,
+        port: matches.value_of_t_or_exit("port"),
+    }
+}
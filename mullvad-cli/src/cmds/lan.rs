@@ -1,4 +1,5 @@
 use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types;
 
 pub struct Lan;
 
@@ -22,6 +23,41 @@ impl Command for Lan {
             .subcommand(
                 clap::App::new("get").about("Display the current local network sharing setting"),
             )
+            .subcommand(
+                clap::App::new("multicast")
+                    .about(
+                        "Control whether mDNS and SSDP discovery is allowed, even when LAN \
+                            sharing is blocked",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Change allow multicast setting")
+                            .arg(
+                                clap::Arg::new("policy")
+                                    .required(true)
+                                    .possible_values(&["allow", "block"]),
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("networks")
+                    .about("Control which private networks are reachable when LAN sharing is allowed")
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Only allow the given private networks")
+                            .arg(
+                                clap::Arg::new("networks")
+                                    .multiple_values(true)
+                                    .help("One or more networks in CIDR notation, e.g. 192.168.1.0/24")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("reset").about("Allow all private networks (default)"),
+                    ),
+            )
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -30,6 +66,23 @@ impl Command for Lan {
             self.set(allow_lan == "allow").await
         } else if let Some(_matches) = matches.subcommand_matches("get") {
             self.get().await
+        } else if let Some(multicast_matches) = matches.subcommand_matches("multicast") {
+            match multicast_matches.subcommand() {
+                Some(("set", matches)) => {
+                    let policy = matches.value_of("policy").expect("missing policy");
+                    self.set_multicast(policy == "allow").await
+                }
+                _ => unreachable!("No lan multicast command given"),
+            }
+        } else if let Some(networks_matches) = matches.subcommand_matches("networks") {
+            match networks_matches.subcommand() {
+                Some(("set", matches)) => {
+                    let networks: Vec<String> = matches.values_of_t_or_exit("networks");
+                    self.set_networks(networks).await
+                }
+                Some(("reset", _)) => self.set_networks(vec![]).await,
+                _ => unreachable!("No lan networks command given"),
+            }
         } else {
             unreachable!("No lan command given");
         }
@@ -44,13 +97,40 @@ impl Lan {
         Ok(())
     }
 
+    async fn set_multicast(&self, allow_multicast: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_allow_multicast(allow_multicast).await?;
+        println!("Changed multicast discovery setting");
+        Ok(())
+    }
+
+    async fn set_networks(&self, networks: Vec<String>) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_allowed_lan_nets(types::AllowedLanNets { networks })
+            .await?;
+        println!("Changed allowed local networks");
+        Ok(())
+    }
+
     async fn get(&self) -> Result<()> {
         let mut rpc = new_rpc_client().await?;
-        let allow_lan = rpc.get_settings(()).await?.into_inner().allow_lan;
+        let settings = rpc.get_settings(()).await?.into_inner();
         println!(
             "Local network sharing setting: {}",
-            if allow_lan { "allow" } else { "block" }
+            if settings.allow_lan { "allow" } else { "block" }
         );
+        println!(
+            "Multicast discovery setting: {}",
+            if settings.allow_multicast {
+                "allow"
+            } else {
+                "block"
+            }
+        );
+        match settings.allowed_lan_nets {
+            Some(nets) => println!("Allowed local networks: {}", nets.networks.join(", ")),
+            None => println!("Allowed local networks: all private networks"),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,138 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types::{self, tunnel_state};
+
+pub struct LinkLayer;
+
+#[mullvad_management_interface::async_trait]
+impl Command for LinkLayer {
+    fn name(&self) -> &'static str {
+        "link-layer"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Control which link layer protocols are permitted while traffic is blocked")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Permit or block a link layer protocol")
+                    .arg(
+                        clap::Arg::new("protocol")
+                            .required(true)
+                            .possible_values(&["dhcp", "ndp"]),
+                    )
+                    .arg(
+                        clap::Arg::new("policy")
+                            .required(true)
+                            .possible_values(&["on", "off"]),
+                    ),
+            )
+            .subcommand(clap::App::new("get").about("Display the current link layer settings"))
+            .subcommand(
+                clap::App::new("status")
+                    .about("Display which link layer exemptions are currently active"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        if let Some(set_matches) = matches.subcommand_matches("set") {
+            let protocol = set_matches.value_of("protocol").expect("missing protocol");
+            let enabled = set_matches.value_of("policy").expect("missing policy") == "on";
+            self.set(protocol, enabled).await
+        } else if let Some(_matches) = matches.subcommand_matches("get") {
+            self.get().await
+        } else if let Some(_matches) = matches.subcommand_matches("status") {
+            self.status().await
+        } else {
+            unreachable!("No link-layer command given");
+        }
+    }
+}
+
+impl LinkLayer {
+    async fn set(&self, protocol: &str, enabled: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut exemptions = Self::get_exemptions(&mut rpc).await?;
+        match protocol {
+            "dhcp" => exemptions.permit_dhcp = enabled,
+            "ndp" => exemptions.permit_ndp = enabled,
+            _ => unreachable!("unknown link layer protocol"),
+        }
+        rpc.set_link_layer_exemptions(exemptions).await?;
+        println!("Changed link layer settings");
+        Ok(())
+    }
+
+    async fn get(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let exemptions = Self::get_exemptions(&mut rpc).await?;
+        println!("DHCP: {}", on_off(exemptions.permit_dhcp));
+        println!("NDP : {}", on_off(exemptions.permit_ndp));
+        Ok(())
+    }
+
+    async fn status(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let settings = rpc.get_settings(()).await?.into_inner();
+        let state = rpc.get_tunnel_state(()).await?.into_inner();
+        let exemptions = settings.link_layer_exemptions.unwrap_or_default();
+
+        match state.state {
+            Some(tunnel_state::State::Disconnected(_)) if !settings.block_when_disconnected => {
+                println!(
+                    "The firewall is not blocking traffic. All link layer traffic is permitted."
+                );
+                return Ok(());
+            }
+            Some(tunnel_state::State::Error(tunnel_state::Error {
+                error_state:
+                    Some(types::ErrorState {
+                        blocking_error: Some(_),
+                        ..
+                    }),
+            })) => {
+                println!(
+                    "The firewall failed to apply a blocking policy. Traffic is not filtered."
+                );
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        println!(
+            "DHCPv4/DHCPv6 client: {}",
+            permitted(exemptions.permit_dhcp)
+        );
+        println!("NDP                 : {}", permitted(exemptions.permit_ndp));
+        println!("DHCPv4 server       : {}", permitted(settings.allow_lan));
+        println!("ARP                 : permitted (not filtered by the firewall)");
+        Ok(())
+    }
+
+    async fn get_exemptions(
+        rpc: &mut mullvad_management_interface::ManagementServiceClient,
+    ) -> Result<types::LinkLayerExemptions> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .link_layer_exemptions
+            .unwrap_or_default())
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn permitted(enabled: bool) -> &'static str {
+    if enabled {
+        "permitted"
+    } else {
+        "blocked"
+    }
+}
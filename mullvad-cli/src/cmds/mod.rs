@@ -4,6 +4,9 @@ use std::collections::HashMap;
 mod account;
 pub use self::account::Account;
 
+mod api_access;
+pub use self::api_access::ApiAccess;
+
 mod auto_connect;
 pub use self::auto_connect::AutoConnect;
 
@@ -16,30 +19,88 @@ pub use self::block_when_disconnected::BlockWhenDisconnected;
 mod bridge;
 pub use self::bridge::Bridge;
 
+mod captive_portal;
+pub use self::captive_portal::CaptivePortal;
+
+mod check;
+pub use self::check::Check;
+
+mod circumvention;
+pub use self::circumvention::Circumvention;
+
 mod connect;
 pub use self::connect::Connect;
 
+mod custom_list;
+pub use self::custom_list::CustomList;
+
+mod debug;
+pub use self::debug::Debug;
+
 mod disconnect;
 pub use self::disconnect::Disconnect;
 
+mod disconnect_warning;
+pub use self::disconnect_warning::DisconnectWarning;
+
 mod dns;
 pub use self::dns::Dns;
 
+mod exit_relay_change;
+pub use self::exit_relay_change::ExitRelayChange;
+
+mod expiry_notifications;
+pub use self::expiry_notifications::ExpiryNotifications;
+
+mod firewall;
+pub use self::firewall::Firewall;
+
+mod hooks;
+pub use self::hooks::Hooks;
+
+mod inbound_ports;
+pub use self::inbound_ports::InboundPorts;
+
 mod lan;
 pub use self::lan::Lan;
 
+#[cfg(windows)]
+mod link_layer;
+#[cfg(windows)]
+pub use self::link_layer::LinkLayer;
+
 mod obfuscation;
 pub use self::obfuscation::Obfuscation;
 
+mod offline_detection;
+pub use self::offline_detection::OfflineDetection;
+
+#[cfg(target_os = "linux")]
+mod policy_routing;
+#[cfg(target_os = "linux")]
+pub use self::policy_routing::PolicyRouting;
+
 mod reconnect;
 pub use self::reconnect::Reconnect;
 
+mod reconnect_debounce;
+pub use self::reconnect_debounce::ReconnectDebounce;
+
 mod relay;
 pub use self::relay::Relay;
 
+mod relay_stats;
+pub use self::relay_stats::RelayStats;
+
 mod reset;
 pub use self::reset::Reset;
 
+mod setting_override;
+pub use self::setting_override::SettingOverride;
+
+mod settings;
+pub use self::settings::Settings;
+
 #[cfg(any(target_os = "linux", windows))]
 mod split_tunnel;
 #[cfg(any(target_os = "linux", windows))]
@@ -48,6 +109,12 @@ pub use self::split_tunnel::SplitTunnel;
 mod status;
 pub use self::status::Status;
 
+mod strict_enforcement;
+pub use self::strict_enforcement::StrictEnforcement;
+
+mod test;
+pub use self::test::Test;
+
 mod tunnel;
 pub use self::tunnel::Tunnel;
 
@@ -58,21 +125,44 @@ pub use self::version::Version;
 pub fn get_commands() -> HashMap<&'static str, Box<dyn Command>> {
     let commands: Vec<Box<dyn Command>> = vec![
         Box::new(Account),
+        Box::new(ApiAccess),
         Box::new(AutoConnect),
         Box::new(BetaProgram),
         Box::new(BlockWhenDisconnected),
         Box::new(Bridge),
+        Box::new(CaptivePortal),
+        Box::new(Check),
+        Box::new(Circumvention),
         Box::new(Connect),
+        Box::new(CustomList),
+        Box::new(Debug),
         Box::new(Disconnect),
+        Box::new(DisconnectWarning),
         Box::new(Dns),
+        Box::new(ExitRelayChange),
+        Box::new(ExpiryNotifications),
+        Box::new(Firewall),
+        Box::new(Hooks),
+        Box::new(InboundPorts),
         Box::new(Reconnect),
+        Box::new(ReconnectDebounce),
         Box::new(Lan),
+        #[cfg(windows)]
+        Box::new(LinkLayer),
         Box::new(Obfuscation),
+        Box::new(OfflineDetection),
+        #[cfg(target_os = "linux")]
+        Box::new(PolicyRouting),
         Box::new(Relay),
+        Box::new(RelayStats),
         Box::new(Reset),
+        Box::new(SettingOverride),
+        Box::new(Settings),
         #[cfg(any(target_os = "linux", windows))]
         Box::new(SplitTunnel),
         Box::new(Status),
+        Box::new(StrictEnforcement),
+        Box::new(Test),
         Box::new(Tunnel),
         Box::new(Version),
     ];
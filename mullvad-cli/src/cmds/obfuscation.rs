@@ -4,7 +4,12 @@ use mullvad_management_interface::{types as grpc_types, ManagementServiceClient}
 
 use mullvad_types::relay_constraints::{ObfuscationSettings, SelectedObfuscation};
 
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use talpid_types::net::obfuscation::ExternalTransportConfig;
 
 pub struct Obfuscation;
 
@@ -24,12 +29,17 @@ impl Command for Obfuscation {
             .setting(clap::AppSettings::SubcommandRequiredElseHelp)
             .subcommand(create_obfuscation_set_subcommand())
             .subcommand(create_obfuscation_get_subcommand())
+            .subcommand(
+                clap::App::new("transports")
+                    .about("List the obfuscation protocols supported by the daemon"),
+            )
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
         match matches.subcommand() {
             Some(("set", set_matches)) => Self::handle_set(set_matches).await,
             Some(("get", _get_matches)) => Self::handle_get().await,
+            Some(("transports", _)) => Self::handle_transports().await,
             _ => unreachable!("unhandled command"),
         }
     }
@@ -46,6 +56,8 @@ impl Obfuscation {
                     "auto" => SelectedObfuscation::Auto,
                     "off" => SelectedObfuscation::Off,
                     "udp2tcp" => SelectedObfuscation::Udp2Tcp,
+                    "wss" => SelectedObfuscation::Wss,
+                    "external" => SelectedObfuscation::External,
                     _ => unreachable!("Unhandled obfuscator mode"),
                 };
                 Self::set_obfuscation_settings(&mut rpc, &settings).await?;
@@ -63,6 +75,47 @@ impl Obfuscation {
                 };
                 Self::set_obfuscation_settings(&mut rpc, &settings).await?;
             }
+            Some(("wss", settings_matches)) => {
+                let port: String = settings_matches.value_of_t_or_exit("port");
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_obfuscation_settings(&mut rpc).await?;
+                settings.wss.port = if port == "any" {
+                    mullvad_types::relay_constraints::Constraint::Any
+                } else {
+                    mullvad_types::relay_constraints::Constraint::Only(
+                        port.parse::<u16>().expect("Invalid port number"),
+                    )
+                };
+                Self::set_obfuscation_settings(&mut rpc, &settings).await?;
+            }
+            Some(("external", settings_matches)) => {
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_obfuscation_settings(&mut rpc).await?;
+                if settings_matches.is_present("clear") {
+                    settings.external = Default::default();
+                } else {
+                    let path = PathBuf::from(settings_matches.value_of("path").unwrap());
+                    settings.external.transport = Some(ExternalTransportConfig {
+                        path,
+                        args: settings_matches
+                            .values_of("arg")
+                            .map(|args| args.map(String::from).collect())
+                            .unwrap_or_default(),
+                        method: settings_matches.value_of("method").unwrap().to_owned(),
+                        method_args: settings_matches
+                            .value_of("method-args")
+                            .unwrap_or_default()
+                            .to_owned(),
+                    });
+                    settings.external.bridge = match settings_matches.value_of("bridge") {
+                        None | Some("relay") => None,
+                        Some(_) => {
+                            Some(settings_matches.value_of_t_or_exit::<SocketAddr>("bridge"))
+                        }
+                    };
+                }
+                Self::set_obfuscation_settings(&mut rpc, &settings).await?;
+            }
             _ => unreachable!("unhandled command"),
         }
         Ok(())
@@ -76,6 +129,21 @@ impl Obfuscation {
             obfuscation_settings.selected_obfuscation
         );
         println!("udp2tcp settings: {}", obfuscation_settings.udp2tcp);
+        println!("wss settings: {}", obfuscation_settings.wss);
+        println!("external settings: {}", obfuscation_settings.external);
+        Ok(())
+    }
+
+    async fn handle_transports() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let transports = rpc
+            .get_supported_obfuscation_transports(())
+            .await?
+            .into_inner()
+            .transports;
+        for transport in transports {
+            println!("{:<10} {}", transport.name, transport.description);
+        }
         Ok(())
     }
 
@@ -116,7 +184,7 @@ fn create_obfuscation_set_subcommand() -> clap::App<'static> {
                     )
                     .required(true)
                     .index(1)
-                    .possible_values(&["auto", "off", "udp2tcp"]),
+                    .possible_values(&["auto", "off", "udp2tcp", "wss", "external"]),
             ),
         )
         .subcommand(
@@ -130,6 +198,82 @@ fn create_obfuscation_set_subcommand() -> clap::App<'static> {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            clap::App::new("wss")
+                .about(
+                    "Specifies the config for the WebSocket over TLS obfuscator. Only relays \
+                    that have WebSocket endpoints can be used with it",
+                )
+                .setting(clap::AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    clap::Arg::new("port")
+                        .help("TCP port of remote endpoint. Either 'any' or a specific port")
+                        .long("port")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::App::new("external")
+                .about(
+                    "Configures a pluggable transport client to run WireGuard traffic through. \
+                    The executable must implement version 1 of the Tor pluggable transport \
+                    specification and is run with the privileges of the daemon, so it must be \
+                    placed in the transports directory of the settings directory, e.g. \
+                    /etc/mullvad-vpn/transports on Linux and macOS, which only administrators can \
+                    write to",
+                )
+                .setting(clap::AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    clap::Arg::new("path")
+                        .help("Absolute path to the transport executable")
+                        .long("path")
+                        .takes_value(true)
+                        .validator(|path| {
+                            if Path::new(path).is_absolute() {
+                                Ok(())
+                            } else {
+                                Err("the path must be absolute")
+                            }
+                        })
+                        .required_unless_present("clear"),
+                )
+                .arg(
+                    clap::Arg::new("method")
+                        .help("Name of the transport method to use, e.g. 'obfs4'")
+                        .long("method")
+                        .takes_value(true)
+                        .required_unless_present("clear"),
+                )
+                .arg(
+                    clap::Arg::new("method-args")
+                        .help("Arguments for the method, e.g. 'cert=...;iat-mode=0'")
+                        .long("method-args")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("arg")
+                        .help("Command line argument to pass to the executable")
+                        .long("arg")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    clap::Arg::new("bridge")
+                        .help(
+                            "Address of the server that the transport connects to, or 'relay' \
+                            to use the TCP obfuscation endpoint of the selected relay",
+                        )
+                        .long("bridge")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("clear")
+                        .help("Remove the external transport configuration")
+                        .long("clear")
+                        .conflicts_with_all(&["path", "method", "method-args", "arg", "bridge"]),
+                ),
+        )
 }
 
 fn create_obfuscation_get_subcommand() -> clap::App<'static> {
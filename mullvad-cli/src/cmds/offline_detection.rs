@@ -0,0 +1,131 @@
+use super::disconnect::{format_duration, parse_duration};
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::time::Duration;
+
+pub struct OfflineDetection;
+
+#[mullvad_management_interface::async_trait]
+impl Command for OfflineDetection {
+    fn name(&self) -> &'static str {
+        "offline-detection"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Control when the device is considered to be offline")
+            .long_about(
+                "Control when the device is considered to be offline. No tunnel is connected \
+                while the device is offline. If the device is wrongly found to be offline, \
+                offline detection can be disabled.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Enable or disable offline detection")
+                    .arg(
+                        clap::Arg::new("policy")
+                            .required(true)
+                            .possible_values(&["on", "off"]),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("grace-period")
+                    .about("Set how long the device must appear to be offline before it is")
+                    .arg(
+                        clap::Arg::new("period")
+                            .help("E.g. 5s or 1m. Use \"off\" to react immediately")
+                            .required(true),
+                    ),
+            )
+            .subcommand(clap::App::new("get").about("Display the offline detection settings"))
+            .subcommand(
+                clap::App::new("status")
+                    .about("Display whether the device is considered to be offline, and why"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("set", matches)) => {
+                let enabled = matches.value_of("policy").unwrap() == "on";
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.enabled = enabled;
+                rpc.set_offline_detection(settings).await?;
+                println!("Changed offline detection setting");
+                Ok(())
+            }
+            Some(("grace-period", matches)) => {
+                let value = matches.value_of("period").unwrap();
+                let period = if value == "off" {
+                    Duration::ZERO
+                } else {
+                    parse_duration(value).ok_or(Error::InvalidCommand(
+                        "invalid grace period, expected e.g. 5s or 1m",
+                    ))?
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.grace_period = Some(types::Duration::from(period));
+                rpc.set_offline_detection(settings).await?;
+                println!("Changed offline detection grace period");
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = Self::get_settings(&mut rpc).await?;
+                let period = settings
+                    .grace_period
+                    .and_then(|period| Duration::try_from(period).ok())
+                    .unwrap_or_default();
+                println!(
+                    "Offline detection : {}",
+                    if settings.enabled { "on" } else { "off" }
+                );
+                if period.is_zero() {
+                    println!("Grace period      : off");
+                } else {
+                    println!("Grace period      : {}", format_duration(period));
+                }
+                Ok(())
+            }
+            Some(("status", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let status = rpc.get_offline_status(()).await?.into_inner();
+                println!(
+                    "The device is considered to be {}",
+                    if status.is_offline {
+                        "offline"
+                    } else {
+                        "online"
+                    }
+                );
+                println!(
+                    "The offline monitor finds the device to be {}",
+                    if status.monitor_offline {
+                        "offline"
+                    } else {
+                        "online"
+                    }
+                );
+                println!("The device is offline when {}", status.detection_method);
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl OfflineDetection {
+    async fn get_settings(rpc: &mut ManagementServiceClient) -> Result<types::OfflineDetection> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .offline_detection
+            .unwrap_or_else(|| {
+                types::OfflineDetection::from(talpid_types::net::OfflineDetection::default())
+            }))
+    }
+}
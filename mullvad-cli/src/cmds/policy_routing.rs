@@ -0,0 +1,116 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::convert::TryFrom;
+use talpid_types::net::PolicyRouting as PolicyRoutingSettings;
+
+pub struct PolicyRouting;
+
+#[mullvad_management_interface::async_trait]
+impl Command for PolicyRouting {
+    fn name(&self) -> &'static str {
+        "policy-routing"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Control the firewall mark and routing table used by the app")
+            .long_about(
+                "Control the firewall mark and routing table used by the app. Change these if \
+                they conflict with other software on this host, such as another VPN. Changes \
+                take effect when the daemon is restarted.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("get").about("Display the current firewall mark and routing table"),
+            )
+            .subcommand(
+                clap::App::new("set")
+                    .about("Change the firewall mark and/or routing table")
+                    .setting(clap::AppSettings::ArgRequiredElseHelp)
+                    .arg(
+                        clap::Arg::new("fwmark")
+                            .long("fwmark")
+                            .takes_value(true)
+                            .help(
+                                "Firewall mark, in decimal or hexadecimal (0x...). Must not be 0",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("table")
+                            .long("table")
+                            .takes_value(true)
+                            .help(
+                                "Routing table ID, in decimal or hexadecimal (0x...). Must not \
+                                be a reserved table (0, 252-255)",
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("reset").about("Use the default firewall mark and routing table"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                println!("{}", Self::get(&mut rpc).await?);
+                Ok(())
+            }
+            Some(("set", matches)) => {
+                let mut rpc = new_rpc_client().await?;
+                let mut routing = Self::get(&mut rpc).await?;
+                if let Some(fwmark) = matches.value_of("fwmark") {
+                    routing.fwmark =
+                        parse_u32(fwmark).ok_or(Error::InvalidCommand("invalid fwmark"))?;
+                }
+                if let Some(table) = matches.value_of("table") {
+                    routing.table_id =
+                        parse_u32(table).ok_or(Error::InvalidCommand("invalid routing table"))?;
+                }
+                Self::set(&mut rpc, routing).await
+            }
+            Some(("reset", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                Self::set(&mut rpc, PolicyRoutingSettings::default()).await
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl PolicyRouting {
+    async fn get(rpc: &mut ManagementServiceClient) -> Result<PolicyRoutingSettings> {
+        let routing = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .policy_routing
+            .ok_or(Error::Other("Received no policy routing settings"))?;
+        PolicyRoutingSettings::try_from(routing)
+            .map_err(|_| Error::Other("Received invalid policy routing settings"))
+    }
+
+    async fn set(rpc: &mut ManagementServiceClient, routing: PolicyRoutingSettings) -> Result<()> {
+        if !routing.is_valid() {
+            return Err(Error::InvalidCommand(
+                "the fwmark must not be 0, and the routing table must not be reserved",
+            ));
+        }
+        rpc.set_policy_routing(types::PolicyRouting::from(routing))
+            .await?;
+        println!("Updated policy routing: {}", routing);
+        println!("Restart the daemon for the change to take effect");
+        Ok(())
+    }
+}
+
+fn parse_u32(value: &str) -> Option<u32> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
@@ -1,3 +1,4 @@
+use super::disconnect::confirm_if_forced;
 use crate::{format, new_rpc_client, state, Command, Error, Result};
 use futures::StreamExt;
 use mullvad_management_interface::types::tunnel_state::State;
@@ -19,6 +20,12 @@ impl Command for Reconnect {
                     .short('w')
                     .help("Wait until reconnected before exiting"),
             )
+            .arg(
+                clap::Arg::new("force")
+                    .long("force")
+                    .short('f')
+                    .help("Reconnect even if the tunnel is busy transferring data"),
+            )
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -30,7 +37,8 @@ impl Command for Reconnect {
             None
         };
 
-        if rpc.reconnect_tunnel(()).await?.into_inner() {
+        let response = rpc.reconnect_tunnel(()).await;
+        if confirm_if_forced(&mut rpc, response, matches.is_present("force")).await? {
             if let Some(mut receiver) = receiver_option {
                 while let Some(state) = receiver.next().await {
                     let state = state?;
@@ -0,0 +1,116 @@
+use super::disconnect::{format_duration, parse_duration};
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use std::time::Duration;
+
+pub struct ReconnectDebounce;
+
+#[mullvad_management_interface::async_trait]
+impl Command for ReconnectDebounce {
+    fn name(&self) -> &'static str {
+        "reconnect-debounce"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Control how quickly the tunnel reacts to the network going up and down")
+            .long_about(
+                "Control how quickly the tunnel reacts to the network going up and down. On a \
+                network that keeps dropping out, waiting for it to settle avoids reconnecting \
+                over and over. Traffic stays blocked while waiting.",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("interval")
+                    .about("Set how long the network must stay up or down before reacting to it")
+                    .arg(
+                        clap::Arg::new("interval")
+                            .help("E.g. 5s or 1m. Use \"off\" to react immediately")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("max-per-minute")
+                    .about("Limit how many times per minute the network coming back may reconnect")
+                    .arg(
+                        clap::Arg::new("count")
+                            .help("Number of reconnects, or \"unlimited\"")
+                            .required(true),
+                    ),
+            )
+            .subcommand(clap::App::new("get").about("Display the current debounce settings"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("interval", matches)) => {
+                let value = matches.value_of("interval").unwrap();
+                let interval = if value == "off" {
+                    Duration::ZERO
+                } else {
+                    parse_duration(value).ok_or(Error::InvalidCommand(
+                        "invalid interval, expected e.g. 5s or 1m",
+                    ))?
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.min_stable_interval = Some(types::Duration::from(interval));
+                rpc.set_reconnect_debounce(settings).await?;
+                println!("Changed reconnect debounce interval");
+                Ok(())
+            }
+            Some(("max-per-minute", matches)) => {
+                let value = matches.value_of("count").unwrap();
+                let count = if value == "unlimited" {
+                    0
+                } else {
+                    match value.parse::<u32>() {
+                        Ok(count) if count > 0 => count,
+                        _ => {
+                            return Err(Error::InvalidCommand(
+                                "invalid count, expected a positive number or \"unlimited\"",
+                            ))
+                        }
+                    }
+                };
+                let mut rpc = new_rpc_client().await?;
+                let mut settings = Self::get_settings(&mut rpc).await?;
+                settings.max_reconnects_per_minute = count;
+                rpc.set_reconnect_debounce(settings).await?;
+                println!("Changed maximum number of reconnects per minute");
+                Ok(())
+            }
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = Self::get_settings(&mut rpc).await?;
+                let interval = settings
+                    .min_stable_interval
+                    .and_then(|interval| Duration::try_from(interval).ok())
+                    .unwrap_or_default();
+                if interval.is_zero() {
+                    println!("Interval          : off");
+                } else {
+                    println!("Interval          : {}", format_duration(interval));
+                }
+                if settings.max_reconnects_per_minute == 0 {
+                    println!("Reconnects/minute : unlimited");
+                } else {
+                    println!("Reconnects/minute : {}", settings.max_reconnects_per_minute);
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+impl ReconnectDebounce {
+    async fn get_settings(rpc: &mut ManagementServiceClient) -> Result<types::ReconnectDebounce> {
+        Ok(rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .reconnect_debounce
+            .unwrap_or_default())
+    }
+}
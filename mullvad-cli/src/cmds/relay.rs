@@ -1,18 +1,46 @@
-use crate::{location, new_rpc_client, Command, Error, Result};
+use super::{
+    disconnect::{format_duration, parse_duration},
+    settings::relay_settings_update_from_constraints,
+};
+use crate::{
+    location, new_rpc_client,
+    relay_expression::{self, ProviderExpression, RelayExpression},
+    share::SharedConstraints,
+    Command, Error, Result,
+};
 use itertools::Itertools;
+use serde::Deserialize;
 use std::{
+    collections::HashMap,
     convert::TryFrom,
+    fs,
     io::{self, BufRead},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
     str::FromStr,
+    time::{Duration, SystemTime},
 };
 
-use mullvad_management_interface::{types, ManagementServiceClient};
-use mullvad_types::relay_constraints::{Constraint, RelaySettings};
+use mullvad_management_interface::{
+    types::{self, relay_selection_strategy},
+    ManagementServiceClient,
+};
+use mullvad_types::{
+    relay_constraints::{
+        hostname_matches, Constraint, ObfuscationSettings, RelaySettings, SelectedObfuscation,
+    },
+    relay_list::RelayOverride,
+};
 use talpid_types::net::all_of_the_internet;
 
 pub struct Relay;
 
+/// Contents of a file that is read by `relay override import`.
+#[derive(Deserialize)]
+struct RelayOverridesFile {
+    relay_overrides: Vec<RelayOverride>,
+}
+
 #[mullvad_management_interface::async_trait]
 impl Command for Relay {
     fn name(&self) -> &'static str {
@@ -66,6 +94,18 @@ impl Command for Relay {
                                         .long("v6-gateway")
                                         .takes_value(true),
                                 )
+                                .arg(
+                                    clap::Arg::new("entry location")
+                                        .help("Reach the server through a Mullvad relay. This can \
+                                               be 'any', 'none', or any location that is valid \
+                                               with 'set location', such as 'se got'. The server \
+                                               must then accept the public key and tunnel \
+                                               addresses of this device, which are used instead \
+                                               of the private key and addresses given here.")
+                                        .long("entry-location")
+                                        .min_values(1)
+                                        .max_values(3),
+                                )
                             )
                             .subcommand(clap::App::new("openvpn")
                                 .arg(
@@ -98,9 +138,51 @@ impl Command for Relay {
                             )
                     )
                     .subcommand(
-                        location::get_subcommand()
+                        clap::App::new("location")
                             .about("Set country or city to select relays from. Use the 'list' \
-                                   command to show available alternatives.")
+                                   command to show available alternatives. Give several \
+                                   countries, or locations written as \
+                                   <country>,<city>[,<hostname>], to select among all of them, \
+                                   such as 'se no fi' or 'se,got no'.")
+                            .arg(
+                                clap::Arg::new("location")
+                                    .help("<country> [<city> [<hostname>]], or several \
+                                          locations. The country may be 'any' for no \
+                                          preference.")
+                                    .required_unless_present("custom-list")
+                                    .conflicts_with("custom-list")
+                                    .multiple_values(true),
+                            )
+                            .arg(
+                                clap::Arg::new("custom-list")
+                                    .help("Select relays from the locations in a custom list \
+                                          instead. See 'mullvad custom-list'.")
+                                    .long("custom-list")
+                                    .takes_value(true),
+                            )
+                    )
+                    .subcommand(
+                        clap::App::new("location-set")
+                            .about("Further limit the locations to select exit relays from, in \
+                                   addition to 'location'. Locations are given as \
+                                   <country>[,<city>[,<hostname>]], such as 'se,sto'. Give \
+                                   neither option to remove the limit.")
+                            .arg(
+                                clap::Arg::new("include")
+                                    .help("Only select relays in one of these locations")
+                                    .long("include")
+                                    .takes_value(true)
+                                    .multiple_values(true)
+                                    .multiple_occurrences(true),
+                            )
+                            .arg(
+                                clap::Arg::new("exclude")
+                                    .help("Never select relays in these locations")
+                                    .long("exclude")
+                                    .takes_value(true)
+                                    .multiple_values(true)
+                                    .multiple_occurrences(true),
+                            ),
                     )
                     .subcommand(
                         clap::App::new("hostname")
@@ -108,7 +190,9 @@ impl Command for Relay {
                                 'location <country> <city> <hostname>'.")
                             .arg(
                                 clap::Arg::new("hostname")
-                                    .help("The hostname")
+                                    .help("The hostname. It may contain '*', which matches any \
+                                          characters, such as in 'se-got-wg-*'. Only relays in \
+                                          the city of the first matching relay are used.")
                                     .required(true),
                             ),
                     )
@@ -134,6 +218,16 @@ impl Command for Relay {
                                 .required(true)
                             )
                     )
+                    .subcommand(
+                        clap::App::new("datacenter")
+                            .about("Set the datacenter to select exit relays from. The 'list' \
+                                   command shows the datacenters that relays are hosted in.")
+                            .arg(
+                                clap::Arg::new("datacenter")
+                                .help("The datacenter to use, or 'any' for no preference.")
+                                .required(true)
+                            )
+                    )
                     .subcommand(
                         clap::App::new("tunnel")
                             .about("Set tunnel protocol-specific constraints.")
@@ -191,15 +285,194 @@ impl Command for Relay {
                                     .index(1)
                                     .possible_values(&["any", "wireguard", "openvpn", ]),
                                     )
-                                ),
+                                )
+                    .subcommand(
+                        clap::App::new("expr")
+                            .about("Set several constraints at once. Constraints that are not \
+                                   mentioned are left unchanged.")
+                            .arg(
+                                clap::Arg::new("expression")
+                                    .help(relay_expression::KEYS_HELP)
+                                    .multiple_values(true)
+                                    .required(true),
+                            ),
+                    ),
             )
             .subcommand(clap::App::new("get"))
             .subcommand(
-                clap::App::new("list").about("List available countries and cities"),
+                clap::App::new("selection-strategy")
+                    .about("Set how relays are chosen among the ones that match the constraints")
+                    .arg(
+                        clap::Arg::new("strategy")
+                            .help(
+                                "'weighted' picks relays at random, in proportion to their \
+                                capacity. 'lowest-latency' picks the relay that has been fastest \
+                                to connect to. 'round-robin' picks the relays in turn",
+                            )
+                            .required(true)
+                            .possible_values(&[
+                                "weighted",
+                                "random",
+                                "lowest-latency",
+                                "round-robin",
+                            ]),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("failure-cooldown")
+                    .about("Set for how long other relays are preferred over one that failed to \
+                           complete a handshake")
+                    .arg(
+                        clap::Arg::new("cooldown")
+                            .help("Duration such as 30s, 10m or 1h, or 'off'")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("list")
+                    .about("List available countries and cities")
+                    .arg(
+                        clap::Arg::new("provider")
+                            .help("Only list relays hosted by this provider. May be repeated")
+                            .long("provider")
+                            .takes_value(true)
+                            .multiple_occurrences(true),
+                    )
+                    .arg(
+                        clap::Arg::new("country")
+                            .help(
+                                "Only list relays in this country, given by its code or name. May \
+                                be repeated",
+                            )
+                            .long("country")
+                            .takes_value(true)
+                            .multiple_occurrences(true),
+                    )
+                    .arg(
+                        clap::Arg::new("ownership")
+                            .help("Only list relays with this ownership")
+                            .long("ownership")
+                            .takes_value(true)
+                            .possible_values(&["owned", "rented"]),
+                    )
+                    .arg(
+                        clap::Arg::new("owned")
+                            .help("Only list Mullvad-owned relays. Same as '--ownership owned'")
+                            .long("owned")
+                            .conflicts_with("ownership"),
+                    )
+                    .arg(
+                        clap::Arg::new("protocol")
+                            .help("Only list relays that support this tunnel protocol")
+                            .long("protocol")
+                            .takes_value(true)
+                            .possible_values(&["openvpn", "wireguard"]),
+                    )
+                    .arg(
+                        clap::Arg::new("include-inactive")
+                            .help("Also list relays that are currently inactive")
+                            .long("include-inactive"),
+                    )
+                    .arg(
+                        clap::Arg::new("sort")
+                            .help(
+                                "Order of the relays. 'hostname' and 'latency' list the relays \
+                                without grouping them by location. 'latency' is the average time \
+                                it has taken to connect to each relay, and relays that have not \
+                                been connected to are listed last",
+                            )
+                            .long("sort")
+                            .takes_value(true)
+                            .default_value("location")
+                            .possible_values(&["location", "hostname", "latency"]),
+                    ),
             )
             .subcommand(
                 clap::App::new("update")
-                    .about("Update the list of available countries and cities"),
+                    .about("Update the list of available countries and cities")
+                    .arg(
+                        clap::Arg::new("wait")
+                            .help("Wait until the update has finished, and report why it failed")
+                            .long("wait"),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("update-interval")
+                    .about("Set how often the list of relays is updated")
+                    .arg(
+                        clap::Arg::new("interval")
+                            .help("Interval such as 30m, 6h or 1d, from 15 minutes up to 7 days")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("info")
+                    .about("Display when the list of relays was last updated"),
+            )
+            .subcommand(
+                clap::App::new("export")
+                    .about("Print the relay constraints and obfuscation settings as a payload \
+                           that others can import"),
+            )
+            .subcommand(
+                clap::App::new("import")
+                    .about("Apply a payload created by 'relay export', after showing what it \
+                           contains")
+                    .arg(
+                        clap::Arg::new("payload")
+                            .required(true),
+                    )
+                    .arg(
+                        clap::Arg::new("yes")
+                            .help("Apply the payload without asking for confirmation")
+                            .long("yes")
+                            .short('y'),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("mirror")
+                    .about("Download the list of relays from a mirror instead of the API")
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Use a mirror that serves a relay list signed with the given key")
+                            .arg(
+                                clap::Arg::new("url")
+                                    .help("HTTPS URL of the relay list. The signature is fetched from the same URL with .sig appended")
+                                    .required(true),
+                            )
+                            .arg(
+                                clap::Arg::new("public key")
+                                    .help("Base64 encoded Ed25519 public key that the relay list is signed with")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("unset").about("Download the list of relays from the API"),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("override")
+                    .about("Connect to relays on other addresses than the ones in the relay list")
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("get").about("Show the relay address overrides"),
+                    )
+                    .subcommand(
+                        clap::App::new("import")
+                            .about("Import overrides from a JSON file of the form \
+                                   {\"relay_overrides\": [{\"hostname\": ..., \
+                                   \"ipv4_addr_in\": ..., \"ipv6_addr_in\": ...}]}. \
+                                   These replace any existing overrides for the same relays")
+                            .arg(
+                                clap::Arg::new("file")
+                                    .help("File to read the overrides from, or '-' to read from stdin")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("clear-all").about("Remove all relay address overrides"),
+                    ),
             )
     }
 
@@ -208,10 +481,64 @@ impl Command for Relay {
             self.set(set_matches).await
         } else if matches.subcommand_matches("get").is_some() {
             self.get().await
-        } else if matches.subcommand_matches("list").is_some() {
-            self.list().await
-        } else if matches.subcommand_matches("update").is_some() {
-            self.update().await
+        } else if let Some(strategy_matches) = matches.subcommand_matches("selection-strategy") {
+            let strategy = match strategy_matches.value_of("strategy").unwrap() {
+                "weighted" => relay_selection_strategy::Strategy::Weighted,
+                "random" => relay_selection_strategy::Strategy::Random,
+                "lowest-latency" => relay_selection_strategy::Strategy::LowestLatency,
+                "round-robin" => relay_selection_strategy::Strategy::RoundRobin,
+                _ => unreachable!("invalid strategy"),
+            };
+            self.set_selection_strategy(strategy).await
+        } else if let Some(cooldown_matches) = matches.subcommand_matches("failure-cooldown") {
+            let cooldown = match cooldown_matches.value_of("cooldown").unwrap() {
+                "off" => Duration::ZERO,
+                cooldown => parse_duration(cooldown).ok_or(Error::InvalidCommand(
+                    "Invalid cooldown. Use a number followed by s, m, h or d, or 'off'",
+                ))?,
+            };
+            self.set_failure_cooldown(cooldown).await
+        } else if let Some(list_matches) = matches.subcommand_matches("list") {
+            self.list(RelayListFilter::from_matches(list_matches)).await
+        } else if let Some(update_matches) = matches.subcommand_matches("update") {
+            self.update(update_matches.is_present("wait")).await
+        } else if let Some(interval_matches) = matches.subcommand_matches("update-interval") {
+            let interval = parse_duration(interval_matches.value_of("interval").unwrap()).ok_or(
+                Error::InvalidCommand("Invalid interval. Use a number followed by s, m, h or d"),
+            )?;
+            self.set_update_interval(interval).await
+        } else if matches.subcommand_matches("info").is_some() {
+            self.info().await
+        } else if matches.subcommand_matches("export").is_some() {
+            self.export().await
+        } else if let Some(import_matches) = matches.subcommand_matches("import") {
+            self.import(
+                import_matches.value_of("payload").unwrap(),
+                import_matches.is_present("yes"),
+            )
+            .await
+        } else if let Some(mirror_matches) = matches.subcommand_matches("mirror") {
+            match mirror_matches.subcommand() {
+                Some(("set", set_matches)) => {
+                    let url = set_matches.value_of("url").unwrap().to_owned();
+                    let public_key = base64::decode(set_matches.value_of("public key").unwrap())
+                        .map_err(|_| Error::InvalidCommand("Invalid public key"))?;
+                    self.set_mirror(types::RelayListMirror { url, public_key })
+                        .await
+                }
+                Some(("unset", _)) => self.unset_mirror().await,
+                _ => unreachable!("unhandled subcommand"),
+            }
+        } else if let Some(override_matches) = matches.subcommand_matches("override") {
+            match override_matches.subcommand() {
+                Some(("get", _)) => self.get_overrides().await,
+                Some(("import", import_matches)) => {
+                    self.import_overrides(Path::new(import_matches.value_of("file").unwrap()))
+                        .await
+                }
+                Some(("clear-all", _)) => self.clear_overrides().await,
+                _ => unreachable!("unhandled subcommand"),
+            }
         } else {
             unreachable!("No relay command given");
         }
@@ -233,12 +560,16 @@ impl Relay {
             self.set_custom(custom_matches).await
         } else if let Some(location_matches) = matches.subcommand_matches("location") {
             self.set_location(location_matches).await
+        } else if let Some(location_set_matches) = matches.subcommand_matches("location-set") {
+            self.set_location_set(location_set_matches).await
         } else if let Some(relay_matches) = matches.subcommand_matches("hostname") {
             self.set_hostname(relay_matches).await
         } else if let Some(providers_matches) = matches.subcommand_matches("provider") {
             self.set_providers(providers_matches).await
         } else if let Some(ownership_matches) = matches.subcommand_matches("ownership") {
             self.set_ownership(ownership_matches).await
+        } else if let Some(datacenter_matches) = matches.subcommand_matches("datacenter") {
+            self.set_datacenter(datacenter_matches).await
         } else if let Some(matches) = matches.subcommand_matches("tunnel") {
             if let Some(tunnel_matches) = matches.subcommand_matches("openvpn") {
                 self.set_openvpn_constraints(tunnel_matches).await
@@ -249,6 +580,8 @@ impl Relay {
             }
         } else if let Some(tunnel_matches) = matches.subcommand_matches("tunnel-protocol") {
             self.set_tunnel_protocol(tunnel_matches).await
+        } else if let Some(expression_matches) = matches.subcommand_matches("expr") {
+            self.set_expression(expression_matches).await
         } else {
             unreachable!("No set relay command given");
         }
@@ -289,6 +622,8 @@ impl Relay {
                     },
                 )),
             }),
+            use_entry_relay: false,
+            entry_location: None,
         }
     }
 
@@ -315,6 +650,9 @@ impl Relay {
         }
         let private_key = Self::validate_wireguard_key(&private_key_str);
         let peer_public_key = Self::validate_wireguard_key(&peer_key_str);
+        let entry_location = matches
+            .values_of("entry location")
+            .and_then(parse_entry_location_constraint);
 
         types::CustomRelaySettings {
             host,
@@ -345,6 +683,8 @@ impl Relay {
                     },
                 )),
             }),
+            use_entry_relay: entry_location.is_some(),
+            entry_location,
         }
     }
 
@@ -383,93 +723,144 @@ impl Relay {
         let hostname = matches.value_of("hostname").unwrap();
         let countries = Self::get_filtered_relays().await?;
 
-        let find_relay = || {
-            for country in countries {
-                for city in country.cities {
-                    for relay in city.relays {
-                        if relay.hostname.to_lowercase() == hostname.to_lowercase() {
-                            return Some(types::RelayLocation {
-                                country: country.code,
-                                city: city.code,
-                                hostname: relay.hostname,
-                            });
-                        }
-                    }
-                }
-            }
-            None
-        };
-
-        if let Some(location) = find_relay() {
+        if let Some(location) = find_relay_location(&countries, hostname) {
             println!(
                 "Setting location constraint to {} in {}, {}",
                 location.hostname, location.city, location.country
             );
+            let other_cities = countries
+                .iter()
+                .flat_map(|country| country.cities.iter().map(move |city| (country, city)))
+                .filter(|(country, city)| {
+                    (country.code != location.country || city.code != location.city)
+                        && city
+                            .relays
+                            .iter()
+                            .any(|relay| relay_hostname_matches(&location.hostname, relay))
+                })
+                .count();
+            if other_cities > 0 {
+                eprintln!(
+                    "Warning: The pattern also matches relays in {} other cities, which will not \
+                     be used",
+                    other_cities
+                );
+            }
 
-            self.update_constraints(types::RelaySettingsUpdate {
-                r#type: Some(types::relay_settings_update::Type::Normal(
-                    types::NormalRelaySettingsUpdate {
-                        location: Some(location),
-                        ..Default::default()
-                    },
-                )),
-            })
-            .await
+            let update = self.location_update(vec![location]).await?;
+            self.update_constraints(update).await
         } else {
             clap::Error::raw(clap::ErrorKind::ValueValidation, "No matching server found").exit()
         }
     }
 
     async fn set_location(&self, matches: &clap::ArgMatches) -> Result<()> {
-        let location_constraint = location::get_constraint_from_args(matches);
-        let mut found = false;
+        if let Some(name) = matches.value_of("custom-list") {
+            let location = types::RelayLocation {
+                custom_list: name.to_owned(),
+                ..Default::default()
+            };
+            let update = self.location_update(vec![location]).await?;
+            return self.update_constraints(update).await;
+        }
+
+        let values: Vec<&str> = matches.values_of("location").unwrap().collect();
+        let locations = parse_location_args(&values)?;
 
-        if !location_constraint.country.is_empty() {
+        if locations
+            .iter()
+            .any(|location| !location.country.is_empty())
+        {
             // TODO: `mullvad_types::relay_constraints::LocationConstraint::matches(&relay)`
             //       could be used to guarantee consistency with the daemon.
             let countries = Self::get_filtered_relays().await?;
-            for country in &countries {
-                if country.code != location_constraint.country {
-                    continue;
-                }
-
-                if location_constraint.city.is_empty() {
-                    found = true;
-                    break;
-                }
-
-                for city in &country.cities {
-                    if city.code != location_constraint.city {
-                        continue;
+            for location in &locations {
+                if !location.country.is_empty() && !relay_location_exists(&countries, location) {
+                    if locations.len() == 1 {
+                        eprintln!("Warning: No matching relay was found.");
+                    } else {
+                        eprintln!(
+                            "Warning: No matching relay was found in {}",
+                            [&location.country, &location.city, &location.hostname]
+                                .iter()
+                                .filter(|part| !part.is_empty())
+                                .join(", ")
+                        );
                     }
+                }
+            }
+        }
 
-                    if location_constraint.hostname.is_empty() {
-                        found = true;
-                        break;
-                    }
+        let update = self.location_update(locations).await?;
+        self.update_constraints(update).await
+    }
 
-                    for relay in &city.relays {
-                        if relay.hostname != location_constraint.hostname {
-                            continue;
-                        }
-                        found = true;
-                        break;
-                    }
+    /// Returns an update that selects relays from any of `locations`. A single location replaces
+    /// the location constraint, while several locations are stored as the locations to include
+    /// in the location set. Locations that are excluded by the location set remain excluded.
+    async fn location_update(
+        &self,
+        mut locations: Vec<types::RelayLocation>,
+    ) -> Result<types::RelaySettingsUpdate> {
+        let mut rpc = new_rpc_client().await?;
+        let mut location_set = self.get_location_set(&mut rpc).await?;
+        let location = if locations.len() == 1 {
+            location_set.include.clear();
+            locations.pop().unwrap()
+        } else {
+            location_set.include = locations;
+            types::RelayLocation::default()
+        };
 
-                    break;
-                }
-                break;
-            }
+        Ok(types::RelaySettingsUpdate {
+            r#type: Some(types::relay_settings_update::Type::Normal(
+                types::NormalRelaySettingsUpdate {
+                    location: Some(location),
+                    location_set: Some(location_set),
+                    ..Default::default()
+                },
+            )),
+        })
+    }
 
-            if !found {
-                eprintln!("Warning: No matching relay was found.");
+    async fn get_location_set(
+        &self,
+        rpc: &mut ManagementServiceClient,
+    ) -> Result<types::LocationSet> {
+        match rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .relay_settings
+            .unwrap()
+            .endpoint
+            .unwrap()
+        {
+            types::relay_settings::Endpoint::Normal(settings) => {
+                Ok(settings.location_set.unwrap_or_default())
             }
+            types::relay_settings::Endpoint::Custom(_settings) => Ok(types::LocationSet::default()),
         }
+    }
+
+    async fn set_location_set(&self, matches: &clap::ArgMatches) -> Result<()> {
+        let parse_locations = |name| {
+            matches
+                .values_of(name)
+                .into_iter()
+                .flatten()
+                .map(parse_location_set_item)
+                .collect::<Result<Vec<_>>>()
+        };
+        let location_set = types::LocationSet {
+            include: parse_locations("include")?,
+            exclude: parse_locations("exclude")?,
+        };
 
         self.update_constraints(types::RelaySettingsUpdate {
             r#type: Some(types::relay_settings_update::Type::Normal(
                 types::NormalRelaySettingsUpdate {
-                    location: Some(location_constraint),
+                    location_set: Some(location_set),
                     ..Default::default()
                 },
             )),
@@ -511,6 +902,34 @@ impl Relay {
         .await
     }
 
+    async fn set_datacenter(&self, matches: &clap::ArgMatches) -> Result<()> {
+        let datacenter = match matches.value_of("datacenter").unwrap() {
+            "any" => String::new(),
+            datacenter => {
+                let countries = Self::get_filtered_relays().await?;
+                let found = countries
+                    .iter()
+                    .flat_map(|country| &country.cities)
+                    .flat_map(|city| &city.relays)
+                    .any(|relay| relay.datacenter.eq_ignore_ascii_case(datacenter));
+                if !found {
+                    eprintln!("Warning: No relay was found in that datacenter.");
+                }
+                datacenter.to_owned()
+            }
+        };
+
+        self.update_constraints(types::RelaySettingsUpdate {
+            r#type: Some(types::relay_settings_update::Type::Normal(
+                types::NormalRelaySettingsUpdate {
+                    datacenter: Some(types::DatacenterUpdate { datacenter }),
+                    ..Default::default()
+                },
+            )),
+        })
+        .await
+    }
+
     async fn set_openvpn_constraints(&self, matches: &clap::ArgMatches) -> Result<()> {
         let mut openvpn_constraints = {
             let mut rpc = new_rpc_client().await?;
@@ -649,25 +1068,285 @@ impl Relay {
         .await
     }
 
-    async fn get(&self) -> Result<()> {
+    async fn set_expression(&self, matches: &clap::ArgMatches) -> Result<()> {
+        let expression: Vec<String> = matches.values_of_t_or_exit("expression");
+        let expression = RelayExpression::parse(&expression.join(" "))?;
+
         let mut rpc = new_rpc_client().await?;
-        let relay_settings = rpc
+        let current = match rpc
             .get_settings(())
             .await?
             .into_inner()
             .relay_settings
-            .unwrap();
+            .unwrap()
+            .endpoint
+            .unwrap()
+        {
+            types::relay_settings::Endpoint::Normal(settings) => settings,
+            types::relay_settings::Endpoint::Custom(_settings) => {
+                println!("Clearing custom tunnel constraints");
+                types::NormalRelaySettings::default()
+            }
+        };
+
+        let needs_relays = expression.hostname.is_some()
+            || expression.datacenter.is_some()
+            || matches!(expression.providers, Some(ProviderExpression::Except(_)));
+        let countries = if needs_relays {
+            Self::get_filtered_relays().await?
+        } else {
+            vec![]
+        };
+        let relays = || {
+            countries
+                .iter()
+                .flat_map(|country| &country.cities)
+                .flat_map(|city| &city.relays)
+        };
+
+        let location = if let Some(hostname) = &expression.hostname {
+            let location = find_relay_location(&countries, hostname)
+                .ok_or(Error::CommandFailed("No matching server found"))?;
+            let in_country = expression
+                .country
+                .as_ref()
+                .map_or(true, |country| *country == location.country);
+            let in_city = expression
+                .city
+                .as_ref()
+                .map_or(true, |city| *city == location.city);
+            if !in_country || !in_city {
+                return Err(Error::InvalidExpression(format!(
+                    "{} is not in the given location",
+                    location.hostname
+                )));
+            }
+            Some(location)
+        } else {
+            expression.country.as_ref().map(|country| {
+                location::get_constraint(country.as_str(), expression.city.as_deref(), None)
+            })
+        };
+
+        let providers = match expression.providers {
+            Some(ProviderExpression::Only(providers)) => Some(providers),
+            Some(ProviderExpression::Except(excluded)) => {
+                let is_excluded = |provider: &str| {
+                    excluded
+                        .iter()
+                        .any(|excluded| excluded.eq_ignore_ascii_case(provider))
+                };
+                for excluded in &excluded {
+                    if !relays().any(|relay| relay.provider.eq_ignore_ascii_case(excluded)) {
+                        eprintln!("Warning: No relay is hosted by {}.", excluded);
+                    }
+                }
+                let providers: Vec<String> = relays()
+                    .map(|relay| relay.provider.clone())
+                    .filter(|provider| !is_excluded(provider))
+                    .unique()
+                    .collect();
+                if providers.is_empty() {
+                    return Err(Error::CommandFailed("Every provider was excluded"));
+                }
+                Some(providers)
+            }
+            None => None,
+        };
+
+        if let Some(datacenter) = &expression.datacenter {
+            if datacenter != "any"
+                && !relays().any(|relay| relay.datacenter.eq_ignore_ascii_case(datacenter))
+            {
+                eprintln!("Warning: No relay was found in that datacenter.");
+            }
+        }
+
+        // A port applies to the tunnel protocol in the expression, or else the current one.
+        let tunnel_type = match expression.tunnel_type {
+            Some(tunnel_type) => tunnel_type,
+            None => current
+                .tunnel_type
+                .as_ref()
+                .and_then(|constraint| types::TunnelType::from_i32(constraint.tunnel_type))
+                .map(Constraint::Only)
+                .unwrap_or(Constraint::Any),
+        };
+        let (wireguard_port, openvpn_port) = match (expression.port, tunnel_type) {
+            (None, _) => (None, None),
+            (Some(Constraint::Any), Constraint::Any) => {
+                (Some(Constraint::Any), Some(Constraint::Any))
+            }
+            (Some(port), Constraint::Only(types::TunnelType::Wireguard)) => (Some(port), None),
+            (Some(port), Constraint::Only(types::TunnelType::Openvpn)) => (None, Some(port)),
+            (Some(Constraint::Only(_)), Constraint::Any) => {
+                return Err(Error::InvalidCommand(
+                    "a tunnel protocol must be given to select a specific port",
+                ))
+            }
+        };
+
+        let wireguard_constraints = if wireguard_port.is_some() || expression.ip_version.is_some() {
+            let mut constraints = current.wireguard_constraints.unwrap_or_default();
+            if let Some(port) = wireguard_port {
+                constraints.port = port.option().map(u32::from).unwrap_or(0);
+            }
+            if let Some(ip_version) = expression.ip_version {
+                constraints.ip_version =
+                    ip_version
+                        .option()
+                        .map(|protocol| types::IpVersionConstraint {
+                            protocol: protocol as i32,
+                        });
+            }
+            Some(constraints)
+        } else {
+            None
+        };
+
+        let openvpn_constraints =
+            if openvpn_port.is_some() || expression.transport_protocol.is_some() {
+                let current_port = current.openvpn_constraints.unwrap_or_default().port;
+                let protocol = expression.transport_protocol.unwrap_or_else(|| {
+                    current_port
+                        .as_ref()
+                        .and_then(|port| types::TransportProtocol::from_i32(port.protocol))
+                        .map(Constraint::Only)
+                        .unwrap_or(Constraint::Any)
+                });
+                let port = openvpn_port.unwrap_or_else(|| {
+                    current_port
+                        .as_ref()
+                        .filter(|port| port.port != 0 && protocol.is_only())
+                        .map(|port| Constraint::Only(port.port as u16))
+                        .unwrap_or(Constraint::Any)
+                });
+                let port = match (port, protocol) {
+                    (Constraint::Any, Constraint::Any) => None,
+                    (port, Constraint::Only(protocol)) => Some(types::TransportPort {
+                        protocol: protocol as i32,
+                        port: port.option().map(u32::from).unwrap_or(0),
+                    }),
+                    (Constraint::Only(_), Constraint::Any) => {
+                        return Err(Error::InvalidCommand(
+                            "a transport protocol must be given to select a specific port",
+                        ))
+                    }
+                };
+                Some(types::OpenvpnConstraints { port })
+            } else {
+                None
+            };
+
+        self.update_constraints(types::RelaySettingsUpdate {
+            r#type: Some(types::relay_settings_update::Type::Normal(
+                types::NormalRelaySettingsUpdate {
+                    location,
+                    providers: providers.map(|providers| types::ProviderUpdate { providers }),
+                    ownership: expression
+                        .ownership
+                        .map(|ownership| types::OwnershipUpdate {
+                            ownership: ownership as i32,
+                        }),
+                    datacenter: expression
+                        .datacenter
+                        .map(|datacenter| types::DatacenterUpdate {
+                            datacenter: if datacenter == "any" {
+                                String::new()
+                            } else {
+                                datacenter
+                            },
+                        }),
+                    tunnel_type: expression.tunnel_type.map(|tunnel_type| {
+                        types::TunnelTypeUpdate {
+                            tunnel_type: tunnel_type.option().map(|tunnel_type| {
+                                types::TunnelTypeConstraint {
+                                    tunnel_type: tunnel_type as i32,
+                                }
+                            }),
+                        }
+                    }),
+                    wireguard_constraints,
+                    openvpn_constraints,
+                },
+            )),
+        })
+        .await
+    }
+
+    async fn get(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let settings = rpc.get_settings(()).await?.into_inner();
 
         println!(
             "Current constraints: {}",
-            RelaySettings::try_from(relay_settings).unwrap()
+            RelaySettings::try_from(settings.relay_settings.unwrap()).unwrap()
+        );
+        let strategy = settings
+            .relay_selection_strategy
+            .and_then(|strategy| relay_selection_strategy::Strategy::from_i32(strategy.strategy))
+            .unwrap_or(relay_selection_strategy::Strategy::Weighted);
+        println!(
+            "Selection strategy: {}",
+            match strategy {
+                relay_selection_strategy::Strategy::Weighted => "weighted",
+                relay_selection_strategy::Strategy::Random => "random",
+                relay_selection_strategy::Strategy::LowestLatency => "lowest-latency",
+                relay_selection_strategy::Strategy::RoundRobin => "round-robin",
+            }
         );
+        let cooldown = settings
+            .relay_failure_cooldown
+            .and_then(|cooldown| Duration::try_from(cooldown).ok())
+            .unwrap_or(Duration::ZERO);
+        if cooldown.is_zero() {
+            println!("Failure cooldown: off");
+        } else {
+            println!("Failure cooldown: {}", format_duration(cooldown));
+        }
+
+        Ok(())
+    }
+
+    async fn set_failure_cooldown(&self, cooldown: Duration) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .set_relay_failure_cooldown(types::Duration::from(cooldown))
+            .await?;
+        println!("Updated the relay failure cooldown");
+        Ok(())
+    }
 
+    async fn set_selection_strategy(
+        &self,
+        strategy: relay_selection_strategy::Strategy,
+    ) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .set_relay_selection_strategy(types::RelaySelectionStrategy {
+                strategy: i32::from(strategy),
+            })
+            .await?;
+        println!("Updated the relay selection strategy");
         Ok(())
     }
 
-    async fn list(&self) -> Result<()> {
-        let mut countries = Self::get_filtered_relays().await?;
+    async fn list(&self, filter: RelayListFilter) -> Result<()> {
+        let mut countries = Self::get_relays(|relay| filter.matches(relay)).await?;
+        countries.retain(|country| filter.country_matches(country));
+        if countries.is_empty() {
+            println!("No relays match the filter");
+        }
+        match filter.sort.as_str() {
+            "hostname" | "latency" => Self::list_flat(countries, filter.sort == "latency").await,
+            _ => {
+                Self::list_by_location(countries);
+                Ok(())
+            }
+        }
+    }
+
+    fn list_by_location(mut countries: Vec<types::RelayListCountry>) {
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
         for mut country in countries {
             country
@@ -682,45 +1361,269 @@ impl Relay {
                     city.name, city.code, city.latitude, city.longitude
                 );
                 for relay in &city.relays {
-                    let tunnels = relay.tunnels.as_ref().unwrap();
-                    let supports_openvpn = !tunnels.openvpn.is_empty();
-                    let supports_wireguard = !tunnels.wireguard.is_empty();
-                    let support_msg = match (supports_openvpn, supports_wireguard) {
-                        (true, true) => "OpenVPN and WireGuard",
-                        (true, false) => "OpenVPN",
-                        (false, true) => "WireGuard",
-                        _ => unreachable!("Bug in relay filtering earlier on"),
-                    };
-                    let ownership = if relay.owned {
-                        "Mullvad-owned"
-                    } else {
-                        "rented"
-                    };
-                    let mut addresses = vec![&relay.ipv4_addr_in];
-                    if !relay.ipv6_addr_in.is_empty() {
-                        addresses.push(&relay.ipv6_addr_in);
-                    }
-                    println!(
-                        "\t\t{} ({}) - {}, hosted by {} ({ownership})",
-                        relay.hostname,
-                        addresses.iter().join(", "),
-                        support_msg,
-                        relay.provider
-                    );
+                    println!("\t\t{}", format_relay(relay));
                 }
             }
             println!();
         }
+    }
+
+    /// Lists the relays one per line, ordered by hostname or by how long it has taken to connect
+    /// to them.
+    async fn list_flat(countries: Vec<types::RelayListCountry>, by_latency: bool) -> Result<()> {
+        let latencies: HashMap<String, Duration> = if by_latency {
+            new_rpc_client()
+                .await?
+                .get_relay_stats(())
+                .await?
+                .into_inner()
+                .relays
+                .into_iter()
+                .filter_map(|stats| {
+                    let latency = stats.average_handshake_time?;
+                    let latency = Duration::new(latency.seconds as u64, latency.nanos as u32);
+                    Some((stats.hostname, latency))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let mut relays: Vec<(String, types::Relay)> = countries
+            .into_iter()
+            .flat_map(|country| {
+                let country_name = country.name;
+                country.cities.into_iter().flat_map(move |city| {
+                    let location = format!("{}, {}", city.name, country_name);
+                    city.relays
+                        .into_iter()
+                        .map(move |relay| (location.clone(), relay))
+                })
+            })
+            .collect();
+        relays.sort_by(|(_, r1), (_, r2)| {
+            let l1 = latencies.get(&r1.hostname);
+            let l2 = latencies.get(&r2.hostname);
+            // Relays without a latency are listed last
+            l1.is_none()
+                .cmp(&l2.is_none())
+                .then_with(|| l1.cmp(&l2))
+                .then_with(|| natord::compare_ignore_case(&r1.hostname, &r2.hostname))
+        });
+
+        for (location, relay) in &relays {
+            let latency = if by_latency {
+                match latencies.get(&relay.hostname) {
+                    Some(latency) => format!(" - {} ms", latency.as_millis()),
+                    None => " - no connections".to_owned(),
+                }
+            } else {
+                String::new()
+            };
+            println!("{} in {}{}", format_relay(relay), location, latency);
+        }
+        Ok(())
+    }
+
+    async fn update(&self, wait: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        if wait {
+            println!("Updating relay list...");
+            rpc.update_relay_locations_and_wait(())
+                .await
+                .map_err(|error| Error::RpcFailedExt("Failed to update the relay list", error))?;
+            println!("Relay list updated");
+        } else {
+            rpc.update_relay_locations(()).await?;
+            println!("Updating relay list in the background...");
+        }
+        Ok(())
+    }
+
+    async fn set_update_interval(&self, interval: Duration) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .set_relay_list_update_interval(types::Duration::from(interval))
+            .await?;
+        println!("Updated the relay list update interval");
+        Ok(())
+    }
+
+    async fn info(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let info = rpc.get_relay_list_info(()).await?.into_inner();
+        let settings = rpc.get_settings(()).await?.into_inner();
+
+        let last_updated = info
+            .last_updated
+            .and_then(|last_updated| SystemTime::try_from(last_updated).ok())
+            .map(|last_updated| {
+                chrono::DateTime::<chrono::Local>::from(last_updated)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "unknown".to_owned());
+        println!("Last updated: {}", last_updated);
+        if info.etag.is_empty() {
+            println!("Version: unknown");
+        } else {
+            println!("Version: {}", info.etag);
+        }
+        if let Some(interval) = settings
+            .relay_list_update_interval
+            .and_then(|interval| Duration::try_from(interval).ok())
+        {
+            println!("Update interval: {}", format_duration(interval));
+        }
+        match settings.relay_list_mirror {
+            Some(mirror) => println!("Source: {}", mirror.url),
+            None => println!("Source: API"),
+        }
+        Ok(())
+    }
+
+    async fn export(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let settings = rpc.get_settings(()).await?.into_inner();
+        let relay_constraints =
+            match RelaySettings::try_from(settings.relay_settings.unwrap()).unwrap() {
+                RelaySettings::Normal(constraints) => constraints,
+                RelaySettings::CustomTunnelEndpoint(_) => {
+                    return Err(Error::CommandFailed(
+                        "A custom tunnel endpoint cannot be exported",
+                    ))
+                }
+            };
+        let obfuscation =
+            ObfuscationSettings::try_from(settings.obfuscation_settings.unwrap()).unwrap();
+        if obfuscation.selected_obfuscation == SelectedObfuscation::External {
+            return Err(Error::CommandFailed(
+                "Settings that use an external transport cannot be exported",
+            ));
+        }
+
+        println!(
+            "{}",
+            SharedConstraints::new(relay_constraints, obfuscation).encode()
+        );
+        Ok(())
+    }
+
+    async fn import(&self, payload: &str, skip_confirmation: bool) -> Result<()> {
+        let shared = SharedConstraints::decode(payload)?;
+        println!("{}", shared);
+
+        if !skip_confirmation && !receive_confirmation("Apply these settings? [y/N] ") {
+            println!("Nothing was changed");
+            return Ok(());
+        }
+
+        let mut rpc = new_rpc_client().await?;
+        // The external transport of this machine is kept, since it is not part of the payload.
+        let current_obfuscation = ObfuscationSettings::try_from(
+            rpc.get_settings(())
+                .await?
+                .into_inner()
+                .obfuscation_settings
+                .unwrap(),
+        )
+        .unwrap();
+        let obfuscation = ObfuscationSettings {
+            external: current_obfuscation.external,
+            ..shared.obfuscation
+        };
+
+        rpc.update_relay_settings(relay_settings_update_from_constraints(
+            shared.relay_constraints,
+        ))
+        .await
+        .map_err(|error| Error::RpcFailedExt("Failed to update relay settings", error))?;
+        rpc.set_obfuscation_settings(types::ObfuscationSettings::from(&obfuscation))
+            .await?;
+        println!("Imported relay constraints and obfuscation settings");
+        Ok(())
+    }
+
+    async fn set_mirror(&self, mirror: types::RelayListMirror) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .set_relay_list_mirror(mirror)
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to set the relay list mirror", error))?;
+        println!("Downloading the relay list from the mirror");
+        Ok(())
+    }
+
+    async fn unset_mirror(&self) -> Result<()> {
+        new_rpc_client().await?.clear_relay_list_mirror(()).await?;
+        println!("Downloading the relay list from the API");
+        Ok(())
+    }
+
+    async fn get_overrides(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let settings = rpc.get_settings(()).await?.into_inner();
+        if settings.relay_overrides.is_empty() {
+            println!("No relay overrides");
+        }
+        for relay_override in settings.relay_overrides {
+            println!("{}", relay_override.hostname);
+            if !relay_override.ipv4_addr_in.is_empty() {
+                println!("\tIPv4: {}", relay_override.ipv4_addr_in);
+            }
+            if !relay_override.ipv6_addr_in.is_empty() {
+                println!("\tIPv6: {}", relay_override.ipv6_addr_in);
+            }
+        }
         Ok(())
     }
 
-    async fn update(&self) -> Result<()> {
-        new_rpc_client().await?.update_relay_locations(()).await?;
-        println!("Updating relay list in the background...");
+    async fn import_overrides(&self, path: &Path) -> Result<()> {
+        let contents = if path == Path::new("-") {
+            let mut contents = String::new();
+            io::Read::read_to_string(&mut io::stdin(), &mut contents)
+                .map_err(Error::RelayOverridesIo)?;
+            contents
+        } else {
+            fs::read_to_string(path).map_err(Error::RelayOverridesIo)?
+        };
+        let file: RelayOverridesFile =
+            serde_json::from_str(&contents).map_err(Error::InvalidRelayOverrides)?;
+        let count = file.relay_overrides.len();
+
+        new_rpc_client()
+            .await?
+            .import_relay_overrides(types::RelayOverrideList {
+                overrides: file
+                    .relay_overrides
+                    .into_iter()
+                    .map(types::RelayOverride::from)
+                    .collect(),
+            })
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to import the relay overrides", error))?;
+        println!("Imported {} relay overrides", count);
+        Ok(())
+    }
+
+    async fn clear_overrides(&self) -> Result<()> {
+        new_rpc_client()
+            .await?
+            .clear_all_relay_overrides(())
+            .await?;
+        println!("Removed all relay overrides");
         Ok(())
     }
 
     async fn get_filtered_relays() -> Result<Vec<types::RelayListCountry>> {
+        Self::get_relays(|relay| relay.active).await
+    }
+
+    /// Returns the countries and cities with relays that `filter` accepts. Relays that support
+    /// neither OpenVPN nor WireGuard are never included.
+    async fn get_relays(
+        filter: impl Fn(&types::Relay) -> bool,
+    ) -> Result<Vec<types::RelayListCountry>> {
         let mut rpc = new_rpc_client().await?;
         let mut locations = rpc
             .get_relay_locations(())
@@ -736,10 +1639,9 @@ impl Relay {
                 .into_iter()
                 .filter_map(|mut city| {
                     city.relays.retain(|relay| {
-                        relay.active
-                            && relay.tunnels.is_some()
-                            && !(relay.tunnels.as_ref().unwrap().openvpn.is_empty()
-                                && relay.tunnels.as_ref().unwrap().wireguard.is_empty())
+                        relay.tunnels.as_ref().map_or(false, |tunnels| {
+                            !(tunnels.openvpn.is_empty() && tunnels.wireguard.is_empty())
+                        }) && filter(relay)
                     });
                     if !city.relays.is_empty() {
                         Some(city)
@@ -757,6 +1659,46 @@ impl Relay {
     }
 }
 
+fn receive_confirmation(prompt: &str) -> bool {
+    print!("{}", prompt);
+    let _ = io::Write::flush(&mut io::stdout());
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"),
+        Err(error) => {
+            eprintln!("Couldn't read from STDIN: {}", error);
+            false
+        }
+    }
+}
+
+fn find_relay_location(
+    countries: &[types::RelayListCountry],
+    hostname: &str,
+) -> Option<types::RelayLocation> {
+    for country in countries {
+        for city in &country.cities {
+            for relay in &city.relays {
+                if relay_hostname_matches(hostname, relay) {
+                    // A pattern is kept as it is, so that it matches every relay in the city
+                    let hostname = if hostname.contains('*') {
+                        hostname.to_lowercase()
+                    } else {
+                        relay.hostname.clone()
+                    };
+                    return Some(types::RelayLocation {
+                        country: country.code.clone(),
+                        city: city.code.clone(),
+                        hostname,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+    None
+}
+
 fn parse_port_constraint(raw_port: &str) -> Result<Constraint<u16>> {
     match raw_port.to_lowercase().as_str() {
         "any" => Ok(Constraint::Any),
@@ -800,6 +1742,207 @@ fn parse_entry_location_constraint<'a, T: Iterator<Item = &'a str>>(
     ))
 }
 
+/// Returns whether the hostname of `relay` matches `hostname`, which may contain `*`.
+fn relay_hostname_matches(hostname: &str, relay: &types::Relay) -> bool {
+    hostname_matches(&hostname.to_lowercase(), &relay.hostname.to_lowercase())
+}
+
+fn relay_location_exists(
+    countries: &[types::RelayListCountry],
+    location: &types::RelayLocation,
+) -> bool {
+    countries
+        .iter()
+        .filter(|country| country.code == location.country)
+        .any(|country| {
+            location.city.is_empty()
+                || country
+                    .cities
+                    .iter()
+                    .filter(|city| city.code == location.city)
+                    .any(|city| {
+                        location.hostname.is_empty()
+                            || city
+                                .relays
+                                .iter()
+                                .any(|relay| relay_hostname_matches(&location.hostname, relay))
+                    })
+        })
+}
+
+/// Restricts which relays `relay list` shows.
+struct RelayListFilter {
+    providers: Vec<String>,
+    countries: Vec<String>,
+    owned: Option<bool>,
+    protocol: Option<String>,
+    include_inactive: bool,
+    sort: String,
+}
+
+impl RelayListFilter {
+    fn from_matches(matches: &clap::ArgMatches) -> Self {
+        RelayListFilter {
+            providers: matches
+                .values_of("provider")
+                .map(|providers| providers.map(str::to_owned).collect())
+                .unwrap_or_default(),
+            countries: matches
+                .values_of("country")
+                .map(|countries| countries.map(str::to_owned).collect())
+                .unwrap_or_default(),
+            owned: if matches.is_present("owned") {
+                Some(true)
+            } else {
+                matches
+                    .value_of("ownership")
+                    .map(|ownership| ownership == "owned")
+            },
+            protocol: matches.value_of("protocol").map(str::to_owned),
+            include_inactive: matches.is_present("include-inactive"),
+            sort: matches.value_of("sort").unwrap_or("location").to_owned(),
+        }
+    }
+
+    fn country_matches(&self, country: &types::RelayListCountry) -> bool {
+        self.countries.is_empty()
+            || self.countries.iter().any(|filter| {
+                filter.eq_ignore_ascii_case(&country.code)
+                    || filter.eq_ignore_ascii_case(&country.name)
+            })
+    }
+
+    fn matches(&self, relay: &types::Relay) -> bool {
+        let tunnels = relay.tunnels.clone().unwrap_or_default();
+        let provider_matches = self.providers.is_empty()
+            || self.providers.iter().any(|provider| {
+                provider.eq_ignore_ascii_case(&relay.provider)
+                    || provider.eq_ignore_ascii_case(&relay.provider_name)
+            });
+        let protocol_matches = match self.protocol.as_deref() {
+            Some("openvpn") => !tunnels.openvpn.is_empty(),
+            Some("wireguard") => !tunnels.wireguard.is_empty(),
+            _ => true,
+        };
+        (self.include_inactive || relay.active)
+            && provider_matches
+            && self.owned.map_or(true, |owned| owned == relay.owned)
+            && protocol_matches
+    }
+}
+
+/// Describes a relay on a single line: its addresses, protocols, provider and ownership.
+fn format_relay(relay: &types::Relay) -> String {
+    let support_msg = relay_protocols(relay).join(", ");
+    let ownership = if relay.owned {
+        "Mullvad-owned"
+    } else {
+        "rented"
+    };
+    let mut addresses = vec![&relay.ipv4_addr_in];
+    if !relay.ipv6_addr_in.is_empty() {
+        addresses.push(&relay.ipv6_addr_in);
+    }
+    // The provider ID is what 'set provider' expects, so show it next to the name.
+    let provider = if relay.provider_name.is_empty() || relay.provider_name == relay.provider {
+        relay.provider.clone()
+    } else {
+        format!("{} [{}]", relay.provider_name, relay.provider)
+    };
+    let mut details = vec![ownership.to_owned()];
+    if !relay.datacenter.is_empty() {
+        details.push(format!("datacenter {}", relay.datacenter));
+    }
+    let status = if relay.active { "" } else { " [inactive]" };
+    format!(
+        "{}{} ({}) - {}, hosted by {} ({})",
+        relay.hostname,
+        status,
+        addresses.iter().join(", "),
+        support_msg,
+        provider,
+        details.join(", "),
+    )
+}
+
+/// Returns the tunnel protocols, bridges and obfuscation methods that `relay` supports.
+fn relay_protocols(relay: &types::Relay) -> Vec<&'static str> {
+    let tunnels = relay.tunnels.clone().unwrap_or_default();
+    let bridges = relay.bridges.clone().unwrap_or_default();
+    let obfuscators = relay.obfuscators.clone().unwrap_or_default();
+    let mut protocols = vec![];
+    if !tunnels.openvpn.is_empty() {
+        protocols.push("OpenVPN");
+    }
+    if !tunnels.wireguard.is_empty() {
+        protocols.push("WireGuard");
+    }
+    if !bridges.shadowsocks.is_empty() {
+        protocols.push("Shadowsocks bridge");
+    }
+    if !obfuscators.udp2tcp_ports.is_empty() {
+        protocols.push("UDP-over-TCP");
+    }
+    if !obfuscators.wss_ports.is_empty() {
+        protocols.push("WebSocket over TLS");
+    }
+    protocols
+}
+
+/// Parses the arguments of `relay set location`. They are either a single location given as
+/// `<country> [<city> [<hostname>]]`, or several locations, such as `se no` or `se,got no`.
+fn parse_location_args(values: &[&str]) -> Result<Vec<types::RelayLocation>> {
+    let is_list = values.iter().any(|value| value.contains(','))
+        || values.get(1).map(|value| value.len() == 2).unwrap_or(false);
+    if is_list {
+        return values
+            .iter()
+            .map(|value| parse_location_set_item(value))
+            .collect();
+    }
+
+    if values.len() > 3 {
+        return Err(Error::InvalidCommand(
+            "A location is given as <country> [<city> [<hostname>]]",
+        ));
+    }
+    location::country_code_validator(values[0])
+        .map_err(|_| Error::InvalidCommand("Country codes must be two letters, or 'any'"))?;
+    if let Some(city) = values.get(1) {
+        location::city_code_validator(city)
+            .map_err(|_| Error::InvalidCommand("City codes must be three letters"))?;
+    }
+    Ok(vec![location::get_constraint(
+        values[0],
+        values.get(1).copied(),
+        values.get(2).copied(),
+    )])
+}
+
+/// Parses a location of a location set, such as `se,sto`.
+pub fn parse_location_set_item(location: &str) -> Result<types::RelayLocation> {
+    let mut parts = location.split(',').map(str::trim);
+    let country = parts.next().unwrap_or_default();
+    if country == "any" || location::country_code_validator(country).is_err() {
+        return Err(Error::InvalidCommand(
+            "Locations must start with a two letter country code",
+        ));
+    }
+    let city = parts.next();
+    if let Some(city) = city {
+        if location::city_code_validator(city).is_err() {
+            return Err(Error::InvalidCommand("City codes must be three letters"));
+        }
+    }
+    let hostname = parts.next();
+    if parts.next().is_some() {
+        return Err(Error::InvalidCommand(
+            "Locations must be given as <country>[,<city>[,<hostname>]]",
+        ));
+    }
+    Ok(location::get_constraint(country, city, hostname))
+}
+
 fn parse_transport_port(
     matches: &clap::ArgMatches,
     current_constraint: &mut Option<types::TransportPort>,
@@ -0,0 +1,104 @@
+use crate::{new_rpc_client, Command, Result};
+use mullvad_management_interface::types;
+
+pub struct RelayStats;
+
+#[mullvad_management_interface::async_trait]
+impl Command for RelayStats {
+    fn name(&self) -> &'static str {
+        "relay-stats"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Show how well connections to each relay have worked, and control whether \
+                relays that keep failing are avoided",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("get")
+                    .about("Display the statistics and whether bad relays are avoided"),
+            )
+            .subcommand(
+                clap::App::new("set")
+                    .about(
+                        "Change whether bad relays are avoided, as long as other relays match \
+                        the relay settings",
+                    )
+                    .arg(
+                        clap::Arg::new("policy")
+                            .required(true)
+                            .possible_values(&["on", "off"]),
+                    ),
+            )
+            .subcommand(clap::App::new("clear").about("Forget the statistics of all relays"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("get", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let settings = rpc.get_settings(()).await?.into_inner();
+                let enabled_str = if settings.avoid_bad_relays {
+                    "on"
+                } else {
+                    "off"
+                };
+                println!("Avoid bad relays: {}", enabled_str);
+
+                let relays = rpc.get_relay_stats(()).await?.into_inner().relays;
+                if relays.is_empty() {
+                    println!("No connections have been made");
+                }
+                for stats in &relays {
+                    print_stats(stats);
+                }
+                Ok(())
+            }
+            Some(("set", matches)) => {
+                let enable_str = matches.value_of("policy").expect("missing policy");
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_avoid_bad_relays(enable_str == "on").await?;
+                println!("Avoid bad relays: {}", enable_str);
+                Ok(())
+            }
+            Some(("clear", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                rpc.clear_relay_stats(()).await?;
+                println!("Cleared relay statistics");
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+fn print_stats(stats: &types::RelayStats) {
+    println!(
+        "{}{}",
+        stats.hostname,
+        if stats.bad { " (bad)" } else { "" }
+    );
+    println!(
+        "  Successful connections: {}/{}",
+        stats.successes, stats.attempts
+    );
+    if let Some(handshake_time) = &stats.average_handshake_time {
+        let millis = handshake_time.seconds * 1000 + i64::from(handshake_time.nanos / 1_000_000);
+        println!("  Average connection time: {} ms", millis);
+    }
+    if stats.average_throughput > 0 {
+        println!(
+            "  Average peak throughput: {:.1} KiB/s",
+            stats.average_throughput as f64 / 1024.0
+        );
+    }
+    if let Some(last_used) = &stats.last_used {
+        let last_used = chrono::NaiveDateTime::from_timestamp(last_used.seconds, 0);
+        let last_used = chrono::DateTime::<chrono::Utc>::from_utc(last_used, chrono::Utc)
+            .with_timezone(&chrono::Local)
+            .format("%c");
+        println!("  Last used: {}", last_used);
+    }
+}
@@ -0,0 +1,124 @@
+use super::disconnect::parse_duration;
+use crate::{new_rpc_client, Command, Error, Result};
+use itertools::Itertools;
+use mullvad_management_interface::types;
+
+pub struct SettingOverride;
+
+#[mullvad_management_interface::async_trait]
+impl Command for SettingOverride {
+    fn name(&self) -> &'static str {
+        "override"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Change a setting temporarily")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about(
+                        "Change a setting and restore its current value once the duration has \
+                        elapsed",
+                    )
+                    .arg(clap::Arg::new("setting").required(true).possible_values(&[
+                        "block_when_disconnected",
+                        "allow_lan",
+                        "allow_multicast",
+                        "enable_ipv6",
+                        "location",
+                    ]))
+                    .arg(
+                        clap::Arg::new("value")
+                            .help(
+                                "\"on\" or \"off\", or for the location \"any\" or \
+                                <country> [city] [hostname]",
+                            )
+                            .required(true)
+                            .multiple_values(true),
+                    )
+                    .arg(
+                        clap::Arg::new("for")
+                            .long("for")
+                            .takes_value(true)
+                            .value_name("DURATION")
+                            .required(true)
+                            .help("How long the override lasts, e.g. 10m or 1h. At most 7 days"),
+                    )
+                    .arg(
+                        clap::Arg::new("until-reconnect")
+                            .long("until-reconnect")
+                            .help("Also end the override when the tunnel is reconnected"),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("clear")
+                    .about("End an override early and restore the previous value")
+                    .arg(clap::Arg::new("setting").required(true)),
+            )
+            .subcommand(clap::App::new("list").about("Display the active overrides"))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("set", matches)) => {
+                let setting = matches.value_of("setting").unwrap();
+                let value = matches.values_of("value").unwrap().join(" ");
+                let duration = parse_duration(matches.value_of("for").unwrap())
+                    .ok_or(Error::InvalidCommand("invalid duration, expected e.g. 10m"))?;
+
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_setting_override(types::SettingOverride {
+                    setting: setting.to_owned(),
+                    value: value.clone(),
+                    duration: Some(types::Duration {
+                        seconds: duration.as_secs() as i64,
+                        nanos: 0,
+                    }),
+                    revert_on_reconnect: matches.is_present("until-reconnect"),
+                })
+                .await
+                .map_err(|error| Error::RpcFailedExt("Failed to override the setting", error))?;
+                println!("Set {} to \"{}\" temporarily", setting, value);
+                Ok(())
+            }
+            Some(("clear", matches)) => {
+                let setting = matches.value_of("setting").unwrap();
+                let mut rpc = new_rpc_client().await?;
+                rpc.clear_setting_override(setting.to_owned()).await?;
+                println!("Restored the previous value of {}", setting);
+                Ok(())
+            }
+            Some(("list", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                let overrides = rpc.get_setting_overrides(()).await?.into_inner().overrides;
+                if overrides.is_empty() {
+                    println!("No active overrides");
+                }
+                for setting_override in overrides {
+                    let revert_at = setting_override
+                        .revert_at
+                        .map(|revert_at| {
+                            let revert_at =
+                                chrono::NaiveDateTime::from_timestamp(revert_at.seconds, 0);
+                            chrono::DateTime::<chrono::Utc>::from_utc(revert_at, chrono::Utc)
+                                .with_timezone(&chrono::Local)
+                                .format("%X")
+                                .to_string()
+                        })
+                        .unwrap_or_default();
+                    print!(
+                        "{}: \"{}\" until {}",
+                        setting_override.setting, setting_override.value, revert_at
+                    );
+                    if setting_override.revert_on_reconnect {
+                        print!(" or the next reconnect");
+                    }
+                    println!(", then \"{}\"", setting_override.restore_value);
+                }
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
@@ -0,0 +1,541 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_management_interface::{types, ManagementServiceClient};
+use mullvad_types::{
+    relay_constraints::{
+        BridgeConstraints, BridgeSettings, BridgeState, ObfuscationSettings, RelayConstraints,
+        RelaySettings,
+    },
+    settings::{AppExclusionSettings, TunnelOptions},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(any(windows, target_os = "linux"))]
+use std::io::{self, Write};
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Version of the bundle format. Bump this when making incompatible changes to
+/// `SettingsBundle`.
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+pub struct Settings;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Settings {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Move settings between machines, undo recent changes and encrypt settings")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("transfer")
+                    .about(
+                        "Export a portable bundle of the current settings. The bundle contains no \
+                        account number, device or keys. A new device and key is created when \
+                        logging in on the target machine",
+                    )
+                    .arg(
+                        clap::Arg::new("file")
+                            .help("File to write the bundle to. Writes to stdout if omitted")
+                            .allow_invalid_utf8(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("import")
+                    .about(
+                        "Apply a bundle created by 'settings transfer'. Paths of excluded \
+                        applications are translated for this machine interactively",
+                    )
+                    .arg(
+                        clap::Arg::new("file")
+                            .required(true)
+                            .allow_invalid_utf8(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("apply")
+                    .about("Change several settings at once")
+                    .long_about(
+                        "Change several settings at once. The input is a JSON object with the \
+                        same layout as the settings file, containing only the settings to change, \
+                        such as '{\"allow_lan\": true, \"auto_connect\": true}'. Objects are \
+                        merged, null resets a setting to its default, and any other value \
+                        replaces the current one. Nothing is changed \
+                        if any of the values are invalid or locked by the administrator",
+                    )
+                    .arg(
+                        clap::Arg::new("file")
+                            .help("File to read the settings from, or '-' to read from stdin")
+                            .required(true)
+                            .allow_invalid_utf8(true),
+                    ),
+            )
+            .subcommand(clap::App::new("history").about("Show the recent changes to the settings"))
+            .subcommand(
+                clap::App::new("rollback")
+                    .about(
+                        "Set the settings changed by an entry in 'settings history' back to their \
+                        previous values. Settings that were changed later are left as they are",
+                    )
+                    .arg(
+                        clap::Arg::new("id")
+                            .help("ID of the change, as shown by 'settings history'")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("encryption")
+                    .about(
+                        "Control if the settings file is encrypted at rest with a key held by \
+                        the keystore of the OS. While it is encrypted, the settings history is \
+                        only kept in memory. Not supported on Linux, where the daemon has no \
+                        keystore to hold the key",
+                    )
+                    .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        clap::App::new("set")
+                            .about("Enable or disable encryption of the settings file")
+                            .arg(
+                                clap::Arg::new("policy")
+                                    .required(true)
+                                    .possible_values(&["on", "off"]),
+                            ),
+                    )
+                    .subcommand(
+                        clap::App::new("get")
+                            .about("Display whether the settings file is encrypted"),
+                    ),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("transfer", matches)) => {
+                Self::transfer(matches.value_of_os("file").map(PathBuf::from)).await
+            }
+            Some(("import", matches)) => {
+                let path = PathBuf::from(matches.value_of_os("file").unwrap());
+                Self::import(&path).await
+            }
+            Some(("apply", matches)) => {
+                let path = PathBuf::from(matches.value_of_os("file").unwrap());
+                Self::apply(&path).await
+            }
+            Some(("history", _)) => Self::history().await,
+            Some(("rollback", matches)) => {
+                let id = matches.value_of_t_or_exit("id");
+                Self::rollback(id).await
+            }
+            Some(("encryption", matches)) => match matches.subcommand() {
+                Some(("set", matches)) => {
+                    let policy = matches.value_of("policy").expect("missing policy");
+                    Self::set_encryption(policy == "on").await
+                }
+                Some(("get", _)) => Self::get_encryption().await,
+                _ => unreachable!("unhandled command"),
+            },
+            _ => unreachable!("unhandled command"),
+        }
+    }
+}
+
+/// Non-secret settings that can be moved to another machine, possibly running a different
+/// operating system.
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsBundle {
+    format_version: u32,
+    /// The OS the bundle was created on, as returned by `std::env::consts::OS`.
+    source_os: String,
+    /// `None` if a custom tunnel endpoint is used, since those contain credentials.
+    relay_constraints: Option<RelayConstraints>,
+    /// `None` if a custom proxy is used, since those may contain credentials.
+    bridge_constraints: Option<BridgeConstraints>,
+    bridge_state: BridgeState,
+    obfuscation_settings: ObfuscationSettings,
+    allow_lan: bool,
+    block_when_disconnected: bool,
+    /// Missing from bundles created by older versions.
+    #[serde(default)]
+    strict_enforcement: bool,
+    auto_connect: bool,
+    tunnel_options: TunnelOptions,
+    split_tunnel: Option<SplitTunnelBundle>,
+    /// Apps excluded by package name on Android. Missing from bundles created by older versions.
+    #[serde(default)]
+    app_exclusions: AppExclusionSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SplitTunnelBundle {
+    enable_exclusions: bool,
+    apps: Vec<String>,
+}
+
+impl Settings {
+    async fn apply(path: &Path) -> Result<()> {
+        let patch = if path == Path::new("-") {
+            let mut patch = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut patch)
+                .map_err(Error::SettingsPatchIo)?;
+            patch
+        } else {
+            fs::read_to_string(path).map_err(Error::SettingsPatchIo)?
+        };
+        let mut rpc = new_rpc_client().await?;
+        rpc.apply_settings_patch(patch)
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to apply the settings", error))?;
+        println!("Applied the settings");
+        Ok(())
+    }
+
+    async fn history() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let entries = rpc.get_settings_history(()).await?.into_inner().entries;
+        if entries.is_empty() {
+            println!("No recorded changes");
+        }
+        for entry in entries {
+            let time = entry
+                .time
+                .map(|time| {
+                    let time = chrono::NaiveDateTime::from_timestamp(time.seconds, 0);
+                    chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc)
+                        .with_timezone(&chrono::Local)
+                        .format("%c")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let source = match types::settings_history_entry::Source::from_i32(entry.source) {
+                Some(types::settings_history_entry::Source::Client) => "client".to_owned(),
+                Some(types::settings_history_entry::Source::SettingsFile) => {
+                    "settings file".to_owned()
+                }
+                Some(types::settings_history_entry::Source::Rollback) => {
+                    format!("rollback of {}", entry.rolled_back_id)
+                }
+                Some(types::settings_history_entry::Source::Reset) => "reset".to_owned(),
+                None => "unknown".to_owned(),
+            };
+            println!("{}: {} ({})", entry.id, time, source);
+            for change in entry.changes {
+                println!(
+                    "    {}: {} -> {}",
+                    change.path, change.old_value, change.new_value
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback(id: u64) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.rollback_settings(id).await?;
+        println!("Undid change {}", id);
+        Ok(())
+    }
+
+    async fn set_encryption(enabled: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_settings_encryption(enabled)
+            .await
+            .map_err(|error| Error::RpcFailedExt("Failed to change settings encryption", error))?;
+        println!("Changed settings encryption");
+        Ok(())
+    }
+
+    async fn get_encryption() -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let encrypted = rpc.get_settings_encryption(()).await?.into_inner();
+        println!(
+            "Settings encryption: {}",
+            if encrypted { "on" } else { "off" }
+        );
+        Ok(())
+    }
+
+    async fn transfer(path: Option<PathBuf>) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let settings = rpc.get_settings(()).await?.into_inner();
+
+        let relay_constraints =
+            match RelaySettings::try_from(settings.relay_settings.unwrap()).unwrap() {
+                RelaySettings::Normal(constraints) => Some(constraints),
+                RelaySettings::CustomTunnelEndpoint(_) => {
+                    eprintln!("Skipping custom tunnel endpoint since it contains credentials");
+                    None
+                }
+            };
+        let bridge_constraints =
+            match BridgeSettings::try_from(settings.bridge_settings.unwrap()).unwrap() {
+                BridgeSettings::Normal(constraints) => Some(constraints),
+                BridgeSettings::Custom(_) => {
+                    eprintln!("Skipping custom bridge since it may contain credentials");
+                    None
+                }
+            };
+
+        let bundle = SettingsBundle {
+            format_version: BUNDLE_FORMAT_VERSION,
+            source_os: std::env::consts::OS.to_string(),
+            relay_constraints,
+            bridge_constraints,
+            bridge_state: BridgeState::try_from(settings.bridge_state.unwrap()).unwrap(),
+            obfuscation_settings: ObfuscationSettings::try_from(
+                settings.obfuscation_settings.unwrap(),
+            )
+            .unwrap(),
+            allow_lan: settings.allow_lan,
+            block_when_disconnected: settings.block_when_disconnected,
+            strict_enforcement: settings.strict_enforcement,
+            auto_connect: settings.auto_connect,
+            tunnel_options: TunnelOptions::try_from(settings.tunnel_options.unwrap()).unwrap(),
+            split_tunnel: settings.split_tunnel.map(|split_tunnel| SplitTunnelBundle {
+                enable_exclusions: split_tunnel.enable_exclusions,
+                apps: split_tunnel.apps,
+            }),
+            app_exclusions: settings
+                .app_exclusions
+                .map(AppExclusionSettings::from)
+                .unwrap_or_default(),
+        };
+
+        let serialized =
+            serde_json::to_string_pretty(&bundle).expect("Failed to serialize settings bundle");
+        match path {
+            Some(path) => {
+                fs::write(&path, serialized).map_err(Error::SettingsBundleIo)?;
+                println!("Wrote settings bundle to {}", path.display());
+            }
+            None => println!("{}", serialized),
+        }
+        Ok(())
+    }
+
+    async fn import(path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path).map_err(Error::SettingsBundleIo)?;
+        let bundle: SettingsBundle =
+            serde_json::from_str(&contents).map_err(Error::InvalidSettingsBundle)?;
+        if bundle.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(Error::CommandFailed(
+                "The settings bundle was created by a newer version of the app",
+            ));
+        }
+
+        let mut rpc = new_rpc_client().await?;
+
+        if let Some(constraints) = bundle.relay_constraints {
+            rpc.update_relay_settings(relay_settings_update_from_constraints(constraints))
+                .await?;
+        }
+        if let Some(constraints) = bundle.bridge_constraints {
+            rpc.set_bridge_settings(types::BridgeSettings::from(BridgeSettings::Normal(
+                constraints,
+            )))
+            .await?;
+        }
+        rpc.set_bridge_state(types::BridgeState::from(bundle.bridge_state))
+            .await?;
+        rpc.set_obfuscation_settings(types::ObfuscationSettings::from(
+            &bundle.obfuscation_settings,
+        ))
+        .await?;
+        rpc.set_allow_lan(bundle.allow_lan).await?;
+        rpc.set_block_when_disconnected(bundle.block_when_disconnected)
+            .await?;
+        rpc.set_strict_enforcement(bundle.strict_enforcement)
+            .await?;
+        rpc.set_auto_connect(bundle.auto_connect).await?;
+
+        let tunnel_options = types::TunnelOptions::from(&bundle.tunnel_options);
+        let wireguard_options = tunnel_options.wireguard.unwrap();
+        rpc.set_openvpn_mssfix(tunnel_options.openvpn.unwrap().mssfix)
+            .await?;
+        rpc.set_wireguard_mtu(wireguard_options.mtu).await?;
+        rpc.set_quantum_resistant_tunnel(wireguard_options.use_pq_safe_psk)
+            .await?;
+        match wireguard_options.rotation_interval {
+            Some(interval) => rpc.set_wireguard_rotation_interval(interval).await?,
+            None => rpc.reset_wireguard_rotation_interval(()).await?,
+        };
+        rpc.set_enable_ipv6(tunnel_options.generic.unwrap().enable_ipv6)
+            .await?;
+        rpc.set_dns_options(tunnel_options.dns_options.unwrap())
+            .await?;
+
+        if let Some(split_tunnel) = bundle.split_tunnel {
+            Self::import_split_tunnel(&mut rpc, &bundle.source_os, split_tunnel).await?;
+        }
+        rpc.set_app_exclusions(types::AppExclusionSettings::from(bundle.app_exclusions))
+            .await?;
+
+        println!("Imported settings from {}", path.display());
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    async fn import_split_tunnel(
+        rpc: &mut ManagementServiceClient,
+        source_os: &str,
+        split_tunnel: SplitTunnelBundle,
+    ) -> Result<()> {
+        rpc.clear_split_tunnel_apps(()).await?;
+        for app in &split_tunnel.apps {
+            if let Some(path) = translate_app_path(app, source_os) {
+                rpc.add_split_tunnel_app(path).await?;
+            }
+        }
+        rpc.set_split_tunnel_state(split_tunnel.enable_exclusions)
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn import_split_tunnel(
+        _rpc: &mut ManagementServiceClient,
+        source_os: &str,
+        split_tunnel: SplitTunnelBundle,
+    ) -> Result<()> {
+        let paths: Vec<String> = split_tunnel
+            .apps
+            .iter()
+            .filter_map(|app| translate_app_path(app, source_os))
+            .collect();
+        if !paths.is_empty() {
+            println!(
+                "Split tunneling applies to processes rather than paths on Linux. To exclude \
+                these applications, launch them using mullvad-exclude:"
+            );
+            for path in &paths {
+                println!("    mullvad-exclude {}", path);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    async fn import_split_tunnel(
+        _rpc: &mut ManagementServiceClient,
+        _source_os: &str,
+        split_tunnel: SplitTunnelBundle,
+    ) -> Result<()> {
+        if !split_tunnel.apps.is_empty() {
+            println!("Skipping excluded applications since split tunneling is not supported");
+        }
+        Ok(())
+    }
+}
+
+/// Returns a full relay settings update that replaces all existing constraints with
+/// `constraints`.
+pub fn relay_settings_update_from_constraints(
+    constraints: RelayConstraints,
+) -> types::RelaySettingsUpdate {
+    let settings = match types::RelaySettings::from(RelaySettings::Normal(constraints)).endpoint {
+        Some(types::relay_settings::Endpoint::Normal(settings)) => settings,
+        _ => unreachable!("normal relay settings were converted to another type"),
+    };
+    types::RelaySettingsUpdate {
+        r#type: Some(types::relay_settings_update::Type::Normal(
+            types::NormalRelaySettingsUpdate {
+                // An empty location is interpreted as `Constraint::Any`
+                location: Some(settings.location.unwrap_or_default()),
+                location_set: settings.location_set,
+                providers: Some(types::ProviderUpdate {
+                    providers: settings.providers,
+                }),
+                ownership: Some(types::OwnershipUpdate {
+                    ownership: settings.ownership,
+                }),
+                datacenter: Some(types::DatacenterUpdate {
+                    datacenter: settings.datacenter,
+                }),
+                tunnel_type: Some(types::TunnelTypeUpdate {
+                    tunnel_type: settings.tunnel_type,
+                }),
+                wireguard_constraints: settings.wireguard_constraints,
+                openvpn_constraints: settings.openvpn_constraints,
+            },
+        )),
+    }
+}
+
+/// Asks the user where an excluded application from the source machine is located on this
+/// machine. Returns `None` if the application should be skipped.
+#[cfg(any(windows, target_os = "linux"))]
+fn translate_app_path(app: &str, source_os: &str) -> Option<String> {
+    let suggestion = if source_os == std::env::consts::OS {
+        Some(PathBuf::from(app)).filter(|path| path.exists())
+    } else {
+        find_executable_in_path(&app_stem(app))
+    };
+
+    let prompt = match &suggestion {
+        Some(suggestion) => format!(
+            "Path of {} on this machine [{}] (enter '-' to skip): ",
+            app,
+            suggestion.display()
+        ),
+        None => format!("Path of {} on this machine (leave empty to skip): ", app),
+    };
+
+    loop {
+        let answer = read_line(&prompt)?;
+        let answer = answer.trim();
+        match (answer, &suggestion) {
+            ("-", _) => return None,
+            ("", Some(suggestion)) => return Some(suggestion.display().to_string()),
+            ("", None) => return None,
+            (answer, _) if Path::new(answer).exists() => return Some(answer.to_string()),
+            (answer, _) => eprintln!("{} does not exist", answer),
+        }
+    }
+}
+
+#[cfg(any(windows, target_os = "linux"))]
+fn read_line(prompt: &str) -> Option<String> {
+    let mut line = String::new();
+    io::stdout()
+        .write_all(prompt.as_bytes())
+        .expect("Failed to write to STDOUT");
+    let _ = io::stdout().flush();
+    match io::stdin().read_line(&mut line) {
+        // Treat EOF as a request to skip the remaining applications
+        Ok(0) => None,
+        Ok(_) => Some(line),
+        Err(error) => {
+            eprintln!("Couldn't read from STDIN: {}", error);
+            None
+        }
+    }
+}
+
+/// Returns the lowercase file name of a path without its extension. Both Windows and Unix path
+/// separators are accepted regardless of the OS that is running.
+#[cfg(any(windows, target_os = "linux"))]
+fn app_stem(app: &str) -> String {
+    let file_name = app.rsplit(|c| c == '\\' || c == '/').next().unwrap_or(app);
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _extension)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+    stem.to_lowercase()
+}
+
+#[cfg(any(windows, target_os = "linux"))]
+fn find_executable_in_path(stem: &str) -> Option<PathBuf> {
+    let executable = if cfg!(windows) {
+        format!("{}.exe", stem)
+    } else {
+        stem.to_string()
+    };
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var)
+        .map(|dir| dir.join(&executable))
+        .find(|candidate| candidate.is_file())
+}
@@ -30,7 +30,15 @@ fn create_pid_subcommand() -> clap::App<'static> {
     clap::App::new("pid")
         .about("Manage processes to exclude from the tunnel")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-        .subcommand(clap::App::new("add").arg(clap::Arg::new("pid").required(true)))
+        .subcommand(
+            clap::App::new("add")
+                .arg(clap::Arg::new("pid").required(true))
+                .arg(
+                    clap::Arg::new("children")
+                        .long("children")
+                        .help("Also exclude all processes that were started by the process"),
+                ),
+        )
         .subcommand(clap::App::new("delete").arg(clap::Arg::new("pid").required(true)))
         .subcommand(clap::App::new("clear"))
         .subcommand(clap::App::new("list"))
@@ -41,10 +49,12 @@ impl SplitTunnel {
         match matches.subcommand() {
             Some(("add", matches)) => {
                 let pid: i32 = matches.value_of_t_or_exit("pid");
-                new_rpc_client()
-                    .await?
-                    .add_split_tunnel_process(pid)
-                    .await?;
+                let mut rpc = new_rpc_client().await?;
+                if matches.is_present("children") {
+                    rpc.add_split_tunnel_process_tree(pid).await?;
+                } else {
+                    rpc.add_split_tunnel_process(pid).await?;
+                }
                 Ok(())
             }
             Some(("delete", matches)) => {
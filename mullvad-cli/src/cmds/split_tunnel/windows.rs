@@ -49,7 +49,17 @@ fn create_app_subcommand() -> clap::App<'static> {
         .about("Manage applications to exclude from the tunnel")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(clap::App::new("list"))
-        .subcommand(clap::App::new("add").arg(clap::Arg::new("path").required(true)))
+        .subcommand(
+            clap::App::new("add").arg(
+                clap::Arg::new("path")
+                    .help(
+                        "Path of the executable. The path may contain the wildcards * and ?, \
+                        e.g. 'C:\\Program Files\\App\\*\\app.exe'. An executable name such \
+                        as 'app.exe' matches the app wherever it is, once it has been started.",
+                    )
+                    .required(true),
+            ),
+        )
         .subcommand(clap::App::new("remove").arg(clap::Arg::new("path").required(true)))
         .subcommand(clap::App::new("clear"))
 }
@@ -1,7 +1,23 @@
+use super::{
+    disconnect::format_duration,
+    disconnect_warning::{format_bytes, format_rate},
+};
 use crate::{format, new_rpc_client, Command, Error, Result};
 use mullvad_management_interface::{
-    types::daemon_event::Event as EventType, ManagementServiceClient,
+    types::{
+        daemon_event::Event as EventType,
+        disconnect_warning_event::Action,
+        exit_relay_change_event::Change,
+        tunnel_state::{
+            self,
+            State::{Connected, Connecting, Disconnected, Disconnecting},
+        },
+        AccountExpiryEvent, AfterDisconnect, ExitRelayChangeEvent, TrafficCounters,
+        TransportProtocol, TunnelState, TunnelStateRelayInfo, TunnelType,
+    },
+    ManagementServiceClient,
 };
+use serde_json::json;
 
 pub struct Status;
 
@@ -16,7 +32,9 @@ impl Command for Status {
             .about("View the state of the VPN tunnel")
             .arg(
                 clap::Arg::new("verbose")
+                    .long("verbose")
                     .short('v')
+                    .global(true)
                     .help("Enables verbose output"),
             )
             .arg(
@@ -31,7 +49,26 @@ impl Command for Status {
                     .global(true)
                     .help("Enables debug output"),
             )
-            .subcommand(clap::App::new("listen").about("Listen for VPN tunnel state changes"))
+            .subcommand(
+                clap::App::new("listen")
+                    .about("Listen for VPN tunnel state changes")
+                    .long_about(
+                        "Listen for VPN tunnel state changes. With --verbose, each change is \
+                        timestamped, and switching to another relay is reported",
+                    )
+                    .arg(
+                        clap::Arg::new("json")
+                            .long("json")
+                            .help("Print each event as a single line of JSON"),
+                    ),
+            )
+            .subcommand(clap::App::new("usage").about(
+                "Show how much data has passed through the tunnel this session and in each of \
+                the last 12 months",
+            ))
+            .subcommand(clap::App::new("leftover").about(
+                "Show whether the previous daemon instance exited without restoring the system",
+            ))
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -40,33 +77,70 @@ impl Command for Status {
         let show_full_location = matches.is_present("location");
 
         let mut rpc = new_rpc_client().await?;
+
+        if matches.subcommand_matches("leftover").is_some() {
+            return print_leftover_state(&mut rpc).await;
+        }
+        if matches.subcommand_matches("usage").is_some() {
+            return print_data_usage(&mut rpc).await;
+        }
+
         let state = rpc.get_tunnel_state(()).await?.into_inner();
 
+        if let Some(listen_matches) = matches.subcommand_matches("listen") {
+            if listen_matches.is_present("json") {
+                return listen_json(&mut rpc, state).await;
+            }
+        }
+
         if debug {
             println!("Tunnel state: {:#?}", state);
         } else {
             format::print_state(&state, verbose);
         }
 
+        if let Some(Disconnected(_)) = state.state {
+            print_tunnel_pause(&mut rpc).await?;
+            print_permissive_mode(&mut rpc).await?;
+        }
+
         if show_full_location {
             print_location(&mut rpc).await?;
         }
 
         if matches.subcommand_matches("listen").is_some() {
             let mut events = rpc.events_listen(()).await?.into_inner();
+            let mut relay = connected_relay(&state);
 
             while let Some(event) = events.message().await? {
                 match event.event.unwrap() {
                     EventType::TunnelState(new_state) => {
+                        if verbose {
+                            print!("[{}] ", chrono::Local::now().format("%X"));
+                        }
                         if debug {
                             println!("New tunnel state: {:#?}", new_state);
                         } else {
                             format::print_state(&new_state, verbose);
                         }
+                        if let Some(new_relay) = connected_relay(&new_state) {
+                            match relay.replace(new_relay.clone()) {
+                                Some(old_relay) if verbose && old_relay != new_relay => {
+                                    println!("Relay changed from {} to {}", old_relay, new_relay);
+                                }
+                                _ => (),
+                            }
+                        }
 
-                        use mullvad_management_interface::types::tunnel_state::State::*;
                         match new_state.state.unwrap() {
-                            Connected(..) | Disconnected(..) => {
+                            Disconnected(..) => {
+                                print_tunnel_pause(&mut rpc).await?;
+                                print_permissive_mode(&mut rpc).await?;
+                                if show_full_location {
+                                    print_location(&mut rpc).await?;
+                                }
+                            }
+                            Connected(..) => {
                                 if show_full_location {
                                     print_location(&mut rpc).await?;
                                 }
@@ -99,6 +173,46 @@ impl Command for Status {
                             println!("Remove device event: {:#?}", device);
                         }
                     }
+                    EventType::ExitRelayChange(change) => {
+                        if debug {
+                            println!("Exit relay change event: {:#?}", change);
+                        } else {
+                            print_exit_relay_change(&change);
+                        }
+                    }
+                    EventType::AccountExpiry(expiry) => {
+                        if debug {
+                            println!("Account expiry event: {:#?}", expiry);
+                        } else {
+                            print_account_expiry(&expiry);
+                        }
+                    }
+                    EventType::AutoConnectFailed(event) => {
+                        if debug {
+                            println!("Auto-connect failed event: {:#?}", event);
+                        } else {
+                            println!(
+                                "Auto-connect kept failing and was retried {} times. Run \
+                                 `mullvad reconnect` to try again",
+                                event.retries
+                            );
+                        }
+                    }
+                    EventType::DisconnectWarning(event) => {
+                        if debug {
+                            println!("Disconnect warning event: {:#?}", event);
+                        } else {
+                            let action = match event.action() {
+                                Action::Disconnect => "disconnect",
+                                Action::Reconnect => "reconnect",
+                            };
+                            println!(
+                                "Held back a {} since {} pass through the tunnel",
+                                action,
+                                format_rate(event.throughput)
+                            );
+                        }
+                    }
                 }
             }
         }
@@ -107,6 +221,277 @@ impl Command for Status {
     }
 }
 
+/// Returns the hostname of the exit relay, if connected.
+fn connected_relay(state: &TunnelState) -> Option<String> {
+    match state.state.as_ref()? {
+        Connected(tunnel_state::Connected { relay_info }) => relay_info
+            .as_ref()
+            .and_then(|relay_info| relay_info.location.as_ref())
+            .map(|location| location.hostname.clone()),
+        _ => None,
+    }
+}
+
+/// Prints the current tunnel state followed by every event from the daemon, each of them as a
+/// single line of JSON.
+async fn listen_json(rpc: &mut ManagementServiceClient, state: TunnelState) -> Result<()> {
+    let mut relay = connected_relay(&state);
+    print_json_event("tunnel_state", tunnel_state_to_json(&state));
+
+    let mut events = rpc.events_listen(()).await?.into_inner();
+    while let Some(event) = events.message().await? {
+        let (name, details) = match event.event.unwrap() {
+            EventType::TunnelState(new_state) => {
+                let mut details = tunnel_state_to_json(&new_state);
+                if let Some(new_relay) = connected_relay(&new_state) {
+                    if let Some(old_relay) = relay.replace(new_relay.clone()) {
+                        if old_relay != new_relay {
+                            details["previous_relay"] = json!(old_relay);
+                        }
+                    }
+                }
+                ("tunnel_state", details)
+            }
+            EventType::Settings(_) => ("settings", json!({})),
+            EventType::RelayList(_) => ("relay_list", json!({})),
+            EventType::VersionInfo(_) => ("version_info", json!({})),
+            EventType::Device(_) => ("device", json!({})),
+            EventType::RemoveDevice(_) => ("remove_device", json!({})),
+            EventType::ExitRelayChange(change) => (
+                "exit_relay_change",
+                json!({
+                    "relay": change.hostname,
+                    "change": match Change::from_i32(change.change) {
+                        Some(Change::Moved) => "moved",
+                        Some(Change::ExcludedFromCountry) => "excluded_from_country",
+                        Some(Change::Deactivated) => "deactivated",
+                        Some(Change::Removed) => "removed",
+                        None => "unknown",
+                    },
+                    "country": change.country,
+                    "city": change.city,
+                    "reconnecting": change.reconnecting,
+                }),
+            ),
+            EventType::AccountExpiry(expiry) => (
+                "account_expiry",
+                json!({ "expiry": expiry.expiry.map(|expiry| expiry.seconds) }),
+            ),
+            EventType::AutoConnectFailed(event) => {
+                ("auto_connect_failed", json!({ "retries": event.retries }))
+            }
+            EventType::DisconnectWarning(event) => (
+                "disconnect_warning",
+                json!({
+                    "action": match event.action() {
+                        Action::Disconnect => "disconnect",
+                        Action::Reconnect => "reconnect",
+                    },
+                    "throughput": event.throughput,
+                }),
+            ),
+        };
+        print_json_event(name, details);
+    }
+    Ok(())
+}
+
+fn print_json_event(name: &str, details: serde_json::Value) {
+    let mut event = json!({
+        "time": chrono::Local::now().to_rfc3339(),
+        "event": name,
+    });
+    if let (Some(event), serde_json::Value::Object(details)) = (event.as_object_mut(), details) {
+        event.extend(details);
+    }
+    println!("{}", event);
+}
+
+fn tunnel_state_to_json(state: &TunnelState) -> serde_json::Value {
+    match state.state.as_ref().unwrap() {
+        Disconnected(_) => json!({ "state": "disconnected" }),
+        Connecting(tunnel_state::Connecting { relay_info }) => {
+            relay_info_to_json("connecting", relay_info.as_ref().unwrap())
+        }
+        Connected(tunnel_state::Connected { relay_info }) => {
+            relay_info_to_json("connected", relay_info.as_ref().unwrap())
+        }
+        Disconnecting(disconnecting) => json!({
+            "state": "disconnecting",
+            "after_disconnect": match disconnecting.after_disconnect() {
+                AfterDisconnect::Nothing => "nothing",
+                AfterDisconnect::Block => "block",
+                AfterDisconnect::Reconnect => "reconnect",
+            },
+        }),
+        tunnel_state::State::Error(error) => {
+            let error_state = error.error_state.as_ref().unwrap();
+            json!({
+                "state": "error",
+                "cause": format::error_state_to_string(error_state),
+                "blocking": error_state.blocking_error.is_none(),
+            })
+        }
+    }
+}
+
+fn relay_info_to_json(state: &str, relay_info: &TunnelStateRelayInfo) -> serde_json::Value {
+    let endpoint = relay_info.tunnel_endpoint.as_ref().unwrap();
+    let location = relay_info.location.as_ref().unwrap();
+    let non_empty = |value: &String| Some(value.clone()).filter(|value| !value.is_empty());
+    let protocol = |protocol: i32| {
+        TransportProtocol::from_i32(protocol)
+            .map(format::format_protocol)
+            .unwrap_or("unknown")
+    };
+
+    json!({
+        "state": state,
+        "relay": location.hostname,
+        "entry_relay": non_empty(&location.entry_hostname),
+        "bridge": non_empty(&location.bridge_hostname),
+        "country": location.country,
+        "city": location.city,
+        "tunnel_type": match TunnelType::from_i32(endpoint.tunnel_type) {
+            Some(TunnelType::Wireguard) => "wireguard",
+            Some(TunnelType::Openvpn) => "openvpn",
+            None => "unknown",
+        },
+        "endpoint": endpoint.address,
+        "protocol": protocol(endpoint.protocol),
+        "quantum_resistant": endpoint.quantum_resistant,
+        "obfuscation": endpoint.obfuscation.as_ref().map(|obfuscation| json!({
+            "type": format::convert_obfuscator_type(obfuscation.obfuscation_type),
+            "address": obfuscation.address,
+            "protocol": protocol(obfuscation.protocol),
+        })),
+    })
+}
+
+async fn print_leftover_state(rpc: &mut ManagementServiceClient) -> Result<()> {
+    let leftover = rpc.get_leftover_state(()).await?.into_inner();
+    if !leftover.unclean_exit {
+        println!("The previous daemon instance exited cleanly");
+        return Ok(());
+    }
+
+    println!("The previous daemon instance did not exit cleanly. When it stopped, it had changed:");
+    let changes = [
+        (leftover.firewall_policy, "Firewall policy"),
+        (leftover.dns, "DNS settings"),
+        (leftover.routes, "Routes"),
+    ];
+    for (_, description) in changes.iter().filter(|(applied, _)| *applied) {
+        println!("  {}", description);
+    }
+    if changes.iter().all(|(applied, _)| !*applied) {
+        println!("  Nothing");
+    }
+    Ok(())
+}
+
+async fn print_data_usage(rpc: &mut ManagementServiceClient) -> Result<()> {
+    let usage = rpc.get_data_usage(()).await?.into_inner();
+    let format_traffic = |traffic: Option<TrafficCounters>| {
+        let traffic = traffic.unwrap_or_default();
+        format!(
+            "{} received, {} sent",
+            format_bytes(traffic.received),
+            format_bytes(traffic.sent)
+        )
+    };
+
+    match usage.session_started {
+        Some(started) => {
+            let started = chrono::NaiveDateTime::from_timestamp(started.seconds, 0);
+            let started = chrono::DateTime::<chrono::Utc>::from_utc(started, chrono::Utc)
+                .with_timezone(&chrono::Local)
+                .format("%c");
+            println!(
+                "This session: {} (connected since {})",
+                format_traffic(usage.session),
+                started
+            );
+        }
+        None => println!("This session: not connected"),
+    }
+
+    if usage.months.is_empty() {
+        println!("No data has passed through the tunnel yet");
+    }
+    for month in usage.months.into_iter().rev() {
+        let name = chrono::NaiveDate::from_ymd_opt(month.year, month.month, 1)
+            .map(|date| date.format("%B %Y").to_string())
+            .unwrap_or_else(|| format!("{}-{:02}", month.year, month.month));
+        println!("{}: {}", name, format_traffic(month.traffic));
+    }
+    Ok(())
+}
+
+fn print_exit_relay_change(event: &ExitRelayChangeEvent) {
+    let change = match Change::from_i32(event.change) {
+        Some(Change::Moved) => format!("moved to {}, {}", event.city, event.country),
+        Some(Change::ExcludedFromCountry) => "no longer counted as part of its country".to_owned(),
+        Some(Change::Deactivated) => "deactivated".to_owned(),
+        Some(Change::Removed) => "removed from the relay list".to_owned(),
+        None => "changed".to_owned(),
+    };
+    println!("Exit relay {} was {}", event.hostname, change);
+    if event.reconnecting {
+        println!("Reconnecting to select a new exit relay");
+    }
+}
+
+fn print_account_expiry(event: &AccountExpiryEvent) {
+    let expiry = event.expiry.as_ref().map(|expiry| {
+        let expiry = chrono::NaiveDateTime::from_timestamp(expiry.seconds, 0);
+        chrono::DateTime::<chrono::Utc>::from_utc(expiry, chrono::Utc)
+    });
+    let threshold = event
+        .threshold
+        .clone()
+        .and_then(|threshold| std::time::Duration::try_from(threshold).ok());
+    match (expiry, threshold) {
+        (Some(expiry), _) if expiry <= chrono::Utc::now() => {
+            println!("The account has run out of time");
+        }
+        (Some(expiry), Some(threshold)) => println!(
+            "The account expires in less than {}, at {}",
+            format_duration(threshold),
+            expiry.with_timezone(&chrono::Local)
+        ),
+        _ => println!("The account is about to expire"),
+    }
+}
+
+async fn print_tunnel_pause(rpc: &mut ManagementServiceClient) -> Result<()> {
+    if let Some(resume_at) = rpc.get_tunnel_pause(()).await?.into_inner().resume_at {
+        let resume_at = chrono::NaiveDateTime::from_timestamp(resume_at.seconds, 0);
+        let resume_at = chrono::DateTime::<chrono::Utc>::from_utc(resume_at, chrono::Utc);
+        println!(
+            "Paused. Blocking is suspended until the tunnel reconnects at {}",
+            resume_at.with_timezone(&chrono::Local).format("%X")
+        );
+    }
+    Ok(())
+}
+
+/// Prints when permissive mode ends, if it is active. Returns whether it is active.
+pub async fn print_permissive_mode(rpc: &mut ManagementServiceClient) -> Result<bool> {
+    match rpc.get_permissive_mode(()).await?.into_inner().ends_at {
+        Some(ends_at) => {
+            let ends_at = chrono::NaiveDateTime::from_timestamp(ends_at.seconds, 0);
+            let ends_at = chrono::DateTime::<chrono::Utc>::from_utc(ends_at, chrono::Utc);
+            println!(
+                "PERMISSIVE MODE: the firewall is not enforced until {}",
+                ends_at.with_timezone(&chrono::Local).format("%X")
+            );
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
 async fn print_location(rpc: &mut ManagementServiceClient) -> Result<()> {
     let location = rpc.get_current_location(()).await;
     let location = match location {
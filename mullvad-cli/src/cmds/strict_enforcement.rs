@@ -0,0 +1,61 @@
+use crate::{new_rpc_client, Command, Result};
+
+pub struct StrictEnforcement;
+
+#[mullvad_management_interface::async_trait]
+impl Command for StrictEnforcement {
+    fn name(&self) -> &'static str {
+        "strict-enforcement"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about(
+                "Control if the system service should block all traffic when the DNS config can \
+                only be partially applied while connecting, instead of continuing without it",
+            )
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::App::new("set")
+                    .about("Change the strict enforcement setting")
+                    .arg(
+                        clap::Arg::new("policy")
+                            .required(true)
+                            .possible_values(&["on", "off"]),
+                    ),
+            )
+            .subcommand(
+                clap::App::new("get").about("Display the current strict enforcement setting"),
+            )
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        if let Some(set_matches) = matches.subcommand_matches("set") {
+            let strict_enforcement = set_matches.value_of("policy").expect("missing policy");
+            self.set(strict_enforcement == "on").await
+        } else if let Some(_matches) = matches.subcommand_matches("get") {
+            self.get().await
+        } else {
+            unreachable!("No strict-enforcement command given");
+        }
+    }
+}
+
+impl StrictEnforcement {
+    async fn set(&self, strict_enforcement: bool) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        rpc.set_strict_enforcement(strict_enforcement).await?;
+        println!("Changed strict enforcement setting");
+        Ok(())
+    }
+
+    async fn get(&self) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let strict_enforcement = rpc.get_settings(()).await?.into_inner().strict_enforcement;
+        println!(
+            "Strict enforcement: {}",
+            if strict_enforcement { "on" } else { "off" }
+        );
+        Ok(())
+    }
+}
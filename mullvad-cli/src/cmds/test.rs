@@ -0,0 +1,46 @@
+use crate::{new_rpc_client, Command, Error, Result};
+use mullvad_types::protocol_probe::ProbeReport;
+use std::convert::TryFrom;
+
+pub struct Test;
+
+#[mullvad_management_interface::async_trait]
+impl Command for Test {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn clap_subcommand(&self) -> clap::App<'static> {
+        clap::App::new(self.name())
+            .about("Test how well the current network works with Mullvad")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(clap::App::new("protocols").about(
+                "Probe nearby relays to find out whether WireGuard over UDP, udp2tcp, OpenVPN over \
+                 UDP and TCP, and bridges get through the current network. This helps to pick \
+                 constraints on networks that block VPN traffic. The tunnel must be disconnected, \
+                 with block-when-disconnected off",
+            ))
+    }
+
+    async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("protocols", _)) => Self::protocols().await,
+            _ => unreachable!("unhandled subcommand"),
+        }
+    }
+}
+
+impl Test {
+    async fn protocols() -> Result<()> {
+        println!("Probing nearby relays. This may take a few seconds.\n");
+        let report = new_rpc_client()
+            .await?
+            .probe_protocols(())
+            .await?
+            .into_inner();
+        let report = ProbeReport::try_from(report)
+            .map_err(|_| Error::Other("the daemon sent an invalid report"))?;
+        print!("{}", report);
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
+use super::disconnect::{format_duration, parse_duration};
+#[cfg(target_os = "linux")]
+use super::disconnect_warning::{format_bitrate, parse_rate};
 use crate::{new_rpc_client, Command, Error, Result};
 use mullvad_management_interface::types::{self, Timestamp, TunnelOptions};
-use mullvad_types::wireguard::DEFAULT_ROTATION_INTERVAL;
+use mullvad_types::wireguard::{
+    validate_mtu, validate_persistent_keepalive, RotationInterval, DEFAULT_ROTATION_INTERVAL,
+};
 use std::{convert::TryFrom, time::Duration};
 
 pub struct Tunnel;
@@ -12,12 +17,21 @@ impl Command for Tunnel {
     }
 
     fn clap_subcommand(&self) -> clap::App<'static> {
-        clap::App::new(self.name())
+        let subcmd = clap::App::new(self.name())
             .about("Manage tunnel specific options")
             .setting(clap::AppSettings::SubcommandRequiredElseHelp)
             .subcommand(create_openvpn_subcommand())
             .subcommand(create_wireguard_subcommand())
             .subcommand(create_ipv6_subcommand())
+            .subcommand(create_retry_policy_subcommand());
+        #[cfg(target_os = "linux")]
+        {
+            subcmd.subcommand(create_rate_limit_subcommand())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            subcmd
+        }
     }
 
     async fn run(&self, matches: &clap::ArgMatches) -> Result<()> {
@@ -25,6 +39,9 @@ impl Command for Tunnel {
             Some(("openvpn", openvpn_matches)) => Self::handle_openvpn_cmd(openvpn_matches).await,
             Some(("wireguard", wg_matches)) => Self::handle_wireguard_cmd(wg_matches).await,
             Some(("ipv6", ipv6_matches)) => Self::handle_ipv6_cmd(ipv6_matches).await,
+            Some(("retry-policy", matches)) => Self::handle_retry_policy_cmd(matches).await,
+            #[cfg(target_os = "linux")]
+            Some(("rate-limit", matches)) => Self::handle_rate_limit_cmd(matches).await,
             _ => {
                 unreachable!("unhandled comand");
             }
@@ -37,25 +54,150 @@ fn create_wireguard_subcommand() -> clap::App<'static> {
         .about("Manage options for Wireguard tunnels")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(create_wireguard_mtu_subcommand())
+        .subcommand(create_wireguard_liveness_subcommand())
+        .subcommand(create_wireguard_keepalive_subcommand())
+        .subcommand(create_wireguard_allowed_ips_subcommand())
         .subcommand(create_wireguard_quantum_resistant_tunnel_subcommand())
         .subcommand(create_wireguard_keys_subcommand());
     #[cfg(windows)]
     {
         subcmd.subcommand(create_wireguard_use_wg_nt_subcommand())
     }
-    #[cfg(not(windows))]
+    #[cfg(target_os = "linux")]
+    {
+        subcmd.subcommand(create_wireguard_namespace_subcommand())
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
     {
         subcmd
     }
 }
 
+#[cfg(target_os = "linux")]
+fn create_wireguard_namespace_subcommand() -> clap::App<'static> {
+    clap::App::new("namespace")
+        .about("Move the tunnel interface into a network namespace")
+        .long_about(
+            "Move the tunnel interface into a network namespace, created using 'ip netns add'. \
+            Only processes that run in the namespace, such as those started with 'ip netns exec', \
+            use the tunnel. Other traffic is still blocked while connected, unless it is excluded \
+            using 'mullvad-exclude'. This requires the kernel WireGuard module, and cannot be \
+            combined with quantum-resistant tunnels.",
+        )
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(
+            clap::App::new("unset").about("Keep the tunnel interface in the root namespace"),
+        )
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("name")
+                    .help("Name of the network namespace")
+                    .required(true),
+            ),
+        )
+}
+
 fn create_wireguard_mtu_subcommand() -> clap::App<'static> {
     clap::App::new("mtu")
         .about("Configure the MTU of the wireguard tunnel")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(clap::App::new("get"))
         .subcommand(clap::App::new("unset"))
-        .subcommand(clap::App::new("set").arg(clap::Arg::new("mtu").required(true)))
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("mtu")
+                    .help("The MTU of the tunnel, from 1280 to 1420 bytes")
+                    .required(true),
+            ),
+        )
+}
+
+fn create_wireguard_keepalive_subcommand() -> clap::App<'static> {
+    clap::App::new("keepalive")
+        .about("Configure how often keepalive packets are sent to the relay")
+        .long_about(
+            "Configure how often keepalive packets are sent to the relay. These keep the tunnel \
+            reachable through NATs and firewalls that forget idle connections. Lower the interval \
+            if the tunnel stops working after being idle, or raise it to save battery.",
+        )
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(clap::App::new("reset").about("Use the default interval"))
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("interval")
+                    .help("Seconds between keepalive packets, from 10 to 3600, or 0 to disable")
+                    .required(true),
+            ),
+        )
+}
+
+fn create_wireguard_allowed_ips_subcommand() -> clap::App<'static> {
+    clap::App::new("allowed-ips")
+        .about("Only route some networks through the tunnel")
+        .long_about(
+            "Only route some networks through the tunnel, instead of all traffic. Traffic to \
+            other destinations is sent outside the tunnel, and is not blocked by the firewall. \
+            DNS is still blocked outside the tunnel, so custom DNS servers must be within the \
+            given networks.",
+        )
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(clap::App::new("unset").about("Route all traffic through the tunnel"))
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("networks")
+                    .multiple_values(true)
+                    .help("One or more networks in CIDR notation, e.g. 10.0.0.0/8")
+                    .required(true),
+            ),
+        )
+}
+
+fn create_wireguard_liveness_subcommand() -> clap::App<'static> {
+    clap::App::new("liveness")
+        .about("Configure how to detect that the wireguard tunnel has stopped working")
+        .long_about(
+            "Configure how to detect that the wireguard tunnel has stopped working. When no \
+            traffic has been received for a while, the relay is pinged, and the tunnel is \
+            reconnected if the pings go unanswered.",
+        )
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(
+            clap::App::new("set")
+                .about("Change the liveness options. Options that are left out keep their values")
+                .arg(
+                    clap::Arg::new("probe-interval")
+                        .help("Time between pings, e.g. 3s")
+                        .long("probe-interval")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("probe-timeout")
+                        .help("How long to wait for traffic after each ping, e.g. 3s")
+                        .long("probe-timeout")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("max-failures")
+                        .help("Number of unanswered pings in a row before reconnecting")
+                        .long("max-failures")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("active-probing")
+                        .help(
+                            "Whether to ping the relay. Turn this off on metered links to only \
+                            reconnect when traffic that is sent goes unanswered",
+                        )
+                        .long("active-probing")
+                        .takes_value(true)
+                        .possible_values(&["on", "off"]),
+                ),
+        )
+        .subcommand(clap::App::new("reset").about("Use the default liveness options"))
 }
 
 fn create_wireguard_quantum_resistant_tunnel_subcommand() -> clap::App<'static> {
@@ -71,14 +213,21 @@ fn create_wireguard_keys_subcommand() -> clap::App<'static> {
         .about("Manage your wireguard key")
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(clap::App::new("check"))
-        .subcommand(clap::App::new("regenerate"))
+        .subcommand(
+            clap::App::new("rotate")
+                .alias("regenerate")
+                .about("Replace the key with a new one right away"),
+        )
         .subcommand(create_wireguard_keys_rotation_interval_subcommand())
 }
 
 #[cfg(windows)]
 fn create_wireguard_use_wg_nt_subcommand() -> clap::App<'static> {
     clap::App::new("use-wireguard-nt")
-        .about("Enable or disable wireguard-nt")
+        .about(
+            "Enable or disable wireguard-nt. If it fails to start, wireguard-go is used instead. \
+            'mullvad status -v' shows which one is in use",
+        )
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(clap::App::new("get"))
         .subcommand(
@@ -97,7 +246,13 @@ fn create_wireguard_keys_rotation_interval_subcommand() -> clap::App<'static> {
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .subcommand(clap::App::new("get"))
         .subcommand(clap::App::new("reset").about("Use the default rotation interval"))
-        .subcommand(clap::App::new("set").arg(clap::Arg::new("interval").required(true)))
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("interval")
+                    .help("Interval in hours, from 1 hour up to 30 days")
+                    .required(true),
+            ),
+        )
 }
 
 fn create_openvpn_subcommand() -> clap::App<'static> {
@@ -130,6 +285,68 @@ fn create_ipv6_subcommand() -> clap::App<'static> {
         )
 }
 
+#[cfg(target_os = "linux")]
+fn create_rate_limit_subcommand() -> clap::App<'static> {
+    clap::App::new("rate-limit")
+        .about("Limit the throughput of the tunnel")
+        .long_about(
+            "Limit the throughput of the tunnel in each direction. Outgoing traffic is queued, \
+            and incoming traffic above the limit is dropped. Changes take effect when the tunnel \
+            is reconnected, which happens automatically.",
+        )
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(clap::App::new("unset").about("Remove the limit"))
+        .subcommand(
+            clap::App::new("set").arg(
+                clap::Arg::new("rate")
+                    .help("Bits per second, optionally followed by k, M or G, e.g. 10M")
+                    .required(true),
+            ),
+        )
+}
+
+fn create_retry_policy_subcommand() -> clap::App<'static> {
+    clap::App::new("retry-policy")
+        .about("Control how long to wait before retrying to connect, and when to give up")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(clap::App::new("get"))
+        .subcommand(
+            clap::App::new("set")
+                .about("Change the retry policy. Options that are left out keep their values")
+                .arg(
+                    clap::Arg::new("initial-delay")
+                        .help(
+                            "Delay before the first retry, e.g. 5s. Use \"none\" to retry at once",
+                        )
+                        .long("initial-delay")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("multiplier")
+                        .help("Factor that the delay is multiplied by for each retry")
+                        .long("multiplier")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("max-delay")
+                        .help("Upper bound for the delay, e.g. 5m, or \"unlimited\"")
+                        .long("max-delay")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::new("max-attempts")
+                        .help(
+                            "Number of attempts after which to give up and block traffic, or \
+                            \"unlimited\"",
+                        )
+                        .long("max-attempts")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(clap::App::new("reset").about("Retry immediately and indefinitely"))
+}
+
 impl Tunnel {
     async fn handle_openvpn_cmd(matches: &clap::ArgMatches) -> Result<()> {
         match matches.subcommand() {
@@ -158,9 +375,84 @@ impl Tunnel {
                 _ => unreachable!("unhandled command"),
             },
 
+            Some(("keepalive", matches)) => match matches.subcommand() {
+                Some(("get", _)) => {
+                    let tunnel_options = Self::get_tunnel_options().await?;
+                    match tunnel_options.wireguard.unwrap().persistent_keepalive {
+                        0 => println!("Keepalive: off"),
+                        interval => println!("Keepalive: every {} seconds", interval),
+                    }
+                    Ok(())
+                }
+                Some(("set", matches)) => {
+                    let interval = matches.value_of_t_or_exit::<u16>("interval");
+                    let interval = validate_persistent_keepalive(interval)
+                        .map_err(Error::InvalidPersistentKeepalive)?;
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_persistent_keepalive(u32::from(interval))
+                        .await?;
+                    println!("WireGuard keepalive interval has been updated");
+                    Ok(())
+                }
+                Some(("reset", _)) => {
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_persistent_keepalive(u32::from(
+                        talpid_types::net::wireguard::DEFAULT_PERSISTENT_KEEPALIVE,
+                    ))
+                    .await?;
+                    println!("WireGuard keepalive interval has been reset");
+                    Ok(())
+                }
+                _ => unreachable!("unhandled command"),
+            },
+
+            Some(("allowed-ips", matches)) => match matches.subcommand() {
+                Some(("get", _)) => {
+                    let tunnel_options = Self::get_tunnel_options().await?;
+                    let allowed_ips = tunnel_options.wireguard.unwrap().allowed_ips;
+                    if allowed_ips.is_empty() {
+                        println!("Allowed IPs: all traffic");
+                    } else {
+                        println!("Allowed IPs: {}", allowed_ips.join(", "));
+                    }
+                    Ok(())
+                }
+                Some(("set", matches)) => {
+                    let networks: Vec<String> = matches.values_of_t_or_exit("networks");
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_allowed_ips(types::WireguardAllowedIps { networks })
+                        .await?;
+                    println!("Only the given networks will be routed through the tunnel");
+                    Ok(())
+                }
+                Some(("unset", _)) => {
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_allowed_ips(types::WireguardAllowedIps { networks: vec![] })
+                        .await?;
+                    println!("All traffic will be routed through the tunnel");
+                    Ok(())
+                }
+                _ => unreachable!("unhandled command"),
+            },
+
+            Some(("liveness", matches)) => match matches.subcommand() {
+                Some(("get", _)) => Self::process_wireguard_liveness_get().await,
+                Some(("set", matches)) => Self::process_wireguard_liveness_set(matches).await,
+                Some(("reset", _)) => {
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_liveness(types::LivenessOptions::from(
+                        talpid_types::net::wireguard::LivenessOptions::default(),
+                    ))
+                    .await?;
+                    println!("Reset the liveness options");
+                    Ok(())
+                }
+                _ => unreachable!("unhandled command"),
+            },
+
             Some(("key", matches)) => match matches.subcommand() {
                 Some(("check", _)) => Self::process_wireguard_key_check().await,
-                Some(("regenerate", _)) => Self::process_wireguard_key_generate().await,
+                Some(("rotate", _)) => Self::process_wireguard_key_rotate().await,
                 Some(("rotation-interval", matches)) => match matches.subcommand() {
                     Some(("get", _)) => Self::process_wireguard_rotation_interval_get().await,
                     Some(("set", matches)) => {
@@ -180,6 +472,34 @@ impl Tunnel {
                 _ => unreachable!("unhandled command"),
             },
 
+            #[cfg(target_os = "linux")]
+            Some(("namespace", matches)) => match matches.subcommand() {
+                Some(("get", _)) => {
+                    let tunnel_options = Self::get_tunnel_options().await?;
+                    let namespace = tunnel_options.wireguard.unwrap().namespace;
+                    if namespace.is_empty() {
+                        println!("unset");
+                    } else {
+                        println!("{}", namespace);
+                    }
+                    Ok(())
+                }
+                Some(("set", matches)) => {
+                    let namespace = matches.value_of("name").unwrap();
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_namespace(namespace.to_owned()).await?;
+                    println!("The tunnel interface will be moved into {}", namespace);
+                    Ok(())
+                }
+                Some(("unset", _)) => {
+                    let mut rpc = new_rpc_client().await?;
+                    rpc.set_wireguard_namespace(String::new()).await?;
+                    println!("The tunnel interface will be kept in the root namespace");
+                    Ok(())
+                }
+                _ => unreachable!("unhandled command"),
+            },
+
             #[cfg(windows)]
             Some(("use-wireguard-nt", matches)) => match matches.subcommand() {
                 Some(("get", _)) => Self::process_wireguard_use_wg_nt_get().await,
@@ -205,8 +525,82 @@ impl Tunnel {
         Ok(())
     }
 
+    async fn process_wireguard_liveness_get() -> Result<()> {
+        let tunnel_options = Self::get_tunnel_options().await?;
+        let liveness = tunnel_options
+            .wireguard
+            .unwrap()
+            .liveness
+            .unwrap_or_else(|| {
+                types::LivenessOptions::from(
+                    talpid_types::net::wireguard::LivenessOptions::default(),
+                )
+            });
+        let duration = |duration: Option<types::Duration>| {
+            duration
+                .and_then(|duration| Duration::try_from(duration).ok())
+                .map(format_duration)
+                .unwrap_or_default()
+        };
+        println!("Probe interval : {}", duration(liveness.probe_interval));
+        println!("Probe timeout  : {}", duration(liveness.probe_timeout));
+        println!("Max failures   : {}", liveness.max_failures);
+        println!(
+            "Active probing : {}",
+            if liveness.active_probing { "on" } else { "off" }
+        );
+        Ok(())
+    }
+
+    async fn process_wireguard_liveness_set(matches: &clap::ArgMatches) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut liveness = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .tunnel_options
+            .and_then(|options| options.wireguard)
+            .and_then(|options| options.liveness)
+            .unwrap_or_else(|| {
+                types::LivenessOptions::from(
+                    talpid_types::net::wireguard::LivenessOptions::default(),
+                )
+            });
+
+        if let Some(value) = matches.value_of("probe-interval") {
+            let interval = parse_duration(value).ok_or(Error::InvalidCommand(
+                "invalid probe interval, expected e.g. 3s",
+            ))?;
+            liveness.probe_interval = Some(types::Duration::from(interval));
+        }
+        if let Some(value) = matches.value_of("probe-timeout") {
+            let timeout = parse_duration(value).ok_or(Error::InvalidCommand(
+                "invalid probe timeout, expected e.g. 3s",
+            ))?;
+            liveness.probe_timeout = Some(types::Duration::from(timeout));
+        }
+        if let Some(value) = matches.value_of("max-failures") {
+            liveness.max_failures = match value.parse::<u32>() {
+                Ok(failures) if failures > 0 => failures,
+                _ => {
+                    return Err(Error::InvalidCommand(
+                        "invalid number of failures, expected a positive number",
+                    ))
+                }
+            };
+        }
+        if let Some(value) = matches.value_of("active-probing") {
+            liveness.active_probing = value == "on";
+        }
+
+        rpc.set_wireguard_liveness(liveness).await?;
+        println!("Updated the liveness options");
+        Ok(())
+    }
+
     async fn process_wireguard_mtu_set(matches: &clap::ArgMatches) -> Result<()> {
         let mtu = matches.value_of_t_or_exit::<u16>("mtu");
+        let mtu = validate_mtu(mtu).map_err(Error::InvalidMtu)?;
         let mut rpc = new_rpc_client().await?;
         rpc.set_wireguard_mtu(mtu as u32).await?;
         println!("Wireguard MTU has been updated");
@@ -307,7 +701,7 @@ impl Tunnel {
         Ok(())
     }
 
-    async fn process_wireguard_key_generate() -> Result<()> {
+    async fn process_wireguard_key_rotate() -> Result<()> {
         let mut rpc = new_rpc_client().await?;
         rpc.rotate_wireguard_key(()).await?;
         println!("Rotated WireGuard key");
@@ -331,11 +725,14 @@ impl Tunnel {
 
     async fn process_wireguard_rotation_interval_set(matches: &clap::ArgMatches) -> Result<()> {
         let rotate_interval = matches.value_of_t_or_exit::<u64>("interval");
+        let interval = rotate_interval
+            .checked_mul(60 * 60)
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::MAX);
+        let interval = RotationInterval::new(interval).map_err(Error::InvalidRotationInterval)?;
         let mut rpc = new_rpc_client().await?;
-        rpc.set_wireguard_rotation_interval(types::Duration::from(Duration::from_secs(
-            60 * 60 * rotate_interval,
-        )))
-        .await?;
+        rpc.set_wireguard_rotation_interval(types::Duration::from(*interval.as_duration()))
+            .await?;
         println!("Set key rotation interval: {} hour(s)", rotate_interval);
         Ok(())
     }
@@ -360,6 +757,53 @@ impl Tunnel {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    async fn handle_rate_limit_cmd(matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("get", _)) => {
+                let tunnel_options = Self::get_tunnel_options().await?;
+                match tunnel_options.generic.unwrap().rate_limit {
+                    0 => println!("Rate limit: unset"),
+                    rate => println!("Rate limit: {}", format_bitrate(rate)),
+                }
+                Ok(())
+            }
+            Some(("set", matches)) => {
+                let rate = parse_rate(matches.value_of("rate").unwrap()).ok_or(
+                    Error::InvalidCommand("invalid rate, expected e.g. 500k or 10M"),
+                )?;
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_rate_limit(rate).await?;
+                println!("Rate limit: {}", format_bitrate(rate));
+                Ok(())
+            }
+            Some(("unset", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_rate_limit(0).await?;
+                println!("Removed the rate limit");
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+
+    async fn handle_retry_policy_cmd(matches: &clap::ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("get", _)) => Self::process_retry_policy_get().await,
+            Some(("set", matches)) => Self::process_retry_policy_set(matches).await,
+            Some(("reset", _)) => {
+                let mut rpc = new_rpc_client().await?;
+                rpc.set_retry_policy(types::RetryPolicy::from(
+                    talpid_types::net::RetryPolicy::default(),
+                ))
+                .await?;
+                println!("Reset the retry policy");
+                Ok(())
+            }
+            _ => unreachable!("unhandled command"),
+        }
+    }
+
     async fn process_openvpn_mssfix_get() -> Result<()> {
         let tunnel_options = Self::get_tunnel_options().await?;
         let mssfix = tunnel_options.openvpn.unwrap().mssfix;
@@ -425,6 +869,99 @@ impl Tunnel {
         Ok(())
     }
 
+    async fn process_retry_policy_get() -> Result<()> {
+        let tunnel_options = Self::get_tunnel_options().await?;
+        let policy = tunnel_options
+            .generic
+            .unwrap()
+            .retry_policy
+            .unwrap_or_default();
+        let initial_delay = policy
+            .initial_delay
+            .and_then(|delay| Duration::try_from(delay).ok())
+            .unwrap_or_default();
+        if initial_delay.is_zero() {
+            println!("Initial delay : none");
+        } else {
+            println!("Initial delay : {}", format_duration(initial_delay));
+        }
+        println!("Multiplier    : {}", policy.multiplier);
+        match policy
+            .max_delay
+            .and_then(|delay| Duration::try_from(delay).ok())
+        {
+            Some(max_delay) => println!("Maximum delay : {}", format_duration(max_delay)),
+            None => println!("Maximum delay : unlimited"),
+        }
+        if policy.max_attempts == 0 {
+            println!("Max attempts  : unlimited");
+        } else {
+            println!("Max attempts  : {}", policy.max_attempts);
+        }
+        Ok(())
+    }
+
+    async fn process_retry_policy_set(matches: &clap::ArgMatches) -> Result<()> {
+        let mut rpc = new_rpc_client().await?;
+        let mut policy = rpc
+            .get_settings(())
+            .await?
+            .into_inner()
+            .tunnel_options
+            .and_then(|options| options.generic)
+            .and_then(|options| options.retry_policy)
+            .unwrap_or_else(|| types::RetryPolicy::from(talpid_types::net::RetryPolicy::default()));
+
+        if let Some(value) = matches.value_of("initial-delay") {
+            let delay = if value == "none" {
+                Duration::ZERO
+            } else {
+                parse_duration(value).ok_or(Error::InvalidCommand(
+                    "invalid initial delay, expected e.g. 5s or 1m",
+                ))?
+            };
+            policy.initial_delay = Some(types::Duration::from(delay));
+        }
+        if let Some(value) = matches.value_of("multiplier") {
+            policy.multiplier = match value.parse::<u32>() {
+                Ok(multiplier) if multiplier > 0 => multiplier,
+                _ => {
+                    return Err(Error::InvalidCommand(
+                        "invalid multiplier, expected a positive number",
+                    ))
+                }
+            };
+        }
+        if let Some(value) = matches.value_of("max-delay") {
+            policy.max_delay = if value == "unlimited" {
+                None
+            } else {
+                Some(types::Duration::from(parse_duration(value).ok_or(
+                    Error::InvalidCommand("invalid maximum delay, expected e.g. 5m or 1h"),
+                )?))
+            };
+        }
+        if let Some(value) = matches.value_of("max-attempts") {
+            policy.max_attempts = if value == "unlimited" {
+                0
+            } else {
+                match value.parse::<u32>() {
+                    Ok(attempts) if attempts > 0 => attempts,
+                    _ => {
+                        return Err(Error::InvalidCommand(
+                            "invalid number of attempts, expected a positive number or \
+                            \"unlimited\"",
+                        ))
+                    }
+                }
+            };
+        }
+
+        rpc.set_retry_policy(policy).await?;
+        println!("Updated the retry policy");
+        Ok(())
+    }
+
     fn format_key_timestamp(timestamp: &Timestamp) -> String {
         let ndt = chrono::NaiveDateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32);
         let utc = chrono::DateTime::<chrono::Utc>::from_utc(ndt, chrono::Utc);
@@ -119,6 +119,8 @@ fn format_relay_connection(relay_info: &TunnelStateRelayInfo, verbose: bool) ->
 
     let mut bridge_type = String::new();
     let mut obfuscator_type = String::new();
+    let mut mtu = String::new();
+    let mut wireguard_implementation = String::new();
     if verbose {
         if let Some(bridge) = endpoint.proxy.as_ref() {
             let bridge = match ProxyType::from_i32(bridge.proxy_type).expect("invalid proxy type") {
@@ -131,19 +133,30 @@ fn format_relay_connection(relay_info: &TunnelStateRelayInfo, verbose: bool) ->
             let obfuscation = convert_obfuscator_type(obfuscator.obfuscation_type);
             obfuscator_type = format!("\nObfuscator: {obfuscation}");
         }
+        if endpoint.mtu != 0 {
+            mtu = format!("\nTunnel MTU: {}", endpoint.mtu);
+        }
+        if !endpoint.wireguard_implementation.is_empty() {
+            wireguard_implementation = format!(
+                "\nWireGuard implementation: {}",
+                endpoint.wireguard_implementation
+            );
+        }
     }
 
     format!(
-        "{exit_endpoint}{first_hop}{bridge}{obfuscator}{tunnel_type}{quantum_resistant}{bridge_type}{obfuscator_type}",
+        "{exit_endpoint}{first_hop}{bridge}{obfuscator}{tunnel_type}{quantum_resistant}{bridge_type}{obfuscator_type}{mtu}{wireguard_implementation}",
         first_hop = first_hop.unwrap_or_default(),
         bridge = bridge.unwrap_or_default(),
         obfuscator = obfuscator.unwrap_or_default(),
     )
 }
 
-fn convert_obfuscator_type(obfuscator: i32) -> &'static str {
+pub fn convert_obfuscator_type(obfuscator: i32) -> &'static str {
     match ObfuscationType::from_i32(obfuscator).expect("invalid obfuscator type") {
         ObfuscationType::Udp2tcp => "Udp2Tcp",
+        ObfuscationType::External => "external transport",
+        ObfuscationType::Wss => "WebSocket over TLS",
     }
 }
 
@@ -179,7 +192,7 @@ fn print_error_state(error_state: &ErrorState) {
     }
 }
 
-fn error_state_to_string(error_state: &ErrorState) -> String {
+pub fn error_state_to_string(error_state: &ErrorState) -> String {
     use ErrorStateCause::*;
 
     let error_str = match ErrorStateCause::from_i32(error_state.cause).expect("unknown error cause")
@@ -211,6 +224,16 @@ fn error_state_to_string(error_state: &ErrorState) -> String {
         VpnPermissionDenied => "The Android VPN permission was denied when creating the tunnel",
         #[cfg(target_os = "windows")]
         SplitTunnelError => "The split tunneling module reported an error",
+        StrictEnforcement => {
+            return format!(
+                "Blocking since strict enforcement is enabled and the tunnel could not be fully \
+                set up: {}",
+                error_state.strict_enforcement_failure
+            );
+        }
+        RetryLimitReached => {
+            "Gave up connecting since the retry policy's maximum number of attempts was reached"
+        }
         #[cfg(not(target_os = "android"))]
         _ => unreachable!("unknown error cause"),
     };
@@ -242,7 +265,7 @@ fn policy_error_to_string(policy_error: &FirewallPolicyError) -> String {
     format!("Failed to set firewall policy: {}", cause)
 }
 
-fn format_protocol(protocol: TransportProtocol) -> &'static str {
+pub fn format_protocol(protocol: TransportProtocol) -> &'static str {
     match protocol {
         TransportProtocol::Udp => "UDP",
         TransportProtocol::Tcp => "TCP",
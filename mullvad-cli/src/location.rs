@@ -55,6 +55,7 @@ pub fn get_constraint<T: AsRef<str>>(
             country,
             city,
             hostname,
+            ..Default::default()
         },
         (..) => clap::Error::raw(
             clap::ErrorKind::InvalidValue,
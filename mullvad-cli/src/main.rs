@@ -7,11 +7,16 @@ use mullvad_management_interface::async_trait;
 use std::{collections::HashMap, io};
 use talpid_types::ErrorExt;
 
-pub use mullvad_management_interface::{self, new_rpc_client};
+pub use mullvad_management_interface;
+use mullvad_management_interface::{
+    negotiate_version, new_negotiated_rpc_client, remote, ManagementServiceClient,
+};
 
 mod cmds;
 mod format;
 mod location;
+mod relay_expression;
+mod share;
 mod state;
 
 pub const BIN_NAME: &str = "mullvad";
@@ -43,6 +48,39 @@ pub enum Error {
     #[error(display = "Command failed: {}", _0)]
     CommandFailed(&'static str),
 
+    #[error(display = "Invalid constraint expression: {}", _0)]
+    InvalidExpression(String),
+
+    #[error(display = "{}", _0)]
+    InvalidRotationInterval(mullvad_types::wireguard::RotationIntervalError),
+
+    #[error(display = "{}", _0)]
+    InvalidMtu(mullvad_types::wireguard::MtuError),
+
+    #[error(display = "{}", _0)]
+    InvalidPersistentKeepalive(mullvad_types::wireguard::PersistentKeepaliveError),
+
+    #[error(display = "Invalid proxy settings: {}", _0)]
+    InvalidProxySettings(String),
+
+    #[error(display = "Failed to read or write settings bundle")]
+    SettingsBundleIo(#[error(source, no_from)] io::Error),
+
+    #[error(display = "Failed to parse settings bundle")]
+    InvalidSettingsBundle(#[error(source)] serde_json::Error),
+
+    #[error(display = "Failed to read settings to apply")]
+    SettingsPatchIo(#[error(source, no_from)] io::Error),
+
+    #[error(display = "Failed to read relay overrides")]
+    RelayOverridesIo(#[error(source, no_from)] io::Error),
+
+    #[error(display = "Failed to parse relay overrides")]
+    InvalidRelayOverrides(#[error(source, no_from)] serde_json::Error),
+
+    #[error(display = "Failed to write snapshot")]
+    SnapshotIo(#[error(source, no_from)] io::Error),
+
     #[error(display = "Failed to listen for status updates")]
     StatusListenerFailed,
 
@@ -54,6 +92,18 @@ pub enum Error {
     Other(&'static str),
 }
 
+/// Connects to the local daemon, or to a remote daemon if `MULLVAD_MANAGEMENT_REMOTE` is set.
+pub async fn new_rpc_client() -> Result<ManagementServiceClient> {
+    match remote::ClientConfig::from_env()? {
+        Some(config) => {
+            let mut rpc = remote::new_remote_rpc_client(config).await?;
+            negotiate_version(&mut rpc).await?;
+            Ok(rpc)
+        }
+        None => Ok(new_negotiated_rpc_client().await?),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let exit_code = match run().await {
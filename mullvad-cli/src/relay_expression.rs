@@ -0,0 +1,197 @@
+//! Parses compact relay constraint expressions, such as
+//! `country:se city:got ownership:owned protocol:wireguard port:443`, so that several
+//! constraints can be set at once.
+
+use crate::{location, Error, Result};
+use mullvad_management_interface::types;
+use mullvad_types::relay_constraints::Constraint;
+
+/// The constraints given in an expression. Constraints that are left out are `None`, and are not
+/// changed.
+#[derive(Debug, Default)]
+pub struct RelayExpression {
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub hostname: Option<String>,
+    pub providers: Option<ProviderExpression>,
+    pub ownership: Option<types::Ownership>,
+    pub datacenter: Option<String>,
+    pub tunnel_type: Option<Constraint<types::TunnelType>>,
+    pub transport_protocol: Option<Constraint<types::TransportProtocol>>,
+    pub port: Option<Constraint<u16>>,
+    pub ip_version: Option<Constraint<types::IpVersion>>,
+}
+
+#[derive(Debug)]
+pub enum ProviderExpression {
+    /// Only use relays hosted by these providers. Empty means any provider.
+    Only(Vec<String>),
+    /// Use relays hosted by any provider except these.
+    Except(Vec<String>),
+}
+
+pub const KEYS_HELP: &str = "Space separated key:value pairs. Supported keys are \
+    country, city, hostname, provider, ownership, datacenter, protocol, transport, port and ipv. \
+    Every value can be 'any'. Several providers are separated by commas, and a leading '!' \
+    excludes providers or negates the ownership. Example: \
+    \"country:se city:got ownership:!rented protocol:wireguard port:443\"";
+
+impl RelayExpression {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let mut parsed = RelayExpression::default();
+
+        for token in expression.split_whitespace() {
+            let (key, value) = token.split_once(':').ok_or_else(|| {
+                Error::InvalidExpression(format!("expected key:value, got '{}'", token))
+            })?;
+            let value = value.to_lowercase();
+            if value.is_empty() {
+                return Err(Error::InvalidExpression(format!(
+                    "missing value for '{}'",
+                    key
+                )));
+            }
+
+            match key.to_lowercase().as_str() {
+                "country" => {
+                    location::country_code_validator(&value).map_err(Error::InvalidExpression)?;
+                    set_once(&mut parsed.country, key, value)?;
+                }
+                "city" => {
+                    location::city_code_validator(&value).map_err(Error::InvalidExpression)?;
+                    set_once(&mut parsed.city, key, value)?;
+                }
+                "hostname" => set_once(&mut parsed.hostname, key, value)?,
+                "provider" => {
+                    let providers = parse_providers(token.split_once(':').unwrap().1)?;
+                    set_once(&mut parsed.providers, key, providers)?;
+                }
+                "ownership" => {
+                    let ownership = match value.as_str() {
+                        "any" => types::Ownership::Any,
+                        "owned" | "!rented" => types::Ownership::MullvadOwned,
+                        "rented" | "!owned" => types::Ownership::Rented,
+                        _ => return Err(invalid_value(key, &value)),
+                    };
+                    set_once(&mut parsed.ownership, key, ownership)?;
+                }
+                "datacenter" => set_once(&mut parsed.datacenter, key, value)?,
+                "protocol" | "tunnel" => {
+                    let tunnel_type = match value.as_str() {
+                        "any" => Constraint::Any,
+                        "wireguard" => Constraint::Only(types::TunnelType::Wireguard),
+                        "openvpn" => Constraint::Only(types::TunnelType::Openvpn),
+                        _ => return Err(invalid_value(key, &value)),
+                    };
+                    set_once(&mut parsed.tunnel_type, key, tunnel_type)?;
+                }
+                "transport" => {
+                    let protocol = match value.as_str() {
+                        "any" => Constraint::Any,
+                        "udp" => Constraint::Only(types::TransportProtocol::Udp),
+                        "tcp" => Constraint::Only(types::TransportProtocol::Tcp),
+                        _ => return Err(invalid_value(key, &value)),
+                    };
+                    set_once(&mut parsed.transport_protocol, key, protocol)?;
+                }
+                "port" => {
+                    let port = match value.as_str() {
+                        "any" => Constraint::Any,
+                        port => Constraint::Only(
+                            port.parse::<u16>()
+                                .map_err(|_| invalid_value(key, &value))?,
+                        ),
+                    };
+                    set_once(&mut parsed.port, key, port)?;
+                }
+                "ipv" => {
+                    let ip_version = match value.as_str() {
+                        "any" => Constraint::Any,
+                        "4" => Constraint::Only(types::IpVersion::V4),
+                        "6" => Constraint::Only(types::IpVersion::V6),
+                        _ => return Err(invalid_value(key, &value)),
+                    };
+                    set_once(&mut parsed.ip_version, key, ip_version)?;
+                }
+                _ => return Err(Error::InvalidExpression(format!("unknown key '{}'", key))),
+            }
+        }
+
+        parsed.validate()?;
+        Ok(parsed)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.country.as_deref() == Some("any")
+            && (self.city.is_some() || self.hostname.is_some())
+        {
+            return Err(Error::InvalidExpression(
+                "a city or hostname can't be given with 'country:any'".to_owned(),
+            ));
+        }
+        if self.city.is_some() && self.country.is_none() {
+            return Err(Error::InvalidExpression(
+                "a city can only be given together with a country".to_owned(),
+            ));
+        }
+        if self.hostname.as_deref() == Some("any") {
+            return Err(Error::InvalidExpression(
+                "use 'country:any' to clear the location".to_owned(),
+            ));
+        }
+        if self.tunnel_type == Some(Constraint::Only(types::TunnelType::Wireguard))
+            && self.transport_protocol.is_some()
+        {
+            return Err(Error::InvalidExpression(
+                "a transport protocol can only be given for OpenVPN".to_owned(),
+            ));
+        }
+        if self.tunnel_type == Some(Constraint::Only(types::TunnelType::Openvpn))
+            && self.ip_version.is_some()
+        {
+            return Err(Error::InvalidExpression(
+                "an IP version can only be given for WireGuard".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_providers(value: &str) -> Result<ProviderExpression> {
+    if value.eq_ignore_ascii_case("any") {
+        return Ok(ProviderExpression::Only(vec![]));
+    }
+    let (excluded, value) = match value.strip_prefix('!') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let providers: Vec<String> = value.split(',').map(str::to_owned).collect();
+    if providers.iter().any(|provider| provider.is_empty()) {
+        return Err(Error::InvalidExpression("empty provider name".to_owned()));
+    }
+    if providers.iter().any(|provider| provider.starts_with('!')) {
+        return Err(Error::InvalidExpression(
+            "'!' must come before the first provider and applies to all of them".to_owned(),
+        ));
+    }
+    Ok(if excluded {
+        ProviderExpression::Except(providers)
+    } else {
+        ProviderExpression::Only(providers)
+    })
+}
+
+fn set_once<T>(field: &mut Option<T>, key: &str, value: T) -> Result<()> {
+    if field.is_some() {
+        return Err(Error::InvalidExpression(format!(
+            "'{}' is given more than once",
+            key
+        )));
+    }
+    *field = Some(value);
+    Ok(())
+}
+
+fn invalid_value(key: &str, value: &str) -> Error {
+    Error::InvalidExpression(format!("invalid value '{}' for '{}'", value, key))
+}
@@ -0,0 +1,105 @@
+//! A text format for sharing relay constraints and obfuscation settings, e.g. on a web page or
+//! in a QR code.
+//!
+//! A payload looks like `mullvad1.<data>.<checksum>`, where `<data>` is the URL-safe base64
+//! encoding of the settings as JSON, and `<checksum>` is the CRC-32 of the JSON as hexadecimal.
+//! The payload is not signed. The checksum only catches payloads that were damaged or cut off
+//! when copied, so an imported payload must always be shown to the user before it is applied.
+
+use crate::{Error, Result};
+use mullvad_types::relay_constraints::{
+    ExternalObfuscationSettings, ObfuscationSettings, RelayConstraints, SelectedObfuscation,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const PREFIX: &str = "mullvad1";
+
+/// Relay constraints and obfuscation settings that can be shared with others.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedConstraints {
+    pub relay_constraints: RelayConstraints,
+    /// The settings of the external transport are never shared, since they name a program that
+    /// would run on the machine of whoever imports the payload.
+    pub obfuscation: ObfuscationSettings,
+}
+
+impl SharedConstraints {
+    pub fn new(relay_constraints: RelayConstraints, obfuscation: ObfuscationSettings) -> Self {
+        SharedConstraints {
+            relay_constraints,
+            obfuscation: ObfuscationSettings {
+                external: ExternalObfuscationSettings::default(),
+                ..obfuscation
+            },
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("Failed to serialize shared constraints");
+        format!(
+            "{}.{}.{:08x}",
+            PREFIX,
+            base64::encode_config(&json, base64::URL_SAFE_NO_PAD),
+            crc32(&json)
+        )
+    }
+
+    pub fn decode(payload: &str) -> Result<Self> {
+        let mut parts = payload.trim().split('.');
+        let (prefix, data, checksum) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(prefix), Some(data), Some(checksum)) if parts.next().is_none() => {
+                (prefix, data, checksum)
+            }
+            _ => return Err(Error::InvalidCommand("Not a relay constraints payload")),
+        };
+        if prefix != PREFIX {
+            return Err(Error::CommandFailed(
+                "The payload was created by a newer version of the app",
+            ));
+        }
+
+        let damaged = || Error::InvalidCommand("The payload is damaged or incomplete");
+        let json = base64::decode_config(data, base64::URL_SAFE_NO_PAD).map_err(|_| damaged())?;
+        let checksum = u32::from_str_radix(checksum, 16).map_err(|_| damaged())?;
+        if crc32(&json) != checksum {
+            return Err(damaged());
+        }
+        let shared: Self = serde_json::from_slice(&json).map_err(|_| damaged())?;
+        if shared.obfuscation.selected_obfuscation == SelectedObfuscation::External {
+            return Err(Error::CommandFailed(
+                "The payload selects an external transport, which cannot be shared",
+            ));
+        }
+        Ok(shared)
+    }
+}
+
+impl fmt::Display for SharedConstraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Relay constraints : {}", self.relay_constraints)?;
+        write!(
+            f,
+            "Obfuscation       : {}",
+            self.obfuscation.selected_obfuscation
+        )?;
+        match self.obfuscation.selected_obfuscation {
+            SelectedObfuscation::Udp2Tcp => write!(f, " over {}", self.obfuscation.udp2tcp),
+            SelectedObfuscation::Wss => write!(f, " over {}", self.obfuscation.wss),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// CRC-32 as used by zlib and PNG.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
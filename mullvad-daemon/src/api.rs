@@ -10,6 +10,7 @@ use mullvad_api::{
     ApiEndpointUpdateCallback,
 };
 use mullvad_relay_selector::RelaySelector;
+use mullvad_types::access_method::{AccessMethod, ApiAccessMethodSettings};
 use std::{
     net::SocketAddr,
     path::PathBuf,
@@ -29,17 +30,20 @@ use talpid_types::{
 ///
 /// When `mullvad-api` fails to contact the API, it requests a new connection mode.
 /// The API can be connected to either directly (i.e., [`ApiConnectionMode::Direct`])
-/// or from a bridge ([`ApiConnectionMode::Proxied`]).
+/// or from a proxy ([`ApiConnectionMode::Proxied`]).
 ///
-/// * Every 3rd attempt returns [`ApiConnectionMode::Direct`].
-/// * Any other attempt returns a configuration for the bridge that is closest to the selected relay
-///   location and matches all bridge constraints.
-/// * When no matching bridge is found, e.g. if the selected hosting providers don't match any
-///   bridge, [`ApiConnectionMode::Direct`] is returned.
+/// * Each attempt uses the next enabled access method in the API access method settings,
+///   starting over from the first one once all of them have been tried.
+/// * [`AccessMethod::Bridges`] returns a configuration for the bridge that is closest to the
+///   selected relay location and matches all bridge constraints. When no matching bridge is found,
+///   e.g. if the selected hosting providers don't match any bridge, [`ApiConnectionMode::Direct`]
+///   is returned.
+/// * When no access method is enabled, [`ApiConnectionMode::Direct`] is returned.
 pub struct ApiConnectionModeProvider {
     cache_dir: PathBuf,
 
     relay_selector: RelaySelector,
+    access_methods: Arc<Mutex<ApiAccessMethodSettings>>,
     retry_attempt: u32,
 
     current_task: Option<Pin<Box<dyn Future<Output = ApiConnectionMode> + Send>>>,
@@ -64,22 +68,7 @@ impl Stream for ApiConnectionModeProvider {
         }
 
         // Create a new task.
-        let config = if Self::should_use_bridge(self.retry_attempt) {
-            self.relay_selector
-                .get_bridge_forced()
-                .map(|settings| match settings {
-                    ProxySettings::Shadowsocks(ss_settings) => {
-                        ApiConnectionMode::Proxied(ProxyConfig::Shadowsocks(ss_settings))
-                    }
-                    _ => {
-                        log::error!("Received unexpected proxy settings type");
-                        ApiConnectionMode::Direct
-                    }
-                })
-                .unwrap_or(ApiConnectionMode::Direct)
-        } else {
-            ApiConnectionMode::Direct
-        };
+        let config = self.next_connection_mode();
 
         self.retry_attempt = self.retry_attempt.wrapping_add(1);
 
@@ -99,19 +88,79 @@ impl Stream for ApiConnectionModeProvider {
 }
 
 impl ApiConnectionModeProvider {
-    pub(crate) fn new(cache_dir: PathBuf, relay_selector: RelaySelector) -> Self {
+    pub(crate) fn new(
+        cache_dir: PathBuf,
+        relay_selector: RelaySelector,
+        access_methods: ApiAccessMethodSettings,
+    ) -> Self {
         Self {
             cache_dir,
 
             relay_selector,
+            access_methods: Arc::new(Mutex::new(access_methods)),
             retry_attempt: 0,
 
             current_task: None,
         }
     }
 
-    fn should_use_bridge(retry_attempt: u32) -> bool {
-        retry_attempt % 3 > 0
+    /// Returns a handle that can be used to replace the access methods after the provider has
+    /// been handed to the API runtime.
+    pub(crate) fn access_methods_handle(&self) -> AccessMethodsHandle {
+        AccessMethodsHandle {
+            access_methods: self.access_methods.clone(),
+        }
+    }
+
+    fn next_connection_mode(&self) -> ApiConnectionMode {
+        let access_method = {
+            let access_methods = self.access_methods.lock().unwrap();
+            let enabled: Vec<_> = access_methods.enabled().collect();
+            if enabled.is_empty() {
+                return ApiConnectionMode::Direct;
+            }
+            let method = enabled[self.retry_attempt as usize % enabled.len()];
+            log::debug!("Using API access method \"{}\"", method.name);
+            method.access_method.clone()
+        };
+
+        match access_method {
+            AccessMethod::Direct => ApiConnectionMode::Direct,
+            AccessMethod::Bridges => self
+                .relay_selector
+                .get_bridge_forced()
+                .map(|settings| match settings {
+                    ProxySettings::Shadowsocks(ss_settings) => {
+                        ApiConnectionMode::Proxied(ProxyConfig::Shadowsocks(ss_settings))
+                    }
+                    _ => {
+                        log::error!("Received unexpected proxy settings type");
+                        ApiConnectionMode::Direct
+                    }
+                })
+                .unwrap_or(ApiConnectionMode::Direct),
+            AccessMethod::Shadowsocks(settings) => {
+                ApiConnectionMode::Proxied(ProxyConfig::Shadowsocks(settings))
+            }
+            AccessMethod::Socks5(settings) => {
+                ApiConnectionMode::Proxied(ProxyConfig::Socks5(settings))
+            }
+            AccessMethod::Http(settings) => ApiConnectionMode::Proxied(ProxyConfig::Http(settings)),
+        }
+    }
+}
+
+/// Updates the access methods used by an [`ApiConnectionModeProvider`].
+#[derive(Clone)]
+pub(crate) struct AccessMethodsHandle {
+    access_methods: Arc<Mutex<ApiAccessMethodSettings>>,
+}
+
+impl AccessMethodsHandle {
+    /// Replaces the access methods. They are used from the next time that a new connection mode
+    /// is requested.
+    pub fn set(&self, access_methods: ApiAccessMethodSettings) {
+        *self.access_methods.lock().unwrap() = access_methods;
     }
 }
 
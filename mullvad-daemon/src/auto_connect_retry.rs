@@ -0,0 +1,170 @@
+use mullvad_types::states::TunnelState;
+use std::time::Duration;
+use talpid_types::tunnel::{ErrorState, ErrorStateCause};
+
+/// Delays before each retry when auto-connecting at startup ends up in the error state. Drivers
+/// and network adapters are often not ready right after boot.
+const RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(5),
+    Duration::from_secs(15),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(2 * 60),
+    Duration::from_secs(5 * 60),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Reconnect after the given delay.
+    Retry(Duration),
+    /// Retrying did not help. Contains the number of retries that were made.
+    GiveUp(u32),
+    /// Nothing needs to be done.
+    Wait,
+}
+
+/// Keeps retrying the connection that is made by auto-connect at startup while it ends up in the
+/// error state, until it connects, the user disconnects or the retries run out.
+#[derive(Debug)]
+pub struct AutoConnectRetry {
+    active: bool,
+    retries: usize,
+    keep_retrying: bool,
+}
+
+impl AutoConnectRetry {
+    /// With `keep_retrying`, the retries never run out, and the longest delay is used once the
+    /// others have been tried. This is used when auto-connect is a machine-wide policy, since no
+    /// one may be logged on to connect once auto-connect has given up.
+    pub fn new(auto_connected: bool, keep_retrying: bool) -> Self {
+        AutoConnectRetry {
+            active: auto_connected,
+            retries: 0,
+            keep_retrying,
+        }
+    }
+
+    /// Returns what to do after the tunnel has entered `new_state`.
+    pub fn update(&mut self, new_state: &TunnelState) -> RetryDecision {
+        if !self.active {
+            return RetryDecision::Wait;
+        }
+        match new_state {
+            TunnelState::Connected { .. } | TunnelState::Disconnected => {
+                self.active = false;
+                RetryDecision::Wait
+            }
+            TunnelState::Error(error_state) if is_retryable(error_state) => {
+                match RETRY_DELAYS.get(self.retries) {
+                    Some(delay) => {
+                        self.retries += 1;
+                        RetryDecision::Retry(*delay)
+                    }
+                    None if self.keep_retrying => {
+                        RetryDecision::Retry(*RETRY_DELAYS.last().unwrap())
+                    }
+                    None => {
+                        self.active = false;
+                        RetryDecision::GiveUp(self.retries as u32)
+                    }
+                }
+            }
+            TunnelState::Connecting { .. }
+            | TunnelState::Disconnecting(_)
+            | TunnelState::Error(_) => RetryDecision::Wait,
+        }
+    }
+}
+
+/// Returns whether the error may go away by itself shortly after boot. Failed authentication is
+/// already retried by the daemon, and the tunnel state machine leaves the offline state by itself
+/// once the device comes online.
+fn is_retryable(error_state: &ErrorState) -> bool {
+    match error_state.cause() {
+        ErrorStateCause::SetFirewallPolicyError(_)
+        | ErrorStateCause::SetDnsError
+        | ErrorStateCause::StartTunnelError
+        | ErrorStateCause::TunnelParameterError(_) => true,
+        #[cfg(target_os = "windows")]
+        ErrorStateCause::SplitTunnelError => true,
+        #[cfg(not(target_os = "android"))]
+        ErrorStateCause::StrictEnforcement(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error(cause: ErrorStateCause) -> TunnelState {
+        TunnelState::Error(ErrorState::new(cause, None))
+    }
+
+    #[test]
+    fn test_retries_with_increasing_delays() {
+        let mut retry = AutoConnectRetry::new(true, false);
+        for delay in RETRY_DELAYS {
+            assert_eq!(
+                retry.update(&error(ErrorStateCause::StartTunnelError)),
+                RetryDecision::Retry(*delay)
+            );
+        }
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::StartTunnelError)),
+            RetryDecision::GiveUp(RETRY_DELAYS.len() as u32)
+        );
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::StartTunnelError)),
+            RetryDecision::Wait
+        );
+    }
+
+    #[test]
+    fn test_stops_after_disconnect() {
+        let mut retry = AutoConnectRetry::new(true, false);
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::IsOffline)),
+            RetryDecision::Wait
+        );
+        assert_eq!(
+            retry.update(&TunnelState::Disconnected),
+            RetryDecision::Wait
+        );
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::StartTunnelError)),
+            RetryDecision::Wait
+        );
+
+        let mut retry = AutoConnectRetry::new(false, false);
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::StartTunnelError)),
+            RetryDecision::Wait
+        );
+    }
+
+    #[test]
+    fn test_keeps_retrying_for_machine_scope() {
+        let mut retry = AutoConnectRetry::new(true, true);
+        for delay in RETRY_DELAYS {
+            assert_eq!(
+                retry.update(&error(ErrorStateCause::StartTunnelError)),
+                RetryDecision::Retry(*delay)
+            );
+        }
+        for _ in 0..3 {
+            assert_eq!(
+                retry.update(&error(ErrorStateCause::StartTunnelError)),
+                RetryDecision::Retry(*RETRY_DELAYS.last().unwrap())
+            );
+        }
+        assert_eq!(
+            retry.update(&TunnelState::Disconnected),
+            RetryDecision::Wait
+        );
+        assert_eq!(
+            retry.update(&error(ErrorStateCause::StartTunnelError)),
+            RetryDecision::Wait
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use mullvad_types::{
+    access_method::{AccessMethod, ApiAccessMethod, ApiAccessMethodSettings},
+    interference::{CheckKind, CheckOutcome, InterferenceReport},
+    relay_constraints::{Constraint, ObfuscationSettings, SelectedObfuscation},
+    settings::Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const CHANGES_FILE: &str = "circumvention-changes.json";
+
+/// Port that the udp2tcp obfuscation is set to, since it is the port least likely to be blocked.
+const UDP2TCP_PORT: u16 = 443;
+
+/// A setting that the circumvention assistant changed, along with its previous value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "setting")]
+pub enum ChangedSetting {
+    Obfuscation {
+        previous: ObfuscationSettings,
+        applied: ObfuscationSettings,
+    },
+    ApiAccessMethods {
+        previous: ApiAccessMethodSettings,
+        applied: ApiAccessMethodSettings,
+    },
+}
+
+impl ChangedSetting {
+    /// Name of the setting, which identifies the change.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangedSetting::Obfuscation { .. } => "obfuscation",
+            ChangedSetting::ApiAccessMethods { .. } => "api_access_methods",
+        }
+    }
+
+    /// Returns whether the setting still has the value that the assistant set.
+    pub fn is_current(&self, settings: &Settings) -> bool {
+        match self {
+            ChangedSetting::Obfuscation { applied, .. } => {
+                settings.obfuscation_settings == *applied
+            }
+            ChangedSetting::ApiAccessMethods { applied, .. } => {
+                settings.api_access_methods == *applied
+            }
+        }
+    }
+}
+
+impl fmt::Display for ChangedSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangedSetting::Obfuscation { applied, .. } => {
+                write!(f, "Set obfuscation to udp2tcp on {}", applied.udp2tcp)
+            }
+            ChangedSetting::ApiAccessMethods { .. } => {
+                "Enabled bridges and made them the first API access method".fmt(f)
+            }
+        }
+    }
+}
+
+/// A change made by the circumvention assistant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircumventionChange {
+    pub setting: ChangedSetting,
+    /// Why the change was made.
+    pub reason: String,
+    pub applied_at: SystemTime,
+}
+
+/// Decides which settings to change to get around the interference found in `report`. Settings
+/// that already get around it are left as they are.
+pub fn plan(report: &InterferenceReport, settings: &Settings) -> Vec<CircumventionChange> {
+    let failed = |kind| report.outcome(kind) == Some(CheckOutcome::Failed);
+    let mut changes = vec![];
+
+    // WebSocket over TLS already avoids plain UDP and is harder to block than udp2tcp.
+    if failed(CheckKind::WireguardUdp)
+        && !matches!(
+            settings.obfuscation_settings.selected_obfuscation,
+            SelectedObfuscation::Udp2Tcp | SelectedObfuscation::Wss
+        )
+    {
+        let mut applied = settings.obfuscation_settings.clone();
+        applied.selected_obfuscation = SelectedObfuscation::Udp2Tcp;
+        applied.udp2tcp.port = Constraint::Only(UDP2TCP_PORT);
+        changes.push(CircumventionChange {
+            setting: ChangedSetting::Obfuscation {
+                previous: settings.obfuscation_settings.clone(),
+                applied,
+            },
+            reason: "WireGuard over UDP appears to be blocked".to_owned(),
+            applied_at: SystemTime::now(),
+        });
+    }
+
+    if failed(CheckKind::ApiTcp) || failed(CheckKind::ApiTls) {
+        let applied = prefer_bridges(&settings.api_access_methods);
+        if applied != settings.api_access_methods {
+            changes.push(CircumventionChange {
+                setting: ChangedSetting::ApiAccessMethods {
+                    previous: settings.api_access_methods.clone(),
+                    applied,
+                },
+                reason: "The API appears to be blocked or intercepted".to_owned(),
+                applied_at: SystemTime::now(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Returns the access methods with the bridges enabled and moved first. They are added if they
+/// have been removed.
+fn prefer_bridges(access_methods: &ApiAccessMethodSettings) -> ApiAccessMethodSettings {
+    let (mut methods, others): (Vec<_>, Vec<_>) = access_methods
+        .methods
+        .iter()
+        .cloned()
+        .partition(|method| method.access_method == AccessMethod::Bridges);
+    if methods.is_empty() {
+        methods.push(ApiAccessMethod {
+            name: "bridges".to_owned(),
+            enabled: true,
+            access_method: AccessMethod::Bridges,
+        });
+    }
+    for method in &mut methods {
+        method.enabled = true;
+    }
+    methods.extend(others);
+    ApiAccessMethodSettings { methods }
+}
+
+/// Keeps a record of the changes made by the circumvention assistant, so that they can be
+/// reverted, also by a later daemon instance.
+pub struct CircumventionChanges {
+    path: PathBuf,
+    changes: Vec<CircumventionChange>,
+}
+
+impl CircumventionChanges {
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(CHANGES_FILE);
+        let changes = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse circumvention changes")
+                );
+                vec![]
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read circumvention changes")
+                    );
+                }
+                vec![]
+            }
+        };
+        CircumventionChanges { path, changes }
+    }
+
+    pub fn list(&self) -> Vec<CircumventionChange> {
+        self.changes.clone()
+    }
+
+    /// Records a change. If the same setting has been changed before, the value from before the
+    /// first change is kept as the one to revert to.
+    pub async fn insert(&mut self, mut change: CircumventionChange) {
+        if let Some(index) = self
+            .changes
+            .iter()
+            .position(|existing| existing.setting.name() == change.setting.name())
+        {
+            let existing = self.changes.remove(index);
+            change.setting = match (existing.setting, change.setting) {
+                (
+                    ChangedSetting::Obfuscation { previous, .. },
+                    ChangedSetting::Obfuscation { applied, .. },
+                ) => ChangedSetting::Obfuscation { previous, applied },
+                (
+                    ChangedSetting::ApiAccessMethods { previous, .. },
+                    ChangedSetting::ApiAccessMethods { applied, .. },
+                ) => ChangedSetting::ApiAccessMethods { previous, applied },
+                (_, setting) => setting,
+            };
+        }
+        self.changes.push(change);
+        self.save().await;
+    }
+
+    /// Removes and returns all recorded changes.
+    pub async fn take(&mut self) -> Vec<CircumventionChange> {
+        let changes = std::mem::take(&mut self.changes);
+        self.save().await;
+        changes
+    }
+
+    async fn save(&self) {
+        if self.changes.is_empty() {
+            if let Err(error) = fs::remove_file(&self.path).await {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to delete circumvention changes")
+                    );
+                }
+            }
+            return;
+        }
+
+        match serde_json::to_string(&self.changes) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write circumvention changes")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize circumvention changes")
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::offset::Utc;
+    use mullvad_types::interference::InterferenceCheck;
+
+    fn report(failed: &[CheckKind]) -> InterferenceReport {
+        InterferenceReport {
+            created: Utc::now(),
+            connection_failures: 3,
+            checks: failed
+                .iter()
+                .map(|kind| InterferenceCheck {
+                    kind: *kind,
+                    outcome: CheckOutcome::Failed,
+                    details: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let settings = Settings::default();
+        assert!(plan(&report(&[]), &settings).is_empty());
+        // Bridges are already tried after the direct method by default, which is not enough
+        let changes = plan(
+            &report(&[CheckKind::WireguardUdp, CheckKind::ApiTls]),
+            &settings,
+        );
+        assert_eq!(changes.len(), 2);
+
+        match &changes[0].setting {
+            ChangedSetting::Obfuscation { applied, .. } => {
+                assert_eq!(applied.selected_obfuscation, SelectedObfuscation::Udp2Tcp);
+                assert_eq!(applied.udp2tcp.port, Constraint::Only(UDP2TCP_PORT));
+            }
+            setting => panic!("unexpected change: {:?}", setting),
+        }
+        match &changes[1].setting {
+            ChangedSetting::ApiAccessMethods { applied, .. } => {
+                assert_eq!(applied.methods[0].access_method, AccessMethod::Bridges);
+                assert_eq!(applied.methods.len(), 2);
+            }
+            setting => panic!("unexpected change: {:?}", setting),
+        }
+    }
+
+    #[test]
+    fn test_prefer_bridges_adds_missing_bridges() {
+        let mut access_methods = ApiAccessMethodSettings::default();
+        access_methods
+            .methods
+            .retain(|method| method.access_method != AccessMethod::Bridges);
+        let applied = prefer_bridges(&access_methods);
+        assert_eq!(applied.methods[0].access_method, AccessMethod::Bridges);
+        assert!(applied.methods[0].enabled);
+        assert_eq!(applied.methods[1].access_method, AccessMethod::Direct);
+    }
+}
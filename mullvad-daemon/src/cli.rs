@@ -1,4 +1,5 @@
 use clap::{crate_authors, crate_description, crate_name, App, Arg};
+use std::path::PathBuf;
 
 use crate::version;
 
@@ -10,6 +11,8 @@ pub struct Config {
     pub run_as_service: bool,
     pub register_service: bool,
     pub restart_service: bool,
+    pub initialize_firewall: bool,
+    pub load_snapshot: Option<PathBuf>,
 }
 
 pub fn get_config() -> &'static Config {
@@ -34,6 +37,9 @@ pub fn create_config() -> Config {
     let run_as_service = cfg!(windows) && matches.is_present("run_as_service");
     let register_service = cfg!(windows) && matches.is_present("register_service");
     let restart_service = cfg!(windows) && matches.is_present("restart_service");
+    let initialize_firewall =
+        cfg!(target_os = "linux") && matches.is_present("initialize_firewall");
+    let load_snapshot = matches.value_of_os("load_snapshot").map(PathBuf::from);
 
     Config {
         log_level,
@@ -42,6 +48,8 @@ pub fn create_config() -> Config {
         run_as_service,
         register_service,
         restart_service,
+        initialize_firewall,
+        load_snapshot,
     }
 }
 
@@ -87,6 +95,15 @@ fn create_app() -> App<'static> {
             Arg::new("disable_stdout_timestamps")
                 .long("disable-stdout-timestamps")
                 .help("Don't log timestamps when logging to stdout, useful when running as a systemd service")
+        )
+        .arg(
+            Arg::new("load_snapshot")
+                .long("load-snapshot")
+                .takes_value(true)
+                .value_name("FILE")
+                .allow_invalid_utf8(true)
+                .hide(true)
+                .help("For developers. Start from a snapshot taken with 'mullvad debug snapshot' instead of the settings and cache of this machine, and never update the relay list"),
         );
 
     if cfg!(windows) {
@@ -106,5 +123,13 @@ fn create_app() -> App<'static> {
                 .help("Restarts the existing system service"),
         )
     }
+
+    if cfg!(target_os = "linux") {
+        app = app.arg(
+            Arg::new("initialize_firewall")
+                .long("initialize-early-boot-firewall")
+                .help("Block all traffic if lockdown mode is enabled, and exit. Used during boot, before the daemon has started"),
+        );
+    }
     app
 }
@@ -0,0 +1,147 @@
+//! Adds up how much data passes through the tunnel in each calendar month, for users on metered
+//! connections. The totals are kept in the cache directory.
+
+use chrono::{offset::Local, Datelike};
+use mullvad_types::data_usage::{MonthlyDataUsage, TrafficCounters};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const DATA_USAGE_FILE: &str = "data-usage.json";
+
+/// Totals are only kept for this many months. The oldest ones are dropped first.
+const MAX_MONTHS: usize = 12;
+
+/// The monthly totals. Clones share the same totals, so that the traffic can be recorded from
+/// the task that samples it.
+#[derive(Clone)]
+pub struct DataUsageStore {
+    path: PathBuf,
+    months: Arc<Mutex<Vec<MonthlyDataUsage>>>,
+}
+
+impl DataUsageStore {
+    pub async fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(DATA_USAGE_FILE);
+        let months = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse data usage")
+                );
+                vec![]
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read data usage")
+                    );
+                }
+                vec![]
+            }
+        };
+        DataUsageStore {
+            path,
+            months: Arc::new(Mutex::new(months)),
+        }
+    }
+
+    /// Adds `traffic` to the total of the current month.
+    pub fn add(&self, traffic: TrafficCounters) {
+        let now = Local::now();
+        add_to_month(
+            &mut self.months.lock().unwrap(),
+            now.year(),
+            now.month(),
+            traffic,
+        );
+    }
+
+    /// Returns the totals of each month, oldest first.
+    pub fn months(&self) -> Vec<MonthlyDataUsage> {
+        self.months.lock().unwrap().clone()
+    }
+
+    pub async fn save(&self) {
+        let months = self.months();
+        if months.is_empty() {
+            return;
+        }
+        match serde_json::to_string(&months) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write data usage")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize data usage")
+                );
+            }
+        }
+    }
+}
+
+fn add_to_month(
+    months: &mut Vec<MonthlyDataUsage>,
+    year: i32,
+    month: u32,
+    traffic: TrafficCounters,
+) {
+    match months.last_mut() {
+        Some(last) if last.year == year && last.month == month => last.traffic.add(traffic),
+        _ => {
+            months.push(MonthlyDataUsage {
+                year,
+                month,
+                traffic,
+            });
+            if months.len() > MAX_MONTHS {
+                months.remove(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn traffic(received: u64, sent: u64) -> TrafficCounters {
+        TrafficCounters { received, sent }
+    }
+
+    #[test]
+    fn test_adds_to_current_month() {
+        let mut months = vec![];
+        add_to_month(&mut months, 2026, 9, traffic(10, 1));
+        add_to_month(&mut months, 2026, 10, traffic(20, 2));
+        add_to_month(&mut months, 2026, 10, traffic(30, 3));
+
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].traffic, traffic(10, 1));
+        assert_eq!((months[1].year, months[1].month), (2026, 10));
+        assert_eq!(months[1].traffic, traffic(50, 5));
+    }
+
+    #[test]
+    fn test_drops_oldest_months() {
+        let mut months = vec![];
+        for month in 1..=12 {
+            add_to_month(&mut months, 2025, month, traffic(1, 1));
+        }
+        add_to_month(&mut months, 2026, 1, traffic(1, 1));
+
+        assert_eq!(months.len(), MAX_MONTHS);
+        assert_eq!((months[0].year, months[0].month), (2025, 2));
+        assert_eq!((months[11].year, months[11].month), (2026, 1));
+    }
+}
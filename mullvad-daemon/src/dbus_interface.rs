@@ -0,0 +1,561 @@
+//! Exposes the tunnel state, connecting and disconnecting, and a few settings on the system bus, so
+//! that desktop environments, network applets and systemd units can integrate with the daemon
+//! without a gRPC client. The management interface remains the complete interface.
+//!
+//! Anyone may read the properties. Methods that change the state of the daemon are subject to
+//! the same restrictions as the management interface: if the socket is limited to the group in
+//! `MULLVAD_MANAGEMENT_SOCKET_GROUP`, the caller must be a member of it, and if authorization is
+//! enabled, the caller must be an authorized user. Since there is no way to present the
+//! management token over D-Bus, only the user and group checks apply.
+
+use crate::{
+    management_interface::ManagementInterfaceEventBroadcaster, settings, DaemonCommand,
+    DaemonCommandSender,
+};
+use futures::channel::oneshot;
+use mullvad_management_interface::{
+    auth::{AccessPolicy, PeerCredentials},
+    types::daemon_event,
+};
+use mullvad_types::{
+    settings::Settings,
+    states::{DisconnectAction, TargetState, TunnelState},
+};
+use std::{collections::BTreeMap, ffi::CString, sync::Arc, time::Duration};
+use talpid_dbus::dbus::{
+    self,
+    arg::{PropMap, RefArg, Variant},
+    blocking::{
+        stdintf::org_freedesktop_dbus::{PropertiesPropertiesChanged, RequestNameReply},
+        SyncConnection,
+    },
+    channel::{MatchingReceiver, Sender},
+    message::{MatchRule, SignalArgs},
+    strings::ErrorName,
+    Message,
+};
+use talpid_types::ErrorExt;
+use tokio_stream::StreamExt;
+
+/// Well-known name that the daemon owns on the system bus.
+pub const BUS_NAME: &str = "net.mullvad.VPN";
+const OBJECT_PATH: &str = "/net/mullvad/VPN";
+const INTERFACE: &str = "net.mullvad.VPN";
+
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+const ERROR_ACCESS_DENIED: &str = "org.freedesktop.DBus.Error.AccessDenied";
+const ERROR_FAILED: &str = "org.freedesktop.DBus.Error.Failed";
+const ERROR_INVALID_ARGS: &str = "org.freedesktop.DBus.Error.InvalidArgs";
+const ERROR_UNKNOWN_METHOD: &str = "org.freedesktop.DBus.Error.UnknownMethod";
+const ERROR_UNKNOWN_OBJECT: &str = "org.freedesktop.DBus.Error.UnknownObject";
+const ERROR_UNKNOWN_PROPERTY: &str = "org.freedesktop.DBus.Error.UnknownProperty";
+const ERROR_PROPERTY_READ_ONLY: &str = "org.freedesktop.DBus.Error.PropertyReadOnly";
+const ERROR_TUNNEL_BUSY: &str = "net.mullvad.VPN.Error.TunnelBusy";
+
+/// How long the connection waits for incoming messages before checking whether it should stop.
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long to wait for the bus to tell which user a caller runs as.
+const BUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="net.mullvad.VPN">
+    <method name="Connect">
+      <arg name="issued" type="b" direction="out"/>
+    </method>
+    <method name="Disconnect">
+      <arg name="issued" type="b" direction="out"/>
+    </method>
+    <method name="Reconnect">
+      <arg name="issued" type="b" direction="out"/>
+    </method>
+    <property name="TunnelState" type="s" access="read"/>
+    <property name="RelayHostname" type="s" access="read"/>
+    <property name="Country" type="s" access="read"/>
+    <property name="City" type="s" access="read"/>
+    <property name="AllowLan" type="b" access="readwrite"/>
+    <property name="BlockWhenDisconnected" type="b" access="readwrite"/>
+    <property name="AutoConnect" type="b" access="readwrite"/>
+  </interface>
+  <interface name="org.freedesktop.DBus.Properties">
+    <method name="Get">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="name" type="s" direction="in"/>
+      <arg name="value" type="v" direction="out"/>
+    </method>
+    <method name="GetAll">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="properties" type="a{sv}" direction="out"/>
+    </method>
+    <method name="Set">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="name" type="s" direction="in"/>
+      <arg name="value" type="v" direction="in"/>
+    </method>
+    <signal name="PropertiesChanged">
+      <arg name="interface" type="s"/>
+      <arg name="changed_properties" type="a{sv}"/>
+      <arg name="invalidated_properties" type="as"/>
+    </signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="data" type="s" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to connect to the system bus")]
+    Connect(#[error(source)] dbus::Error),
+
+    #[error(display = "Failed to request the name {} on the system bus", BUS_NAME)]
+    RequestName(#[error(source)] dbus::Error),
+
+    #[error(display = "The name {} is already owned on the system bus", BUS_NAME)]
+    NameTaken,
+
+    #[error(display = "Failed to load the management interface access policy")]
+    AccessPolicy(#[error(source)] mullvad_management_interface::Error),
+
+    #[error(
+        display = "Failed to look up the management interface socket group {}",
+        _0
+    )]
+    SocketGroup(String),
+}
+
+pub struct DbusInterface(());
+
+impl DbusInterface {
+    /// Claims [`BUS_NAME`] on the system bus and starts answering method calls on it. Changes to
+    /// the properties are signalled for as long as `event_broadcaster` has subscribers.
+    pub fn start(
+        daemon_tx: DaemonCommandSender,
+        event_broadcaster: &ManagementInterfaceEventBroadcaster,
+    ) -> Result<(), Error> {
+        let policy = AccessPolicy::from_env().map_err(Error::AccessPolicy)?;
+        let socket_gid = match mullvad_management_interface::socket_group() {
+            Some(group_name) => Some(
+                nix::unistd::Group::from_name(group_name)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(|| Error::SocketGroup(group_name.to_owned()))?
+                    .gid,
+            ),
+            None => None,
+        };
+
+        let connection = Arc::new(SyncConnection::new_system().map_err(Error::Connect)?);
+        let reply = connection
+            .request_name(BUS_NAME, false, true, true)
+            .map_err(Error::RequestName)?;
+        if reply != RequestNameReply::PrimaryOwner {
+            return Err(Error::NameTaken);
+        }
+
+        let service = Service {
+            daemon_tx: daemon_tx.clone(),
+            runtime: tokio::runtime::Handle::current(),
+            policy,
+            socket_gid,
+        };
+        connection.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |message, connection| {
+                let _ = connection.send(service.handle_method_call(&message, connection));
+                true
+            }),
+        );
+
+        // Method calls are answered on a thread of their own, since the connection is blocking.
+        let process_connection = connection.clone();
+        std::thread::spawn(move || {
+            while Arc::strong_count(&process_connection) > 1 {
+                if let Err(error) = process_connection.process(PROCESS_TIMEOUT) {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("The D-Bus interface connection failed")
+                    );
+                    break;
+                }
+            }
+            log::info!("D-Bus interface shut down");
+        });
+
+        let mut events = event_broadcaster.subscribe();
+        tokio::spawn(async move {
+            let mut last_properties = properties(&daemon_tx).await.ok();
+            while let Some(event) = events.next().await {
+                let is_relevant = matches!(
+                    event.map(|event| event.event),
+                    Ok(Some(daemon_event::Event::TunnelState(_)))
+                        | Ok(Some(daemon_event::Event::Settings(_)))
+                );
+                if !is_relevant {
+                    continue;
+                }
+                let new_properties = match properties(&daemon_tx).await {
+                    Ok(properties) => properties,
+                    Err(_) => break,
+                };
+                let changed_properties: PropMap = new_properties
+                    .iter()
+                    .filter(|(name, value)| {
+                        last_properties
+                            .as_ref()
+                            .and_then(|properties| properties.get(*name))
+                            != Some(*value)
+                    })
+                    .map(|(name, value)| (name.to_string(), value.to_variant()))
+                    .collect();
+                last_properties = Some(new_properties);
+                if changed_properties.is_empty() {
+                    continue;
+                }
+
+                let signal = PropertiesPropertiesChanged {
+                    interface_name: INTERFACE.to_owned(),
+                    changed_properties,
+                    invalidated_properties: vec![],
+                };
+                let path = dbus::Path::from(OBJECT_PATH);
+                if connection.send(signal.to_emit_message(&path)).is_err() {
+                    log::error!("Failed to signal property changes on D-Bus");
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// The value of a property, in a form that can be compared with earlier values.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    fn to_variant(&self) -> Variant<Box<dyn RefArg>> {
+        match self {
+            Value::Bool(value) => Variant(Box::new(*value)),
+            Value::String(value) => Variant(Box::new(value.clone())),
+        }
+    }
+}
+
+type Properties = BTreeMap<&'static str, Value>;
+
+/// Properties that clients may change with `Set`.
+const WRITABLE_PROPERTIES: [&str; 3] = ["AllowLan", "BlockWhenDisconnected", "AutoConnect"];
+
+fn properties_from(state: &TunnelState, settings: &Settings) -> Properties {
+    let (tunnel_state, location) = match state {
+        TunnelState::Disconnected => ("disconnected", None),
+        TunnelState::Connecting { location, .. } => ("connecting", location.as_ref()),
+        TunnelState::Connected { location, .. } => ("connected", location.as_ref()),
+        TunnelState::Disconnecting(_) => ("disconnecting", None),
+        TunnelState::Error(_) => ("error", None),
+    };
+    let location_value = |value: Option<&String>| Value::String(value.cloned().unwrap_or_default());
+
+    let mut properties = Properties::new();
+    properties.insert("TunnelState", Value::String(tunnel_state.to_owned()));
+    properties.insert(
+        "RelayHostname",
+        location_value(location.and_then(|location| location.hostname.as_ref())),
+    );
+    properties.insert(
+        "Country",
+        location_value(location.map(|location| &location.country)),
+    );
+    properties.insert(
+        "City",
+        location_value(location.and_then(|location| location.city.as_ref())),
+    );
+    properties.insert("AllowLan", Value::Bool(settings.allow_lan));
+    properties.insert(
+        "BlockWhenDisconnected",
+        Value::Bool(settings.block_when_disconnected),
+    );
+    properties.insert("AutoConnect", Value::Bool(settings.auto_connect));
+    properties
+}
+
+async fn properties(daemon_tx: &DaemonCommandSender) -> Result<Properties, MethodError> {
+    let state = request(daemon_tx, DaemonCommand::GetState).await?;
+    let settings = request(daemon_tx, DaemonCommand::GetSettings).await?;
+    Ok(properties_from(&state, &settings))
+}
+
+async fn request<T>(
+    daemon_tx: &DaemonCommandSender,
+    command: impl FnOnce(oneshot::Sender<T>) -> DaemonCommand,
+) -> Result<T, MethodError> {
+    let (tx, rx) = oneshot::channel();
+    daemon_tx
+        .send(command(tx))
+        .map_err(|_| MethodError::failed("The daemon is not running"))?;
+    rx.await
+        .map_err(|_| MethodError::failed("The daemon did not respond"))
+}
+
+/// An error that is returned to the caller of a method.
+struct MethodError {
+    name: &'static str,
+    message: String,
+}
+
+impl MethodError {
+    fn new(name: &'static str, message: impl Into<String>) -> Self {
+        MethodError {
+            name,
+            message: message.into(),
+        }
+    }
+
+    fn failed(message: impl Into<String>) -> Self {
+        Self::new(ERROR_FAILED, message)
+    }
+
+    fn reply_to(&self, message: &Message) -> Message {
+        let name = ErrorName::new(self.name).expect("invalid D-Bus error name");
+        let description = CString::new(self.message.replace('\0', ""))
+            .expect("error message contains no nul bytes");
+        message.error(&name, &description)
+    }
+}
+
+impl From<settings::Error> for MethodError {
+    fn from(error: settings::Error) -> Self {
+        MethodError::failed(error.display_chain())
+    }
+}
+
+struct Service {
+    daemon_tx: DaemonCommandSender,
+    runtime: tokio::runtime::Handle,
+    /// Who may change the state of the daemon, if authorization is enabled.
+    policy: Option<AccessPolicy>,
+    /// The group that the management interface socket is restricted to, if any.
+    socket_gid: Option<nix::unistd::Gid>,
+}
+
+impl Service {
+    fn handle_method_call(&self, message: &Message, connection: &SyncConnection) -> Message {
+        let result = if message.path().as_deref() == Some(OBJECT_PATH) {
+            self.dispatch(message, connection)
+        } else {
+            Err(MethodError::new(ERROR_UNKNOWN_OBJECT, "No such object"))
+        };
+        result.unwrap_or_else(|error| error.reply_to(message))
+    }
+
+    fn dispatch(
+        &self,
+        message: &Message,
+        connection: &SyncConnection,
+    ) -> Result<Message, MethodError> {
+        let interface = message.interface();
+        let member = message.member();
+        let changes_state = matches!(
+            (interface.as_deref(), member.as_deref()),
+            (Some(INTERFACE), _) | (Some(PROPERTIES_INTERFACE), Some("Set"))
+        );
+        if changes_state {
+            self.authorize(message, connection)?;
+        }
+        match (interface.as_deref(), member.as_deref()) {
+            (Some(INTERFACE), Some("Connect")) => {
+                let issued =
+                    self.request(|tx| DaemonCommand::SetTargetState(tx, TargetState::Secured))?;
+                Ok(message.method_return().append1(issued))
+            }
+            (Some(INTERFACE), Some("Disconnect")) => {
+                self.check_disconnect_warning(DisconnectAction::Disconnect)?;
+                let issued =
+                    self.request(|tx| DaemonCommand::SetTargetState(tx, TargetState::Unsecured))?;
+                Ok(message.method_return().append1(issued))
+            }
+            (Some(INTERFACE), Some("Reconnect")) => {
+                self.check_disconnect_warning(DisconnectAction::Reconnect)?;
+                let issued = self.request(DaemonCommand::Reconnect)?;
+                Ok(message.method_return().append1(issued))
+            }
+            (Some(PROPERTIES_INTERFACE), Some("Get")) => {
+                let (interface, name): (&str, &str) = message.read2().map_err(invalid_args)?;
+                check_interface(interface)?;
+                let properties = self.properties()?;
+                let value = properties.get(name).ok_or_else(|| unknown_property(name))?;
+                Ok(message.method_return().append1(value.to_variant()))
+            }
+            (Some(PROPERTIES_INTERFACE), Some("GetAll")) => {
+                let interface: &str = message.read1().map_err(invalid_args)?;
+                let properties: PropMap = if interface.is_empty() || interface == INTERFACE {
+                    self.properties()?
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.to_variant()))
+                        .collect()
+                } else {
+                    PropMap::new()
+                };
+                Ok(message.method_return().append1(properties))
+            }
+            (Some(PROPERTIES_INTERFACE), Some("Set")) => {
+                let (interface, name, value): (&str, &str, Variant<Box<dyn RefArg>>) =
+                    message.read3().map_err(invalid_args)?;
+                check_interface(interface)?;
+                self.set_property(name, &*value.0)?;
+                Ok(message.method_return())
+            }
+            (Some(INTROSPECTABLE_INTERFACE), Some("Introspect")) => {
+                Ok(message.method_return().append1(INTROSPECTION))
+            }
+            _ => Err(MethodError::new(ERROR_UNKNOWN_METHOD, "No such method")),
+        }
+    }
+
+    /// Fails unless the caller may use the management interface to change the state of the
+    /// daemon.
+    fn authorize(&self, message: &Message, connection: &SyncConnection) -> Result<(), MethodError> {
+        if self.policy.is_none() && self.socket_gid.is_none() {
+            return Ok(());
+        }
+        let denied = || MethodError::new(ERROR_ACCESS_DENIED, "Not authorized to call this method");
+
+        let sender = message.sender().ok_or_else(denied)?;
+        let (uid,): (u32,) = connection
+            .with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", BUS_TIMEOUT)
+            .method_call("org.freedesktop.DBus", "GetConnectionUnixUser", (&*sender,))
+            .map_err(|error| MethodError::failed(error.display_chain()))?;
+        if uid == 0 {
+            return Ok(());
+        }
+        let user = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+            .ok()
+            .flatten()
+            .ok_or_else(denied)?;
+
+        if let Some(socket_gid) = self.socket_gid {
+            let name = CString::new(user.name.as_str()).map_err(|_| denied())?;
+            let groups = nix::unistd::getgrouplist(&name, user.gid).map_err(|_| denied())?;
+            if !groups.contains(&socket_gid) {
+                return Err(denied());
+            }
+        }
+        if let Some(policy) = &self.policy {
+            let peer = PeerCredentials {
+                uid,
+                gid: user.gid.as_raw(),
+            };
+            if !policy.is_authorized_peer(peer) {
+                return Err(denied());
+            }
+        }
+        Ok(())
+    }
+
+    fn set_property(&self, name: &str, value: &(dyn RefArg + 'static)) -> Result<(), MethodError> {
+        if !WRITABLE_PROPERTIES.contains(&name) {
+            if self.properties()?.contains_key(name) {
+                return Err(MethodError::new(
+                    ERROR_PROPERTY_READ_ONLY,
+                    format!("{} is read-only", name),
+                ));
+            }
+            return Err(unknown_property(name));
+        }
+        let value = *value
+            .as_any()
+            .downcast_ref::<bool>()
+            .ok_or_else(|| MethodError::new(ERROR_INVALID_ARGS, "Expected a boolean"))?;
+        log::debug!("D-Bus: set {} to {}", name, value);
+        match name {
+            "AllowLan" => self.request(|tx| DaemonCommand::SetAllowLan(tx, value))?,
+            "BlockWhenDisconnected" => {
+                self.request(|tx| DaemonCommand::SetBlockWhenDisconnected(tx, value))?
+            }
+            "AutoConnect" => self.request(|tx| DaemonCommand::SetAutoConnect(tx, value))?,
+            _ => unreachable!("unhandled writable property"),
+        }
+        .map_err(MethodError::from)
+    }
+
+    fn check_disconnect_warning(&self, action: DisconnectAction) -> Result<(), MethodError> {
+        self.request(|tx| DaemonCommand::CheckDisconnectWarning(tx, action))?
+            .map_err(|error| match error {
+                crate::Error::ActiveTransfer(_) => {
+                    MethodError::new(ERROR_TUNNEL_BUSY, error.to_string())
+                }
+                error => MethodError::failed(error.display_chain()),
+            })
+    }
+
+    fn properties(&self) -> Result<Properties, MethodError> {
+        self.runtime.block_on(properties(&self.daemon_tx))
+    }
+
+    fn request<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> DaemonCommand,
+    ) -> Result<T, MethodError> {
+        self.runtime.block_on(request(&self.daemon_tx, command))
+    }
+}
+
+fn check_interface(interface: &str) -> Result<(), MethodError> {
+    if interface.is_empty() || interface == INTERFACE {
+        Ok(())
+    } else {
+        Err(MethodError::new(
+            ERROR_UNKNOWN_PROPERTY,
+            format!("No properties on {}", interface),
+        ))
+    }
+}
+
+fn unknown_property(name: &str) -> MethodError {
+    MethodError::new(
+        ERROR_UNKNOWN_PROPERTY,
+        format!("No such property: {}", name),
+    )
+}
+
+fn invalid_args(error: dbus::arg::TypeMismatchError) -> MethodError {
+    MethodError::new(ERROR_INVALID_ARGS, error.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_properties() {
+        let mut settings = Settings::default();
+        settings.allow_lan = true;
+        let properties = properties_from(&TunnelState::Disconnected, &settings);
+
+        assert_eq!(
+            properties.get("TunnelState"),
+            Some(&Value::String("disconnected".to_owned()))
+        );
+        assert_eq!(
+            properties.get("RelayHostname"),
+            Some(&Value::String(String::new()))
+        );
+        assert_eq!(properties.get("AllowLan"), Some(&Value::Bool(true)));
+        for name in WRITABLE_PROPERTIES {
+            assert!(properties.contains_key(name));
+        }
+        assert!(INTROSPECTION.contains(&format!("<interface name=\"{}\">", INTERFACE)));
+    }
+}
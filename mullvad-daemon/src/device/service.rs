@@ -3,7 +3,7 @@ use std::{future::Future, time::Duration};
 use chrono::{DateTime, Utc};
 use futures::future::{abortable, AbortHandle};
 use mullvad_types::{
-    account::{AccountToken, VoucherSubmission},
+    account::{AccountToken, LoginLink, VoucherSubmission},
     device::{Device, DeviceId},
     wireguard::WireguardData,
 };
@@ -294,6 +294,25 @@ impl AccountService {
         )
     }
 
+    pub fn create_login_link(&self) -> impl Future<Output = Result<LoginLink, rest::Error>> {
+        let proxy = self.proxy.clone();
+        let api_handle = self.api_availability.clone();
+        retry_future_n(
+            move || proxy.create_login_link(),
+            move |result| should_retry(result, &api_handle),
+            constant_interval(RETRY_ACTION_INTERVAL),
+            RETRY_ACTION_MAX_RETRIES,
+        )
+    }
+
+    /// Checks once whether a login link has been approved. Callers are expected to poll.
+    pub fn poll_login_link(
+        &self,
+        code: String,
+    ) -> impl Future<Output = Result<Option<AccountToken>, rest::Error>> {
+        self.proxy.poll_login_link(code)
+    }
+
     pub async fn check_expiry(&self, token: AccountToken) -> Result<DateTime<Utc>, rest::Error> {
         let proxy = self.proxy.clone();
         let api_handle = self.api_availability.clone();
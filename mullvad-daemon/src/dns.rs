@@ -1,5 +1,13 @@
 use mullvad_types::settings::{DnsOptions, DnsState};
 use std::net::{IpAddr, Ipv4Addr};
+#[cfg(not(target_os = "android"))]
+use std::sync::Arc;
+#[cfg(not(target_os = "android"))]
+use std::{io, net::Ipv6Addr};
+#[cfg(not(target_os = "android"))]
+use talpid_core::dns::forwarder::{DnsFilter, DnsQueryLog, RuleFilter};
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::dns::{nat64_prefix, NAT64_DISCOVERY_NAME};
 
 /// When we want to block certain contents with the help of DNS server side,
 /// we compute the resolver IP to use based on these constants. The last
@@ -11,6 +19,22 @@ const DNS_MALWARE_BLOCKING_IP_BIT: u8 = 1 << 2; // 0b00000100
 const DNS_ADULT_BLOCKING_IP_BIT: u8 = 1 << 3; // 0b00001000
 const DNS_GAMBLING_BLOCKING_IP_BIT: u8 = 1 << 4; // 0b00010000
 
+/// Custom DNS servers that cannot work on the current network.
+#[cfg(not(target_os = "android"))]
+#[derive(err_derive::Error, Debug, PartialEq, Eq)]
+#[error(no_from)]
+pub enum Nat64Error {
+    /// Resolvers on the local network are reached outside the tunnel, where an IPv6-only network
+    /// has no IPv4 connectivity. NAT64 does not translate private addresses either.
+    #[error(
+        display = "The custom DNS servers {:?} are on a local IPv4 network, but this network is \
+        IPv6-only (NAT64 prefix {})",
+        _0,
+        _1
+    )]
+    LocalIpv4Resolvers(Vec<Ipv4Addr>, Ipv6Addr),
+}
+
 /// Return the resolvers as a vector of `IpAddr`s. Returns `None` when no special resolvers
 /// are requested and the tunnel default gateway should be used.
 pub fn addresses_from_options(options: &DnsOptions) -> Option<Vec<IpAddr>> {
@@ -53,3 +77,97 @@ pub fn addresses_from_options(options: &DnsOptions) -> Option<Vec<IpAddr>> {
         }
     }
 }
+
+/// Returns the filter that DNS queries pass through while connected, or `None` if queries should
+/// not be filtered. Builds that need other filtering than the filter rules can return their own
+/// [`DnsFilter`] here.
+#[cfg(not(target_os = "android"))]
+pub fn filter_from_options(options: &DnsOptions) -> Option<Arc<dyn DnsFilter>> {
+    if options.filter_rules.is_empty() {
+        None
+    } else {
+        Some(Arc::new(RuleFilter::new(options.filter_rules.clone())))
+    }
+}
+
+/// Returns `query_log` if the query log is enabled in `options`.
+#[cfg(not(target_os = "android"))]
+pub fn query_log_from_options(
+    query_log: &Arc<DnsQueryLog>,
+    options: &DnsOptions,
+) -> Option<Arc<DnsQueryLog>> {
+    if options.query_log {
+        Some(query_log.clone())
+    } else {
+        None
+    }
+}
+
+/// Looks up the NAT64 prefix of the network using the system resolver. This returns `None` if
+/// the network does not use DNS64, and is only meaningful while the host's own resolvers are in
+/// use, i.e. when disconnected.
+#[cfg(not(target_os = "android"))]
+pub async fn detect_nat64_prefix() -> io::Result<Option<Ipv6Addr>> {
+    let addresses = tokio::net::lookup_host((NAT64_DISCOVERY_NAME, 0)).await?;
+    Ok(addresses
+        .filter_map(|address| match address.ip() {
+            IpAddr::V6(address) => nat64_prefix(address),
+            IpAddr::V4(_) => None,
+        })
+        .next())
+}
+
+/// Checks that the custom DNS servers in `options` can be reached on a network that uses the
+/// NAT64 prefix `prefix`. Servers in the tunnel are unaffected, since it always has IPv4.
+#[cfg(not(target_os = "android"))]
+pub fn check_nat64(options: &DnsOptions, prefix: Ipv6Addr) -> Result<(), Nat64Error> {
+    if options.state != DnsState::Custom {
+        return Ok(());
+    }
+    let local_resolvers: Vec<Ipv4Addr> = options
+        .custom_options
+        .addresses
+        .iter()
+        .filter_map(|address| match address {
+            IpAddr::V4(address) if address.is_private() || address.is_link_local() => {
+                Some(*address)
+            }
+            _ => None,
+        })
+        .collect();
+    if local_resolvers.is_empty() {
+        Ok(())
+    } else {
+        Err(Nat64Error::LocalIpv4Resolvers(local_resolvers, prefix))
+    }
+}
+
+#[cfg(all(test, not(target_os = "android")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_nat64() {
+        let prefix: Ipv6Addr = "64:ff9b::".parse().unwrap();
+        let mut options = DnsOptions::default();
+        options.state = DnsState::Custom;
+        options.custom_options.addresses =
+            vec!["1.1.1.1".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+        assert_eq!(check_nat64(&options, prefix), Ok(()));
+
+        options
+            .custom_options
+            .addresses
+            .push("192.168.1.1".parse().unwrap());
+        assert_eq!(
+            check_nat64(&options, prefix),
+            Err(Nat64Error::LocalIpv4Resolvers(
+                vec![Ipv4Addr::new(192, 168, 1, 1)],
+                prefix
+            ))
+        );
+
+        options.state = DnsState::Default;
+        assert_eq!(check_nat64(&options, prefix), Ok(()));
+    }
+}
@@ -0,0 +1,152 @@
+//! Checks which of several custom DNS servers respond while connected. Most resolvers only try
+//! the first server and wait for it to time out before moving on, so servers that do not respond
+//! are moved after the ones that do, and back again once they recover.
+
+use crate::{DaemonEventSender, InternalDaemonEvent};
+use futures::future::{abortable, join_all, AbortHandle};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use talpid_core::mpsc::Sender;
+use tokio::{net::UdpSocket, time::timeout};
+
+/// How often the servers are probed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait for a server to respond to a probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const DNS_PORT: u16 = 53;
+
+/// Query for the NS records of the root zone. Any response, even an error, shows that the server
+/// is reachable.
+const PROBE_QUERY: [u8; 17] = [
+    0x6d, 0x76, // ID
+    0x01, 0x00, // Recursion desired
+    0x00, 0x01, // One question
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // No other records
+    0x00, // The root name
+    0x00, 0x02, // Type NS
+    0x00, 0x01, // Class IN
+];
+
+pub struct DnsFailover {
+    event_tx: DaemonEventSender,
+    job: Option<AbortHandle>,
+}
+
+impl DnsFailover {
+    pub fn new(event_tx: DaemonEventSender) -> Self {
+        DnsFailover {
+            event_tx,
+            job: None,
+        }
+    }
+
+    /// Starts probing `servers`, which are in order of preference, or stops probing if there is
+    /// nothing to fall back to. New orders are sent as [`InternalDaemonEvent::DnsServersReordered`].
+    pub fn update(&mut self, servers: Option<Vec<IpAddr>>) {
+        if let Some(job) = self.job.take() {
+            job.abort();
+        }
+        let servers = match servers {
+            Some(servers) if servers.len() > 1 => servers,
+            _ => return,
+        };
+
+        let event_tx = self.event_tx.clone();
+        let (prober, job) = abortable(async move {
+            let mut current_order = servers.clone();
+            let mut interval = tokio::time::interval(PROBE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let reachable = join_all(servers.iter().map(|server| probe(*server))).await;
+                let order = failover_order(&servers, &reachable);
+                if order != current_order {
+                    log::info!("Changing the order of the DNS servers to {:?}", order);
+                    if event_tx
+                        .send(InternalDaemonEvent::DnsServersReordered(order.clone()))
+                        .is_err()
+                    {
+                        return;
+                    }
+                    current_order = order;
+                }
+            }
+        });
+        tokio::spawn(prober);
+        self.job = Some(job);
+    }
+}
+
+/// Returns the servers that responded, followed by the ones that did not, each in order of
+/// preference. If none of them responded, the problem is likely elsewhere, so the order is kept.
+fn failover_order(servers: &[IpAddr], reachable: &[bool]) -> Vec<IpAddr> {
+    if !reachable.contains(&true) {
+        return servers.to_vec();
+    }
+    let (mut order, unreachable): (Vec<_>, Vec<_>) = servers
+        .iter()
+        .zip(reachable)
+        .partition(|(_, reachable)| **reachable);
+    order.extend(unreachable);
+    order.into_iter().map(|(server, _)| *server).collect()
+}
+
+async fn probe(server: IpAddr) -> bool {
+    match timeout(PROBE_TIMEOUT, send_probe(server)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(error)) => {
+            log::debug!("Failed to probe DNS server {}: {}", server, error);
+            false
+        }
+        Err(_) => {
+            log::debug!("DNS server {} did not respond", server);
+            false
+        }
+    }
+}
+
+async fn send_probe(server: IpAddr) -> io::Result<()> {
+    let bind_address = match server {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(bind_address, 0)).await?;
+    socket.connect(SocketAddr::new(server, DNS_PORT)).await?;
+    socket.send(&PROBE_QUERY).await?;
+
+    let mut buffer = [0u8; 512];
+    loop {
+        let length = socket.recv(&mut buffer).await?;
+        if length >= 2 && buffer[..2] == PROBE_QUERY[..2] {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_failover_order() {
+        let servers: Vec<IpAddr> = vec![
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+            "10.0.0.3".parse().unwrap(),
+        ];
+        assert_eq!(failover_order(&servers, &[true, true, true]), servers);
+        assert_eq!(
+            failover_order(&servers, &[false, true, false]),
+            vec![servers[1], servers[0], servers[2]]
+        );
+        assert_eq!(
+            failover_order(&servers, &[false, true, true]),
+            vec![servers[1], servers[2], servers[0]]
+        );
+        assert_eq!(failover_order(&servers, &[false, false, false]), servers);
+    }
+}
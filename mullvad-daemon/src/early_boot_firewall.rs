@@ -0,0 +1,61 @@
+//! Blocks traffic from early boot until the daemon has started, if `block_when_disconnected` is
+//! enabled. This is invoked by a systemd unit that runs before the network is brought up. The
+//! rules are left in place when the process exits, and are replaced by the daemon once it starts.
+
+use crate::{api, apply_policy_routing, settings::SettingsPersister};
+use talpid_core::firewall::{self, Firewall, FirewallPolicy};
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Unable to get settings dir")]
+    SettingsDir(#[error(source)] mullvad_paths::Error),
+
+    #[error(display = "Unable to get cache dir")]
+    CacheDir(#[error(source)] mullvad_paths::Error),
+
+    #[error(display = "Unable to load the API address")]
+    ApiAddress(#[error(source)] mullvad_api::Error),
+
+    #[error(display = "Unable to initialize the firewall")]
+    Initialize(#[error(source)] firewall::Error),
+
+    #[error(display = "Unable to apply the blocking firewall policy")]
+    ApplyPolicy(#[error(source)] firewall::Error),
+}
+
+/// Applies the blocked firewall policy if `block_when_disconnected` is enabled. Otherwise, this
+/// does nothing.
+pub async fn initialize_firewall() -> Result<(), Error> {
+    let settings_dir = mullvad_paths::settings_dir().map_err(Error::SettingsDir)?;
+    let settings = SettingsPersister::load(&settings_dir).await;
+    if !settings.block_when_disconnected {
+        log::info!("Not blocking traffic during boot, since lockdown mode is disabled");
+        return Ok(());
+    }
+
+    apply_policy_routing(settings.policy_routing);
+
+    // Let the app reach the API as soon as the daemon has started
+    let cache_dir = mullvad_paths::cache_dir().map_err(Error::CacheDir)?;
+    let api_address = mullvad_api::Runtime::with_cache(&cache_dir, false)
+        .await
+        .map_err(Error::ApiAddress)?
+        .address_cache
+        .get_address()
+        .await;
+
+    let policy = FirewallPolicy::Blocked {
+        allow_lan: settings.allow_lan,
+        allowed_lan_nets: settings.allowed_lan_nets.clone(),
+        firewall_exceptions: settings.firewall_exceptions.clone(),
+        allow_multicast: settings.allow_multicast,
+        allowed_endpoint: api::get_allowed_endpoint(api_address),
+        captive_portal: None,
+    };
+
+    let mut firewall = Firewall::new().map_err(Error::Initialize)?;
+    firewall.apply_policy(policy).map_err(Error::ApplyPolicy)?;
+    log::info!("Blocking traffic until the daemon has started");
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use mullvad_types::states::TunnelState;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use talpid_core::tunnel_state_machine::LeftoverState;
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const EXIT_STATE_FILE: &str = "exit-state.json";
+
+/// System changes that a daemon instance had in effect when it last recorded its state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedState {
+    /// A firewall policy was applied.
+    pub firewall_policy: bool,
+    /// The system resolvers were pointed at the tunnel, or overridden to block DNS.
+    pub dns: bool,
+    /// Routes through the tunnel were added.
+    pub routes: bool,
+}
+
+impl AppliedState {
+    pub fn new(tunnel_state: &TunnelState, block_when_disconnected: bool) -> Self {
+        match tunnel_state {
+            TunnelState::Disconnected => AppliedState {
+                firewall_policy: block_when_disconnected,
+                ..Default::default()
+            },
+            TunnelState::Connecting { .. } => AppliedState {
+                firewall_policy: true,
+                dns: false,
+                routes: true,
+            },
+            TunnelState::Connected { .. } | TunnelState::Disconnecting(..) => AppliedState {
+                firewall_policy: true,
+                dns: true,
+                routes: true,
+            },
+            TunnelState::Error(error_state) => AppliedState {
+                firewall_policy: error_state.is_blocking(),
+                dns: cfg!(target_os = "macos"),
+                routes: false,
+            },
+        }
+    }
+}
+
+impl From<AppliedState> for LeftoverState {
+    fn from(state: AppliedState) -> Self {
+        LeftoverState {
+            dns: state.dns,
+            routes: state.routes,
+        }
+    }
+}
+
+/// Keeps a record of what the running daemon has applied to the system. The record is removed
+/// once the daemon has shut down cleanly, so finding one on startup means that the previous
+/// instance crashed or did not finish its teardown in time.
+pub struct ExitStateTracker {
+    cache_path: PathBuf,
+    current: Option<AppliedState>,
+    leftover: Option<AppliedState>,
+}
+
+impl ExitStateTracker {
+    pub async fn new(cache_dir: &Path) -> Self {
+        let cache_path = cache_dir.join(EXIT_STATE_FILE);
+        let leftover = match fs::read_to_string(&cache_path).await {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(state) => Some(state),
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse cached exit state")
+                    );
+                    // Something was written, so the previous instance did not exit cleanly
+                    Some(AppliedState {
+                        firewall_policy: true,
+                        dns: true,
+                        routes: true,
+                    })
+                }
+            },
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read cached exit state")
+                    );
+                }
+                None
+            }
+        };
+
+        if let Some(leftover) = leftover {
+            log::warn!(
+                "The previous daemon instance did not exit cleanly. It had applied: {:?}",
+                leftover
+            );
+        }
+
+        ExitStateTracker {
+            cache_path,
+            current: None,
+            leftover,
+        }
+    }
+
+    /// Returns what the previous instance had applied, if it did not exit cleanly.
+    pub fn leftover(&self) -> Option<AppliedState> {
+        self.leftover
+    }
+
+    pub async fn update(&mut self, state: AppliedState) {
+        if self.current == Some(state) {
+            return;
+        }
+        self.current = Some(state);
+
+        match serde_json::to_string(&state) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.cache_path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write cached exit state")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize cached exit state")
+                );
+            }
+        }
+    }
+
+    /// Records that the daemon has shut down cleanly.
+    pub async fn finalize(self) {
+        if let Err(error) = fs::remove_file(&self.cache_path).await {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Cannot delete cached exit state")
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_leftover_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path();
+        let connected = AppliedState {
+            firewall_policy: true,
+            dns: true,
+            routes: true,
+        };
+
+        block_on(async {
+            let mut tracker = ExitStateTracker::new(cache_dir).await;
+            assert_eq!(tracker.leftover(), None);
+            tracker.update(connected).await;
+
+            // The daemon is killed without finalizing the tracker
+            let mut tracker = ExitStateTracker::new(cache_dir).await;
+            assert_eq!(tracker.leftover(), Some(connected));
+            assert_eq!(
+                LeftoverState::from(connected),
+                LeftoverState {
+                    dns: true,
+                    routes: true,
+                }
+            );
+            tracker.update(AppliedState::default()).await;
+            tracker.finalize().await;
+
+            let tracker = ExitStateTracker::new(cache_dir).await;
+            assert_eq!(tracker.leftover(), None);
+        });
+    }
+
+    #[test]
+    fn test_unparsable_exit_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path();
+        std::fs::write(cache_dir.join(EXIT_STATE_FILE), "{").unwrap();
+
+        let tracker = block_on(ExitStateTracker::new(cache_dir));
+        assert_eq!(
+            tracker.leftover().map(LeftoverState::from),
+            Some(LeftoverState {
+                dns: true,
+                routes: true,
+            })
+        );
+    }
+}
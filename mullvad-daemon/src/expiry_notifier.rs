@@ -0,0 +1,235 @@
+use chrono::{offset::Utc, DateTime};
+use mullvad_types::account::AccountExpiryEvent;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io, process::Command};
+
+const NOTIFIED_STATE_FILE: &str = "expiry-notifications.json";
+
+/// How often the account expiry is checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The user and group that notification scripts are run as when the daemon runs as root.
+#[cfg(target_os = "linux")]
+const UNPRIVILEGED_ID: u32 = 65534;
+
+/// The thresholds that have already been notified about for an expiry date.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct NotifiedState {
+    expiry: Option<DateTime<Utc>>,
+    thresholds: Vec<Duration>,
+}
+
+/// Decides when the account expiry has crossed a notification threshold. The thresholds that
+/// have been notified about are cached, so that a restarted daemon does not notify again.
+pub struct ExpiryNotifier {
+    cache_path: PathBuf,
+    state: NotifiedState,
+}
+
+impl ExpiryNotifier {
+    pub async fn new(cache_dir: &Path) -> Self {
+        let cache_path = cache_dir.join(NOTIFIED_STATE_FILE);
+        let state = match fs::read_to_string(&cache_path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse cached expiry notifications")
+                );
+                NotifiedState::default()
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read cached expiry notifications")
+                    );
+                }
+                NotifiedState::default()
+            }
+        };
+        ExpiryNotifier { cache_path, state }
+    }
+
+    /// Returns an event if the time left until `expiry` has dropped below a threshold that has
+    /// not been notified about yet. If several thresholds were crossed at once, only the smallest
+    /// one is reported. Adding time to the account changes the expiry, which rearms all
+    /// thresholds.
+    pub async fn check(
+        &mut self,
+        expiry: DateTime<Utc>,
+        thresholds: &[Duration],
+    ) -> Option<AccountExpiryEvent> {
+        let previous_state = self.state.clone();
+        let threshold = self.update(expiry, thresholds, Utc::now());
+        if self.state != previous_state {
+            self.save().await;
+        }
+        threshold.map(|threshold| AccountExpiryEvent { expiry, threshold })
+    }
+
+    fn update(
+        &mut self,
+        expiry: DateTime<Utc>,
+        thresholds: &[Duration],
+        now: DateTime<Utc>,
+    ) -> Option<Duration> {
+        if self.state.expiry != Some(expiry) {
+            self.state = NotifiedState {
+                expiry: Some(expiry),
+                thresholds: vec![],
+            };
+        }
+
+        let time_left = (expiry - now).to_std().unwrap_or(Duration::ZERO);
+        let crossed: Vec<Duration> = thresholds
+            .iter()
+            .filter(|threshold| time_left <= **threshold)
+            .filter(|threshold| !self.state.thresholds.contains(threshold))
+            .copied()
+            .collect();
+        self.state.thresholds.extend(&crossed);
+        crossed.into_iter().min()
+    }
+
+    async fn save(&self) {
+        match serde_json::to_string(&self.state) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.cache_path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write cached expiry notifications")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize cached expiry notifications")
+                );
+            }
+        }
+    }
+}
+
+/// Runs the user's notification script in the background. The script gets an empty environment
+/// apart from `MULLVAD_ACCOUNT_EXPIRY`, in RFC 3339 format, and `MULLVAD_EXPIRY_THRESHOLD`, in
+/// seconds. On Linux, it is run as `nobody` if the daemon runs as root.
+pub fn run_script(script: &Path, event: &AccountExpiryEvent) {
+    if !script.is_absolute() {
+        log::error!(
+            "Not running expiry notification script, the path must be absolute: {}",
+            script.display()
+        );
+        return;
+    }
+
+    let mut command = std::process::Command::new(script);
+    command
+        .env_clear()
+        .env("MULLVAD_ACCOUNT_EXPIRY", event.expiry.to_rfc3339())
+        .env(
+            "MULLVAD_EXPIRY_THRESHOLD",
+            event.threshold.as_secs().to_string(),
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    #[cfg(windows)]
+    if let Some(system_root) = std::env::var_os("SystemRoot") {
+        command.env("SystemRoot", system_root);
+    }
+
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::geteuid() } == 0 {
+        use std::os::unix::process::CommandExt;
+        command.uid(UNPRIVILEGED_ID).gid(UNPRIVILEGED_ID);
+    }
+
+    let mut command = Command::from(command);
+    let script = script.to_owned();
+    tokio::spawn(async move {
+        match command.status().await {
+            Ok(status) if status.success() => {
+                log::debug!("Expiry notification script finished");
+            }
+            Ok(status) => {
+                log::error!(
+                    "Expiry notification script {} failed: {}",
+                    script.display(),
+                    status
+                );
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!(
+                        "Failed to run expiry notification script {}",
+                        script.display()
+                    ))
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn notifier() -> ExpiryNotifier {
+        ExpiryNotifier {
+            cache_path: PathBuf::new(),
+            state: NotifiedState::default(),
+        }
+    }
+
+    #[test]
+    fn test_notifies_each_threshold_once() {
+        let mut notifier = notifier();
+        let thresholds = [7 * DAY, DAY];
+        let now = Utc::now();
+        let expiry = now + chrono::Duration::days(10);
+
+        assert_eq!(notifier.update(expiry, &thresholds, now), None);
+
+        let now = expiry - chrono::Duration::days(6);
+        assert_eq!(notifier.update(expiry, &thresholds, now), Some(7 * DAY));
+        assert_eq!(notifier.update(expiry, &thresholds, now), None);
+
+        let now = expiry - chrono::Duration::hours(12);
+        assert_eq!(notifier.update(expiry, &thresholds, now), Some(DAY));
+        assert_eq!(notifier.update(expiry, &thresholds, now), None);
+    }
+
+    #[test]
+    fn test_reports_smallest_crossed_threshold() {
+        let mut notifier = notifier();
+        let now = Utc::now();
+        let expiry = now + chrono::Duration::hours(1);
+
+        assert_eq!(notifier.update(expiry, &[7 * DAY, DAY], now), Some(DAY));
+        assert_eq!(notifier.update(expiry, &[7 * DAY, DAY], now), None);
+    }
+
+    #[test]
+    fn test_new_expiry_rearms_thresholds() {
+        let mut notifier = notifier();
+        let now = Utc::now();
+        let expiry = now + chrono::Duration::hours(1);
+        assert_eq!(notifier.update(expiry, &[DAY], now), Some(DAY));
+
+        let new_expiry = expiry + chrono::Duration::days(30);
+        assert_eq!(notifier.update(new_expiry, &[DAY], now), None);
+        let now = new_expiry - chrono::Duration::hours(1);
+        assert_eq!(notifier.update(new_expiry, &[DAY], now), Some(DAY));
+    }
+}
@@ -0,0 +1,254 @@
+//! Checks the executables that are run as external obfuscation transports. Anyone who can reach
+//! the management interface can configure a transport, and the daemon launches it with its own
+//! privileges, so only executables that administrators have placed in the transports directory
+//! are accepted.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Name of the directory in the settings directory that transport executables must be placed in.
+pub const TRANSPORTS_DIR: &str = "transports";
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Transport executables must be placed in {}", _0)]
+    OutsideTransportsDir(String),
+
+    #[error(display = "Failed to resolve the path of the transport executable")]
+    ResolvePath(#[error(source)] io::Error),
+
+    #[error(display = "The transport executable is not a file")]
+    NotAFile,
+
+    #[cfg(unix)]
+    #[error(display = "The transport executable is not executable")]
+    NotExecutable,
+
+    #[error(
+        display = "{} must be owned by an administrator and not be writable by other users",
+        _0
+    )]
+    InsecurePermissions(String),
+
+    #[cfg(windows)]
+    #[error(display = "Failed to read the security descriptor of {}", _0)]
+    SecurityInformation(String, #[error(source)] io::Error),
+}
+
+/// Resolves `transport` and checks that it is an executable file in `transports_dir`, and that
+/// neither the file nor the directories above it can be modified by unprivileged users. The
+/// returned path is the resolved one, which is what should be launched.
+///
+/// This must be checked again right before every launch, since the file may have been replaced
+/// since the settings were saved.
+pub fn validate(transports_dir: &Path, transport: &Path) -> Result<PathBuf, Error> {
+    let outside = || Error::OutsideTransportsDir(transports_dir.display().to_string());
+    let transports_dir = transports_dir.canonicalize().map_err(|_| outside())?;
+    let transport = transport.canonicalize().map_err(Error::ResolvePath)?;
+    if !transport.starts_with(&transports_dir) || transport == transports_dir {
+        return Err(outside());
+    }
+
+    let metadata = transport.metadata().map_err(Error::ResolvePath)?;
+    if !metadata.is_file() {
+        return Err(Error::NotAFile);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(Error::NotExecutable);
+        }
+    }
+
+    check_permissions(&transports_dir, &transport)?;
+    Ok(transport)
+}
+
+/// Checks the file and every directory above it, since write access to any of them is enough to
+/// swap the executable. They must be owned by root, or by the user the daemon runs as when it does
+/// not run as root.
+#[cfg(unix)]
+fn check_permissions(_transports_dir: &Path, transport: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let daemon_uid = unsafe { libc::geteuid() };
+    for path in transport.ancestors() {
+        let metadata = path.metadata().map_err(Error::ResolvePath)?;
+        let trusted_owner = metadata.uid() == 0 || metadata.uid() == daemon_uid;
+        let other_writable = metadata.mode() & 0o002 != 0;
+        let group_writable = metadata.mode() & 0o020 != 0 && metadata.gid() != 0;
+        if !trusted_owner || other_writable || group_writable {
+            return Err(Error::InsecurePermissions(path.display().to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the file and the transports directory. The directories above it belong to the system
+/// profile, which is owned by `TrustedInstaller` rather than by SYSTEM.
+#[cfg(windows)]
+fn check_permissions(transports_dir: &Path, transport: &Path) -> Result<(), Error> {
+    for path in [transport, transports_dir] {
+        if !windows::is_admin_only(path)
+            .map_err(|error| Error::SecurityInformation(path.display().to_string(), error))?
+        {
+            return Err(Error::InsecurePermissions(path.display().to_string()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{ffi::OsStr, io, mem, os::windows::ffi::OsStrExt, path::Path, ptr};
+    use winapi::{
+        shared::{minwindef::TRUE, winerror::ERROR_SUCCESS},
+        um::{
+            accctrl::{SE_FILE_OBJECT, TRUSTEE_W},
+            aclapi::{BuildTrusteeWithSidW, GetEffectiveRightsFromAclW, GetNamedSecurityInfoW},
+            securitybaseapi::{CreateWellKnownSid, IsWellKnownSid},
+            winbase::LocalFree,
+            winnt::{
+                WinAuthenticatedUserSid, WinBuiltinAdministratorsSid, WinBuiltinUsersSid,
+                WinLocalSystemSid, WinWorldSid, ACL, DACL_SECURITY_INFORMATION, DELETE,
+                FILE_APPEND_DATA, FILE_WRITE_DATA, OWNER_SECURITY_INFORMATION, PSID,
+                SECURITY_MAX_SID_SIZE, WELL_KNOWN_SID_TYPE, WRITE_DAC, WRITE_OWNER,
+            },
+        },
+    };
+
+    /// Rights that would let the holder replace or alter the file.
+    const WRITE_RIGHTS: u32 = FILE_WRITE_DATA | FILE_APPEND_DATA | WRITE_DAC | WRITE_OWNER | DELETE;
+
+    /// Returns whether `path` is owned by SYSTEM or the administrators group, and does not grant
+    /// write access to ordinary users.
+    pub fn is_admin_only(path: &Path) -> io::Result<bool> {
+        let mut u16_path: Vec<u16> = OsStr::new(path).encode_wide().collect();
+        u16_path.push(0u16);
+
+        let mut owner: PSID = ptr::null_mut();
+        let mut dacl: *mut ACL = ptr::null_mut();
+        let mut security_descriptor = ptr::null_mut();
+        let status = unsafe {
+            GetNamedSecurityInfoW(
+                u16_path.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+                &mut owner,
+                ptr::null_mut(),
+                &mut dacl,
+                ptr::null_mut(),
+                &mut security_descriptor,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+
+        let result = is_admin_only_descriptor(owner, dacl);
+        unsafe { LocalFree(security_descriptor) };
+        result
+    }
+
+    fn is_admin_only_descriptor(owner: PSID, dacl: *mut ACL) -> io::Result<bool> {
+        if owner.is_null()
+            || (!is_well_known_sid(owner, WinLocalSystemSid)
+                && !is_well_known_sid(owner, WinBuiltinAdministratorsSid))
+        {
+            return Ok(false);
+        }
+        // A missing DACL grants everyone full access.
+        if dacl.is_null() {
+            return Ok(false);
+        }
+        for sid_type in [WinWorldSid, WinAuthenticatedUserSid, WinBuiltinUsersSid] {
+            if effective_rights(dacl, sid_type)? & WRITE_RIGHTS != 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn effective_rights(dacl: *mut ACL, sid_type: WELL_KNOWN_SID_TYPE) -> io::Result<u32> {
+        // `u32` elements give the buffer the alignment of a SID.
+        let mut sid = [0u32; SECURITY_MAX_SID_SIZE / 4];
+        let mut sid_size = mem::size_of_val(&sid) as u32;
+        if unsafe {
+            CreateWellKnownSid(
+                sid_type,
+                ptr::null_mut(),
+                sid.as_mut_ptr() as PSID,
+                &mut sid_size,
+            )
+        } != TRUE
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut trustee: TRUSTEE_W = unsafe { mem::zeroed() };
+        unsafe { BuildTrusteeWithSidW(&mut trustee, sid.as_mut_ptr() as PSID) };
+        let mut rights = 0;
+        let status = unsafe { GetEffectiveRightsFromAclW(dacl, &mut trustee, &mut rights) };
+        if status != ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+        Ok(rights)
+    }
+
+    fn is_well_known_sid(sid: PSID, well_known_sid_type: WELL_KNOWN_SID_TYPE) -> bool {
+        unsafe { IsWellKnownSid(sid, well_known_sid_type) == TRUE }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_transports_outside_transports_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let transports_dir = dir.path().join(TRANSPORTS_DIR);
+        std::fs::create_dir_all(&transports_dir).unwrap();
+        let outside = dir.path().join("obfs4proxy");
+        std::fs::write(&outside, "").unwrap();
+
+        assert!(matches!(
+            validate(&transports_dir, &outside),
+            Err(Error::OutsideTransportsDir(_))
+        ));
+        assert!(matches!(
+            validate(
+                &transports_dir,
+                &transports_dir.join("..").join("obfs4proxy")
+            ),
+            Err(Error::OutsideTransportsDir(_))
+        ));
+        assert!(matches!(
+            validate(&transports_dir, &transports_dir),
+            Err(Error::OutsideTransportsDir(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_rejects_writable_transports() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let transports_dir = dir.path().join(TRANSPORTS_DIR);
+        std::fs::create_dir_all(&transports_dir).unwrap();
+        let transport = transports_dir.join("obfs4proxy");
+        std::fs::write(&transport, "").unwrap();
+        std::fs::set_permissions(&transport, std::fs::Permissions::from_mode(0o777)).unwrap();
+
+        // Both the file and the temporary directory are writable by everyone.
+        assert!(matches!(
+            validate(&transports_dir, &transport),
+            Err(Error::InsecurePermissions(_))
+        ));
+    }
+}
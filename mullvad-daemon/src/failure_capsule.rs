@@ -0,0 +1,175 @@
+use crate::logging;
+use chrono::{offset::Utc, DateTime};
+use mullvad_problem_report::redact;
+use mullvad_types::{
+    failure_capsule::FailureCapsule, location::GeoIpLocation, settings::Settings,
+    states::TunnelState,
+};
+
+/// Maximum number of log lines in a capsule. The earliest lines of long attempts are left out.
+const MAX_LOG_LINES: usize = 200;
+
+/// Follows the connection attempts and keeps a capsule for the most recent one that failed.
+#[derive(Debug, Default)]
+pub struct FailureRecorder {
+    attempt: Option<Attempt>,
+    last_failure: Option<FailureCapsule>,
+}
+
+#[derive(Debug)]
+struct Attempt {
+    started: DateTime<Utc>,
+    log_position: u64,
+}
+
+impl FailureRecorder {
+    /// Updates the recorder for a tunnel state transition. Leaving the connecting state for
+    /// another attempt or for the error state means that the attempt failed.
+    pub fn update(
+        &mut self,
+        old_state: &TunnelState,
+        new_state: &TunnelState,
+        settings: &Settings,
+    ) {
+        if let (TunnelState::Connecting { endpoint, location }, Some(attempt)) =
+            (old_state, self.attempt.take())
+        {
+            let cause = match new_state {
+                TunnelState::Connecting { .. } => {
+                    Some("The tunnel could not be established".to_owned())
+                }
+                TunnelState::Error(error_state) => Some(error_state.cause().to_string()),
+                _ => None,
+            };
+            if let Some(cause) = cause {
+                let mut log = logging::lines_since(attempt.log_position);
+                if log.len() > MAX_LOG_LINES {
+                    log.drain(..log.len() - MAX_LOG_LINES);
+                }
+                self.last_failure = Some(FailureCapsule {
+                    started: attempt.started,
+                    failed: Utc::now(),
+                    cause: redact(&cause),
+                    endpoint: redact(&endpoint.to_string()),
+                    parameters: parameters(location.as_ref(), settings),
+                    log: log.iter().map(|line| redact(line)).collect(),
+                });
+            }
+        }
+
+        if let TunnelState::Connecting { .. } = new_state {
+            self.attempt = Some(Attempt {
+                started: Utc::now(),
+                log_position: logging::log_position(),
+            });
+        }
+    }
+
+    pub fn last_failure(&self) -> Option<FailureCapsule> {
+        self.last_failure.clone()
+    }
+}
+
+fn parameters(location: Option<&GeoIpLocation>, settings: &Settings) -> Vec<(String, String)> {
+    let mut parameters = vec![];
+    if let Some(location) = location {
+        let hostnames = [
+            ("relay", &location.hostname),
+            ("entry relay", &location.entry_hostname),
+            ("bridge", &location.bridge_hostname),
+            ("obfuscator", &location.obfuscator_hostname),
+        ];
+        for (name, hostname) in hostnames {
+            if let Some(hostname) = hostname {
+                parameters.push((name.to_owned(), hostname.clone()));
+            }
+        }
+    }
+
+    let mtu = settings
+        .tunnel_options
+        .wireguard
+        .options
+        .mtu
+        .map(|mtu| mtu.to_string())
+        .unwrap_or_else(|| "default".to_owned());
+    parameters.extend([
+        (
+            "obfuscation".to_owned(),
+            settings
+                .obfuscation_settings
+                .selected_obfuscation
+                .to_string(),
+        ),
+        (
+            "bridge state".to_owned(),
+            settings.get_bridge_state().to_string(),
+        ),
+        ("wireguard mtu".to_owned(), mtu),
+        (
+            "ipv6".to_owned(),
+            settings.tunnel_options.generic.enable_ipv6.to_string(),
+        ),
+        ("allow lan".to_owned(), settings.allow_lan.to_string()),
+    ]);
+    parameters
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::{
+        net::{Endpoint, TransportProtocol, TunnelEndpoint, TunnelType},
+        tunnel::{ErrorState, ErrorStateCause},
+    };
+
+    fn connecting() -> TunnelState {
+        TunnelState::Connecting {
+            endpoint: TunnelEndpoint {
+                endpoint: Endpoint::new([1, 2, 3, 4], 51820, TransportProtocol::Udp),
+                tunnel_type: TunnelType::Wireguard,
+                quantum_resistant: false,
+                proxy: None,
+                obfuscation: None,
+                entry_endpoint: None,
+                mtu: None,
+                wireguard_implementation: None,
+            },
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_records_failed_attempts() {
+        let settings = Settings::default();
+        let mut recorder = FailureRecorder::default();
+        recorder.update(&TunnelState::Disconnected, &connecting(), &settings);
+        assert!(recorder.last_failure().is_none());
+
+        recorder.update(&connecting(), &connecting(), &settings);
+        let capsule = recorder.last_failure().expect("missing capsule");
+        assert!(!capsule.endpoint.contains("1.2.3.4"));
+
+        let error_state = TunnelState::Error(ErrorState::new(ErrorStateCause::IsOffline, None));
+        recorder.update(&connecting(), &error_state, &settings);
+        let capsule = recorder.last_failure().expect("missing capsule");
+        assert_eq!(capsule.cause, ErrorStateCause::IsOffline.to_string());
+    }
+
+    #[test]
+    fn test_ignores_successful_and_cancelled_attempts() {
+        let settings = Settings::default();
+        let mut recorder = FailureRecorder::default();
+        recorder.update(&TunnelState::Disconnected, &connecting(), &settings);
+        recorder.update(
+            &connecting(),
+            &TunnelState::Disconnecting(talpid_types::tunnel::ActionAfterDisconnect::Nothing),
+            &settings,
+        );
+        assert!(recorder.last_failure().is_none());
+
+        // An attempt that was not seen starting is not recorded
+        recorder.update(&connecting(), &connecting(), &settings);
+        assert!(recorder.last_failure().is_none());
+    }
+}
@@ -43,6 +43,15 @@ pub async fn send_location_request(
     }
 }
 
+/// Asks am.i.mullvad.net where the IPv4 or IPv6 traffic of the daemon exits from.
+#[cfg(not(target_os = "android"))]
+pub async fn send_exit_ip_request(
+    ipv6: bool,
+    request_sender: RequestServiceHandle,
+) -> Result<AmIMullvad, Error> {
+    send_location_request_internal(if ipv6 { URI_V6 } else { URI_V4 }, request_sender).await
+}
+
 async fn send_location_request_internal(
     uri: &'static str,
     service: RequestServiceHandle,
@@ -0,0 +1,152 @@
+use chrono::offset::Utc;
+use mullvad_api::AddressCache;
+use mullvad_types::{
+    interference::{CheckKind, CheckOutcome, InterferenceCheck, InterferenceReport},
+    states::TunnelState,
+};
+use talpid_types::net::{TransportProtocol, TunnelEndpoint, TunnelType};
+
+/// Number of connection attempts in a row that may fail before the interference checks are run
+/// automatically.
+const FAILURES_BEFORE_CHECK: u32 = 3;
+
+/// Counts the connection attempts that have failed since the tunnel was last connected, and
+/// remembers which of them were made with WireGuard over plain UDP.
+#[derive(Debug, Default)]
+pub struct ConnectionFailures {
+    failures: u32,
+    udp_failures: u32,
+    udp_ports: Vec<u16>,
+}
+
+impl ConnectionFailures {
+    /// Updates the counters for a tunnel state transition. Returns `true` when the checks should
+    /// be run automatically, which happens once for each series of failures.
+    pub fn update(&mut self, old_state: &TunnelState, new_state: &TunnelState) -> bool {
+        match new_state {
+            TunnelState::Connected { .. } | TunnelState::Disconnected => {
+                *self = Self::default();
+                return false;
+            }
+            TunnelState::Connecting { .. } | TunnelState::Error(_) => (),
+            TunnelState::Disconnecting(_) => return false,
+        }
+
+        // Leaving the connecting state for anything but the connected state means that the
+        // attempt failed.
+        let endpoint = match old_state {
+            TunnelState::Connecting { endpoint, .. } => endpoint,
+            _ => return false,
+        };
+        self.failures += 1;
+        if is_plain_udp(endpoint) {
+            self.udp_failures += 1;
+            if !self.udp_ports.contains(&endpoint.endpoint.address.port()) {
+                self.udp_ports.push(endpoint.endpoint.address.port());
+            }
+        }
+        self.failures == FAILURES_BEFORE_CHECK
+    }
+
+    /// Judges whether WireGuard over UDP works from the connection attempts that have been made.
+    fn check_wireguard_udp(&self, tunnel_state: &TunnelState) -> InterferenceCheck {
+        let (outcome, details) = match tunnel_state {
+            TunnelState::Connected { endpoint, .. } if is_plain_udp(endpoint) => (
+                CheckOutcome::Passed,
+                format!("Connected to {} over UDP", endpoint.endpoint.address),
+            ),
+            _ if self.udp_failures > 0 => {
+                let ports: Vec<String> = self.udp_ports.iter().map(u16::to_string).collect();
+                (
+                    CheckOutcome::Failed,
+                    format!(
+                        "{} connection attempt(s) over UDP port {} failed",
+                        self.udp_failures,
+                        ports.join(", ")
+                    ),
+                )
+            }
+            _ => (
+                CheckOutcome::Inconclusive,
+                "No connections over plain UDP have failed since the tunnel was last connected"
+                    .to_owned(),
+            ),
+        };
+        InterferenceCheck {
+            kind: CheckKind::WireguardUdp,
+            outcome,
+            details,
+        }
+    }
+
+    /// Runs the checks and returns a report. This takes a few seconds if connections time out.
+    pub fn run_checks(
+        &self,
+        tunnel_state: &TunnelState,
+        address_cache: AddressCache,
+    ) -> impl std::future::Future<Output = InterferenceReport> {
+        let connection_failures = self.failures;
+        let udp_check = self.check_wireguard_udp(tunnel_state);
+        async move {
+            let api_address = address_cache.get_address().await;
+            let mut checks = mullvad_api::interference::check_api(api_address).await;
+            checks.insert(1, udp_check);
+            InterferenceReport {
+                created: Utc::now(),
+                connection_failures,
+                checks,
+            }
+        }
+    }
+}
+
+/// Returns whether `endpoint` is a WireGuard endpoint that is reached over UDP without any
+/// obfuscation or proxy.
+fn is_plain_udp(endpoint: &TunnelEndpoint) -> bool {
+    endpoint.tunnel_type == TunnelType::Wireguard
+        && endpoint.endpoint.protocol == TransportProtocol::Udp
+        && endpoint.obfuscation.is_none()
+        && endpoint.proxy.is_none()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::Endpoint;
+
+    fn connecting(port: u16) -> TunnelState {
+        TunnelState::Connecting {
+            endpoint: TunnelEndpoint {
+                endpoint: Endpoint::new([1, 2, 3, 4], port, TransportProtocol::Udp),
+                tunnel_type: TunnelType::Wireguard,
+                quantum_resistant: false,
+                proxy: None,
+                obfuscation: None,
+                entry_endpoint: None,
+                mtu: None,
+                wireguard_implementation: None,
+            },
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_counts_failed_attempts() {
+        let mut failures = ConnectionFailures::default();
+        assert!(!failures.update(&TunnelState::Disconnected, &connecting(51820)));
+        assert!(!failures.update(&connecting(51820), &connecting(53)));
+        assert!(!failures.update(&connecting(53), &connecting(51820)));
+        assert!(failures.update(&connecting(51820), &connecting(51820)));
+        assert!(!failures.update(&connecting(51820), &connecting(51820)));
+        assert_eq!(failures.failures, 4);
+        assert_eq!(failures.udp_ports, [51820, 53]);
+
+        let check = failures.check_wireguard_udp(&connecting(51820));
+        assert_eq!(check.outcome, CheckOutcome::Failed);
+
+        failures.update(&connecting(51820), &TunnelState::Disconnected);
+        assert_eq!(failures.failures, 0);
+        let check = failures.check_wireguard_udp(&TunnelState::Disconnected);
+        assert_eq!(check.outcome, CheckOutcome::Inconclusive);
+    }
+}
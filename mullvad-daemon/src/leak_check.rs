@@ -0,0 +1,373 @@
+use crate::geoip;
+use chrono::offset::Utc;
+use mullvad_api::{
+    interference::is_public,
+    rest::{self, RequestServiceHandle},
+};
+use mullvad_types::{
+    interference::CheckOutcome,
+    leak_check::{LeakCheck, LeakCheckKind, LeakReport},
+    location::AmIMullvad,
+    settings::{DnsState, Settings},
+    states::TunnelState,
+};
+use std::{future::Future, io, net::IpAddr};
+
+/// A resolver that a DNS leak test query reached, as reported by am.i.mullvad.net.
+#[derive(Debug, serde::Deserialize)]
+struct Resolver {
+    ip: IpAddr,
+    #[serde(default)]
+    mullvad_dns: bool,
+    #[serde(default)]
+    organization: Option<String>,
+}
+
+/// The settings that decide what the checks expect to see.
+struct Expectations {
+    connected: bool,
+    ipv6_enabled: bool,
+    custom_dns: bool,
+}
+
+/// Runs the checks and returns a report. This takes a few seconds if requests time out.
+pub fn run_checks(
+    tunnel_state: &TunnelState,
+    settings: &Settings,
+    rest_service: RequestServiceHandle,
+) -> impl Future<Output = LeakReport> {
+    let expectations = Expectations {
+        connected: matches!(tunnel_state, TunnelState::Connected { .. }),
+        ipv6_enabled: settings.tunnel_options.generic.enable_ipv6,
+        custom_dns: settings.tunnel_options.dns_options.state == DnsState::Custom,
+    };
+    let webrtc_check = check_webrtc(&expectations, local_addresses());
+
+    async move {
+        let (ipv4, ipv6, resolvers) = futures::join!(
+            geoip::send_exit_ip_request(false, rest_service.clone()),
+            geoip::send_exit_ip_request(true, rest_service.clone()),
+            send_dns_leak_request(rest_service),
+        );
+        LeakReport {
+            created: Utc::now(),
+            connected: expectations.connected,
+            checks: vec![
+                check_exit_ip(&expectations, ipv4),
+                check_dns(&expectations, resolvers),
+                check_ipv6(&expectations, ipv6),
+                webrtc_check,
+            ],
+        }
+    }
+}
+
+fn check_exit_ip(
+    expectations: &Expectations,
+    result: Result<AmIMullvad, rest::Error>,
+) -> LeakCheck {
+    let (outcome, details) = match result {
+        Ok(exit) if !expectations.connected => (
+            CheckOutcome::Inconclusive,
+            format!(
+                "Traffic exits from {}, since the tunnel is not connected",
+                exit.ip
+            ),
+        ),
+        Ok(exit) if exit.mullvad_exit_ip => (
+            CheckOutcome::Passed,
+            format!(
+                "Traffic exits from {} ({}), a Mullvad relay",
+                exit.ip,
+                place(&exit)
+            ),
+        ),
+        Ok(exit) => (
+            CheckOutcome::Failed,
+            format!(
+                "Traffic exits from {} ({}), which is not a Mullvad relay",
+                exit.ip,
+                place(&exit)
+            ),
+        ),
+        Err(error) => (
+            CheckOutcome::Inconclusive,
+            format!("Could not reach am.i.mullvad.net: {}", error),
+        ),
+    };
+    check(LeakCheckKind::ExitIp, outcome, details)
+}
+
+fn check_ipv6(expectations: &Expectations, result: Result<AmIMullvad, rest::Error>) -> LeakCheck {
+    let (outcome, details) = match result {
+        Ok(exit) if !expectations.connected => (
+            CheckOutcome::Inconclusive,
+            format!(
+                "IPv6 traffic exits from {}, since the tunnel is not connected",
+                exit.ip
+            ),
+        ),
+        Ok(exit) if exit.mullvad_exit_ip => (
+            CheckOutcome::Passed,
+            format!("IPv6 traffic exits from {}, a Mullvad relay", exit.ip),
+        ),
+        Ok(exit) => (
+            CheckOutcome::Failed,
+            format!("IPv6 traffic exits from {}, outside of the tunnel", exit.ip),
+        ),
+        Err(_) if !expectations.connected => (
+            CheckOutcome::Inconclusive,
+            "IPv6 does not work, and the tunnel is not connected".to_owned(),
+        ),
+        Err(_) if !expectations.ipv6_enabled => (
+            CheckOutcome::Passed,
+            "IPv6 is disabled in the tunnel, and no IPv6 traffic gets out".to_owned(),
+        ),
+        Err(error) => (
+            CheckOutcome::Inconclusive,
+            format!(
+                "IPv6 is enabled in the tunnel, but am.i.mullvad.net could not be reached over \
+                 it: {}",
+                error
+            ),
+        ),
+    };
+    check(LeakCheckKind::Ipv6, outcome, details)
+}
+
+/// Looks up a unique name under the leak test domain of am.i.mullvad.net, and asks which
+/// resolvers the query passed through on its way there.
+async fn send_dns_leak_request(
+    rest_service: RequestServiceHandle,
+) -> Result<Vec<Resolver>, rest::Error> {
+    let uri = format!(
+        "https://{}.dnsleak.am.i.mullvad.net/",
+        uuid::Uuid::new_v4().to_simple()
+    );
+    let request = rest::RestRequest::get(&uri)?;
+    let response = rest_service.request(request).await?;
+    rest::deserialize_body(response).await
+}
+
+fn check_dns(expectations: &Expectations, result: Result<Vec<Resolver>, rest::Error>) -> LeakCheck {
+    let resolvers = match result {
+        Ok(resolvers) if !resolvers.is_empty() => resolvers,
+        Ok(_) => {
+            return check(
+                LeakCheckKind::Dns,
+                CheckOutcome::Inconclusive,
+                "The query did not reach any resolver that am.i.mullvad.net could see".to_owned(),
+            )
+        }
+        Err(error) => {
+            return check(
+                LeakCheckKind::Dns,
+                CheckOutcome::Inconclusive,
+                format!("The DNS leak test failed: {}", error),
+            )
+        }
+    };
+    let resolver_list = resolvers
+        .iter()
+        .map(|resolver| match &resolver.organization {
+            Some(organization) => format!("{} ({})", resolver.ip, organization),
+            None => resolver.ip.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let (outcome, details) = if !expectations.connected {
+        (
+            CheckOutcome::Inconclusive,
+            format!(
+                "Queries are resolved by {}, since the tunnel is not connected",
+                resolver_list
+            ),
+        )
+    } else if resolvers.iter().all(|resolver| resolver.mullvad_dns) {
+        (
+            CheckOutcome::Passed,
+            format!("Queries are resolved by Mullvad at {}", resolver_list),
+        )
+    } else if expectations.custom_dns {
+        (
+            CheckOutcome::Inconclusive,
+            format!(
+                "Custom DNS servers are in use, and queries are resolved by {}. Whether this is \
+                 a leak depends on the servers",
+                resolver_list
+            ),
+        )
+    } else {
+        (
+            CheckOutcome::Failed,
+            format!(
+                "Queries are resolved by {}, which includes resolvers outside of the tunnel",
+                resolver_list
+            ),
+        )
+    };
+    check(LeakCheckKind::Dns, outcome, details)
+}
+
+/// Browsers include the addresses of local interfaces in WebRTC connection candidates, which
+/// websites can read even though the firewall blocks the traffic itself. Private addresses reveal
+/// little, but a public one identifies the user.
+fn check_webrtc(
+    expectations: &Expectations,
+    addresses: io::Result<Vec<(String, IpAddr)>>,
+) -> LeakCheck {
+    let public_addresses = match addresses {
+        Ok(addresses) => addresses
+            .into_iter()
+            .filter(|(_, address)| is_public(address))
+            .map(|(interface, address)| format!("{} ({})", address, interface))
+            .collect::<Vec<_>>(),
+        Err(error) => {
+            return check(
+                LeakCheckKind::WebRtc,
+                CheckOutcome::Inconclusive,
+                format!(
+                    "Failed to list the addresses of the network interfaces: {}",
+                    error
+                ),
+            )
+        }
+    };
+
+    let (outcome, details) = if !expectations.connected {
+        (
+            CheckOutcome::Inconclusive,
+            "The tunnel is not connected".to_owned(),
+        )
+    } else if public_addresses.is_empty() {
+        (
+            CheckOutcome::Passed,
+            "No network interface has a public address".to_owned(),
+        )
+    } else {
+        (
+            CheckOutcome::Failed,
+            format!(
+                "Browsers may reveal {} to websites through WebRTC. Disable WebRTC, or make sure \
+                 that the browser hides local addresses",
+                public_addresses.join(", ")
+            ),
+        )
+    };
+    check(LeakCheckKind::WebRtc, outcome, details)
+}
+
+fn check(kind: LeakCheckKind, outcome: CheckOutcome, details: String) -> LeakCheck {
+    LeakCheck {
+        kind,
+        outcome,
+        details,
+    }
+}
+
+fn place(exit: &AmIMullvad) -> String {
+    match &exit.city {
+        Some(city) => format!("{}, {}", city, exit.country),
+        None => exit.country.clone(),
+    }
+}
+
+/// Returns the addresses of all network interfaces, along with the names of the interfaces.
+#[cfg(unix)]
+fn local_addresses() -> io::Result<Vec<(String, IpAddr)>> {
+    let addresses = nix::ifaddrs::getifaddrs()
+        .map_err(|error| io::Error::from_raw_os_error(error as i32))?
+        .filter_map(|interface| {
+            let address = match interface.address? {
+                nix::sys::socket::SockAddr::Inet(address) => address.ip().to_std(),
+                _ => return None,
+            };
+            Some((interface.interface_name, address))
+        })
+        .collect();
+    Ok(addresses)
+}
+
+#[cfg(windows)]
+fn local_addresses() -> io::Result<Vec<(String, IpAddr)>> {
+    use talpid_core::windows;
+
+    let mut addresses = vec![];
+    for row in windows::get_unicast_table(None)? {
+        let address = match windows::try_socketaddr_from_inet_sockaddr(row.Address) {
+            Ok(address) => address.ip(),
+            Err(_) => continue,
+        };
+        let interface = windows::alias_from_luid(&row.InterfaceLuid)
+            .map(|alias| alias.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| row.InterfaceLuid.Value.to_string());
+        addresses.push((interface, address));
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expectations(connected: bool) -> Expectations {
+        Expectations {
+            connected,
+            ipv6_enabled: false,
+            custom_dns: false,
+        }
+    }
+
+    fn resolver(ip: &str, mullvad_dns: bool) -> Resolver {
+        Resolver {
+            ip: ip.parse().unwrap(),
+            mullvad_dns,
+            organization: None,
+        }
+    }
+
+    #[test]
+    fn test_check_dns() {
+        let check = check_dns(&expectations(true), Ok(vec![resolver("10.64.0.1", true)]));
+        assert_eq!(check.outcome, CheckOutcome::Passed);
+
+        let resolvers = vec![resolver("10.64.0.1", true), resolver("192.0.2.53", false)];
+        let check = check_dns(&expectations(true), Ok(resolvers));
+        assert_eq!(check.outcome, CheckOutcome::Failed);
+
+        let check = check_dns(
+            &expectations(false),
+            Ok(vec![resolver("192.0.2.53", false)]),
+        );
+        assert_eq!(check.outcome, CheckOutcome::Inconclusive);
+
+        let mut custom_dns = expectations(true);
+        custom_dns.custom_dns = true;
+        let check = check_dns(&custom_dns, Ok(vec![resolver("192.0.2.53", false)]));
+        assert_eq!(check.outcome, CheckOutcome::Inconclusive);
+    }
+
+    #[test]
+    fn test_check_webrtc() {
+        let addresses = || {
+            Ok(vec![
+                ("lo".to_owned(), "127.0.0.1".parse().unwrap()),
+                ("wg0-mullvad".to_owned(), "10.64.12.34".parse().unwrap()),
+                ("eth0".to_owned(), "192.168.1.10".parse().unwrap()),
+                ("eth0".to_owned(), "fe80::1".parse().unwrap()),
+            ])
+        };
+        let check = check_webrtc(&expectations(true), addresses());
+        assert_eq!(check.outcome, CheckOutcome::Passed);
+
+        let mut with_public = addresses().unwrap();
+        with_public.push(("eth0".to_owned(), "2001:db8::1".parse().unwrap()));
+        let check = check_webrtc(&expectations(true), Ok(with_public));
+        assert_eq!(check.outcome, CheckOutcome::Failed);
+        assert!(check.details.contains("2001:db8::1 (eth0)"));
+
+        let check = check_webrtc(&expectations(false), addresses());
+        assert_eq!(check.outcome, CheckOutcome::Inconclusive);
+    }
+}
@@ -6,62 +6,138 @@ extern crate serde;
 
 pub mod account_history;
 mod api;
+mod auto_connect_retry;
+#[cfg(not(target_os = "android"))]
+mod circumvention;
 #[cfg(not(target_os = "android"))]
 mod cleanup;
+#[cfg(not(target_os = "android"))]
+mod data_usage;
+#[cfg(target_os = "linux")]
+pub mod dbus_interface;
 pub mod device;
 mod dns;
+#[cfg(not(target_os = "android"))]
+mod dns_failover;
+#[cfg(target_os = "linux")]
+pub mod early_boot_firewall;
 pub mod exception_logging;
+mod exit_state;
+mod expiry_notifier;
+mod external_transports;
+#[cfg(not(target_os = "android"))]
+mod failure_capsule;
 mod geoip;
+#[cfg(not(target_os = "android"))]
+mod interference;
+#[cfg(not(target_os = "android"))]
+mod leak_check;
+mod locked_settings;
 pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(not(target_os = "android"))]
 pub mod management_interface;
 mod migrations;
+mod overrides;
+#[cfg(not(target_os = "android"))]
+mod protocol_probe;
+#[cfg(not(target_os = "android"))]
+mod relay_stats;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
 pub mod settings;
+#[cfg(not(target_os = "android"))]
+mod settings_encryption;
+mod settings_history;
+#[cfg(not(target_os = "android"))]
+pub mod snapshot;
 mod target_state;
+#[cfg(not(target_os = "android"))]
+mod transfer_monitor;
 mod tunnel;
+#[cfg(not(target_os = "android"))]
+mod tunnel_hooks;
+mod tunnel_pause;
 pub mod version;
 mod version_check;
 
 use crate::target_state::PersistentTargetState;
+use chrono::{offset::Utc, DateTime};
 use device::{PrivateAccountAndDevice, PrivateDeviceEvent};
 use futures::{
     channel::{mpsc, oneshot},
     future::{abortable, AbortHandle, Future, LocalBoxFuture},
     StreamExt,
 };
+use ipnetwork::IpNetwork;
+use mullvad_api::stats::EndpointStats;
 use mullvad_relay_selector::{
     updater::{RelayListUpdater, RelayListUpdaterHandle},
     RelaySelector, SelectorConfig,
 };
+#[cfg(not(target_os = "android"))]
+use mullvad_types::custom_list::CustomList;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::data_usage::DataUsage;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::failure_capsule::FailureCapsule;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::interference::InterferenceReport;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::leak_check::LeakReport;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::protocol_probe::ProbeReport;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::relay_stats::RelayStats;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::settings::TunnelHookSettings;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::states::DisconnectAction;
 use mullvad_types::{
-    account::{AccountData, AccountToken, VoucherSubmission},
+    access_method::ApiAccessMethodSettings,
+    account::{AccountData, AccountExpiryEvent, AccountToken, LoginLink, VoucherSubmission},
+    custom_list::CustomListsSettings,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
     location::GeoIpLocation,
-    relay_constraints::{BridgeSettings, BridgeState, ObfuscationSettings, RelaySettingsUpdate},
-    relay_list::RelayList,
-    settings::{DnsOptions, Settings},
-    states::{TargetState, TunnelState},
+    relay_constraints::{
+        BridgeSettings, BridgeState, Constraint, ObfuscationSettings, RelaySettings,
+        RelaySettingsUpdate, SelectionStrategy, TransportPort,
+    },
+    relay_list::{
+        merge_relay_overrides, ExitRelayChange, ExitRelayChangeEvent, RelayList, RelayListInfo,
+        RelayListMirror, RelayListUpdateInterval, RelayOverride,
+    },
+    settings::{
+        AppExclusionSettings, AutoConnectScope, DnsOptions, ExitRelayChangePolicy,
+        ExpiryNotificationSettings, Settings, SettingsChangeError, SettingsChanged,
+    },
+    states::{AutoConnectFailedEvent, DisconnectWarningEvent, TargetState, TunnelState},
     version::{AppVersion, AppVersionInfo},
     wireguard::{PublicKey, RotationInterval},
 };
 use settings::SettingsPersister;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
-#[cfg(target_os = "windows")]
-use std::{collections::HashSet, ffi::OsString};
 use std::{
+    collections::BTreeMap,
     marker::PhantomData,
     mem,
     path::PathBuf,
     pin::Pin,
     sync::{Arc, Weak},
-    time::Duration,
+    time::{Duration, SystemTime},
+};
+#[cfg(target_os = "windows")]
+use std::{collections::HashSet, ffi::OsString};
+#[cfg(not(target_os = "android"))]
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    time::Instant,
 };
+#[cfg(not(target_os = "android"))]
+use talpid_core::dns::forwarder::DnsQueryLog;
 #[cfg(any(target_os = "linux", windows))]
 use talpid_core::split_tunnel;
 use talpid_core::{
@@ -70,8 +146,18 @@ use talpid_core::{
 };
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::dns::DnsQueryLogEntry;
+#[cfg(windows)]
+use talpid_types::net::LinkLayerExemptions;
+#[cfg(target_os = "linux")]
+use talpid_types::net::{DnsBackend, PolicyRouting};
 use talpid_types::{
-    net::{TunnelEndpoint, TunnelType},
+    net::{
+        wireguard::LivenessOptions, CaptivePortal, FirewallException, InboundTunnelPort,
+        OfflineDetection, OfflineStatus, ReconnectDebounce, RetryPolicy, TransportProtocol,
+        TunnelEndpoint, TunnelType,
+    },
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -82,6 +168,9 @@ use tokio::io;
 /// Delay between generating a new WireGuard key and reconnecting
 const WG_RECONNECT_DELAY: Duration = Duration::from_secs(4 * 60);
 
+/// How often to check whether a login link has been approved.
+const WEB_LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 pub type ResponseTx<T, E> = oneshot::Sender<Result<T, E>>;
 
 #[derive(err_derive::Error, Debug)]
@@ -150,6 +239,44 @@ pub enum Error {
     AccountHistory(#[error(source)] account_history::Error),
 
     #[cfg(not(target_os = "android"))]
+    #[error(display = "Failed to override setting")]
+    SettingOverrideError(#[error(source)] overrides::Error),
+
+    #[error(display = "Failed to update the relay list")]
+    RelayListUpdateError(#[error(source)] mullvad_relay_selector::Error),
+
+    #[cfg(not(target_os = "android"))]
+    #[error(display = "Failed to restore snapshot")]
+    RestoreSnapshot(#[error(source)] snapshot::Error),
+
+    #[cfg(not(target_os = "android"))]
+    #[error(display = "Failed to undo settings change")]
+    SettingsRollback(#[error(source)] settings_history::Error),
+
+    #[cfg(not(target_os = "android"))]
+    #[error(
+        display = "Protocols can only be probed while disconnected, with block-when-disconnected off"
+    )]
+    ProbeRequiresDisconnected,
+
+    #[cfg(not(target_os = "android"))]
+    #[error(display = "Invalid hook script")]
+    TunnelHookError(#[error(source)] tunnel_hooks::Error),
+
+    #[error(display = "Invalid external transport")]
+    ExternalTransportError(#[error(source)] external_transports::Error),
+
+    #[cfg(not(target_os = "android"))]
+    #[error(
+        display = "The tunnel is busy, with {} bytes per second passing through it",
+        _0
+    )]
+    ActiveTransfer(u64),
+
+    #[cfg(not(target_os = "android"))]
+    #[error(display = "There is no disconnect or reconnect to confirm")]
+    NoPendingDisconnect,
+
     #[error(display = "Factory reset partially failed: {}", _0)]
     FactoryResetError(&'static str),
 
@@ -161,14 +288,109 @@ pub enum Error {
     GroupIdError(#[error(source)] io::Error),
 }
 
+impl From<settings::Error> for Error {
+    fn from(error: settings::Error) -> Self {
+        Error::SettingsError(error)
+    }
+}
+
 /// Enum representing commands that can be sent to the daemon.
 pub enum DaemonCommand {
     /// Set target state. Does nothing if the daemon already has the state that is being set.
     SetTargetState(oneshot::Sender<bool>, TargetState),
+    /// Disconnect and stop enforcing `block_when_disconnected` for the given duration. When it
+    /// has elapsed, lockdown is restored and the tunnel is connected again. The duration must
+    /// not exceed `MAX_TUNNEL_PAUSE_DURATION`.
+    PauseTunnel(ResponseTx<bool, settings::Error>, Duration),
+    /// Request the time at which a paused tunnel will be resumed, if it is paused.
+    GetTunnelPause(oneshot::Sender<Option<SystemTime>>),
+    /// Disconnect and relax the firewall to the default behavior of the OS for the given
+    /// duration, for troubleshooting. When it has elapsed, the previous target state and lockdown
+    /// are restored. The duration must not exceed `MAX_PERMISSIVE_MODE_DURATION`. Fails if the
+    /// administrator has locked `block_when_disconnected`.
+    SetPermissiveMode(ResponseTx<(), settings::Error>, Duration),
+    /// End permissive mode early. Returns whether it was active.
+    EndPermissiveMode(oneshot::Sender<bool>),
+    /// Request the time at which permissive mode ends, if it is active.
+    GetPermissiveMode(oneshot::Sender<Option<SystemTime>>),
+    /// Permit traffic outside the tunnel to a captive portal, or all traffic, for the given
+    /// duration, so that the user can sign in to the network. DNS remains blocked. The duration
+    /// must not exceed `MAX_CAPTIVE_PORTAL_DURATION`. Fails if the administrator has locked
+    /// `block_when_disconnected`.
+    SetCaptivePortal(ResponseTx<(), settings::Error>, CaptivePortal, Duration),
+    /// Stop permitting traffic to the captive portal early. Returns whether it was permitted.
+    EndCaptivePortal(oneshot::Sender<bool>),
+    /// Request what is permitted for a captive portal, and until when, if anything.
+    GetCaptivePortal(oneshot::Sender<Option<(CaptivePortal, SystemTime)>>),
+    /// Change a setting temporarily. The previous value is restored once the duration has
+    /// elapsed, or when the tunnel is reconnected if the flag is set. The duration must not
+    /// exceed `MAX_SETTING_OVERRIDE_DURATION`.
+    SetSettingOverride(
+        ResponseTx<(), Error>,
+        overrides::SettingValue,
+        Duration,
+        bool,
+    ),
+    /// End the override of a setting early, restoring its previous value.
+    ClearSettingOverride(ResponseTx<(), Error>, String),
+    /// Request the currently active setting overrides.
+    GetSettingOverrides(oneshot::Sender<Vec<overrides::SettingOverride>>),
     /// Reconnect the tunnel, if one is connecting/connected.
     Reconnect(oneshot::Sender<bool>),
+    /// Check whether a disconnect or reconnect should be held back because the tunnel is busy. If
+    /// so, an error is returned and the action waits for confirmation.
+    #[cfg(not(target_os = "android"))]
+    CheckDisconnectWarning(ResponseTx<(), Error>, DisconnectAction),
+    /// Carry out the disconnect or reconnect that was held back by the last warning.
+    #[cfg(not(target_os = "android"))]
+    ConfirmDisconnect(ResponseTx<bool, Error>),
     /// Request the current state.
     GetState(oneshot::Sender<TunnelState>),
+    /// Request what the previous daemon instance left applied to the system, if it did not exit
+    /// cleanly.
+    GetLeftoverState(oneshot::Sender<Option<exit_state::AppliedState>>),
+    /// Request whether the host is considered to be offline, and why.
+    GetOfflineStatus(oneshot::Sender<OfflineStatus>),
+    /// Request the counters of the API requests made since the daemon started, by endpoint.
+    GetApiStats(oneshot::Sender<BTreeMap<String, EndpointStats>>),
+    /// Check the network for signs of blocking or tampering with VPN and API traffic.
+    #[cfg(not(target_os = "android"))]
+    CheckNetworkInterference(oneshot::Sender<InterferenceReport>),
+    /// Request the settings that the circumvention assistant has changed.
+    #[cfg(not(target_os = "android"))]
+    GetCircumventionChanges(oneshot::Sender<Vec<circumvention::CircumventionChange>>),
+    /// Revert the settings that the circumvention assistant has changed, and respond with the
+    /// reverted changes.
+    #[cfg(not(target_os = "android"))]
+    RevertCircumventionChanges(oneshot::Sender<Vec<circumvention::CircumventionChange>>),
+    /// Request the statistics of the connections to each relay.
+    #[cfg(not(target_os = "android"))]
+    GetRelayStats(oneshot::Sender<Vec<RelayStats>>),
+    /// Forget the statistics of the connections to each relay.
+    #[cfg(not(target_os = "android"))]
+    ClearRelayStats(oneshot::Sender<()>),
+    /// Request how much data has passed through the tunnel this session and in recent months.
+    #[cfg(not(target_os = "android"))]
+    GetDataUsage(oneshot::Sender<DataUsage>),
+    /// Request the logged DNS queries after the given sequence number.
+    #[cfg(not(target_os = "android"))]
+    GetDnsQueryLog(oneshot::Sender<Vec<DnsQueryLogEntry>>, u64),
+    /// Set whether relays that connecting to keeps failing for are avoided
+    #[cfg(not(target_os = "android"))]
+    SetAvoidBadRelays(ResponseTx<(), settings::Error>, bool),
+    /// Request the context of the most recent connection attempt that failed.
+    #[cfg(not(target_os = "android"))]
+    GetLastConnectionFailure(oneshot::Sender<Option<FailureCapsule>>),
+    /// Capture the settings and relay list, with credentials removed, for reproducing problems.
+    #[cfg(not(target_os = "android"))]
+    GetSnapshot(oneshot::Sender<snapshot::Snapshot>),
+    /// Check that traffic, DNS queries and local addresses do not leak outside of the tunnel.
+    #[cfg(not(target_os = "android"))]
+    CheckLeaks(oneshot::Sender<LeakReport>),
+    /// Probe nearby relays to find out which protocols and ports get through the current
+    /// network. This requires the tunnel to be disconnected and nothing to be blocked.
+    #[cfg(not(target_os = "android"))]
+    ProbeProtocols(ResponseTx<ProbeReport, Error>),
     /// Get the current geographical location.
     GetCurrentLocation(oneshot::Sender<Option<GeoIpLocation>>),
     CreateNewAccount(ResponseTx<String, Error>),
@@ -190,8 +412,38 @@ pub enum DaemonCommand {
     /// Trigger an asynchronous relay list update. This returns before the relay list is actually
     /// updated.
     UpdateRelayLocations,
+    /// Update the relay list and respond once it has been updated, or the download has failed.
+    UpdateRelayLocationsAndWait(ResponseTx<(), Error>),
+    /// Request when the relay list was last updated, and its version.
+    GetRelayListInfo(oneshot::Sender<RelayListInfo>),
+    /// Set how old the relay list may get before it is updated
+    SetRelayListUpdateInterval(ResponseTx<(), settings::Error>, RelayListUpdateInterval),
+    /// Set a mirror to download the relay list from instead of the API, or unset it
+    SetRelayListMirror(ResponseTx<(), settings::Error>, Option<RelayListMirror>),
+    /// Add addresses to connect to relays on instead of the ones in the relay list, replacing
+    /// any existing overrides for the same relays
+    ImportRelayOverrides(ResponseTx<(), settings::Error>, Vec<RelayOverride>),
+    /// Remove all relay address overrides
+    ClearAllRelayOverrides(ResponseTx<(), settings::Error>),
+    /// Set the methods that may be used to reach the API
+    SetApiAccessMethods(ResponseTx<(), settings::Error>, ApiAccessMethodSettings),
+    /// Set whether circumvention settings are changed automatically when interference is detected
+    SetCircumventionAssistant(ResponseTx<(), settings::Error>, bool),
+    /// Add a named list of locations
+    #[cfg(not(target_os = "android"))]
+    CreateCustomList(ResponseTx<(), settings::Error>, CustomList),
+    /// Replace the locations of an existing custom list
+    #[cfg(not(target_os = "android"))]
+    UpdateCustomList(ResponseTx<(), settings::Error>, CustomList),
+    /// Remove a custom list that is not used by the relay settings
+    #[cfg(not(target_os = "android"))]
+    DeleteCustomList(ResponseTx<(), settings::Error>, String),
     /// Log in with a given account and create a new device.
     LoginAccount(ResponseTx<(), Error>, AccountToken),
+    /// Request a login link. The daemon logs in once the link has been approved in a browser.
+    StartWebLogin(ResponseTx<LoginLink, Error>),
+    /// Stop waiting for a login link to be approved.
+    CancelWebLogin(oneshot::Sender<()>),
     /// Log out of the current account and remove the device, if they exist.
     LogoutAccount(ResponseTx<(), Error>),
     /// Return the current device configuration.
@@ -206,12 +458,44 @@ pub enum DaemonCommand {
     UpdateRelaySettings(ResponseTx<(), settings::Error>, RelaySettingsUpdate),
     /// Set the allow LAN setting.
     SetAllowLan(ResponseTx<(), settings::Error>, bool),
+    /// Set the networks to allow when LAN access is enabled. `None` allows all private networks.
+    SetAllowedLanNets(ResponseTx<(), settings::Error>, Option<Vec<IpNetwork>>),
+    /// Set whether mDNS and SSDP discovery is allowed on the local network.
+    SetAllowMulticast(ResponseTx<(), settings::Error>, bool),
+    /// Set the ports that the firewall keeps open towards the local network.
+    SetFirewallExceptions(ResponseTx<(), settings::Error>, Vec<FirewallException>),
+    /// Set the ports that accept inbound connections from inside the tunnel.
+    SetInboundTunnelPorts(ResponseTx<(), settings::Error>, Vec<InboundTunnelPort>),
     /// Set the beta program setting.
     SetShowBetaReleases(ResponseTx<(), settings::Error>, bool),
+    /// Set the Android apps to exclude from the tunnel.
+    SetAppExclusions(ResponseTx<(), settings::Error>, AppExclusionSettings),
     /// Set the block_when_disconnected setting.
     SetBlockWhenDisconnected(ResponseTx<(), settings::Error>, bool),
+    /// Set whether failing to apply part of the DNS config should block the tunnel.
+    #[cfg(not(target_os = "android"))]
+    SetStrictEnforcement(ResponseTx<(), settings::Error>, bool),
     /// Set the auto-connect setting.
     SetAutoConnect(ResponseTx<(), settings::Error>, bool),
+    /// Set whether auto-connect is a per-user or machine-wide setting.
+    SetAutoConnectScope(ResponseTx<(), settings::Error>, AutoConnectScope),
+    /// Set what to do when the exit relay is moved or reclassified while connected.
+    SetExitRelayChangePolicy(ResponseTx<(), settings::Error>, ExitRelayChangePolicy),
+    /// Set how relays are chosen among the ones that match the constraints.
+    SetRelaySelectionStrategy(ResponseTx<(), settings::Error>, SelectionStrategy),
+    /// Set for how long a relay that failed to complete a handshake is deprioritized.
+    SetRelayFailureCooldown(ResponseTx<(), settings::Error>, Duration),
+    /// Set when and how to notify that the account is about to expire.
+    SetExpiryNotifications(ResponseTx<(), settings::Error>, ExpiryNotificationSettings),
+    /// Set the scripts to run when the tunnel state changes.
+    #[cfg(not(target_os = "android"))]
+    SetTunnelHooks(ResponseTx<(), Error>, TunnelHookSettings),
+    /// Set the throughput above which disconnecting and reconnecting require confirmation.
+    SetDisconnectWarningThreshold(ResponseTx<(), settings::Error>, Option<u64>),
+    /// Set how long to wait for the network to settle before reconnecting after it has changed.
+    SetReconnectDebounce(ResponseTx<(), settings::Error>, ReconnectDebounce),
+    /// Set when to consider the host to be offline.
+    SetOfflineDetection(ResponseTx<(), settings::Error>, OfflineDetection),
     /// Set the mssfix argument for OpenVPN
     SetOpenVpnMssfix(ResponseTx<(), settings::Error>, Option<u16>),
     /// Set proxy details for OpenVPN
@@ -220,6 +504,8 @@ pub enum DaemonCommand {
     SetBridgeState(ResponseTx<(), settings::Error>, BridgeState),
     /// Set if IPv6 should be enabled in the tunnel
     SetEnableIpv6(ResponseTx<(), settings::Error>, bool),
+    /// Set how to retry after failing to connect
+    SetRetryPolicy(ResponseTx<(), settings::Error>, RetryPolicy),
     /// Set whether to enable PQ PSK exchange in the tunnel
     SetQuantumResistantTunnel(ResponseTx<(), settings::Error>, bool),
     /// Set DNS options or servers to use
@@ -227,10 +513,37 @@ pub enum DaemonCommand {
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
+    /// Set how to detect that a wireguard tunnel has stopped working
+    SetWireguardLiveness(ResponseTx<(), settings::Error>, LivenessOptions),
+    /// Set the number of seconds between WireGuard keepalive packets
+    SetWireguardPersistentKeepalive(ResponseTx<(), settings::Error>, u16),
+    /// Set the networks to route through WireGuard tunnels, instead of all traffic
+    #[cfg(not(target_os = "android"))]
+    SetWireguardAllowedIps(ResponseTx<(), settings::Error>, Option<Vec<IpNetwork>>),
+    /// Set the network namespace to move the WireGuard interface into
+    #[cfg(target_os = "linux")]
+    SetWireguardNamespace(ResponseTx<(), settings::Error>, Option<String>),
+    /// Set the maximum throughput of the tunnel, in bits per second
+    #[cfg(target_os = "linux")]
+    SetRateLimit(ResponseTx<(), settings::Error>, Option<u64>),
     /// Set automatic key rotation interval for wireguard tunnels
     SetWireguardRotationInterval(ResponseTx<(), settings::Error>, Option<RotationInterval>),
     /// Get the daemon settings
     GetSettings(oneshot::Sender<Settings>),
+    /// Get the recent changes to the settings, oldest first
+    #[cfg(not(target_os = "android"))]
+    GetSettingsHistory(oneshot::Sender<Vec<settings_history::HistoryEntry>>),
+    /// Undo a change in the settings history
+    #[cfg(not(target_os = "android"))]
+    RollbackSettings(ResponseTx<(), Error>, u64),
+    /// Merge a JSON object into the settings and apply the result
+    ApplySettingsPatch(ResponseTx<(), Error>, String),
+    /// Return whether the settings file is encrypted at rest
+    #[cfg(not(target_os = "android"))]
+    GetSettingsEncryption(oneshot::Sender<bool>),
+    /// Enable or disable encryption of the settings file at rest
+    #[cfg(not(target_os = "android"))]
+    SetSettingsEncryption(ResponseTx<(), settings::Error>, bool),
     /// Generate new wireguard key
     RotateWireguardKey(ResponseTx<(), Error>),
     /// Return a public key of the currently set wireguard private key, if there is one
@@ -250,6 +563,9 @@ pub enum DaemonCommand {
     /// Exclude traffic of a process (PID) from the tunnel
     #[cfg(target_os = "linux")]
     AddSplitTunnelProcess(ResponseTx<(), split_tunnel::Error>, i32),
+    /// Exclude traffic of a process (PID) and all of its descendants from the tunnel
+    #[cfg(target_os = "linux")]
+    AddSplitTunnelProcessTree(ResponseTx<(), split_tunnel::Error>, i32),
     /// Remove process (PID) from list of processes excluded from the tunnel
     #[cfg(target_os = "linux")]
     RemoveSplitTunnelProcess(ResponseTx<(), split_tunnel::Error>, i32),
@@ -277,8 +593,18 @@ pub enum DaemonCommand {
     /// Notify the split tunnel monitor that a volume was mounted or dismounted
     #[cfg(target_os = "windows")]
     CheckVolumes(ResponseTx<(), Error>),
+    /// Set which link layer protocols the firewall permits on physical adapters
+    #[cfg(windows)]
+    SetLinkLayerExemptions(ResponseTx<(), settings::Error>, LinkLayerExemptions),
+    /// Set how DNS is configured on the host
+    #[cfg(target_os = "linux")]
+    SetDnsBackend(ResponseTx<(), settings::Error>, DnsBackend),
+    /// Set the firewall mark and routing table to use. This takes effect when the daemon is
+    /// restarted.
+    #[cfg(target_os = "linux")]
+    SetPolicyRouting(ResponseTx<(), settings::Error>, PolicyRouting),
     /// Register settings for WireGuard obfuscator
-    SetObfuscationSettings(ResponseTx<(), settings::Error>, ObfuscationSettings),
+    SetObfuscationSettings(ResponseTx<(), Error>, ObfuscationSettings),
     /// Makes the daemon exit the main loop and quit.
     Shutdown,
     /// Saves the target tunnel state and enters a blocking state. The state is restored
@@ -305,6 +631,33 @@ pub(crate) enum InternalDaemonEvent {
     /// The split tunnel paths or state were updated.
     #[cfg(target_os = "windows")]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
+    /// The duration of a tunnel pause has elapsed.
+    TunnelPauseExpired,
+    /// The duration of permissive mode has elapsed.
+    PermissiveModeExpired,
+    /// The duration for which a captive portal was permitted has elapsed.
+    CaptivePortalExpired,
+    /// The duration of the override of a setting has elapsed.
+    SettingOverrideExpired(&'static str),
+    /// A new relay list was downloaded.
+    RelayListUpdated(RelayList),
+    /// It is time to check whether the account is about to expire.
+    CheckAccountExpiry,
+    /// The expiry of the logged in account was fetched.
+    AccountExpiry(DateTime<Utc>),
+    /// The settings file was modified, possibly by another program.
+    SettingsFileChanged,
+    /// The interference checks that run after repeated connection failures have finished.
+    #[cfg(not(target_os = "android"))]
+    InterferenceChecked(InterferenceReport),
+    /// Some of the custom DNS servers stopped or started responding, and they should be used in
+    /// this order instead.
+    #[cfg(not(target_os = "android"))]
+    DnsServersReordered(Vec<IpAddr>),
+    /// The NAT64 prefix of the network was looked up. It is `None` if the network does not use
+    /// NAT64.
+    #[cfg(not(target_os = "android"))]
+    Nat64PrefixDetected(Option<Ipv6Addr>),
 }
 
 #[cfg(target_os = "windows")]
@@ -509,6 +862,71 @@ pub trait EventListener {
 
     /// Notify that a device was revoked using `RemoveDevice`.
     fn notify_remove_device_event(&self, event: RemoveDeviceEvent);
+
+    /// Notify that the relay that the tunnel exits through was moved or reclassified.
+    fn notify_exit_relay_change(&self, event: ExitRelayChangeEvent);
+
+    /// Notify that the account is about to expire.
+    fn notify_account_expiry(&self, event: AccountExpiryEvent);
+
+    /// Notify that auto-connect at startup kept failing and is no longer retried.
+    fn notify_auto_connect_failed(&self, event: AutoConnectFailedEvent);
+
+    /// Notify that a disconnect or reconnect is waiting for confirmation.
+    fn notify_disconnect_warning(&self, event: DisconnectWarningEvent);
+}
+
+/// How long a disconnect or reconnect that was held back can be confirmed.
+#[cfg(not(target_os = "android"))]
+const DISCONNECT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Makes talpid use the given firewall mark and routing table, unless they are invalid.
+#[cfg(target_os = "linux")]
+fn apply_policy_routing(routing: PolicyRouting) {
+    if routing.is_valid() {
+        talpid_core::routing::set_policy_routing(routing);
+    } else {
+        log::error!(
+            "Ignoring invalid policy routing settings ({}). Using {}",
+            routing,
+            PolicyRouting::default()
+        );
+    }
+}
+
+/// A temporary disconnect during which `block_when_disconnected` is not enforced.
+struct TunnelPause {
+    resume_at: SystemTime,
+    job: AbortHandle,
+}
+
+/// A temporary recovery mode during which neither the tunnel nor the blocking firewall is used,
+/// so that the user can regain connectivity when the tunnel stack is broken.
+struct PermissiveMode {
+    ends_at: SystemTime,
+    job: AbortHandle,
+    /// The target state to restore when permissive mode ends.
+    target_state: TargetState,
+}
+
+/// The longest that the tunnel can be paused at a time.
+const MAX_TUNNEL_PAUSE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The longest that permissive mode can last at a time.
+const MAX_PERMISSIVE_MODE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The longest that a setting can be overridden at a time.
+const MAX_SETTING_OVERRIDE_DURATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The longest that traffic to a captive portal can be permitted at a time.
+const MAX_CAPTIVE_PORTAL_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Traffic that is temporarily permitted outside the tunnel so that the user can sign in to a
+/// captive portal, without otherwise lifting the blocking firewall.
+struct CaptivePortalMode {
+    ends_at: SystemTime,
+    job: AbortHandle,
+    portal: CaptivePortal,
 }
 
 pub struct Daemon<L: EventListener> {
@@ -520,14 +938,28 @@ pub struct Daemon<L: EventListener> {
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
+    auto_connect_retry: auto_connect_retry::AutoConnectRetry,
+    tunnel_pause: Option<TunnelPause>,
+    tunnel_pause_record: tunnel_pause::TunnelPauseRecord,
+    permissive_mode: Option<PermissiveMode>,
+    captive_portal: Option<CaptivePortalMode>,
+    setting_overrides: overrides::SettingOverrides,
+    /// The last exit relay change that was reported, so that it is not reported again.
+    last_exit_relay_change: Option<(String, ExitRelayChange)>,
+    expiry_notifier: expiry_notifier::ExpiryNotifier,
+    web_login_job: Option<AbortHandle>,
     event_listener: L,
     migration_complete: migrations::MigrationComplete,
     settings: SettingsPersister,
+    /// Set while several settings are changed at once, and whether any of them has changed, so
+    /// that clients are only told about the settings once.
+    settings_batch: Option<bool>,
     account_history: account_history::AccountHistory,
     device_checker: device::TunnelStateChangeHandler,
     account_manager: device::AccountManagerHandle,
     api_runtime: mullvad_api::Runtime,
     api_handle: mullvad_api::rest::MullvadRestHandle,
+    api_access_methods: api::AccessMethodsHandle,
     version_updater_handle: version_check::VersionUpdaterHandle,
     relay_selector: RelaySelector,
     relay_list_updater: RelayListUpdaterHandle,
@@ -535,6 +967,34 @@ pub struct Daemon<L: EventListener> {
     app_version_info: Option<AppVersionInfo>,
     shutdown_tasks: Vec<Pin<Box<dyn Future<Output = ()>>>>,
     tunnel_state_machine_handle: TunnelStateMachineHandle,
+    exit_state: exit_state::ExitStateTracker,
+    #[cfg(not(target_os = "android"))]
+    connection_failures: interference::ConnectionFailures,
+    #[cfg(not(target_os = "android"))]
+    failure_recorder: failure_capsule::FailureRecorder,
+    #[cfg(not(target_os = "android"))]
+    circumvention_changes: circumvention::CircumventionChanges,
+    #[cfg(not(target_os = "android"))]
+    tunnel_hooks: tunnel_hooks::HookRunner,
+    external_transports_dir: PathBuf,
+    #[cfg(not(target_os = "android"))]
+    transfer_monitor: transfer_monitor::TransferMonitor,
+    #[cfg(not(target_os = "android"))]
+    relay_stats: relay_stats::RelayStatsStore,
+    #[cfg(not(target_os = "android"))]
+    data_usage: data_usage::DataUsageStore,
+    #[cfg(not(target_os = "android"))]
+    dns_failover: dns_failover::DnsFailover,
+    /// Queries recorded by the DNS forwarder while the query log is enabled. It is only kept in
+    /// memory.
+    #[cfg(not(target_os = "android"))]
+    dns_query_log: Arc<DnsQueryLog>,
+    /// The NAT64 prefix of the network, as last detected while disconnected.
+    #[cfg(not(target_os = "android"))]
+    nat64_prefix: Option<Ipv6Addr>,
+    /// A disconnect or reconnect that was held back, and when it was requested.
+    #[cfg(not(target_os = "android"))]
+    pending_disconnect: Option<(DisconnectAction, Instant)>,
     #[cfg(target_os = "windows")]
     volume_update_tx: mpsc::UnboundedSender<()>,
 }
@@ -550,6 +1010,7 @@ where
         cache_dir: PathBuf,
         event_listener: L,
         command_channel: DaemonCommandChannel,
+        #[cfg(not(target_os = "android"))] snapshot: Option<snapshot::Snapshot>,
         #[cfg(target_os = "android")] android_context: AndroidContext,
     ) -> Result<Self, Error> {
         #[cfg(target_os = "macos")]
@@ -560,9 +1021,23 @@ where
 
         mullvad_api::proxy::ApiConnectionMode::try_delete_cache(&cache_dir).await;
 
+        #[cfg(not(target_os = "android"))]
+        let freeze_relay_list = match snapshot {
+            Some(snapshot) => {
+                snapshot
+                    .restore(&settings_dir, &cache_dir)
+                    .await
+                    .map_err(Error::RestoreSnapshot)?;
+                true
+            }
+            None => false,
+        };
+        #[cfg(target_os = "android")]
+        let freeze_relay_list = false;
+
         let (internal_event_tx, internal_event_rx) = command_channel.destructure();
 
-        let api_runtime = mullvad_api::Runtime::with_cache(
+        let mut api_runtime = mullvad_api::Runtime::with_cache(
             &cache_dir,
             true,
             #[cfg(target_os = "android")]
@@ -585,13 +1060,70 @@ where
                 );
                 None
             });
-        let settings = SettingsPersister::load(&settings_dir).await;
+        let mut settings = SettingsPersister::load(&settings_dir).await;
+
+        let (setting_overrides, leftover_overrides) =
+            overrides::SettingOverrides::load(&settings_dir).await;
+        for restore in leftover_overrides {
+            log::info!(
+                "Restoring {} to \"{}\" after an override that did not end",
+                restore.path(),
+                restore
+            );
+            if let Err(error) = restore.save(&mut settings).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to restore overridden setting")
+                );
+            }
+        }
+
+        let (tunnel_pause_record, resume_at) =
+            tunnel_pause::TunnelPauseRecord::load(&settings_dir).await;
+        // A pause that was ongoing when the previous daemon instance exited continues, unless it
+        // has ended in the meantime
+        let remaining_pause = resume_at.and_then(|resume_at| {
+            resume_at
+                .duration_since(SystemTime::now())
+                .ok()
+                .filter(|remaining| !remaining.is_zero())
+        });
+        let block_when_disconnected = settings.block_when_disconnected && remaining_pause.is_none();
+
+        #[cfg(not(target_os = "android"))]
+        let circumvention_changes = circumvention::CircumventionChanges::load(&settings_dir).await;
+
+        let settings_file_tx = internal_event_tx.clone();
+        settings.watch(move || {
+            settings_file_tx
+                .send(InternalDaemonEvent::SettingsFileChanged)
+                .is_ok()
+        });
 
         let initial_selector_config = new_selector_config(&settings);
         let relay_selector = RelaySelector::new(initial_selector_config, &resource_dir, &cache_dir);
+        relay_selector.set_custom_lists(settings.custom_lists.clone());
+        relay_selector.set_relay_overrides(settings.relay_overrides.clone());
+        relay_selector.set_selection_strategy(settings.relay_selection_strategy);
+        relay_selector.set_failure_cooldown(settings.relay_failure_cooldown);
+        #[cfg(not(target_os = "android"))]
+        let relay_stats = relay_stats::RelayStatsStore::load(&cache_dir).await;
+        #[cfg(not(target_os = "android"))]
+        let data_usage = data_usage::DataUsageStore::load(&cache_dir).await;
+        #[cfg(not(target_os = "android"))]
+        {
+            if settings.avoid_bad_relays {
+                relay_selector.set_avoided_relays(relay_stats.bad_relays());
+            }
+            relay_selector.set_relay_latencies(relay_stats.latencies());
+        }
 
-        let proxy_provider =
-            api::ApiConnectionModeProvider::new(cache_dir.clone(), relay_selector.clone());
+        let proxy_provider = api::ApiConnectionModeProvider::new(
+            cache_dir.clone(),
+            relay_selector.clone(),
+            settings.api_access_methods.clone(),
+        );
+        let api_access_methods = proxy_provider.access_methods_handle();
         let api_handle = api_runtime
             .mullvad_rest_handle(proxy_provider, endpoint_updater.callback())
             .await;
@@ -626,12 +1158,34 @@ where
         .await
         .map_err(Error::LoadAccountHistory)?;
 
-        let target_state = if settings.auto_connect {
-            log::info!("Automatically connecting since auto-connect is turned on");
+        let mut exit_state = exit_state::ExitStateTracker::new(&cache_dir).await;
+        exit_state
+            .update(exit_state::AppliedState::new(
+                &TunnelState::Disconnected,
+                block_when_disconnected,
+            ))
+            .await;
+
+        let mut target_state = if settings.auto_connect {
+            log::info!(
+                "Automatically connecting since auto-connect is turned on (scope: {})",
+                settings.auto_connect_scope
+            );
             PersistentTargetState::force(&cache_dir, TargetState::Secured).await
         } else {
             PersistentTargetState::new(&cache_dir).await
         };
+        if let Some(remaining) = remaining_pause {
+            log::info!(
+                "Continuing a tunnel pause for {} seconds",
+                remaining.as_secs()
+            );
+            target_state.set(TargetState::Unsecured).await;
+        } else if resume_at.is_some() {
+            log::info!("A tunnel pause ended while the daemon was not running. Reconnecting");
+            target_state.set(TargetState::Secured).await;
+            tunnel_pause_record.save(None).await;
+        }
 
         #[cfg(windows)]
         let exclude_paths = if settings.split_tunnel.enable_exclusions {
@@ -651,19 +1205,57 @@ where
             account_manager.clone(),
             relay_selector.clone(),
             settings.tunnel_options.clone(),
+            settings_dir.join(external_transports::TRANSPORTS_DIR),
         );
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
+        #[cfg(target_os = "linux")]
+        talpid_core::dns::set_backend(settings.dns_backend);
+        #[cfg(target_os = "linux")]
+        apply_policy_routing(settings.policy_routing);
+        #[cfg(not(target_os = "android"))]
+        let dns_query_log = Arc::new(DnsQueryLog::new());
+
         let tunnel_state_machine_handle = tunnel_state_machine::spawn(
             tunnel_state_machine::InitialTunnelState {
                 allow_lan: settings.allow_lan,
-                block_when_disconnected: settings.block_when_disconnected,
+                allowed_lan_nets: settings.allowed_lan_nets.clone(),
+                firewall_exceptions: settings.firewall_exceptions.clone(),
+                inbound_tunnel_ports: settings.inbound_tunnel_ports.clone(),
+                allow_multicast: settings.allow_multicast,
+                block_when_disconnected,
+                #[cfg(not(target_os = "android"))]
+                strict_enforcement: settings.strict_enforcement,
                 dns_servers: dns::addresses_from_options(&settings.tunnel_options.dns_options),
+                #[cfg(not(target_os = "android"))]
+                split_dns_rules: settings.tunnel_options.dns_options.split_rules.clone(),
+                #[cfg(not(target_os = "android"))]
+                dns_filter: dns::filter_from_options(&settings.tunnel_options.dns_options),
+                #[cfg(not(target_os = "android"))]
+                dns_query_log: dns::query_log_from_options(
+                    &dns_query_log,
+                    &settings.tunnel_options.dns_options,
+                ),
+                #[cfg(not(target_os = "android"))]
+                search_domains: settings.tunnel_options.dns_options.search_domains.clone(),
+                #[cfg(not(target_os = "android"))]
+                strict_dns_leak_prevention: settings
+                    .tunnel_options
+                    .dns_options
+                    .strict_leak_prevention,
                 allowed_endpoint: initial_api_endpoint,
                 reset_firewall: *target_state != TargetState::Secured,
+                reconnect_debounce: settings.reconnect_debounce,
+                offline_detection: settings.offline_detection,
                 #[cfg(windows)]
                 exclude_paths,
+                #[cfg(windows)]
+                link_layer_exemptions: settings.link_layer_exemptions,
+                leftover_state: exit_state
+                    .leftover()
+                    .map(tunnel_state_machine::LeftoverState::from)
+                    .unwrap_or_default(),
             },
             parameters_generator.clone(),
             log_dir,
@@ -686,14 +1278,20 @@ where
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
         let relay_list_listener = event_listener.clone();
+        let relay_list_event_tx = internal_event_tx.clone();
         let on_relay_list_update = move |relay_list: &RelayList| {
             relay_list_listener.notify_relay_list(relay_list.clone());
+            let _ =
+                relay_list_event_tx.send(InternalDaemonEvent::RelayListUpdated(relay_list.clone()));
         };
 
         let mut relay_list_updater = RelayListUpdater::spawn(
             relay_selector.clone(),
             api_handle.clone(),
+            api_runtime.rest_handle().await,
             &cache_dir,
+            settings.relay_list_update_interval.as_duration(),
+            settings.relay_list_mirror.clone(),
             on_relay_list_update,
         );
 
@@ -708,10 +1306,35 @@ where
         );
         tokio::spawn(version_updater.run());
 
-        // Attempt to download a fresh relay list
-        relay_list_updater.update().await;
+        let expiry_notifier = expiry_notifier::ExpiryNotifier::new(&cache_dir).await;
+        let expiry_check_tx = internal_event_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(expiry_notifier::CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if expiry_check_tx
+                    .send(InternalDaemonEvent::CheckAccountExpiry)
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        if freeze_relay_list {
+            // Keep using the relay list from the snapshot
+            relay_list_updater
+                .set_update_interval(mullvad_types::relay_list::MAX_RELAY_LIST_UPDATE_INTERVAL)
+                .await;
+        } else {
+            // Attempt to download a fresh relay list
+            relay_list_updater.update().await;
+        }
+
+        #[cfg(not(target_os = "android"))]
+        let dns_failover = dns_failover::DnsFailover::new(internal_event_tx.clone());
 
-        let daemon = Daemon {
+        let mut daemon = Daemon {
             tunnel_state: TunnelState::Disconnected,
             target_state,
             state: DaemonExecutionState::Running,
@@ -720,14 +1343,28 @@ where
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
+            auto_connect_retry: auto_connect_retry::AutoConnectRetry::new(
+                settings.auto_connect,
+                settings.auto_connect_scope == AutoConnectScope::Machine,
+            ),
+            tunnel_pause: None,
+            tunnel_pause_record,
+            permissive_mode: None,
+            captive_portal: None,
+            setting_overrides,
+            last_exit_relay_change: None,
+            expiry_notifier,
+            web_login_job: None,
             event_listener,
             migration_complete,
             settings,
+            settings_batch: None,
             account_history,
             device_checker: device::TunnelStateChangeHandler::new(account_manager.clone()),
             account_manager,
             api_runtime,
             api_handle,
+            api_access_methods,
             version_updater_handle,
             relay_selector,
             relay_list_updater,
@@ -735,9 +1372,36 @@ where
             app_version_info,
             shutdown_tasks: vec![],
             tunnel_state_machine_handle,
+            exit_state,
+            #[cfg(not(target_os = "android"))]
+            connection_failures: interference::ConnectionFailures::default(),
+            #[cfg(not(target_os = "android"))]
+            failure_recorder: failure_capsule::FailureRecorder::default(),
+            #[cfg(not(target_os = "android"))]
+            circumvention_changes,
+            #[cfg(not(target_os = "android"))]
+            tunnel_hooks: tunnel_hooks::HookRunner::new(settings_dir.join(tunnel_hooks::HOOKS_DIR)),
+            external_transports_dir: settings_dir.join(external_transports::TRANSPORTS_DIR),
+            #[cfg(not(target_os = "android"))]
+            transfer_monitor: transfer_monitor::TransferMonitor::new(data_usage.clone()),
+            #[cfg(not(target_os = "android"))]
+            data_usage,
+            #[cfg(not(target_os = "android"))]
+            relay_stats,
+            #[cfg(not(target_os = "android"))]
+            dns_failover,
+            #[cfg(not(target_os = "android"))]
+            dns_query_log,
+            #[cfg(not(target_os = "android"))]
+            nat64_prefix: None,
+            #[cfg(not(target_os = "android"))]
+            pending_disconnect: None,
             #[cfg(target_os = "windows")]
             volume_update_tx,
         };
+        if let Some(remaining) = remaining_pause {
+            daemon.schedule_tunnel_pause(remaining);
+        }
 
         api_availability.unsuspend();
 
@@ -770,13 +1434,16 @@ where
     }
 
     async fn finalize(self) {
-        let (event_listener, shutdown_tasks, api_runtime, tunnel_state_machine_handle) =
+        let (event_listener, shutdown_tasks, api_runtime, tunnel_state_machine_handle, exit_state) =
             self.shutdown();
         for future in shutdown_tasks {
             future.await;
         }
 
-        tunnel_state_machine_handle.try_join().await;
+        // Only consider the exit clean if the firewall, routes and DNS had time to be torn down
+        if tunnel_state_machine_handle.try_join().await {
+            exit_state.finalize().await;
+        }
 
         drop(event_listener);
         drop(api_runtime);
@@ -798,6 +1465,7 @@ where
         Vec<LocalBoxFuture<'a, ()>>,
         mullvad_api::Runtime,
         TunnelStateMachineHandle,
+        exit_state::ExitStateTracker,
     ) {
         let Daemon {
             event_listener,
@@ -806,17 +1474,23 @@ where
             tunnel_state_machine_handle,
             target_state,
             account_manager,
+            exit_state,
+            #[cfg(not(target_os = "android"))]
+            data_usage,
             ..
         } = self;
 
         shutdown_tasks.push(Box::pin(target_state.finalize()));
         shutdown_tasks.push(Box::pin(account_manager.shutdown()));
+        #[cfg(not(target_os = "android"))]
+        shutdown_tasks.push(Box::pin(async move { data_usage.save().await }));
 
         (
             event_listener,
             shutdown_tasks,
             api_runtime,
             tunnel_state_machine_handle,
+            exit_state,
         )
     }
 
@@ -835,6 +1509,47 @@ where
             DeviceMigrationEvent(event) => self.handle_device_migration_event(event).await,
             #[cfg(windows)]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
+            TunnelPauseExpired => self.handle_tunnel_pause_expired().await,
+            PermissiveModeExpired => self.handle_permissive_mode_expired().await,
+            CaptivePortalExpired => self.handle_captive_portal_expired(),
+            SettingOverrideExpired(path) => self.handle_setting_override_expired(path).await,
+            RelayListUpdated(relay_list) => self.handle_relay_list_update(relay_list).await,
+            CheckAccountExpiry => self.handle_check_account_expiry().await,
+            AccountExpiry(expiry) => self.handle_account_expiry(expiry).await,
+            SettingsFileChanged => self.handle_settings_file_changed().await,
+            #[cfg(not(target_os = "android"))]
+            InterferenceChecked(report) => self.handle_interference_report(report).await,
+            #[cfg(not(target_os = "android"))]
+            DnsServersReordered(servers) => self.handle_dns_servers_reordered(servers),
+            #[cfg(not(target_os = "android"))]
+            Nat64PrefixDetected(prefix) => self.handle_nat64_prefix_detected(prefix),
+        }
+    }
+
+    /// Remembers the transport of a working OpenVPN tunnel, so that the relay selector tries it
+    /// first the next time. It is forgotten when the device goes offline, since that is usually
+    /// what happens when moving to another network.
+    fn update_working_openvpn_transport(&self, tunnel_state: &TunnelState) {
+        match tunnel_state {
+            TunnelState::Connected { endpoint, .. }
+                if endpoint.tunnel_type == TunnelType::OpenVpn && endpoint.proxy.is_none() =>
+            {
+                let protocol = endpoint.endpoint.protocol;
+                // Any UDP port tends to work if one does, whereas TCP is often only let through
+                // on some ports
+                let port = match protocol {
+                    TransportProtocol::Udp => Constraint::Any,
+                    TransportProtocol::Tcp => Constraint::Only(endpoint.endpoint.address.port()),
+                };
+                self.relay_selector
+                    .set_working_openvpn_transport(Some(TransportPort { protocol, port }));
+            }
+            TunnelState::Error(error_state)
+                if matches!(error_state.cause(), ErrorStateCause::IsOffline) =>
+            {
+                self.relay_selector.set_working_openvpn_transport(None);
+            }
+            _ => (),
         }
     }
 
@@ -847,16 +1562,21 @@ where
         self.device_checker
             .handle_state_transition(&tunnel_state_transition);
 
+        #[cfg_attr(target_os = "android", allow(unused))]
+        let mut tunnel_interface = None;
         let tunnel_state = match tunnel_state_transition {
             TunnelStateTransition::Disconnected => TunnelState::Disconnected,
             TunnelStateTransition::Connecting(endpoint) => TunnelState::Connecting {
                 endpoint,
                 location: self.parameters_generator.get_last_location().await,
             },
-            TunnelStateTransition::Connected(endpoint) => TunnelState::Connected {
-                endpoint,
-                location: self.parameters_generator.get_last_location().await,
-            },
+            TunnelStateTransition::Connected(endpoint, interface) => {
+                tunnel_interface = Some(interface);
+                TunnelState::Connected {
+                    endpoint,
+                    location: self.parameters_generator.get_last_location().await,
+                }
+            }
             TunnelStateTransition::Disconnecting(after_disconnect) => {
                 TunnelState::Disconnecting(after_disconnect)
             }
@@ -872,6 +1592,8 @@ where
 
         log::debug!("New tunnel state: {:?}", tunnel_state);
 
+        self.update_working_openvpn_transport(&tunnel_state);
+
         match tunnel_state {
             TunnelState::Disconnected => {
                 self.api_handle.availability.reset_inactivity_timer();
@@ -903,6 +1625,105 @@ where
             _ => {}
         }
 
+        match self.auto_connect_retry.update(&tunnel_state) {
+            auto_connect_retry::RetryDecision::Retry(delay) => {
+                log::info!(
+                    "Auto-connect ended up in the error state. Retrying in {} seconds",
+                    delay.as_secs()
+                );
+                self.schedule_reconnect(delay);
+            }
+            auto_connect_retry::RetryDecision::GiveUp(retries) => {
+                log::warn!(
+                    "Auto-connect still fails after {} retries. Giving up until the user connects",
+                    retries
+                );
+                self.event_listener
+                    .notify_auto_connect_failed(AutoConnectFailedEvent { retries });
+            }
+            auto_connect_retry::RetryDecision::Wait => (),
+        }
+
+        self.exit_state
+            .update(exit_state::AppliedState::new(
+                &tunnel_state,
+                self.settings.block_when_disconnected,
+            ))
+            .await;
+
+        #[cfg(not(target_os = "android"))]
+        self.failure_recorder
+            .update(&self.tunnel_state, &tunnel_state, &self.settings);
+
+        #[cfg(not(target_os = "android"))]
+        self.tunnel_hooks.tunnel_state_changed(
+            &self.settings.tunnel_hooks,
+            &tunnel_state,
+            tunnel_interface.as_ref(),
+        );
+
+        // The peak throughput must be read before the transfer monitor is reset
+        #[cfg(not(target_os = "android"))]
+        if self
+            .relay_stats
+            .update(
+                &self.tunnel_state,
+                &tunnel_state,
+                self.transfer_monitor.peak_throughput(),
+            )
+            .await
+        {
+            self.update_selector_relay_stats();
+        }
+
+        #[cfg(not(target_os = "android"))]
+        {
+            self.transfer_monitor
+                .tunnel_state_changed(tunnel_interface.as_ref());
+            self.pending_disconnect = None;
+            self.dns_failover.update(if tunnel_state.is_connected() {
+                dns::addresses_from_options(&self.settings.tunnel_options.dns_options)
+            } else {
+                None
+            });
+        }
+
+        // The host's own resolvers are only in use while disconnected
+        #[cfg(not(target_os = "android"))]
+        if tunnel_state.is_disconnected() {
+            let event_tx = self.tx.clone();
+            tokio::spawn(async move {
+                match dns::detect_nat64_prefix().await {
+                    Ok(prefix) => {
+                        let _ = event_tx.send(InternalDaemonEvent::Nat64PrefixDetected(prefix));
+                    }
+                    Err(error) => log::debug!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to look up the NAT64 prefix")
+                    ),
+                }
+            });
+        }
+
+        #[cfg(not(target_os = "android"))]
+        if self
+            .connection_failures
+            .update(&self.tunnel_state, &tunnel_state)
+        {
+            let checks = self
+                .connection_failures
+                .run_checks(&tunnel_state, self.api_runtime.address_cache.clone());
+            let event_tx = self.tx.clone();
+            tokio::spawn(async move {
+                let report = checks.await;
+                log::warn!(
+                    "Connecting keeps failing, checking the network for interference\n{}",
+                    report
+                );
+                let _ = event_tx.send(InternalDaemonEvent::InterferenceChecked(report));
+            });
+        }
+
         self.tunnel_state = tunnel_state.clone();
         self.event_listener.notify_new_state(tunnel_state);
     }
@@ -913,7 +1734,7 @@ where
     ) {
         match (&self.tunnel_state, &tunnel_state_transition) {
             // only reset the API sockets if when connected or leaving the connected state
-            (&TunnelState::Connected { .. }, _) | (_, &TunnelStateTransition::Connected(_)) => {
+            (&TunnelState::Connected { .. }, _) | (_, &TunnelStateTransition::Connected(..)) => {
                 self.api_handle.service().reset();
             }
             _ => (),
@@ -943,6 +1764,65 @@ where
         }
     }
 
+    /// Ends an ongoing tunnel pause, if any, and enforces `block_when_disconnected` again.
+    /// Returns whether the tunnel was paused.
+    async fn end_tunnel_pause(&mut self) -> bool {
+        match self.tunnel_pause.take() {
+            Some(pause) => {
+                pause.job.abort();
+                self.tunnel_pause_record.save(None).await;
+                if self.settings.block_when_disconnected {
+                    self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(true));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn handle_tunnel_pause_expired(&mut self) {
+        if self.end_tunnel_pause().await {
+            log::info!("Tunnel pause has expired. Reconnecting");
+            self.set_target_state(TargetState::Secured).await;
+        }
+    }
+
+    /// Ends permissive mode, if it is active, and enforces `block_when_disconnected` again.
+    /// Returns the target state to restore if permissive mode was active.
+    fn end_permissive_mode(&mut self) -> Option<TargetState> {
+        let mode = self.permissive_mode.take()?;
+        mode.job.abort();
+        if self.settings.block_when_disconnected {
+            self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(true));
+        }
+        Some(mode.target_state)
+    }
+
+    async fn handle_permissive_mode_expired(&mut self) {
+        if let Some(target_state) = self.end_permissive_mode() {
+            log::warn!("Permissive mode has expired. Enforcing the firewall again");
+            self.set_target_state(target_state).await;
+        }
+    }
+
+    /// Stops permitting traffic to a captive portal. Returns whether it was permitted.
+    fn end_captive_portal(&mut self) -> bool {
+        match self.captive_portal.take() {
+            Some(mode) => {
+                mode.job.abort();
+                self.send_tunnel_command(TunnelCommand::CaptivePortal(None));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn handle_captive_portal_expired(&mut self) {
+        if self.end_captive_portal() {
+            log::info!("Captive portal access has expired. Blocking traffic again");
+        }
+    }
+
     async fn handle_command(&mut self, command: DaemonCommand) {
         use self::DaemonCommand::*;
         if !self.state.is_running() {
@@ -956,48 +1836,192 @@ where
 
         match command {
             SetTargetState(tx, state) => self.on_set_target_state(tx, state).await,
-            Reconnect(tx) => self.on_reconnect(tx),
-            GetState(tx) => self.on_get_state(tx),
-            GetCurrentLocation(tx) => self.on_get_current_location(tx).await,
-            CreateNewAccount(tx) => self.on_create_new_account(tx).await,
-            GetAccountData(tx, account_token) => self.on_get_account_data(tx, account_token).await,
-            GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
-            SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher).await,
-            GetRelayLocations(tx) => self.on_get_relay_locations(tx),
-            UpdateRelayLocations => self.on_update_relay_locations().await,
-            LoginAccount(tx, account_token) => self.on_login_account(tx, account_token),
-            LogoutAccount(tx) => self.on_logout_account(tx),
-            GetDevice(tx) => self.on_get_device(tx).await,
-            UpdateDevice(tx) => self.on_update_device(tx).await,
-            ListDevices(tx, account_token) => self.on_list_devices(tx, account_token).await,
-            RemoveDevice(tx, account_token, device_id) => {
-                self.on_remove_device(tx, account_token, device_id).await
+            PauseTunnel(tx, duration) => self.on_pause_tunnel(tx, duration).await,
+            GetTunnelPause(tx) => self.on_get_tunnel_pause(tx),
+            SetPermissiveMode(tx, duration) => self.on_set_permissive_mode(tx, duration).await,
+            EndPermissiveMode(tx) => self.on_end_permissive_mode(tx).await,
+            GetPermissiveMode(tx) => self.on_get_permissive_mode(tx),
+            SetCaptivePortal(tx, portal, duration) => {
+                self.on_set_captive_portal(tx, portal, duration)
             }
-            GetAccountHistory(tx) => self.on_get_account_history(tx),
+            EndCaptivePortal(tx) => self.on_end_captive_portal(tx),
+            GetCaptivePortal(tx) => self.on_get_captive_portal(tx),
+            SetSettingOverride(tx, value, duration, revert_on_reconnect) => {
+                self.on_set_setting_override(tx, value, duration, revert_on_reconnect)
+                    .await
+            }
+            ClearSettingOverride(tx, path) => self.on_clear_setting_override(tx, path).await,
+            GetSettingOverrides(tx) => self.on_get_setting_overrides(tx),
+            Reconnect(tx) => self.on_reconnect(tx).await,
+            #[cfg(not(target_os = "android"))]
+            CheckDisconnectWarning(tx, action) => self.on_check_disconnect_warning(tx, action),
+            #[cfg(not(target_os = "android"))]
+            ConfirmDisconnect(tx) => self.on_confirm_disconnect(tx).await,
+            GetState(tx) => self.on_get_state(tx),
+            GetLeftoverState(tx) => self.on_get_leftover_state(tx),
+            GetOfflineStatus(tx) => self.on_get_offline_status(tx),
+            GetApiStats(tx) => self.on_get_api_stats(tx),
+            #[cfg(not(target_os = "android"))]
+            CheckNetworkInterference(tx) => self.on_check_network_interference(tx),
+            #[cfg(not(target_os = "android"))]
+            GetCircumventionChanges(tx) => self.on_get_circumvention_changes(tx),
+            #[cfg(not(target_os = "android"))]
+            RevertCircumventionChanges(tx) => self.on_revert_circumvention_changes(tx).await,
+            #[cfg(not(target_os = "android"))]
+            GetRelayStats(tx) => self.on_get_relay_stats(tx),
+            #[cfg(not(target_os = "android"))]
+            ClearRelayStats(tx) => self.on_clear_relay_stats(tx).await,
+            #[cfg(not(target_os = "android"))]
+            GetDataUsage(tx) => self.on_get_data_usage(tx),
+            #[cfg(not(target_os = "android"))]
+            GetDnsQueryLog(tx, sequence) => self.on_get_dns_query_log(tx, sequence),
+            #[cfg(not(target_os = "android"))]
+            SetAvoidBadRelays(tx, enabled) => self.on_set_avoid_bad_relays(tx, enabled).await,
+            #[cfg(not(target_os = "android"))]
+            GetLastConnectionFailure(tx) => self.on_get_last_connection_failure(tx),
+            #[cfg(not(target_os = "android"))]
+            GetSnapshot(tx) => self.on_get_snapshot(tx),
+            #[cfg(not(target_os = "android"))]
+            CheckLeaks(tx) => self.on_check_leaks(tx).await,
+            #[cfg(not(target_os = "android"))]
+            ProbeProtocols(tx) => self.on_probe_protocols(tx).await,
+            GetCurrentLocation(tx) => self.on_get_current_location(tx).await,
+            CreateNewAccount(tx) => self.on_create_new_account(tx).await,
+            GetAccountData(tx, account_token) => self.on_get_account_data(tx, account_token).await,
+            GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
+            SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher).await,
+            GetRelayLocations(tx) => self.on_get_relay_locations(tx),
+            UpdateRelayLocations => self.on_update_relay_locations().await,
+            UpdateRelayLocationsAndWait(tx) => self.on_update_relay_locations_and_wait(tx),
+            GetRelayListInfo(tx) => self.on_get_relay_list_info(tx),
+            SetRelayListUpdateInterval(tx, interval) => {
+                self.on_set_relay_list_update_interval(tx, interval).await
+            }
+            SetRelayListMirror(tx, mirror) => self.on_set_relay_list_mirror(tx, mirror).await,
+            ImportRelayOverrides(tx, overrides) => {
+                let overrides = merge_relay_overrides(&self.settings.relay_overrides, overrides);
+                self.on_set_relay_overrides(tx, overrides).await
+            }
+            ClearAllRelayOverrides(tx) => self.on_set_relay_overrides(tx, vec![]).await,
+            SetApiAccessMethods(tx, access_methods) => {
+                self.on_set_api_access_methods(tx, access_methods).await
+            }
+            SetCircumventionAssistant(tx, enabled) => {
+                self.on_set_circumvention_assistant(tx, enabled).await
+            }
+            #[cfg(not(target_os = "android"))]
+            CreateCustomList(tx, list) => self.on_create_custom_list(tx, list).await,
+            #[cfg(not(target_os = "android"))]
+            UpdateCustomList(tx, list) => self.on_update_custom_list(tx, list).await,
+            #[cfg(not(target_os = "android"))]
+            DeleteCustomList(tx, name) => self.on_delete_custom_list(tx, name).await,
+            LoginAccount(tx, account_token) => self.on_login_account(tx, account_token),
+            StartWebLogin(tx) => self.on_start_web_login(tx),
+            CancelWebLogin(tx) => self.on_cancel_web_login(tx),
+            LogoutAccount(tx) => self.on_logout_account(tx),
+            GetDevice(tx) => self.on_get_device(tx).await,
+            UpdateDevice(tx) => self.on_update_device(tx).await,
+            ListDevices(tx, account_token) => self.on_list_devices(tx, account_token).await,
+            RemoveDevice(tx, account_token, device_id) => {
+                self.on_remove_device(tx, account_token, device_id).await
+            }
+            GetAccountHistory(tx) => self.on_get_account_history(tx),
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
             UpdateRelaySettings(tx, update) => self.on_update_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
+            SetAllowedLanNets(tx, allowed_lan_nets) => {
+                self.on_set_allowed_lan_nets(tx, allowed_lan_nets).await
+            }
+            SetAllowMulticast(tx, allow_multicast) => {
+                self.on_set_allow_multicast(tx, allow_multicast).await
+            }
+            SetFirewallExceptions(tx, exceptions) => {
+                self.on_set_firewall_exceptions(tx, exceptions).await
+            }
+            SetInboundTunnelPorts(tx, ports) => self.on_set_inbound_tunnel_ports(tx, ports).await,
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
+            SetAppExclusions(tx, app_exclusions) => {
+                self.on_set_app_exclusions(tx, app_exclusions).await
+            }
             SetBlockWhenDisconnected(tx, block_when_disconnected) => {
                 self.on_set_block_when_disconnected(tx, block_when_disconnected)
                     .await
             }
+            #[cfg(not(target_os = "android"))]
+            SetStrictEnforcement(tx, strict_enforcement) => {
+                self.on_set_strict_enforcement(tx, strict_enforcement).await
+            }
             SetAutoConnect(tx, auto_connect) => self.on_set_auto_connect(tx, auto_connect).await,
+            SetAutoConnectScope(tx, scope) => self.on_set_auto_connect_scope(tx, scope).await,
+            SetExitRelayChangePolicy(tx, policy) => {
+                self.on_set_exit_relay_change_policy(tx, policy).await
+            }
+            SetRelaySelectionStrategy(tx, strategy) => {
+                self.on_set_relay_selection_strategy(tx, strategy).await
+            }
+            SetRelayFailureCooldown(tx, cooldown) => {
+                self.on_set_relay_failure_cooldown(tx, cooldown).await
+            }
+            SetExpiryNotifications(tx, expiry_notifications) => {
+                self.on_set_expiry_notifications(tx, expiry_notifications)
+                    .await
+            }
+            #[cfg(not(target_os = "android"))]
+            SetTunnelHooks(tx, tunnel_hooks) => self.on_set_tunnel_hooks(tx, tunnel_hooks).await,
+            SetDisconnectWarningThreshold(tx, threshold) => {
+                self.on_set_disconnect_warning_threshold(tx, threshold)
+                    .await
+            }
+            SetReconnectDebounce(tx, reconnect_debounce) => {
+                self.on_set_reconnect_debounce(tx, reconnect_debounce).await
+            }
+            SetOfflineDetection(tx, offline_detection) => {
+                self.on_set_offline_detection(tx, offline_detection).await
+            }
             SetOpenVpnMssfix(tx, mssfix_arg) => self.on_set_openvpn_mssfix(tx, mssfix_arg).await,
             SetBridgeSettings(tx, bridge_settings) => {
                 self.on_set_bridge_settings(tx, bridge_settings).await
             }
             SetBridgeState(tx, bridge_state) => self.on_set_bridge_state(tx, bridge_state).await,
             SetEnableIpv6(tx, enable_ipv6) => self.on_set_enable_ipv6(tx, enable_ipv6).await,
+            SetRetryPolicy(tx, retry_policy) => self.on_set_retry_policy(tx, retry_policy).await,
             SetQuantumResistantTunnel(tx, enable_pq) => {
                 self.on_set_quantum_resistant_tunnel(tx, enable_pq).await
             }
             SetDnsOptions(tx, dns_servers) => self.on_set_dns_options(tx, dns_servers).await,
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
+            SetWireguardLiveness(tx, liveness) => {
+                self.on_set_wireguard_liveness(tx, liveness).await
+            }
+            SetWireguardPersistentKeepalive(tx, interval) => {
+                self.on_set_wireguard_persistent_keepalive(tx, interval)
+                    .await
+            }
+            #[cfg(not(target_os = "android"))]
+            SetWireguardAllowedIps(tx, allowed_ips) => {
+                self.on_set_wireguard_allowed_ips(tx, allowed_ips).await
+            }
+            #[cfg(target_os = "linux")]
+            SetWireguardNamespace(tx, namespace) => {
+                self.on_set_wireguard_namespace(tx, namespace).await
+            }
+            #[cfg(target_os = "linux")]
+            SetRateLimit(tx, rate_limit) => self.on_set_rate_limit(tx, rate_limit).await,
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
             }
             GetSettings(tx) => self.on_get_settings(tx),
+            #[cfg(not(target_os = "android"))]
+            GetSettingsHistory(tx) => self.on_get_settings_history(tx),
+            #[cfg(not(target_os = "android"))]
+            RollbackSettings(tx, id) => self.on_rollback_settings(tx, id).await,
+            ApplySettingsPatch(tx, patch) => self.on_apply_settings_patch(tx, patch).await,
+            #[cfg(not(target_os = "android"))]
+            GetSettingsEncryption(tx) => self.on_get_settings_encryption(tx),
+            #[cfg(not(target_os = "android"))]
+            SetSettingsEncryption(tx, enabled) => {
+                self.on_set_settings_encryption(tx, enabled).await
+            }
             RotateWireguardKey(tx) => self.on_rotate_wireguard_key(tx).await,
             GetWireguardKey(tx) => self.on_get_wireguard_key(tx).await,
             GetVersionInfo(tx) => self.on_get_version_info(tx).await,
@@ -1010,6 +2034,8 @@ where
             #[cfg(target_os = "linux")]
             AddSplitTunnelProcess(tx, pid) => self.on_add_split_tunnel_process(tx, pid),
             #[cfg(target_os = "linux")]
+            AddSplitTunnelProcessTree(tx, pid) => self.on_add_split_tunnel_process_tree(tx, pid),
+            #[cfg(target_os = "linux")]
             RemoveSplitTunnelProcess(tx, pid) => self.on_remove_split_tunnel_process(tx, pid),
             #[cfg(target_os = "linux")]
             ClearSplitTunnelProcesses(tx) => self.on_clear_split_tunnel_processes(tx),
@@ -1027,6 +2053,14 @@ where
             UseWireGuardNt(tx, state) => self.on_use_wireguard_nt(tx, state).await,
             #[cfg(target_os = "windows")]
             CheckVolumes(tx) => self.on_check_volumes(tx).await,
+            #[cfg(windows)]
+            SetLinkLayerExemptions(tx, exemptions) => {
+                self.on_set_link_layer_exemptions(tx, exemptions).await
+            }
+            #[cfg(target_os = "linux")]
+            SetDnsBackend(tx, backend) => self.on_set_dns_backend(tx, backend).await,
+            #[cfg(target_os = "linux")]
+            SetPolicyRouting(tx, routing) => self.on_set_policy_routing(tx, routing).await,
             SetObfuscationSettings(tx, settings) => {
                 self.on_set_obfuscation_settings(tx, settings).await
             }
@@ -1042,6 +2076,84 @@ where
         self.event_listener.notify_app_version(app_version_info);
     }
 
+    async fn handle_relay_list_update(&mut self, relay_list: RelayList) {
+        if !self.tunnel_state.is_connected() {
+            return;
+        }
+        if let RelaySettings::CustomTunnelEndpoint(_) = self.settings.get_relay_settings() {
+            return;
+        }
+        let exit_relay = match self.parameters_generator.get_last_exit_relay().await {
+            Some(relay) => relay,
+            None => return,
+        };
+        let change = match ExitRelayChange::detect(&exit_relay, &relay_list) {
+            Some(change) => change,
+            None => return,
+        };
+
+        let reported_change = (exit_relay.hostname.clone(), change.clone());
+        if self.last_exit_relay_change.as_ref() == Some(&reported_change) {
+            return;
+        }
+        self.last_exit_relay_change = Some(reported_change);
+
+        let reconnecting =
+            self.settings.exit_relay_change_policy == ExitRelayChangePolicy::Reconnect;
+        log::warn!("Exit relay {} was {}", exit_relay.hostname, change);
+        self.event_listener
+            .notify_exit_relay_change(ExitRelayChangeEvent {
+                hostname: exit_relay.hostname,
+                change,
+                reconnecting,
+            });
+        if reconnecting {
+            log::info!("Reconnecting to select a new exit relay");
+            self.reconnect_tunnel();
+        }
+    }
+
+    async fn handle_check_account_expiry(&mut self) {
+        if self.settings.expiry_notifications.thresholds.is_empty() {
+            return;
+        }
+        let account_token = match self.account_manager.data().await.map(|s| s.into_device()) {
+            Ok(Some(device)) => device.account_token,
+            _ => return,
+        };
+        let account = self.account_manager.account_service.clone();
+        let event_tx = self.tx.clone();
+        tokio::spawn(async move {
+            match account.check_expiry(account_token).await {
+                Ok(expiry) => {
+                    let _ = event_tx.send(InternalDaemonEvent::AccountExpiry(expiry));
+                }
+                Err(error) => {
+                    log::debug!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to check account expiry")
+                    );
+                }
+            }
+        });
+    }
+
+    async fn handle_account_expiry(&mut self, expiry: DateTime<Utc>) {
+        let event = match self
+            .expiry_notifier
+            .check(expiry, &self.settings.expiry_notifications.thresholds)
+            .await
+        {
+            Some(event) => event,
+            None => return,
+        };
+        log::info!("The account expires at {}", expiry);
+        if let Some(script) = &self.settings.expiry_notifications.script {
+            expiry_notifier::run_script(script, &event);
+        }
+        self.event_listener.notify_account_expiry(event);
+    }
+
     async fn handle_device_event(&mut self, event: PrivateDeviceEvent) {
         match &event {
             PrivateDeviceEvent::Login(device) => {
@@ -1138,8 +2250,7 @@ where
         let changed = *save_result.as_ref().unwrap_or(&false);
         let _ = tx.send(save_result.map(|_| ()));
         if changed {
-            self.event_listener
-                .notify_settings(self.settings.to_settings());
+            self.notify_settings();
         }
     }
 
@@ -1149,6 +2260,12 @@ where
         new_target_state: TargetState,
     ) {
         if self.state.is_running() {
+            if self.end_tunnel_pause().await {
+                log::info!("Tunnel pause was cancelled by a target state change");
+            }
+            if self.end_permissive_mode().is_some() {
+                log::warn!("Permissive mode was ended by a target state change");
+            }
             let state_change_initated = self.set_target_state(new_target_state).await;
             Self::oneshot_send(tx, state_change_initated, "state change initiated");
         } else {
@@ -1156,380 +2273,1721 @@ where
         }
     }
 
-    fn on_reconnect(&mut self, tx: oneshot::Sender<bool>) {
-        if *self.target_state == TargetState::Secured || self.tunnel_state.is_in_error_state() {
-            self.connect_tunnel();
-            Self::oneshot_send(tx, true, "reconnect issued");
-        } else {
-            log::debug!("Ignoring reconnect command. Currently not in secured state");
-            Self::oneshot_send(tx, false, "reconnect issued");
+    async fn on_pause_tunnel(&mut self, tx: ResponseTx<bool, settings::Error>, duration: Duration) {
+        if !self.state.is_running() {
+            log::warn!("Ignoring tunnel pause request due to shutdown");
+            return;
+        }
+        // The pause lifts the block while it lasts
+        if self.settings.block_when_disconnected {
+            if let Err(error) = self.settings.ensure_unlocked("block_when_disconnected") {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Refusing to pause the tunnel")
+                );
+                Self::oneshot_send(tx, Err(error), "tunnel pause response");
+                return;
+            }
         }
-    }
 
-    fn on_get_state(&self, tx: oneshot::Sender<TunnelState>) {
-        Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
+        if let Some(pause) = self.tunnel_pause.take() {
+            pause.job.abort();
+        }
+        if let Some(mode) = self.permissive_mode.take() {
+            log::warn!("Permissive mode was ended by a tunnel pause");
+            mode.job.abort();
+        }
+
+        let resume_at = self.schedule_tunnel_pause(duration);
+        self.tunnel_pause_record.save(Some(resume_at)).await;
+
+        log::info!("Pausing the tunnel for {} seconds", duration.as_secs());
+        if self.settings.block_when_disconnected {
+            self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(false));
+        }
+        self.set_target_state(TargetState::Unsecured).await;
+        Self::oneshot_send(tx, Ok(true), "tunnel pause response");
     }
 
-    async fn on_is_performing_post_upgrade(&self, tx: oneshot::Sender<bool>) {
-        let performing_post_upgrade = !self.migration_complete.is_complete();
-        Self::oneshot_send(tx, performing_post_upgrade, "performing post upgrade");
+    /// Starts the timer that ends the tunnel pause once `duration` has elapsed. Returns the time
+    /// at which the tunnel is resumed.
+    fn schedule_tunnel_pause(&mut self, duration: Duration) -> SystemTime {
+        let event_tx = self.tx.clone();
+        let (future, job) = abortable(Box::pin(async move {
+            tokio::time::sleep(duration).await;
+            let _ = event_tx.send(InternalDaemonEvent::TunnelPauseExpired);
+        }));
+        tokio::spawn(future);
+        let resume_at = SystemTime::now() + duration;
+        self.tunnel_pause = Some(TunnelPause { resume_at, job });
+        resume_at
     }
 
-    async fn on_get_current_location(&mut self, tx: oneshot::Sender<Option<GeoIpLocation>>) {
-        use self::TunnelState::*;
+    fn on_get_tunnel_pause(&self, tx: oneshot::Sender<Option<SystemTime>>) {
+        Self::oneshot_send(
+            tx,
+            self.tunnel_pause.as_ref().map(|pause| pause.resume_at),
+            "tunnel pause",
+        );
+    }
 
-        match &self.tunnel_state {
-            Disconnected => {
-                let location = self.get_geo_location().await;
-                tokio::spawn(async {
-                    Self::oneshot_send(tx, location.await.ok(), "current location");
-                });
-            }
-            Connecting { location, .. } => {
-                Self::oneshot_send(tx, location.clone(), "current location")
-            }
-            Disconnecting(..) => Self::oneshot_send(
-                tx,
-                self.parameters_generator.get_last_location().await,
-                "current location",
-            ),
-            Connected { location, .. } => {
-                let relay_location = location.clone();
-                let location_future = self.get_geo_location().await;
-                tokio::spawn(async {
-                    let location = location_future.await;
-                    Self::oneshot_send(
-                        tx,
-                        location.ok().map(|fetched_location| GeoIpLocation {
-                            ipv4: fetched_location.ipv4,
-                            ipv6: fetched_location.ipv6,
-                            ..relay_location.unwrap_or(fetched_location)
-                        }),
-                        "current location",
-                    );
-                });
-            }
-            Error(_) => {
-                // We are not online at all at this stage so no location data is available.
-                Self::oneshot_send(tx, None, "current location");
-            }
+    async fn on_set_permissive_mode(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        duration: Duration,
+    ) {
+        if !self.state.is_running() {
+            log::warn!("Ignoring permissive mode request due to shutdown");
+            return;
+        }
+        if let Err(error) = self.settings.ensure_unlocked("block_when_disconnected") {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Refusing permissive mode")
+            );
+            Self::oneshot_send(tx, Err(error), "set_permissive_mode response");
+            return;
         }
+
+        let target_state = if let Some(mode) = self.permissive_mode.take() {
+            mode.job.abort();
+            mode.target_state
+        } else if let Some(pause) = self.tunnel_pause.take() {
+            // The paused tunnel would have been connected again once the pause ended
+            pause.job.abort();
+            self.tunnel_pause_record.save(None).await;
+            TargetState::Secured
+        } else {
+            *self.target_state
+        };
+
+        let event_tx = self.tx.clone();
+        let (future, job) = abortable(Box::pin(async move {
+            tokio::time::sleep(duration).await;
+            let _ = event_tx.send(InternalDaemonEvent::PermissiveModeExpired);
+        }));
+        tokio::spawn(future);
+        self.permissive_mode = Some(PermissiveMode {
+            ends_at: SystemTime::now() + duration,
+            job,
+            target_state,
+        });
+
+        log::warn!(
+            "Entering permissive mode for {} seconds. The firewall is not enforced",
+            duration.as_secs()
+        );
+        self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(false));
+        self.set_target_state(TargetState::Unsecured).await;
+        Self::oneshot_send(tx, Ok(()), "set_permissive_mode response");
     }
 
-    async fn get_geo_location(&mut self) -> impl Future<Output = Result<GeoIpLocation, ()>> {
-        let rest_service = self.api_runtime.rest_handle().await;
-        async {
-            geoip::send_location_request(rest_service)
-                .await
-                .map_err(|e| {
-                    log::warn!("Unable to fetch GeoIP location: {}", e.display_chain());
-                })
+    async fn on_end_permissive_mode(&mut self, tx: oneshot::Sender<bool>) {
+        let target_state = self.end_permissive_mode();
+        if let Some(target_state) = target_state {
+            log::warn!("Permissive mode was ended. Enforcing the firewall again");
+            self.set_target_state(target_state).await;
         }
+        Self::oneshot_send(tx, target_state.is_some(), "end_permissive_mode response");
     }
 
-    async fn on_create_new_account(&mut self, tx: ResponseTx<String, Error>) {
-        let account_manager = self.account_manager.clone();
-        tokio::spawn(async move {
-            let result = async {
-                if let Ok(data) = account_manager.data().await {
-                    if data.logged_in() {
-                        return Err(Error::AlreadyLoggedIn);
-                    }
-                }
-                let token = account_manager
-                    .account_service
-                    .create_account()
-                    .await
-                    .map_err(Error::RestError)?;
-                account_manager
-                    .login(token.clone())
-                    .await
-                    .map_err(|error| {
-                        log::error!(
-                            "{}",
-                            error.display_chain_with_msg("Creating new account failed")
-                        );
-                        Error::LoginError(error)
-                    })?;
-                Ok(token)
-            };
-            Self::oneshot_send(tx, result.await, "create new account");
-        });
+    fn on_get_permissive_mode(&self, tx: oneshot::Sender<Option<SystemTime>>) {
+        Self::oneshot_send(
+            tx,
+            self.permissive_mode.as_ref().map(|mode| mode.ends_at),
+            "get_permissive_mode response",
+        );
     }
 
-    async fn on_get_account_data(
+    fn on_set_captive_portal(
         &mut self,
-        tx: ResponseTx<AccountData, mullvad_api::rest::Error>,
-        account_token: AccountToken,
+        tx: ResponseTx<(), settings::Error>,
+        portal: CaptivePortal,
+        duration: Duration,
     ) {
-        let account = self.account_manager.account_service.clone();
-        tokio::spawn(async move {
-            let result = account.check_expiry(account_token).await;
-            Self::oneshot_send(
-                tx,
-                result.map(|expiry| AccountData { expiry }),
-                "account data",
+        if !self.state.is_running() {
+            log::warn!("Ignoring captive portal request due to shutdown");
+            return;
+        }
+        if let Err(error) = self.settings.ensure_unlocked("block_when_disconnected") {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Refusing to permit a captive portal")
             );
-        });
-    }
+            Self::oneshot_send(tx, Err(error), "set_captive_portal response");
+            return;
+        }
 
-    async fn on_get_www_auth_token(&mut self, tx: ResponseTx<String, Error>) {
-        if let Ok(Some(device)) = self.account_manager.data().await.map(|s| s.into_device()) {
-            let future = self
-                .account_manager
-                .account_service
-                .get_www_auth_token(device.account_token);
-            tokio::spawn(async {
-                Self::oneshot_send(
-                    tx,
-                    future.await.map_err(Error::RestError),
-                    "get_www_auth_token response",
-                );
-            });
-        } else {
-            Self::oneshot_send(
-                tx,
-                Err(Error::NoAccountToken),
-                "get_www_auth_token response",
-            );
-        }
-    }
-
-    async fn on_submit_voucher(
-        &mut self,
-        tx: ResponseTx<VoucherSubmission, Error>,
-        voucher: String,
-    ) {
-        if let Ok(Some(device)) = self.account_manager.data().await.map(|s| s.into_device()) {
-            let mut account = self.account_manager.account_service.clone();
-            tokio::spawn(async move {
-                Self::oneshot_send(
-                    tx,
-                    account
-                        .submit_voucher(device.account_token, voucher)
-                        .await
-                        .map_err(Error::RestError),
-                    "submit_voucher response",
-                );
-            });
-        } else {
-            Self::oneshot_send(tx, Err(Error::NoAccountToken), "submit_voucher response");
+        if let Some(mode) = self.captive_portal.take() {
+            mode.job.abort();
         }
-    }
-
-    fn on_get_relay_locations(&mut self, tx: oneshot::Sender<RelayList>) {
-        Self::oneshot_send(tx, self.relay_selector.get_locations(), "relay locations");
-    }
-
-    async fn on_update_relay_locations(&mut self) {
-        self.relay_list_updater.update().await;
-    }
 
-    fn on_login_account(&mut self, tx: ResponseTx<(), Error>, account_token: String) {
-        let account_manager = self.account_manager.clone();
-        tokio::spawn(async move {
-            let result = async {
-                account_manager.login(account_token).await.map_err(|error| {
-                    log::error!("{}", error.display_chain_with_msg("Login failed"));
-                    Error::LoginError(error)
-                })
-            };
-            Self::oneshot_send(tx, result.await, "login_account response");
-        });
-    }
-
-    fn on_logout_account(&mut self, tx: ResponseTx<(), Error>) {
-        let account_manager = self.account_manager.clone();
-        tokio::spawn(async move {
-            let result = async {
-                account_manager.logout().await.map_err(|error| {
-                    log::error!("{}", error.display_chain_with_msg("Logout failed"));
-                    Error::LogoutError(error)
-                })
-            };
-            Self::oneshot_send(tx, result.await, "logout_account response");
+        let event_tx = self.tx.clone();
+        let (future, job) = abortable(Box::pin(async move {
+            tokio::time::sleep(duration).await;
+            let _ = event_tx.send(InternalDaemonEvent::CaptivePortalExpired);
+        }));
+        tokio::spawn(future);
+        self.captive_portal = Some(CaptivePortalMode {
+            ends_at: SystemTime::now() + duration,
+            job,
+            portal,
         });
-    }
 
-    async fn on_get_device(&mut self, tx: ResponseTx<DeviceState, Error>) {
-        let account_manager = self.account_manager.clone();
-        tokio::spawn(async move {
-            Self::oneshot_send(
-                tx,
-                account_manager
-                    .data()
-                    .await
-                    .map_err(|_| Error::NoAccountToken)
-                    .map(DeviceState::from),
-                "get_device response",
-            );
-        });
+        log::info!(
+            "Permitting {} for a captive portal for {} seconds",
+            portal,
+            duration.as_secs()
+        );
+        self.send_tunnel_command(TunnelCommand::CaptivePortal(Some(portal)));
+        Self::oneshot_send(tx, Ok(()), "set_captive_portal response");
     }
 
-    async fn on_update_device(&mut self, tx: ResponseTx<(), Error>) {
-        let account_manager = self.account_manager.clone();
-        tokio::spawn(async move {
-            let result = match account_manager.validate_device().await {
-                Ok(_) | Err(device::Error::NoDevice) => Ok(()),
-                Err(error) => Err(error),
-            };
-            Self::oneshot_send(
-                tx,
-                result.map_err(Error::UpdateDeviceError),
-                "update_device response",
-            );
-        });
+    fn on_end_captive_portal(&mut self, tx: oneshot::Sender<bool>) {
+        let was_active = self.end_captive_portal();
+        if was_active {
+            log::info!("Captive portal access was ended. Blocking traffic again");
+        }
+        Self::oneshot_send(tx, was_active, "end_captive_portal response");
     }
 
-    async fn on_list_devices(&self, tx: ResponseTx<Vec<Device>, Error>, token: AccountToken) {
-        let service = self.account_manager.device_service.clone();
-        tokio::spawn(async move {
-            Self::oneshot_send(
-                tx,
-                service
-                    .list_devices(token)
-                    .await
-                    .map_err(Error::ListDevicesError),
-                "list_devices response",
-            );
-        });
+    fn on_get_captive_portal(&self, tx: oneshot::Sender<Option<(CaptivePortal, SystemTime)>>) {
+        Self::oneshot_send(
+            tx,
+            self.captive_portal
+                .as_ref()
+                .map(|mode| (mode.portal, mode.ends_at)),
+            "get_captive_portal response",
+        );
     }
 
-    async fn on_remove_device(
+    async fn on_set_setting_override(
         &mut self,
         tx: ResponseTx<(), Error>,
-        account_token: AccountToken,
-        device_id: DeviceId,
+        value: overrides::SettingValue,
+        duration: Duration,
+        revert_on_reconnect: bool,
     ) {
-        let device_service = self.account_manager.device_service.clone();
-        let event_listener = self.event_listener.clone();
+        // Extending an override must not make the overridden value the one to restore
+        let restore = match self.setting_overrides.get(value.path()) {
+            Some(existing) => existing.restore.clone(),
+            None => match value.current(&self.settings) {
+                Ok(current) => current,
+                Err(error) => {
+                    Self::oneshot_send(
+                        tx,
+                        Err(Error::SettingOverrideError(error)),
+                        "set_setting_override response",
+                    );
+                    return;
+                }
+            },
+        };
 
-        tokio::spawn(async move {
-            let result = device_service
-                .remove_device(account_token.clone(), device_id)
-                .await
-                .map(move |new_devices| {
-                    // FIXME: We should be able to get away with only returning the removed ID,
-                    //        and not have to request the list from the API.
-                    event_listener.notify_remove_device_event(RemoveDeviceEvent {
-                        account_token,
-                        new_devices,
-                    });
-                });
+        if let Err(error) = self.apply_setting_value(value.clone()).await {
             Self::oneshot_send(
                 tx,
-                result.map_err(Error::RemoveDeviceError),
-                "remove_device response",
+                Err(Error::SettingsError(error)),
+                "set_setting_override response",
             );
-        });
-    }
+            return;
+        }
 
-    fn on_get_account_history(&mut self, tx: oneshot::Sender<Option<AccountToken>>) {
-        Self::oneshot_send(
-            tx,
-            self.account_history.get(),
-            "get_account_history response",
-        );
-    }
+        let path = value.path();
+        let event_tx = self.tx.clone();
+        let (future, job) = abortable(Box::pin(async move {
+            tokio::time::sleep(duration).await;
+            let _ = event_tx.send(InternalDaemonEvent::SettingOverrideExpired(path));
+        }));
+        tokio::spawn(future);
 
-    async fn on_clear_account_history(&mut self, tx: ResponseTx<(), Error>) {
-        let result = self
-            .account_history
-            .clear()
-            .await
-            .map_err(Error::AccountHistory);
-        Self::oneshot_send(tx, result, "clear_account_history response");
+        log::info!(
+            "Overriding {} with \"{}\" for {} seconds",
+            path,
+            value,
+            duration.as_secs()
+        );
+        self.setting_overrides
+            .insert(
+                overrides::SettingOverride {
+                    value,
+                    restore,
+                    revert_at: SystemTime::now() + duration,
+                    revert_on_reconnect,
+                },
+                job,
+            )
+            .await;
+        Self::oneshot_send(tx, Ok(()), "set_setting_override response");
     }
 
-    async fn on_get_version_info(&mut self, tx: oneshot::Sender<Option<AppVersionInfo>>) {
-        if self.app_version_info.is_none() {
-            log::debug!("No version cache found. Fetching new info");
-            let mut handle = self.version_updater_handle.clone();
-            tokio::spawn(async move {
-                Self::oneshot_send(
-                    tx,
-                    handle
-                        .run_version_check()
-                        .await
-                        .map_err(|error| {
-                            log::error!(
-                                "{}",
-                                error.display_chain_with_msg("Error running version check")
-                            )
-                        })
-                        .ok(),
-                    "get_version_info response",
-                );
-            });
-        } else {
-            Self::oneshot_send(
+    async fn on_clear_setting_override(&mut self, tx: ResponseTx<(), Error>, path: String) {
+        match self.setting_overrides.remove(&path).await {
+            Some(setting_override) => {
+                log::info!("Ending the override of {} early", path);
+                self.end_setting_override(setting_override).await;
+                Self::oneshot_send(tx, Ok(()), "clear_setting_override response");
+            }
+            None => Self::oneshot_send(
                 tx,
-                self.app_version_info.clone(),
-                "get_version_info response",
-            );
+                Err(Error::SettingOverrideError(
+                    overrides::Error::NotOverridden(path),
+                )),
+                "clear_setting_override response",
+            ),
         }
     }
 
-    fn on_get_current_version(&mut self, tx: oneshot::Sender<AppVersion>) {
-        Self::oneshot_send(
-            tx,
-            version::PRODUCT_VERSION.to_owned(),
-            "get_current_version response",
-        );
+    fn on_get_setting_overrides(&self, tx: oneshot::Sender<Vec<overrides::SettingOverride>>) {
+        Self::oneshot_send(tx, self.setting_overrides.list(), "setting overrides");
     }
 
-    #[cfg(not(target_os = "android"))]
-    async fn on_factory_reset(&mut self, tx: ResponseTx<(), Error>) {
-        let mut last_error = Ok(());
+    async fn handle_setting_override_expired(&mut self, path: &'static str) {
+        // The override may have been replaced after this event was sent
+        let expired = self
+            .setting_overrides
+            .get(path)
+            .map(|setting_override| setting_override.revert_at <= SystemTime::now())
+            .unwrap_or(false);
+        if !expired {
+            return;
+        }
+        if let Some(setting_override) = self.setting_overrides.remove(path).await {
+            log::info!("The override of {} has expired", path);
+            self.end_setting_override(setting_override).await;
+        }
+    }
 
-        if let Err(error) = self.account_manager.logout().await {
-            log::error!(
-                "{}",
-                error.display_chain_with_msg("Failed to clear device cache")
-            );
+    /// Restores the value that a setting had before it was overridden, unless it has been changed
+    /// by other means in the meantime.
+    async fn end_setting_override(&mut self, setting_override: overrides::SettingOverride) {
+        match setting_override.value.current(&self.settings) {
+            Ok(current) if current == setting_override.value => (),
+            _ => {
+                log::info!(
+                    "Keeping the current value of {} since it was changed during the override",
+                    setting_override.value.path()
+                );
+                return;
+            }
         }
 
-        if let Err(error) = self.account_history.clear().await {
+        log::info!(
+            "Restoring {} to \"{}\"",
+            setting_override.restore.path(),
+            setting_override.restore
+        );
+        if let Err(error) = self.apply_setting_value(setting_override.restore).await {
             log::error!(
                 "{}",
-                error.display_chain_with_msg("Failed to clear account history")
+                error.display_chain_with_msg("Failed to restore overridden setting")
             );
-            last_error = Err(Error::FactoryResetError("Failed to clear account history"));
-        }
-
-        if let Err(e) = self.settings.reset().await {
-            log::error!("Failed to reset settings: {}", e);
-            last_error = Err(Error::FactoryResetError("Failed to reset settings"));
         }
+    }
 
-        // Shut the daemon down.
-        self.trigger_shutdown_event();
+    /// Changes a setting in the same way as the command that normally sets it.
+    async fn apply_setting_value(
+        &mut self,
+        value: overrides::SettingValue,
+    ) -> Result<(), settings::Error> {
+        use overrides::SettingValue;
 
-        self.shutdown_tasks.push(Box::pin(async move {
-            if let Err(e) = cleanup::clear_directories().await {
-                log::error!(
-                    "{}",
-                    e.display_chain_with_msg("Failed to clear cache and log directories")
-                );
-                last_error = Err(Error::FactoryResetError(
-                    "Failed to clear cache and log directories",
-                ));
+        let (tx, rx) = oneshot::channel();
+        match value {
+            SettingValue::BlockWhenDisconnected(block_when_disconnected) => {
+                self.on_set_block_when_disconnected(tx, block_when_disconnected)
+                    .await
             }
-            Self::oneshot_send(tx, last_error, "factory_reset response");
-        }));
+            SettingValue::AllowLan(allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
+            SettingValue::AllowMulticast(allow_multicast) => {
+                self.on_set_allow_multicast(tx, allow_multicast).await
+            }
+            SettingValue::EnableIpv6(enable_ipv6) => self.on_set_enable_ipv6(tx, enable_ipv6).await,
+            SettingValue::Location(location) => {
+                self.on_update_relay_settings(tx, overrides::location_update(location))
+                    .await
+            }
+        }
+        rx.await.unwrap_or(Ok(()))
     }
 
-    #[cfg(target_os = "linux")]
-    fn on_get_split_tunnel_processes(&mut self, tx: ResponseTx<Vec<i32>, split_tunnel::Error>) {
-        let result = self.exclude_pids.list().map_err(|error| {
-            log::error!("{}", error.display_chain_with_msg("Unable to obtain PIDs"));
-            error
-        });
-        Self::oneshot_send(tx, result, "get_split_tunnel_processes response");
+    #[cfg(not(target_os = "android"))]
+    fn handle_dns_servers_reordered(&mut self, servers: Vec<IpAddr>) {
+        // Ignore orders of servers that have been replaced since they were probed
+        let mut configured = dns::addresses_from_options(&self.settings.tunnel_options.dns_options)
+            .unwrap_or_default();
+        let mut reordered = servers.clone();
+        configured.sort();
+        reordered.sort();
+        if self.tunnel_state.is_connected() && configured == reordered {
+            self.send_tunnel_command(TunnelCommand::Dns(Some(servers)));
+        }
     }
 
-    #[cfg(target_os = "linux")]
-    fn on_add_split_tunnel_process(&mut self, tx: ResponseTx<(), split_tunnel::Error>, pid: i32) {
+    #[cfg(not(target_os = "android"))]
+    fn handle_nat64_prefix_detected(&mut self, prefix: Option<Ipv6Addr>) {
+        if self.nat64_prefix == prefix {
+            return;
+        }
+        match prefix {
+            Some(prefix) => log::info!("The network uses NAT64 with the prefix {}", prefix),
+            None => log::debug!("The network does not use NAT64"),
+        }
+        self.nat64_prefix = prefix;
+        self.check_nat64();
+    }
+
+    /// Warns if the custom DNS servers cannot be reached on an IPv6-only network.
+    #[cfg(not(target_os = "android"))]
+    fn check_nat64(&self) {
+        if let Some(prefix) = self.nat64_prefix {
+            if let Err(error) = dns::check_nat64(&self.settings.tunnel_options.dns_options, prefix)
+            {
+                log::warn!("{}", error.display_chain());
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn handle_interference_report(&mut self, report: InterferenceReport) {
+        if !self.settings.circumvention_assistant {
+            return;
+        }
+        for change in circumvention::plan(&report, &self.settings) {
+            log::info!(
+                "Circumvention assistant: {}, since {}",
+                change.setting,
+                change.reason.to_lowercase()
+            );
+            if let Err(error) = self.apply_changed_setting(&change.setting, false).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to apply circumvention setting")
+                );
+                continue;
+            }
+            self.circumvention_changes.insert(change).await;
+        }
+    }
+
+    /// Sets a setting changed by the circumvention assistant to the value that it applied, or
+    /// back to its previous value if `revert` is set.
+    #[cfg(not(target_os = "android"))]
+    async fn apply_changed_setting(
+        &mut self,
+        setting: &circumvention::ChangedSetting,
+        revert: bool,
+    ) -> Result<(), Error> {
+        use circumvention::ChangedSetting;
+
+        match setting {
+            ChangedSetting::Obfuscation { previous, applied } => {
+                let value = if revert { previous } else { applied };
+                let (tx, rx) = oneshot::channel();
+                self.on_set_obfuscation_settings(tx, value.clone()).await;
+                rx.await.unwrap_or(Ok(()))
+            }
+            ChangedSetting::ApiAccessMethods { previous, applied } => {
+                let value = if revert { previous } else { applied };
+                let (tx, rx) = oneshot::channel();
+                self.on_set_api_access_methods(tx, value.clone()).await;
+                rx.await.unwrap_or(Ok(())).map_err(Error::SettingsError)
+            }
+        }
+    }
+
+    async fn handle_settings_file_changed(&mut self) {
+        let new_settings = match self.settings.read_external_changes().await {
+            Ok(Some(settings)) => settings,
+            Ok(None) => return,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Ignoring invalid changes to the settings file")
+                );
+                return;
+            }
+        };
+        log::info!("Applying changes that another program made to the settings file");
+        self.settings
+            .set_change_source(settings_history::ChangeSource::SettingsFile);
+        if let Err(error) = self.apply_settings(new_settings).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to apply changes to the settings file")
+            );
+        }
+        self.settings
+            .set_change_source(settings_history::ChangeSource::Client);
+    }
+
+    /// Changes every setting that differs from `new_settings` in the same way as the command that
+    /// normally sets it, so that the new values reach the components that use them. Each command
+    /// logs its own errors. If any of them fails, the settings are changed back to what they were,
+    /// so that the new settings are applied either in full or not at all, and the first error is
+    /// returned. Clients are told about the new settings once all of them are applied.
+    async fn apply_settings(&mut self, new_settings: Settings) -> Result<(), Error> {
+        let previous_settings = self.settings.to_settings();
+        self.settings_batch = Some(false);
+        let mut errors = self.apply_each_setting(new_settings).await;
+        if !errors.is_empty() {
+            log::warn!("Restoring the previous settings, since not every setting could be changed");
+            self.apply_each_setting(previous_settings).await;
+        }
+        if self.settings_batch.take() == Some(true) {
+            self.notify_settings();
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.swap_remove(0))
+        }
+    }
+
+    async fn apply_each_setting(&mut self, new_settings: Settings) -> Vec<Error> {
+        let mut errors = vec![];
+        // The relay settings may refer to lists that are new
+        let (tx, rx) = oneshot::channel();
+        self.on_set_custom_lists(tx, new_settings.custom_lists.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_update_relay_settings(tx, new_settings.get_relay_settings().into())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_bridge_settings(tx, new_settings.bridge_settings.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_obfuscation_settings(tx, new_settings.obfuscation_settings.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_bridge_state(tx, new_settings.get_bridge_state())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_allow_lan(tx, new_settings.allow_lan).await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_allowed_lan_nets(tx, new_settings.allowed_lan_nets.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_firewall_exceptions(tx, new_settings.firewall_exceptions.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_inbound_tunnel_ports(tx, new_settings.inbound_tunnel_ports.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_allow_multicast(tx, new_settings.allow_multicast)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_block_when_disconnected(tx, new_settings.block_when_disconnected)
+            .await;
+        errors.extend(reply_error(rx));
+        #[cfg(not(target_os = "android"))]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_set_strict_enforcement(tx, new_settings.strict_enforcement)
+                .await;
+            errors.extend(reply_error(rx));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.on_set_auto_connect(tx, new_settings.auto_connect)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_auto_connect_scope(tx, new_settings.auto_connect_scope)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_exit_relay_change_policy(tx, new_settings.exit_relay_change_policy)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_relay_selection_strategy(tx, new_settings.relay_selection_strategy)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_relay_failure_cooldown(tx, new_settings.relay_failure_cooldown)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_expiry_notifications(tx, new_settings.expiry_notifications.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        #[cfg(not(target_os = "android"))]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_set_tunnel_hooks(tx, new_settings.tunnel_hooks.clone())
+                .await;
+            errors.extend(reply_error(rx));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.on_set_disconnect_warning_threshold(tx, new_settings.disconnect_warning_threshold)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_reconnect_debounce(tx, new_settings.reconnect_debounce)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_offline_detection(tx, new_settings.offline_detection)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_relay_list_update_interval(tx, new_settings.relay_list_update_interval)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_relay_list_mirror(tx, new_settings.relay_list_mirror.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_relay_overrides(tx, new_settings.relay_overrides.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_api_access_methods(tx, new_settings.api_access_methods.clone())
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_circumvention_assistant(tx, new_settings.circumvention_assistant)
+            .await;
+        errors.extend(reply_error(rx));
+        #[cfg(not(target_os = "android"))]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_set_avoid_bad_relays(tx, new_settings.avoid_bad_relays)
+                .await;
+            errors.extend(reply_error(rx));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.on_set_show_beta_releases(tx, new_settings.show_beta_releases)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_app_exclusions(tx, new_settings.app_exclusions.clone())
+            .await;
+        errors.extend(reply_error(rx));
+
+        let tunnel_options = &new_settings.tunnel_options;
+        let (tx, rx) = oneshot::channel();
+        self.on_set_openvpn_mssfix(tx, tunnel_options.openvpn.mssfix)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_wireguard_mtu(tx, tunnel_options.wireguard.options.mtu)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_wireguard_liveness(tx, tunnel_options.wireguard.options.liveness)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_wireguard_persistent_keepalive(
+            tx,
+            tunnel_options.wireguard.options.persistent_keepalive,
+        )
+        .await;
+        errors.extend(reply_error(rx));
+        #[cfg(not(target_os = "android"))]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_set_wireguard_allowed_ips(
+                tx,
+                tunnel_options.wireguard.options.allowed_ips.clone(),
+            )
+            .await;
+            errors.extend(reply_error(rx));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.on_set_quantum_resistant_tunnel(tx, tunnel_options.wireguard.options.use_pq_safe_psk)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_wireguard_rotation_interval(tx, tunnel_options.wireguard.rotation_interval)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_enable_ipv6(tx, tunnel_options.generic.enable_ipv6)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_retry_policy(tx, tunnel_options.generic.retry_policy)
+            .await;
+        errors.extend(reply_error(rx));
+        let (tx, rx) = oneshot::channel();
+        self.on_set_dns_options(tx, tunnel_options.dns_options.clone())
+            .await;
+        errors.extend(reply_error(rx));
+
+        #[cfg(windows)]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_use_wireguard_nt(tx, tunnel_options.wireguard.options.use_wireguard_nt)
+                .await;
+            errors.extend(reply_error(rx));
+            let (tx, rx) = oneshot::channel();
+            self.on_set_link_layer_exemptions(tx, new_settings.link_layer_exemptions)
+                .await;
+            errors.extend(reply_error(rx));
+            self.apply_split_tunnel_settings(new_settings.split_tunnel);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let (tx, rx) = oneshot::channel();
+            self.on_set_dns_backend(tx, new_settings.dns_backend).await;
+            errors.extend(reply_error(rx));
+            let (tx, rx) = oneshot::channel();
+            self.on_set_policy_routing(tx, new_settings.policy_routing)
+                .await;
+            errors.extend(reply_error(rx));
+            let (tx, rx) = oneshot::channel();
+            self.on_set_wireguard_namespace(tx, tunnel_options.wireguard.options.namespace.clone())
+                .await;
+            errors.extend(reply_error(rx));
+            let (tx, rx) = oneshot::channel();
+            self.on_set_rate_limit(tx, tunnel_options.generic.rate_limit)
+                .await;
+            errors.extend(reply_error(rx));
+        }
+
+        errors
+    }
+
+    /// Split tunnel changes only take effect once the tunnel has applied them, so they are sent
+    /// one after another through the command channel instead.
+    #[cfg(windows)]
+    fn apply_split_tunnel_settings(
+        &self,
+        split_tunnel: mullvad_types::settings::SplitTunnelSettings,
+    ) {
+        let current = self.settings.split_tunnel.clone();
+        let daemon_tx = self.tx.clone();
+        tokio::spawn(async move {
+            if split_tunnel.apps != current.apps {
+                Self::send_split_tunnel_command(&daemon_tx, DaemonCommand::ClearSplitTunnelApps)
+                    .await;
+                for path in split_tunnel.apps {
+                    Self::send_split_tunnel_command(&daemon_tx, |tx| {
+                        DaemonCommand::AddSplitTunnelApp(tx, path)
+                    })
+                    .await;
+                }
+            }
+            Self::send_split_tunnel_command(&daemon_tx, |tx| {
+                DaemonCommand::SetSplitTunnelState(tx, split_tunnel.enable_exclusions)
+            })
+            .await;
+        });
+    }
+
+    #[cfg(windows)]
+    async fn send_split_tunnel_command(
+        daemon_tx: &DaemonEventSender,
+        command: impl FnOnce(ResponseTx<(), Error>) -> DaemonCommand,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        if daemon_tx
+            .send(InternalDaemonEvent::Command(command(tx)))
+            .is_ok()
+        {
+            if let Ok(Err(error)) = rx.await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to apply split tunnel settings")
+                );
+            }
+        }
+    }
+
+    async fn on_reconnect(&mut self, tx: oneshot::Sender<bool>) {
+        for setting_override in self.setting_overrides.remove_revert_on_reconnect().await {
+            log::info!(
+                "Ending the override of {} due to a reconnect",
+                setting_override.value.path()
+            );
+            self.end_setting_override(setting_override).await;
+        }
+
+        if *self.target_state == TargetState::Secured || self.tunnel_state.is_in_error_state() {
+            self.connect_tunnel();
+            Self::oneshot_send(tx, true, "reconnect issued");
+        } else {
+            log::debug!("Ignoring reconnect command. Currently not in secured state");
+            Self::oneshot_send(tx, false, "reconnect issued");
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_check_disconnect_warning(&mut self, tx: ResponseTx<(), Error>, action: DisconnectAction) {
+        let throughput = self.transfer_monitor.throughput();
+        let result = match (self.settings.disconnect_warning_threshold, throughput) {
+            (Some(threshold), Some(throughput)) if throughput > threshold => {
+                log::info!(
+                    "Holding back {} until it is confirmed, {} bytes per second pass through the                      tunnel",
+                    action,
+                    throughput
+                );
+                self.pending_disconnect = Some((action, Instant::now()));
+                self.event_listener
+                    .notify_disconnect_warning(DisconnectWarningEvent { action, throughput });
+                Err(Error::ActiveTransfer(throughput))
+            }
+            _ => {
+                self.pending_disconnect = None;
+                Ok(())
+            }
+        };
+        Self::oneshot_send(tx, result, "disconnect warning response");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_confirm_disconnect(&mut self, tx: ResponseTx<bool, Error>) {
+        let action = match self.pending_disconnect.take() {
+            Some((action, requested)) if requested.elapsed() < DISCONNECT_CONFIRMATION_TIMEOUT => {
+                action
+            }
+            _ => {
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::NoPendingDisconnect),
+                    "disconnect confirmation response",
+                );
+                return;
+            }
+        };
+        log::info!("The user confirmed the {}", action);
+
+        let (issued_tx, issued_rx) = oneshot::channel();
+        match action {
+            DisconnectAction::Disconnect => {
+                self.on_set_target_state(issued_tx, TargetState::Unsecured)
+                    .await
+            }
+            DisconnectAction::Reconnect => self.on_reconnect(issued_tx).await,
+        }
+        let issued = issued_rx.await.unwrap_or(false);
+        Self::oneshot_send(tx, Ok(issued), "disconnect confirmation response");
+    }
+
+    fn on_get_state(&self, tx: oneshot::Sender<TunnelState>) {
+        Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
+    }
+
+    fn on_get_leftover_state(&self, tx: oneshot::Sender<Option<exit_state::AppliedState>>) {
+        Self::oneshot_send(tx, self.exit_state.leftover(), "leftover state");
+    }
+
+    fn on_get_offline_status(&self, tx: oneshot::Sender<OfflineStatus>) {
+        Self::oneshot_send(
+            tx,
+            self.tunnel_state_machine_handle.offline_status(),
+            "offline status",
+        );
+    }
+
+    fn on_get_api_stats(&self, tx: oneshot::Sender<BTreeMap<String, EndpointStats>>) {
+        Self::oneshot_send(tx, self.api_runtime.stats().snapshot(), "API stats");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_check_network_interference(&self, tx: oneshot::Sender<InterferenceReport>) {
+        let checks = self
+            .connection_failures
+            .run_checks(&self.tunnel_state, self.api_runtime.address_cache.clone());
+        tokio::spawn(async move {
+            Self::oneshot_send(tx, checks.await, "network interference report");
+        });
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_last_connection_failure(&self, tx: oneshot::Sender<Option<FailureCapsule>>) {
+        Self::oneshot_send(
+            tx,
+            self.failure_recorder.last_failure(),
+            "last connection failure",
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_snapshot(&mut self, tx: oneshot::Sender<snapshot::Snapshot>) {
+        let snapshot = snapshot::Snapshot::new(
+            self.settings.to_settings(),
+            self.relay_selector.get_locations(),
+            *self.target_state,
+            &self.tunnel_state,
+        );
+        Self::oneshot_send(tx, snapshot, "snapshot");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_check_leaks(&mut self, tx: oneshot::Sender<LeakReport>) {
+        let rest_service = self.api_runtime.rest_handle().await;
+        let checks = leak_check::run_checks(&self.tunnel_state, &self.settings, rest_service);
+        tokio::spawn(async move {
+            Self::oneshot_send(tx, checks.await, "leak check report");
+        });
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_probe_protocols(&mut self, tx: ResponseTx<ProbeReport, Error>) {
+        // The firewall would block the probes in any other state.
+        if !matches!(self.tunnel_state, TunnelState::Disconnected)
+            || self.settings.block_when_disconnected
+        {
+            Self::oneshot_send(
+                tx,
+                Err(Error::ProbeRequiresDisconnected),
+                "protocol probe report",
+            );
+            return;
+        }
+
+        let private_key = self
+            .account_manager
+            .data()
+            .await
+            .ok()
+            .and_then(|data| data.into_device())
+            .map(|device| device.device.wg_data.private_key);
+        let location = self.get_geo_location().await;
+        let relay_selector = self.relay_selector.clone();
+        tokio::spawn(async move {
+            let location =
+                location
+                    .await
+                    .ok()
+                    .map(|location| mullvad_types::location::Coordinates {
+                        latitude: location.latitude,
+                        longitude: location.longitude,
+                    });
+            let report =
+                protocol_probe::probe_protocols(&relay_selector, location, private_key).await;
+            Self::oneshot_send(tx, Ok(report), "protocol probe report");
+        });
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_relay_stats(&self, tx: oneshot::Sender<Vec<RelayStats>>) {
+        Self::oneshot_send(tx, self.relay_stats.list(), "relay stats");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_data_usage(&self, tx: oneshot::Sender<DataUsage>) {
+        Self::oneshot_send(tx, self.transfer_monitor.data_usage(), "data usage");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_dns_query_log(&self, tx: oneshot::Sender<Vec<DnsQueryLogEntry>>, sequence: u64) {
+        Self::oneshot_send(
+            tx,
+            self.dns_query_log.entries_after(sequence),
+            "DNS query log",
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_clear_relay_stats(&mut self, tx: oneshot::Sender<()>) {
+        self.relay_stats.clear().await;
+        self.update_selector_relay_stats();
+        Self::oneshot_send(tx, (), "clear relay stats response");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_avoid_bad_relays(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        let save_result = self.settings.set_avoid_bad_relays(enabled).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set avoid bad relays response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.update_selector_relay_stats();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set avoid bad relays response");
+            }
+        }
+    }
+
+    /// Tells the relay selector which relays to avoid, and how fast connecting to each relay is.
+    /// The current connection is left alone, since it is working if it was made to a bad relay.
+    #[cfg(not(target_os = "android"))]
+    fn update_selector_relay_stats(&self) {
+        let avoided_relays = if self.settings.avoid_bad_relays {
+            self.relay_stats.bad_relays()
+        } else {
+            Default::default()
+        };
+        self.relay_selector.set_avoided_relays(avoided_relays);
+        self.relay_selector
+            .set_relay_latencies(self.relay_stats.latencies());
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_circumvention_changes(
+        &self,
+        tx: oneshot::Sender<Vec<circumvention::CircumventionChange>>,
+    ) {
+        Self::oneshot_send(
+            tx,
+            self.circumvention_changes.list(),
+            "circumvention changes",
+        );
+    }
+
+    /// Restores the settings changed by the circumvention assistant, except for those that have
+    /// been changed by other means since.
+    #[cfg(not(target_os = "android"))]
+    async fn on_revert_circumvention_changes(
+        &mut self,
+        tx: oneshot::Sender<Vec<circumvention::CircumventionChange>>,
+    ) {
+        let changes = self.circumvention_changes.take().await;
+        for change in &changes {
+            if !change.setting.is_current(&self.settings) {
+                log::info!(
+                    "Keeping the current value of {} since it was changed after the \
+                     circumvention assistant changed it",
+                    change.setting.name()
+                );
+                continue;
+            }
+            log::info!("Reverting {}", change.setting.name());
+            if let Err(error) = self.apply_changed_setting(&change.setting, true).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to revert circumvention setting")
+                );
+            }
+        }
+        Self::oneshot_send(tx, changes, "reverted circumvention changes");
+    }
+
+    async fn on_is_performing_post_upgrade(&self, tx: oneshot::Sender<bool>) {
+        let performing_post_upgrade = !self.migration_complete.is_complete();
+        Self::oneshot_send(tx, performing_post_upgrade, "performing post upgrade");
+    }
+
+    async fn on_get_current_location(&mut self, tx: oneshot::Sender<Option<GeoIpLocation>>) {
+        use self::TunnelState::*;
+
+        match &self.tunnel_state {
+            Disconnected => {
+                let location = self.get_geo_location().await;
+                tokio::spawn(async {
+                    Self::oneshot_send(tx, location.await.ok(), "current location");
+                });
+            }
+            Connecting { location, .. } => {
+                Self::oneshot_send(tx, location.clone(), "current location")
+            }
+            Disconnecting(..) => Self::oneshot_send(
+                tx,
+                self.parameters_generator.get_last_location().await,
+                "current location",
+            ),
+            Connected { location, .. } => {
+                let relay_location = location.clone();
+                let location_future = self.get_geo_location().await;
+                tokio::spawn(async {
+                    let location = location_future.await;
+                    Self::oneshot_send(
+                        tx,
+                        location.ok().map(|fetched_location| GeoIpLocation {
+                            ipv4: fetched_location.ipv4,
+                            ipv6: fetched_location.ipv6,
+                            ..relay_location.unwrap_or(fetched_location)
+                        }),
+                        "current location",
+                    );
+                });
+            }
+            Error(_) => {
+                // We are not online at all at this stage so no location data is available.
+                Self::oneshot_send(tx, None, "current location");
+            }
+        }
+    }
+
+    async fn get_geo_location(&mut self) -> impl Future<Output = Result<GeoIpLocation, ()>> {
+        let rest_service = self.api_runtime.rest_handle().await;
+        async {
+            geoip::send_location_request(rest_service)
+                .await
+                .map_err(|e| {
+                    log::warn!("Unable to fetch GeoIP location: {}", e.display_chain());
+                })
+        }
+    }
+
+    async fn on_create_new_account(&mut self, tx: ResponseTx<String, Error>) {
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            let result = async {
+                if let Ok(data) = account_manager.data().await {
+                    if data.logged_in() {
+                        return Err(Error::AlreadyLoggedIn);
+                    }
+                }
+                let token = account_manager
+                    .account_service
+                    .create_account()
+                    .await
+                    .map_err(Error::RestError)?;
+                account_manager
+                    .login(token.clone())
+                    .await
+                    .map_err(|error| {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Creating new account failed")
+                        );
+                        Error::LoginError(error)
+                    })?;
+                Ok(token)
+            };
+            Self::oneshot_send(tx, result.await, "create new account");
+        });
+    }
+
+    async fn on_get_account_data(
+        &mut self,
+        tx: ResponseTx<AccountData, mullvad_api::rest::Error>,
+        account_token: AccountToken,
+    ) {
+        let account = self.account_manager.account_service.clone();
+        tokio::spawn(async move {
+            let result = account.check_expiry(account_token).await;
+            Self::oneshot_send(
+                tx,
+                result.map(|expiry| AccountData { expiry }),
+                "account data",
+            );
+        });
+    }
+
+    async fn on_get_www_auth_token(&mut self, tx: ResponseTx<String, Error>) {
+        if let Ok(Some(device)) = self.account_manager.data().await.map(|s| s.into_device()) {
+            let future = self
+                .account_manager
+                .account_service
+                .get_www_auth_token(device.account_token);
+            tokio::spawn(async {
+                Self::oneshot_send(
+                    tx,
+                    future.await.map_err(Error::RestError),
+                    "get_www_auth_token response",
+                );
+            });
+        } else {
+            Self::oneshot_send(
+                tx,
+                Err(Error::NoAccountToken),
+                "get_www_auth_token response",
+            );
+        }
+    }
+
+    async fn on_submit_voucher(
+        &mut self,
+        tx: ResponseTx<VoucherSubmission, Error>,
+        voucher: String,
+    ) {
+        if let Ok(Some(device)) = self.account_manager.data().await.map(|s| s.into_device()) {
+            let mut account = self.account_manager.account_service.clone();
+            tokio::spawn(async move {
+                Self::oneshot_send(
+                    tx,
+                    account
+                        .submit_voucher(device.account_token, voucher)
+                        .await
+                        .map_err(Error::RestError),
+                    "submit_voucher response",
+                );
+            });
+        } else {
+            Self::oneshot_send(tx, Err(Error::NoAccountToken), "submit_voucher response");
+        }
+    }
+
+    fn on_get_relay_locations(&mut self, tx: oneshot::Sender<RelayList>) {
+        Self::oneshot_send(tx, self.relay_selector.get_locations(), "relay locations");
+    }
+
+    async fn on_update_relay_locations(&mut self) {
+        self.relay_list_updater.update().await;
+    }
+
+    fn on_update_relay_locations_and_wait(&mut self, tx: ResponseTx<(), Error>) {
+        let mut relay_list_updater = self.relay_list_updater.clone();
+        tokio::spawn(async move {
+            let result = relay_list_updater
+                .update_and_wait()
+                .await
+                .map_err(Error::RelayListUpdateError);
+            Self::oneshot_send(tx, result, "update_relay_locations_and_wait response");
+        });
+    }
+
+    fn on_get_relay_list_info(&self, tx: oneshot::Sender<RelayListInfo>) {
+        Self::oneshot_send(
+            tx,
+            self.relay_selector.get_relay_list_info(),
+            "relay list info",
+        );
+    }
+
+    async fn on_set_relay_list_update_interval(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        interval: RelayListUpdateInterval,
+    ) {
+        let save_result = self.settings.set_relay_list_update_interval(interval).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set relay list update interval response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.relay_list_updater
+                        .set_update_interval(interval.as_duration())
+                        .await;
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set relay list update interval response");
+            }
+        }
+    }
+
+    async fn on_set_relay_list_mirror(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        mirror: Option<RelayListMirror>,
+    ) {
+        let save_result = self.settings.set_relay_list_mirror(mirror.clone()).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set relay list mirror response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.relay_list_updater.set_mirror(mirror).await;
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set relay list mirror response");
+            }
+        }
+    }
+
+    async fn on_set_relay_overrides(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        overrides: Vec<RelayOverride>,
+    ) {
+        let save_result = self.settings.set_relay_overrides(overrides.clone()).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set relay overrides response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.relay_selector.set_relay_overrides(overrides);
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set relay overrides response");
+            }
+        }
+    }
+
+    async fn on_set_api_access_methods(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        access_methods: ApiAccessMethodSettings,
+    ) {
+        let save_result = self
+            .settings
+            .set_api_access_methods(access_methods.clone())
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set API access methods response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.api_access_methods.set(access_methods);
+                    // The current method may have been disabled or removed
+                    if let Err(error) = self.api_handle.service().next_api_endpoint().await {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to rotate API endpoint")
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set API access methods response");
+            }
+        }
+    }
+
+    async fn on_set_custom_lists(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        custom_lists: CustomListsSettings,
+    ) {
+        let save_result = self.settings.set_custom_lists(custom_lists).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set custom lists response");
+                if settings_changed {
+                    self.custom_lists_changed(None);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set custom lists response");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_create_custom_list(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        list: CustomList,
+    ) {
+        let name = list.name.clone();
+        let save_result = self.settings.create_custom_list(list).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "create custom list response");
+                if settings_changed {
+                    self.custom_lists_changed(Some(&name));
+                }
+            }
+            Err(e) => {
+                log::error!(
+                    "{}",
+                    e.display_chain_with_msg("Unable to create custom list")
+                );
+                Self::oneshot_send(tx, Err(e), "create custom list response");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_update_custom_list(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        list: CustomList,
+    ) {
+        let name = list.name.clone();
+        let save_result = self.settings.update_custom_list(list).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "update custom list response");
+                if settings_changed {
+                    self.custom_lists_changed(Some(&name));
+                }
+            }
+            Err(e) => {
+                log::error!(
+                    "{}",
+                    e.display_chain_with_msg("Unable to update custom list")
+                );
+                Self::oneshot_send(tx, Err(e), "update custom list response");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_delete_custom_list(&mut self, tx: ResponseTx<(), settings::Error>, name: String) {
+        let save_result = self.settings.delete_custom_list(&name).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "delete custom list response");
+                if settings_changed {
+                    self.custom_lists_changed(Some(&name));
+                }
+            }
+            Err(e) => {
+                log::error!(
+                    "{}",
+                    e.display_chain_with_msg("Unable to delete custom list")
+                );
+                Self::oneshot_send(tx, Err(e), "delete custom list response");
+            }
+        }
+    }
+
+    /// Hands the custom lists to the relay selector, and reconnects if the relay settings use
+    /// the list named `changed_list`, or any list if no name is given.
+    #[cfg_attr(target_os = "android", allow(unused_variables))]
+    fn custom_lists_changed(&mut self, changed_list: Option<&str>) {
+        self.notify_settings();
+        self.relay_selector
+            .set_custom_lists(self.settings.custom_lists.clone());
+
+        #[cfg(not(target_os = "android"))]
+        if let RelaySettings::Normal(constraints) = self.settings.get_relay_settings() {
+            let in_use = match changed_list {
+                Some(name) => constraints.references_custom_list(name),
+                None => self
+                    .settings
+                    .custom_lists
+                    .custom_lists
+                    .iter()
+                    .any(|list| constraints.references_custom_list(&list.name)),
+            };
+            if in_use {
+                log::info!("Initiating tunnel restart because a custom list in use changed");
+                self.reconnect_tunnel();
+            }
+        }
+    }
+
+    async fn on_set_circumvention_assistant(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        let save_result = self.settings.set_circumvention_assistant(enabled).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set circumvention assistant response");
+                if settings_changed {
+                    self.notify_settings();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set circumvention assistant response");
+            }
+        }
+    }
+
+    fn on_login_account(&mut self, tx: ResponseTx<(), Error>, account_token: String) {
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            let result = async {
+                account_manager.login(account_token).await.map_err(|error| {
+                    log::error!("{}", error.display_chain_with_msg("Login failed"));
+                    Error::LoginError(error)
+                })
+            };
+            Self::oneshot_send(tx, result.await, "login_account response");
+        });
+    }
+
+    fn on_start_web_login(&mut self, tx: ResponseTx<LoginLink, Error>) {
+        if let Some(job) = self.web_login_job.take() {
+            job.abort();
+        }
+        let account_manager = self.account_manager.clone();
+        let (future, job) = abortable(Box::pin(async move {
+            let result = async {
+                if let Ok(data) = account_manager.data().await {
+                    if data.logged_in() {
+                        return Err(Error::AlreadyLoggedIn);
+                    }
+                }
+                account_manager
+                    .account_service
+                    .create_login_link()
+                    .await
+                    .map_err(Error::RestError)
+            };
+            match result.await {
+                Ok(link) => {
+                    Self::oneshot_send(tx, Ok(link.clone()), "start_web_login response");
+                    Self::complete_web_login(account_manager, link).await;
+                }
+                Err(error) => Self::oneshot_send(tx, Err(error), "start_web_login response"),
+            }
+        }));
+        tokio::spawn(future);
+        self.web_login_job = Some(job);
+    }
+
+    /// Polls the API until `link` has been approved or has expired, and then logs in.
+    async fn complete_web_login(account_manager: device::AccountManagerHandle, link: LoginLink) {
+        let account_token = loop {
+            tokio::time::sleep(WEB_LOGIN_POLL_INTERVAL).await;
+            if link.is_expired() {
+                log::info!("The login link expired before it was approved");
+                return;
+            }
+            match account_manager
+                .account_service
+                .poll_login_link(link.code.clone())
+                .await
+            {
+                Ok(Some(account_token)) => break account_token,
+                Ok(None) => (),
+                Err(error) if error.is_network_error() => {
+                    log::debug!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to check login link")
+                    );
+                }
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to check login link")
+                    );
+                    return;
+                }
+            }
+        };
+
+        if let Ok(data) = account_manager.data().await {
+            if data.logged_in() {
+                log::info!("Ignoring approved login link since an account is already set");
+                return;
+            }
+        }
+        match account_manager.login(account_token).await {
+            Ok(()) => log::info!("Logged in using a login link"),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to log in using a login link")
+                );
+            }
+        }
+    }
+
+    fn on_cancel_web_login(&mut self, tx: oneshot::Sender<()>) {
+        if let Some(job) = self.web_login_job.take() {
+            job.abort();
+        }
+        Self::oneshot_send(tx, (), "cancel_web_login response");
+    }
+
+    fn on_logout_account(&mut self, tx: ResponseTx<(), Error>) {
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            let result = async {
+                account_manager.logout().await.map_err(|error| {
+                    log::error!("{}", error.display_chain_with_msg("Logout failed"));
+                    Error::LogoutError(error)
+                })
+            };
+            Self::oneshot_send(tx, result.await, "logout_account response");
+        });
+    }
+
+    async fn on_get_device(&mut self, tx: ResponseTx<DeviceState, Error>) {
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(
+                tx,
+                account_manager
+                    .data()
+                    .await
+                    .map_err(|_| Error::NoAccountToken)
+                    .map(DeviceState::from),
+                "get_device response",
+            );
+        });
+    }
+
+    async fn on_update_device(&mut self, tx: ResponseTx<(), Error>) {
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            let result = match account_manager.validate_device().await {
+                Ok(_) | Err(device::Error::NoDevice) => Ok(()),
+                Err(error) => Err(error),
+            };
+            Self::oneshot_send(
+                tx,
+                result.map_err(Error::UpdateDeviceError),
+                "update_device response",
+            );
+        });
+    }
+
+    async fn on_list_devices(&self, tx: ResponseTx<Vec<Device>, Error>, token: AccountToken) {
+        let service = self.account_manager.device_service.clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(
+                tx,
+                service
+                    .list_devices(token)
+                    .await
+                    .map_err(Error::ListDevicesError),
+                "list_devices response",
+            );
+        });
+    }
+
+    async fn on_remove_device(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        account_token: AccountToken,
+        device_id: DeviceId,
+    ) {
+        let device_service = self.account_manager.device_service.clone();
+        let event_listener = self.event_listener.clone();
+
+        tokio::spawn(async move {
+            let result = device_service
+                .remove_device(account_token.clone(), device_id)
+                .await
+                .map(move |new_devices| {
+                    // FIXME: We should be able to get away with only returning the removed ID,
+                    //        and not have to request the list from the API.
+                    event_listener.notify_remove_device_event(RemoveDeviceEvent {
+                        account_token,
+                        new_devices,
+                    });
+                });
+            Self::oneshot_send(
+                tx,
+                result.map_err(Error::RemoveDeviceError),
+                "remove_device response",
+            );
+        });
+    }
+
+    fn on_get_account_history(&mut self, tx: oneshot::Sender<Option<AccountToken>>) {
+        Self::oneshot_send(
+            tx,
+            self.account_history.get(),
+            "get_account_history response",
+        );
+    }
+
+    async fn on_clear_account_history(&mut self, tx: ResponseTx<(), Error>) {
+        let result = self
+            .account_history
+            .clear()
+            .await
+            .map_err(Error::AccountHistory);
+        Self::oneshot_send(tx, result, "clear_account_history response");
+    }
+
+    async fn on_get_version_info(&mut self, tx: oneshot::Sender<Option<AppVersionInfo>>) {
+        if self.app_version_info.is_none() {
+            log::debug!("No version cache found. Fetching new info");
+            let mut handle = self.version_updater_handle.clone();
+            tokio::spawn(async move {
+                Self::oneshot_send(
+                    tx,
+                    handle
+                        .run_version_check()
+                        .await
+                        .map_err(|error| {
+                            log::error!(
+                                "{}",
+                                error.display_chain_with_msg("Error running version check")
+                            )
+                        })
+                        .ok(),
+                    "get_version_info response",
+                );
+            });
+        } else {
+            Self::oneshot_send(
+                tx,
+                self.app_version_info.clone(),
+                "get_version_info response",
+            );
+        }
+    }
+
+    fn on_get_current_version(&mut self, tx: oneshot::Sender<AppVersion>) {
+        Self::oneshot_send(
+            tx,
+            version::PRODUCT_VERSION.to_owned(),
+            "get_current_version response",
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_factory_reset(&mut self, tx: ResponseTx<(), Error>) {
+        let mut last_error = Ok(());
+
+        if let Err(error) = self.account_manager.logout().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to clear device cache")
+            );
+        }
+
+        if let Err(error) = self.account_history.clear().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to clear account history")
+            );
+            last_error = Err(Error::FactoryResetError("Failed to clear account history"));
+        }
+
+        self.setting_overrides.clear().await;
+        self.tunnel_pause_record.save(None).await;
+        #[cfg(not(target_os = "android"))]
+        self.circumvention_changes.take().await;
+
+        if let Err(e) = self.settings.reset().await {
+            log::error!("Failed to reset settings: {}", e);
+            last_error = Err(Error::FactoryResetError("Failed to reset settings"));
+        }
+        if let Err(e) = self.settings.set_encryption(false).await {
+            log::error!(
+                "{}",
+                e.display_chain_with_msg("Failed to disable settings encryption")
+            );
+            last_error = Err(Error::FactoryResetError(
+                "Failed to disable settings encryption",
+            ));
+        }
+
+        // Shut the daemon down.
+        self.trigger_shutdown_event();
+
+        self.shutdown_tasks.push(Box::pin(async move {
+            if let Err(e) = cleanup::clear_directories().await {
+                log::error!(
+                    "{}",
+                    e.display_chain_with_msg("Failed to clear cache and log directories")
+                );
+                last_error = Err(Error::FactoryResetError(
+                    "Failed to clear cache and log directories",
+                ));
+            }
+            Self::oneshot_send(tx, last_error, "factory_reset response");
+        }));
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_get_split_tunnel_processes(&mut self, tx: ResponseTx<Vec<i32>, split_tunnel::Error>) {
+        let result = self.exclude_pids.list().map_err(|error| {
+            log::error!("{}", error.display_chain_with_msg("Unable to obtain PIDs"));
+            error
+        });
+        Self::oneshot_send(tx, result, "get_split_tunnel_processes response");
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_add_split_tunnel_process(&mut self, tx: ResponseTx<(), split_tunnel::Error>, pid: i32) {
         let result = self.exclude_pids.add(pid).map_err(|error| {
             log::error!("{}", error.display_chain_with_msg("Unable to add PID"));
             error
@@ -1538,314 +3996,753 @@ where
     }
 
     #[cfg(target_os = "linux")]
-    fn on_remove_split_tunnel_process(
+    fn on_add_split_tunnel_process_tree(
+        &mut self,
+        tx: ResponseTx<(), split_tunnel::Error>,
+        pid: i32,
+    ) {
+        let result = self.exclude_pids.add_tree(pid).map_err(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to add process tree")
+            );
+            error
+        });
+        Self::oneshot_send(tx, result, "add_split_tunnel_process_tree response");
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_remove_split_tunnel_process(
+        &mut self,
+        tx: ResponseTx<(), split_tunnel::Error>,
+        pid: i32,
+    ) {
+        let result = self.exclude_pids.remove(pid).map_err(|error| {
+            log::error!("{}", error.display_chain_with_msg("Unable to remove PID"));
+            error
+        });
+        Self::oneshot_send(tx, result, "remove_split_tunnel_process response");
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_clear_split_tunnel_processes(&mut self, tx: ResponseTx<(), split_tunnel::Error>) {
+        let result = self.exclude_pids.clear().map_err(|error| {
+            log::error!("{}", error.display_chain_with_msg("Unable to clear PIDs"));
+            error
+        });
+        Self::oneshot_send(tx, result, "clear_split_tunnel_processes response");
+    }
+
+    /// Update the split app paths in both the settings and tunnel
+    #[cfg(windows)]
+    async fn set_split_tunnel_paths(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        response_msg: &'static str,
+        settings: Settings,
+        update: ExcludedPathsUpdate,
+    ) {
+        let new_list = match update {
+            ExcludedPathsUpdate::SetPaths(ref paths) => {
+                if *paths == settings.split_tunnel.apps {
+                    Self::oneshot_send(tx, Ok(()), response_msg);
+                    return;
+                }
+                paths.iter()
+            }
+            ExcludedPathsUpdate::SetState(_) => settings.split_tunnel.apps.iter(),
+        };
+        let new_state = match update {
+            ExcludedPathsUpdate::SetPaths(_) => settings.split_tunnel.enable_exclusions,
+            ExcludedPathsUpdate::SetState(state) => {
+                if state == settings.split_tunnel.enable_exclusions {
+                    Self::oneshot_send(tx, Ok(()), response_msg);
+                    return;
+                }
+                state
+            }
+        };
+
+        if new_state || new_state != settings.split_tunnel.enable_exclusions {
+            let tunnel_list = if new_state {
+                new_list.map(|s| OsString::from(s)).collect()
+            } else {
+                vec![]
+            };
+
+            let (result_tx, result_rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::SetExcludedApps(result_tx, tunnel_list));
+            let daemon_tx = self.tx.clone();
+
+            tokio::spawn(async move {
+                match result_rx.await {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(error)) => {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to set excluded apps list")
+                        );
+                        Self::oneshot_send(tx, Err(Error::SplitTunnelError(error)), response_msg);
+                        return;
+                    }
+                    Err(_) => {
+                        log::error!("The tunnel failed to return a result");
+                        return;
+                    }
+                }
+
+                let _ = daemon_tx.send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+            });
+        } else {
+            let _ = self
+                .tx
+                .send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+        }
+    }
+
+    #[cfg(windows)]
+    async fn on_add_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, path: PathBuf) {
+        let settings = self.settings.to_settings();
+
+        let mut new_list = settings.split_tunnel.apps.clone();
+        new_list.insert(path);
+
+        self.set_split_tunnel_paths(
+            tx,
+            "add_split_tunnel_app response",
+            settings,
+            ExcludedPathsUpdate::SetPaths(new_list),
+        )
+        .await;
+    }
+
+    #[cfg(windows)]
+    async fn on_remove_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, path: PathBuf) {
+        let settings = self.settings.to_settings();
+
+        let mut new_list = settings.split_tunnel.apps.clone();
+        new_list.remove(&path);
+
+        self.set_split_tunnel_paths(
+            tx,
+            "remove_split_tunnel_app response",
+            settings,
+            ExcludedPathsUpdate::SetPaths(new_list),
+        )
+        .await;
+    }
+
+    #[cfg(windows)]
+    async fn on_clear_split_tunnel_apps(&mut self, tx: ResponseTx<(), Error>) {
+        let settings = self.settings.to_settings();
+        let new_list = HashSet::new();
+        self.set_split_tunnel_paths(
+            tx,
+            "clear_split_tunnel_apps response",
+            settings,
+            ExcludedPathsUpdate::SetPaths(new_list),
+        )
+        .await;
+    }
+
+    #[cfg(windows)]
+    async fn on_set_split_tunnel_state(&mut self, tx: ResponseTx<(), Error>, state: bool) {
+        let settings = self.settings.to_settings();
+        self.set_split_tunnel_paths(
+            tx,
+            "set_split_tunnel_state response",
+            settings,
+            ExcludedPathsUpdate::SetState(state),
+        )
+        .await;
+    }
+
+    #[cfg(windows)]
+    fn on_get_split_tunnel_processes(
+        &self,
+        tx: ResponseTx<Vec<split_tunnel::ExcludedProcess>, split_tunnel::Error>,
+    ) {
+        Self::oneshot_send(
+            tx,
+            self.tunnel_state_machine_handle
+                .split_tunnel()
+                .get_processes(),
+            "get_split_tunnel_processes response",
+        );
+    }
+
+    #[cfg(windows)]
+    async fn on_use_wireguard_nt(&mut self, tx: ResponseTx<(), Error>, state: bool) {
+        let save_result = self
+            .settings
+            .set_use_wireguard_nt(state)
+            .await
+            .map_err(Error::SettingsError);
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "use_wireguard_nt response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    if let Some(TunnelType::Wireguard) = self.get_target_tunnel_type() {
+                        log::info!("Initiating tunnel restart");
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Unable to save settings")
+                );
+                Self::oneshot_send(tx, Err(error), "use_wireguard_nt response");
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    async fn on_check_volumes(&mut self, tx: ResponseTx<(), Error>) {
+        if self.volume_update_tx.unbounded_send(()).is_ok() {
+            let _ = tx.send(Ok(()));
+        }
+    }
+
+    #[cfg(windows)]
+    async fn on_set_link_layer_exemptions(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        exemptions: LinkLayerExemptions,
+    ) {
+        let save_result = self.settings.set_link_layer_exemptions(exemptions).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_link_layer_exemptions response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::LinkLayerExemptions(exemptions));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_link_layer_exemptions response");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_set_dns_backend(
         &mut self,
-        tx: ResponseTx<(), split_tunnel::Error>,
-        pid: i32,
+        tx: ResponseTx<(), settings::Error>,
+        backend: DnsBackend,
     ) {
-        let result = self.exclude_pids.remove(pid).map_err(|error| {
-            log::error!("{}", error.display_chain_with_msg("Unable to remove PID"));
-            error
-        });
-        Self::oneshot_send(tx, result, "remove_split_tunnel_process response");
+        let save_result = self.settings.set_dns_backend(backend).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_dns_backend response");
+                if settings_changed {
+                    self.notify_settings();
+                    talpid_core::dns::set_backend(backend);
+                    // The backend also decides how WireGuard tunnels are created.
+                    log::info!("Initiating tunnel restart because the DNS backend changed");
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_dns_backend response");
+            }
+        }
     }
 
     #[cfg(target_os = "linux")]
-    fn on_clear_split_tunnel_processes(&mut self, tx: ResponseTx<(), split_tunnel::Error>) {
-        let result = self.exclude_pids.clear().map_err(|error| {
-            log::error!("{}", error.display_chain_with_msg("Unable to clear PIDs"));
-            error
-        });
-        Self::oneshot_send(tx, result, "clear_split_tunnel_processes response");
+    async fn on_set_policy_routing(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        routing: PolicyRouting,
+    ) {
+        let save_result = self.settings.set_policy_routing(routing).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_policy_routing response");
+                if settings_changed {
+                    self.notify_settings();
+                    // Routing rules and firewall rules that exist use the old values
+                    log::info!(
+                        "Policy routing changed to {}. This takes effect when the daemon is restarted",
+                        routing
+                    );
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_policy_routing response");
+            }
+        }
+    }
+
+    async fn on_update_relay_settings(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        update: RelaySettingsUpdate,
+    ) {
+        let save_result = self.settings.update_relay_settings(update).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "update_relay_settings response");
+                if settings_changed == SettingsChanged::RelaySettingsAndBridgeState {
+                    log::info!(
+                        "Changed the bridge state to auto, since the new relay settings do not \
+                         support bridges"
+                    );
+                }
+                if settings_changed.is_changed() {
+                    self.notify_settings();
+                    self.relay_selector
+                        .set_config(new_selector_config(&self.settings));
+                    log::info!("Initiating tunnel restart because the relay settings changed");
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "update_relay_settings response");
+            }
+        }
+    }
+
+    async fn on_set_allow_lan(&mut self, tx: ResponseTx<(), settings::Error>, allow_lan: bool) {
+        let save_result = self.settings.set_allow_lan(allow_lan).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_allow_lan response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::AllowLan(allow_lan));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_allow_lan response");
+            }
+        }
+    }
+
+    async fn on_set_allow_multicast(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        allow_multicast: bool,
+    ) {
+        let save_result = self.settings.set_allow_multicast(allow_multicast).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_allow_multicast response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::AllowMulticast(allow_multicast));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_allow_multicast response");
+            }
+        }
+    }
+
+    async fn on_set_allowed_lan_nets(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        allowed_lan_nets: Option<Vec<IpNetwork>>,
+    ) {
+        let save_result = self
+            .settings
+            .set_allowed_lan_nets(allowed_lan_nets.clone())
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_allowed_lan_nets response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::AllowedLanNets(allowed_lan_nets));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_allowed_lan_nets response");
+            }
+        }
+    }
+
+    async fn on_set_firewall_exceptions(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        exceptions: Vec<FirewallException>,
+    ) {
+        let save_result = self
+            .settings
+            .set_firewall_exceptions(exceptions.clone())
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_firewall_exceptions response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::FirewallExceptions(exceptions));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_firewall_exceptions response");
+            }
+        }
+    }
+
+    async fn on_set_inbound_tunnel_ports(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        ports: Vec<InboundTunnelPort>,
+    ) {
+        let save_result = self.settings.set_inbound_tunnel_ports(ports.clone()).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_inbound_tunnel_ports response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::InboundTunnelPorts(ports));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_inbound_tunnel_ports response");
+            }
+        }
+    }
+
+    async fn on_set_show_beta_releases(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        let save_result = self.settings.set_show_beta_releases(enabled).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_show_beta_releases response");
+                if settings_changed {
+                    self.notify_settings();
+                    let mut handle = self.version_updater_handle.clone();
+                    handle.set_show_beta_releases(enabled).await;
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_show_beta_releases response");
+            }
+        }
     }
 
-    /// Update the split app paths in both the settings and tunnel
-    #[cfg(windows)]
-    async fn set_split_tunnel_paths(
+    async fn on_set_app_exclusions(
         &mut self,
-        tx: ResponseTx<(), Error>,
-        response_msg: &'static str,
-        settings: Settings,
-        update: ExcludedPathsUpdate,
+        tx: ResponseTx<(), settings::Error>,
+        app_exclusions: AppExclusionSettings,
     ) {
-        let new_list = match update {
-            ExcludedPathsUpdate::SetPaths(ref paths) => {
-                if *paths == settings.split_tunnel.apps {
-                    Self::oneshot_send(tx, Ok(()), response_msg);
-                    return;
+        let save_result = self.settings.set_app_exclusions(app_exclusions).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_app_exclusions response");
+                if settings_changed {
+                    self.notify_settings();
                 }
-                paths.iter()
             }
-            ExcludedPathsUpdate::SetState(_) => settings.split_tunnel.apps.iter(),
-        };
-        let new_state = match update {
-            ExcludedPathsUpdate::SetPaths(_) => settings.split_tunnel.enable_exclusions,
-            ExcludedPathsUpdate::SetState(state) => {
-                if state == settings.split_tunnel.enable_exclusions {
-                    Self::oneshot_send(tx, Ok(()), response_msg);
-                    return;
-                }
-                state
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_app_exclusions response");
             }
-        };
-
-        if new_state || new_state != settings.split_tunnel.enable_exclusions {
-            let tunnel_list = if new_state {
-                new_list.map(|s| OsString::from(s)).collect()
-            } else {
-                vec![]
-            };
-
-            let (result_tx, result_rx) = oneshot::channel();
-            self.send_tunnel_command(TunnelCommand::SetExcludedApps(result_tx, tunnel_list));
-            let daemon_tx = self.tx.clone();
+        }
+    }
 
-            tokio::spawn(async move {
-                match result_rx.await {
-                    Ok(Ok(_)) => (),
-                    Ok(Err(error)) => {
-                        log::error!(
-                            "{}",
-                            error.display_chain_with_msg("Failed to set excluded apps list")
-                        );
-                        Self::oneshot_send(tx, Err(Error::SplitTunnelError(error)), response_msg);
-                        return;
-                    }
-                    Err(_) => {
-                        log::error!("The tunnel failed to return a result");
-                        return;
+    async fn on_set_block_when_disconnected(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        block_when_disconnected: bool,
+    ) {
+        let save_result = self
+            .settings
+            .set_block_when_disconnected(block_when_disconnected)
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_block_when_disconnected response");
+                if settings_changed {
+                    self.notify_settings();
+                    // A paused tunnel or permissive mode picks up the new value when it ends
+                    if self.tunnel_pause.is_none() && self.permissive_mode.is_none() {
+                        self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
+                            block_when_disconnected,
+                        ));
                     }
                 }
-
-                let _ = daemon_tx.send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
-            });
-        } else {
-            let _ = self
-                .tx
-                .send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_block_when_disconnected response");
+            }
         }
     }
 
-    #[cfg(windows)]
-    async fn on_add_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, path: PathBuf) {
-        let settings = self.settings.to_settings();
-
-        let mut new_list = settings.split_tunnel.apps.clone();
-        new_list.insert(path);
-
-        self.set_split_tunnel_paths(
-            tx,
-            "add_split_tunnel_app response",
-            settings,
-            ExcludedPathsUpdate::SetPaths(new_list),
-        )
-        .await;
-    }
-
-    #[cfg(windows)]
-    async fn on_remove_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, path: PathBuf) {
-        let settings = self.settings.to_settings();
-
-        let mut new_list = settings.split_tunnel.apps.clone();
-        new_list.remove(&path);
-
-        self.set_split_tunnel_paths(
-            tx,
-            "remove_split_tunnel_app response",
-            settings,
-            ExcludedPathsUpdate::SetPaths(new_list),
-        )
-        .await;
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_strict_enforcement(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        strict_enforcement: bool,
+    ) {
+        let save_result = self
+            .settings
+            .set_strict_enforcement(strict_enforcement)
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_strict_enforcement response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.send_tunnel_command(TunnelCommand::StrictEnforcement(strict_enforcement));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_strict_enforcement response");
+            }
+        }
     }
 
-    #[cfg(windows)]
-    async fn on_clear_split_tunnel_apps(&mut self, tx: ResponseTx<(), Error>) {
-        let settings = self.settings.to_settings();
-        let new_list = HashSet::new();
-        self.set_split_tunnel_paths(
-            tx,
-            "clear_split_tunnel_apps response",
-            settings,
-            ExcludedPathsUpdate::SetPaths(new_list),
-        )
-        .await;
+    async fn on_set_auto_connect(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        auto_connect: bool,
+    ) {
+        let save_result = self.settings.set_auto_connect(auto_connect).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set auto-connect response");
+                if settings_changed {
+                    self.notify_settings();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set auto-connect response");
+            }
+        }
     }
 
-    #[cfg(windows)]
-    async fn on_set_split_tunnel_state(&mut self, tx: ResponseTx<(), Error>, state: bool) {
-        let settings = self.settings.to_settings();
-        self.set_split_tunnel_paths(
-            tx,
-            "set_split_tunnel_state response",
-            settings,
-            ExcludedPathsUpdate::SetState(state),
-        )
-        .await;
+    async fn on_set_auto_connect_scope(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        scope: AutoConnectScope,
+    ) {
+        let save_result = self.settings.set_auto_connect_scope(scope).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set auto-connect scope response");
+                if settings_changed {
+                    self.notify_settings();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set auto-connect scope response");
+            }
+        }
     }
 
-    #[cfg(windows)]
-    fn on_get_split_tunnel_processes(
-        &self,
-        tx: ResponseTx<Vec<split_tunnel::ExcludedProcess>, split_tunnel::Error>,
+    async fn on_set_exit_relay_change_policy(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        policy: ExitRelayChangePolicy,
     ) {
-        Self::oneshot_send(
-            tx,
-            self.tunnel_state_machine_handle
-                .split_tunnel()
-                .get_processes(),
-            "get_split_tunnel_processes response",
-        );
+        let save_result = self.settings.set_exit_relay_change_policy(policy).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set exit relay change policy response");
+                if settings_changed {
+                    self.notify_settings();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set exit relay change policy response");
+            }
+        }
     }
 
-    #[cfg(windows)]
-    async fn on_use_wireguard_nt(&mut self, tx: ResponseTx<(), Error>, state: bool) {
-        let save_result = self
-            .settings
-            .set_use_wireguard_nt(state)
-            .await
-            .map_err(Error::SettingsError);
+    async fn on_set_relay_selection_strategy(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        strategy: SelectionStrategy,
+    ) {
+        let save_result = self.settings.set_relay_selection_strategy(strategy).await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "use_wireguard_nt response");
+                Self::oneshot_send(tx, Ok(()), "set relay selection strategy response");
                 if settings_changed {
-                    self.parameters_generator
-                        .set_tunnel_options(&self.settings.tunnel_options)
-                        .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
-                    if let Some(TunnelType::Wireguard) = self.get_target_tunnel_type() {
-                        log::info!("Initiating tunnel restart");
-                        self.reconnect_tunnel();
-                    }
+                    self.notify_settings();
+                    self.relay_selector.set_selection_strategy(strategy);
                 }
             }
-            Err(error) => {
-                log::error!(
-                    "{}",
-                    error.display_chain_with_msg("Unable to save settings")
-                );
-                Self::oneshot_send(tx, Err(error), "use_wireguard_nt response");
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set relay selection strategy response");
             }
         }
     }
 
-    #[cfg(windows)]
-    async fn on_check_volumes(&mut self, tx: ResponseTx<(), Error>) {
-        if self.volume_update_tx.unbounded_send(()).is_ok() {
-            let _ = tx.send(Ok(()));
+    async fn on_set_relay_failure_cooldown(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        cooldown: Duration,
+    ) {
+        let save_result = self.settings.set_relay_failure_cooldown(cooldown).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set relay failure cooldown response");
+                if settings_changed {
+                    self.notify_settings();
+                    self.relay_selector.set_failure_cooldown(cooldown);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set relay failure cooldown response");
+            }
         }
     }
 
-    async fn on_update_relay_settings(
+    async fn on_set_expiry_notifications(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
-        update: RelaySettingsUpdate,
+        expiry_notifications: ExpiryNotificationSettings,
     ) {
-        let save_result = self.settings.update_relay_settings(update).await;
+        let save_result = self
+            .settings
+            .set_expiry_notifications(expiry_notifications)
+            .await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "update_relay_settings response");
+                Self::oneshot_send(tx, Ok(()), "set expiry notifications response");
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
-                    self.relay_selector
-                        .set_config(new_selector_config(&self.settings));
-                    log::info!("Initiating tunnel restart because the relay settings changed");
-                    self.reconnect_tunnel();
+                    self.notify_settings();
+                    // Notify right away if a new threshold has already been crossed
+                    let _ = self.tx.send(InternalDaemonEvent::CheckAccountExpiry);
                 }
             }
             Err(e) => {
                 log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
-                Self::oneshot_send(tx, Err(e), "update_relay_settings response");
+                Self::oneshot_send(tx, Err(e), "set expiry notifications response");
             }
         }
     }
 
-    async fn on_set_allow_lan(&mut self, tx: ResponseTx<(), settings::Error>, allow_lan: bool) {
-        let save_result = self.settings.set_allow_lan(allow_lan).await;
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_tunnel_hooks(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        tunnel_hooks: TunnelHookSettings,
+    ) {
+        let tunnel_hooks = match self.tunnel_hooks.validate_all(tunnel_hooks) {
+            Ok(tunnel_hooks) => tunnel_hooks,
+            Err(error) => {
+                let error = Error::TunnelHookError(error);
+                log::error!("{}", error.display_chain_with_msg("Rejected hook script"));
+                Self::oneshot_send(tx, Err(error), "set tunnel hooks response");
+                return;
+            }
+        };
+
+        let save_result = self.settings.set_tunnel_hooks(tunnel_hooks).await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "set_allow_lan response");
+                Self::oneshot_send(tx, Ok(()), "set tunnel hooks response");
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
-                    self.send_tunnel_command(TunnelCommand::AllowLan(allow_lan));
+                    self.notify_settings();
                 }
             }
             Err(e) => {
                 log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
-                Self::oneshot_send(tx, Err(e), "set_allow_lan response");
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(e)),
+                    "set tunnel hooks response",
+                );
             }
         }
     }
 
-    async fn on_set_show_beta_releases(
+    async fn on_set_disconnect_warning_threshold(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
-        enabled: bool,
+        threshold: Option<u64>,
     ) {
-        let save_result = self.settings.set_show_beta_releases(enabled).await;
+        let save_result = self
+            .settings
+            .set_disconnect_warning_threshold(threshold)
+            .await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "set_show_beta_releases response");
+                Self::oneshot_send(tx, Ok(()), "set disconnect warning threshold response");
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
-                    let mut handle = self.version_updater_handle.clone();
-                    handle.set_show_beta_releases(enabled).await;
+                    self.notify_settings();
                 }
             }
             Err(e) => {
                 log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
-                Self::oneshot_send(tx, Err(e), "set_show_beta_releases response");
+                Self::oneshot_send(tx, Err(e), "set disconnect warning threshold response");
             }
         }
     }
 
-    async fn on_set_block_when_disconnected(
+    async fn on_set_reconnect_debounce(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
-        block_when_disconnected: bool,
+        reconnect_debounce: ReconnectDebounce,
     ) {
         let save_result = self
             .settings
-            .set_block_when_disconnected(block_when_disconnected)
+            .set_reconnect_debounce(reconnect_debounce)
             .await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "set_block_when_disconnected response");
+                Self::oneshot_send(tx, Ok(()), "set reconnect debounce response");
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
-                    self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
-                        block_when_disconnected,
-                    ));
+                    self.notify_settings();
+                    self.tunnel_state_machine_handle
+                        .set_reconnect_debounce(reconnect_debounce);
                 }
             }
             Err(e) => {
                 log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
-                Self::oneshot_send(tx, Err(e), "set_block_when_disconnected response");
+                Self::oneshot_send(tx, Err(e), "set reconnect debounce response");
             }
         }
     }
 
-    async fn on_set_auto_connect(
+    async fn on_set_offline_detection(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
-        auto_connect: bool,
+        offline_detection: OfflineDetection,
     ) {
-        let save_result = self.settings.set_auto_connect(auto_connect).await;
+        let save_result = self.settings.set_offline_detection(offline_detection).await;
         match save_result {
             Ok(settings_changed) => {
-                Self::oneshot_send(tx, Ok(()), "set auto-connect response");
+                Self::oneshot_send(tx, Ok(()), "set offline detection response");
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
+                    self.tunnel_state_machine_handle
+                        .set_offline_detection(offline_detection);
                 }
             }
             Err(e) => {
                 log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
-                Self::oneshot_send(tx, Err(e), "set auto-connect response");
+                Self::oneshot_send(tx, Err(e), "set offline detection response");
             }
         }
     }
@@ -1863,8 +4760,7 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&self.settings.tunnel_options)
                         .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     if self.get_target_tunnel_type() == Some(TunnelType::OpenVpn) {
                         log::info!(
                             "Initiating tunnel restart because the OpenVPN mssfix setting changed"
@@ -1885,11 +4781,21 @@ where
         tx: ResponseTx<(), settings::Error>,
         new_settings: BridgeSettings,
     ) {
+        if let BridgeSettings::Normal(constraints) = &new_settings {
+            if let Err(error) = self.relay_selector.validate_bridge_constraints(constraints) {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Rejecting new bridge settings")
+                );
+                let error = settings::Error::Rejected(SettingsChangeError::NoMatchingBridge);
+                Self::oneshot_send(tx, Err(error), "set_bridge_settings");
+                return;
+            }
+        }
         match self.settings.set_bridge_settings(new_settings).await {
             Ok(settings_changes) => {
                 if settings_changes {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     self.relay_selector
                         .set_config(new_selector_config(&self.settings));
                     if let Err(error) = self.api_handle.service().next_api_endpoint().await {
@@ -1912,14 +4818,25 @@ where
 
     async fn on_set_obfuscation_settings(
         &mut self,
-        tx: ResponseTx<(), settings::Error>,
-        new_settings: ObfuscationSettings,
+        tx: ResponseTx<(), Error>,
+        mut new_settings: ObfuscationSettings,
     ) {
+        if let Some(transport) = new_settings.external.transport.as_mut() {
+            match external_transports::validate(&self.external_transports_dir, &transport.path) {
+                Ok(path) => transport.path = path,
+                Err(error) => {
+                    let error = Error::ExternalTransportError(error);
+                    log::error!("{}", error.display_chain_with_msg("Rejected transport"));
+                    Self::oneshot_send(tx, Err(error), "set_obfuscation_settings");
+                    return;
+                }
+            }
+        }
+
         match self.settings.set_obfuscation_settings(new_settings).await {
             Ok(settings_changed) => {
                 if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     self.relay_selector
                         .set_config(new_selector_config(&self.settings));
                     self.reconnect_tunnel();
@@ -1931,7 +4848,11 @@ where
                     "{}",
                     err.display_chain_with_msg("Failed to set obfuscation settings")
                 );
-                Self::oneshot_send(tx, Err(err), "set_obfuscation_settings");
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(err)),
+                    "set_obfuscation_settings",
+                );
             }
         }
     }
@@ -1943,9 +4864,8 @@ where
     ) {
         let result = match self.settings.set_bridge_state(bridge_state).await {
             Ok(settings_changed) => {
-                if settings_changed {
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                if settings_changed.is_changed() {
+                    self.notify_settings();
                     self.relay_selector
                         .set_config(new_selector_config(&self.settings));
                     log::info!("Initiating tunnel restart because bridge state changed");
@@ -1973,8 +4893,7 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&self.settings.tunnel_options)
                         .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     log::info!("Initiating tunnel restart because the enable IPv6 setting changed");
                     self.reconnect_tunnel();
                 }
@@ -1986,6 +4905,57 @@ where
         }
     }
 
+    #[cfg(target_os = "linux")]
+    async fn on_set_rate_limit(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        rate_limit: Option<u64>,
+    ) {
+        let save_result = self.settings.set_rate_limit(rate_limit).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_rate_limit response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    log::info!("Initiating tunnel restart because the rate limit changed");
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_rate_limit response");
+            }
+        }
+    }
+
+    async fn on_set_retry_policy(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        retry_policy: RetryPolicy,
+    ) {
+        let save_result = self.settings.set_retry_policy(retry_policy).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_retry_policy response");
+                if settings_changed {
+                    // The policy applies from the next attempt at connecting, so there is no need
+                    // to reconnect
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_retry_policy response");
+            }
+        }
+    }
+
     async fn on_set_quantum_resistant_tunnel(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2002,8 +4972,7 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&self.settings.tunnel_options)
                         .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     if self.get_target_tunnel_type() == Some(TunnelType::Wireguard) {
                         log::info!("Reconnecting because the PQ safety setting changed");
                         self.reconnect_tunnel();
@@ -2033,8 +5002,41 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&settings.tunnel_options)
                         .await;
-                    self.event_listener.notify_settings(settings);
+                    self.notify_settings();
+                    #[cfg(not(target_os = "android"))]
+                    {
+                        if self.tunnel_state.is_connected() {
+                            self.dns_failover.update(resolvers.clone());
+                        }
+                        self.check_nat64();
+                    }
                     self.send_tunnel_command(TunnelCommand::Dns(resolvers));
+                    #[cfg(not(target_os = "android"))]
+                    self.send_tunnel_command(TunnelCommand::SplitDns(
+                        settings.tunnel_options.dns_options.split_rules.clone(),
+                    ));
+                    #[cfg(not(target_os = "android"))]
+                    self.send_tunnel_command(TunnelCommand::DnsFilter(dns::filter_from_options(
+                        &settings.tunnel_options.dns_options,
+                    )));
+                    #[cfg(not(target_os = "android"))]
+                    {
+                        let dns_options = &settings.tunnel_options.dns_options;
+                        if !dns_options.query_log {
+                            self.dns_query_log.clear();
+                        }
+                        self.send_tunnel_command(TunnelCommand::DnsQueryLog(
+                            dns::query_log_from_options(&self.dns_query_log, dns_options),
+                        ));
+                    }
+                    #[cfg(not(target_os = "android"))]
+                    self.send_tunnel_command(TunnelCommand::SearchDomains(
+                        settings.tunnel_options.dns_options.search_domains,
+                    ));
+                    #[cfg(not(target_os = "android"))]
+                    self.send_tunnel_command(TunnelCommand::StrictDnsLeakPrevention(
+                        settings.tunnel_options.dns_options.strict_leak_prevention,
+                    ));
                 }
             }
             Err(e) => {
@@ -2057,8 +5059,7 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&self.settings.tunnel_options)
                         .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                     if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
                         log::info!(
                             "Initiating tunnel restart because the WireGuard MTU setting changed"
@@ -2074,6 +5075,130 @@ where
         }
     }
 
+    async fn on_set_wireguard_liveness(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        liveness: LivenessOptions,
+    ) {
+        let save_result = self.settings.set_wireguard_liveness(liveness).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_liveness response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard liveness settings \
+                            changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_liveness response");
+            }
+        }
+    }
+
+    async fn on_set_wireguard_persistent_keepalive(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        interval: u16,
+    ) {
+        let save_result = self
+            .settings
+            .set_wireguard_persistent_keepalive(interval)
+            .await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_persistent_keepalive response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard keepalive interval \
+                            changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_persistent_keepalive response");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_wireguard_allowed_ips(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        allowed_ips: Option<Vec<IpNetwork>>,
+    ) {
+        let save_result = self.settings.set_wireguard_allowed_ips(allowed_ips).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_allowed_ips response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard allowed IPs changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_allowed_ips response");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_set_wireguard_namespace(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        namespace: Option<String>,
+    ) {
+        let save_result = self.settings.set_wireguard_namespace(namespace).await;
+        match save_result {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_namespace response");
+                if settings_changed {
+                    self.parameters_generator
+                        .set_tunnel_options(&self.settings.tunnel_options)
+                        .await;
+                    self.notify_settings();
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard network namespace \
+                            changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_namespace response");
+            }
+        }
+    }
+
     async fn on_set_wireguard_rotation_interval(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2100,8 +5225,7 @@ where
                     self.parameters_generator
                         .set_tunnel_options(&self.settings.tunnel_options)
                         .await;
-                    self.event_listener
-                        .notify_settings(self.settings.to_settings());
+                    self.notify_settings();
                 }
             }
             Err(e) => {
@@ -2137,6 +5261,116 @@ where
         Self::oneshot_send(tx, self.settings.to_settings(), "get_settings response");
     }
 
+    #[cfg(not(target_os = "android"))]
+    fn on_get_settings_history(&self, tx: oneshot::Sender<Vec<settings_history::HistoryEntry>>) {
+        Self::oneshot_send(tx, self.settings.history(), "get_settings_history response");
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_rollback_settings(&mut self, tx: ResponseTx<(), Error>, id: u64) {
+        match self.settings.rollback(id) {
+            Ok(new_settings) => {
+                if let Err(error) = self.settings.validate(&new_settings) {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Unable to undo settings change")
+                    );
+                    Self::oneshot_send(
+                        tx,
+                        Err(Error::SettingsError(error)),
+                        "rollback_settings response",
+                    );
+                    return;
+                }
+                log::info!("Undoing settings change {}", id);
+                self.settings
+                    .set_change_source(settings_history::ChangeSource::Rollback(id));
+                let result = self.apply_settings(new_settings).await;
+                self.settings
+                    .set_change_source(settings_history::ChangeSource::Client);
+                Self::oneshot_send(tx, result, "rollback_settings response");
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Unable to undo settings change")
+                );
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsRollback(error)),
+                    "rollback_settings response",
+                );
+            }
+        }
+    }
+
+    async fn on_apply_settings_patch(&mut self, tx: ResponseTx<(), Error>, patch: String) {
+        match self.settings.patch(&patch) {
+            Ok(new_settings) => {
+                log::info!("Applying a settings patch");
+                let result = self.apply_settings(new_settings).await;
+                Self::oneshot_send(tx, result, "apply_settings_patch response");
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Unable to apply settings patch")
+                );
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(error)),
+                    "apply_settings_patch response",
+                );
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn on_get_settings_encryption(&self, tx: oneshot::Sender<bool>) {
+        Self::oneshot_send(
+            tx,
+            self.settings.is_encrypted(),
+            "get_settings_encryption response",
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_settings_encryption(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        enabled: bool,
+    ) {
+        match self.settings.set_encryption(enabled).await {
+            Ok(changed) => {
+                if changed {
+                    log::info!(
+                        "Settings encryption {}",
+                        if enabled { "enabled" } else { "disabled" }
+                    );
+                }
+                Self::oneshot_send(tx, Ok(()), "set_settings_encryption response");
+            }
+            Err(e) => {
+                log::error!(
+                    "{}",
+                    e.display_chain_with_msg("Unable to change settings encryption")
+                );
+                Self::oneshot_send(tx, Err(e), "set_settings_encryption response");
+            }
+        }
+    }
+
+    /// Tells clients about the current settings. While several settings are changed at once, this
+    /// is held back until all of them have been changed.
+    fn notify_settings(&mut self) {
+        match self.settings_batch.as_mut() {
+            Some(changed) => *changed = true,
+            None => self
+                .event_listener
+                .notify_settings(self.settings.to_settings()),
+        }
+    }
+
     fn oneshot_send<T>(tx: oneshot::Sender<T>, t: T, msg: &'static str) {
         if tx.send(t).is_err() {
             log::warn!("Unable to send {} to the daemon command sender", msg);
@@ -2261,3 +5495,12 @@ fn new_selector_config(settings: &Settings) -> SelectorConfig {
         obfuscation_settings: settings.obfuscation_settings.clone(),
     }
 }
+
+/// Returns the error that a command replied with, if any. The setting commands reply before they
+/// return, so the reply is already waiting when this is called.
+fn reply_error<E: Into<Error>>(mut rx: oneshot::Receiver<Result<(), E>>) -> Option<Error> {
+    match rx.try_recv() {
+        Ok(Some(Err(error))) => Some(error.into()),
+        _ => None,
+    }
+}
@@ -0,0 +1,216 @@
+//! Settings that are locked by an administrator.
+//!
+//! An administrator may place `LOCKED_SETTINGS_FILE` in the settings directory. It has the same
+//! layout as the settings file, but only contains the settings that are locked, such as
+//! `{"block_when_disconnected": true}`. The daemon never writes to it. The locked values replace
+//! those in the user settings whenever the settings are loaded, and changes to them are refused.
+
+use mullvad_types::settings::Settings;
+use serde_json::{Map, Value};
+use std::path::Path;
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+pub const LOCKED_SETTINGS_FILE: &str = "locked-settings.json";
+
+#[derive(Debug, Default)]
+pub struct LockedSettings {
+    values: Map<String, Value>,
+}
+
+impl LockedSettings {
+    /// Reads the locked settings. Nothing is locked if the file does not exist, or if it is
+    /// invalid, in which case an error is logged.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(LOCKED_SETTINGS_FILE);
+        let bytes = match fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!("Failed to read {}", path.display()))
+                );
+                return Self::default();
+            }
+        };
+        match Self::from_bytes(&bytes) {
+            Ok(locked) => {
+                if !locked.values.is_empty() {
+                    log::info!(
+                        "Settings locked by the administrator: {}",
+                        locked.paths().join(", ")
+                    );
+                }
+                locked
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!(
+                        "Ignoring invalid locked settings in {}",
+                        path.display()
+                    ))
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        let values = serde_json::from_slice(bytes)?;
+        let locked = LockedSettings { values };
+        // Make sure that the locked values can be applied, so that they never have to be ignored
+        // later
+        let mut settings = serde_json::to_value(Settings::default())?;
+        merge(&mut settings, &locked.values);
+        serde_json::from_value::<Settings>(settings)?;
+        Ok(locked)
+    }
+
+    /// Returns `settings` with the locked values in place of their own.
+    pub fn apply(&self, settings: Settings) -> Settings {
+        if self.values.is_empty() {
+            return settings;
+        }
+        let mut value = serde_json::to_value(&settings).expect("Failed to serialize settings");
+        merge(&mut value, &self.values);
+        match serde_json::from_value(value) {
+            Ok(locked_settings) => locked_settings,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to apply the locked settings")
+                );
+                settings
+            }
+        }
+    }
+
+    /// Returns the paths of the locked settings that have other values in `settings`.
+    pub fn violations(&self, settings: &Settings) -> Vec<String> {
+        let value = serde_json::to_value(settings).expect("Failed to serialize settings");
+        let mut violations = vec![];
+        for (path, locked_value) in leaves(&self.values) {
+            let mut current = Some(&value);
+            for key in &path {
+                current = current.and_then(|value| value.get(key));
+            }
+            if current != Some(locked_value) {
+                violations.push(path.join("."));
+            }
+        }
+        violations
+    }
+
+    /// Returns whether the setting at `path`, such as `tunnel_options.generic.enable_ipv6`, or
+    /// any part of it, is locked.
+    pub fn is_locked(&self, path: &str) -> bool {
+        leaves(&self.values).iter().any(|(locked_path, _)| {
+            let locked_path = locked_path.join(".");
+            locked_path == path
+                || locked_path.starts_with(&format!("{}.", path))
+                || path.starts_with(&format!("{}.", locked_path))
+        })
+    }
+
+    /// Returns the paths of all locked settings.
+    pub fn paths(&self) -> Vec<String> {
+        leaves(&self.values)
+            .into_iter()
+            .map(|(path, _)| path.join("."))
+            .collect()
+    }
+}
+
+/// Replaces the values in `target` with those in `values`. Objects are merged, and any other
+/// value replaces the existing one.
+fn merge(target: &mut Value, values: &Map<String, Value>) {
+    let target = match target {
+        Value::Object(target) => target,
+        target => {
+            *target = Value::Object(values.clone());
+            return;
+        }
+    };
+    for (key, value) in values {
+        match (target.get_mut(key), value) {
+            (Some(existing), Value::Object(values)) => merge(existing, values),
+            (_, value) => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Returns the path and value of every value in `values` that is not an object.
+fn leaves(values: &Map<String, Value>) -> Vec<(Vec<String>, &Value)> {
+    let mut result = vec![];
+    for (key, value) in values {
+        match value {
+            Value::Object(values) => {
+                for (mut path, value) in leaves(values) {
+                    path.insert(0, key.clone());
+                    result.push((path, value));
+                }
+            }
+            value => result.push((vec![key.clone()], value)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn locked() -> LockedSettings {
+        LockedSettings::from_bytes(
+            br#"{
+                "block_when_disconnected": true,
+                "tunnel_options": { "generic": { "enable_ipv6": false } }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut settings = Settings::default();
+        settings.block_when_disconnected = false;
+        settings.tunnel_options.generic.enable_ipv6 = true;
+        settings.allow_lan = true;
+
+        let settings = locked().apply(settings);
+        assert!(settings.block_when_disconnected);
+        assert!(!settings.tunnel_options.generic.enable_ipv6);
+        assert!(settings.allow_lan);
+        assert!(locked().violations(&settings).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut settings = locked().apply(Settings::default());
+        settings.block_when_disconnected = false;
+        assert_eq!(
+            locked().violations(&settings),
+            vec!["block_when_disconnected".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_is_locked() {
+        let locked = locked();
+        assert!(locked.is_locked("block_when_disconnected"));
+        assert!(locked.is_locked("tunnel_options"));
+        assert!(locked.is_locked("tunnel_options.generic.enable_ipv6"));
+        assert!(!locked.is_locked("tunnel_options.wireguard"));
+        assert!(!locked.is_locked("allow_lan"));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(LockedSettings::from_bytes(br#"{"block_when_disconnected": "yes"}"#).is_err());
+        assert!(LockedSettings::from_bytes(br#"[true]"#).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use fern::{
     colors::{Color, ColoredLevelConfig},
     Output,
 };
-use std::{fmt, io, path::PathBuf};
+use std::{collections::VecDeque, fmt, io, path::PathBuf, sync::Mutex};
 use talpid_core::logging::rotate_log;
 
 #[derive(err_derive::Error, Debug)]
@@ -62,6 +62,48 @@ const LINE_SEPARATOR: &str = "\r\n";
 
 const DATE_TIME_FORMAT_STR: &str = "[%Y-%m-%d %H:%M:%S%.3f]";
 
+/// Number of log lines that are kept in memory, so that the lines logged during a connection
+/// attempt can be retrieved without reading the log file.
+const RECENT_LINES_CAPACITY: usize = 1000;
+
+lazy_static::lazy_static! {
+    static ref RECENT_LINES: Mutex<RecentLines> = Mutex::new(RecentLines::default());
+}
+
+#[derive(Debug, Default)]
+struct RecentLines {
+    lines: VecDeque<String>,
+    /// Number of lines that have been logged in total.
+    total: u64,
+}
+
+impl RecentLines {
+    fn push(&mut self, line: String) {
+        if self.lines.len() == RECENT_LINES_CAPACITY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+        self.total += 1;
+    }
+
+    fn since(&self, position: u64) -> Vec<String> {
+        let count = self.total.saturating_sub(position);
+        let skip = self.lines.len().saturating_sub(count as usize);
+        self.lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// Returns the current position in the log, to be passed to `lines_since` later.
+pub fn log_position() -> u64 {
+    RECENT_LINES.lock().unwrap().total
+}
+
+/// Returns the lines that have been logged since `position`. Only the most recent lines are kept
+/// in memory, so older ones may be missing.
+pub fn lines_since(position: u64) -> Vec<String> {
+    RECENT_LINES.lock().unwrap().since(position)
+}
+
 pub fn init_logger(
     log_level: log::LevelFilter,
     log_file: Option<&PathBuf>,
@@ -87,6 +129,17 @@ pub fn init_logger(
         .chain(io::stdout());
     top_dispatcher = top_dispatcher.chain(stdout_dispatcher);
 
+    let recent_lines_formatter = Formatter {
+        output_timestamp: true,
+        output_color: false,
+    };
+    let recent_lines_dispatcher = fern::Dispatch::new()
+        .format(move |out, message, record| recent_lines_formatter.output_msg(out, message, record))
+        .chain(Output::call(|record| {
+            RECENT_LINES.lock().unwrap().push(record.args().to_string())
+        }));
+    top_dispatcher = top_dispatcher.chain(recent_lines_dispatcher);
+
     if let Some(ref log_file) = log_file {
         rotate_log(log_file).map_err(Error::RotateLog)?;
         let file_formatter = Formatter {
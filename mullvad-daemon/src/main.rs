@@ -5,9 +5,10 @@ use mullvad_daemon::{
     management_interface::{ManagementInterfaceEventBroadcaster, ManagementInterfaceServer},
     rpc_uniqueness_check,
     runtime::new_runtime_builder,
+    snapshot::Snapshot,
     version, Daemon, DaemonCommandChannel, DaemonCommandSender,
 };
-use std::{path::PathBuf, thread, time::Duration};
+use std::{fs, path::PathBuf, thread, time::Duration};
 use talpid_types::ErrorExt;
 
 mod cli;
@@ -95,7 +96,17 @@ async fn run_platform(config: &cli::Config, log_dir: Option<PathBuf>) -> Result<
     }
 }
 
-#[cfg(not(windows))]
+#[cfg(target_os = "linux")]
+async fn run_platform(config: &cli::Config, log_dir: Option<PathBuf>) -> Result<(), String> {
+    if config.initialize_firewall {
+        return mullvad_daemon::early_boot_firewall::initialize_firewall()
+            .await
+            .map_err(|e| e.display_chain());
+    }
+    run_standalone(log_dir).await
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
 async fn run_platform(_config: &cli::Config, log_dir: Option<PathBuf>) -> Result<(), String> {
     run_standalone(log_dir).await
 }
@@ -133,13 +144,32 @@ async fn create_daemon(
     log_dir: Option<PathBuf>,
 ) -> Result<Daemon<ManagementInterfaceEventBroadcaster>, String> {
     let resource_dir = mullvad_paths::get_resource_dir();
-    let settings_dir = mullvad_paths::settings_dir()
-        .map_err(|e| e.display_chain_with_msg("Unable to get settings dir"))?;
-    let cache_dir = mullvad_paths::cache_dir()
-        .map_err(|e| e.display_chain_with_msg("Unable to get cache dir"))?;
+    let (settings_dir, cache_dir, snapshot) = match &cli::get_config().load_snapshot {
+        Some(path) => {
+            let snapshot = Snapshot::load(path)
+                .await
+                .map_err(|e| e.display_chain_with_msg("Unable to load snapshot"))?;
+            let (settings_dir, cache_dir) = create_snapshot_dirs()
+                .map_err(|e| e.display_chain_with_msg("Unable to create snapshot directories"))?;
+            log::warn!(
+                "Running from a snapshot. Settings are stored in {}",
+                settings_dir.display()
+            );
+            (settings_dir, cache_dir, Some(snapshot))
+        }
+        None => {
+            let settings_dir = mullvad_paths::settings_dir()
+                .map_err(|e| e.display_chain_with_msg("Unable to get settings dir"))?;
+            let cache_dir = mullvad_paths::cache_dir()
+                .map_err(|e| e.display_chain_with_msg("Unable to get cache dir"))?;
+            (settings_dir, cache_dir, None)
+        }
+    };
 
     let command_channel = DaemonCommandChannel::new();
     let event_listener = spawn_management_interface(command_channel.sender()).await?;
+    #[cfg(target_os = "linux")]
+    spawn_dbus_interface(command_channel.sender(), &event_listener);
 
     Daemon::start(
         log_dir,
@@ -148,11 +178,26 @@ async fn create_daemon(
         cache_dir,
         event_listener,
         command_channel,
+        snapshot,
     )
     .await
     .map_err(|e| e.display_chain_with_msg("Unable to initialize daemon"))
 }
 
+/// Creates empty settings and cache directories for a daemon that runs from a snapshot, so that
+/// the state of this machine is neither used nor changed.
+fn create_snapshot_dirs() -> std::io::Result<(PathBuf, PathBuf)> {
+    let snapshot_dir = std::env::temp_dir().join("mullvad-snapshot");
+    if snapshot_dir.exists() {
+        fs::remove_dir_all(&snapshot_dir)?;
+    }
+    let settings_dir = snapshot_dir.join("settings");
+    let cache_dir = snapshot_dir.join("cache");
+    fs::create_dir_all(&settings_dir)?;
+    fs::create_dir_all(&cache_dir)?;
+    Ok((settings_dir, cache_dir))
+}
+
 async fn spawn_management_interface(
     command_sender: DaemonCommandSender,
 ) -> Result<ManagementInterfaceEventBroadcaster, String> {
@@ -167,6 +212,23 @@ async fn spawn_management_interface(
     Ok(event_broadcaster)
 }
 
+/// The D-Bus interface is optional, so the daemon runs without it if the system bus is missing.
+#[cfg(target_os = "linux")]
+fn spawn_dbus_interface(
+    command_sender: DaemonCommandSender,
+    event_broadcaster: &ManagementInterfaceEventBroadcaster,
+) {
+    use mullvad_daemon::dbus_interface::{DbusInterface, BUS_NAME};
+
+    match DbusInterface::start(command_sender, event_broadcaster) {
+        Ok(()) => log::info!("D-Bus interface available as {}", BUS_NAME),
+        Err(error) => log::warn!(
+            "{}",
+            error.display_chain_with_msg("Unable to start the D-Bus interface")
+        ),
+    }
+}
+
 #[cfg(unix)]
 fn running_as_admin() -> bool {
     let uid = unsafe { libc::getuid() };
@@ -1,22 +1,37 @@
-use crate::{account_history, device, settings, DaemonCommand, DaemonCommandSender, EventListener};
+use crate::{
+    account_history, circumvention, device, overrides, settings,
+    settings_history::{self, ChangeSource},
+    DaemonCommand, DaemonCommandSender, EventListener,
+};
 use futures::{
     channel::{mpsc, oneshot},
     StreamExt,
 };
+use ipnetwork::IpNetwork;
 use mullvad_api::{rest::Error as RestError, StatusCode};
 use mullvad_management_interface::{
     types::{self, daemon_event, management_service_server::ManagementService},
-    Code, Request, Response, Status,
+    Code, Request, Response, Status, INTERFACE_VERSION, MIN_SUPPORTED_INTERFACE_VERSION,
 };
 use mullvad_paths;
 #[cfg(not(target_os = "android"))]
 use mullvad_types::settings::DnsOptions;
 use mullvad_types::{
+    access_method::ApiAccessMethodSettings,
     account::AccountToken,
-    relay_constraints::{BridgeSettings, BridgeState, ObfuscationSettings, RelaySettingsUpdate},
-    relay_list::RelayList,
-    settings::Settings,
-    states::{TargetState, TunnelState},
+    custom_list::CustomList,
+    relay_constraints::{
+        BridgeSettings, BridgeState, ObfuscationSettings, RelaySettingsUpdate, SelectionStrategy,
+    },
+    relay_list::{
+        RelayList, RelayListMirror, RelayListUpdateInterval, RelayListUpdateIntervalError,
+        RelayOverride,
+    },
+    settings::{
+        AppExclusionSettings, AutoConnectScope, ExitRelayChangePolicy, ExpiryNotificationSettings,
+        Settings, TunnelHookSettings,
+    },
+    states::{DisconnectAction, TargetState, TunnelState},
     version,
     wireguard::{RotationInterval, RotationIntervalError},
 };
@@ -25,13 +40,22 @@ use parking_lot::RwLock;
 use std::path::PathBuf;
 use std::{
     cmp,
+    collections::VecDeque,
     convert::{TryFrom, TryInto},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
+};
+use talpid_types::{
+    net::{
+        wireguard::LivenessOptions, CaptivePortal, OfflineDetection, ReconnectDebounce, RetryPolicy,
+    },
+    ErrorExt,
 };
-use talpid_types::ErrorExt;
 use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
 
+/// Number of recent events that are kept, so that they can be replayed to new subscribers.
+const EVENT_REPLAY_BUFFER_SIZE: usize = 50;
+
 #[derive(err_derive::Error, Debug)]
 #[error(no_from)]
 pub enum Error {
@@ -42,7 +66,53 @@ pub enum Error {
 
 struct ManagementServiceImpl {
     daemon_tx: DaemonCommandSender,
-    subscriptions: Arc<RwLock<Vec<EventsListenerSender>>>,
+    subscriptions: Arc<RwLock<Subscriptions>>,
+}
+
+/// The subscribers of the event stream and the events that were most recently sent to them. Both
+/// are kept behind the same lock, so that an event cannot be missed by a subscriber that asks for
+/// a replay.
+#[derive(Default)]
+struct Subscriptions {
+    senders: Vec<EventsListenerSender>,
+    /// Recent events, oldest first. Relay lists are not kept, since they are large and the
+    /// current one can be requested instead.
+    recent_events: VecDeque<types::DaemonEvent>,
+    /// Sequence number of the last event that was sent.
+    last_sequence: u64,
+}
+
+impl Subscriptions {
+    fn subscribe(&mut self) -> EventsListenerReceiver {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.senders.push(tx);
+        UnboundedReceiverStream::new(rx)
+    }
+
+    /// Subscribes to the events that are sent from now on, after the buffered events with a
+    /// sequence number of at least `first_sequence`.
+    fn subscribe_with_replay(&mut self, first_sequence: u64) -> EventsListenerReceiver {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        for event in &self.recent_events {
+            if event.sequence >= first_sequence {
+                let _ = tx.send(Ok(event.clone()));
+            }
+        }
+        self.senders.push(tx);
+        UnboundedReceiverStream::new(rx)
+    }
+
+    fn send(&mut self, mut event: types::DaemonEvent) {
+        self.last_sequence += 1;
+        event.sequence = self.last_sequence;
+        self.senders.retain(|tx| tx.send(Ok(event.clone())).is_ok());
+        if !matches!(event.event, Some(daemon_event::Event::RelayList(_))) {
+            if self.recent_events.len() == EVENT_REPLAY_BUFFER_SIZE {
+                self.recent_events.pop_front();
+            }
+            self.recent_events.push_back(event);
+        }
+    }
 }
 
 pub type ServiceResult<T> = std::result::Result<Response<T>, Status>;
@@ -57,6 +127,7 @@ impl ManagementService for ManagementServiceImpl {
     type GetRelayLocationsStream = ReceiverStream<Result<types::RelayListCountry, Status>>;
     type GetSplitTunnelProcessesStream = UnboundedReceiverStream<Result<i32, Status>>;
     type EventsListenStream = EventsListenerReceiver;
+    type EventsReplayStream = EventsListenerReceiver;
 
     // Control and get the tunnel state
     //
@@ -73,20 +144,135 @@ impl ManagementService for ManagementServiceImpl {
     async fn disconnect_tunnel(&self, _: Request<()>) -> ServiceResult<bool> {
         log::debug!("disconnect_tunnel");
 
+        self.check_disconnect_warning(DisconnectAction::Disconnect)
+            .await?;
         let (tx, rx) = oneshot::channel();
         self.send_command_to_daemon(DaemonCommand::SetTargetState(tx, TargetState::Unsecured))?;
         let disconnect_issued = self.wait_for_result(rx).await?;
         Ok(Response::new(disconnect_issued))
     }
 
+    async fn pause_tunnel(&self, request: Request<types::Duration>) -> ServiceResult<bool> {
+        let duration = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative pause duration"))?;
+        let duration = validate_duration(duration, crate::MAX_TUNNEL_PAUSE_DURATION)?;
+        log::debug!("pause_tunnel({:?})", duration);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::PauseTunnel(tx, duration))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn get_tunnel_pause(&self, _: Request<()>) -> ServiceResult<types::TunnelPause> {
+        log::debug!("get_tunnel_pause");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetTunnelPause(tx))?;
+        let resume_at = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::TunnelPause {
+            resume_at: resume_at.map(types::Timestamp::from),
+        }))
+    }
+
+    async fn set_permissive_mode(&self, request: Request<types::Duration>) -> ServiceResult<()> {
+        let duration = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative duration"))?;
+        let duration = validate_duration(duration, crate::MAX_PERMISSIVE_MODE_DURATION)?;
+        log::debug!("set_permissive_mode({:?})", duration);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetPermissiveMode(tx, duration))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn end_permissive_mode(&self, _: Request<()>) -> ServiceResult<bool> {
+        log::debug!("end_permissive_mode");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::EndPermissiveMode(tx))?;
+        let was_active = self.wait_for_result(rx).await?;
+        Ok(Response::new(was_active))
+    }
+
+    async fn get_permissive_mode(&self, _: Request<()>) -> ServiceResult<types::PermissiveMode> {
+        log::debug!("get_permissive_mode");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetPermissiveMode(tx))?;
+        let ends_at = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::PermissiveMode {
+            ends_at: ends_at.map(types::Timestamp::from),
+        }))
+    }
+
+    async fn set_captive_portal(
+        &self,
+        request: Request<types::CaptivePortalAccess>,
+    ) -> ServiceResult<()> {
+        let request = request.into_inner();
+        let portal = CaptivePortal::try_from(request.portal.unwrap_or_default())
+            .map_err(map_protobuf_type_err)?;
+        let duration = request
+            .duration
+            .and_then(|duration| Duration::try_from(duration).ok())
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| Status::invalid_argument("invalid captive portal duration"))?;
+        let duration = validate_duration(duration, crate::MAX_CAPTIVE_PORTAL_DURATION)?;
+        log::debug!("set_captive_portal({}, {:?})", portal, duration);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetCaptivePortal(tx, portal, duration))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn end_captive_portal(&self, _: Request<()>) -> ServiceResult<bool> {
+        log::debug!("end_captive_portal");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::EndCaptivePortal(tx))?;
+        let was_active = self.wait_for_result(rx).await?;
+        Ok(Response::new(was_active))
+    }
+
+    async fn get_captive_portal(&self, _: Request<()>) -> ServiceResult<types::CaptivePortalMode> {
+        log::debug!("get_captive_portal");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetCaptivePortal(tx))?;
+        let mode = self.wait_for_result(rx).await?;
+        Ok(Response::new(match mode {
+            Some((portal, ends_at)) => types::CaptivePortalMode {
+                portal: Some(types::CaptivePortal::from(portal)),
+                ends_at: Some(types::Timestamp::from(ends_at)),
+            },
+            None => types::CaptivePortalMode::default(),
+        }))
+    }
+
     async fn reconnect_tunnel(&self, _: Request<()>) -> ServiceResult<bool> {
         log::debug!("reconnect_tunnel");
+        self.check_disconnect_warning(DisconnectAction::Reconnect)
+            .await?;
         let (tx, rx) = oneshot::channel();
         self.send_command_to_daemon(DaemonCommand::Reconnect(tx))?;
         let reconnect_issued = self.wait_for_result(rx).await?;
         Ok(Response::new(reconnect_issued))
     }
 
+    async fn confirm_disconnect(&self, _: Request<()>) -> ServiceResult<bool> {
+        log::debug!("confirm_disconnect");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ConfirmDisconnect(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     async fn get_tunnel_state(&self, _: Request<()>) -> ServiceResult<types::TunnelState> {
         log::debug!("get_tunnel_state");
         let (tx, rx) = oneshot::channel();
@@ -99,12 +285,20 @@ impl ManagementService for ManagementServiceImpl {
     //
 
     async fn events_listen(&self, _: Request<()>) -> ServiceResult<Self::EventsListenStream> {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        let mut subscriptions = self.subscriptions.write();
-        subscriptions.push(tx);
+        Ok(Response::new(self.subscriptions.write().subscribe()))
+    }
 
-        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    async fn events_replay(
+        &self,
+        request: Request<u64>,
+    ) -> ServiceResult<Self::EventsReplayStream> {
+        let first_sequence = request.into_inner();
+        log::debug!("events_replay({})", first_sequence);
+        Ok(Response::new(
+            self.subscriptions
+                .write()
+                .subscribe_with_replay(first_sequence),
+        ))
     }
 
     async fn prepare_restart(&self, _: Request<()>) -> ServiceResult<()> {
@@ -136,6 +330,205 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
+    async fn get_leftover_state(&self, _: Request<()>) -> ServiceResult<types::LeftoverState> {
+        log::debug!("get_leftover_state");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetLeftoverState(tx))?;
+        let leftover = self.wait_for_result(rx).await?;
+        Ok(Response::new(match leftover {
+            Some(state) => types::LeftoverState {
+                unclean_exit: true,
+                firewall_policy: state.firewall_policy,
+                dns: state.dns,
+                routes: state.routes,
+            },
+            None => types::LeftoverState::default(),
+        }))
+    }
+
+    async fn get_offline_status(&self, _: Request<()>) -> ServiceResult<types::OfflineStatus> {
+        log::debug!("get_offline_status");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetOfflineStatus(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::OfflineStatus::from)
+            .map(Response::new)
+    }
+
+    async fn get_api_stats(&self, _: Request<()>) -> ServiceResult<types::ApiStats> {
+        log::debug!("get_api_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetApiStats(tx))?;
+        let endpoints = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::ApiStats {
+            endpoints: endpoints
+                .into_iter()
+                .map(|(endpoint, stats)| types::ApiEndpointStats {
+                    endpoint,
+                    requests: stats.requests,
+                    failures: stats.failures,
+                    error_responses: stats.error_responses,
+                    rate_limited: stats.rate_limited,
+                    access_methods: stats
+                        .access_methods
+                        .into_iter()
+                        .map(|(access_method, requests)| types::ApiAccessMethodCount {
+                            access_method,
+                            requests,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn check_network_interference(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::InterferenceReport> {
+        log::debug!("check_network_interference");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CheckNetworkInterference(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::InterferenceReport::from)
+            .map(Response::new)
+    }
+
+    async fn get_circumvention_changes(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::CircumventionChanges> {
+        log::debug!("get_circumvention_changes");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetCircumventionChanges(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(convert_circumvention_changes)
+            .map(Response::new)
+    }
+
+    async fn revert_circumvention_changes(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::CircumventionChanges> {
+        log::debug!("revert_circumvention_changes");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RevertCircumventionChanges(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(convert_circumvention_changes)
+            .map(Response::new)
+    }
+
+    async fn get_last_connection_failure(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::LastConnectionFailure> {
+        log::debug!("get_last_connection_failure");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetLastConnectionFailure(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|capsule| types::LastConnectionFailure {
+                capsule: capsule.map(types::FailureCapsule::from),
+            })
+            .map(Response::new)
+    }
+
+    async fn get_relay_stats(&self, _: Request<()>) -> ServiceResult<types::RelayStatsList> {
+        log::debug!("get_relay_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayStats(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|stats| types::RelayStatsList {
+                relays: stats.into_iter().map(types::RelayStats::from).collect(),
+            })
+            .map(Response::new)
+    }
+
+    async fn get_data_usage(&self, _: Request<()>) -> ServiceResult<types::DataUsage> {
+        log::debug!("get_data_usage");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDataUsage(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::DataUsage::from)
+            .map(Response::new)
+    }
+
+    async fn get_dns_query_log(&self, request: Request<u64>) -> ServiceResult<types::DnsQueryLog> {
+        log::debug!("get_dns_query_log");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDnsQueryLog(tx, request.into_inner()))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|entries| types::DnsQueryLog {
+                entries: entries
+                    .into_iter()
+                    .map(types::DnsQueryLogEntry::from)
+                    .collect(),
+            })
+            .map(Response::new)
+    }
+
+    async fn clear_relay_stats(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_relay_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ClearRelayStats(tx))?;
+        self.wait_for_result(rx).await.map(Response::new)
+    }
+
+    async fn get_snapshot(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("get_snapshot");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSnapshot(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|snapshot| Response::new(snapshot.to_json()))
+    }
+
+    async fn check_leaks(&self, _: Request<()>) -> ServiceResult<types::LeakReport> {
+        log::debug!("check_leaks");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CheckLeaks(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::LeakReport::from)
+            .map(Response::new)
+    }
+
+    async fn probe_protocols(&self, _: Request<()>) -> ServiceResult<types::ProbeReport> {
+        log::debug!("probe_protocols");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ProbeProtocols(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(types::ProbeReport::from)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn negotiate_interface_version(
+        &self,
+        request: Request<types::InterfaceVersion>,
+    ) -> ServiceResult<types::SupportedInterfaceVersions> {
+        let client_version = request.into_inner().version;
+        log::debug!("negotiate_interface_version({})", client_version);
+        if !(MIN_SUPPORTED_INTERFACE_VERSION..=INTERFACE_VERSION).contains(&client_version) {
+            log::warn!(
+                "Client uses unsupported management interface version {}",
+                client_version
+            );
+        }
+        Ok(Response::new(types::SupportedInterfaceVersions {
+            min_version: MIN_SUPPORTED_INTERFACE_VERSION,
+            max_version: INTERFACE_VERSION,
+        }))
+    }
+
     async fn get_current_version(&self, _: Request<()>) -> ServiceResult<String> {
         log::debug!("get_current_version");
         let (tx, rx) = oneshot::channel();
@@ -172,6 +565,101 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn update_relay_locations_and_wait(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("update_relay_locations_and_wait");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::UpdateRelayLocationsAndWait(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_relay_list_info(&self, _: Request<()>) -> ServiceResult<types::RelayListInfo> {
+        log::debug!("get_relay_list_info");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayListInfo(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::RelayListInfo::from)
+            .map(Response::new)
+    }
+
+    async fn set_relay_list_update_interval(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<()> {
+        let interval: RelayListUpdateInterval = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative update interval"))?
+            .try_into()
+            .map_err(|error: RelayListUpdateIntervalError| {
+                Status::invalid_argument(error.to_string())
+            })?;
+
+        log::debug!("set_relay_list_update_interval({:?})", interval);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelayListUpdateInterval(tx, interval))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_relay_list_mirror(
+        &self,
+        request: Request<types::RelayListMirror>,
+    ) -> ServiceResult<()> {
+        let mirror =
+            RelayListMirror::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_relay_list_mirror({})", mirror.url);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelayListMirror(tx, Some(mirror)))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn clear_relay_list_mirror(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_relay_list_mirror");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelayListMirror(tx, None))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn import_relay_overrides(
+        &self,
+        request: Request<types::RelayOverrideList>,
+    ) -> ServiceResult<()> {
+        let overrides = request
+            .into_inner()
+            .overrides
+            .into_iter()
+            .map(RelayOverride::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("import_relay_overrides({} overrides)", overrides.len());
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportRelayOverrides(tx, overrides))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn clear_all_relay_overrides(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_all_relay_overrides");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ClearAllRelayOverrides(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
     async fn update_relay_settings(
         &self,
         request: Request<types::RelaySettingsUpdate>,
@@ -236,170 +724,760 @@ impl ManagementService for ManagementServiceImpl {
     ) -> ServiceResult<()> {
         let settings =
             BridgeSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        if let BridgeSettings::Custom(proxy) = &settings {
+            talpid_types::net::openvpn::validate_proxy_settings(proxy)
+                .map_err(Status::invalid_argument)?;
+        }
 
         log::debug!("set_bridge_settings({:?})", settings);
 
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetBridgeSettings(tx, settings))?;
-        let settings_result = self.wait_for_result(rx).await?;
-        settings_result
+        self.send_command_to_daemon(DaemonCommand::SetBridgeSettings(tx, settings))?;
+        let settings_result = self.wait_for_result(rx).await?;
+        settings_result
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_obfuscation_settings(
+        &self,
+        request: Request<types::ObfuscationSettings>,
+    ) -> ServiceResult<()> {
+        let settings =
+            ObfuscationSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_obfuscation_settings({:?})", settings);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetObfuscationSettings(tx, settings))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_supported_obfuscation_transports(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::ObfuscationTransportList> {
+        log::debug!("get_supported_obfuscation_transports");
+        let transports = tunnel_obfuscation::transports()
+            .iter()
+            .map(|transport| types::ObfuscationTransport {
+                name: transport.name().to_owned(),
+                description: transport.description().to_owned(),
+            })
+            .collect();
+        Ok(Response::new(types::ObfuscationTransportList {
+            transports,
+        }))
+    }
+
+    async fn set_bridge_state(&self, request: Request<types::BridgeState>) -> ServiceResult<()> {
+        let bridge_state =
+            BridgeState::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+
+        log::debug!("set_bridge_state({:?})", bridge_state);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetBridgeState(tx, bridge_state))?;
+        let settings_result = self.wait_for_result(rx).await?;
+        settings_result
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    // Settings
+    //
+
+    async fn get_settings(&self, _: Request<()>) -> ServiceResult<types::Settings> {
+        log::debug!("get_settings");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettings(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|settings| Response::new(types::Settings::from(&settings)))
+    }
+
+    async fn set_allow_lan(&self, request: Request<bool>) -> ServiceResult<()> {
+        let allow_lan = request.into_inner();
+        log::debug!("set_allow_lan({})", allow_lan);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAllowLan(tx, allow_lan))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_allow_multicast(&self, request: Request<bool>) -> ServiceResult<()> {
+        let allow_multicast = request.into_inner();
+        log::debug!("set_allow_multicast({})", allow_multicast);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAllowMulticast(tx, allow_multicast))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_allowed_lan_nets(
+        &self,
+        request: Request<types::AllowedLanNets>,
+    ) -> ServiceResult<()> {
+        let networks = request.into_inner().networks;
+        let allowed_lan_nets = if networks.is_empty() {
+            None
+        } else {
+            let nets = networks
+                .iter()
+                .map(|network| network.parse::<IpNetwork>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| Status::invalid_argument("invalid network"))?;
+            Some(nets)
+        };
+        log::debug!("set_allowed_lan_nets({:?})", allowed_lan_nets);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAllowedLanNets(tx, allowed_lan_nets))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_firewall_exceptions(
+        &self,
+        request: Request<types::FirewallExceptions>,
+    ) -> ServiceResult<()> {
+        let exceptions = request
+            .into_inner()
+            .exceptions
+            .into_iter()
+            .map(talpid_types::net::FirewallException::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_firewall_exceptions({:?})", exceptions);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetFirewallExceptions(tx, exceptions))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_inbound_tunnel_ports(
+        &self,
+        request: Request<types::InboundTunnelPorts>,
+    ) -> ServiceResult<()> {
+        let ports = request
+            .into_inner()
+            .ports
+            .into_iter()
+            .map(talpid_types::net::InboundTunnelPort::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_inbound_tunnel_ports({:?})", ports);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetInboundTunnelPorts(tx, ports))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_show_beta_releases(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enabled = request.into_inner();
+        log::debug!("set_show_beta_releases({})", enabled);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetShowBetaReleases(tx, enabled))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_block_when_disconnected(&self, request: Request<bool>) -> ServiceResult<()> {
+        let block_when_disconnected = request.into_inner();
+        log::debug!("set_block_when_disconnected({})", block_when_disconnected);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetBlockWhenDisconnected(
+            tx,
+            block_when_disconnected,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_strict_enforcement(&self, request: Request<bool>) -> ServiceResult<()> {
+        let strict_enforcement = request.into_inner();
+        log::debug!("set_strict_enforcement({})", strict_enforcement);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetStrictEnforcement(tx, strict_enforcement))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_auto_connect(&self, request: Request<bool>) -> ServiceResult<()> {
+        let auto_connect = request.into_inner();
+        log::debug!("set_auto_connect({})", auto_connect);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAutoConnect(tx, auto_connect))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_auto_connect_scope(
+        &self,
+        request: Request<types::AutoConnectScope>,
+    ) -> ServiceResult<()> {
+        let scope =
+            AutoConnectScope::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_auto_connect_scope({})", scope);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAutoConnectScope(tx, scope))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_exit_relay_change_policy(
+        &self,
+        request: Request<types::ExitRelayChangePolicy>,
+    ) -> ServiceResult<()> {
+        let policy =
+            ExitRelayChangePolicy::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_exit_relay_change_policy({})", policy);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExitRelayChangePolicy(tx, policy))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_relay_selection_strategy(
+        &self,
+        request: Request<types::RelaySelectionStrategy>,
+    ) -> ServiceResult<()> {
+        let strategy =
+            SelectionStrategy::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_relay_selection_strategy({})", strategy);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelaySelectionStrategy(tx, strategy))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_relay_failure_cooldown(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<()> {
+        let cooldown = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative cooldown"))?;
+        log::debug!("set_relay_failure_cooldown({:?})", cooldown);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelayFailureCooldown(tx, cooldown))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_expiry_notifications(
+        &self,
+        request: Request<types::ExpiryNotificationSettings>,
+    ) -> ServiceResult<()> {
+        let expiry_notifications = ExpiryNotificationSettings::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_expiry_notifications({:?})", expiry_notifications);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExpiryNotifications(
+            tx,
+            expiry_notifications,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_tunnel_hooks(
+        &self,
+        request: Request<types::TunnelHookSettings>,
+    ) -> ServiceResult<()> {
+        let tunnel_hooks =
+            TunnelHookSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_tunnel_hooks({:?})", tunnel_hooks);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetTunnelHooks(tx, tunnel_hooks))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn set_disconnect_warning_threshold(&self, request: Request<u64>) -> ServiceResult<()> {
+        let threshold = request.into_inner();
+        let threshold = if threshold != 0 {
+            Some(threshold)
+        } else {
+            None
+        };
+        log::debug!("set_disconnect_warning_threshold({:?})", threshold);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetDisconnectWarningThreshold(tx, threshold))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_reconnect_debounce(
+        &self,
+        request: Request<types::ReconnectDebounce>,
+    ) -> ServiceResult<()> {
+        let reconnect_debounce =
+            ReconnectDebounce::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_reconnect_debounce({:?})", reconnect_debounce);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetReconnectDebounce(tx, reconnect_debounce))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_offline_detection(
+        &self,
+        request: Request<types::OfflineDetection>,
+    ) -> ServiceResult<()> {
+        let offline_detection =
+            OfflineDetection::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_offline_detection({:?})", offline_detection);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetOfflineDetection(tx, offline_detection))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_openvpn_mssfix(&self, request: Request<u32>) -> ServiceResult<()> {
+        let mssfix = request.into_inner();
+        let mssfix = if mssfix != 0 {
+            Some(mssfix as u16)
+        } else {
+            None
+        };
+        log::debug!("set_openvpn_mssfix({:?})", mssfix);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetOpenVpnMssfix(tx, mssfix))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_wireguard_mtu(&self, request: Request<u32>) -> ServiceResult<()> {
+        let mtu = request.into_inner();
+        let mtu = if mtu != 0 {
+            let mtu = u16::try_from(mtu).unwrap_or(u16::MAX);
+            Some(
+                mullvad_types::wireguard::validate_mtu(mtu)
+                    .map_err(|error| Status::invalid_argument(error.to_string()))?,
+            )
+        } else {
+            None
+        };
+        log::debug!("set_wireguard_mtu({:?})", mtu);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardMtu(tx, mtu))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_wireguard_persistent_keepalive(&self, request: Request<u32>) -> ServiceResult<()> {
+        let interval = u16::try_from(request.into_inner()).unwrap_or(u16::MAX);
+        let interval = mullvad_types::wireguard::validate_persistent_keepalive(interval)
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+        log::debug!("set_wireguard_persistent_keepalive({})", interval);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardPersistentKeepalive(tx, interval))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_wireguard_liveness(
+        &self,
+        request: Request<types::LivenessOptions>,
+    ) -> ServiceResult<()> {
+        let liveness =
+            LivenessOptions::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_wireguard_liveness({:?})", liveness);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardLiveness(tx, liveness))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_wireguard_namespace(&self, request: Request<String>) -> ServiceResult<()> {
+        let namespace = request.into_inner();
+        log::debug!("set_wireguard_namespace({:?})", namespace);
+        let namespace = if namespace.is_empty() {
+            None
+        } else if talpid_types::net::wireguard::is_valid_namespace_name(&namespace) {
+            Some(namespace)
+        } else {
+            return Err(Status::invalid_argument("invalid network namespace name"));
+        };
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardNamespace(tx, namespace))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_wireguard_namespace(&self, _: Request<String>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn set_wireguard_allowed_ips(
+        &self,
+        request: Request<types::WireguardAllowedIps>,
+    ) -> ServiceResult<()> {
+        let networks = request.into_inner().networks;
+        let allowed_ips = if networks.is_empty() {
+            None
+        } else {
+            let mut nets = Vec::with_capacity(networks.len());
+            for network in networks {
+                let net: IpNetwork = network
+                    .parse()
+                    .map_err(|_| Status::invalid_argument("invalid network"))?;
+                nets.push(net);
+            }
+            Some(nets)
+        };
+        log::debug!("set_wireguard_allowed_ips({:?})", allowed_ips);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardAllowedIps(tx, allowed_ips))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(target_os = "android")]
+    async fn set_wireguard_allowed_ips(
+        &self,
+        _: Request<types::WireguardAllowedIps>,
+    ) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_rate_limit(&self, request: Request<u64>) -> ServiceResult<()> {
+        let rate_limit = Some(request.into_inner()).filter(|rate| *rate > 0);
+        log::debug!("set_rate_limit({:?})", rate_limit);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRateLimit(tx, rate_limit))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_rate_limit(&self, _: Request<u64>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    async fn set_enable_ipv6(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enable_ipv6 = request.into_inner();
+        log::debug!("set_enable_ipv6({})", enable_ipv6);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetEnableIpv6(tx, enable_ipv6))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
+    async fn set_retry_policy(&self, request: Request<types::RetryPolicy>) -> ServiceResult<()> {
+        let retry_policy =
+            RetryPolicy::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_retry_policy({:?})", retry_policy);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRetryPolicy(tx, retry_policy))?;
+        self.wait_for_result(rx)
+            .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_obfuscation_settings(
-        &self,
-        request: Request<types::ObfuscationSettings>,
-    ) -> ServiceResult<()> {
-        let settings =
-            ObfuscationSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
-        log::debug!("set_obfuscation_settings({:?})", settings);
+    async fn set_quantum_resistant_tunnel(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enable = request.into_inner();
+        log::debug!("set_quantum_resistant_tunnel({})", enable);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetObfuscationSettings(tx, settings))?;
-        let settings_result = self.wait_for_result(rx).await?;
-        settings_result
+        self.send_command_to_daemon(DaemonCommand::SetQuantumResistantTunnel(tx, enable))?;
+        self.wait_for_result(rx)
+            .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_bridge_state(&self, request: Request<types::BridgeState>) -> ServiceResult<()> {
-        let bridge_state =
-            BridgeState::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+    #[cfg(not(target_os = "android"))]
+    async fn set_dns_options(&self, request: Request<types::DnsOptions>) -> ServiceResult<()> {
+        let options = DnsOptions::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_dns_options({:?})", options);
 
-        log::debug!("set_bridge_state({:?})", bridge_state);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetBridgeState(tx, bridge_state))?;
-        let settings_result = self.wait_for_result(rx).await?;
-        settings_result
+        self.send_command_to_daemon(DaemonCommand::SetDnsOptions(tx, options))?;
+        self.wait_for_result(rx)
+            .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    // Settings
-    //
+    #[cfg(target_os = "android")]
+    async fn set_dns_options(&self, _: Request<types::DnsOptions>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    async fn set_api_access_methods(
+        &self,
+        request: Request<types::ApiAccessMethods>,
+    ) -> ServiceResult<()> {
+        let access_methods = ApiAccessMethodSettings::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        // The methods may contain passwords, so only their names are logged
+        log::debug!(
+            "set_api_access_methods({:?})",
+            access_methods
+                .methods
+                .iter()
+                .map(|method| method.name.as_str())
+                .collect::<Vec<_>>()
+        );
 
-    async fn get_settings(&self, _: Request<()>) -> ServiceResult<types::Settings> {
-        log::debug!("get_settings");
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::GetSettings(tx))?;
+        self.send_command_to_daemon(DaemonCommand::SetApiAccessMethods(tx, access_methods))?;
         self.wait_for_result(rx)
-            .await
-            .map(|settings| Response::new(types::Settings::from(&settings)))
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
     }
 
-    async fn set_allow_lan(&self, request: Request<bool>) -> ServiceResult<()> {
-        let allow_lan = request.into_inner();
-        log::debug!("set_allow_lan({})", allow_lan);
+    async fn set_circumvention_assistant(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enabled = request.into_inner();
+        log::debug!("set_circumvention_assistant({})", enabled);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetAllowLan(tx, allow_lan))?;
+        self.send_command_to_daemon(DaemonCommand::SetCircumventionAssistant(tx, enabled))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_show_beta_releases(&self, request: Request<bool>) -> ServiceResult<()> {
+    async fn set_avoid_bad_relays(&self, request: Request<bool>) -> ServiceResult<()> {
         let enabled = request.into_inner();
-        log::debug!("set_show_beta_releases({})", enabled);
+        log::debug!("set_avoid_bad_relays({})", enabled);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetShowBetaReleases(tx, enabled))?;
+        self.send_command_to_daemon(DaemonCommand::SetAvoidBadRelays(tx, enabled))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_block_when_disconnected(&self, request: Request<bool>) -> ServiceResult<()> {
-        let block_when_disconnected = request.into_inner();
-        log::debug!("set_block_when_disconnected({})", block_when_disconnected);
+    async fn create_custom_list(&self, request: Request<types::CustomList>) -> ServiceResult<()> {
+        let list = CustomList::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("create_custom_list({})", list.name);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetBlockWhenDisconnected(
-            tx,
-            block_when_disconnected,
-        ))?;
+        self.send_command_to_daemon(DaemonCommand::CreateCustomList(tx, list))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_auto_connect(&self, request: Request<bool>) -> ServiceResult<()> {
-        let auto_connect = request.into_inner();
-        log::debug!("set_auto_connect({})", auto_connect);
+    async fn update_custom_list(&self, request: Request<types::CustomList>) -> ServiceResult<()> {
+        let list = CustomList::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("update_custom_list({})", list.name);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetAutoConnect(tx, auto_connect))?;
+        self.send_command_to_daemon(DaemonCommand::UpdateCustomList(tx, list))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_openvpn_mssfix(&self, request: Request<u32>) -> ServiceResult<()> {
-        let mssfix = request.into_inner();
-        let mssfix = if mssfix != 0 {
-            Some(mssfix as u16)
-        } else {
-            None
-        };
-        log::debug!("set_openvpn_mssfix({:?})", mssfix);
+    async fn delete_custom_list(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("delete_custom_list({})", name);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetOpenVpnMssfix(tx, mssfix))?;
+        self.send_command_to_daemon(DaemonCommand::DeleteCustomList(tx, name))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
             .map_err(map_settings_error)
     }
 
-    async fn set_wireguard_mtu(&self, request: Request<u32>) -> ServiceResult<()> {
-        let mtu = request.into_inner();
-        let mtu = if mtu != 0 { Some(mtu as u16) } else { None };
-        log::debug!("set_wireguard_mtu({:?})", mtu);
+    async fn set_setting_override(
+        &self,
+        request: Request<types::SettingOverride>,
+    ) -> ServiceResult<()> {
+        let request = request.into_inner();
+        let value = overrides::SettingValue::parse(&request.setting, &request.value)
+            .map_err(map_setting_override_error)?;
+        let duration = request
+            .duration
+            .and_then(|duration| Duration::try_from(duration).ok())
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| Status::invalid_argument("invalid override duration"))?;
+        let duration = validate_duration(duration, crate::MAX_SETTING_OVERRIDE_DURATION)?;
+        log::debug!(
+            "set_setting_override({}, {}, {:?}, {})",
+            request.setting,
+            value,
+            duration,
+            request.revert_on_reconnect
+        );
+
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetWireguardMtu(tx, mtu))?;
+        self.send_command_to_daemon(DaemonCommand::SetSettingOverride(
+            tx,
+            value,
+            duration,
+            request.revert_on_reconnect,
+        ))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
-            .map_err(map_settings_error)
+            .map_err(map_daemon_error)
     }
 
-    async fn set_enable_ipv6(&self, request: Request<bool>) -> ServiceResult<()> {
-        let enable_ipv6 = request.into_inner();
-        log::debug!("set_enable_ipv6({})", enable_ipv6);
+    async fn clear_setting_override(&self, request: Request<String>) -> ServiceResult<()> {
+        let setting = request.into_inner();
+        log::debug!("clear_setting_override({})", setting);
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetEnableIpv6(tx, enable_ipv6))?;
+        self.send_command_to_daemon(DaemonCommand::ClearSettingOverride(tx, setting))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
-            .map_err(map_settings_error)
+            .map_err(map_daemon_error)
     }
 
-    async fn set_quantum_resistant_tunnel(&self, request: Request<bool>) -> ServiceResult<()> {
-        let enable = request.into_inner();
-        log::debug!("set_quantum_resistant_tunnel({})", enable);
+    async fn get_setting_overrides(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::SettingOverrides> {
+        log::debug!("get_setting_overrides");
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetQuantumResistantTunnel(tx, enable))?;
+        self.send_command_to_daemon(DaemonCommand::GetSettingOverrides(tx))?;
+        let overrides = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingOverrides {
+            overrides: overrides
+                .into_iter()
+                .map(|setting_override| types::ActiveSettingOverride {
+                    setting: setting_override.value.path().to_owned(),
+                    value: setting_override.value.to_string(),
+                    restore_value: setting_override.restore.to_string(),
+                    revert_at: Some(types::Timestamp::from(setting_override.revert_at)),
+                    revert_on_reconnect: setting_override.revert_on_reconnect,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_settings_history(&self, _: Request<()>) -> ServiceResult<types::SettingsHistory> {
+        log::debug!("get_settings_history");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsHistory(tx))?;
+        let entries = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingsHistory {
+            entries: entries
+                .into_iter()
+                .map(|entry| {
+                    use types::settings_history_entry::Source;
+                    let (source, rolled_back_id) = match entry.source {
+                        ChangeSource::Client => (Source::Client, 0),
+                        ChangeSource::SettingsFile => (Source::SettingsFile, 0),
+                        ChangeSource::Rollback(id) => (Source::Rollback, id),
+                        ChangeSource::Reset => (Source::Reset, 0),
+                    };
+                    types::SettingsHistoryEntry {
+                        id: entry.id,
+                        time: Some(types::Timestamp::from(SystemTime::from(entry.time))),
+                        source: i32::from(source),
+                        rolled_back_id,
+                        changes: entry
+                            .changes
+                            .into_iter()
+                            .map(|change| types::SettingChange {
+                                path: change.path.join("."),
+                                old_value: change.old_value.to_string(),
+                                new_value: change.new_value.to_string(),
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }))
+    }
+
+    async fn rollback_settings(&self, request: Request<u64>) -> ServiceResult<()> {
+        let id = request.into_inner();
+        log::debug!("rollback_settings({})", id);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RollbackSettings(tx, id))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
-            .map_err(map_settings_error)
+            .map_err(map_daemon_error)
     }
 
-    #[cfg(not(target_os = "android"))]
-    async fn set_dns_options(&self, request: Request<types::DnsOptions>) -> ServiceResult<()> {
-        let options = DnsOptions::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
-        log::debug!("set_dns_options({:?})", options);
-
+    async fn apply_settings_patch(&self, request: Request<String>) -> ServiceResult<()> {
+        let patch = request.into_inner();
+        log::debug!("apply_settings_patch");
         let (tx, rx) = oneshot::channel();
-        self.send_command_to_daemon(DaemonCommand::SetDnsOptions(tx, options))?;
+        self.send_command_to_daemon(DaemonCommand::ApplySettingsPatch(tx, patch))?;
         self.wait_for_result(rx)
             .await?
             .map(Response::new)
-            .map_err(map_settings_error)
+            .map_err(map_daemon_error)
     }
 
-    #[cfg(target_os = "android")]
-    async fn set_dns_options(&self, _: Request<types::DnsOptions>) -> ServiceResult<()> {
-        Ok(Response::new(()))
+    async fn get_settings_encryption(&self, _: Request<()>) -> ServiceResult<bool> {
+        log::debug!("get_settings_encryption");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsEncryption(tx))?;
+        let encrypted = self.wait_for_result(rx).await?;
+        Ok(Response::new(encrypted))
+    }
+
+    async fn set_settings_encryption(&self, request: Request<bool>) -> ServiceResult<()> {
+        let enabled = request.into_inner();
+        log::debug!("set_settings_encryption({})", enabled);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSettingsEncryption(tx, enabled))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
     }
 
     // Account management
@@ -426,6 +1504,33 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn start_web_login(&self, _: Request<()>) -> ServiceResult<types::LoginLink> {
+        log::debug!("start_web_login");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::StartWebLogin(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(|link| {
+                Response::new(types::LoginLink {
+                    code: link.code,
+                    url: link.url,
+                    expiry: Some(types::Timestamp {
+                        seconds: link.expiry.timestamp(),
+                        nanos: 0,
+                    }),
+                })
+            })
+            .map_err(map_daemon_error)
+    }
+
+    async fn cancel_web_login(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("cancel_web_login");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CancelWebLogin(tx))?;
+        self.wait_for_result(rx).await?;
+        Ok(Response::new(()))
+    }
+
     async fn logout_account(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("logout_account");
         let (tx, rx) = oneshot::channel();
@@ -671,6 +1776,22 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    #[cfg(target_os = "linux")]
+    async fn add_split_tunnel_process_tree(&self, request: Request<i32>) -> ServiceResult<()> {
+        let pid = request.into_inner();
+        log::debug!("add_split_tunnel_process_tree");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddSplitTunnelProcessTree(tx, pid))?;
+        self.wait_for_result(rx)
+            .await?
+            .map_err(|error| Status::failed_precondition(error.to_string()))?;
+        Ok(Response::new(()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn add_split_tunnel_process_tree(&self, _: Request<i32>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
     #[cfg(target_os = "linux")]
     async fn remove_split_tunnel_process(&self, request: Request<i32>) -> ServiceResult<()> {
         let pid = request.into_inner();
@@ -819,6 +1940,79 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    #[cfg(windows)]
+    async fn set_link_layer_exemptions(
+        &self,
+        request: Request<types::LinkLayerExemptions>,
+    ) -> ServiceResult<()> {
+        log::debug!("set_link_layer_exemptions");
+        let exemptions = talpid_types::net::LinkLayerExemptions::from(request.into_inner());
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetLinkLayerExemptions(tx, exemptions))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(not(windows))]
+    async fn set_link_layer_exemptions(
+        &self,
+        _: Request<types::LinkLayerExemptions>,
+    ) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_dns_backend(&self, request: Request<types::DnsBackend>) -> ServiceResult<()> {
+        log::debug!("set_dns_backend");
+        let backend = talpid_types::net::DnsBackend::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetDnsBackend(tx, backend))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_dns_backend(&self, _: Request<types::DnsBackend>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_policy_routing(
+        &self,
+        request: Request<types::PolicyRouting>,
+    ) -> ServiceResult<()> {
+        let routing = talpid_types::net::PolicyRouting::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_policy_routing({})", routing);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetPolicyRouting(tx, routing))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_policy_routing(&self, _: Request<types::PolicyRouting>) -> ServiceResult<()> {
+        Ok(Response::new(()))
+    }
+
+    async fn set_app_exclusions(
+        &self,
+        request: Request<types::AppExclusionSettings>,
+    ) -> ServiceResult<()> {
+        log::debug!("set_app_exclusions");
+        let app_exclusions = AppExclusionSettings::from(request.into_inner());
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetAppExclusions(tx, app_exclusions))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_settings_error)
+    }
+
     #[cfg(windows)]
     async fn check_volumes(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("check_volumes");
@@ -847,6 +2041,13 @@ impl ManagementServiceImpl {
     async fn wait_for_result<T>(&self, rx: oneshot::Receiver<T>) -> Result<T, Status> {
         rx.await.map_err(|_| Status::internal("sender was dropped"))
     }
+
+    /// Fails with `FAILED_PRECONDITION` if `action` must be confirmed because the tunnel is busy.
+    async fn check_disconnect_warning(&self, action: DisconnectAction) -> Result<(), Status> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CheckDisconnectWarning(tx, action))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)
+    }
 }
 
 pub struct ManagementInterfaceServer(());
@@ -855,7 +2056,7 @@ impl ManagementInterfaceServer {
     pub async fn start(
         tunnel_tx: DaemonCommandSender,
     ) -> Result<(String, ManagementInterfaceEventBroadcaster), Error> {
-        let subscriptions = Arc::<RwLock<Vec<EventsListenerSender>>>::default();
+        let subscriptions = Arc::<RwLock<Subscriptions>>::default();
 
         let socket_path = mullvad_paths::get_rpc_socket_path()
             .to_string_lossy()
@@ -892,7 +2093,7 @@ impl ManagementInterfaceServer {
 /// A handle that allows broadcasting messages to all subscribers of the management interface.
 #[derive(Clone)]
 pub struct ManagementInterfaceEventBroadcaster {
-    subscriptions: Arc<RwLock<Vec<EventsListenerSender>>>,
+    subscriptions: Arc<RwLock<Subscriptions>>,
     _close_handle: mpsc::Sender<()>,
 }
 
@@ -900,6 +2101,7 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
     /// Sends a new state update to all `new_state` subscribers of the management interface.
     fn notify_new_state(&self, new_state: TunnelState) {
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::TunnelState(types::TunnelState::from(
                 new_state,
             ))),
@@ -910,6 +2112,7 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
     fn notify_settings(&self, settings: Settings) {
         log::debug!("Broadcasting new settings");
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::Settings(types::Settings::from(
                 &settings,
             ))),
@@ -929,6 +2132,7 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
                 .push(types::RelayListCountry::from(country));
         }
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::RelayList(new_list)),
         })
     }
@@ -936,6 +2140,7 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
     fn notify_app_version(&self, app_version_info: version::AppVersionInfo) {
         log::debug!("Broadcasting new app version info");
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::VersionInfo(
                 types::AppVersionInfo::from(app_version_info),
             )),
@@ -945,6 +2150,7 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
     fn notify_device_event(&self, device: mullvad_types::device::DeviceEvent) {
         log::debug!("Broadcasting device event");
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::Device(types::DeviceEvent::from(
                 device,
             ))),
@@ -954,19 +2160,93 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
     fn notify_remove_device_event(&self, remove_event: mullvad_types::device::RemoveDeviceEvent) {
         log::debug!("Broadcasting remove device event");
         self.notify(types::DaemonEvent {
+            sequence: 0,
             event: Some(daemon_event::Event::RemoveDevice(
                 types::RemoveDeviceEvent::from(remove_event),
             )),
         })
     }
+
+    fn notify_exit_relay_change(&self, event: mullvad_types::relay_list::ExitRelayChangeEvent) {
+        log::debug!("Broadcasting exit relay change event");
+        self.notify(types::DaemonEvent {
+            sequence: 0,
+            event: Some(daemon_event::Event::ExitRelayChange(
+                types::ExitRelayChangeEvent::from(event),
+            )),
+        })
+    }
+
+    fn notify_account_expiry(&self, event: mullvad_types::account::AccountExpiryEvent) {
+        log::debug!("Broadcasting account expiry event");
+        self.notify(types::DaemonEvent {
+            sequence: 0,
+            event: Some(daemon_event::Event::AccountExpiry(
+                types::AccountExpiryEvent::from(event),
+            )),
+        })
+    }
+
+    fn notify_auto_connect_failed(&self, event: mullvad_types::states::AutoConnectFailedEvent) {
+        log::debug!("Broadcasting auto-connect failed event");
+        self.notify(types::DaemonEvent {
+            sequence: 0,
+            event: Some(daemon_event::Event::AutoConnectFailed(
+                types::AutoConnectFailedEvent {
+                    retries: event.retries,
+                },
+            )),
+        })
+    }
+
+    fn notify_disconnect_warning(&self, event: mullvad_types::states::DisconnectWarningEvent) {
+        log::debug!("Broadcasting disconnect warning event");
+        self.notify(types::DaemonEvent {
+            sequence: 0,
+            event: Some(daemon_event::Event::DisconnectWarning(
+                types::DisconnectWarningEvent::from(event),
+            )),
+        })
+    }
 }
 
 impl ManagementInterfaceEventBroadcaster {
+    /// Returns a stream of the events that are sent to subscribers of the management interface.
+    #[cfg(target_os = "linux")]
+    pub fn subscribe(&self) -> EventsListenerReceiver {
+        self.subscriptions.write().subscribe()
+    }
+
     fn notify(&self, value: types::DaemonEvent) {
-        let mut subscriptions = self.subscriptions.write();
         // TODO: using write-lock everywhere. use a mutex instead?
-        subscriptions.retain(|tx| tx.send(Ok(value.clone())).is_ok());
+        self.subscriptions.write().send(value);
+    }
+}
+
+fn convert_circumvention_changes(
+    changes: Vec<circumvention::CircumventionChange>,
+) -> types::CircumventionChanges {
+    types::CircumventionChanges {
+        changes: changes
+            .into_iter()
+            .map(|change| types::CircumventionChange {
+                description: change.setting.to_string(),
+                reason: change.reason,
+                applied_at: Some(types::Timestamp::from(change.applied_at)),
+            })
+            .collect(),
+    }
+}
+
+/// Rejects a requested duration that is longer than `max`, rather than silently shortening it.
+fn validate_duration(duration: Duration, max: Duration) -> Result<Duration, Status> {
+    if duration > max {
+        return Err(Status::invalid_argument(format!(
+            "the duration must not exceed {} seconds",
+            max.as_secs()
+        )));
     }
+    Ok(duration)
 }
 
 /// Converts [`mullvad_daemon::Error`] into a tonic status.
@@ -976,6 +2256,7 @@ fn map_daemon_error(error: crate::Error) -> Status {
     match error {
         DaemonError::RestError(error) => map_rest_error(&error),
         DaemonError::SettingsError(error) => map_settings_error(error),
+        DaemonError::SettingOverrideError(error) => map_setting_override_error(error),
         DaemonError::AlreadyLoggedIn => Status::already_exists(error.to_string()),
         DaemonError::LoginError(error) => map_device_error(&error),
         DaemonError::LogoutError(error) => map_device_error(&error),
@@ -986,6 +2267,16 @@ fn map_daemon_error(error: crate::Error) -> Status {
         #[cfg(windows)]
         DaemonError::SplitTunnelError(error) => map_split_tunnel_error(error),
         DaemonError::AccountHistory(error) => map_account_history_error(error),
+        DaemonError::RelayListUpdateError(error) => Status::unavailable(error.to_string()),
+        DaemonError::ProbeRequiresDisconnected => Status::failed_precondition(error.to_string()),
+        DaemonError::TunnelHookError(error) => Status::invalid_argument(error.to_string()),
+        DaemonError::ExternalTransportError(error) => Status::invalid_argument(error.to_string()),
+        DaemonError::ActiveTransfer(_) => Status::failed_precondition(error.to_string()),
+        DaemonError::NoPendingDisconnect => Status::not_found(error.to_string()),
+        DaemonError::SettingsRollback(settings_history::Error::UnknownChange(_)) => {
+            Status::not_found(error.to_string())
+        }
+        DaemonError::SettingsRollback(_) => Status::failed_precondition(error.to_string()),
         DaemonError::NoAccountToken | DaemonError::NoAccountTokenHistory => {
             Status::unauthenticated(error.to_string())
         }
@@ -993,6 +2284,17 @@ fn map_daemon_error(error: crate::Error) -> Status {
     }
 }
 
+/// Converts an [`overrides::Error`] into a tonic status.
+fn map_setting_override_error(error: overrides::Error) -> Status {
+    match error {
+        overrides::Error::UnknownSetting(..) | overrides::Error::InvalidValue(..) => {
+            Status::invalid_argument(error.to_string())
+        }
+        overrides::Error::CustomRelay => Status::failed_precondition(error.to_string()),
+        overrides::Error::NotOverridden(..) => Status::not_found(error.to_string()),
+    }
+}
+
 #[cfg(windows)]
 /// Converts [`talpid_core::split_tunnel::Error`] into a tonic status.
 fn map_split_tunnel_error(error: talpid_core::split_tunnel::Error) -> Status {
@@ -1043,15 +2345,47 @@ fn map_rest_error(error: &RestError) -> Status {
 /// Converts an instance of [`mullvad_daemon::settings::Error`] into a tonic status.
 fn map_settings_error(error: settings::Error) -> Status {
     match error {
+        #[cfg(not(target_os = "android"))]
+        settings::Error::Encryption(crate::settings_encryption::Error::Unsupported) => Status::new(
+            Code::Unimplemented,
+            crate::settings_encryption::Error::Unsupported.to_string(),
+        ),
         settings::Error::DeleteError(..)
         | settings::Error::WriteError(..)
         | settings::Error::ReadError(..)
-        | settings::Error::SetPermissions(..) => {
+        | settings::Error::SetPermissions(..)
+        | settings::Error::UnsupportedVersion(..)
+        | settings::Error::Encryption(..) => {
             Status::new(Code::FailedPrecondition, error.to_string())
         }
         settings::Error::SerializeError(..) | settings::Error::ParseError(..) => {
             Status::new(Code::Internal, error.to_string())
         }
+        settings::Error::Locked(..) => Status::new(Code::PermissionDenied, error.to_string()),
+        settings::Error::InvalidPatch(..)
+        | settings::Error::InvalidMtu(..)
+        | settings::Error::InvalidPersistentKeepalive(..)
+        | settings::Error::NoAllowedIps
+        | settings::Error::InvalidSplitDnsRule(..)
+        | settings::Error::InvalidDnsFilterRule(..)
+        | settings::Error::InvalidSearchDomain(..)
+        | settings::Error::UnroutableDnsServer(..)
+        | settings::Error::NotLocalNetwork(..) => {
+            Status::new(Code::InvalidArgument, error.to_string())
+        }
+        settings::Error::Rejected(reason) => {
+            Status::new(Code::FailedPrecondition, reason.to_string())
+        }
+        settings::Error::CustomList(error) => {
+            use mullvad_types::custom_list::Error;
+            let code = match error {
+                Error::ListExists(_) => Code::AlreadyExists,
+                Error::ListNotFound(_) => Code::NotFound,
+                Error::ListInUse(_) => Code::FailedPrecondition,
+                Error::EmptyName | Error::NestedList => Code::InvalidArgument,
+            };
+            Status::new(code, error.to_string())
+        }
     }
 }
 
@@ -86,6 +86,10 @@ pub enum Error {
     #[error(display = "Failed to parse account history")]
     ParseHistoryError,
 
+    #[cfg(not(target_os = "android"))]
+    #[error(display = "Failed to decrypt the settings")]
+    Decrypt(#[error(source)] crate::settings_encryption::Error),
+
     #[cfg(windows)]
     #[error(display = "Failed to restore Windows update backup")]
     WinMigrationError(#[error(source)] windows::Error),
@@ -131,6 +135,18 @@ pub(crate) async fn migrate_all(
 
     let settings_bytes = fs::read(&path).await.map_err(Error::Read)?;
 
+    // Encrypted settings are migrated in plain text, and encrypted again with the same key
+    #[cfg(not(target_os = "android"))]
+    let (settings_bytes, cipher) = if crate::settings_encryption::is_encrypted(&settings_bytes) {
+        let cipher = crate::settings_encryption::Cipher::load(settings_dir)
+            .await
+            .map_err(Error::Decrypt)?;
+        let settings_bytes = cipher.decrypt(&settings_bytes).map_err(Error::Decrypt)?;
+        (settings_bytes, Some(cipher))
+    } else {
+        (settings_bytes, None)
+    };
+
     let mut settings: serde_json::Value =
         serde_json::from_reader(&settings_bytes[..]).map_err(Error::Parse)?;
 
@@ -155,7 +171,14 @@ pub(crate) async fn migrate_all(
         return Ok(migration_data);
     }
 
-    let buffer = serde_json::to_string_pretty(&settings).map_err(Error::Serialize)?;
+    let buffer = serde_json::to_string_pretty(&settings)
+        .map_err(Error::Serialize)?
+        .into_bytes();
+    #[cfg(not(target_os = "android"))]
+    let buffer = match cipher {
+        Some(cipher) => cipher.encrypt(&buffer),
+        None => buffer,
+    };
 
     let mut options = fs::OpenOptions::new();
     #[cfg(unix)]
@@ -169,9 +192,7 @@ pub(crate) async fn migrate_all(
         .open(&path)
         .await
         .map_err(Error::Open)?;
-    file.write_all(&buffer.into_bytes())
-        .await
-        .map_err(Error::Write)?;
+    file.write_all(&buffer).await.map_err(Error::Write)?;
     file.sync_data().await.map_err(Error::SyncSettings)?;
 
     log::debug!("Migrated settings. Wrote settings to {}", path.display());
@@ -214,8 +235,10 @@ mod windows {
     };
 
     const MIGRATION_DIRNAME: &str = "windows.old";
-    const MIGRATE_FILES: [(&str, bool); 3] = [
+    const MIGRATE_FILES: [(&str, bool); 4] = [
         ("settings.json", true),
+        // Only exists if the settings are encrypted
+        ("settings.key", false),
         ("device.json", true),
         ("account-history.json", false),
     ];
@@ -0,0 +1,332 @@
+use crate::settings::{self, SettingsPersister};
+use futures::future::AbortHandle;
+use mullvad_types::{
+    relay_constraints::{
+        Constraint, LocationConstraint, RelayConstraintsUpdate, RelaySettings, RelaySettingsUpdate,
+    },
+    settings::Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const OVERRIDES_FILE: &str = "setting-overrides.json";
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "The setting \"{}\" cannot be overridden", _0)]
+    UnknownSetting(String),
+
+    #[error(display = "Invalid value for \"{}\": {}", _0, _1)]
+    InvalidValue(&'static str, &'static str),
+
+    #[error(display = "The location cannot be overridden while a custom relay is used")]
+    CustomRelay,
+
+    #[error(display = "The setting \"{}\" is not overridden", _0)]
+    NotOverridden(String),
+}
+
+/// A setting that can be changed temporarily, along with its value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "setting", content = "value")]
+pub enum SettingValue {
+    BlockWhenDisconnected(bool),
+    AllowLan(bool),
+    AllowMulticast(bool),
+    EnableIpv6(bool),
+    Location(Constraint<LocationConstraint>),
+}
+
+impl SettingValue {
+    /// Parses a value for the setting named `path`. Boolean settings accept `on` and `off`, and
+    /// the location accepts `any` or a country code, optionally followed by a city code and a
+    /// hostname.
+    pub fn parse(path: &str, value: &str) -> Result<Self, Error> {
+        match path {
+            "block_when_disconnected" => Ok(SettingValue::BlockWhenDisconnected(parse_bool(
+                "block_when_disconnected",
+                value,
+            )?)),
+            "allow_lan" => Ok(SettingValue::AllowLan(parse_bool("allow_lan", value)?)),
+            "allow_multicast" => Ok(SettingValue::AllowMulticast(parse_bool(
+                "allow_multicast",
+                value,
+            )?)),
+            "enable_ipv6" => Ok(SettingValue::EnableIpv6(parse_bool("enable_ipv6", value)?)),
+            "location" => Ok(SettingValue::Location(parse_location(value)?)),
+            _ => Err(Error::UnknownSetting(path.to_owned())),
+        }
+    }
+
+    /// Name of the setting, as accepted by [`SettingValue::parse`].
+    pub fn path(&self) -> &'static str {
+        match self {
+            SettingValue::BlockWhenDisconnected(_) => "block_when_disconnected",
+            SettingValue::AllowLan(_) => "allow_lan",
+            SettingValue::AllowMulticast(_) => "allow_multicast",
+            SettingValue::EnableIpv6(_) => "enable_ipv6",
+            SettingValue::Location(_) => "location",
+        }
+    }
+
+    /// Returns the value that the same setting currently has in `settings`.
+    pub fn current(&self, settings: &Settings) -> Result<Self, Error> {
+        Ok(match self {
+            SettingValue::BlockWhenDisconnected(_) => {
+                SettingValue::BlockWhenDisconnected(settings.block_when_disconnected)
+            }
+            SettingValue::AllowLan(_) => SettingValue::AllowLan(settings.allow_lan),
+            SettingValue::AllowMulticast(_) => {
+                SettingValue::AllowMulticast(settings.allow_multicast)
+            }
+            SettingValue::EnableIpv6(_) => {
+                SettingValue::EnableIpv6(settings.tunnel_options.generic.enable_ipv6)
+            }
+            SettingValue::Location(_) => match settings.get_relay_settings() {
+                RelaySettings::Normal(constraints) => SettingValue::Location(constraints.location),
+                RelaySettings::CustomTunnelEndpoint(_) => return Err(Error::CustomRelay),
+            },
+        })
+    }
+
+    /// Stores the value in `settings` without applying it anywhere else. This is only meant to be
+    /// used before the value has been handed to any other component.
+    pub async fn save(&self, settings: &mut SettingsPersister) -> Result<bool, settings::Error> {
+        match self {
+            SettingValue::BlockWhenDisconnected(value) => {
+                settings.set_block_when_disconnected(*value).await
+            }
+            SettingValue::AllowLan(value) => settings.set_allow_lan(*value).await,
+            SettingValue::AllowMulticast(value) => settings.set_allow_multicast(*value).await,
+            SettingValue::EnableIpv6(value) => settings.set_enable_ipv6(*value).await,
+            SettingValue::Location(location) => settings
+                .update_relay_settings(location_update(location.clone()))
+                .await
+                .map(|changed| changed.is_changed()),
+        }
+    }
+}
+
+/// Returns a relay settings update that only changes the location.
+pub fn location_update(location: Constraint<LocationConstraint>) -> RelaySettingsUpdate {
+    RelaySettingsUpdate::Normal(RelayConstraintsUpdate {
+        location: Some(location),
+        ..Default::default()
+    })
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingValue::BlockWhenDisconnected(value)
+            | SettingValue::AllowLan(value)
+            | SettingValue::AllowMulticast(value)
+            | SettingValue::EnableIpv6(value) => {
+                if *value {
+                    "on".fmt(f)
+                } else {
+                    "off".fmt(f)
+                }
+            }
+            SettingValue::Location(Constraint::Any) => "any".fmt(f),
+            SettingValue::Location(Constraint::Only(location)) => match location {
+                LocationConstraint::Country(country) => write!(f, "{}", country),
+                LocationConstraint::City(country, city) => write!(f, "{} {}", country, city),
+                LocationConstraint::Hostname(country, city, hostname) => {
+                    write!(f, "{} {} {}", country, city, hostname)
+                }
+                #[cfg(not(target_os = "android"))]
+                LocationConstraint::CustomList(name) => write!(f, "custom list {}", name),
+            },
+        }
+    }
+}
+
+fn parse_bool(path: &'static str, value: &str) -> Result<bool, Error> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(Error::InvalidValue(path, "expected \"on\" or \"off\"")),
+    }
+}
+
+fn parse_location(value: &str) -> Result<Constraint<LocationConstraint>, Error> {
+    let parts: Vec<String> = value.split_whitespace().map(str::to_lowercase).collect();
+    let location = match parts.as_slice() {
+        [any] if any == "any" => return Ok(Constraint::Any),
+        [country] => LocationConstraint::Country(country.clone()),
+        [country, city] => LocationConstraint::City(country.clone(), city.clone()),
+        [country, city, hostname] => {
+            LocationConstraint::Hostname(country.clone(), city.clone(), hostname.clone())
+        }
+        _ => {
+            return Err(Error::InvalidValue(
+                "location",
+                "expected \"any\" or <country> [city] [hostname]",
+            ))
+        }
+    };
+    Ok(Constraint::Only(location))
+}
+
+/// A temporary change to a setting and the value to restore once it ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingOverride {
+    pub value: SettingValue,
+    pub restore: SettingValue,
+    pub revert_at: SystemTime,
+    /// Also end the override when the user reconnects the tunnel.
+    pub revert_on_reconnect: bool,
+}
+
+/// Keeps track of the active overrides. A record of them is kept on disk so that a later daemon
+/// instance can restore the original values if this one does not get to do it.
+pub struct SettingOverrides {
+    path: PathBuf,
+    overrides: Vec<(SettingOverride, AbortHandle)>,
+}
+
+impl SettingOverrides {
+    /// Returns an empty tracker, along with the values to restore for any overrides that were
+    /// active when the previous daemon instance exited.
+    pub async fn load(settings_dir: &Path) -> (Self, Vec<SettingValue>) {
+        let path = settings_dir.join(OVERRIDES_FILE);
+        let leftover = match fs::read_to_string(&path).await {
+            Ok(content) => match serde_json::from_str::<Vec<SettingOverride>>(&content) {
+                Ok(overrides) => overrides
+                    .into_iter()
+                    .map(|setting_override| setting_override.restore)
+                    .collect(),
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse setting overrides")
+                    );
+                    vec![]
+                }
+            },
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read setting overrides")
+                    );
+                }
+                vec![]
+            }
+        };
+
+        (
+            SettingOverrides {
+                path,
+                overrides: vec![],
+            },
+            leftover,
+        )
+    }
+
+    pub fn get(&self, path: &str) -> Option<&SettingOverride> {
+        self.overrides
+            .iter()
+            .find(|(setting_override, _)| setting_override.value.path() == path)
+            .map(|(setting_override, _)| setting_override)
+    }
+
+    pub fn list(&self) -> Vec<SettingOverride> {
+        self.overrides
+            .iter()
+            .map(|(setting_override, _)| setting_override.clone())
+            .collect()
+    }
+
+    /// Adds an override, replacing any existing override of the same setting. `job` is aborted
+    /// when the override is removed.
+    pub async fn insert(&mut self, setting_override: SettingOverride, job: AbortHandle) {
+        self.take(setting_override.value.path());
+        self.overrides.push((setting_override, job));
+        self.save().await;
+    }
+
+    /// Removes the override of the setting named `path`.
+    pub async fn remove(&mut self, path: &str) -> Option<SettingOverride> {
+        let removed = self.take(path);
+        if removed.is_some() {
+            self.save().await;
+        }
+        removed
+    }
+
+    /// Removes all overrides that should end when the tunnel is reconnected.
+    pub async fn remove_revert_on_reconnect(&mut self) -> Vec<SettingOverride> {
+        let paths: Vec<_> = self
+            .overrides
+            .iter()
+            .filter(|(setting_override, _)| setting_override.revert_on_reconnect)
+            .map(|(setting_override, _)| setting_override.value.path())
+            .collect();
+        let removed: Vec<_> = paths
+            .into_iter()
+            .filter_map(|path| self.take(path))
+            .collect();
+        if !removed.is_empty() {
+            self.save().await;
+        }
+        removed
+    }
+
+    /// Forgets all overrides without restoring anything.
+    pub async fn clear(&mut self) {
+        for (_, job) in self.overrides.drain(..) {
+            job.abort();
+        }
+        self.save().await;
+    }
+
+    fn take(&mut self, path: &str) -> Option<SettingOverride> {
+        let index = self
+            .overrides
+            .iter()
+            .position(|(setting_override, _)| setting_override.value.path() == path)?;
+        let (setting_override, job) = self.overrides.remove(index);
+        job.abort();
+        Some(setting_override)
+    }
+
+    async fn save(&self) {
+        if self.overrides.is_empty() {
+            if let Err(error) = fs::remove_file(&self.path).await {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to delete setting overrides")
+                    );
+                }
+            }
+            return;
+        }
+
+        match serde_json::to_string(&self.list()) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write setting overrides")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize setting overrides")
+                );
+            }
+        }
+    }
+}
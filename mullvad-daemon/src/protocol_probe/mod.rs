@@ -0,0 +1,323 @@
+//! Probes nearby relays with each way of connecting to them, to find out which ones get through
+//! the current network.
+
+use chrono::offset::Utc;
+use futures::future::join_all;
+use mullvad_relay_selector::RelaySelector;
+use mullvad_types::{
+    location::Coordinates,
+    protocol_probe::{ProbeOutcome, ProbeProtocol, ProbeReport, ProbeResult},
+    relay_list::Relay,
+};
+use std::{
+    future::Future,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    pin::Pin,
+    time::{Duration, Instant},
+};
+use talpid_types::net::{
+    wireguard::{PrivateKey, PublicKey},
+    TransportProtocol,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+mod wireguard;
+
+/// Time to wait for a relay to reply to a probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Number of relays that are probed with each protocol.
+const RELAYS_PER_PROTOCOL: usize = 2;
+/// Ports that WireGuard is probed on over plain UDP, if the relays accept them.
+const WIREGUARD_PORTS: [u16; 3] = [51820, 53, 4000];
+/// Maximum number of ports that OpenVPN is probed on for each transport protocol.
+const MAX_OPENVPN_PORTS: usize = 3;
+
+/// P_CONTROL_HARD_RESET_CLIENT_V2 with key ID 0.
+const OPENVPN_HARD_RESET_CLIENT: u8 = 7 << 3;
+/// P_CONTROL_HARD_RESET_SERVER_V2.
+const OPENVPN_HARD_RESET_SERVER: u8 = 8;
+
+type Probe = Pin<Box<dyn Future<Output = ProbeResult> + Send>>;
+
+/// Probes the relays nearest to `location` with every protocol, and returns the results. WireGuard
+/// can only be probed with the key of a device, so those probes are skipped without one.
+pub async fn probe_protocols(
+    relay_selector: &RelaySelector,
+    location: Option<Coordinates>,
+    private_key: Option<PrivateKey>,
+) -> ProbeReport {
+    let nearest = |filter: fn(&Relay) -> bool| {
+        let mut relays = relay_selector.get_nearest_relays(location.as_ref(), filter);
+        relays.truncate(RELAYS_PER_PROTOCOL);
+        relays
+    };
+    let wireguard_relays = nearest(|relay| !relay.tunnels.wireguard.is_empty());
+    let udp2tcp_relays = nearest(|relay| {
+        !relay.tunnels.wireguard.is_empty() && !relay.obfuscators.udp2tcp.is_empty()
+    });
+    let openvpn_relays = nearest(|relay| !relay.tunnels.openvpn.is_empty());
+    let bridges = nearest(|relay| {
+        relay
+            .bridges
+            .shadowsocks
+            .iter()
+            .any(|bridge| bridge.protocol == TransportProtocol::Tcp)
+    });
+
+    let mut probes: Vec<Probe> = vec![];
+    let mut sender_index = rand::random::<u32>();
+
+    for relay in &wireguard_relays {
+        let endpoint = &relay.tunnels.wireguard[0];
+        for port in WIREGUARD_PORTS {
+            let accepted = endpoint
+                .port_ranges
+                .iter()
+                .any(|(first, last)| (*first..=*last).contains(&port));
+            if !accepted {
+                continue;
+            }
+            let address = SocketAddr::new(relay.ipv4_addr_in.into(), port);
+            let probe = probe_wireguard(
+                address,
+                private_key.clone(),
+                endpoint.public_key.clone(),
+                sender_index,
+            );
+            sender_index = sender_index.wrapping_add(1);
+            probes.push(result(ProbeProtocol::WireguardUdp, relay, port, probe));
+        }
+    }
+
+    for relay in &udp2tcp_relays {
+        let public_key = relay.tunnels.wireguard[0].public_key.clone();
+        for udp2tcp in &relay.obfuscators.udp2tcp {
+            let address = SocketAddr::new(relay.ipv4_addr_in.into(), udp2tcp.port);
+            let probe = probe_udp2tcp(
+                address,
+                private_key.clone(),
+                public_key.clone(),
+                sender_index,
+            );
+            sender_index = sender_index.wrapping_add(1);
+            probes.push(result(ProbeProtocol::Udp2Tcp, relay, udp2tcp.port, probe));
+        }
+    }
+
+    for relay in &openvpn_relays {
+        for (transport, protocol) in [
+            (TransportProtocol::Udp, ProbeProtocol::OpenVpnUdp),
+            (TransportProtocol::Tcp, ProbeProtocol::OpenVpnTcp),
+        ] {
+            let mut ports: Vec<u16> = relay
+                .tunnels
+                .openvpn
+                .iter()
+                .filter(|endpoint| endpoint.protocol == transport)
+                .map(|endpoint| endpoint.port)
+                .collect();
+            ports.sort_unstable();
+            ports.dedup();
+            ports.truncate(MAX_OPENVPN_PORTS);
+            for port in ports {
+                let address = SocketAddr::new(relay.ipv4_addr_in.into(), port);
+                probes.push(result(
+                    protocol,
+                    relay,
+                    port,
+                    probe_openvpn(address, transport),
+                ));
+            }
+        }
+    }
+
+    for relay in &bridges {
+        for bridge in &relay.bridges.shadowsocks {
+            if bridge.protocol != TransportProtocol::Tcp {
+                continue;
+            }
+            let address = SocketAddr::new(relay.ipv4_addr_in.into(), bridge.port);
+            let probe = async move { probe_tcp_connect(address).await.map(|(_, latency)| latency) };
+            probes.push(result(ProbeProtocol::Bridge, relay, bridge.port, probe));
+        }
+    }
+
+    ProbeReport {
+        created: Utc::now(),
+        results: join_all(probes).await,
+    }
+}
+
+fn result(
+    protocol: ProbeProtocol,
+    relay: &Relay,
+    port: u16,
+    probe: impl Future<Output = Result<Duration, ProbeOutcome>> + Send + 'static,
+) -> Probe {
+    let hostname = relay.hostname.clone();
+    Box::pin(async move {
+        let outcome = match probe.await {
+            Ok(latency) => ProbeOutcome::Reachable(latency),
+            Err(outcome) => outcome,
+        };
+        ProbeResult {
+            protocol,
+            port,
+            hostname,
+            outcome,
+        }
+    })
+}
+
+/// Sends a handshake initiation over UDP and waits for the relay to respond.
+async fn probe_wireguard(
+    address: SocketAddr,
+    private_key: Option<PrivateKey>,
+    public_key: PublicKey,
+    sender_index: u32,
+) -> Result<Duration, ProbeOutcome> {
+    let private_key = private_key.ok_or_else(no_device)?;
+    let initiation = wireguard::initiation(&private_key, &public_key, sender_index);
+
+    let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+        .await
+        .map_err(skipped)?;
+    socket.connect(address).await.map_err(skipped)?;
+    let start = Instant::now();
+    socket.send(&initiation).await.map_err(blocked)?;
+
+    let reply = async {
+        let mut buffer = [0u8; 256];
+        loop {
+            let len = socket.recv(&mut buffer).await?;
+            if wireguard::is_reply(&buffer[..len], sender_index) {
+                return Ok::<_, io::Error>(());
+            }
+        }
+    };
+    with_timeout(reply).await?;
+    Ok(start.elapsed())
+}
+
+/// Sends a handshake initiation through the udp2tcp obfuscation endpoint, which frames each
+/// datagram with its length, and waits for the relay to respond. Without a device key, all that
+/// can be checked is that the TCP connection can be established.
+async fn probe_udp2tcp(
+    address: SocketAddr,
+    private_key: Option<PrivateKey>,
+    public_key: PublicKey,
+    sender_index: u32,
+) -> Result<Duration, ProbeOutcome> {
+    let (mut stream, connect_latency) = probe_tcp_connect(address).await?;
+    let private_key = match private_key {
+        Some(private_key) => private_key,
+        None => return Ok(connect_latency),
+    };
+    let initiation = wireguard::initiation(&private_key, &public_key, sender_index);
+
+    let start = Instant::now();
+    let exchange = async {
+        stream
+            .write_all(&(wireguard::INITIATION_SIZE as u16).to_be_bytes())
+            .await?;
+        stream.write_all(&initiation).await?;
+        loop {
+            let len = usize::from(stream.read_u16().await?);
+            let mut datagram = vec![0u8; len];
+            stream.read_exact(&mut datagram).await?;
+            if wireguard::is_reply(&datagram, sender_index) {
+                return Ok::<_, io::Error>(());
+            }
+        }
+    };
+    with_timeout(exchange).await?;
+    Ok(start.elapsed())
+}
+
+/// Asks the relay to start an OpenVPN session, and waits for it to reply.
+async fn probe_openvpn(
+    address: SocketAddr,
+    transport: TransportProtocol,
+) -> Result<Duration, ProbeOutcome> {
+    // The opcode, a random session ID, an empty list of acknowledgements and the packet ID.
+    let mut packet = vec![OPENVPN_HARD_RESET_CLIENT];
+    packet.extend_from_slice(&rand::random::<[u8; 8]>());
+    packet.extend_from_slice(&[0u8; 5]);
+
+    match transport {
+        TransportProtocol::Udp => {
+            let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+                .await
+                .map_err(skipped)?;
+            socket.connect(address).await.map_err(skipped)?;
+            let start = Instant::now();
+            socket.send(&packet).await.map_err(blocked)?;
+            let reply = async {
+                let mut buffer = [0u8; 2048];
+                loop {
+                    let len = socket.recv(&mut buffer).await?;
+                    if len > 0 && buffer[0] >> 3 == OPENVPN_HARD_RESET_SERVER {
+                        return Ok::<_, io::Error>(());
+                    }
+                }
+            };
+            with_timeout(reply).await?;
+            Ok(start.elapsed())
+        }
+        TransportProtocol::Tcp => {
+            let (mut stream, _) = probe_tcp_connect(address).await?;
+            let start = Instant::now();
+            let exchange = async {
+                stream
+                    .write_all(&(packet.len() as u16).to_be_bytes())
+                    .await?;
+                stream.write_all(&packet).await?;
+                let len = usize::from(stream.read_u16().await?);
+                let mut reply = vec![0u8; len];
+                stream.read_exact(&mut reply).await?;
+                if reply.first().map(|opcode| opcode >> 3) == Some(OPENVPN_HARD_RESET_SERVER) {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected reply from the relay",
+                    ))
+                }
+            };
+            with_timeout(exchange).await?;
+            Ok(start.elapsed())
+        }
+    }
+}
+
+async fn probe_tcp_connect(address: SocketAddr) -> Result<(TcpStream, Duration), ProbeOutcome> {
+    let start = Instant::now();
+    let stream = with_timeout(TcpStream::connect(address)).await?;
+    Ok((stream, start.elapsed()))
+}
+
+async fn with_timeout<T>(future: impl Future<Output = io::Result<T>>) -> Result<T, ProbeOutcome> {
+    match timeout(PROBE_TIMEOUT, future).await {
+        Ok(result) => result.map_err(blocked),
+        Err(_) => Err(ProbeOutcome::Unreachable(
+            "The relay did not reply in time".to_owned(),
+        )),
+    }
+}
+
+fn blocked(error: io::Error) -> ProbeOutcome {
+    ProbeOutcome::Unreachable(error.to_string())
+}
+
+fn skipped(error: io::Error) -> ProbeOutcome {
+    ProbeOutcome::Skipped(error.to_string())
+}
+
+fn no_device() -> ProbeOutcome {
+    ProbeOutcome::Skipped("WireGuard can only be probed while logged in".to_owned())
+}
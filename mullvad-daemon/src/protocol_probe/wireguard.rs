@@ -0,0 +1,319 @@
+//! Builds WireGuard handshake initiations. A relay only replies to an initiation that comes
+//! from a key that it knows and that it can decrypt, so a reply shows that WireGuard traffic gets
+//! through in both directions.
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::{
+    convert::TryInto,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use talpid_types::net::wireguard::{PrivateKey, PublicKey};
+
+const CONSTRUCTION: &[u8] = b"Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+const IDENTIFIER: &[u8] = b"WireGuard v1 zx2c4 Jason@zx2c4.com";
+const LABEL_MAC1: &[u8] = b"mac1----";
+
+const MESSAGE_INITIATION: u8 = 1;
+const MESSAGE_RESPONSE: u8 = 2;
+const MESSAGE_COOKIE_REPLY: u8 = 3;
+
+/// Size of a handshake initiation.
+pub const INITIATION_SIZE: usize = 148;
+/// Offset of the first MAC in a handshake initiation. It covers everything before it.
+const MAC1_OFFSET: usize = 116;
+
+/// TAI64 label of the Unix epoch.
+const TAI64_EPOCH: u64 = 0x4000_0000_0000_000a;
+
+/// Returns a handshake initiation from `private_key` to the peer with `peer_public_key`.
+pub fn initiation(
+    private_key: &PrivateKey,
+    peer_public_key: &PublicKey,
+    sender_index: u32,
+) -> [u8; INITIATION_SIZE] {
+    let ephemeral = PrivateKey::new_from_random();
+    let ephemeral_public = ephemeral.public_key();
+
+    let chaining_key = hash(&[CONSTRUCTION]);
+    let transcript = hash(&[&chaining_key, IDENTIFIER]);
+    let transcript = hash(&[&transcript, peer_public_key.as_bytes()]);
+
+    let chaining_key = kdf1(&chaining_key, ephemeral_public.as_bytes());
+    let transcript = hash(&[&transcript, ephemeral_public.as_bytes()]);
+
+    let (chaining_key, key) = kdf2(&chaining_key, &ephemeral.diffie_hellman(peer_public_key));
+    let encrypted_static = seal(&key, private_key.public_key().as_bytes(), &transcript);
+    let transcript = hash(&[&transcript, &encrypted_static]);
+
+    let (_, key) = kdf2(&chaining_key, &private_key.diffie_hellman(peer_public_key));
+    let encrypted_timestamp = seal(&key, &tai64n(SystemTime::now()), &transcript);
+
+    let mut message = [0u8; INITIATION_SIZE];
+    message[0] = MESSAGE_INITIATION;
+    message[4..8].copy_from_slice(&sender_index.to_le_bytes());
+    message[8..40].copy_from_slice(ephemeral_public.as_bytes());
+    message[40..88].copy_from_slice(&encrypted_static);
+    message[88..MAC1_OFFSET].copy_from_slice(&encrypted_timestamp);
+
+    let mac1_key = hash(&[LABEL_MAC1, peer_public_key.as_bytes()]);
+    let mac1 = mac(&mac1_key, &message[..MAC1_OFFSET]);
+    message[MAC1_OFFSET..MAC1_OFFSET + 16].copy_from_slice(&mac1);
+    // The second MAC is only required when the peer is under load, and is left as zeroes.
+    message
+}
+
+/// Returns whether `packet` is a reply to the initiation that was sent with `sender_index`.
+/// A relay that is under load sends a cookie reply instead of a handshake response, but that
+/// still shows that the initiation got through.
+pub fn is_reply(packet: &[u8], sender_index: u32) -> bool {
+    let index_at = |offset: usize| {
+        packet
+            .get(offset..offset + 4)
+            .map(|index| index == sender_index.to_le_bytes())
+            .unwrap_or(false)
+    };
+    match packet.first() {
+        Some(&MESSAGE_RESPONSE) => packet.len() == 92 && index_at(8),
+        Some(&MESSAGE_COOKIE_REPLY) => packet.len() == 64 && index_at(4),
+        _ => false,
+    }
+}
+
+fn tai64n(time: SystemTime) -> [u8; 12] {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut timestamp = [0u8; 12];
+    timestamp[..8].copy_from_slice(&(TAI64_EPOCH + since_epoch.as_secs()).to_be_bytes());
+    timestamp[8..].copy_from_slice(&since_epoch.subsec_nanos().to_be_bytes());
+    timestamp
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8], transcript: &[u8; 32]) -> Vec<u8> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: plaintext,
+                aad: transcript,
+            },
+        )
+        .expect("plaintext is too large")
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut state = Blake2s::new(32, &[]);
+    for part in parts {
+        state.update(part);
+    }
+    state.finalize().try_into().unwrap()
+}
+
+fn mac(key: &[u8; 32], data: &[u8]) -> [u8; 16] {
+    let mut state = Blake2s::new(16, key);
+    state.update(data);
+    state.finalize().try_into().unwrap()
+}
+
+fn hmac(key: &[u8; 32], parts: &[&[u8]]) -> [u8; 32] {
+    let mut inner_key = [0x36u8; BLOCK_SIZE];
+    let mut outer_key = [0x5cu8; BLOCK_SIZE];
+    for (i, byte) in key.iter().enumerate() {
+        inner_key[i] ^= byte;
+        outer_key[i] ^= byte;
+    }
+    let mut inner = Blake2s::new(32, &[]);
+    inner.update(&inner_key);
+    for part in parts {
+        inner.update(part);
+    }
+    let mut outer = Blake2s::new(32, &[]);
+    outer.update(&outer_key);
+    outer.update(&inner.finalize());
+    outer.finalize().try_into().unwrap()
+}
+
+fn kdf1(key: &[u8; 32], input: &[u8]) -> [u8; 32] {
+    let secret = hmac(key, &[input]);
+    hmac(&secret, &[&[1]])
+}
+
+fn kdf2(key: &[u8; 32], input: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let secret = hmac(key, &[input]);
+    let first = hmac(&secret, &[&[1]]);
+    let second = hmac(&secret, &[&first, &[2]]);
+    (first, second)
+}
+
+const BLOCK_SIZE: usize = 64;
+
+const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// BLAKE2s as specified in RFC 7693, which WireGuard uses for hashing and MACs.
+struct Blake2s {
+    state: [u32; 8],
+    counter: u64,
+    block: [u8; BLOCK_SIZE],
+    block_len: usize,
+    output_len: usize,
+}
+
+impl Blake2s {
+    fn new(output_len: usize, key: &[u8]) -> Self {
+        let mut state = IV;
+        state[0] ^= 0x0101_0000 ^ ((key.len() as u32) << 8) ^ output_len as u32;
+        let mut hasher = Blake2s {
+            state,
+            counter: 0,
+            block: [0u8; BLOCK_SIZE],
+            block_len: 0,
+            output_len,
+        };
+        if !key.is_empty() {
+            let mut key_block = [0u8; BLOCK_SIZE];
+            key_block[..key.len()].copy_from_slice(key);
+            hasher.update(&key_block);
+        }
+        hasher
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // The last block has to be compressed differently, so a full block is only
+            // compressed once it is known that more data follows.
+            if self.block_len == BLOCK_SIZE {
+                self.counter += BLOCK_SIZE as u64;
+                self.compress(false);
+                self.block_len = 0;
+            }
+            let len = (BLOCK_SIZE - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + len].copy_from_slice(&data[..len]);
+            self.block_len += len;
+            data = &data[len..];
+        }
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        self.counter += self.block_len as u64;
+        for byte in &mut self.block[self.block_len..] {
+            *byte = 0;
+        }
+        self.compress(true);
+        self.state
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .take(self.output_len)
+            .collect()
+    }
+
+    fn compress(&mut self, last: bool) {
+        let mut message = [0u32; 16];
+        for (word, bytes) in message.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+
+        let mut v = [0u32; 16];
+        v[..8].copy_from_slice(&self.state);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.counter as u32;
+        v[13] ^= (self.counter >> 32) as u32;
+        if last {
+            v[14] = !v[14];
+        }
+
+        for s in &SIGMA {
+            mix(&mut v, 0, 4, 8, 12, message[s[0]], message[s[1]]);
+            mix(&mut v, 1, 5, 9, 13, message[s[2]], message[s[3]]);
+            mix(&mut v, 2, 6, 10, 14, message[s[4]], message[s[5]]);
+            mix(&mut v, 3, 7, 11, 15, message[s[6]], message[s[7]]);
+            mix(&mut v, 0, 5, 10, 15, message[s[8]], message[s[9]]);
+            mix(&mut v, 1, 6, 11, 12, message[s[10]], message[s[11]]);
+            mix(&mut v, 2, 7, 8, 13, message[s[12]], message[s[13]]);
+            mix(&mut v, 3, 4, 9, 14, message[s[14]], message[s[15]]);
+        }
+
+        for i in 0..8 {
+            self.state[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+fn mix(v: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_blake2s() {
+        assert_eq!(
+            hex(&hash(&[b"abc"])),
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+        );
+        assert_eq!(
+            hex(&hash(&[])),
+            "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9"
+        );
+        // Messages that end exactly on a block boundary
+        assert_eq!(hash(&[&[7u8; 128]]), hash(&[&[7u8; 64], &[7u8; 64]]));
+    }
+
+    #[test]
+    fn test_initiation() {
+        let private_key = PrivateKey::new_from_random();
+        let peer_public_key = PrivateKey::new_from_random().public_key();
+        let message = initiation(&private_key, &peer_public_key, 0x01020304);
+
+        assert_eq!(message[..8], [1, 0, 0, 0, 4, 3, 2, 1]);
+        let mac1_key = hash(&[LABEL_MAC1, peer_public_key.as_bytes()]);
+        assert_eq!(
+            message[MAC1_OFFSET..MAC1_OFFSET + 16],
+            mac(&mac1_key, &message[..MAC1_OFFSET])
+        );
+        assert_eq!(message[MAC1_OFFSET + 16..], [0u8; 16]);
+    }
+
+    #[test]
+    fn test_is_reply() {
+        let mut response = [0u8; 92];
+        response[0] = MESSAGE_RESPONSE;
+        response[8..12].copy_from_slice(&7u32.to_le_bytes());
+        assert!(is_reply(&response, 7));
+        assert!(!is_reply(&response, 8));
+        assert!(!is_reply(&response[..60], 7));
+
+        let mut cookie_reply = [0u8; 64];
+        cookie_reply[0] = MESSAGE_COOKIE_REPLY;
+        cookie_reply[4..8].copy_from_slice(&7u32.to_le_bytes());
+        assert!(is_reply(&cookie_reply, 7));
+    }
+}
@@ -0,0 +1,329 @@
+//! Records how well connections to each relay work out, so that relays that keep failing can be
+//! avoided. The statistics are kept in the cache directory, since they can be rebuilt at any time.
+
+use chrono::offset::Utc;
+use mullvad_types::{location::GeoIpLocation, relay_stats::RelayStats, states::TunnelState};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const RELAY_STATS_FILE: &str = "relay-stats.json";
+
+/// Statistics are only kept for this many relays. The ones that were used the longest ago are
+/// dropped first.
+const MAX_RELAYS: usize = 500;
+
+pub struct RelayStatsStore {
+    path: PathBuf,
+    stats: BTreeMap<String, RelayStats>,
+    attempt: Option<Attempt>,
+}
+
+/// A connection that is being established, or that has been.
+struct Attempt {
+    /// The exit relay, followed by the entry relay when using multihop.
+    hostnames: Vec<String>,
+    started: Instant,
+}
+
+impl RelayStatsStore {
+    pub async fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(RELAY_STATS_FILE);
+        let stats: Vec<RelayStats> = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse relay statistics")
+                );
+                vec![]
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read relay statistics")
+                    );
+                }
+                vec![]
+            }
+        };
+        RelayStatsStore {
+            path,
+            stats: stats
+                .into_iter()
+                .map(|stats| (stats.hostname.clone(), stats))
+                .collect(),
+            attempt: None,
+        }
+    }
+
+    /// Updates the statistics for a tunnel state transition. `peak_throughput` is the highest
+    /// throughput, in bytes per second, that was seen while in `old_state`. Returns whether the
+    /// statistics changed.
+    pub async fn update(
+        &mut self,
+        old_state: &TunnelState,
+        new_state: &TunnelState,
+        peak_throughput: Option<u64>,
+    ) -> bool {
+        let changed = self.record(old_state, new_state, peak_throughput);
+        if changed {
+            self.save().await;
+        }
+        changed
+    }
+
+    fn record(
+        &mut self,
+        old_state: &TunnelState,
+        new_state: &TunnelState,
+        peak_throughput: Option<u64>,
+    ) -> bool {
+        let mut changed = false;
+        match (old_state, self.attempt.as_ref()) {
+            (TunnelState::Connecting { .. }, Some(attempt)) => {
+                // Attempts that are cancelled say nothing about the relay
+                let outcome = match new_state {
+                    TunnelState::Connected { .. } => Some(true),
+                    TunnelState::Connecting { .. } | TunnelState::Error(_) => Some(false),
+                    TunnelState::Disconnecting(_) | TunnelState::Disconnected => None,
+                };
+                if let Some(succeeded) = outcome {
+                    let handshake_time = attempt.started.elapsed();
+                    for hostname in &attempt.hostnames {
+                        let stats = Self::entry(&mut self.stats, hostname);
+                        stats.attempts += 1;
+                        stats.last_used = Utc::now();
+                        if succeeded {
+                            stats.successes += 1;
+                            stats.total_handshake_time += handshake_time;
+                        }
+                        changed = true;
+                    }
+                }
+            }
+            (TunnelState::Connected { .. }, Some(attempt)) => {
+                if let Some(throughput) = peak_throughput.filter(|throughput| *throughput > 0) {
+                    for hostname in &attempt.hostnames {
+                        let stats = Self::entry(&mut self.stats, hostname);
+                        stats.throughput_samples += 1;
+                        stats.total_throughput += throughput;
+                        changed = true;
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        match new_state {
+            TunnelState::Connecting { location, .. } => {
+                self.attempt = Some(Attempt {
+                    hostnames: location.as_ref().map(hostnames).unwrap_or_default(),
+                    started: Instant::now(),
+                });
+            }
+            TunnelState::Connected { .. } => (),
+            _ => self.attempt = None,
+        }
+
+        if changed {
+            self.truncate();
+        }
+        changed
+    }
+
+    fn entry<'a>(
+        stats: &'a mut BTreeMap<String, RelayStats>,
+        hostname: &str,
+    ) -> &'a mut RelayStats {
+        stats
+            .entry(hostname.to_owned())
+            .or_insert_with(|| RelayStats::new(hostname.to_owned()))
+    }
+
+    fn truncate(&mut self) {
+        while self.stats.len() > MAX_RELAYS {
+            let oldest = self
+                .stats
+                .values()
+                .min_by_key(|stats| stats.last_used)
+                .map(|stats| stats.hostname.clone());
+            match oldest {
+                Some(hostname) => self.stats.remove(&hostname),
+                None => break,
+            };
+        }
+    }
+
+    /// Returns the statistics of every relay that has been connected to, ordered by hostname.
+    pub fn list(&self) -> Vec<RelayStats> {
+        self.stats.values().cloned().collect()
+    }
+
+    /// Returns the hostnames of the relays that connecting to has failed consistently for.
+    pub fn bad_relays(&self) -> HashSet<String> {
+        self.stats
+            .values()
+            .filter(|stats| stats.is_bad())
+            .map(|stats| stats.hostname.clone())
+            .collect()
+    }
+
+    /// Returns the average time that it took to connect to each relay that has been connected to.
+    pub fn latencies(&self) -> HashMap<String, Duration> {
+        self.stats
+            .values()
+            .filter_map(|stats| {
+                stats
+                    .average_handshake_time()
+                    .map(|latency| (stats.hostname.clone(), latency))
+            })
+            .collect()
+    }
+
+    /// Forgets all statistics. An attempt that is in progress is not counted when it completes.
+    pub async fn clear(&mut self) {
+        self.stats.clear();
+        self.attempt = None;
+        self.save().await;
+    }
+
+    async fn save(&self) {
+        if self.stats.is_empty() {
+            if let Err(error) = fs::remove_file(&self.path).await {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to delete relay statistics")
+                    );
+                }
+            }
+            return;
+        }
+
+        match serde_json::to_string(&self.list()) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write relay statistics")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize relay statistics")
+                );
+            }
+        }
+    }
+}
+
+/// Returns the hostnames of the exit and entry relays of a connection.
+fn hostnames(location: &GeoIpLocation) -> Vec<String> {
+    location
+        .hostname
+        .iter()
+        .chain(location.entry_hostname.iter())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::{Endpoint, TransportProtocol, TunnelEndpoint, TunnelType};
+
+    fn store() -> RelayStatsStore {
+        RelayStatsStore {
+            path: PathBuf::new(),
+            stats: BTreeMap::new(),
+            attempt: None,
+        }
+    }
+
+    fn endpoint() -> TunnelEndpoint {
+        TunnelEndpoint {
+            endpoint: Endpoint::new([1, 2, 3, 4], 51820, TransportProtocol::Udp),
+            tunnel_type: TunnelType::Wireguard,
+            quantum_resistant: false,
+            proxy: None,
+            obfuscation: None,
+            entry_endpoint: None,
+            mtu: None,
+            wireguard_implementation: None,
+        }
+    }
+
+    fn location(hostname: &str) -> Option<GeoIpLocation> {
+        Some(GeoIpLocation {
+            ipv4: None,
+            ipv6: None,
+            country: "Sweden".to_owned(),
+            city: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            mullvad_exit_ip: true,
+            hostname: Some(hostname.to_owned()),
+            bridge_hostname: None,
+            entry_hostname: None,
+            obfuscator_hostname: None,
+        })
+    }
+
+    fn connecting(hostname: &str) -> TunnelState {
+        TunnelState::Connecting {
+            endpoint: endpoint(),
+            location: location(hostname),
+        }
+    }
+
+    fn connected(hostname: &str) -> TunnelState {
+        TunnelState::Connected {
+            endpoint: endpoint(),
+            location: location(hostname),
+        }
+    }
+
+    #[test]
+    fn test_records_attempts() {
+        let mut store = store();
+        assert!(!store.record(&TunnelState::Disconnected, &connecting("a"), None));
+        assert!(store.record(&connecting("a"), &connecting("b"), None));
+        assert!(store.record(&connecting("b"), &connected("b"), None));
+        assert!(store.record(&connected("b"), &TunnelState::Disconnected, Some(1000)));
+
+        let stats = store.list();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].attempts, stats[0].successes), (1, 0));
+        assert_eq!((stats[1].attempts, stats[1].successes), (1, 1));
+        assert_eq!(stats[1].average_throughput(), Some(1000));
+    }
+
+    #[test]
+    fn test_ignores_cancelled_attempts() {
+        let mut store = store();
+        store.record(&TunnelState::Disconnected, &connecting("a"), None);
+        assert!(!store.record(
+            &connecting("a"),
+            &TunnelState::Disconnecting(talpid_types::tunnel::ActionAfterDisconnect::Nothing),
+            None
+        ));
+        assert!(store.list().is_empty());
+    }
+
+    #[test]
+    fn test_bad_relays() {
+        let mut store = store();
+        store.record(&TunnelState::Disconnected, &connecting("a"), None);
+        for _ in 0..mullvad_types::relay_stats::MIN_ATTEMPTS {
+            store.record(&connecting("a"), &connecting("a"), None);
+        }
+        assert_eq!(store.bad_relays(), HashSet::from(["a".to_owned()]));
+    }
+}
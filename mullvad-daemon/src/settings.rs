@@ -1,23 +1,72 @@
 #[cfg(not(target_os = "android"))]
+use crate::settings_encryption::{self, Cipher};
+use crate::{
+    locked_settings::LockedSettings,
+    settings_history::{self, ChangeSource, HistoryEntry, SettingsHistory},
+};
+#[cfg(not(target_os = "android"))]
 use futures::TryFutureExt;
+use ipnetwork::IpNetwork;
+#[cfg(not(target_os = "android"))]
+use mullvad_types::settings::TunnelHookSettings;
 use mullvad_types::{
-    relay_constraints::{BridgeSettings, BridgeState, ObfuscationSettings, RelaySettingsUpdate},
-    settings::{DnsOptions, Settings},
-    wireguard::RotationInterval,
+    access_method::ApiAccessMethodSettings,
+    custom_list::{self, CustomListsSettings},
+    relay_constraints::{
+        BridgeSettings, BridgeState, ObfuscationSettings, RelaySettingsUpdate, SelectionStrategy,
+    },
+    relay_list::{RelayListMirror, RelayListUpdateInterval, RelayOverride},
+    settings::{
+        AppExclusionSettings, AutoConnectScope, DnsOptions, DnsState, ExitRelayChangePolicy,
+        ExpiryNotificationSettings, Settings, SettingsChangeError, SettingsChanged,
+        CURRENT_SETTINGS_VERSION,
+    },
+    wireguard::{self, RotationInterval},
 };
+#[cfg(not(target_os = "android"))]
+use mullvad_types::{custom_list::CustomList, relay_constraints::RelaySettings};
 #[cfg(target_os = "windows")]
 use std::collections::HashSet;
 use std::{
+    net::IpAddr,
     ops::Deref,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+#[cfg(windows)]
+use talpid_types::net::LinkLayerExemptions;
+#[cfg(target_os = "linux")]
+use talpid_types::net::{DnsBackend, PolicyRouting};
+use talpid_types::{
+    net::{
+        dns::is_valid_domain, wireguard::LivenessOptions, FirewallException, InboundTunnelPort,
+        OfflineDetection, ReconnectDebounce, RetryPolicy,
+    },
+    ErrorExt,
 };
-use talpid_types::ErrorExt;
 use tokio::{
     fs,
     io::{self, AsyncWriteExt},
 };
 
-const SETTINGS_FILE: &str = "settings.json";
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+
+/// Where an encrypted settings file is moved if it cannot be decrypted, so that it is not
+/// overwritten by the default settings. The key may only be unavailable for a while. A number is
+/// appended if an earlier backup already exists, so that it is not replaced.
+#[cfg(not(target_os = "android"))]
+const UNDECRYPTABLE_SETTINGS_FILE: &str = "settings.json.undecryptable";
+
+/// How many times the settings are read at startup if the keystore cannot be reached, since it
+/// may not be available right away after boot.
+#[cfg(not(target_os = "android"))]
+const KEYSTORE_ATTEMPTS: usize = 5;
+
+#[cfg(not(target_os = "android"))]
+const KEYSTORE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How often the settings file is checked for changes made by other programs.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(err_derive::Error, Debug)]
 #[error(no_from)]
@@ -40,45 +89,144 @@ pub enum Error {
 
     #[error(display = "Unable to set settings file permissions")]
     SetPermissions(#[error(source)] io::Error),
+
+    #[error(
+        display = "The settings file has version {}, which requires a restart to migrate",
+        _0
+    )]
+    UnsupportedVersion(u32),
+
+    #[error(display = "Unable to encrypt or decrypt the settings file")]
+    #[cfg(not(target_os = "android"))]
+    Encryption(#[error(source)] settings_encryption::Error),
+
+    #[error(display = "Locked by the administrator: {}", _0)]
+    Locked(String),
+
+    #[error(display = "The change was rejected")]
+    Rejected(#[error(source)] SettingsChangeError),
+
+    #[error(display = "Invalid custom list change")]
+    CustomList(#[error(source)] custom_list::Error),
+
+    #[error(display = "Invalid settings patch")]
+    InvalidPatch(#[error(source)] serde_json::Error),
+
+    #[error(display = "Invalid WireGuard MTU")]
+    InvalidMtu(#[error(source)] wireguard::MtuError),
+
+    #[error(display = "Invalid WireGuard keepalive interval")]
+    InvalidPersistentKeepalive(#[error(source)] wireguard::PersistentKeepaliveError),
+
+    #[error(display = "The WireGuard allowed IPs must include at least one network")]
+    NoAllowedIps,
+
+    #[error(display = "Invalid split DNS rule: {}", _0)]
+    InvalidSplitDnsRule(String),
+
+    #[error(display = "Invalid DNS filter rule: {}", _0)]
+    InvalidDnsFilterRule(String),
+
+    #[error(display = "Invalid DNS search domain: {}", _0)]
+    InvalidSearchDomain(String),
+
+    #[error(display = "The DNS server {} cannot be used, since {}", _0, _1)]
+    UnroutableDnsServer(IpAddr, &'static str),
+
+    #[error(display = "{} is not a private network", _0)]
+    NotLocalNetwork(IpNetwork),
 }
 
 #[derive(Debug)]
 pub struct SettingsPersister {
     settings: Settings,
+    /// The settings as they were last written to the file.
+    saved_settings: Settings,
     path: PathBuf,
+    history: SettingsHistory,
+    /// What changes that are saved are attributed to in the history.
+    change_source: ChangeSource,
+    /// Encrypts the settings file, if encryption is enabled.
+    #[cfg(not(target_os = "android"))]
+    cipher: Option<Cipher>,
+    /// Settings that the administrator does not allow to be changed.
+    locked: LockedSettings,
 }
 
 impl SettingsPersister {
     /// Loads user settings from file. If it fails, it returns the defaults.
     pub async fn load(settings_dir: &Path) -> Self {
-        let path = settings_dir.join(SETTINGS_FILE);
-        let (mut settings, mut should_save) = match Self::load_from_file(&path).await {
-            Ok(value) => value,
+        let mut persister = SettingsPersister {
+            settings: Self::default_settings(),
+            saved_settings: Self::default_settings(),
+            path: settings_dir.join(SETTINGS_FILE),
+            history: SettingsHistory::load(settings_dir).await,
+            change_source: ChangeSource::Client,
+            #[cfg(not(target_os = "android"))]
+            cipher: None,
+            locked: LockedSettings::load(settings_dir).await,
+        };
+
+        let mut result = persister.load_from_file().await;
+        #[cfg(not(target_os = "android"))]
+        for _ in 1..KEYSTORE_ATTEMPTS {
+            match &result {
+                Err(Error::Encryption(settings_encryption::Error::Keystore(error))) => {
+                    log::warn!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to reach the keystore. Retrying")
+                    );
+                    tokio::time::sleep(KEYSTORE_RETRY_DELAY).await;
+                    result = persister.load_from_file().await;
+                }
+                _ => break,
+            }
+        }
+
+        let mut should_save = match result {
+            Ok(should_save) => should_save,
             Err(error) => {
                 log::warn!(
                     "{}",
                     error.display_chain_with_msg("Failed to load settings. Using defaults.")
                 );
+                #[cfg(not(target_os = "android"))]
+                if let Error::Encryption(_) = error {
+                    persister.keep_undecryptable_file().await;
+                }
+
                 let mut settings = Self::default_settings();
 
                 // Protect the user by blocking the internet by default. Previous settings may
                 // not have caused the daemon to enter the non-blocking disconnected state.
                 settings.block_when_disconnected = true;
 
-                (settings, true)
+                persister.settings = settings;
+                true
             }
         };
 
         // Force IPv6 to be enabled on Android
         if cfg!(target_os = "android") {
-            should_save |=
-                Self::update_field(&mut settings.tunnel_options.generic.enable_ipv6, true);
+            should_save |= Self::update_field(
+                &mut persister.settings.tunnel_options.generic.enable_ipv6,
+                true,
+            );
         }
         if crate::version::is_beta_version() {
-            should_save |= Self::update_field(&mut settings.show_beta_releases, true);
+            should_save |= Self::update_field(&mut persister.settings.show_beta_releases, true);
+        }
+        let locked_settings = persister.locked.apply(persister.settings.clone());
+        if locked_settings != persister.settings {
+            persister.settings = locked_settings;
+            should_save = true;
         }
+        persister.saved_settings = persister.settings.clone();
 
-        let mut persister = SettingsPersister { settings, path };
+        #[cfg(not(target_os = "android"))]
+        if persister.cipher.is_some() {
+            persister.history.set_persistent(false).await;
+        }
 
         if should_save {
             if let Err(error) = persister.save().await {
@@ -92,32 +240,87 @@ impl SettingsPersister {
         persister
     }
 
-    async fn load_from_file(path: &Path) -> Result<(Settings, bool), Error> {
-        log::info!("Loading settings from {}", path.display());
+    /// Replaces the settings with those in the settings file. Returns whether the settings
+    /// should be saved, which is the case if there was no file.
+    async fn load_from_file(&mut self) -> Result<bool, Error> {
+        log::info!("Loading settings from {}", self.path.display());
 
-        let settings_bytes = match fs::read(path).await {
+        let settings_bytes = match fs::read(&self.path).await {
             Ok(bytes) => bytes,
             Err(error) => {
                 if error.kind() == io::ErrorKind::NotFound {
                     log::info!("No settings were found. Using defaults.");
-                    return Ok((Self::default_settings(), true));
+                    return Ok(true);
                 } else {
-                    return Err(Error::ReadError(path.display().to_string(), error));
+                    return Err(Error::ReadError(self.path.display().to_string(), error));
                 }
             }
         };
-        Ok((Self::load_from_bytes(&settings_bytes)?, false))
+        #[cfg(not(target_os = "android"))]
+        let settings_bytes = self.decrypt(settings_bytes).await?;
+        self.settings = Self::load_from_bytes(&settings_bytes)?;
+        Ok(false)
     }
 
     fn load_from_bytes(bytes: &[u8]) -> Result<Settings, Error> {
         serde_json::from_slice(bytes).map_err(Error::ParseError)
     }
 
+    /// Decrypts the contents of the settings file if it is encrypted. The key is loaded from the
+    /// keystore the first time an encrypted file is read. Plain files are returned as they are,
+    /// and are encrypted the next time the settings are saved.
+    #[cfg(not(target_os = "android"))]
+    async fn decrypt(&mut self, bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if !settings_encryption::is_encrypted(&bytes) {
+            return Ok(bytes);
+        }
+        if self.cipher.is_none() {
+            let cipher = Cipher::load(self.settings_dir())
+                .await
+                .map_err(Error::Encryption)?;
+            log::info!("The settings file is encrypted");
+            self.cipher = Some(cipher);
+        }
+        self.cipher
+            .as_ref()
+            .unwrap()
+            .decrypt(&bytes)
+            .map_err(Error::Encryption)
+    }
+
+    /// Moves an encrypted settings file that could not be decrypted out of the way.
+    #[cfg(not(target_os = "android"))]
+    async fn keep_undecryptable_file(&self) {
+        let backup = unused_backup_path(self.settings_dir()).await;
+        match fs::rename(&self.path, &backup).await {
+            Ok(()) => log::warn!(
+                "Moved the settings file that could not be decrypted to {}",
+                backup.display()
+            ),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to move the settings file out of the way")
+            ),
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn settings_dir(&self) -> &Path {
+        self.path
+            .parent()
+            .expect("settings file is not in a directory")
+    }
+
     /// Serializes the settings and saves them to the file it was loaded from.
     async fn save(&mut self) -> Result<(), Error> {
         log::debug!("Writing settings to {}", self.path.display());
 
-        let buffer = serde_json::to_string_pretty(&self.settings).map_err(Error::SerializeError)?;
+        let buffer = serde_json::to_vec_pretty(&self.settings).map_err(Error::SerializeError)?;
+        #[cfg(not(target_os = "android"))]
+        let buffer = match &self.cipher {
+            Some(cipher) => cipher.encrypt(&buffer),
+            None => buffer,
+        };
         let mut options = fs::OpenOptions::new();
         #[cfg(unix)]
         {
@@ -130,7 +333,7 @@ impl SettingsPersister {
             .open(&self.path)
             .await
             .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
-        file.write_all(&buffer.into_bytes())
+        file.write_all(&buffer)
             .await
             .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
 
@@ -155,15 +358,22 @@ impl SettingsPersister {
             .await
             .map_err(|e| Error::WriteError(self.path.display().to_string(), e))?;
 
+        self.history
+            .record(&self.saved_settings, &self.settings, self.change_source)
+            .await;
+        self.saved_settings = self.settings.clone();
+
         Ok(())
     }
 
     /// Resets default settings
     #[cfg(not(target_os = "android"))]
     pub async fn reset(&mut self) -> Result<(), Error> {
-        self.settings = Self::default_settings();
+        self.settings = self.locked.apply(Self::default_settings());
         let path = self.path.clone();
-        self.save()
+        let source = std::mem::replace(&mut self.change_source, ChangeSource::Reset);
+        let result = self
+            .save()
             .or_else(|e| async move {
                 log::error!(
                     "{}",
@@ -174,13 +384,155 @@ impl SettingsPersister {
                     .map_err(|e| Error::DeleteError(path.display().to_string(), e))
                     .await
             })
-            .await
+            .await;
+        self.change_source = source;
+        result
     }
 
     pub fn to_settings(&self) -> Settings {
         self.settings.clone()
     }
 
+    /// Returns whether the settings file is encrypted at rest.
+    #[cfg(not(target_os = "android"))]
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Enables or disables encryption of the settings file. Enabling it creates a new key in the
+    /// keystore of the OS. Disabling it writes the settings in plain text and removes the key.
+    #[cfg(not(target_os = "android"))]
+    pub async fn set_encryption(&mut self, enabled: bool) -> Result<bool, Error> {
+        if enabled == self.cipher.is_some() {
+            return Ok(false);
+        }
+
+        let previous_cipher = if enabled {
+            let cipher = Cipher::create(self.settings_dir())
+                .await
+                .map_err(Error::Encryption)?;
+            self.cipher.replace(cipher)
+        } else {
+            self.cipher.take()
+        };
+        if let Err(error) = self.save().await {
+            self.cipher = previous_cipher;
+            return Err(error);
+        }
+        self.history.set_persistent(!enabled).await;
+
+        if !enabled {
+            if let Err(error) = Cipher::remove(self.settings_dir()).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to remove the settings key")
+                );
+            }
+        }
+        Ok(true)
+    }
+
+    /// Fails if the setting at `path`, or any part of it, is locked by the administrator. This
+    /// is for changes that bypass the settings, such as temporarily not blocking the internet.
+    pub fn ensure_unlocked(&self, path: &str) -> Result<(), Error> {
+        if self.locked.is_locked(path) {
+            return Err(Error::Locked(path.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Attributes the changes that are saved from now on to `source` in the settings history.
+    pub fn set_change_source(&mut self, source: ChangeSource) {
+        self.change_source = source;
+    }
+
+    /// Returns the recorded changes to the settings, oldest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.entries()
+    }
+
+    /// Returns the current settings with the change `id` in the history undone. The settings are
+    /// not changed, so that the caller can apply them in the same way as other changes.
+    pub fn rollback(&self, id: u64) -> Result<Settings, settings_history::Error> {
+        self.history.rollback(id, &self.settings)
+    }
+
+    /// Returns the current settings with `patch` merged into them. The patch is a JSON object with
+    /// the same layout as the settings file, such as `{"allow_lan": true}`. It is rejected as a
+    /// whole if the result would be invalid or would change a locked setting. The current
+    /// settings are left untouched, so that the caller can apply the changes in the same way as
+    /// when they are made through the daemon.
+    pub fn patch(&self, patch: &str) -> Result<Settings, Error> {
+        let settings = patched(&self.settings, patch)?;
+        self.validate(&settings)?;
+        Ok(settings)
+    }
+
+    /// Fails if `settings` would change a locked setting, or if any of the settings is one that
+    /// the daemon would refuse to set. This lets changes to many settings at once be rejected
+    /// before any of them is applied.
+    pub fn validate(&self, settings: &Settings) -> Result<(), Error> {
+        let violations = self.locked.violations(settings);
+        if !violations.is_empty() {
+            return Err(Error::Locked(violations.join(", ")));
+        }
+        validate_settings(settings)
+    }
+
+    /// Reads the settings file back, in case it has been modified by another program. Returns
+    /// the settings in the file if they differ from the current ones. The current settings are
+    /// left untouched, so that the caller can apply the changes in the same way as when they are
+    /// made through the daemon.
+    pub async fn read_external_changes(&mut self) -> Result<Option<Settings>, Error> {
+        let settings_bytes = match fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                log::warn!("The settings file was removed. Writing the current settings again");
+                self.save().await?;
+                return Ok(None);
+            }
+            Err(error) => return Err(Error::ReadError(self.path.display().to_string(), error)),
+        };
+        #[cfg(not(target_os = "android"))]
+        let settings_bytes = self.decrypt(settings_bytes).await?;
+        let settings = self.locked.apply(Self::load_from_bytes(&settings_bytes)?);
+        if settings.get_settings_version() != CURRENT_SETTINGS_VERSION {
+            return Err(Error::UnsupportedVersion(
+                settings.get_settings_version() as u32
+            ));
+        }
+        validate_settings(&settings)?;
+        if settings == self.settings {
+            return Ok(None);
+        }
+        Ok(Some(settings))
+    }
+
+    /// Polls the settings file in the background and calls `on_change` once the file has been
+    /// modified and has then stayed the same for a while, so that a file that is still being
+    /// written is not read. Stops when `on_change` returns false.
+    pub fn watch(&self, on_change: impl Fn() -> bool + Send + 'static) {
+        let path = self.path.clone();
+        tokio::spawn(async move {
+            let mut last_seen = file_version(&path).await;
+            let mut changed = false;
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let version = file_version(&path).await;
+                if version != last_seen {
+                    last_seen = version;
+                    changed = true;
+                } else if changed {
+                    changed = false;
+                    if !on_change() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
     /// Modifies `Settings::default()` somewhat, e.g. depending on whether a beta version
     /// is being run or not.
     fn default_settings() -> Settings {
@@ -194,16 +546,55 @@ impl SettingsPersister {
     pub async fn update_relay_settings(
         &mut self,
         update: RelaySettingsUpdate,
-    ) -> Result<bool, Error> {
-        let should_save = self.settings.update_relay_settings(update);
-        self.update(should_save).await
+    ) -> Result<SettingsChanged, Error> {
+        let changed = self.settings.update_relay_settings(update);
+        self.update(changed.is_changed()).await.map(|_| changed)
     }
 
     pub async fn set_allow_lan(&mut self, allow_lan: bool) -> Result<bool, Error> {
+        validate_custom_dns_servers(
+            &self.settings.tunnel_options.dns_options,
+            allow_lan,
+            self.settings.tunnel_options.generic.enable_ipv6,
+        )?;
         let should_save = Self::update_field(&mut self.settings.allow_lan, allow_lan);
         self.update(should_save).await
     }
 
+    pub async fn set_allow_multicast(&mut self, allow_multicast: bool) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.allow_multicast, allow_multicast);
+        self.update(should_save).await
+    }
+
+    pub async fn set_allowed_lan_nets(
+        &mut self,
+        allowed_lan_nets: Option<Vec<IpNetwork>>,
+    ) -> Result<bool, Error> {
+        validate_allowed_lan_nets(&allowed_lan_nets)?;
+        let should_save = Self::update_field(&mut self.settings.allowed_lan_nets, allowed_lan_nets);
+        self.update(should_save).await
+    }
+
+    pub async fn set_firewall_exceptions(
+        &mut self,
+        firewall_exceptions: Vec<FirewallException>,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.firewall_exceptions, firewall_exceptions);
+        self.update(should_save).await
+    }
+
+    pub async fn set_inbound_tunnel_ports(
+        &mut self,
+        inbound_tunnel_ports: Vec<InboundTunnelPort>,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.inbound_tunnel_ports,
+            inbound_tunnel_ports,
+        );
+        self.update(should_save).await
+    }
+
     pub async fn set_block_when_disconnected(
         &mut self,
         block_when_disconnected: bool,
@@ -215,11 +606,188 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
+    #[cfg(not(target_os = "android"))]
+    pub async fn set_strict_enforcement(
+        &mut self,
+        strict_enforcement: bool,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.strict_enforcement, strict_enforcement);
+        self.update(should_save).await
+    }
+
     pub async fn set_auto_connect(&mut self, auto_connect: bool) -> Result<bool, Error> {
         let should_save = Self::update_field(&mut self.settings.auto_connect, auto_connect);
         self.update(should_save).await
     }
 
+    pub async fn set_auto_connect_scope(
+        &mut self,
+        auto_connect_scope: AutoConnectScope,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.auto_connect_scope, auto_connect_scope);
+        self.update(should_save).await
+    }
+
+    pub async fn set_exit_relay_change_policy(
+        &mut self,
+        policy: ExitRelayChangePolicy,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.exit_relay_change_policy, policy);
+        self.update(should_save).await
+    }
+
+    pub async fn set_expiry_notifications(
+        &mut self,
+        expiry_notifications: ExpiryNotificationSettings,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.expiry_notifications,
+            expiry_notifications,
+        );
+        self.update(should_save).await
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub async fn set_tunnel_hooks(
+        &mut self,
+        tunnel_hooks: TunnelHookSettings,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.tunnel_hooks, tunnel_hooks);
+        self.update(should_save).await
+    }
+
+    pub async fn set_disconnect_warning_threshold(
+        &mut self,
+        threshold: Option<u64>,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.disconnect_warning_threshold, threshold);
+        self.update(should_save).await
+    }
+
+    pub async fn set_reconnect_debounce(
+        &mut self,
+        reconnect_debounce: ReconnectDebounce,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.reconnect_debounce, reconnect_debounce);
+        self.update(should_save).await
+    }
+
+    pub async fn set_offline_detection(
+        &mut self,
+        offline_detection: OfflineDetection,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.offline_detection, offline_detection);
+        self.update(should_save).await
+    }
+
+    pub async fn set_relay_list_update_interval(
+        &mut self,
+        interval: RelayListUpdateInterval,
+    ) -> Result<bool, Error> {
+        let should_save =
+            Self::update_field(&mut self.settings.relay_list_update_interval, interval);
+        self.update(should_save).await
+    }
+
+    pub async fn set_relay_list_mirror(
+        &mut self,
+        mirror: Option<RelayListMirror>,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.relay_list_mirror, mirror);
+        self.update(should_save).await
+    }
+
+    pub async fn set_relay_overrides(
+        &mut self,
+        overrides: Vec<RelayOverride>,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.relay_overrides, overrides);
+        self.update(should_save).await
+    }
+
+    pub async fn set_api_access_methods(
+        &mut self,
+        access_methods: ApiAccessMethodSettings,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.api_access_methods, access_methods);
+        self.update(should_save).await
+    }
+
+    pub async fn set_custom_lists(
+        &mut self,
+        custom_lists: CustomListsSettings,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.custom_lists, custom_lists);
+        self.update(should_save).await
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub async fn create_custom_list(&mut self, list: CustomList) -> Result<bool, Error> {
+        self.settings
+            .custom_lists
+            .create(list)
+            .map_err(Error::CustomList)?;
+        self.update(true).await
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub async fn update_custom_list(&mut self, list: CustomList) -> Result<bool, Error> {
+        if self.settings.custom_lists.get(&list.name) == Some(&list) {
+            return Ok(false);
+        }
+        self.settings
+            .custom_lists
+            .update(list)
+            .map_err(Error::CustomList)?;
+        self.update(true).await
+    }
+
+    /// Removes the list named `name`. Lists that the relay settings refer to cannot be removed,
+    /// since the relay selector would then have nothing to connect to.
+    #[cfg(not(target_os = "android"))]
+    pub async fn delete_custom_list(&mut self, name: &str) -> Result<bool, Error> {
+        if let RelaySettings::Normal(constraints) = &self.settings.get_relay_settings() {
+            if constraints.references_custom_list(name) {
+                return Err(Error::CustomList(custom_list::Error::ListInUse(
+                    name.to_owned(),
+                )));
+            }
+        }
+        self.settings
+            .custom_lists
+            .delete(name)
+            .map_err(Error::CustomList)?;
+        self.update(true).await
+    }
+
+    pub async fn set_circumvention_assistant(&mut self, enabled: bool) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.circumvention_assistant, enabled);
+        self.update(should_save).await
+    }
+
+    pub async fn set_avoid_bad_relays(&mut self, enabled: bool) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.avoid_bad_relays, enabled);
+        self.update(should_save).await
+    }
+
+    pub async fn set_relay_selection_strategy(
+        &mut self,
+        strategy: SelectionStrategy,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.relay_selection_strategy, strategy);
+        self.update(should_save).await
+    }
+
+    pub async fn set_relay_failure_cooldown(&mut self, cooldown: Duration) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.relay_failure_cooldown, cooldown);
+        self.update(should_save).await
+    }
+
     pub async fn set_openvpn_mssfix(&mut self, openvpn_mssfix: Option<u16>) -> Result<bool, Error> {
         let should_save = Self::update_field(
             &mut self.settings.tunnel_options.openvpn.mssfix,
@@ -229,6 +797,11 @@ impl SettingsPersister {
     }
 
     pub async fn set_enable_ipv6(&mut self, enable_ipv6: bool) -> Result<bool, Error> {
+        validate_custom_dns_servers(
+            &self.settings.tunnel_options.dns_options,
+            self.settings.allow_lan,
+            enable_ipv6,
+        )?;
         let should_save = Self::update_field(
             &mut self.settings.tunnel_options.generic.enable_ipv6,
             enable_ipv6,
@@ -236,6 +809,23 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
+    #[cfg(target_os = "linux")]
+    pub async fn set_rate_limit(&mut self, rate_limit: Option<u64>) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.tunnel_options.generic.rate_limit,
+            rate_limit,
+        );
+        self.update(should_save).await
+    }
+
+    pub async fn set_retry_policy(&mut self, retry_policy: RetryPolicy) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.tunnel_options.generic.retry_policy,
+            retry_policy,
+        );
+        self.update(should_save).await
+    }
+
     pub async fn set_quantum_resistant_tunnel(
         &mut self,
         use_pq_safe_psk: bool,
@@ -253,6 +843,12 @@ impl SettingsPersister {
     }
 
     pub async fn set_dns_options(&mut self, options: DnsOptions) -> Result<bool, Error> {
+        validate_dns_options(&options)?;
+        validate_custom_dns_servers(
+            &options,
+            self.settings.allow_lan,
+            self.settings.tunnel_options.generic.enable_ipv6,
+        )?;
         let should_save =
             Self::update_field(&mut self.settings.tunnel_options.dns_options, options);
         self.update(should_save).await
@@ -264,6 +860,60 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
+    pub async fn set_wireguard_liveness(
+        &mut self,
+        liveness: LivenessOptions,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.tunnel_options.wireguard.options.liveness,
+            liveness,
+        );
+        self.update(should_save).await
+    }
+
+    pub async fn set_wireguard_persistent_keepalive(
+        &mut self,
+        interval: u16,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self
+                .settings
+                .tunnel_options
+                .wireguard
+                .options
+                .persistent_keepalive,
+            interval,
+        );
+        self.update(should_save).await
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub async fn set_wireguard_allowed_ips(
+        &mut self,
+        allowed_ips: Option<Vec<IpNetwork>>,
+    ) -> Result<bool, Error> {
+        if allowed_ips.as_ref().map(Vec::is_empty).unwrap_or(false) {
+            return Err(Error::NoAllowedIps);
+        }
+        let should_save = Self::update_field(
+            &mut self.settings.tunnel_options.wireguard.options.allowed_ips,
+            allowed_ips,
+        );
+        self.update(should_save).await
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn set_wireguard_namespace(
+        &mut self,
+        namespace: Option<String>,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(
+            &mut self.settings.tunnel_options.wireguard.options.namespace,
+            namespace,
+        );
+        self.update(should_save).await
+    }
+
     pub async fn set_wireguard_rotation_interval(
         &mut self,
         interval: Option<RotationInterval>,
@@ -284,6 +934,16 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
+    pub async fn set_app_exclusions(
+        &mut self,
+        mut app_exclusions: AppExclusionSettings,
+    ) -> Result<bool, Error> {
+        app_exclusions.excluded_packages.sort();
+        app_exclusions.excluded_packages.dedup();
+        let should_save = Self::update_field(&mut self.settings.app_exclusions, app_exclusions);
+        self.update(should_save).await
+    }
+
     pub async fn set_bridge_settings(
         &mut self,
         bridge_settings: BridgeSettings,
@@ -292,9 +952,15 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
-    pub async fn set_bridge_state(&mut self, bridge_state: BridgeState) -> Result<bool, Error> {
-        let should_save = self.settings.set_bridge_state(bridge_state);
-        self.update(should_save).await
+    pub async fn set_bridge_state(
+        &mut self,
+        bridge_state: BridgeState,
+    ) -> Result<SettingsChanged, Error> {
+        let changed = self
+            .settings
+            .set_bridge_state(bridge_state)
+            .map_err(Error::Rejected)?;
+        self.update(changed.is_changed()).await.map(|_| changed)
     }
 
     #[cfg(windows)]
@@ -327,6 +993,27 @@ impl SettingsPersister {
         self.update(should_save).await
     }
 
+    #[cfg(windows)]
+    pub async fn set_link_layer_exemptions(
+        &mut self,
+        exemptions: LinkLayerExemptions,
+    ) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.link_layer_exemptions, exemptions);
+        self.update(should_save).await
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn set_dns_backend(&mut self, backend: DnsBackend) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.dns_backend, backend);
+        self.update(should_save).await
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn set_policy_routing(&mut self, routing: PolicyRouting) -> Result<bool, Error> {
+        let should_save = Self::update_field(&mut self.settings.policy_routing, routing);
+        self.update(should_save).await
+    }
+
     fn update_field<T: Eq>(field: &mut T, new_value: T) -> bool {
         if *field != new_value {
             *field = new_value;
@@ -349,14 +1036,163 @@ impl SettingsPersister {
     }
 
     async fn update(&mut self, should_save: bool) -> Result<bool, Error> {
-        if should_save {
-            self.save().await.map(|_| true)
-        } else {
-            Ok(false)
+        if !should_save {
+            return Ok(false);
+        }
+        let violations = self.locked.violations(&self.settings);
+        if !violations.is_empty() {
+            // Undo the whole change, rather than only the locked part of it
+            self.settings = self.saved_settings.clone();
+            return Err(Error::Locked(violations.join(", ")));
         }
+        self.save().await.map(|_| true)
     }
 }
 
+/// Checks the settings that the daemon validates when they are set one at a time.
+fn validate_settings(settings: &Settings) -> Result<(), Error> {
+    if let Some(mtu) = settings.tunnel_options.wireguard.options.mtu {
+        wireguard::validate_mtu(mtu).map_err(Error::InvalidMtu)?;
+    }
+    wireguard::validate_persistent_keepalive(
+        settings
+            .tunnel_options
+            .wireguard
+            .options
+            .persistent_keepalive,
+    )
+    .map_err(Error::InvalidPersistentKeepalive)?;
+    #[cfg(not(target_os = "android"))]
+    if let Some(allowed_ips) = &settings.tunnel_options.wireguard.options.allowed_ips {
+        if allowed_ips.is_empty() {
+            return Err(Error::NoAllowedIps);
+        }
+    }
+    validate_dns_options(&settings.tunnel_options.dns_options)?;
+    validate_custom_dns_servers(
+        &settings.tunnel_options.dns_options,
+        settings.allow_lan,
+        settings.tunnel_options.generic.enable_ipv6,
+    )?;
+    if settings.get_bridge_state() == BridgeState::On
+        && !RelaySettingsUpdate::from(settings.get_relay_settings()).supports_bridge()
+    {
+        return Err(Error::Rejected(SettingsChangeError::BridgeNotSupported));
+    }
+    validate_allowed_lan_nets(&settings.allowed_lan_nets)
+}
+
+/// Rejects LAN networks that are not private, since traffic to them would bypass the tunnel.
+fn validate_allowed_lan_nets(allowed_lan_nets: &Option<Vec<IpNetwork>>) -> Result<(), Error> {
+    for net in allowed_lan_nets.iter().flatten() {
+        if !talpid_core::firewall::is_local_network(net) {
+            return Err(Error::NotLocalNetwork(*net));
+        }
+    }
+    Ok(())
+}
+
+fn validate_dns_options(options: &DnsOptions) -> Result<(), Error> {
+    if let Some(rule) = options.split_rules.iter().find(|rule| !rule.is_valid()) {
+        return Err(Error::InvalidSplitDnsRule(rule.domain.clone()));
+    }
+    if let Some(rule) = options.filter_rules.iter().find(|rule| !rule.is_valid()) {
+        return Err(Error::InvalidDnsFilterRule(rule.domain.clone()));
+    }
+    match options
+        .search_domains
+        .iter()
+        .find(|domain| !is_valid_domain(domain))
+    {
+        Some(domain) => Err(Error::InvalidSearchDomain(domain.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Rejects custom DNS servers that cannot be reached with the given LAN and IPv6 settings. This
+/// is checked when either side changes, so that the combination is never saved.
+fn validate_custom_dns_servers(
+    options: &DnsOptions,
+    allow_lan: bool,
+    enable_ipv6: bool,
+) -> Result<(), Error> {
+    if options.state != DnsState::Custom {
+        return Ok(());
+    }
+    for server in &options.custom_options.addresses {
+        if let Some(reason) = unroutable_reason(server, allow_lan, enable_ipv6) {
+            return Err(Error::UnroutableDnsServer(*server, reason));
+        }
+    }
+    Ok(())
+}
+
+fn unroutable_reason(server: &IpAddr, allow_lan: bool, enable_ipv6: bool) -> Option<&'static str> {
+    match server {
+        IpAddr::V4(v4) if v4.is_unspecified() || v4.is_multicast() || v4.is_broadcast() => {
+            return Some("it is not a unicast address");
+        }
+        IpAddr::V6(v6) if v6.is_unspecified() || v6.is_multicast() => {
+            return Some("it is not a unicast address");
+        }
+        _ => (),
+    }
+    if server.is_loopback() && !allow_lan {
+        // A resolver on this host needs to reach its own upstream resolvers on the LAN
+        return Some("local network sharing is disabled");
+    }
+    if let IpAddr::V6(v6) = server {
+        let prefix = v6.segments()[0];
+        let is_local = v6.is_loopback() || prefix & 0xfe00 == 0xfc00 || prefix & 0xffc0 == 0xfe80;
+        if !is_local && !enable_ipv6 {
+            return Some("IPv6 is disabled in the tunnel");
+        }
+    }
+    None
+}
+
+/// Applies `patch` to `settings` as a JSON merge patch (RFC 7396): objects are merged key by key,
+/// `null` resets a setting to its default, and any other value replaces the current one. The
+/// settings version is kept, since the settings are migrated before they reach the daemon.
+fn patched(settings: &Settings, patch: &str) -> Result<Settings, Error> {
+    let mut patch: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(patch).map_err(Error::InvalidPatch)?;
+    patch.remove("settings_version");
+    let mut value = serde_json::to_value(settings).map_err(Error::SerializeError)?;
+    merge_patch(&mut value, &patch);
+    serde_json::from_value(value).map_err(Error::InvalidPatch)
+}
+
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Map<String, serde_json::Value>) {
+    if !target.is_object() {
+        *target = serde_json::Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        match value {
+            serde_json::Value::Null => {
+                // Missing settings are deserialized as their defaults
+                target.remove(key);
+            }
+            serde_json::Value::Object(patch) => {
+                merge_patch(
+                    target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                    patch,
+                );
+            }
+            value => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Returns the modification time and size of a file, or `None` if it cannot be read.
+async fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).await.ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
 impl Deref for SettingsPersister {
     type Target = Settings;
 
@@ -365,10 +1201,28 @@ impl Deref for SettingsPersister {
     }
 }
 
+/// Returns a path for a backup of an undecryptable settings file that does not replace any
+/// earlier backup.
+#[cfg(not(target_os = "android"))]
+async fn unused_backup_path(settings_dir: &Path) -> PathBuf {
+    let mut backup = settings_dir.join(UNDECRYPTABLE_SETTINGS_FILE);
+    let mut number = 1;
+    while fs::symlink_metadata(&backup).await.is_ok() {
+        backup = settings_dir.join(format!("{}.{}", UNDECRYPTABLE_SETTINGS_FILE, number));
+        number += 1;
+    }
+    backup
+}
+
 #[cfg(test)]
 mod test {
-    use super::SettingsPersister;
-    use mullvad_types::settings::SettingsVersion;
+    use super::{
+        patched, validate_custom_dns_servers, validate_dns_options, validate_settings, Error,
+        SettingsPersister,
+    };
+    #[cfg(not(target_os = "android"))]
+    use super::{unused_backup_path, UNDECRYPTABLE_SETTINGS_FILE};
+    use mullvad_types::settings::{DnsOptions, DnsState, Settings, SettingsVersion};
     use serde_json;
 
     #[test]
@@ -446,4 +1300,167 @@ mod test {
 
         let _ = SettingsPersister::load_from_bytes(settings).unwrap();
     }
+
+    #[test]
+    fn test_patch() {
+        let settings = Settings::default();
+        let patch = r#"{
+            "allow_lan": true,
+            "tunnel_options": { "wireguard": { "mtu": 1280 } },
+            "settings_version": 2
+        }"#;
+        let new_settings = patched(&settings, patch).unwrap();
+        assert!(new_settings.allow_lan);
+        assert_eq!(
+            new_settings.tunnel_options.wireguard.options.mtu,
+            Some(1280)
+        );
+        assert_eq!(
+            new_settings.tunnel_options.generic,
+            settings.tunnel_options.generic
+        );
+        assert_eq!(
+            new_settings.get_settings_version(),
+            settings.get_settings_version()
+        );
+    }
+
+    #[test]
+    fn test_invalid_patch() {
+        let settings = Settings::default();
+        assert!(matches!(
+            patched(&settings, r#"{"allow_lan": "yes"}"#),
+            Err(Error::InvalidPatch(_))
+        ));
+        assert!(matches!(
+            patched(&settings, "[]"),
+            Err(Error::InvalidPatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_allowed_lan_nets() {
+        let settings = Settings::default();
+        let private = patched(&settings, r#"{"allowed_lan_nets": ["192.168.1.0/24"]}"#).unwrap();
+        assert!(validate_settings(&private).is_ok());
+        let public = patched(&settings, r#"{"allowed_lan_nets": ["8.8.8.0/24"]}"#).unwrap();
+        assert!(matches!(
+            validate_settings(&public),
+            Err(Error::NotLocalNetwork(_))
+        ));
+        let too_wide = patched(&settings, r#"{"allowed_lan_nets": ["192.0.0.0/8"]}"#).unwrap();
+        assert!(matches!(
+            validate_settings(&too_wide),
+            Err(Error::NotLocalNetwork(_))
+        ));
+    }
+
+    #[test]
+    fn test_patch_null_resets() {
+        let mut settings = Settings::default();
+        settings.allow_lan = true;
+        settings.tunnel_options.wireguard.options.mtu = Some(1300);
+        let patch = r#"{"allow_lan": null, "tunnel_options": {"wireguard": {"mtu": null}}}"#;
+        let new_settings = patched(&settings, patch).unwrap();
+        assert!(!new_settings.allow_lan);
+        assert_eq!(new_settings.tunnel_options.wireguard.options.mtu, None);
+    }
+
+    #[test]
+    fn test_persistent_keepalive_default() {
+        let mut settings = Settings::default();
+        settings
+            .tunnel_options
+            .wireguard
+            .options
+            .persistent_keepalive = 0;
+        let patch = r#"{"tunnel_options": {"wireguard": {"persistent_keepalive": null}}}"#;
+        let new_settings = patched(&settings, patch).unwrap();
+        assert_eq!(
+            new_settings
+                .tunnel_options
+                .wireguard
+                .options
+                .persistent_keepalive,
+            talpid_types::net::wireguard::DEFAULT_PERSISTENT_KEEPALIVE
+        );
+    }
+
+    #[test]
+    fn test_invalid_search_domain() {
+        let mut options = DnsOptions::default();
+        options.search_domains = vec!["corp.example".to_owned()];
+        assert!(validate_dns_options(&options).is_ok());
+
+        options.search_domains.push("corp..example".to_owned());
+        assert!(matches!(
+            validate_dns_options(&options),
+            Err(Error::InvalidSearchDomain(domain)) if domain == "corp..example"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_dns_filter_rule() {
+        use talpid_types::net::dns::{DnsFilterAction, DnsFilterRule};
+
+        let mut options = DnsOptions::default();
+        options.filter_rules = vec![DnsFilterRule {
+            domain: "*.ads.example".to_owned(),
+            action: DnsFilterAction::Block,
+        }];
+        assert!(validate_dns_options(&options).is_ok());
+
+        options.filter_rules.push(DnsFilterRule {
+            domain: "router.home".to_owned(),
+            action: DnsFilterAction::Rewrite(vec![]),
+        });
+        assert!(matches!(
+            validate_dns_options(&options),
+            Err(Error::InvalidDnsFilterRule(domain)) if domain == "router.home"
+        ));
+    }
+
+    #[test]
+    fn test_unroutable_dns_server() {
+        let mut options = DnsOptions::default();
+        options.state = DnsState::Custom;
+        options.custom_options.addresses = vec![
+            "192.168.1.1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+        ];
+        let ipv6_server = options.custom_options.addresses[1];
+        assert!(validate_custom_dns_servers(&options, false, true).is_ok());
+        assert!(matches!(
+            validate_custom_dns_servers(&options, false, false),
+            Err(Error::UnroutableDnsServer(server, _)) if server == ipv6_server
+        ));
+
+        options.custom_options.addresses = vec!["127.0.0.1".parse().unwrap()];
+        assert!(validate_custom_dns_servers(&options, true, false).is_ok());
+        assert!(validate_custom_dns_servers(&options, false, false).is_err());
+
+        options.custom_options.addresses = vec!["0.0.0.0".parse().unwrap()];
+        assert!(validate_custom_dns_servers(&options, true, true).is_err());
+
+        options.state = DnsState::Default;
+        assert!(validate_custom_dns_servers(&options, true, true).is_ok());
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[tokio::test]
+    async fn test_undecryptable_backups_are_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = unused_backup_path(dir.path()).await;
+        assert_eq!(first, dir.path().join(UNDECRYPTABLE_SETTINGS_FILE));
+
+        std::fs::write(&first, "").unwrap();
+        let second = unused_backup_path(dir.path()).await;
+        assert_eq!(second, dir.path().join("settings.json.undecryptable.1"));
+
+        std::fs::write(&second, "").unwrap();
+        assert_eq!(
+            unused_backup_path(dir.path()).await,
+            dir.path().join("settings.json.undecryptable.2")
+        );
+    }
 }
@@ -0,0 +1,51 @@
+//! Linux has no keystore that the daemon can use when it runs as the systemd service. The Secret
+//! Service is only available on the session bus of a logged in user, and stays locked until that
+//! user unlocks it, so the settings could not be read at boot. Storing a key is therefore not
+//! supported, and enabling encryption fails.
+//!
+//! A key that was stored with `secret-tool` from libsecret by an earlier version can still be
+//! loaded and removed, so that such settings can be read and encryption be disabled.
+
+use super::command_error;
+use std::{io, path::Path};
+use tokio::process::Command;
+
+const SECRET_TOOL: &str = "secret-tool";
+const ATTRIBUTES: &[&str] = &["application", "mullvad-vpn", "type", "settings-key"];
+
+pub async fn store_secret(_settings_dir: &Path, _secret: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the system service has no keystore to hold the key",
+    ))
+}
+
+pub async fn load_secret(_settings_dir: &Path) -> io::Result<Option<String>> {
+    let output = Command::new(SECRET_TOOL)
+        .arg("lookup")
+        .args(ATTRIBUTES)
+        .output()
+        .await?;
+    if !output.status.success() {
+        // `secret-tool lookup` fails without a message when there is no matching secret
+        if output.stderr.is_empty() {
+            return Ok(None);
+        }
+        return Err(command_error(SECRET_TOOL, &output));
+    }
+    String::from_utf8(output.stdout)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub async fn remove_secret(_settings_dir: &Path) -> io::Result<()> {
+    let output = Command::new(SECRET_TOOL)
+        .arg("clear")
+        .args(ATTRIBUTES)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(command_error(SECRET_TOOL, &output));
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Stores the key as a generic password in the system keychain, using the `security` tool.
+
+use super::command_error;
+use std::{io, path::Path, process::Stdio};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+const SECURITY: &str = "/usr/bin/security";
+const KEYCHAIN: &str = "/Library/Keychains/System.keychain";
+const SERVICE: &str = "net.mullvad.vpn.settings-key";
+const ACCOUNT: &str = "mullvad-daemon";
+
+/// Exit code of `security` when no matching item exists in the keychain
+/// (`errSecItemNotFound`).
+const ITEM_NOT_FOUND: i32 = 44;
+
+pub async fn store_secret(settings_dir: &Path, secret: &str) -> io::Result<()> {
+    // The command is given on stdin, in interactive mode, so that the secret is not visible in
+    // the process list.
+    let mut child = Command::new(SECURITY)
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(
+            format!(
+                "add-generic-password -U -a {} -s {} -w {} {}\n",
+                ACCOUNT, SERVICE, secret, KEYCHAIN
+            )
+            .as_bytes(),
+        )
+        .await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(command_error(SECURITY, &output));
+    }
+
+    // Interactive mode does not report whether the command failed
+    if load_secret(settings_dir).await?.as_deref().map(str::trim) != Some(secret) {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "the key could not be added to the keychain: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn load_secret(_settings_dir: &Path) -> io::Result<Option<String>> {
+    let output = Command::new(SECURITY)
+        .args(&[
+            "find-generic-password",
+            "-a",
+            ACCOUNT,
+            "-s",
+            SERVICE,
+            "-w",
+            KEYCHAIN,
+        ])
+        .output()
+        .await?;
+    match output.status.code() {
+        Some(0) => String::from_utf8(output.stdout)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        Some(ITEM_NOT_FOUND) => Ok(None),
+        _ => Err(command_error(SECURITY, &output)),
+    }
+}
+
+pub async fn remove_secret(_settings_dir: &Path) -> io::Result<()> {
+    let output = Command::new(SECURITY)
+        .args(&[
+            "delete-generic-password",
+            "-a",
+            ACCOUNT,
+            "-s",
+            SERVICE,
+            KEYCHAIN,
+        ])
+        .output()
+        .await?;
+    match output.status.code() {
+        Some(0) | Some(ITEM_NOT_FOUND) => Ok(()),
+        _ => Err(command_error(SECURITY, &output)),
+    }
+}
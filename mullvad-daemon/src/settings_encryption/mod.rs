@@ -0,0 +1,212 @@
+//! Optional encryption of the settings file at rest.
+//!
+//! The settings are encrypted with ChaCha20-Poly1305, using a random key that is held by the
+//! keystore of the OS: DPAPI on Windows and the system keychain on macOS. There is no keystore
+//! that the system service can use on Linux, so encryption cannot be enabled there. An encrypted
+//! file starts with `MAGIC`, followed by the nonce and the ciphertext. Since a JSON file can
+//! never start with `MAGIC`, plain settings files are still read as they are.
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+use std::{fmt, io, path::Path};
+
+#[cfg(target_os = "linux")]
+#[path = "linux.rs"]
+mod keystore;
+
+#[cfg(target_os = "macos")]
+#[path = "macos.rs"]
+mod keystore;
+
+#[cfg(windows)]
+#[path = "windows.rs"]
+mod keystore;
+
+const MAGIC: &[u8] = b"MULLVAD-ENCRYPTED-SETTINGS-V1\n";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to access the key in the keystore")]
+    Keystore(#[error(source)] io::Error),
+
+    #[error(display = "Encryption of the settings is not supported on this platform")]
+    Unsupported,
+
+    #[error(display = "The key of the encrypted settings is not in the keystore")]
+    MissingKey,
+
+    #[error(display = "The key in the keystore is invalid")]
+    InvalidKey,
+
+    #[error(display = "The encrypted settings are damaged or were encrypted with another key")]
+    Decrypt,
+}
+
+/// Returns whether `data` was encrypted by a [`Cipher`].
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+#[derive(Clone)]
+pub struct Cipher {
+    key: [u8; KEY_LEN],
+}
+
+impl Cipher {
+    /// Creates a new random key and stores it in the keystore, replacing any previous key.
+    pub async fn create(settings_dir: &Path) -> Result<Self, Error> {
+        let mut key = [0u8; KEY_LEN];
+        rand::thread_rng().fill_bytes(&mut key);
+        keystore::store_secret(settings_dir, &to_hex(&key))
+            .await
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::Unsupported => Error::Unsupported,
+                _ => Error::Keystore(error),
+            })?;
+        Ok(Cipher { key })
+    }
+
+    /// Loads the key from the keystore.
+    pub async fn load(settings_dir: &Path) -> Result<Self, Error> {
+        let secret = keystore::load_secret(settings_dir)
+            .await
+            .map_err(Error::Keystore)?
+            .ok_or(Error::MissingKey)?;
+        let key = from_hex(secret.trim())
+            .and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+            .ok_or(Error::InvalidKey)?;
+        Ok(Cipher { key })
+    }
+
+    /// Removes the key from the keystore. Anything encrypted with it can no longer be decrypted.
+    pub async fn remove(settings_dir: &Path) -> Result<(), Error> {
+        keystore::remove_secret(settings_dir)
+            .await
+            .map_err(Error::Keystore)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .aead()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: MAGIC,
+                },
+            )
+            .expect("Failed to encrypt settings");
+
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        data
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let data = data.strip_prefix(MAGIC).ok_or(Error::Decrypt)?;
+        if data.len() < NONCE_LEN {
+            return Err(Error::Decrypt);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.aead()
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: MAGIC,
+                },
+            )
+            .map_err(|_| Error::Decrypt)
+    }
+
+    fn aead(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Turns the output of a keystore program that failed into an error.
+#[cfg(unix)]
+fn command_error(program: &str, output: &std::process::Output) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "{} failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cipher() -> Cipher {
+        Cipher {
+            key: [7u8; KEY_LEN],
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let plaintext = br#"{"allow_lan": true}"#;
+        let encrypted = cipher().encrypt(plaintext);
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(plaintext));
+        assert_eq!(cipher().decrypt(&encrypted).unwrap(), plaintext);
+
+        let other = Cipher {
+            key: [8u8; KEY_LEN],
+        };
+        assert!(matches!(other.decrypt(&encrypted), Err(Error::Decrypt)));
+
+        let mut damaged = encrypted.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(matches!(cipher().decrypt(&damaged), Err(Error::Decrypt)));
+        assert!(matches!(
+            cipher().decrypt(&encrypted[..MAGIC.len() + 4]),
+            Err(Error::Decrypt)
+        ));
+    }
+
+    #[test]
+    fn test_hex() {
+        let key = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(to_hex(&key), "007fabff");
+        assert_eq!(from_hex("007fabff").unwrap(), key);
+        assert_eq!(from_hex("007"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! Stores the key in a file next to the settings, protected with DPAPI. Since the daemon runs as
+//! `SYSTEM`, only processes running as `SYSTEM` can decrypt the key.
+
+use std::{io, path::Path, ptr, slice};
+use tokio::fs;
+use winapi::um::{
+    dpapi::{CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN},
+    winbase::LocalFree,
+    wincrypt::DATA_BLOB,
+};
+
+const KEY_FILE: &str = "settings.key";
+
+pub async fn store_secret(settings_dir: &Path, secret: &str) -> io::Result<()> {
+    let protected = protect(secret.as_bytes())?;
+    fs::write(settings_dir.join(KEY_FILE), protected).await
+}
+
+pub async fn load_secret(settings_dir: &Path) -> io::Result<Option<String>> {
+    let protected = match fs::read(settings_dir.join(KEY_FILE)).await {
+        Ok(protected) => protected,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    String::from_utf8(unprotect(&protected)?)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub async fn remove_secret(settings_dir: &Path) -> io::Result<()> {
+    match fs::remove_file(settings_dir.join(KEY_FILE)).await {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+fn protect(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut input = blob(data);
+    let mut output = blob(&[]);
+    let result = unsafe {
+        CryptProtectData(
+            &mut input,
+            ptr::null(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { take_blob(output) })
+}
+
+fn unprotect(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut input = blob(data);
+    let mut output = blob(&[]);
+    let result = unsafe {
+        CryptUnprotectData(
+            &mut input,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { take_blob(output) })
+}
+
+fn blob(data: &[u8]) -> DATA_BLOB {
+    DATA_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    }
+}
+
+/// Copies the data of a blob that was allocated by DPAPI and frees it.
+unsafe fn take_blob(blob: DATA_BLOB) -> Vec<u8> {
+    let data = slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+    LocalFree(blob.pbData as _);
+    data
+}
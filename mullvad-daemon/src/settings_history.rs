@@ -0,0 +1,309 @@
+//! Keeps a bounded history of the changes made to the settings, so that a change that was made
+//! by mistake can be found and undone.
+
+use chrono::{offset::Utc, DateTime};
+use mullvad_types::settings::Settings;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const HISTORY_FILE: &str = "settings-history.json";
+
+/// Maximum number of changes to keep. The oldest ones are dropped first.
+const MAX_ENTRIES: usize = 50;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "There is no change with ID {} in the settings history", _0)]
+    UnknownChange(u64),
+
+    #[error(
+        display = "The change can no longer be undone, since a later change replaced \"{}\"",
+        _0
+    )]
+    Conflict(String),
+
+    #[error(display = "The settings would be invalid after undoing the change")]
+    InvalidSettings(#[error(source)] serde_json::Error),
+}
+
+/// What made a change to the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    /// A client of the daemon, such as the GUI or the CLI.
+    Client,
+    /// Another program that edited the settings file.
+    SettingsFile,
+    /// Undoing the change with this ID.
+    Rollback(u64),
+    /// Resetting all settings to their defaults.
+    Reset,
+}
+
+/// A setting that changed, found by its path in the settings file, such as
+/// `["tunnel_options", "wireguard", "mtu"]`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SettingChange {
+    pub path: Vec<String>,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+/// All settings that changed when the settings were saved once.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub source: ChangeSource,
+    pub changes: Vec<SettingChange>,
+}
+
+#[derive(Debug)]
+pub struct SettingsHistory {
+    entries: VecDeque<HistoryEntry>,
+    path: PathBuf,
+    /// Whether the history is written to disk.
+    persistent: bool,
+}
+
+impl SettingsHistory {
+    /// Loads the history from the settings directory. An empty history is used if it cannot be
+    /// read.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(HISTORY_FILE);
+        let entries = match fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse settings history")
+                );
+                VecDeque::new()
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read settings history")
+                    );
+                }
+                VecDeque::new()
+            }
+        };
+        SettingsHistory {
+            entries,
+            path,
+            persistent: true,
+        }
+    }
+
+    /// Records the settings that differ between `old_settings` and `new_settings`, if any.
+    pub async fn record(
+        &mut self,
+        old_settings: &Settings,
+        new_settings: &Settings,
+        source: ChangeSource,
+    ) {
+        let changes = diff(
+            &serde_json::to_value(old_settings).unwrap(),
+            &serde_json::to_value(new_settings).unwrap(),
+        );
+        if changes.is_empty() {
+            return;
+        }
+
+        let id = self.entries.back().map(|entry| entry.id + 1).unwrap_or(1);
+        self.entries.push_back(HistoryEntry {
+            id,
+            time: Utc::now(),
+            source,
+            changes,
+        });
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+
+        if self.persistent {
+            self.save().await;
+        }
+    }
+
+    /// Sets whether the history is written to disk. The history holds previous values of the
+    /// settings, so it is only kept in memory while the settings file is encrypted.
+    #[cfg(not(target_os = "android"))]
+    pub async fn set_persistent(&mut self, persistent: bool) {
+        if persistent == self.persistent {
+            return;
+        }
+        self.persistent = persistent;
+        if persistent {
+            self.save().await;
+        } else if let Err(error) = fs::remove_file(&self.path).await {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to remove settings history")
+                );
+            }
+        }
+    }
+
+    async fn save(&self) {
+        let buffer = serde_json::to_vec(&self.entries).unwrap();
+        if let Err(error) = fs::write(&self.path, buffer).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to write settings history")
+            );
+        }
+    }
+
+    /// Returns the recorded changes, oldest first.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Returns `current` with the settings that were changed by the change `id` set back to the
+    /// values they had before it. Other settings are left as they are.
+    pub fn rollback(&self, id: u64, current: &Settings) -> Result<Settings, Error> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .ok_or(Error::UnknownChange(id))?;
+
+        let mut settings = serde_json::to_value(current).unwrap();
+        for change in &entry.changes {
+            let value = lookup_mut(&mut settings, &change.path)
+                .ok_or_else(|| Error::Conflict(change.path.join(".")))?;
+            *value = change.old_value.clone();
+        }
+        serde_json::from_value(settings).map_err(Error::InvalidSettings)
+    }
+}
+
+/// Returns the values that differ between `old` and `new`. Objects are compared key by key, and
+/// objects whose keys differ are reported as a whole. This keeps enum variants together, so that
+/// each change can be undone on its own.
+fn diff(old: &Value, new: &Value) -> Vec<SettingChange> {
+    let mut changes = vec![];
+    diff_inner(&mut vec![], old, new, &mut changes);
+    changes
+}
+
+fn diff_inner(path: &mut Vec<String>, old: &Value, new: &Value, changes: &mut Vec<SettingChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) if same_keys(old_map, new_map) => {
+            for (key, old_value) in old_map {
+                path.push(key.clone());
+                diff_inner(path, old_value, &new_map[key], changes);
+                path.pop();
+            }
+        }
+        _ if old != new => changes.push(SettingChange {
+            path: path.clone(),
+            old_value: old.clone(),
+            new_value: new.clone(),
+        }),
+        _ => (),
+    }
+}
+
+fn same_keys(a: &Map<String, Value>, b: &Map<String, Value>) -> bool {
+    a.len() == b.len() && a.keys().all(|key| b.contains_key(key))
+}
+
+fn lookup_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter()
+        .try_fold(value, |value, key| value.as_object_mut()?.get_mut(key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({
+            "allow_lan": false,
+            "relay_settings": { "normal": { "location": "any" } },
+            "tunnel_options": { "wireguard": { "mtu": null, "rotation_interval": 24 } },
+        });
+        let new = json!({
+            "allow_lan": true,
+            "relay_settings": { "custom_tunnel_endpoint": { "host": "example.com" } },
+            "tunnel_options": { "wireguard": { "mtu": 1380, "rotation_interval": 24 } },
+        });
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            changes,
+            vec![
+                SettingChange {
+                    path: vec!["allow_lan".to_owned()],
+                    old_value: json!(false),
+                    new_value: json!(true),
+                },
+                SettingChange {
+                    path: vec!["relay_settings".to_owned()],
+                    old_value: old["relay_settings"].clone(),
+                    new_value: new["relay_settings"].clone(),
+                },
+                SettingChange {
+                    path: vec![
+                        "tunnel_options".to_owned(),
+                        "wireguard".to_owned(),
+                        "mtu".to_owned()
+                    ],
+                    old_value: json!(null),
+                    new_value: json!(1380),
+                },
+            ]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = SettingsHistory::load(dir.path()).await;
+
+        let original = Settings::default();
+        let mut changed = original.clone();
+        changed.block_when_disconnected = !original.block_when_disconnected;
+        history
+            .record(&original, &changed, ChangeSource::Client)
+            .await;
+        let mut later = changed.clone();
+        later.allow_lan = !changed.allow_lan;
+        history.record(&changed, &later, ChangeSource::Client).await;
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 1);
+
+        let restored = history.rollback(1, &later).unwrap();
+        assert_eq!(
+            restored.block_when_disconnected,
+            original.block_when_disconnected
+        );
+        assert_eq!(restored.allow_lan, later.allow_lan);
+
+        assert!(matches!(
+            history.rollback(3, &later),
+            Err(Error::UnknownChange(3))
+        ));
+
+        // The history is kept across restarts
+        let reloaded = SettingsHistory::load(dir.path()).await;
+        assert_eq!(reloaded.entries(), entries);
+    }
+}
@@ -0,0 +1,210 @@
+//! Snapshots of the state of the daemon, for reproducing problems that users report.
+//!
+//! A snapshot holds the settings, the relay list and the target state that the relay selector
+//! works from. Credentials are removed when the snapshot is taken, so that it can be attached to
+//! a bug report. A daemon started with `--load-snapshot` restores the snapshot into empty
+//! settings and cache directories instead of using those of the machine.
+
+use crate::version;
+use chrono::{offset::Utc, DateTime};
+use mullvad_problem_report::redact;
+use mullvad_types::{
+    access_method::AccessMethod,
+    relay_constraints::{BridgeSettings, RelaySettings, RelaySettingsUpdate},
+    relay_list::RelayList,
+    settings::Settings,
+    states::{TargetState, TunnelState},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use talpid_types::net::openvpn::ProxySettings;
+use tokio::{fs, io};
+
+/// Version of the snapshot format. Bump this when making incompatible changes to `Snapshot`.
+const FORMAT_VERSION: u32 = 1;
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to read snapshot")]
+    Read(#[error(source)] io::Error),
+
+    #[error(display = "Failed to parse snapshot")]
+    Parse(#[error(source)] serde_json::Error),
+
+    #[error(display = "The snapshot was taken by a newer version of the daemon")]
+    UnsupportedVersion,
+
+    #[error(display = "Failed to write the state in the snapshot")]
+    Restore(#[error(source)] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    format_version: u32,
+    daemon_version: String,
+    taken: DateTime<Utc>,
+    settings: Settings,
+    relay_list: RelayList,
+    target_state: TargetState,
+    /// The tunnel state when the snapshot was taken. It is not restored, since it depends on the
+    /// network and the relays.
+    tunnel_state: String,
+    /// Settings that were left out or changed because they contained credentials.
+    redacted: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn new(
+        mut settings: Settings,
+        relay_list: RelayList,
+        target_state: TargetState,
+        tunnel_state: &TunnelState,
+    ) -> Self {
+        let redacted = redact_settings(&mut settings);
+        let tunnel_state = serde_json::to_string(tunnel_state)
+            .map(|state| redact(&state))
+            .unwrap_or_default();
+        Snapshot {
+            format_version: FORMAT_VERSION,
+            daemon_version: version::PRODUCT_VERSION.to_owned(),
+            taken: Utc::now(),
+            settings,
+            relay_list,
+            target_state,
+            tunnel_state,
+            redacted,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize snapshot")
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read(path).await.map_err(Error::Read)?;
+        let snapshot: Self = serde_json::from_slice(&contents).map_err(Error::Parse)?;
+        if snapshot.format_version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+        log::info!(
+            "Loaded snapshot taken {} by daemon version {}",
+            snapshot.taken.to_rfc3339(),
+            snapshot.daemon_version
+        );
+        if !snapshot.redacted.is_empty() {
+            log::info!(
+                "Settings that were redacted in the snapshot: {}",
+                snapshot.redacted.join(", ")
+            );
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the settings, relay list and target state to the files that the daemon reads them
+    /// from when it starts.
+    pub async fn restore(&self, settings_dir: &Path, cache_dir: &Path) -> Result<(), Error> {
+        fs::write(
+            settings_dir.join(crate::settings::SETTINGS_FILE),
+            serde_json::to_string_pretty(&self.settings).unwrap(),
+        )
+        .await
+        .map_err(Error::Restore)?;
+        fs::write(
+            cache_dir.join(mullvad_relay_selector::RELAYS_FILENAME),
+            serde_json::to_string(&self.relay_list).unwrap(),
+        )
+        .await
+        .map_err(Error::Restore)?;
+        fs::write(
+            cache_dir.join(crate::target_state::TARGET_START_STATE_FILE),
+            serde_json::to_string(&self.target_state).unwrap(),
+        )
+        .await
+        .map_err(Error::Restore)
+    }
+}
+
+/// Removes credentials from `settings`, and returns the names of the settings that were changed.
+fn redact_settings(settings: &mut Settings) -> Vec<String> {
+    let mut redacted = vec![];
+
+    if let RelaySettings::CustomTunnelEndpoint(_) = settings.get_relay_settings() {
+        settings.update_relay_settings(RelaySettingsUpdate::Normal(Default::default()));
+        redacted.push("custom tunnel endpoint".to_owned());
+    }
+
+    if let BridgeSettings::Custom(ref mut proxy) = settings.bridge_settings {
+        match proxy {
+            ProxySettings::Local(_) => (),
+            ProxySettings::Remote(remote) => {
+                if let Some(auth) = &mut remote.auth {
+                    auth.password = REDACTED.to_owned();
+                    redacted.push("custom bridge password".to_owned());
+                }
+            }
+            ProxySettings::Shadowsocks(shadowsocks) => {
+                shadowsocks.password = REDACTED.to_owned();
+                redacted.push("custom bridge password".to_owned());
+            }
+        }
+    }
+
+    for method in &mut settings.api_access_methods.methods {
+        let password = match &mut method.access_method {
+            AccessMethod::Shadowsocks(shadowsocks) => Some(&mut shadowsocks.password),
+            AccessMethod::Socks5(proxy) | AccessMethod::Http(proxy) => {
+                proxy.auth.as_mut().map(|auth| &mut auth.password)
+            }
+            AccessMethod::Direct | AccessMethod::Bridges => None,
+        };
+        if let Some(password) = password {
+            *password = REDACTED.to_owned();
+            redacted.push(format!("password of API access method \"{}\"", method.name));
+        }
+    }
+
+    redacted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::access_method::ApiAccessMethod;
+    use talpid_types::net::openvpn::ShadowsocksProxySettings;
+
+    fn shadowsocks() -> ShadowsocksProxySettings {
+        ShadowsocksProxySettings {
+            peer: "192.0.2.1:443".parse().unwrap(),
+            password: "secret".to_owned(),
+            cipher: "aes-256-gcm".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_redact_passwords() {
+        let mut settings = Settings::default();
+        settings.bridge_settings =
+            BridgeSettings::Custom(ProxySettings::Shadowsocks(shadowsocks()));
+        settings.api_access_methods.methods.push(ApiAccessMethod {
+            name: "proxy".to_owned(),
+            enabled: true,
+            access_method: AccessMethod::Shadowsocks(shadowsocks()),
+        });
+
+        let redacted = redact_settings(&mut settings);
+
+        assert_eq!(redacted.len(), 2);
+        assert!(!serde_json::to_string(&settings).unwrap().contains("secret"));
+    }
+
+    #[test]
+    fn test_redact_nothing() {
+        let mut settings = Settings::default();
+        let original = settings.clone();
+
+        assert!(redact_settings(&mut settings).is_empty());
+        assert_eq!(settings, original);
+    }
+}
@@ -8,7 +8,7 @@ use tokio::{fs, io};
 
 /// State to use by default if there is no cache.
 const DEFAULT_TARGET_STATE: TargetState = TargetState::Unsecured;
-const TARGET_START_STATE_FILE: &str = "target-start-state.json";
+pub(crate) const TARGET_START_STATE_FILE: &str = "target-start-state.json";
 
 /// Persists the target state to a file, which is only removed if the instance is dropped cleanly.
 pub struct PersistentTargetState {
@@ -0,0 +1,206 @@
+use crate::data_usage::DataUsageStore;
+use chrono::offset::Utc;
+use futures::future::{abortable, AbortHandle};
+use mullvad_types::data_usage::{DataUsage, SessionDataUsage, TrafficCounters};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use talpid_types::{tunnel::TunnelInterface, ErrorExt};
+
+/// How often the traffic counters of the tunnel interface are read.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the monthly data usage is saved while connected.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Keeps track of how much data passes through the tunnel interface while connected, so that an
+/// interruption can be held back while the tunnel is busy, and so that the data usage of each
+/// session and month is known.
+pub struct TransferMonitor {
+    throughput: Arc<Mutex<Option<u64>>>,
+    peak_throughput: Arc<Mutex<Option<u64>>>,
+    session: Arc<Mutex<Option<SessionDataUsage>>>,
+    data_usage: DataUsageStore,
+    job: Option<AbortHandle>,
+}
+
+impl TransferMonitor {
+    pub fn new(data_usage: DataUsageStore) -> Self {
+        TransferMonitor {
+            throughput: Arc::new(Mutex::new(None)),
+            peak_throughput: Arc::new(Mutex::new(None)),
+            session: Arc::new(Mutex::new(None)),
+            data_usage,
+            job: None,
+        }
+    }
+
+    /// Starts sampling the traffic counters of `interface`, or stops sampling if there is none.
+    pub fn tunnel_state_changed(&mut self, interface: Option<&TunnelInterface>) {
+        if let Some(job) = self.job.take() {
+            job.abort();
+            let data_usage = self.data_usage.clone();
+            tokio::spawn(async move { data_usage.save().await });
+        }
+        *self.throughput.lock().unwrap() = None;
+        *self.peak_throughput.lock().unwrap() = None;
+        *self.session.lock().unwrap() = None;
+
+        let interface = match interface {
+            Some(interface) => interface.name.clone(),
+            None => return,
+        };
+        *self.session.lock().unwrap() = Some(SessionDataUsage {
+            started: Utc::now(),
+            traffic: TrafficCounters::default(),
+        });
+        let throughput = self.throughput.clone();
+        let peak_throughput = self.peak_throughput.clone();
+        let session = self.session.clone();
+        let data_usage = self.data_usage.clone();
+        let (sampler, job) = abortable(async move {
+            let mut previous: Option<(TrafficCounters, Instant)> = None;
+            let mut last_saved = Instant::now();
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let counters = match transferred_bytes(&interface) {
+                    Ok(counters) => counters,
+                    Err(error) => {
+                        log::debug!(
+                            "{}",
+                            error.display_chain_with_msg(&format!(
+                                "Failed to read the traffic counters of {}",
+                                interface
+                            ))
+                        );
+                        continue;
+                    }
+                };
+                let now = Instant::now();
+                if let Some((previous_counters, previous_time)) = previous {
+                    // Counters that wrap around or are reset read as no traffic for a sample.
+                    let transferred = TrafficCounters {
+                        received: counters.received.saturating_sub(previous_counters.received),
+                        sent: counters.sent.saturating_sub(previous_counters.sent),
+                    };
+                    if let Some(session) = session.lock().unwrap().as_mut() {
+                        session.traffic.add(transferred);
+                    }
+                    data_usage.add(transferred);
+
+                    let elapsed = now.duration_since(previous_time).as_secs_f64();
+                    if elapsed > 0.0 {
+                        let rate = (transferred.total() as f64 / elapsed) as u64;
+                        *throughput.lock().unwrap() = Some(rate);
+                        let mut peak = peak_throughput.lock().unwrap();
+                        *peak = Some(peak.map_or(rate, |peak| peak.max(rate)));
+                    }
+                }
+                previous = Some((counters, now));
+
+                if last_saved.elapsed() >= SAVE_INTERVAL {
+                    data_usage.save().await;
+                    last_saved = Instant::now();
+                }
+            }
+        });
+        tokio::spawn(sampler);
+        self.job = Some(job);
+    }
+
+    /// Returns the number of bytes per second that passed through the tunnel, in both directions,
+    /// during the last sample. This is `None` while not connected, and until the first sample has
+    /// completed.
+    pub fn throughput(&self) -> Option<u64> {
+        *self.throughput.lock().unwrap()
+    }
+
+    /// Returns the highest throughput of any sample since the tunnel was connected.
+    pub fn peak_throughput(&self) -> Option<u64> {
+        *self.peak_throughput.lock().unwrap()
+    }
+
+    pub fn data_usage(&self) -> DataUsage {
+        DataUsage {
+            session: *self.session.lock().unwrap(),
+            months: self.data_usage.months(),
+        }
+    }
+}
+
+impl Drop for TransferMonitor {
+    fn drop(&mut self) {
+        if let Some(job) = self.job.take() {
+            job.abort();
+        }
+    }
+}
+
+/// Returns the number of bytes received and sent on `interface`.
+#[cfg(target_os = "linux")]
+fn transferred_bytes(interface: &str) -> io::Result<TrafficCounters> {
+    let statistics = std::path::Path::new("/sys/class/net")
+        .join(interface)
+        .join("statistics");
+    let read = |name: &str| -> io::Result<u64> {
+        std::fs::read_to_string(statistics.join(name))?
+            .trim()
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid traffic counter"))
+    };
+    Ok(TrafficCounters {
+        received: read("rx_bytes")?,
+        sent: read("tx_bytes")?,
+    })
+}
+
+/// Returns the number of bytes received and sent on `interface`. The counters in `if_data` are
+/// 32 bits wide, so they wrap around after 4 GiB.
+#[cfg(target_os = "macos")]
+fn transferred_bytes(interface: &str) -> io::Result<TrafficCounters> {
+    use std::ffi::CStr;
+
+    let mut addresses: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addresses) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut bytes = None;
+    let mut current = addresses;
+    while !current.is_null() {
+        let entry = unsafe { &*current };
+        current = entry.ifa_next;
+        if entry.ifa_addr.is_null()
+            || entry.ifa_data.is_null()
+            || i32::from(unsafe { (*entry.ifa_addr).sa_family }) != libc::AF_LINK
+        {
+            continue;
+        }
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) };
+        if name.to_bytes() == interface.as_bytes() {
+            let data = unsafe { &*(entry.ifa_data as *const libc::if_data) };
+            bytes = Some(TrafficCounters {
+                received: u64::from(data.ifi_ibytes),
+                sent: u64::from(data.ifi_obytes),
+            });
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(addresses) };
+    bytes.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "interface not found"))
+}
+
+/// Returns the number of bytes received and sent on `interface`.
+#[cfg(windows)]
+fn transferred_bytes(interface: &str) -> io::Result<TrafficCounters> {
+    use talpid_core::windows;
+
+    let luid = windows::luid_from_alias(interface)?;
+    let row = windows::get_if_entry(&luid)?;
+    Ok(TrafficCounters {
+        received: row.InOctets,
+        sent: row.OutOctets,
+    })
+}
@@ -1,4 +1,4 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{future::Future, path::PathBuf, pin::Pin, sync::Arc};
 
 use tokio::sync::Mutex;
 
@@ -8,7 +8,7 @@ use mullvad_types::{
 };
 use talpid_core::tunnel_state_machine::TunnelParametersGenerator;
 use talpid_types::{
-    net::{wireguard, TunnelParameters},
+    net::{obfuscation::ObfuscatorConfig, wireguard, TunnelParameters},
     tunnel::ParameterGenerationError,
     ErrorExt,
 };
@@ -16,7 +16,10 @@ use talpid_types::{
 #[cfg(not(target_os = "android"))]
 use talpid_types::net::openvpn;
 
-use crate::device::{AccountManagerHandle, PrivateAccountAndDevice};
+use crate::{
+    device::{AccountManagerHandle, PrivateAccountAndDevice},
+    external_transports,
+};
 
 #[derive(err_derive::Error, Debug)]
 pub enum Error {
@@ -31,6 +34,9 @@ pub enum Error {
 
     #[error(display = "Failed to resolve hostname for custom relay")]
     ResolveCustomHostname,
+
+    #[error(display = "The external transport may not be run")]
+    InvalidTransport(#[error(source)] external_transports::Error),
 }
 
 #[derive(Clone)]
@@ -40,6 +46,8 @@ struct InnerParametersGenerator {
     relay_selector: RelaySelector,
     tunnel_options: TunnelOptions,
     account_manager: AccountManagerHandle,
+    /// Directory that external transport executables must be placed in.
+    transports_dir: PathBuf,
 
     // TODO: Move this to `RelaySelector`?
     last_generated_relays: Option<LastSelectedRelays>,
@@ -51,12 +59,14 @@ impl ParametersGenerator {
         account_manager: AccountManagerHandle,
         relay_selector: RelaySelector,
         tunnel_options: TunnelOptions,
+        transports_dir: PathBuf,
     ) -> Self {
         Self(Arc::new(Mutex::new(InnerParametersGenerator {
             tunnel_options,
             relay_selector,
 
             account_manager,
+            transports_dir,
 
             last_generated_relays: None,
         })))
@@ -67,6 +77,17 @@ impl ParametersGenerator {
         self.0.lock().await.tunnel_options = tunnel_options.clone();
     }
 
+    /// Gets the relay that traffic exits through, as it was when the last tunnel parameters were
+    /// generated.
+    pub async fn get_last_exit_relay(&self) -> Option<Relay> {
+        let inner = self.0.lock().await;
+        match inner.last_generated_relays.as_ref()? {
+            LastSelectedRelays::WireGuard { wg_exit, .. } => Some(wg_exit.clone()),
+            #[cfg(not(target_os = "android"))]
+            LastSelectedRelays::OpenVpn { relay, .. } => Some(relay.clone()),
+        }
+    }
+
     /// Gets the location associated with the last generated tunnel parameters.
     pub async fn get_last_location(&self) -> Option<GeoIpLocation> {
         let inner = self.0.lock().await;
@@ -122,6 +143,10 @@ impl ParametersGenerator {
 impl InnerParametersGenerator {
     async fn generate(&mut self, retry_attempt: u32) -> Result<TunnelParameters, Error> {
         let _data = self.device().await?;
+        if retry_attempt > 0 {
+            // The previous attempt did not result in a working tunnel
+            self.report_failed_relays();
+        }
         match self.relay_selector.get_relay(retry_attempt) {
             Ok((SelectedRelay::Custom(custom_relay), _bridge, _obfsucator)) => {
                 custom_relay
@@ -132,6 +157,25 @@ impl InnerParametersGenerator {
                         Error::ResolveCustomHostname
                     })
             }
+            Ok((SelectedRelay::CustomExit(relay), _bridge, obfuscator)) => {
+                // The custom relay is not one of ours, so there is nothing to report if it fails
+                self.last_generated_relays = None;
+                let tunnel = Self::tunnel_config(self.device().await?);
+                let obfuscator_config =
+                    self.check_obfuscator(obfuscator.map(|obfuscator| obfuscator.config))?;
+                relay
+                    .exit
+                    .to_multihop_tunnel_parameters(
+                        tunnel,
+                        relay.entry_endpoint.peer,
+                        self.tunnel_options.clone(),
+                        obfuscator_config,
+                    )
+                    .map_err(|e| {
+                        log::error!("Failed to resolve hostname for custom tunnel config: {}", e);
+                        Error::ResolveCustomHostname
+                    })
+            }
             Ok((SelectedRelay::Normal(constraints), bridge, obfuscator)) => {
                 self.create_tunnel_parameters(
                     &constraints.exit_relay,
@@ -190,18 +234,13 @@ impl InnerParametersGenerator {
                 unreachable!("OpenVPN is not supported on Android");
             }
             MullvadEndpoint::Wireguard(endpoint) => {
-                let tunnel = wireguard::TunnelConfig {
-                    private_key: data.device.wg_data.private_key,
-                    addresses: vec![
-                        data.device.wg_data.addresses.ipv4_address.ip().into(),
-                        data.device.wg_data.addresses.ipv6_address.ip().into(),
-                    ],
-                };
+                let tunnel = Self::tunnel_config(data);
 
                 let (obfuscator_relay, obfuscator_config) = match obfuscator {
                     Some(obfuscator) => (Some(obfuscator.relay), Some(obfuscator.config)),
                     None => (None, None),
                 };
+                let obfuscator_config = self.check_obfuscator(obfuscator_config)?;
 
                 self.last_generated_relays = Some(LastSelectedRelays::WireGuard {
                     wg_entry: entry_relay.clone(),
@@ -226,6 +265,54 @@ impl InnerParametersGenerator {
         }
     }
 
+    /// Checks that an external transport is still safe to launch, since the executable may have
+    /// been replaced after the settings were saved. The resolved path is the one that is run.
+    fn check_obfuscator(
+        &self,
+        config: Option<ObfuscatorConfig>,
+    ) -> Result<Option<ObfuscatorConfig>, Error> {
+        match config {
+            Some(ObfuscatorConfig::External {
+                endpoint,
+                mut transport,
+            }) => {
+                transport.path =
+                    external_transports::validate(&self.transports_dir, &transport.path)
+                        .map_err(Error::InvalidTransport)?;
+                Ok(Some(ObfuscatorConfig::External {
+                    endpoint,
+                    transport,
+                }))
+            }
+            config => Ok(config),
+        }
+    }
+
+    /// Tells the relay selector to prefer other relays than the last ones for a while.
+    fn report_failed_relays(&self) {
+        let relays: Vec<&Relay> = match &self.last_generated_relays {
+            Some(LastSelectedRelays::WireGuard {
+                wg_entry, wg_exit, ..
+            }) => std::iter::once(wg_exit).chain(wg_entry.iter()).collect(),
+            #[cfg(not(target_os = "android"))]
+            Some(LastSelectedRelays::OpenVpn { relay, .. }) => vec![relay],
+            None => vec![],
+        };
+        for relay in relays {
+            self.relay_selector.report_failed_relay(&relay.hostname);
+        }
+    }
+
+    fn tunnel_config(data: PrivateAccountAndDevice) -> wireguard::TunnelConfig {
+        wireguard::TunnelConfig {
+            private_key: data.device.wg_data.private_key,
+            addresses: vec![
+                data.device.wg_data.addresses.ipv4_address.ip().into(),
+                data.device.wg_data.addresses.ipv6_address.ip().into(),
+            ],
+        }
+    }
+
     async fn device(&self) -> Result<PrivateAccountAndDevice, Error> {
         self.account_manager
             .data()
@@ -0,0 +1,360 @@
+use mullvad_types::{settings::TunnelHookSettings, states::TunnelState};
+use std::{
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use talpid_types::{tunnel::TunnelInterface, ErrorExt};
+use tokio::{process::Command, sync::mpsc};
+
+/// Name of the directory in the settings directory that hook scripts must be placed in.
+pub const HOOKS_DIR: &str = "hooks";
+
+/// How long a hook script may run before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Hook scripts must be placed in {}", _0)]
+    OutsideHooksDir(String),
+
+    #[error(display = "Failed to resolve the path of the hook script")]
+    ResolvePath(#[error(source)] io::Error),
+
+    #[error(display = "The hook script is not a file")]
+    NotAFile,
+
+    #[cfg(unix)]
+    #[error(display = "The hook script is not executable")]
+    NotExecutable,
+}
+
+/// Resolves `script` and checks that it is an executable file in `hooks_dir`. Hook scripts run
+/// with the privileges of the daemon, so they must not be placed anywhere that unprivileged users
+/// can write to. The hooks directory is in the settings directory, which only administrators can
+/// modify.
+pub fn validate(hooks_dir: &Path, script: &Path) -> Result<PathBuf, Error> {
+    let outside = || Error::OutsideHooksDir(hooks_dir.display().to_string());
+    let hooks_dir = hooks_dir.canonicalize().map_err(|_| outside())?;
+    let script = script.canonicalize().map_err(Error::ResolvePath)?;
+    if !script.starts_with(&hooks_dir) {
+        return Err(outside());
+    }
+
+    let metadata = script.metadata().map_err(Error::ResolvePath)?;
+    if !metadata.is_file() {
+        return Err(Error::NotAFile);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(Error::NotExecutable);
+        }
+    }
+    Ok(script)
+}
+
+type Environment = Vec<(&'static str, String)>;
+
+/// Runs the hook scripts of the user when the tunnel changes state. Scripts are run one at a
+/// time, in the order of the state changes, so that a disconnect hook never overtakes the connect
+/// hook before it.
+pub struct HookRunner {
+    hooks_dir: PathBuf,
+    tx: mpsc::UnboundedSender<(PathBuf, Environment)>,
+    /// Describes the last connection, so that the disconnect and error hooks can tell which
+    /// connection went away.
+    last_connection: Environment,
+}
+
+impl HookRunner {
+    pub fn new(hooks_dir: PathBuf) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(PathBuf, Environment)>();
+        let worker_hooks_dir = hooks_dir.clone();
+        tokio::spawn(async move {
+            while let Some((script, environment)) = rx.recv().await {
+                run_script(&worker_hooks_dir, &script, environment).await;
+            }
+        });
+        HookRunner {
+            hooks_dir,
+            tx,
+            last_connection: vec![],
+        }
+    }
+
+    /// Checks that `script` can be used as a hook, and returns its resolved path.
+    pub fn validate(&self, script: &Path) -> Result<PathBuf, Error> {
+        validate(&self.hooks_dir, script)
+    }
+
+    /// Validates every script in `hooks`, and returns the settings with their resolved paths.
+    pub fn validate_all(&self, hooks: TunnelHookSettings) -> Result<TunnelHookSettings, Error> {
+        let validate =
+            |script: Option<PathBuf>| script.map(|script| self.validate(&script)).transpose();
+        Ok(TunnelHookSettings {
+            connected: validate(hooks.connected)?,
+            disconnected: validate(hooks.disconnected)?,
+            error: validate(hooks.error)?,
+        })
+    }
+
+    /// Runs the hook for `tunnel_state`, if there is one. `interface` is the tunnel interface,
+    /// which is only known in the connected state.
+    pub fn tunnel_state_changed(
+        &mut self,
+        hooks: &TunnelHookSettings,
+        tunnel_state: &TunnelState,
+        interface: Option<&TunnelInterface>,
+    ) {
+        let (script, environment) = match tunnel_state {
+            TunnelState::Connected { .. } => {
+                self.last_connection = connection_environment(tunnel_state, interface);
+                let mut environment = self.last_connection.clone();
+                environment.push(("MULLVAD_TUNNEL_STATE", "connected".to_owned()));
+                (&hooks.connected, environment)
+            }
+            TunnelState::Disconnected => {
+                let mut environment = std::mem::take(&mut self.last_connection);
+                environment.push(("MULLVAD_TUNNEL_STATE", "disconnected".to_owned()));
+                (&hooks.disconnected, environment)
+            }
+            TunnelState::Error(error_state) => {
+                let mut environment = std::mem::take(&mut self.last_connection);
+                environment.push(("MULLVAD_TUNNEL_STATE", "error".to_owned()));
+                environment.push(("MULLVAD_ERROR", error_state.cause().to_string()));
+                environment.push((
+                    "MULLVAD_BLOCKING",
+                    (if error_state.is_blocking() { "1" } else { "0" }).to_owned(),
+                ));
+                (&hooks.error, environment)
+            }
+            TunnelState::Connecting { .. } | TunnelState::Disconnecting(..) => return,
+        };
+
+        if let Some(script) = script {
+            let _ = self.tx.send((script.clone(), environment));
+        }
+    }
+}
+
+/// Returns the variables that describe a connection. Variables that are not known are left out.
+fn connection_environment(
+    tunnel_state: &TunnelState,
+    interface: Option<&TunnelInterface>,
+) -> Environment {
+    let mut environment = vec![];
+    let (endpoint, location) = match tunnel_state {
+        TunnelState::Connected { endpoint, location } => (endpoint, location),
+        _ => return environment,
+    };
+
+    environment.push(("MULLVAD_TUNNEL_TYPE", endpoint.tunnel_type.to_string()));
+    environment.push((
+        "MULLVAD_ENDPOINT_IP",
+        endpoint.endpoint.address.ip().to_string(),
+    ));
+    environment.push((
+        "MULLVAD_ENDPOINT_PORT",
+        endpoint.endpoint.address.port().to_string(),
+    ));
+    environment.push((
+        "MULLVAD_ENDPOINT_PROTOCOL",
+        endpoint.endpoint.protocol.to_string(),
+    ));
+
+    if let Some(location) = location {
+        if let Some(hostname) = &location.hostname {
+            environment.push(("MULLVAD_RELAY_HOSTNAME", hostname.clone()));
+        }
+        if let Some(hostname) = &location.entry_hostname {
+            environment.push(("MULLVAD_ENTRY_HOSTNAME", hostname.clone()));
+        }
+        environment.push(("MULLVAD_COUNTRY", location.country.clone()));
+        if let Some(city) = &location.city {
+            environment.push(("MULLVAD_CITY", city.clone()));
+        }
+    }
+
+    if let Some(interface) = interface {
+        environment.push(("MULLVAD_INTERFACE", interface.name.clone()));
+        let ips = |ipv4: bool| {
+            interface
+                .ips
+                .iter()
+                .filter(|ip| ip.is_ipv4() == ipv4)
+                .map(IpAddr::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for (name, ips) in [
+            ("MULLVAD_TUNNEL_IPV4", ips(true)),
+            ("MULLVAD_TUNNEL_IPV6", ips(false)),
+        ] {
+            if !ips.is_empty() {
+                environment.push((name, ips));
+            }
+        }
+    }
+
+    environment
+}
+
+/// Runs a hook script and waits for it to finish. The script is validated again first, since the
+/// settings can be changed without going through the daemon. The script gets an empty
+/// environment apart from `environment` and `PATH`.
+async fn run_script(hooks_dir: &Path, script: &Path, environment: Environment) {
+    let script = match validate(hooks_dir, script) {
+        Ok(script) => script,
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg(&format!(
+                    "Not running hook script {}",
+                    script.display()
+                ))
+            );
+            return;
+        }
+    };
+
+    let mut command = std::process::Command::new(&script);
+    command
+        .env_clear()
+        .envs(environment)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    if let Some(path) = std::env::var_os("PATH") {
+        command.env("PATH", path);
+    }
+    #[cfg(windows)]
+    if let Some(system_root) = std::env::var_os("SystemRoot") {
+        command.env("SystemRoot", system_root);
+    }
+
+    let mut command = Command::from(command);
+    command.kill_on_drop(true);
+    let status = match command.spawn() {
+        Ok(mut child) => tokio::time::timeout(HOOK_TIMEOUT, child.wait()).await,
+        Err(error) => Ok(Err(error)),
+    };
+    match status {
+        Ok(Ok(status)) if status.success() => {
+            log::debug!("Hook script {} finished", script.display());
+        }
+        Ok(Ok(status)) => {
+            log::error!("Hook script {} failed: {}", script.display(), status);
+        }
+        Ok(Err(error)) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg(&format!(
+                    "Failed to run hook script {}",
+                    script.display()
+                ))
+            );
+        }
+        Err(_) => {
+            log::error!(
+                "Hook script {} did not finish within {} seconds and was killed",
+                script.display(),
+                HOOK_TIMEOUT.as_secs()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::{Endpoint, TransportProtocol, TunnelEndpoint, TunnelType};
+
+    fn connected() -> TunnelState {
+        TunnelState::Connected {
+            endpoint: TunnelEndpoint {
+                endpoint: Endpoint::new(
+                    "192.0.2.1".parse().unwrap(),
+                    51820,
+                    TransportProtocol::Udp,
+                ),
+                tunnel_type: TunnelType::Wireguard,
+                quantum_resistant: false,
+                proxy: None,
+                obfuscation: None,
+                entry_endpoint: None,
+                mtu: None,
+                wireguard_implementation: None,
+            },
+            location: None,
+        }
+    }
+
+    fn value<'a>(environment: &'a Environment, name: &str) -> Option<&'a str> {
+        environment
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_connection_environment() {
+        let interface = TunnelInterface {
+            name: "wg-mullvad".to_owned(),
+            ips: vec![
+                "10.64.0.2".parse().unwrap(),
+                "fc00:bbbb:bbbb:bb01::2".parse().unwrap(),
+            ],
+        };
+        let environment = connection_environment(&connected(), Some(&interface));
+        assert_eq!(
+            value(&environment, "MULLVAD_TUNNEL_TYPE"),
+            Some("WireGuard")
+        );
+        assert_eq!(
+            value(&environment, "MULLVAD_ENDPOINT_IP"),
+            Some("192.0.2.1")
+        );
+        assert_eq!(value(&environment, "MULLVAD_ENDPOINT_PORT"), Some("51820"));
+        assert_eq!(value(&environment, "MULLVAD_INTERFACE"), Some("wg-mullvad"));
+        assert_eq!(
+            value(&environment, "MULLVAD_TUNNEL_IPV4"),
+            Some("10.64.0.2")
+        );
+        assert_eq!(
+            value(&environment, "MULLVAD_TUNNEL_IPV6"),
+            Some("fc00:bbbb:bbbb:bb01::2")
+        );
+        assert_eq!(value(&environment, "MULLVAD_RELAY_HOSTNAME"), None);
+
+        let environment = connection_environment(&TunnelState::Disconnected, Some(&interface));
+        assert!(environment.is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_scripts_outside_hooks_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks_dir = dir.path().join(HOOKS_DIR);
+        std::fs::create_dir_all(&hooks_dir).unwrap();
+        let outside = dir.path().join("script");
+        std::fs::write(&outside, "").unwrap();
+
+        assert!(matches!(
+            validate(&hooks_dir, &outside),
+            Err(Error::OutsideHooksDir(_))
+        ));
+        assert!(matches!(
+            validate(&hooks_dir, &hooks_dir.join("..").join("script")),
+            Err(Error::OutsideHooksDir(_))
+        ));
+        assert!(matches!(
+            validate(&hooks_dir, &hooks_dir),
+            Err(Error::NotAFile)
+        ));
+    }
+}
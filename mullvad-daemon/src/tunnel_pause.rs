@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+const TUNNEL_PAUSE_FILE: &str = "tunnel-pause.json";
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    resume_at: SystemTime,
+}
+
+/// Keeps a record on disk of when a paused tunnel is to be resumed, so that a later daemon
+/// instance can continue the pause, or end it if the time has already passed.
+pub struct TunnelPauseRecord {
+    path: PathBuf,
+}
+
+impl TunnelPauseRecord {
+    /// Returns the record, along with the time at which the tunnel was to be resumed if it was
+    /// paused when the previous daemon instance exited.
+    pub async fn load(settings_dir: &Path) -> (Self, Option<SystemTime>) {
+        let path = settings_dir.join(TUNNEL_PAUSE_FILE);
+        let resume_at = match fs::read_to_string(&path).await {
+            Ok(content) => match serde_json::from_str::<Record>(&content) {
+                Ok(record) => Some(record.resume_at),
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to parse the tunnel pause")
+                    );
+                    // Reconnect rather than leave the tunnel paused indefinitely
+                    Some(SystemTime::UNIX_EPOCH)
+                }
+            },
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read the tunnel pause")
+                    );
+                }
+                None
+            }
+        };
+        (TunnelPauseRecord { path }, resume_at)
+    }
+
+    /// Records when the paused tunnel is to be resumed, or that it is not paused.
+    pub async fn save(&self, resume_at: Option<SystemTime>) {
+        let resume_at = match resume_at {
+            Some(resume_at) => resume_at,
+            None => {
+                if let Err(error) = fs::remove_file(&self.path).await {
+                    if error.kind() != io::ErrorKind::NotFound {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to delete the tunnel pause")
+                        );
+                    }
+                }
+                return;
+            }
+        };
+
+        match serde_json::to_string(&Record { resume_at }) {
+            Ok(data) => {
+                if let Err(error) = fs::write(&self.path, data).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to write the tunnel pause")
+                    );
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize the tunnel pause")
+                );
+            }
+        }
+    }
+}
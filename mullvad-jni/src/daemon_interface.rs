@@ -6,7 +6,7 @@ use mullvad_types::{
     location::GeoIpLocation,
     relay_constraints::RelaySettingsUpdate,
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{AppExclusionSettings, DnsOptions, Settings},
     states::{TargetState, TunnelState},
     version::AppVersionInfo,
     wireguard,
@@ -262,6 +262,16 @@ impl DaemonInterface {
             .map_err(|_| Error::SettingsError)
     }
 
+    pub fn set_app_exclusions(&self, app_exclusions: AppExclusionSettings) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(DaemonCommand::SetAppExclusions(tx, app_exclusions))?;
+
+        block_on(rx)
+            .map_err(|_| Error::NoResponse)?
+            .map_err(|_| Error::SettingsError)
+    }
+
     pub fn set_auto_connect(&self, auto_connect: bool) -> Result<()> {
         let (tx, rx) = oneshot::channel();
 
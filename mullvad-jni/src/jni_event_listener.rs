@@ -7,10 +7,11 @@ use jnix::{
 };
 use mullvad_daemon::EventListener;
 use mullvad_types::{
+    account::AccountExpiryEvent,
     device::{DeviceEvent, RemoveDeviceEvent},
-    relay_list::RelayList,
+    relay_list::{ExitRelayChangeEvent, RelayList},
     settings::Settings,
-    states::TunnelState,
+    states::{AutoConnectFailedEvent, DisconnectWarningEvent, TunnelState},
     version::AppVersionInfo,
 };
 use std::{sync::mpsc, thread};
@@ -71,6 +72,22 @@ impl EventListener for JniEventListener {
     fn notify_remove_device_event(&self, event: RemoveDeviceEvent) {
         let _ = self.0.send(Event::RemoveDeviceEvent(event));
     }
+
+    fn notify_exit_relay_change(&self, _event: ExitRelayChangeEvent) {
+        // Not shown by the Android app
+    }
+
+    fn notify_account_expiry(&self, _event: AccountExpiryEvent) {
+        // The Android app schedules its own expiry notifications
+    }
+
+    fn notify_auto_connect_failed(&self, _event: AutoConnectFailedEvent) {
+        // The Android app shows the error state
+    }
+
+    fn notify_disconnect_warning(&self, _event: DisconnectWarningEvent) {
+        // Disconnect warnings are not supported on Android
+    }
 }
 
 struct JniEventHandler<'env> {
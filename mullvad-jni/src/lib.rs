@@ -25,7 +25,7 @@ use mullvad_daemon::{
 };
 use mullvad_types::{
     account::{AccountData, VoucherSubmission},
-    settings::DnsOptions,
+    settings::{AppExclusionSettings, DnsOptions},
 };
 use std::{
     io,
@@ -917,6 +917,28 @@ pub extern "system" fn Java_net_mullvad_mullvadvpn_service_MullvadDaemon_setAllo
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn Java_net_mullvad_mullvadvpn_service_MullvadDaemon_setAppExclusions(
+    env: JNIEnv<'_>,
+    _: JObject<'_>,
+    daemon_interface_address: jlong,
+    appExclusions: JObject<'_>,
+) {
+    let env = JnixEnv::from(env);
+
+    if let Some(daemon_interface) = get_daemon_interface(daemon_interface_address) {
+        let app_exclusions = AppExclusionSettings::from_java(&env, appExclusions);
+
+        if let Err(error) = daemon_interface.set_app_exclusions(app_exclusions) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to set excluded apps")
+            );
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "system" fn Java_net_mullvad_mullvadvpn_service_MullvadDaemon_setAutoConnect(
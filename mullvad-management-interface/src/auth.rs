@@ -0,0 +1,385 @@
+//! Optional authorization of clients of the management interface.
+//!
+//! By default, every client that can connect to the socket or named pipe may call every method.
+//! When authorization is enabled, methods that only report the state of the daemon stay open to
+//! everyone, while all other methods require the client to be authorized. The settings and the
+//! event stream are not open, since they contain the credentials of custom tunnels, proxies and
+//! API access methods. A client is authorized
+//! if it presents the management token, or, on Linux and macOS, if it runs as root or as one of
+//! the users or groups listed in `MULLVAD_MANAGEMENT_AUTHORIZED_USERS` and
+//! `MULLVAD_MANAGEMENT_AUTHORIZED_GROUPS`.
+//!
+//! The token is created by the daemon and stored in `MANAGEMENT_TOKEN_FILE` in the settings
+//! directory, which only administrators can read. Clients send it if it is set in
+//! `MULLVAD_MANAGEMENT_TOKEN` or if they can read the file.
+
+use crate::Error;
+use rand::RngCore;
+#[cfg(unix)]
+use std::collections::HashSet;
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::{
+    body::BoxBody,
+    codegen::http,
+    metadata::{Ascii, MetadataValue},
+    service::Interceptor,
+    transport::{Body, NamedService},
+    Request, Status,
+};
+use tower::Service;
+
+/// Name of the file in the settings directory that holds the management token.
+pub const MANAGEMENT_TOKEN_FILE: &str = "management-token";
+
+/// Metadata key under which clients send the management token.
+const TOKEN_METADATA_KEY: &str = "mullvad-management-token";
+
+/// Methods that can be called without being authorized. They report the state of the daemon,
+/// but not the account number or any credentials. `GetSettings`, `GetSettingsHistory` and the
+/// event stream, which includes the settings, are left out for that reason.
+const OPEN_METHODS: &[&str] = &[
+    "GetApiStats",
+    "GetCaptivePortal",
+    "GetCircumventionChanges",
+    "GetCurrentLocation",
+    "GetCurrentVersion",
+    "GetDataUsage",
+    "GetExcludedProcesses",
+    "GetLastConnectionFailure",
+    "GetLeftoverState",
+    "GetOfflineStatus",
+    "GetPermissiveMode",
+    "GetRelayListInfo",
+    "GetRelayLocations",
+    "GetRelayStats",
+    "GetSettingOverrides",
+    "GetSettingsEncryption",
+    "GetSplitTunnelProcesses",
+    "GetSupportedObfuscationTransports",
+    "GetTunnelPause",
+    "GetTunnelState",
+    "GetVersionInfo",
+    "GetWireguardKey",
+    "IsPerformingPostUpgrade",
+    "NegotiateInterfaceVersion",
+];
+
+/// Credentials of the process on the other end of the socket.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Who may call the methods that are not open to everyone.
+#[derive(Debug)]
+pub struct AccessPolicy {
+    token: String,
+    #[cfg(unix)]
+    uids: HashSet<u32>,
+    #[cfg(unix)]
+    gids: HashSet<u32>,
+}
+
+impl AccessPolicy {
+    /// Reads the policy from the environment. Returns `None` if authorization is not enabled,
+    /// in which case every client may call every method.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let enabled = env::var("MULLVAD_MANAGEMENT_AUTHORIZATION")
+            .map(|value| value == "1")
+            .unwrap_or(false);
+        #[cfg(unix)]
+        let users = list_from_env("MULLVAD_MANAGEMENT_AUTHORIZED_USERS");
+        #[cfg(unix)]
+        let groups = list_from_env("MULLVAD_MANAGEMENT_AUTHORIZED_GROUPS");
+        #[cfg(unix)]
+        let enabled = enabled || !users.is_empty() || !groups.is_empty();
+
+        if !enabled {
+            return Ok(None);
+        }
+
+        let settings_dir = mullvad_paths::settings_dir().map_err(Error::TokenDirectory)?;
+        let token = load_or_create_token(&settings_dir.join(MANAGEMENT_TOKEN_FILE))
+            .map_err(Error::TokenError)?;
+
+        #[cfg(unix)]
+        {
+            let mut uids = HashSet::new();
+            let mut gids = HashSet::new();
+            for name in &users {
+                let user = nix::unistd::User::from_name(name)
+                    .map_err(|error| Error::LookupAuthorizedError(name.clone(), error))?
+                    .ok_or_else(|| Error::NoAuthorizedUserOrGroup(name.clone()))?;
+                uids.insert(user.uid.as_raw());
+            }
+            for name in &groups {
+                let group = nix::unistd::Group::from_name(name)
+                    .map_err(|error| Error::LookupAuthorizedError(name.clone(), error))?
+                    .ok_or_else(|| Error::NoAuthorizedUserOrGroup(name.clone()))?;
+                gids.insert(group.gid.as_raw());
+                // The peer credentials only contain the primary group, so members for whom the
+                // group is a supplementary group are authorized by their user ID.
+                for member in &group.mem {
+                    if let Ok(Some(user)) = nix::unistd::User::from_name(member) {
+                        uids.insert(user.uid.as_raw());
+                    }
+                }
+            }
+            log::info!(
+                "Management interface authorization is enabled for users {:?} and groups {:?}",
+                users,
+                groups
+            );
+            Ok(Some(AccessPolicy { token, uids, gids }))
+        }
+        #[cfg(not(unix))]
+        {
+            log::info!("Management interface authorization is enabled");
+            Ok(Some(AccessPolicy { token }))
+        }
+    }
+
+    /// Returns whether a process with the credentials `peer` is authorized without presenting the
+    /// token.
+    #[cfg(unix)]
+    pub fn is_authorized_peer(&self, peer: PeerCredentials) -> bool {
+        peer.uid == 0 || self.uids.contains(&peer.uid) || self.gids.contains(&peer.gid)
+    }
+
+    fn check<B>(&self, request: &http::Request<B>) -> Result<(), Status> {
+        let method = request.uri().path().rsplit('/').next().unwrap_or_default();
+        if OPEN_METHODS.contains(&method) {
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        if let Some(Some(peer)) = request.extensions().get::<Option<PeerCredentials>>() {
+            if self.is_authorized_peer(*peer) {
+                return Ok(());
+            }
+        }
+
+        let token = request
+            .headers()
+            .get(TOKEN_METADATA_KEY)
+            .map(|value| value.as_bytes());
+        if let Some(token) = token {
+            if constant_time_eq(token, self.token.as_bytes()) {
+                return Ok(());
+            }
+        }
+
+        Err(Status::permission_denied(format!(
+            "Not authorized to call {}. Run as an authorized user, or set \
+             MULLVAD_MANAGEMENT_TOKEN to the management token",
+            method
+        )))
+    }
+}
+
+#[cfg(unix)]
+fn list_from_env(key: &str) -> Vec<String> {
+    env::var(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_owned()),
+        Ok(_) => (),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+        Err(error) => return Err(error),
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)?
+        .write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Returns the token that clients send, if any.
+fn client_token() -> Option<MetadataValue<Ascii>> {
+    let token = env::var("MULLVAD_MANAGEMENT_TOKEN").ok().or_else(|| {
+        let settings_dir = mullvad_paths::get_default_settings_dir().ok()?;
+        fs::read_to_string(settings_dir.join(MANAGEMENT_TOKEN_FILE)).ok()
+    })?;
+    MetadataValue::from_str(token.trim()).ok()
+}
+
+/// Adds the management token to the requests of a client, if it is available.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    token: Option<MetadataValue<Ascii>>,
+}
+
+impl TokenInterceptor {
+    pub fn from_env() -> Self {
+        TokenInterceptor {
+            token: client_token(),
+        }
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            request
+                .metadata_mut()
+                .insert(TOKEN_METADATA_KEY, token.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Rejects requests that are not allowed by the access policy before they reach `inner`.
+#[derive(Clone)]
+pub struct Authorize<S> {
+    inner: S,
+    policy: Option<Arc<AccessPolicy>>,
+}
+
+impl<S> Authorize<S> {
+    pub fn new(inner: S, policy: Option<AccessPolicy>) -> Self {
+        Authorize {
+            inner,
+            policy: policy.map(Arc::new),
+        }
+    }
+}
+
+impl<S: NamedService> NamedService for Authorize<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for Authorize<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        if let Some(policy) = &self.policy {
+            if let Err(status) = policy.check(&request) {
+                log::warn!(
+                    "Rejected management interface request: {}",
+                    status.message()
+                );
+                return Box::pin(async move { Ok(status.to_http()) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy() -> AccessPolicy {
+        AccessPolicy {
+            token: "secret".to_owned(),
+            #[cfg(unix)]
+            uids: HashSet::from([1000]),
+            #[cfg(unix)]
+            gids: HashSet::new(),
+        }
+    }
+
+    fn request(method: &str, token: Option<&str>) -> http::Request<()> {
+        let mut builder = http::Request::builder().uri(format!(
+            "/mullvad_daemon.management_interface.ManagementService/{}",
+            method
+        ));
+        if let Some(token) = token {
+            builder = builder.header(TOKEN_METADATA_KEY, token);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_open_methods() {
+        assert!(policy().check(&request("GetTunnelState", None)).is_ok());
+        assert!(policy().check(&request("DisconnectTunnel", None)).is_err());
+        assert!(policy().check(&request("GetAccountHistory", None)).is_err());
+    }
+
+    #[test]
+    fn test_credentials_are_not_open() {
+        for method in [
+            "GetSettings",
+            "GetSettingsHistory",
+            "EventsListen",
+            "EventsReplay",
+        ] {
+            assert!(policy().check(&request(method, None)).is_err());
+            assert!(policy().check(&request(method, Some("secret"))).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_token() {
+        assert!(policy()
+            .check(&request("DisconnectTunnel", Some("secret")))
+            .is_ok());
+        assert!(policy()
+            .check(&request("DisconnectTunnel", Some("wrong")))
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_peer_credentials() {
+        let with_peer = |uid| {
+            let mut request = request("SetAllowLan", None);
+            request
+                .extensions_mut()
+                .insert(Some(PeerCredentials { uid, gid: 1000 }));
+            request
+        };
+        assert!(policy().check(&with_peer(0)).is_ok());
+        assert!(policy().check(&with_peer(1000)).is_ok());
+        assert!(policy().check(&with_peer(1001)).is_err());
+    }
+}
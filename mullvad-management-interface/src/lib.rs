@@ -1,5 +1,8 @@
+pub mod auth;
+pub mod remote;
 pub mod types;
 
+use futures::FutureExt;
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 #[cfg(unix)]
 use std::{env, fs, os::unix::fs::PermissionsExt};
@@ -9,22 +12,44 @@ use std::{
     pin::Pin,
     task::{Context, Poll},
 };
+use talpid_types::ErrorExt;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tonic::transport::{server::Connected, Endpoint, Server, Uri};
+use tonic::{
+    codegen::InterceptedService,
+    transport::{server::Connected, Endpoint, Server, Uri},
+};
 use tower::service_fn;
 
 pub use tonic::{async_trait, transport::Channel, Code, Request, Response, Status};
 
-pub type ManagementServiceClient =
-    types::management_service_client::ManagementServiceClient<Channel>;
+pub type ManagementServiceClient = types::management_service_client::ManagementServiceClient<
+    InterceptedService<Channel, auth::TokenInterceptor>,
+>;
 pub use types::management_service_server::{ManagementService, ManagementServiceServer};
 
+/// Version of the management interface that this crate implements. Bump it when the interface
+/// changes in a way that breaks existing clients, and keep serving the previous version until
+/// `MIN_SUPPORTED_INTERFACE_VERSION` is raised.
+pub const INTERFACE_VERSION: u32 = 1;
+
+/// Oldest version of the interface that the daemon serves, and that clients can talk to. Version
+/// 0 denotes daemons and clients that predate version negotiation. They use the same messages as
+/// version 1.
+pub const MIN_SUPPORTED_INTERFACE_VERSION: u32 = 0;
+
 #[cfg(unix)]
 lazy_static::lazy_static! {
     static ref MULLVAD_MANAGEMENT_SOCKET_GROUP: Option<String> = env::var("MULLVAD_MANAGEMENT_SOCKET_GROUP")
         .ok();
 }
 
+/// Returns the group that the management interface socket is restricted to, if any. Other
+/// interfaces to the daemon should only let members of the group in as well.
+#[cfg(unix)]
+pub fn socket_group() -> Option<&'static str> {
+    MULLVAD_MANAGEMENT_SOCKET_GROUP.as_deref()
+}
+
 #[derive(err_derive::Error, Debug)]
 #[error(no_from)]
 pub enum Error {
@@ -40,6 +65,27 @@ pub enum Error {
     #[error(display = "Unable to set permissions for IPC endpoint")]
     PermissionsError(#[error(source)] io::Error),
 
+    #[error(display = "Failed to negotiate the management interface version")]
+    NegotiateVersion(#[error(source)] Status),
+
+    #[error(
+        display = "The daemon is too old for this client. The daemon supports management \
+                   interface versions {} to {}, but the client requires at least version {}",
+        min_version,
+        max_version,
+        MIN_SUPPORTED_INTERFACE_VERSION
+    )]
+    DaemonTooOld { min_version: u32, max_version: u32 },
+
+    #[error(
+        display = "The daemon is too new for this client. The daemon supports management \
+                   interface versions {} to {}, but the client supports at most version {}",
+        min_version,
+        max_version,
+        INTERFACE_VERSION
+    )]
+    DaemonTooNew { min_version: u32, max_version: u32 },
+
     #[cfg(unix)]
     #[error(display = "Group not found")]
     NoGidError,
@@ -51,6 +97,35 @@ pub enum Error {
     #[cfg(unix)]
     #[error(display = "Failed to set group ID")]
     SetGidError(#[error(source)] nix::Error),
+
+    #[cfg(unix)]
+    #[error(display = "Authorized user or group {} not found", _0)]
+    NoAuthorizedUserOrGroup(String),
+
+    #[cfg(unix)]
+    #[error(display = "Failed to look up authorized user or group {}", _0)]
+    LookupAuthorizedError(String, #[error(source)] nix::Error),
+
+    #[error(display = "Failed to find the directory of the management token")]
+    TokenDirectory(#[error(source)] mullvad_paths::Error),
+
+    #[error(display = "Failed to read or create the management token")]
+    TokenError(#[error(source)] io::Error),
+
+    #[error(display = "{} must be set to use remote management", _0)]
+    MissingRemoteConfig(&'static str),
+
+    #[error(display = "Invalid remote management address: {}", _0)]
+    InvalidRemoteAddress(String, #[error(source)] std::net::AddrParseError),
+
+    #[error(display = "Invalid remote management URI: {}", _0)]
+    InvalidRemoteUri(String),
+
+    #[error(display = "Failed to read {}", _0)]
+    ReadTlsFile(String, #[error(source)] io::Error),
+
+    #[error(display = "Failed to listen for remote management connections")]
+    RemoteListenError(#[error(source)] io::Error),
 }
 
 pub async fn new_rpc_client() -> Result<ManagementServiceClient, Error> {
@@ -64,7 +139,54 @@ pub async fn new_rpc_client() -> Result<ManagementServiceClient, Error> {
         .await
         .map_err(Error::GrpcTransportError)?;
 
-    Ok(ManagementServiceClient::new(channel))
+    Ok(
+        types::management_service_client::ManagementServiceClient::with_interceptor(
+            channel,
+            auth::TokenInterceptor::from_env(),
+        ),
+    )
+}
+
+/// Connects to the daemon, like [`new_rpc_client`], and fails unless the daemon supports a version
+/// of the interface that this client understands.
+pub async fn new_negotiated_rpc_client() -> Result<ManagementServiceClient, Error> {
+    let mut client = new_rpc_client().await?;
+    negotiate_version(&mut client).await?;
+    Ok(client)
+}
+
+/// Returns the newest version of the interface that both the daemon and this client support.
+pub async fn negotiate_version(client: &mut ManagementServiceClient) -> Result<u32, Error> {
+    let request = types::InterfaceVersion {
+        version: INTERFACE_VERSION,
+    };
+    let (min_version, max_version) = match client.negotiate_interface_version(request).await {
+        Ok(response) => {
+            let versions = response.into_inner();
+            (versions.min_version, versions.max_version)
+        }
+        // The daemon predates version negotiation
+        Err(status) if status.code() == Code::Unimplemented => (0, 0),
+        Err(status) => return Err(Error::NegotiateVersion(status)),
+    };
+    select_version(min_version, max_version)
+}
+
+fn select_version(min_version: u32, max_version: u32) -> Result<u32, Error> {
+    if min_version > INTERFACE_VERSION {
+        return Err(Error::DaemonTooNew {
+            min_version,
+            max_version,
+        });
+    }
+    let version = std::cmp::min(max_version, INTERFACE_VERSION);
+    if !(MIN_SUPPORTED_INTERFACE_VERSION..=INTERFACE_VERSION).contains(&version) {
+        return Err(Error::DaemonTooOld {
+            min_version,
+            max_version,
+        });
+    }
+    Ok(version)
 }
 
 pub type ServerJoinHandle = tokio::task::JoinHandle<Result<(), Error>>;
@@ -73,19 +195,48 @@ pub async fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Sen
     service: T,
     abort_rx: F,
 ) -> std::result::Result<ServerJoinHandle, Error> {
-    use futures::stream::TryStreamExt;
-    use parity_tokio_ipc::SecurityAttributes;
-
     let socket_path = mullvad_paths::get_rpc_socket_path();
+    let policy = auth::AccessPolicy::from_env()?;
+    // Remote management is optional, so failing to set it up must not stop local clients from
+    // managing the daemon.
+    let remote = remote::RemoteServer::bind_from_env()
+        .await
+        .unwrap_or_else(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to start remote management")
+            );
+            None
+        });
 
-    let mut endpoint = IpcEndpoint::new(socket_path.to_string_lossy().to_string());
-    endpoint.set_security_attributes(
-        SecurityAttributes::allow_everyone_create()
-            .map_err(Error::SecurityAttributes)?
-            .set_mode(0o766)
-            .map_err(Error::SecurityAttributes)?,
-    );
-    let incoming = endpoint.incoming().map_err(Error::StartServerError)?;
+    // The socket is set up without `IpcEndpoint` on Unix, since it does not expose the peer
+    // credentials that the access policy is checked against.
+    #[cfg(unix)]
+    let incoming = {
+        let listener =
+            tokio::net::UnixListener::bind(&socket_path).map_err(Error::StartServerError)?;
+        fs::set_permissions(&socket_path, PermissionsExt::from_mode(0o766))
+            .map_err(Error::PermissionsError)?;
+        futures::stream::poll_fn(move |cx| {
+            listener
+                .poll_accept(cx)
+                .map(|result| Some(result.map(|(stream, _)| StreamBox(stream))))
+        })
+    };
+    #[cfg(windows)]
+    let incoming = {
+        use futures::stream::TryStreamExt;
+        use parity_tokio_ipc::SecurityAttributes;
+
+        let mut endpoint = IpcEndpoint::new(socket_path.to_string_lossy().to_string());
+        endpoint.set_security_attributes(
+            SecurityAttributes::allow_everyone_create().map_err(Error::SecurityAttributes)?,
+        );
+        endpoint
+            .incoming()
+            .map_err(Error::StartServerError)?
+            .map_ok(StreamBox)
+    };
 
     #[cfg(unix)]
     if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
@@ -98,16 +249,43 @@ pub async fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Sen
     }
 
     Ok(tokio::spawn(async move {
-        Server::builder()
-            .add_service(ManagementServiceServer::new(service))
-            .serve_with_incoming_shutdown(incoming.map_ok(StreamBox), abort_rx)
-            .await
-            .map_err(Error::GrpcTransportError)
+        let service = ManagementServiceServer::new(service);
+        let abort_rx = abort_rx.shared();
+        let local = Server::builder()
+            .add_service(auth::Authorize::new(service.clone(), policy))
+            .serve_with_incoming_shutdown(incoming, abort_rx.clone())
+            .map(|result| result.map_err(Error::GrpcTransportError));
+        if let Some(remote) = remote {
+            tokio::spawn(async move {
+                if let Err(error) = remote.serve(service, abort_rx).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Remote management server failed")
+                    );
+                }
+            });
+        }
+        local.await
     }))
 }
 
 #[derive(Debug)]
 struct StreamBox<T: AsyncRead + AsyncWrite>(pub T);
+#[cfg(unix)]
+impl Connected for StreamBox<tokio::net::UnixStream> {
+    type ConnectInfo = Option<auth::PeerCredentials>;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.0
+            .peer_cred()
+            .map(|cred| auth::PeerCredentials {
+                uid: cred.uid(),
+                gid: cred.gid(),
+            })
+            .ok()
+    }
+}
+#[cfg(windows)]
 impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
     type ConnectInfo = Option<()>;
 
@@ -141,3 +319,25 @@ impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for StreamBox<T> {
         Pin::new(&mut self.0).poll_shutdown(cx)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_version() {
+        assert_eq!(select_version(0, 0).unwrap(), 0);
+        assert_eq!(
+            select_version(MIN_SUPPORTED_INTERFACE_VERSION, INTERFACE_VERSION).unwrap(),
+            INTERFACE_VERSION
+        );
+        assert_eq!(
+            select_version(INTERFACE_VERSION, INTERFACE_VERSION + 1).unwrap(),
+            INTERFACE_VERSION
+        );
+        assert!(matches!(
+            select_version(INTERFACE_VERSION + 1, INTERFACE_VERSION + 2),
+            Err(Error::DaemonTooNew { .. })
+        ));
+    }
+}
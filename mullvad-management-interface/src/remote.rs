@@ -0,0 +1,204 @@
+//! Optional remote access to the management interface over TCP, protected with mutual TLS.
+//!
+//! The daemon listens on `MULLVAD_MANAGEMENT_LISTEN_ADDRESS` if it is set. Clients must present a
+//! certificate that is signed by the CA in `MULLVAD_MANAGEMENT_TLS_CLIENT_CA`. Since the
+//! certificate is what authorizes a client, remote clients may call every method, regardless of
+//! the access policy of the local socket.
+//!
+//! The remote server runs separately from the local socket, so if it cannot be started or fails,
+//! the error is logged and local clients are unaffected. The firewall of the daemon does not open
+//! the listening port: while it is blocking traffic, remote clients can only connect from the
+//! local network, and only when local network sharing is enabled or there is an inbound TCP
+//! firewall exception for the port (`mullvad firewall add inbound tcp <port>`).
+//!
+//! Clients connect to a remote daemon with [`ClientConfig`], which is read from
+//! `MULLVAD_MANAGEMENT_REMOTE` and the variables next to it.
+
+use crate::{auth, types, Error, ManagementService, ManagementServiceClient};
+use std::{fs, future::Future, net::SocketAddr, path::Path};
+use tokio::net::TcpListener;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, Server, ServerTlsConfig};
+
+const LISTEN_ADDRESS_VAR: &str = "MULLVAD_MANAGEMENT_LISTEN_ADDRESS";
+const SERVER_CERT_VAR: &str = "MULLVAD_MANAGEMENT_TLS_CERT";
+const SERVER_KEY_VAR: &str = "MULLVAD_MANAGEMENT_TLS_KEY";
+const CLIENT_CA_VAR: &str = "MULLVAD_MANAGEMENT_TLS_CLIENT_CA";
+
+const REMOTE_VAR: &str = "MULLVAD_MANAGEMENT_REMOTE";
+const REMOTE_CA_VAR: &str = "MULLVAD_MANAGEMENT_REMOTE_CA";
+const REMOTE_CERT_VAR: &str = "MULLVAD_MANAGEMENT_REMOTE_CERT";
+const REMOTE_KEY_VAR: &str = "MULLVAD_MANAGEMENT_REMOTE_KEY";
+
+/// A TCP listener for remote clients, that has not started serving yet.
+pub(crate) struct RemoteServer {
+    listener: TcpListener,
+    builder: Server,
+}
+
+impl RemoteServer {
+    /// Starts listening if remote management is enabled. The listener is bound here, rather than
+    /// when serving, so that a bad configuration is logged when the daemon starts.
+    pub async fn bind_from_env() -> Result<Option<Self>, Error> {
+        let config = match ServerConfig::from_vars(|key| std::env::var(key).ok())? {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let builder = Server::builder()
+            .tls_config(config.tls)
+            .map_err(Error::GrpcTransportError)?;
+        let listener = TcpListener::bind(config.address)
+            .await
+            .map_err(Error::RemoteListenError)?;
+        log::info!(
+            "Listening for remote management connections on {}",
+            config.address
+        );
+        Ok(Some(RemoteServer { listener, builder }))
+    }
+
+    pub async fn serve<T: ManagementService, F: Future<Output = ()>>(
+        mut self,
+        service: types::management_service_server::ManagementServiceServer<T>,
+        abort_rx: F,
+    ) -> Result<(), Error> {
+        let listener = self.listener;
+        let incoming = futures::stream::poll_fn(move |cx| {
+            listener
+                .poll_accept(cx)
+                .map(|result| Some(result.map(|(stream, _)| stream)))
+        });
+        self.builder
+            .add_service(service)
+            .serve_with_incoming_shutdown(incoming, abort_rx)
+            .await
+            .map_err(Error::GrpcTransportError)
+    }
+}
+
+struct ServerConfig {
+    address: SocketAddr,
+    tls: ServerTlsConfig,
+}
+
+impl ServerConfig {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Error> {
+        let address = match var(LISTEN_ADDRESS_VAR) {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        let address = address
+            .parse()
+            .map_err(|error| Error::InvalidRemoteAddress(address, error))?;
+
+        let required = |key: &'static str| var(key).ok_or(Error::MissingRemoteConfig(key));
+        let cert_path = required(SERVER_CERT_VAR)?;
+        let key_path = required(SERVER_KEY_VAR)?;
+        let client_ca_path = required(CLIENT_CA_VAR)?;
+        let cert = read_pem(&cert_path)?;
+        let key = read_pem(&key_path)?;
+        let client_ca = read_pem(&client_ca_path)?;
+
+        let tls = ServerTlsConfig::new()
+            .identity(Identity::from_pem(cert, key))
+            .client_ca_root(Certificate::from_pem(client_ca));
+        Ok(Some(ServerConfig { address, tls }))
+    }
+}
+
+/// How to reach a daemon over TCP.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// URI of the daemon, such as `https://vpn-01.example.com:8765`.
+    pub uri: String,
+    tls: ClientTlsConfig,
+}
+
+impl ClientConfig {
+    /// Reads the configuration from the environment. Returns `None` if `MULLVAD_MANAGEMENT_REMOTE`
+    /// is not set, in which case clients should connect to the local daemon.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Error> {
+        let uri = match var(REMOTE_VAR) {
+            Some(uri) => uri,
+            None => return Ok(None),
+        };
+
+        let required = |key: &'static str| var(key).ok_or(Error::MissingRemoteConfig(key));
+        let ca_path = required(REMOTE_CA_VAR)?;
+        let cert_path = required(REMOTE_CERT_VAR)?;
+        let key_path = required(REMOTE_KEY_VAR)?;
+        let ca = read_pem(&ca_path)?;
+        let cert = read_pem(&cert_path)?;
+        let key = read_pem(&key_path)?;
+
+        let tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(ca))
+            .identity(Identity::from_pem(cert, key));
+        Ok(Some(ClientConfig { uri, tls }))
+    }
+}
+
+/// Connects to the daemon described by `config`.
+pub async fn new_remote_rpc_client(config: ClientConfig) -> Result<ManagementServiceClient, Error> {
+    let uri = config.uri.clone();
+    let channel = Endpoint::from_shared(config.uri)
+        .map_err(|_| Error::InvalidRemoteUri(uri))?
+        .tls_config(config.tls)
+        .map_err(Error::GrpcTransportError)?
+        .connect()
+        .await
+        .map_err(Error::GrpcTransportError)?;
+
+    Ok(
+        types::management_service_client::ManagementServiceClient::with_interceptor(
+            channel,
+            auth::TokenInterceptor::from_env(),
+        ),
+    )
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, Error> {
+    fs::read(Path::new(path)).map_err(|error| Error::ReadTlsFile(path.to_owned(), error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_server_config() {
+        assert!(matches!(ServerConfig::from_vars(vars(&[])), Ok(None)));
+        assert!(matches!(
+            ServerConfig::from_vars(vars(&[(LISTEN_ADDRESS_VAR, "vpn-01:8765")])),
+            Err(Error::InvalidRemoteAddress(..))
+        ));
+        assert!(matches!(
+            ServerConfig::from_vars(vars(&[
+                (LISTEN_ADDRESS_VAR, "0.0.0.0:8765"),
+                (SERVER_CERT_VAR, "server.pem"),
+            ])),
+            Err(Error::MissingRemoteConfig(SERVER_KEY_VAR))
+        ));
+    }
+
+    #[test]
+    fn test_client_config() {
+        assert!(matches!(ClientConfig::from_vars(vars(&[])), Ok(None)));
+        assert!(matches!(
+            ClientConfig::from_vars(vars(&[(REMOTE_VAR, "https://vpn-01:8765")])),
+            Err(Error::MissingRemoteConfig(REMOTE_CA_VAR))
+        ));
+    }
+}
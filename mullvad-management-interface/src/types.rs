@@ -1,6 +1,6 @@
 pub use prost_types::{Duration, Timestamp};
 
-use mullvad_types::relay_constraints::Constraint;
+use mullvad_types::relay_constraints::{Constraint, ConstraintSet};
 use std::convert::TryFrom;
 use talpid_types::{net::wireguard, ErrorExt};
 
@@ -54,12 +54,19 @@ impl From<talpid_types::net::TunnelEndpoint> for TunnelEndpoint {
                     )),
                     obfuscation_type: match obfuscation_endpoint.obfuscation_type {
                         net::ObfuscationType::Udp2Tcp => i32::from(ObfuscationType::Udp2tcp),
+                        net::ObfuscationType::External => i32::from(ObfuscationType::External),
+                        net::ObfuscationType::Wss => i32::from(ObfuscationType::Wss),
                     },
                 }),
             entry_endpoint: endpoint.entry_endpoint.map(|entry| Endpoint {
                 address: entry.address.to_string(),
                 protocol: i32::from(TransportProtocol::from(entry.protocol)),
             }),
+            mtu: endpoint.mtu.map(u32::from).unwrap_or(0),
+            wireguard_implementation: endpoint
+                .wireguard_implementation
+                .map(|implementation| implementation.to_string())
+                .unwrap_or_default(),
         }
     }
 }
@@ -163,6 +170,14 @@ impl From<mullvad_types::states::TunnelState> for TunnelState {
                             talpid_tunnel::ErrorStateCause::SplitTunnelError => {
                                 i32::from(Cause::SplitTunnelError)
                             }
+                            #[cfg(not(target_os = "android"))]
+                            talpid_tunnel::ErrorStateCause::StrictEnforcement(_) => {
+                                i32::from(Cause::StrictEnforcement)
+                            }
+                            #[cfg(not(target_os = "android"))]
+                            talpid_tunnel::ErrorStateCause::RetryLimitReached(_) => {
+                                i32::from(Cause::RetryLimitReached)
+                            }
                         },
                         blocking_error: error_state.block_failure().map(map_firewall_error),
                         auth_fail_reason: if let talpid_tunnel::ErrorStateCause::AuthFailed(
@@ -202,6 +217,13 @@ impl From<mullvad_types::states::TunnelState> for TunnelState {
                             } else {
                                 None
                             },
+                        strict_enforcement_failure: match error_state.cause() {
+                            #[cfg(not(target_os = "android"))]
+                            talpid_tunnel::ErrorStateCause::StrictEnforcement(failure) => {
+                                failure.clone()
+                            }
+                            _ => "".to_string(),
+                        },
                     }),
                 })
             }
@@ -227,6 +249,188 @@ impl From<mullvad_types::device::Device> for Device {
     }
 }
 
+impl From<mullvad_types::interference::InterferenceReport> for InterferenceReport {
+    fn from(report: mullvad_types::interference::InterferenceReport) -> Self {
+        use mullvad_types::interference::{CheckKind, CheckOutcome};
+
+        InterferenceReport {
+            created: Some(Timestamp {
+                seconds: report.created.timestamp(),
+                nanos: 0,
+            }),
+            connection_failures: report.connection_failures,
+            checks: report
+                .checks
+                .into_iter()
+                .map(|check| InterferenceCheck {
+                    kind: i32::from(match check.kind {
+                        CheckKind::ApiDns => interference_check::Kind::ApiDns,
+                        CheckKind::WireguardUdp => interference_check::Kind::WireguardUdp,
+                        CheckKind::ApiTcp => interference_check::Kind::ApiTcp,
+                        CheckKind::ApiTls => interference_check::Kind::ApiTls,
+                    }),
+                    outcome: i32::from(match check.outcome {
+                        CheckOutcome::Passed => interference_check::Outcome::Passed,
+                        CheckOutcome::Failed => interference_check::Outcome::Failed,
+                        CheckOutcome::Inconclusive => interference_check::Outcome::Inconclusive,
+                    }),
+                    details: check.details,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<mullvad_types::leak_check::LeakReport> for LeakReport {
+    fn from(report: mullvad_types::leak_check::LeakReport) -> Self {
+        use mullvad_types::{interference::CheckOutcome, leak_check::LeakCheckKind};
+
+        LeakReport {
+            created: Some(Timestamp {
+                seconds: report.created.timestamp(),
+                nanos: 0,
+            }),
+            connected: report.connected,
+            checks: report
+                .checks
+                .into_iter()
+                .map(|check| LeakCheck {
+                    kind: i32::from(match check.kind {
+                        LeakCheckKind::ExitIp => leak_check::Kind::ExitIp,
+                        LeakCheckKind::Dns => leak_check::Kind::Dns,
+                        LeakCheckKind::Ipv6 => leak_check::Kind::Ipv6,
+                        LeakCheckKind::WebRtc => leak_check::Kind::Webrtc,
+                    }),
+                    outcome: i32::from(match check.outcome {
+                        CheckOutcome::Passed => interference_check::Outcome::Passed,
+                        CheckOutcome::Failed => interference_check::Outcome::Failed,
+                        CheckOutcome::Inconclusive => interference_check::Outcome::Inconclusive,
+                    }),
+                    details: check.details,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<mullvad_types::protocol_probe::ProbeReport> for ProbeReport {
+    fn from(report: mullvad_types::protocol_probe::ProbeReport) -> Self {
+        use mullvad_types::protocol_probe::{ProbeOutcome, ProbeProtocol};
+
+        ProbeReport {
+            created: Some(Timestamp {
+                seconds: report.created.timestamp(),
+                nanos: 0,
+            }),
+            results: report
+                .results
+                .into_iter()
+                .map(|result| {
+                    let (outcome, latency, details) = match result.outcome {
+                        ProbeOutcome::Reachable(latency) => (
+                            probe_result::Outcome::Reachable,
+                            Some(Duration::from(latency)),
+                            String::new(),
+                        ),
+                        ProbeOutcome::Unreachable(details) => {
+                            (probe_result::Outcome::Unreachable, None, details)
+                        }
+                        ProbeOutcome::Skipped(details) => {
+                            (probe_result::Outcome::Skipped, None, details)
+                        }
+                    };
+                    ProbeResult {
+                        protocol: i32::from(match result.protocol {
+                            ProbeProtocol::WireguardUdp => probe_result::Protocol::WireguardUdp,
+                            ProbeProtocol::Udp2Tcp => probe_result::Protocol::Udp2tcp,
+                            ProbeProtocol::OpenVpnUdp => probe_result::Protocol::OpenvpnUdp,
+                            ProbeProtocol::OpenVpnTcp => probe_result::Protocol::OpenvpnTcp,
+                            ProbeProtocol::Bridge => probe_result::Protocol::Bridge,
+                        }),
+                        port: u32::from(result.port),
+                        hostname: result.hostname,
+                        outcome: i32::from(outcome),
+                        latency,
+                        details,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<mullvad_types::failure_capsule::FailureCapsule> for FailureCapsule {
+    fn from(capsule: mullvad_types::failure_capsule::FailureCapsule) -> Self {
+        FailureCapsule {
+            started: Some(Timestamp {
+                seconds: capsule.started.timestamp(),
+                nanos: 0,
+            }),
+            failed: Some(Timestamp {
+                seconds: capsule.failed.timestamp(),
+                nanos: 0,
+            }),
+            cause: capsule.cause,
+            endpoint: capsule.endpoint,
+            parameters: capsule
+                .parameters
+                .into_iter()
+                .map(|(name, value)| failure_capsule::Parameter { name, value })
+                .collect(),
+            log: capsule.log,
+        }
+    }
+}
+
+impl From<mullvad_types::data_usage::TrafficCounters> for TrafficCounters {
+    fn from(traffic: mullvad_types::data_usage::TrafficCounters) -> Self {
+        TrafficCounters {
+            received: traffic.received,
+            sent: traffic.sent,
+        }
+    }
+}
+
+impl From<mullvad_types::data_usage::DataUsage> for DataUsage {
+    fn from(usage: mullvad_types::data_usage::DataUsage) -> Self {
+        DataUsage {
+            session: usage
+                .session
+                .map(|session| TrafficCounters::from(session.traffic)),
+            session_started: usage.session.map(|session| Timestamp {
+                seconds: session.started.timestamp(),
+                nanos: 0,
+            }),
+            months: usage
+                .months
+                .into_iter()
+                .map(|month| MonthlyDataUsage {
+                    year: month.year,
+                    month: month.month,
+                    traffic: Some(TrafficCounters::from(month.traffic)),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_stats::RelayStats> for RelayStats {
+    fn from(stats: mullvad_types::relay_stats::RelayStats) -> Self {
+        RelayStats {
+            average_handshake_time: stats.average_handshake_time().map(Duration::from),
+            average_throughput: stats.average_throughput().unwrap_or(0),
+            last_used: Some(Timestamp {
+                seconds: stats.last_used.timestamp(),
+                nanos: 0,
+            }),
+            bad: stats.is_bad(),
+            hostname: stats.hostname,
+            attempts: stats.attempts,
+            successes: stats.successes,
+        }
+    }
+}
+
 impl From<mullvad_types::device::DevicePort> for DevicePort {
     fn from(port: mullvad_types::device::DevicePort) -> Self {
         DevicePort { id: port.id }
@@ -287,6 +491,62 @@ impl From<mullvad_types::device::RemoveDeviceEvent> for RemoveDeviceEvent {
     }
 }
 
+impl From<mullvad_types::account::AccountExpiryEvent> for AccountExpiryEvent {
+    fn from(event: mullvad_types::account::AccountExpiryEvent) -> Self {
+        AccountExpiryEvent {
+            expiry: Some(Timestamp {
+                seconds: event.expiry.timestamp(),
+                nanos: 0,
+            }),
+            threshold: Some(Duration::from(event.threshold)),
+        }
+    }
+}
+
+impl From<mullvad_types::states::DisconnectWarningEvent> for DisconnectWarningEvent {
+    fn from(event: mullvad_types::states::DisconnectWarningEvent) -> Self {
+        use mullvad_types::states::DisconnectAction;
+        DisconnectWarningEvent {
+            action: i32::from(match event.action {
+                DisconnectAction::Disconnect => disconnect_warning_event::Action::Disconnect,
+                DisconnectAction::Reconnect => disconnect_warning_event::Action::Reconnect,
+            }),
+            throughput: event.throughput,
+        }
+    }
+}
+
+impl From<mullvad_types::relay_list::ExitRelayChangeEvent> for ExitRelayChangeEvent {
+    fn from(event: mullvad_types::relay_list::ExitRelayChangeEvent) -> Self {
+        use mullvad_types::relay_list::ExitRelayChange;
+
+        let mut country = String::new();
+        let mut city = String::new();
+        let change = match event.change {
+            ExitRelayChange::Moved {
+                country: new_country,
+                city: new_city,
+            } => {
+                country = new_country;
+                city = new_city;
+                exit_relay_change_event::Change::Moved
+            }
+            ExitRelayChange::ExcludedFromCountry => {
+                exit_relay_change_event::Change::ExcludedFromCountry
+            }
+            ExitRelayChange::Deactivated => exit_relay_change_event::Change::Deactivated,
+            ExitRelayChange::Removed => exit_relay_change_event::Change::Removed,
+        };
+        ExitRelayChangeEvent {
+            hostname: event.hostname,
+            change: i32::from(change),
+            country,
+            city,
+            reconnecting: event.reconnecting,
+        }
+    }
+}
+
 impl From<mullvad_types::device::AccountAndDevice> for AccountAndDevice {
     fn from(device: mullvad_types::device::AccountAndDevice) -> Self {
         AccountAndDevice {
@@ -382,6 +642,33 @@ impl From<talpid_types::net::TransportProtocol> for TransportProtocol {
     }
 }
 
+impl From<talpid_types::net::FirewallException> for FirewallException {
+    fn from(exception: talpid_types::net::FirewallException) -> Self {
+        let direction = match exception.direction {
+            talpid_types::net::FirewallExceptionDirection::Inbound => {
+                firewall_exception::Direction::Inbound
+            }
+            talpid_types::net::FirewallExceptionDirection::Outbound => {
+                firewall_exception::Direction::Outbound
+            }
+        };
+        FirewallException {
+            direction: i32::from(direction),
+            protocol: i32::from(TransportProtocol::from(exception.protocol)),
+            port: u32::from(exception.port),
+        }
+    }
+}
+
+impl From<talpid_types::net::InboundTunnelPort> for InboundTunnelPort {
+    fn from(port: talpid_types::net::InboundTunnelPort) -> Self {
+        InboundTunnelPort {
+            protocol: i32::from(TransportProtocol::from(port.protocol)),
+            port: u32::from(port.port),
+        }
+    }
+}
+
 impl From<talpid_types::net::IpVersion> for IpVersion {
     fn from(version: talpid_types::net::IpVersion) -> Self {
         match version {
@@ -445,11 +732,77 @@ impl From<mullvad_types::relay_constraints::LocationConstraint> for RelayLocatio
                 country,
                 city,
                 hostname,
+                ..Default::default()
+            },
+            #[cfg(not(target_os = "android"))]
+            LocationConstraint::CustomList(custom_list) => Self {
+                custom_list,
+                ..Default::default()
             },
         }
     }
 }
 
+impl From<&mullvad_types::custom_list::CustomListsSettings> for CustomListSettings {
+    fn from(settings: &mullvad_types::custom_list::CustomListsSettings) -> Self {
+        Self {
+            custom_lists: settings
+                .custom_lists
+                .iter()
+                .cloned()
+                .map(CustomList::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<CustomListSettings> for mullvad_types::custom_list::CustomListsSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: CustomListSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            custom_lists: settings
+                .custom_lists
+                .into_iter()
+                .map(mullvad_types::custom_list::CustomList::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl From<mullvad_types::custom_list::CustomList> for CustomList {
+    fn from(list: mullvad_types::custom_list::CustomList) -> Self {
+        Self {
+            name: list.name,
+            locations: list
+                .locations
+                .into_iter()
+                .map(RelayLocation::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<CustomList> for mullvad_types::custom_list::CustomList {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(list: CustomList) -> Result<Self, Self::Error> {
+        let locations =
+            list.locations
+                .into_iter()
+                .map(|location| {
+                    Constraint::from(location).option().ok_or(
+                        FromProtobufTypeError::InvalidArgument("empty location in custom list"),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name: list.name,
+            locations,
+        })
+    }
+}
+
 impl From<&mullvad_types::settings::Settings> for Settings {
     fn from(settings: &mullvad_types::settings::Settings) -> Self {
         #[cfg(windows)]
@@ -472,17 +825,339 @@ impl From<&mullvad_types::settings::Settings> for Settings {
         #[cfg(not(windows))]
         let split_tunnel = None;
 
+        #[cfg(windows)]
+        let link_layer_exemptions = Some(LinkLayerExemptions::from(settings.link_layer_exemptions));
+        #[cfg(not(windows))]
+        let link_layer_exemptions = None;
+
+        #[cfg(target_os = "linux")]
+        let dns_backend = Some(DnsBackend::from(settings.dns_backend));
+        #[cfg(not(target_os = "linux"))]
+        let dns_backend = None;
+
+        #[cfg(target_os = "linux")]
+        let policy_routing = Some(PolicyRouting::from(settings.policy_routing));
+        #[cfg(not(target_os = "linux"))]
+        let policy_routing = None;
+
         Self {
             relay_settings: Some(RelaySettings::from(settings.get_relay_settings())),
             bridge_settings: Some(BridgeSettings::from(settings.bridge_settings.clone())),
             bridge_state: Some(BridgeState::from(settings.get_bridge_state())),
             allow_lan: settings.allow_lan,
+            allowed_lan_nets: settings
+                .allowed_lan_nets
+                .as_ref()
+                .map(|nets| AllowedLanNets {
+                    networks: nets.iter().map(|net| net.to_string()).collect(),
+                }),
+            allow_multicast: settings.allow_multicast,
+            firewall_exceptions: Some(FirewallExceptions {
+                exceptions: settings
+                    .firewall_exceptions
+                    .iter()
+                    .map(|exception| FirewallException::from(*exception))
+                    .collect(),
+            }),
+            inbound_tunnel_ports: Some(InboundTunnelPorts {
+                ports: settings
+                    .inbound_tunnel_ports
+                    .iter()
+                    .map(|port| InboundTunnelPort::from(*port))
+                    .collect(),
+            }),
             block_when_disconnected: settings.block_when_disconnected,
+            strict_enforcement: settings.strict_enforcement,
             auto_connect: settings.auto_connect,
+            auto_connect_scope: Some(AutoConnectScope::from(settings.auto_connect_scope)),
+            exit_relay_change_policy: Some(ExitRelayChangePolicy::from(
+                settings.exit_relay_change_policy,
+            )),
+            expiry_notifications: Some(ExpiryNotificationSettings::from(
+                &settings.expiry_notifications,
+            )),
+            relay_list_update_interval: Some(Duration::from(
+                settings.relay_list_update_interval.as_duration(),
+            )),
+            relay_list_mirror: settings
+                .relay_list_mirror
+                .clone()
+                .map(RelayListMirror::from),
+            relay_overrides: settings
+                .relay_overrides
+                .iter()
+                .cloned()
+                .map(RelayOverride::from)
+                .collect(),
+            api_access_methods: Some(ApiAccessMethods::from(&settings.api_access_methods)),
+            circumvention_assistant: settings.circumvention_assistant,
+            avoid_bad_relays: settings.avoid_bad_relays,
+            relay_selection_strategy: Some(RelaySelectionStrategy::from(
+                settings.relay_selection_strategy,
+            )),
+            relay_failure_cooldown: Some(Duration::from(settings.relay_failure_cooldown)),
+            tunnel_hooks: Some(TunnelHookSettings::from(&settings.tunnel_hooks)),
+            disconnect_warning_threshold: settings.disconnect_warning_threshold.unwrap_or(0),
+            reconnect_debounce: Some(ReconnectDebounce::from(settings.reconnect_debounce)),
+            offline_detection: Some(OfflineDetection::from(settings.offline_detection)),
             tunnel_options: Some(TunnelOptions::from(&settings.tunnel_options)),
             show_beta_releases: settings.show_beta_releases,
             obfuscation_settings: Some(ObfuscationSettings::from(&settings.obfuscation_settings)),
             split_tunnel,
+            link_layer_exemptions,
+            dns_backend,
+            policy_routing,
+            app_exclusions: Some(AppExclusionSettings::from(settings.app_exclusions.clone())),
+            custom_lists: Some(CustomListSettings::from(&settings.custom_lists)),
+        }
+    }
+}
+
+impl From<mullvad_types::settings::AppExclusionSettings> for AppExclusionSettings {
+    fn from(settings: mullvad_types::settings::AppExclusionSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            excluded_packages: settings.excluded_packages,
+        }
+    }
+}
+
+impl From<AppExclusionSettings> for mullvad_types::settings::AppExclusionSettings {
+    fn from(settings: AppExclusionSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            excluded_packages: settings.excluded_packages,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<talpid_types::net::LinkLayerExemptions> for LinkLayerExemptions {
+    fn from(exemptions: talpid_types::net::LinkLayerExemptions) -> Self {
+        Self {
+            permit_dhcp: exemptions.permit_dhcp,
+            permit_ndp: exemptions.permit_ndp,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<LinkLayerExemptions> for talpid_types::net::LinkLayerExemptions {
+    fn from(exemptions: LinkLayerExemptions) -> Self {
+        Self {
+            permit_dhcp: exemptions.permit_dhcp,
+            permit_ndp: exemptions.permit_ndp,
+        }
+    }
+}
+
+impl From<talpid_types::net::DnsBackend> for DnsBackend {
+    fn from(backend: talpid_types::net::DnsBackend) -> Self {
+        use talpid_types::net::{DnsBackend as TalpidDnsBackend, DnssecMode};
+
+        match backend {
+            TalpidDnsBackend::Auto => Self {
+                backend: i32::from(dns_backend::Backend::Auto),
+                dnssec: i32::from(dns_backend::Dnssec::Global),
+            },
+            TalpidDnsBackend::SystemdResolved { dnssec } => Self {
+                backend: i32::from(dns_backend::Backend::SystemdResolved),
+                dnssec: i32::from(match dnssec {
+                    None => dns_backend::Dnssec::Global,
+                    Some(DnssecMode::No) => dns_backend::Dnssec::No,
+                    Some(DnssecMode::AllowDowngrade) => dns_backend::Dnssec::AllowDowngrade,
+                    Some(DnssecMode::Yes) => dns_backend::Dnssec::Yes,
+                }),
+            },
+        }
+    }
+}
+
+impl From<talpid_types::net::PolicyRouting> for PolicyRouting {
+    fn from(routing: talpid_types::net::PolicyRouting) -> Self {
+        Self {
+            fwmark: routing.fwmark,
+            table_id: routing.table_id,
+        }
+    }
+}
+
+impl TryFrom<PolicyRouting> for talpid_types::net::PolicyRouting {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(routing: PolicyRouting) -> Result<Self, Self::Error> {
+        let routing = talpid_types::net::PolicyRouting {
+            fwmark: routing.fwmark,
+            table_id: routing.table_id,
+        };
+        if !routing.is_valid() {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "the fwmark must not be zero, and the routing table must not be reserved",
+            ));
+        }
+        Ok(routing)
+    }
+}
+
+impl TryFrom<DnsBackend> for talpid_types::net::DnsBackend {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(backend: DnsBackend) -> Result<Self, Self::Error> {
+        use talpid_types::net::{DnsBackend as TalpidDnsBackend, DnssecMode};
+
+        let dnssec = match dns_backend::Dnssec::from_i32(backend.dnssec) {
+            Some(dns_backend::Dnssec::Global) => None,
+            Some(dns_backend::Dnssec::No) => Some(DnssecMode::No),
+            Some(dns_backend::Dnssec::AllowDowngrade) => Some(DnssecMode::AllowDowngrade),
+            Some(dns_backend::Dnssec::Yes) => Some(DnssecMode::Yes),
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid DNSSEC mode",
+                ))
+            }
+        };
+        match dns_backend::Backend::from_i32(backend.backend) {
+            Some(dns_backend::Backend::Auto) => Ok(TalpidDnsBackend::Auto),
+            Some(dns_backend::Backend::SystemdResolved) => {
+                Ok(TalpidDnsBackend::SystemdResolved { dnssec })
+            }
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid DNS backend",
+            )),
+        }
+    }
+}
+
+impl From<mullvad_types::settings::AutoConnectScope> for AutoConnectScope {
+    fn from(scope: mullvad_types::settings::AutoConnectScope) -> Self {
+        use mullvad_types::settings::AutoConnectScope;
+        Self {
+            scope: i32::from(match scope {
+                AutoConnectScope::User => auto_connect_scope::Scope::User,
+                AutoConnectScope::Machine => auto_connect_scope::Scope::Machine,
+            }),
+        }
+    }
+}
+
+impl From<&mullvad_types::settings::ExpiryNotificationSettings> for ExpiryNotificationSettings {
+    fn from(settings: &mullvad_types::settings::ExpiryNotificationSettings) -> Self {
+        Self {
+            thresholds: settings
+                .thresholds
+                .iter()
+                .map(|threshold| Duration::from(*threshold))
+                .collect(),
+            script: settings
+                .script
+                .as_ref()
+                .map(|script| script.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&mullvad_types::settings::TunnelHookSettings> for TunnelHookSettings {
+    fn from(settings: &mullvad_types::settings::TunnelHookSettings) -> Self {
+        let path = |script: &Option<std::path::PathBuf>| {
+            script
+                .as_ref()
+                .map(|script| script.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        Self {
+            connected: path(&settings.connected),
+            disconnected: path(&settings.disconnected),
+            error: path(&settings.error),
+        }
+    }
+}
+
+impl From<talpid_types::net::ReconnectDebounce> for ReconnectDebounce {
+    fn from(debounce: talpid_types::net::ReconnectDebounce) -> Self {
+        Self {
+            min_stable_interval: Some(Duration::from(debounce.min_stable_interval)),
+            max_reconnects_per_minute: debounce.max_reconnects_per_minute.unwrap_or(0),
+        }
+    }
+}
+
+impl From<talpid_types::net::OfflineDetection> for OfflineDetection {
+    fn from(detection: talpid_types::net::OfflineDetection) -> Self {
+        Self {
+            enabled: detection.enabled,
+            grace_period: Some(Duration::from(detection.grace_period)),
+        }
+    }
+}
+
+impl From<talpid_types::net::OfflineStatus> for OfflineStatus {
+    fn from(status: talpid_types::net::OfflineStatus) -> Self {
+        Self {
+            is_offline: status.is_offline,
+            monitor_offline: status.monitor_offline,
+            detection_method: status.detection_method,
+        }
+    }
+}
+
+impl From<talpid_types::net::CaptivePortal> for CaptivePortal {
+    fn from(portal: talpid_types::net::CaptivePortal) -> Self {
+        Self {
+            address: match portal {
+                talpid_types::net::CaptivePortal::Address(address) => address.to_string(),
+                talpid_types::net::CaptivePortal::AllTraffic => String::new(),
+            },
+        }
+    }
+}
+
+impl From<wireguard::LivenessOptions> for LivenessOptions {
+    fn from(options: wireguard::LivenessOptions) -> Self {
+        Self {
+            probe_interval: Some(Duration::from(options.probe_interval)),
+            probe_timeout: Some(Duration::from(options.probe_timeout)),
+            max_failures: options.max_failures,
+            active_probing: options.active_probing,
+        }
+    }
+}
+
+impl From<talpid_types::net::RetryPolicy> for RetryPolicy {
+    fn from(policy: talpid_types::net::RetryPolicy) -> Self {
+        Self {
+            initial_delay: Some(Duration::from(policy.initial_delay)),
+            multiplier: policy.multiplier,
+            max_delay: policy.max_delay.map(Duration::from),
+            max_attempts: policy.max_attempts.unwrap_or(0),
+        }
+    }
+}
+
+impl From<mullvad_types::settings::ExitRelayChangePolicy> for ExitRelayChangePolicy {
+    fn from(policy: mullvad_types::settings::ExitRelayChangePolicy) -> Self {
+        use mullvad_types::settings::ExitRelayChangePolicy;
+        Self {
+            policy: i32::from(match policy {
+                ExitRelayChangePolicy::Notify => exit_relay_change_policy::Policy::Notify,
+                ExitRelayChangePolicy::Reconnect => exit_relay_change_policy::Policy::Reconnect,
+            }),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_constraints::SelectionStrategy> for RelaySelectionStrategy {
+    fn from(strategy: mullvad_types::relay_constraints::SelectionStrategy) -> Self {
+        use mullvad_types::relay_constraints::SelectionStrategy;
+        Self {
+            strategy: i32::from(match strategy {
+                SelectionStrategy::Weighted => relay_selection_strategy::Strategy::Weighted,
+                SelectionStrategy::Random => relay_selection_strategy::Strategy::Random,
+                SelectionStrategy::LowestLatency => {
+                    relay_selection_strategy::Strategy::LowestLatency
+                }
+                SelectionStrategy::RoundRobin => relay_selection_strategy::Strategy::RoundRobin,
+            }),
         }
     }
 }
@@ -507,10 +1182,36 @@ impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for Obfuscatio
             SelectedObfuscation::Auto => obfuscation_settings::SelectedObfuscation::Auto,
             SelectedObfuscation::Off => obfuscation_settings::SelectedObfuscation::Off,
             SelectedObfuscation::Udp2Tcp => obfuscation_settings::SelectedObfuscation::Udp2tcp,
+            SelectedObfuscation::External => obfuscation_settings::SelectedObfuscation::External,
+            SelectedObfuscation::Wss => obfuscation_settings::SelectedObfuscation::Wss,
         });
         Self {
             selected_obfuscation,
             udp2tcp: Some(Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
+            external: Some(ExternalObfuscationSettings::from(&settings.external)),
+            wss: Some(WssObfuscationSettings::from(&settings.wss)),
+        }
+    }
+}
+
+impl From<&mullvad_types::relay_constraints::ExternalObfuscationSettings>
+    for ExternalObfuscationSettings
+{
+    fn from(settings: &mullvad_types::relay_constraints::ExternalObfuscationSettings) -> Self {
+        Self {
+            transport: settings
+                .transport
+                .as_ref()
+                .map(|transport| ExternalTransport {
+                    path: transport.path.to_string_lossy().into_owned(),
+                    args: transport.args.clone(),
+                    method: transport.method.clone(),
+                    method_args: transport.method_args.clone(),
+                }),
+            bridge: settings
+                .bridge
+                .map(|bridge| bridge.to_string())
+                .unwrap_or_default(),
         }
     }
 }
@@ -525,6 +1226,14 @@ impl From<&mullvad_types::relay_constraints::Udp2TcpObfuscationSettings>
     }
 }
 
+impl From<&mullvad_types::relay_constraints::WssObfuscationSettings> for WssObfuscationSettings {
+    fn from(settings: &mullvad_types::relay_constraints::WssObfuscationSettings) -> Self {
+        Self {
+            port: u32::from(settings.port.unwrap_or(0)),
+        }
+    }
+}
+
 impl From<mullvad_types::relay_constraints::BridgeSettings> for BridgeSettings {
     fn from(settings: mullvad_types::relay_constraints::BridgeSettings) -> Self {
         use mullvad_types::relay_constraints::BridgeSettings as MullvadBridgeSettings;
@@ -540,6 +1249,8 @@ impl From<mullvad_types::relay_constraints::BridgeSettings> for BridgeSettings {
                         .map(RelayLocation::from),
                     providers: convert_providers_constraint(&constraints.providers),
                     ownership: convert_ownership_constraint(&constraints.ownership) as i32,
+                    port: constraints.port.map(u32::from).unwrap_or(0),
+                    cipher: constraints.cipher.option().unwrap_or_default(),
                 })
             }
             MullvadBridgeSettings::Custom(proxy_settings) => match proxy_settings {
@@ -586,13 +1297,20 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for RelaySettings {
                 relay_settings::Endpoint::Custom(CustomRelaySettings {
                     host: endpoint.host,
                     config: Some(ConnectionConfig::from(endpoint.config)),
+                    use_entry_relay: endpoint.entry_location.is_some(),
+                    entry_location: endpoint
+                        .entry_location
+                        .and_then(Constraint::option)
+                        .map(RelayLocation::from),
                 })
             }
             MullvadRelaySettings::Normal(constraints) => {
                 relay_settings::Endpoint::Normal(NormalRelaySettings {
                     location: constraints.location.option().map(RelayLocation::from),
+                    location_set: Some(LocationSet::from(constraints.location_set)),
                     providers: convert_providers_constraint(&constraints.providers),
                     ownership: convert_ownership_constraint(&constraints.ownership) as i32,
+                    datacenter: convert_datacenter_constraint(&constraints.datacenter),
                     tunnel_type: match constraints.tunnel_protocol {
                         Constraint::Any => None,
                         Constraint::Only(talpid_net::TunnelType::Wireguard) => {
@@ -661,6 +1379,68 @@ impl From<&mullvad_types::settings::DnsOptions> for DnsOptions {
                     .map(|addr| addr.to_string())
                     .collect(),
             }),
+            split_rules: options.split_rules.iter().map(SplitDnsRule::from).collect(),
+            search_domains: options.search_domains.clone(),
+            strict_leak_prevention: options.strict_leak_prevention,
+            filter_rules: options
+                .filter_rules
+                .iter()
+                .map(DnsFilterRule::from)
+                .collect(),
+            query_log: options.query_log,
+        }
+    }
+}
+
+impl From<&talpid_types::net::dns::SplitDnsRule> for SplitDnsRule {
+    fn from(rule: &talpid_types::net::dns::SplitDnsRule) -> Self {
+        SplitDnsRule {
+            domain: rule.domain.clone(),
+            servers: rule.servers.iter().map(|addr| addr.to_string()).collect(),
+        }
+    }
+}
+
+impl From<&talpid_types::net::dns::DnsFilterRule> for DnsFilterRule {
+    fn from(rule: &talpid_types::net::dns::DnsFilterRule) -> Self {
+        use talpid_types::net::dns::DnsFilterAction;
+
+        let (action, addresses) = match &rule.action {
+            DnsFilterAction::Block => (dns_filter_rule::Action::Block, vec![]),
+            DnsFilterAction::Rewrite(addresses) => (
+                dns_filter_rule::Action::Rewrite,
+                addresses.iter().map(|addr| addr.to_string()).collect(),
+            ),
+        };
+        DnsFilterRule {
+            domain: rule.domain.clone(),
+            action: action as i32,
+            addresses,
+        }
+    }
+}
+
+impl From<talpid_types::net::dns::DnsQueryLogEntry> for DnsQueryLogEntry {
+    fn from(entry: talpid_types::net::dns::DnsQueryLogEntry) -> Self {
+        use talpid_types::net::dns::DnsQueryOutcome;
+
+        let (outcome, server) = match entry.outcome {
+            DnsQueryOutcome::Resolved(server) => {
+                (dns_query_log_entry::Outcome::Resolved, server.to_string())
+            }
+            DnsQueryOutcome::Blocked => (dns_query_log_entry::Outcome::Blocked, String::new()),
+            DnsQueryOutcome::Rewritten => (dns_query_log_entry::Outcome::Rewritten, String::new()),
+            DnsQueryOutcome::NoResponse => {
+                (dns_query_log_entry::Outcome::NoResponse, String::new())
+            }
+        };
+        DnsQueryLogEntry {
+            sequence: entry.sequence,
+            time: Some(Timestamp::from(entry.time)),
+            name: entry.name,
+            query_type: u32::from(entry.query_type),
+            outcome: outcome as i32,
+            server,
         }
     }
 }
@@ -682,9 +1462,36 @@ impl From<&mullvad_types::settings::TunnelOptions> for TunnelOptions {
                 #[cfg(not(windows))]
                 use_wireguard_nt: false,
                 use_pq_safe_psk: options.wireguard.options.use_pq_safe_psk,
+                liveness: Some(LivenessOptions::from(options.wireguard.options.liveness)),
+                #[cfg(target_os = "linux")]
+                namespace: options
+                    .wireguard
+                    .options
+                    .namespace
+                    .clone()
+                    .unwrap_or_default(),
+                #[cfg(not(target_os = "linux"))]
+                namespace: String::new(),
+                persistent_keepalive: u32::from(options.wireguard.options.persistent_keepalive),
+                #[cfg(not(target_os = "android"))]
+                allowed_ips: options
+                    .wireguard
+                    .options
+                    .allowed_ips
+                    .iter()
+                    .flatten()
+                    .map(|net| net.to_string())
+                    .collect(),
+                #[cfg(target_os = "android")]
+                allowed_ips: vec![],
             }),
             generic: Some(tunnel_options::GenericOptions {
                 enable_ipv6: options.generic.enable_ipv6,
+                retry_policy: Some(RetryPolicy::from(options.generic.retry_policy)),
+                #[cfg(target_os = "linux")]
+                rate_limit: options.generic.rate_limit.unwrap_or(0),
+                #[cfg(not(target_os = "linux"))]
+                rate_limit: 0,
             }),
             #[cfg(not(target_os = "android"))]
             dns_options: Some(DnsOptions::from(&options.dns_options)),
@@ -694,6 +1501,216 @@ impl From<&mullvad_types::settings::TunnelOptions> for TunnelOptions {
     }
 }
 
+impl From<mullvad_types::relay_list::RelayListInfo> for RelayListInfo {
+    fn from(info: mullvad_types::relay_list::RelayListInfo) -> Self {
+        RelayListInfo {
+            last_updated: Some(Timestamp::from(info.last_updated)),
+            etag: info.etag.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_list::RelayListMirror> for RelayListMirror {
+    fn from(mirror: mullvad_types::relay_list::RelayListMirror) -> Self {
+        RelayListMirror {
+            url: mirror.url,
+            public_key: mirror.public_key.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<RelayListMirror> for mullvad_types::relay_list::RelayListMirror {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(mirror: RelayListMirror) -> Result<Self, Self::Error> {
+        if !mirror.url.starts_with("https://") {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "the mirror URL must use HTTPS",
+            ));
+        }
+        let public_key = <[u8; 32]>::try_from(&mirror.public_key[..])
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid public key length"))?;
+        Ok(mullvad_types::relay_list::RelayListMirror {
+            url: mirror.url,
+            public_key,
+        })
+    }
+}
+
+impl From<mullvad_types::relay_list::RelayOverride> for RelayOverride {
+    fn from(relay_override: mullvad_types::relay_list::RelayOverride) -> Self {
+        RelayOverride {
+            hostname: relay_override.hostname,
+            ipv4_addr_in: relay_override
+                .ipv4_addr_in
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+            ipv6_addr_in: relay_override
+                .ipv6_addr_in
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl TryFrom<RelayOverride> for mullvad_types::relay_list::RelayOverride {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(relay_override: RelayOverride) -> Result<Self, Self::Error> {
+        if relay_override.hostname.is_empty() {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "the relay override must name a relay",
+            ));
+        }
+        Ok(mullvad_types::relay_list::RelayOverride {
+            ipv4_addr_in: parse_override_address(&relay_override.ipv4_addr_in)?,
+            ipv6_addr_in: parse_override_address(&relay_override.ipv6_addr_in)?,
+            hostname: relay_override.hostname,
+        })
+    }
+}
+
+fn parse_override_address<T: std::str::FromStr>(
+    address: &str,
+) -> Result<Option<T>, FromProtobufTypeError> {
+    if address.is_empty() {
+        return Ok(None);
+    }
+    address
+        .parse()
+        .map(Some)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP address"))
+}
+
+impl From<&mullvad_types::access_method::ApiAccessMethodSettings> for ApiAccessMethods {
+    fn from(settings: &mullvad_types::access_method::ApiAccessMethodSettings) -> Self {
+        use mullvad_types::access_method::AccessMethod as MullvadAccessMethod;
+
+        let methods = settings
+            .methods
+            .iter()
+            .map(|method| ApiAccessMethod {
+                name: method.name.clone(),
+                enabled: method.enabled,
+                access_method: Some(match &method.access_method {
+                    MullvadAccessMethod::Direct => {
+                        api_access_method::AccessMethod::Direct(api_access_method::Direct {})
+                    }
+                    MullvadAccessMethod::Bridges => {
+                        api_access_method::AccessMethod::Bridges(api_access_method::Bridges {})
+                    }
+                    MullvadAccessMethod::Shadowsocks(settings) => {
+                        api_access_method::AccessMethod::Shadowsocks(
+                            bridge_settings::ShadowsocksProxySettings {
+                                peer: settings.peer.to_string(),
+                                password: settings.password.clone(),
+                                cipher: settings.cipher.clone(),
+                            },
+                        )
+                    }
+                    MullvadAccessMethod::Socks5(settings) => {
+                        api_access_method::AccessMethod::Socks5(remote_proxy_settings(settings))
+                    }
+                    MullvadAccessMethod::Http(settings) => {
+                        api_access_method::AccessMethod::Http(remote_proxy_settings(settings))
+                    }
+                }),
+            })
+            .collect();
+        ApiAccessMethods { methods }
+    }
+}
+
+impl TryFrom<ApiAccessMethods> for mullvad_types::access_method::ApiAccessMethodSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: ApiAccessMethods) -> Result<Self, Self::Error> {
+        use mullvad_types::access_method::{
+            AccessMethod as MullvadAccessMethod, ApiAccessMethod as MullvadApiAccessMethod,
+        };
+        use talpid_types::net::openvpn;
+
+        let mut methods: Vec<MullvadApiAccessMethod> = Vec::with_capacity(settings.methods.len());
+        for method in settings.methods {
+            if method.name.is_empty() {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "access method names must not be empty",
+                ));
+            }
+            if methods.iter().any(|existing| existing.name == method.name) {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "access method names must be unique",
+                ));
+            }
+
+            let access_method =
+                match method
+                    .access_method
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "missing access method",
+                    ))? {
+                    api_access_method::AccessMethod::Direct(_) => MullvadAccessMethod::Direct,
+                    api_access_method::AccessMethod::Bridges(_) => MullvadAccessMethod::Bridges,
+                    api_access_method::AccessMethod::Shadowsocks(settings) => {
+                        let peer = settings.peer.parse().map_err(|_| {
+                            FromProtobufTypeError::InvalidArgument("failed to parse peer address")
+                        })?;
+                        MullvadAccessMethod::Shadowsocks(openvpn::ShadowsocksProxySettings {
+                            peer,
+                            password: settings.password,
+                            cipher: settings.cipher,
+                        })
+                    }
+                    api_access_method::AccessMethod::Socks5(settings) => {
+                        MullvadAccessMethod::Socks5(try_remote_proxy_settings(settings)?)
+                    }
+                    api_access_method::AccessMethod::Http(settings) => {
+                        MullvadAccessMethod::Http(try_remote_proxy_settings(settings)?)
+                    }
+                };
+
+            methods.push(MullvadApiAccessMethod {
+                name: method.name,
+                enabled: method.enabled,
+                access_method,
+            });
+        }
+
+        Ok(mullvad_types::access_method::ApiAccessMethodSettings { methods })
+    }
+}
+
+fn remote_proxy_settings(
+    settings: &talpid_types::net::openvpn::RemoteProxySettings,
+) -> bridge_settings::RemoteProxySettings {
+    bridge_settings::RemoteProxySettings {
+        address: settings.address.to_string(),
+        auth: settings
+            .auth
+            .as_ref()
+            .map(|auth| bridge_settings::RemoteProxyAuth {
+                username: auth.username.clone(),
+                password: auth.password.clone(),
+            }),
+    }
+}
+
+fn try_remote_proxy_settings(
+    settings: bridge_settings::RemoteProxySettings,
+) -> Result<talpid_types::net::openvpn::RemoteProxySettings, FromProtobufTypeError> {
+    let address = settings
+        .address
+        .parse()
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("failed to parse proxy address"))?;
+    let auth = settings
+        .auth
+        .map(|auth| talpid_types::net::openvpn::ProxyAuth {
+            username: auth.username,
+            password: auth.password,
+        });
+    Ok(talpid_types::net::openvpn::RemoteProxySettings { address, auth })
+}
+
 impl From<mullvad_types::relay_list::RelayListCountry> for RelayListCountry {
     fn from(country: mullvad_types::relay_list::RelayListCountry) -> Self {
         let mut proto_country = RelayListCountry {
@@ -729,6 +1746,8 @@ impl From<mullvad_types::relay_list::Relay> for Relay {
             active: relay.active,
             owned: relay.owned,
             provider: relay.provider,
+            provider_name: relay.provider_name.unwrap_or_default(),
+            datacenter: relay.datacenter.unwrap_or_default(),
             weight: relay.weight,
             tunnels: Some(RelayTunnels {
                 openvpn: relay
@@ -775,6 +1794,20 @@ impl From<mullvad_types::relay_list::Relay> for Relay {
                     })
                     .collect(),
             }),
+            obfuscators: Some(RelayObfuscators {
+                udp2tcp_ports: relay
+                    .obfuscators
+                    .udp2tcp
+                    .iter()
+                    .map(|endpoint| u32::from(endpoint.port))
+                    .collect(),
+                wss_ports: relay
+                    .obfuscators
+                    .wss
+                    .iter()
+                    .map(|endpoint| u32::from(endpoint.port))
+                    .collect(),
+            }),
             location: relay.location.map(|location| Location {
                 country: location.country,
                 country_code: location.country_code,
@@ -831,6 +1864,215 @@ impl TryFrom<Device> for mullvad_types::device::Device {
     }
 }
 
+impl TryFrom<InterferenceReport> for mullvad_types::interference::InterferenceReport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(report: InterferenceReport) -> Result<Self, Self::Error> {
+        use mullvad_types::interference::{CheckKind, CheckOutcome, InterferenceCheck as Check};
+
+        let created = report
+            .created
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'created' field",
+            ))?;
+        let checks = report
+            .checks
+            .into_iter()
+            .map(|check| {
+                let kind = match interference_check::Kind::from_i32(check.kind) {
+                    Some(interference_check::Kind::ApiDns) => CheckKind::ApiDns,
+                    Some(interference_check::Kind::WireguardUdp) => CheckKind::WireguardUdp,
+                    Some(interference_check::Kind::ApiTcp) => CheckKind::ApiTcp,
+                    Some(interference_check::Kind::ApiTls) => CheckKind::ApiTls,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid interference check",
+                        ))
+                    }
+                };
+                let outcome = match interference_check::Outcome::from_i32(check.outcome) {
+                    Some(interference_check::Outcome::Passed) => CheckOutcome::Passed,
+                    Some(interference_check::Outcome::Failed) => CheckOutcome::Failed,
+                    Some(interference_check::Outcome::Inconclusive) => CheckOutcome::Inconclusive,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid interference check outcome",
+                        ))
+                    }
+                };
+                Ok(Check {
+                    kind,
+                    outcome,
+                    details: check.details,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mullvad_types::interference::InterferenceReport {
+            created: chrono::DateTime::from_utc(
+                chrono::NaiveDateTime::from_timestamp(created.seconds, 0),
+                chrono::Utc,
+            ),
+            connection_failures: report.connection_failures,
+            checks,
+        })
+    }
+}
+
+impl TryFrom<LeakReport> for mullvad_types::leak_check::LeakReport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(report: LeakReport) -> Result<Self, Self::Error> {
+        use mullvad_types::{
+            interference::CheckOutcome,
+            leak_check::{LeakCheck as Check, LeakCheckKind},
+        };
+
+        let created = report
+            .created
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'created' field",
+            ))?;
+        let checks = report
+            .checks
+            .into_iter()
+            .map(|check| {
+                let kind = match leak_check::Kind::from_i32(check.kind) {
+                    Some(leak_check::Kind::ExitIp) => LeakCheckKind::ExitIp,
+                    Some(leak_check::Kind::Dns) => LeakCheckKind::Dns,
+                    Some(leak_check::Kind::Ipv6) => LeakCheckKind::Ipv6,
+                    Some(leak_check::Kind::Webrtc) => LeakCheckKind::WebRtc,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument("invalid leak check"))
+                    }
+                };
+                let outcome = match interference_check::Outcome::from_i32(check.outcome) {
+                    Some(interference_check::Outcome::Passed) => CheckOutcome::Passed,
+                    Some(interference_check::Outcome::Failed) => CheckOutcome::Failed,
+                    Some(interference_check::Outcome::Inconclusive) => CheckOutcome::Inconclusive,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid leak check outcome",
+                        ))
+                    }
+                };
+                Ok(Check {
+                    kind,
+                    outcome,
+                    details: check.details,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mullvad_types::leak_check::LeakReport {
+            created: chrono::DateTime::from_utc(
+                chrono::NaiveDateTime::from_timestamp(created.seconds, 0),
+                chrono::Utc,
+            ),
+            connected: report.connected,
+            checks,
+        })
+    }
+}
+
+impl TryFrom<ProbeReport> for mullvad_types::protocol_probe::ProbeReport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(report: ProbeReport) -> Result<Self, Self::Error> {
+        use mullvad_types::protocol_probe::{ProbeOutcome, ProbeProtocol, ProbeResult as Probe};
+
+        let created = report
+            .created
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'created' field",
+            ))?;
+        let results = report
+            .results
+            .into_iter()
+            .map(|result| {
+                let protocol = match probe_result::Protocol::from_i32(result.protocol) {
+                    Some(probe_result::Protocol::WireguardUdp) => ProbeProtocol::WireguardUdp,
+                    Some(probe_result::Protocol::Udp2tcp) => ProbeProtocol::Udp2Tcp,
+                    Some(probe_result::Protocol::OpenvpnUdp) => ProbeProtocol::OpenVpnUdp,
+                    Some(probe_result::Protocol::OpenvpnTcp) => ProbeProtocol::OpenVpnTcp,
+                    Some(probe_result::Protocol::Bridge) => ProbeProtocol::Bridge,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid probe protocol",
+                        ))
+                    }
+                };
+                let outcome = match probe_result::Outcome::from_i32(result.outcome) {
+                    Some(probe_result::Outcome::Reachable) => {
+                        let latency = result
+                            .latency
+                            .and_then(|latency| std::time::Duration::try_from(latency).ok())
+                            .ok_or(FromProtobufTypeError::InvalidArgument(
+                                "missing or invalid probe latency",
+                            ))?;
+                        ProbeOutcome::Reachable(latency)
+                    }
+                    Some(probe_result::Outcome::Unreachable) => {
+                        ProbeOutcome::Unreachable(result.details)
+                    }
+                    Some(probe_result::Outcome::Skipped) => ProbeOutcome::Skipped(result.details),
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid probe outcome",
+                        ))
+                    }
+                };
+                let port = u16::try_from(result.port)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))?;
+                Ok(Probe {
+                    protocol,
+                    port,
+                    hostname: result.hostname,
+                    outcome,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mullvad_types::protocol_probe::ProbeReport {
+            created: chrono::DateTime::from_utc(
+                chrono::NaiveDateTime::from_timestamp(created.seconds, 0),
+                chrono::Utc,
+            ),
+            results,
+        })
+    }
+}
+
+impl TryFrom<FailureCapsule> for mullvad_types::failure_capsule::FailureCapsule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(capsule: FailureCapsule) -> Result<Self, Self::Error> {
+        let to_date_time = |timestamp: Option<Timestamp>, missing_error: &'static str| {
+            timestamp
+                .map(|timestamp| {
+                    chrono::DateTime::from_utc(
+                        chrono::NaiveDateTime::from_timestamp(timestamp.seconds, 0),
+                        chrono::Utc,
+                    )
+                })
+                .ok_or(FromProtobufTypeError::InvalidArgument(missing_error))
+        };
+
+        Ok(mullvad_types::failure_capsule::FailureCapsule {
+            started: to_date_time(capsule.started, "missing 'started' field")?,
+            failed: to_date_time(capsule.failed, "missing 'failed' field")?,
+            cause: capsule.cause,
+            endpoint: capsule.endpoint,
+            parameters: capsule
+                .parameters
+                .into_iter()
+                .map(|parameter| (parameter.name, parameter.value))
+                .collect(),
+            log: capsule.log,
+        })
+    }
+}
+
 impl From<DevicePort> for mullvad_types::device::DevicePort {
     fn from(port: DevicePort) -> Self {
         mullvad_types::device::DevicePort { id: port.id }
@@ -899,7 +2141,7 @@ impl TryFrom<RelaySettings> for mullvad_types::relay_constraints::RelaySettings
     fn try_from(
         settings: RelaySettings,
     ) -> Result<mullvad_types::relay_constraints::RelaySettings, Self::Error> {
-        use mullvad_types::{relay_constraints as mullvad_constraints, CustomTunnelEndpoint};
+        use mullvad_types::relay_constraints as mullvad_constraints;
         use talpid_types::net;
 
         let update_value = settings
@@ -910,17 +2152,8 @@ impl TryFrom<RelaySettings> for mullvad_types::relay_constraints::RelaySettings
 
         match update_value {
             relay_settings::Endpoint::Custom(settings) => {
-                let config = settings
-                    .config
-                    .ok_or(FromProtobufTypeError::InvalidArgument(
-                        "missing relay connection config",
-                    ))?;
-                let config = mullvad_types::ConnectionConfig::try_from(config)?;
                 Ok(mullvad_constraints::RelaySettings::CustomTunnelEndpoint(
-                    CustomTunnelEndpoint {
-                        host: settings.host,
-                        config,
-                    },
+                    mullvad_types::CustomTunnelEndpoint::try_from(settings)?,
                 ))
             }
 
@@ -929,8 +2162,14 @@ impl TryFrom<RelaySettings> for mullvad_types::relay_constraints::RelaySettings
                     .location
                     .map(Constraint::<mullvad_types::relay_constraints::LocationConstraint>::from)
                     .unwrap_or(Constraint::Any);
+                let location_set = settings
+                    .location_set
+                    .map(ConstraintSet::try_from)
+                    .transpose()?
+                    .unwrap_or_default();
                 let providers = try_providers_constraint_from_proto(&settings.providers)?;
                 let ownership = try_ownership_constraint_from_i32(settings.ownership)?;
+                let datacenter = datacenter_constraint_from_proto(settings.datacenter);
                 let tunnel_protocol = settings
                     .tunnel_type
                     .map(Constraint::<net::TunnelType>::try_from)
@@ -951,8 +2190,10 @@ impl TryFrom<RelaySettings> for mullvad_types::relay_constraints::RelaySettings
                 Ok(mullvad_constraints::RelaySettings::Normal(
                     mullvad_constraints::RelayConstraints {
                         location,
+                        location_set,
                         providers,
                         ownership,
+                        datacenter,
                         tunnel_protocol,
                         wireguard_constraints,
                         openvpn_constraints,
@@ -969,7 +2210,7 @@ impl TryFrom<RelaySettingsUpdate> for mullvad_types::relay_constraints::RelaySet
     fn try_from(
         settings: RelaySettingsUpdate,
     ) -> Result<mullvad_types::relay_constraints::RelaySettingsUpdate, Self::Error> {
-        use mullvad_types::{relay_constraints as mullvad_constraints, CustomTunnelEndpoint};
+        use mullvad_types::relay_constraints as mullvad_constraints;
         use talpid_types::net;
 
         let update_value = settings
@@ -979,22 +2220,11 @@ impl TryFrom<RelaySettingsUpdate> for mullvad_types::relay_constraints::RelaySet
             ))?;
 
         match update_value {
-            relay_settings_update::Type::Custom(settings) => {
-                let config = settings
-                    .config
-                    .ok_or(FromProtobufTypeError::InvalidArgument(
-                        "missing relay connection config",
-                    ))?;
-                let config = mullvad_types::ConnectionConfig::try_from(config)?;
-                Ok(
-                    mullvad_constraints::RelaySettingsUpdate::CustomTunnelEndpoint(
-                        CustomTunnelEndpoint {
-                            host: settings.host,
-                            config,
-                        },
-                    ),
-                )
-            }
+            relay_settings_update::Type::Custom(settings) => Ok(
+                mullvad_constraints::RelaySettingsUpdate::CustomTunnelEndpoint(
+                    mullvad_types::CustomTunnelEndpoint::try_from(settings)?,
+                ),
+            ),
 
             relay_settings_update::Type::Normal(settings) => {
                 // If `location` isn't provided, no changes are made.
@@ -1003,6 +2233,10 @@ impl TryFrom<RelaySettingsUpdate> for mullvad_types::relay_constraints::RelaySet
                 let location = settings
                     .location
                     .map(Constraint::<mullvad_types::relay_constraints::LocationConstraint>::from);
+                let location_set = settings
+                    .location_set
+                    .map(ConstraintSet::try_from)
+                    .transpose()?;
                 let providers = if let Some(ref provider_update) = settings.providers {
                     Some(try_providers_constraint_from_proto(
                         &provider_update.providers,
@@ -1017,6 +2251,9 @@ impl TryFrom<RelaySettingsUpdate> for mullvad_types::relay_constraints::RelaySet
                 } else {
                     None
                 };
+                let datacenter = settings
+                    .datacenter
+                    .map(|update| datacenter_constraint_from_proto(update.datacenter));
                 let tunnel_protocol = if let Some(update) = settings.tunnel_type {
                     Some(
                         update
@@ -1047,8 +2284,10 @@ impl TryFrom<RelaySettingsUpdate> for mullvad_types::relay_constraints::RelaySet
                 Ok(mullvad_constraints::RelaySettingsUpdate::Normal(
                     mullvad_constraints::RelayConstraintsUpdate {
                         location,
+                        location_set,
                         providers,
                         ownership,
+                        datacenter,
                         tunnel_protocol,
                         wireguard_constraints,
                         openvpn_constraints,
@@ -1079,6 +2318,43 @@ impl TryFrom<TunnelTypeConstraint> for Constraint<talpid_types::net::TunnelType>
     }
 }
 
+impl TryFrom<CustomRelaySettings> for mullvad_types::CustomTunnelEndpoint {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(
+        settings: CustomRelaySettings,
+    ) -> Result<mullvad_types::CustomTunnelEndpoint, Self::Error> {
+        let config = settings
+            .config
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing relay connection config",
+            ))?;
+        let config = mullvad_types::ConnectionConfig::try_from(config)?;
+
+        let entry_location = if settings.use_entry_relay {
+            if let mullvad_types::ConnectionConfig::OpenVpn(_) = config {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "entry relays are only supported for WireGuard",
+                ));
+            }
+            Some(
+                settings
+                    .entry_location
+                    .map(Constraint::<mullvad_types::relay_constraints::LocationConstraint>::from)
+                    .unwrap_or(Constraint::Any),
+            )
+        } else {
+            None
+        };
+
+        Ok(mullvad_types::CustomTunnelEndpoint {
+            host: settings.host,
+            config,
+            entry_location,
+        })
+    }
+}
+
 impl TryFrom<ConnectionConfig> for mullvad_types::ConnectionConfig {
     type Error = FromProtobufTypeError;
 
@@ -1212,6 +2488,10 @@ impl From<RelayLocation> for Constraint<mullvad_types::relay_constraints::Locati
     fn from(location: RelayLocation) -> Self {
         use mullvad_types::relay_constraints::LocationConstraint;
 
+        #[cfg(not(target_os = "android"))]
+        if !location.custom_list.is_empty() {
+            return Constraint::Only(LocationConstraint::CustomList(location.custom_list));
+        }
         if !location.hostname.is_empty() {
             Constraint::Only(LocationConstraint::Hostname(
                 location.country,
@@ -1228,6 +2508,36 @@ impl From<RelayLocation> for Constraint<mullvad_types::relay_constraints::Locati
     }
 }
 
+impl From<ConstraintSet<mullvad_types::relay_constraints::LocationConstraint>> for LocationSet {
+    fn from(set: ConstraintSet<mullvad_types::relay_constraints::LocationConstraint>) -> Self {
+        LocationSet {
+            include: set.include.into_iter().map(RelayLocation::from).collect(),
+            exclude: set.exclude.into_iter().map(RelayLocation::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<LocationSet> for ConstraintSet<mullvad_types::relay_constraints::LocationConstraint> {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(set: LocationSet) -> Result<Self, Self::Error> {
+        let convert = |locations: Vec<RelayLocation>| {
+            locations
+                .into_iter()
+                .map(|location| {
+                    Constraint::from(location).option().ok_or(
+                        FromProtobufTypeError::InvalidArgument("empty location in location set"),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(ConstraintSet {
+            include: convert(set.include)?,
+            exclude: convert(set.exclude)?,
+        })
+    }
+}
+
 impl TryFrom<BridgeSettings> for mullvad_types::relay_constraints::BridgeSettings {
     type Error = FromProtobufTypeError;
 
@@ -1249,12 +2559,31 @@ impl TryFrom<BridgeSettings> for mullvad_types::relay_constraints::BridgeSetting
                 };
                 let providers = try_providers_constraint_from_proto(&constraints.providers)?;
                 let ownership = try_ownership_constraint_from_i32(constraints.ownership)?;
+                let port = match constraints.port {
+                    0 => Constraint::Any,
+                    port => Constraint::Only(u16::try_from(port).map_err(|_| {
+                        FromProtobufTypeError::InvalidArgument("invalid bridge port")
+                    })?),
+                };
+                let cipher = if constraints.cipher.is_empty() {
+                    Constraint::Any
+                } else if talpid_net::openvpn::SHADOWSOCKS_CIPHERS
+                    .contains(&constraints.cipher.as_str())
+                {
+                    Constraint::Only(constraints.cipher)
+                } else {
+                    return Err(FromProtobufTypeError::InvalidArgument(
+                        "invalid Shadowsocks cipher",
+                    ));
+                };
 
                 Ok(mullvad_constraints::BridgeSettings::Normal(
                     mullvad_constraints::BridgeConstraints {
                         location,
                         providers,
                         ownership,
+                        port,
+                        cipher,
                     },
                 ))
             }
@@ -1313,6 +2642,8 @@ impl TryFrom<ObfuscationSettings> for mullvad_types::relay_constraints::Obfuscat
                 Some(IpcSelectedObfuscation::Auto) => SelectedObfuscation::Auto,
                 Some(IpcSelectedObfuscation::Off) => SelectedObfuscation::Off,
                 Some(IpcSelectedObfuscation::Udp2tcp) => SelectedObfuscation::Udp2Tcp,
+                Some(IpcSelectedObfuscation::External) => SelectedObfuscation::External,
+                Some(IpcSelectedObfuscation::Wss) => SelectedObfuscation::Wss,
                 None => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "invalid selected obfuscator",
@@ -1331,13 +2662,69 @@ impl TryFrom<ObfuscationSettings> for mullvad_types::relay_constraints::Obfuscat
             }
         };
 
+        let external = match settings.external {
+            Some(settings) => {
+                mullvad_types::relay_constraints::ExternalObfuscationSettings::try_from(settings)?
+            }
+            None => Default::default(),
+        };
+
+        let wss = match settings.wss {
+            Some(settings) => {
+                mullvad_types::relay_constraints::WssObfuscationSettings::try_from(&settings)?
+            }
+            None => Default::default(),
+        };
+
         Ok(Self {
             selected_obfuscation,
             udp2tcp,
+            wss,
+            external,
         })
     }
 }
 
+impl TryFrom<ExternalObfuscationSettings>
+    for mullvad_types::relay_constraints::ExternalObfuscationSettings
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: ExternalObfuscationSettings) -> Result<Self, Self::Error> {
+        let transport = match settings.transport {
+            Some(transport) => {
+                let path = std::path::PathBuf::from(transport.path);
+                if !path.is_absolute() {
+                    return Err(FromProtobufTypeError::InvalidArgument(
+                        "transport path must be absolute",
+                    ));
+                }
+                if transport.method.is_empty() {
+                    return Err(FromProtobufTypeError::InvalidArgument(
+                        "missing transport method",
+                    ));
+                }
+                Some(talpid_types::net::obfuscation::ExternalTransportConfig {
+                    path,
+                    args: transport.args,
+                    method: transport.method,
+                    method_args: transport.method_args,
+                })
+            }
+            None => None,
+        };
+        let bridge =
+            if settings.bridge.is_empty() {
+                None
+            } else {
+                Some(settings.bridge.parse().map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid bridge address")
+                })?)
+            };
+        Ok(Self { transport, bridge })
+    }
+}
+
 impl TryFrom<&Udp2TcpObfuscationSettings>
     for mullvad_types::relay_constraints::Udp2TcpObfuscationSettings
 {
@@ -1354,6 +2741,20 @@ impl TryFrom<&Udp2TcpObfuscationSettings>
     }
 }
 
+impl TryFrom<&WssObfuscationSettings> for mullvad_types::relay_constraints::WssObfuscationSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: &WssObfuscationSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            port: if settings.port == 0 {
+                Constraint::Any
+            } else {
+                Constraint::Only(settings.port as u16)
+            },
+        })
+    }
+}
+
 impl TryFrom<BridgeState> for mullvad_types::relay_constraints::BridgeState {
     type Error = FromProtobufTypeError;
 
@@ -1373,6 +2774,276 @@ impl TryFrom<BridgeState> for mullvad_types::relay_constraints::BridgeState {
     }
 }
 
+impl TryFrom<AutoConnectScope> for mullvad_types::settings::AutoConnectScope {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(scope: AutoConnectScope) -> Result<Self, Self::Error> {
+        match auto_connect_scope::Scope::from_i32(scope.scope) {
+            Some(auto_connect_scope::Scope::User) => {
+                Ok(mullvad_types::settings::AutoConnectScope::User)
+            }
+            Some(auto_connect_scope::Scope::Machine) => {
+                Ok(mullvad_types::settings::AutoConnectScope::Machine)
+            }
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid auto-connect scope",
+            )),
+        }
+    }
+}
+
+impl TryFrom<ExpiryNotificationSettings> for mullvad_types::settings::ExpiryNotificationSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: ExpiryNotificationSettings) -> Result<Self, Self::Error> {
+        let thresholds = settings
+            .thresholds
+            .into_iter()
+            .map(|threshold| match std::time::Duration::try_from(threshold) {
+                Ok(threshold) if threshold > std::time::Duration::ZERO => Ok(threshold),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid expiry notification threshold",
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let script = if settings.script.is_empty() {
+            None
+        } else {
+            let script = std::path::PathBuf::from(settings.script);
+            if !script.is_absolute() {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "the notification script path must be absolute",
+                ));
+            }
+            Some(script)
+        };
+        Ok(mullvad_types::settings::ExpiryNotificationSettings { thresholds, script })
+    }
+}
+
+impl TryFrom<TunnelHookSettings> for mullvad_types::settings::TunnelHookSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: TunnelHookSettings) -> Result<Self, Self::Error> {
+        let path = |script: String| {
+            if script.is_empty() {
+                return Ok(None);
+            }
+            let script = std::path::PathBuf::from(script);
+            if !script.is_absolute() {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "hook script paths must be absolute",
+                ));
+            }
+            Ok(Some(script))
+        };
+        Ok(mullvad_types::settings::TunnelHookSettings {
+            connected: path(settings.connected)?,
+            disconnected: path(settings.disconnected)?,
+            error: path(settings.error)?,
+        })
+    }
+}
+
+impl TryFrom<ReconnectDebounce> for talpid_types::net::ReconnectDebounce {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(debounce: ReconnectDebounce) -> Result<Self, Self::Error> {
+        let min_stable_interval = match debounce.min_stable_interval {
+            Some(interval) => std::time::Duration::try_from(interval).map_err(|_| {
+                FromProtobufTypeError::InvalidArgument("invalid minimum stable interval")
+            })?,
+            None => std::time::Duration::ZERO,
+        };
+        let max_reconnects_per_minute = if debounce.max_reconnects_per_minute != 0 {
+            Some(debounce.max_reconnects_per_minute)
+        } else {
+            None
+        };
+        Ok(talpid_types::net::ReconnectDebounce {
+            min_stable_interval,
+            max_reconnects_per_minute,
+        })
+    }
+}
+
+impl TryFrom<OfflineDetection> for talpid_types::net::OfflineDetection {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(detection: OfflineDetection) -> Result<Self, Self::Error> {
+        let grace_period = match detection.grace_period {
+            Some(period) => std::time::Duration::try_from(period)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid grace period"))?,
+            None => std::time::Duration::ZERO,
+        };
+        Ok(talpid_types::net::OfflineDetection {
+            enabled: detection.enabled,
+            grace_period,
+        })
+    }
+}
+
+impl TryFrom<CaptivePortal> for talpid_types::net::CaptivePortal {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(portal: CaptivePortal) -> Result<Self, Self::Error> {
+        if portal.address.is_empty() {
+            return Ok(talpid_types::net::CaptivePortal::AllTraffic);
+        }
+        portal
+            .address
+            .parse()
+            .map(talpid_types::net::CaptivePortal::Address)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid captive portal address"))
+    }
+}
+
+impl TryFrom<LivenessOptions> for wireguard::LivenessOptions {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: LivenessOptions) -> Result<Self, Self::Error> {
+        let duration = |duration: Option<Duration>, error| {
+            duration
+                .and_then(|duration| std::time::Duration::try_from(duration).ok())
+                .filter(|duration| !duration.is_zero())
+                .ok_or(FromProtobufTypeError::InvalidArgument(error))
+        };
+        if options.max_failures == 0 {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "the number of failures must be at least one",
+            ));
+        }
+        Ok(wireguard::LivenessOptions {
+            probe_interval: duration(options.probe_interval, "invalid probe interval")?,
+            probe_timeout: duration(options.probe_timeout, "invalid probe timeout")?,
+            max_failures: options.max_failures,
+            active_probing: options.active_probing,
+        })
+    }
+}
+
+impl TryFrom<RetryPolicy> for talpid_types::net::RetryPolicy {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(policy: RetryPolicy) -> Result<Self, Self::Error> {
+        let initial_delay = match policy.initial_delay {
+            Some(delay) => std::time::Duration::try_from(delay)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid initial delay"))?,
+            None => std::time::Duration::ZERO,
+        };
+        if policy.multiplier == 0 {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "the multiplier must be at least one",
+            ));
+        }
+        let max_delay = policy
+            .max_delay
+            .map(std::time::Duration::try_from)
+            .transpose()
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid maximum delay"))?;
+        let max_attempts = if policy.max_attempts != 0 {
+            Some(policy.max_attempts)
+        } else {
+            None
+        };
+        Ok(talpid_types::net::RetryPolicy {
+            initial_delay,
+            multiplier: policy.multiplier,
+            max_delay,
+            max_attempts,
+        })
+    }
+}
+
+impl TryFrom<ExitRelayChangePolicy> for mullvad_types::settings::ExitRelayChangePolicy {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(policy: ExitRelayChangePolicy) -> Result<Self, Self::Error> {
+        match exit_relay_change_policy::Policy::from_i32(policy.policy) {
+            Some(exit_relay_change_policy::Policy::Notify) => {
+                Ok(mullvad_types::settings::ExitRelayChangePolicy::Notify)
+            }
+            Some(exit_relay_change_policy::Policy::Reconnect) => {
+                Ok(mullvad_types::settings::ExitRelayChangePolicy::Reconnect)
+            }
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid exit relay change policy",
+            )),
+        }
+    }
+}
+
+impl TryFrom<RelaySelectionStrategy> for mullvad_types::relay_constraints::SelectionStrategy {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(strategy: RelaySelectionStrategy) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_constraints::SelectionStrategy;
+        match relay_selection_strategy::Strategy::from_i32(strategy.strategy) {
+            Some(relay_selection_strategy::Strategy::Weighted) => Ok(SelectionStrategy::Weighted),
+            Some(relay_selection_strategy::Strategy::Random) => Ok(SelectionStrategy::Random),
+            Some(relay_selection_strategy::Strategy::LowestLatency) => {
+                Ok(SelectionStrategy::LowestLatency)
+            }
+            Some(relay_selection_strategy::Strategy::RoundRobin) => {
+                Ok(SelectionStrategy::RoundRobin)
+            }
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid relay selection strategy",
+            )),
+        }
+    }
+}
+
+impl TryFrom<FirewallException> for talpid_types::net::FirewallException {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(exception: FirewallException) -> Result<Self, Self::Error> {
+        let direction = match firewall_exception::Direction::from_i32(exception.direction) {
+            Some(firewall_exception::Direction::Inbound) => {
+                talpid_types::net::FirewallExceptionDirection::Inbound
+            }
+            Some(firewall_exception::Direction::Outbound) => {
+                talpid_types::net::FirewallExceptionDirection::Outbound
+            }
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid firewall exception direction",
+                ))
+            }
+        };
+        let port = match u16::try_from(exception.port) {
+            Ok(port) if port != 0 => port,
+            _ => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid firewall exception port",
+                ))
+            }
+        };
+        Ok(talpid_types::net::FirewallException {
+            direction,
+            protocol: try_transport_protocol_from_i32(exception.protocol)?,
+            port,
+        })
+    }
+}
+
+impl TryFrom<InboundTunnelPort> for talpid_types::net::InboundTunnelPort {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(port: InboundTunnelPort) -> Result<Self, Self::Error> {
+        let protocol = try_transport_protocol_from_i32(port.protocol)?;
+        let port = match u16::try_from(port.port) {
+            Ok(port) if port != 0 => port,
+            _ => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid inbound tunnel port",
+                ))
+            }
+        };
+        Ok(talpid_types::net::InboundTunnelPort { protocol, port })
+    }
+}
+
 impl TryFrom<TunnelOptions> for mullvad_types::settings::TunnelOptions {
     type Error = FromProtobufTypeError;
 
@@ -1418,6 +3089,36 @@ impl TryFrom<TunnelOptions> for mullvad_types::settings::TunnelOptions {
                     use_pq_safe_psk: wireguard_options.use_pq_safe_psk,
                     #[cfg(windows)]
                     use_wireguard_nt: wireguard_options.use_wireguard_nt,
+                    liveness: wireguard_options
+                        .liveness
+                        .map(net::wireguard::LivenessOptions::try_from)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    #[cfg(target_os = "linux")]
+                    namespace: if wireguard_options.namespace.is_empty() {
+                        None
+                    } else {
+                        Some(wireguard_options.namespace)
+                    },
+                    persistent_keepalive: u16::try_from(wireguard_options.persistent_keepalive)
+                        .map_err(|_| {
+                            FromProtobufTypeError::InvalidArgument("invalid keepalive interval")
+                        })?,
+                    #[cfg(not(target_os = "android"))]
+                    allowed_ips: if wireguard_options.allowed_ips.is_empty() {
+                        None
+                    } else {
+                        Some(
+                            wireguard_options
+                                .allowed_ips
+                                .iter()
+                                .map(|net| net.parse())
+                                .collect::<Result<_, _>>()
+                                .map_err(|_| {
+                                    FromProtobufTypeError::InvalidArgument("invalid allowed IP")
+                                })?,
+                        )
+                    },
                 },
                 rotation_interval: wireguard_options
                     .rotation_interval
@@ -1436,6 +3137,13 @@ impl TryFrom<TunnelOptions> for mullvad_types::settings::TunnelOptions {
             },
             generic: net::GenericTunnelOptions {
                 enable_ipv6: generic_options.enable_ipv6,
+                retry_policy: generic_options
+                    .retry_policy
+                    .map(net::RetryPolicy::try_from)
+                    .transpose()?
+                    .unwrap_or_default(),
+                #[cfg(target_os = "linux")]
+                rate_limit: Some(generic_options.rate_limit).filter(|rate| *rate > 0),
             },
             #[cfg(not(target_os = "android"))]
             dns_options: mullvad_types::settings::DnsOptions::try_from(dns_options)?,
@@ -1496,6 +3204,68 @@ impl TryFrom<DnsOptions> for mullvad_types::settings::DnsOptions {
                     })
                     .collect::<Result<Vec<_>, _>>()?,
             },
+            split_rules: options
+                .split_rules
+                .into_iter()
+                .map(talpid_types::net::dns::SplitDnsRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            search_domains: options.search_domains,
+            strict_leak_prevention: options.strict_leak_prevention,
+            filter_rules: options
+                .filter_rules
+                .into_iter()
+                .map(talpid_types::net::dns::DnsFilterRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            query_log: options.query_log,
+        })
+    }
+}
+
+impl TryFrom<SplitDnsRule> for talpid_types::net::dns::SplitDnsRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: SplitDnsRule) -> Result<Self, Self::Error> {
+        Ok(talpid_types::net::dns::SplitDnsRule {
+            domain: rule.domain,
+            servers: rule
+                .servers
+                .into_iter()
+                .map(|addr| {
+                    addr.parse()
+                        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP address"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<DnsFilterRule> for talpid_types::net::dns::DnsFilterRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: DnsFilterRule) -> Result<Self, Self::Error> {
+        use talpid_types::net::dns::DnsFilterAction;
+
+        let action = match dns_filter_rule::Action::from_i32(rule.action) {
+            Some(dns_filter_rule::Action::Block) => DnsFilterAction::Block,
+            Some(dns_filter_rule::Action::Rewrite) => DnsFilterAction::Rewrite(
+                rule.addresses
+                    .into_iter()
+                    .map(|addr| {
+                        addr.parse().map_err(|_| {
+                            FromProtobufTypeError::InvalidArgument("invalid IP address")
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid DNS filter action",
+                ))
+            }
+        };
+        Ok(talpid_types::net::dns::DnsFilterRule {
+            domain: rule.domain,
+            action,
         })
     }
 }
@@ -1570,6 +3340,25 @@ fn convert_providers_constraint(
     }
 }
 
+pub fn datacenter_constraint_from_proto(
+    datacenter: String,
+) -> Constraint<mullvad_types::relay_constraints::Datacenter> {
+    if datacenter.is_empty() {
+        Constraint::Any
+    } else {
+        Constraint::Only(mullvad_types::relay_constraints::Datacenter(datacenter))
+    }
+}
+
+fn convert_datacenter_constraint(
+    datacenter: &Constraint<mullvad_types::relay_constraints::Datacenter>,
+) -> String {
+    match datacenter.as_ref() {
+        Constraint::Any => String::new(),
+        Constraint::Only(datacenter) => datacenter.0.clone(),
+    }
+}
+
 fn convert_ownership_constraint(
     ownership: &Constraint<mullvad_types::relay_constraints::Ownership>,
 ) -> Ownership {
@@ -15,6 +15,7 @@ use std::{
 use talpid_types::ErrorExt;
 
 pub mod metadata;
+pub mod summary;
 
 /// Maximum number of bytes to read from each log file
 const LOG_MAX_READ_BYTES: usize = 128 * 1024;
@@ -285,6 +286,17 @@ pub fn send_problem_report(
     ))
 }
 
+/// Reads a previously collected report and summarizes its contents.
+pub fn view_report(report_path: &Path) -> Result<summary::ReportSummary, Error> {
+    let report_content = read_file_lossy(report_path, REPORT_MAX_SIZE).map_err(|source| {
+        Error::ReadProblemReportError {
+            path: report_path.display().to_string(),
+            source,
+        }
+    })?;
+    Ok(summary::ReportSummary::parse(&report_content))
+}
+
 async fn send_problem_report_inner(
     user_email: &str,
     user_message: &str,
@@ -345,6 +357,14 @@ fn write_problem_report(path: &Path, problem_report: &ProblemReport) -> io::Resu
     Ok(())
 }
 
+/// Removes account numbers, the home directory, IP and MAC addresses, and GUIDs from `input`.
+pub fn redact(input: &str) -> String {
+    let out1 = ProblemReport::redact_account_number(input);
+    let out2 = ProblemReport::redact_home_dir(&out1);
+    let out3 = ProblemReport::redact_network_info(&out2);
+    ProblemReport::redact_guids(&out3).to_string()
+}
+
 #[derive(Debug)]
 struct ProblemReport {
     metadata: BTreeMap<String, String>,
@@ -405,11 +425,7 @@ impl ProblemReport {
     }
 
     fn redact(&self, input: &str) -> String {
-        let out1 = Self::redact_account_number(input);
-        let out2 = Self::redact_home_dir(&out1);
-        let out3 = Self::redact_network_info(&out2);
-        let out4 = Self::redact_guids(&out3);
-        self.redact_custom_strings(&out4).to_string()
+        self.redact_custom_strings(&redact(input)).to_string()
     }
 
     fn redact_account_number(input: &str) -> Cow<'_, str> {
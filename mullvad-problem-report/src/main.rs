@@ -1,7 +1,7 @@
 #![deny(rust_2018_idioms)]
 
 use clap::{crate_authors, crate_name};
-use mullvad_problem_report::{collect_report, metadata, Error};
+use mullvad_problem_report::{collect_report, metadata, view_report, Error};
 use std::{env, path::Path, process};
 use talpid_types::ErrorExt;
 
@@ -85,6 +85,17 @@ fn run() -> Result<(), Error> {
                         .takes_value(true)
                         .required(false),
                 ),
+        )
+        .subcommand(
+            clap::App::new("view")
+                .about("Summarize a collected problem report, to review it before sending it")
+                .arg(
+                    clap::Arg::new("report")
+                        .help("The path to previously collected report file.")
+                        .allow_invalid_utf8(true)
+                        .takes_value(true)
+                        .required(true),
+                ),
         );
 
     let matches = app.get_matches();
@@ -116,6 +127,10 @@ fn run() -> Result<(), Error> {
         let user_email = send_matches.value_of("email").unwrap_or("");
         let user_message = send_matches.value_of("message").unwrap_or("");
         send_problem_report(user_email, user_message, report_path)
+    } else if let Some(view_matches) = matches.subcommand_matches("view") {
+        let report_path = Path::new(view_matches.value_of_os("report").unwrap());
+        print!("{}", view_report(report_path)?);
+        Ok(())
     } else {
         unreachable!("No sub command given");
     }
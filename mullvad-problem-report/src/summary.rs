@@ -0,0 +1,232 @@
+//! Extracts the facts that are most useful when triaging a collected problem report, so that it
+//! can be reviewed without reading through every log.
+
+use super::{ProblemReport, LOG_DELIMITER};
+use std::{collections::BTreeMap, fmt};
+
+/// Number of tunnel state changes to include in a summary.
+const MAX_TUNNEL_STATES: usize = 20;
+
+/// Number of errors to include in a summary.
+const MAX_ERRORS: usize = 10;
+
+const TUNNEL_STATE_PREFIX: &str = "New tunnel state: ";
+const FIREWALL_POLICY_PREFIX: &str = "Applying firewall policy: ";
+const FIREWALL_RESET_MESSAGE: &str = "Resetting firewall policy";
+
+/// A log line in the format written by the daemon and the other components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Label of the log that the line was found in.
+    pub log: String,
+    pub timestamp: String,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.timestamp, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReportSummary {
+    pub metadata: BTreeMap<String, String>,
+    /// Label and number of lines of each included log.
+    pub logs: Vec<(String, usize)>,
+    /// The most recent tunnel state changes, oldest first.
+    pub tunnel_states: Vec<LogEntry>,
+    /// The firewall policy that was applied last, if any.
+    pub firewall_policy: Option<LogEntry>,
+    /// The most recent errors, oldest first.
+    pub errors: Vec<LogEntry>,
+}
+
+impl ReportSummary {
+    /// Summarizes the contents of a report written by `collect_report`.
+    pub fn parse(report: &str) -> Self {
+        let mut summary = ReportSummary {
+            metadata: ProblemReport::parse_metadata(report).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let mut lines = report.lines().peekable();
+        let mut current_log = None;
+        while let Some(line) = lines.next() {
+            if line == LOG_DELIMITER {
+                if let Some(label) = lines.peek().and_then(|next| next.strip_prefix("Log: ")) {
+                    let label = label.to_owned();
+                    lines.next();
+                    if lines.peek() == Some(&LOG_DELIMITER) {
+                        lines.next();
+                    }
+                    summary.logs.push((label.clone(), 0));
+                    current_log = Some(label);
+                    continue;
+                }
+            }
+
+            let log = match &current_log {
+                Some(log) => log,
+                // Still in the system information section
+                None => continue,
+            };
+            if let Some((_, line_count)) = summary.logs.last_mut() {
+                *line_count += 1;
+            }
+            if let Some((timestamp, level, message)) = parse_log_line(line) {
+                summary.add_entry(log, timestamp, level, message);
+            }
+        }
+
+        summary
+    }
+
+    fn add_entry(&mut self, log: &str, timestamp: &str, level: &str, message: &str) {
+        let entry = |message: &str| LogEntry {
+            log: log.to_owned(),
+            timestamp: timestamp.to_owned(),
+            message: message.to_owned(),
+        };
+
+        if level == "ERROR" {
+            push_bounded(&mut self.errors, entry(message), MAX_ERRORS);
+        }
+        if let Some(state) = message.strip_prefix(TUNNEL_STATE_PREFIX) {
+            let end = state
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(state.len());
+            push_bounded(
+                &mut self.tunnel_states,
+                entry(&state[..end]),
+                MAX_TUNNEL_STATES,
+            );
+        }
+        if let Some(policy) = message.strip_prefix(FIREWALL_POLICY_PREFIX) {
+            self.firewall_policy = Some(entry(policy));
+        } else if message == FIREWALL_RESET_MESSAGE {
+            self.firewall_policy = Some(entry("None"));
+        }
+    }
+}
+
+impl fmt::Display for ReportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "System information:")?;
+        for (key, value) in &self.metadata {
+            writeln!(f, "  {}: {}", key, value)?;
+        }
+
+        writeln!(f, "\nLogs:")?;
+        for (label, line_count) in &self.logs {
+            writeln!(f, "  {} ({} lines)", label, line_count)?;
+        }
+
+        writeln!(f, "\nRecent tunnel states:")?;
+        if self.tunnel_states.is_empty() {
+            writeln!(f, "  None found")?;
+        }
+        for state in &self.tunnel_states {
+            writeln!(f, "  {}", state)?;
+        }
+
+        writeln!(f, "\nLast applied firewall policy:")?;
+        match &self.firewall_policy {
+            Some(policy) => writeln!(f, "  {}", policy)?,
+            None => writeln!(f, "  None found")?,
+        }
+
+        writeln!(f, "\nRecent errors:")?;
+        if self.errors.is_empty() {
+            writeln!(f, "  None found")?;
+        }
+        for error in &self.errors {
+            writeln!(f, "  {} (in {})", error, error.log)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line in the format `[timestamp][target][level] message` into the timestamp, level
+/// and message.
+fn parse_log_line(line: &str) -> Option<(&str, &str, &str)> {
+    let (timestamp, rest) = line.strip_prefix('[')?.split_once(']')?;
+    let (_target, rest) = rest.strip_prefix('[')?.split_once(']')?;
+    let (level, message) = rest.strip_prefix('[')?.split_once(']')?;
+    Some((timestamp, level, message.trim_start()))
+}
+
+fn push_bounded<T>(entries: &mut Vec<T>, entry: T, max_entries: usize) {
+    if entries.len() == max_entries {
+        entries.remove(0);
+    }
+    entries.push(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "System information:
+id: 1234
+mullvad-product-version: 2022.3
+
+====================
+Log: /var/log/mullvad-vpn/daemon.log
+====================
+[2022-05-10 10:00:00.000][mullvad_daemon][DEBUG] New tunnel state: Connecting { endpoint: .. }
+[2022-05-10 10:00:01.000][talpid_core::firewall][INFO] Applying firewall policy: Connected to ..
+[2022-05-10 10:00:01.500][mullvad_daemon][DEBUG] New tunnel state: Connected { endpoint: .. }
+[2022-05-10 10:05:00.000][mullvad_daemon::api][ERROR] Error: Failed to fetch relays
+[2022-05-10 10:06:00.000][mullvad_daemon][DEBUG] New tunnel state: Disconnected
+[2022-05-10 10:06:00.100][talpid_core::firewall][INFO] Resetting firewall policy
+
+====================
+Log: /var/log/mullvad-vpn/frontend.log
+====================
+not a log line
+";
+
+    #[test]
+    fn test_parse_summary() {
+        let summary = ReportSummary::parse(REPORT);
+
+        assert_eq!(
+            summary
+                .metadata
+                .get("mullvad-product-version")
+                .map(String::as_str),
+            Some("2022.3")
+        );
+        assert_eq!(
+            summary.logs,
+            vec![
+                ("/var/log/mullvad-vpn/daemon.log".to_owned(), 7),
+                ("/var/log/mullvad-vpn/frontend.log".to_owned(), 1),
+            ]
+        );
+
+        let states: Vec<_> = summary
+            .tunnel_states
+            .iter()
+            .map(|state| state.message.as_str())
+            .collect();
+        assert_eq!(states, ["Connecting", "Connected", "Disconnected"]);
+
+        assert_eq!(summary.firewall_policy.unwrap().message, "None");
+
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].message, "Error: Failed to fetch relays");
+        assert_eq!(summary.errors[0].log, "/var/log/mullvad-vpn/daemon.log");
+    }
+
+    #[test]
+    fn test_keeps_most_recent_errors() {
+        let mut summary = ReportSummary::default();
+        for i in 0..MAX_ERRORS + 5 {
+            summary.add_entry("log", "ts", "ERROR", &i.to_string());
+        }
+        assert_eq!(summary.errors.len(), MAX_ERRORS);
+        assert_eq!(summary.errors[0].message, "5");
+    }
+}
@@ -3,27 +3,28 @@
 
 use chrono::{DateTime, Local};
 use ipnetwork::IpNetwork;
-use matcher::AnyTunnelMatcher;
 use mullvad_types::{
+    custom_list::CustomListsSettings,
     endpoint::{MullvadEndpoint, MullvadWireguardEndpoint},
     location::{Coordinates, Location},
     relay_constraints::{
-        BridgeSettings, BridgeState, Constraint, InternalBridgeConstraints, LocationConstraint,
-        Match, ObfuscationSettings, OpenVpnConstraints, Ownership, Providers, RelayConstraints,
-        RelaySettings, SelectedObfuscation, Set, TransportPort, Udp2TcpObfuscationSettings,
-        WireguardConstraints,
+        BridgeConstraints, BridgeSettings, BridgeState, Constraint, ConstraintSet, Datacenter,
+        InternalBridgeConstraints, LocationConstraint, Match, ObfuscationSettings,
+        OpenVpnConstraints, RelayConstraints, RelaySettings, SelectedObfuscation,
+        SelectionStrategy, Set, TransportPort, Udp2TcpObfuscationSettings, WssObfuscationSettings,
     },
-    relay_list::{Relay, RelayList, Udp2TcpEndpointData},
+    relay_list::{Relay, RelayList, RelayListInfo, RelayOverride, Udp2TcpEndpointData},
     CustomTunnelEndpoint,
 };
 use parking_lot::{Mutex, MutexGuard};
 use rand::{self, seq::SliceRandom, Rng};
 use std::{
+    collections::{HashMap, HashSet},
     io,
     net::{IpAddr, SocketAddr},
     path::Path,
     sync::Arc,
-    time::{self, SystemTime},
+    time::{self, Duration, Instant, SystemTime},
 };
 use talpid_types::{
     net::{
@@ -33,13 +34,13 @@ use talpid_types::{
     ErrorExt,
 };
 
-use self::matcher::{RelayMatcher, TunnelMatcher, WireguardMatcher};
+use self::matcher::{RelayMatcher, ResolvedLocationConstraint, TunnelMatcher, WireguardMatcher};
 
 mod matcher;
 pub mod updater;
 
 const DATE_TIME_FORMAT_STR: &str = "%Y-%m-%d %H:%M:%S%.3f";
-const RELAYS_FILENAME: &str = "relays.json";
+pub const RELAYS_FILENAME: &str = "relays.json";
 
 const DEFAULT_WIREGUARD_PORT: u16 = 51820;
 const WIREGUARD_EXIT_CONSTRAINTS: WireguardMatcher = WireguardMatcher {
@@ -78,6 +79,9 @@ pub enum Error {
 
     #[error(display = "Downloader already shut down")]
     DownloaderShutDown,
+
+    #[error(display = "Failed to download the relay list: {}", _0)]
+    DownloadRelayList(String),
 }
 
 struct ParsedRelays {
@@ -95,7 +99,57 @@ impl ParsedRelays {
         }
     }
 
-    pub fn from_relay_list(relay_list: RelayList, last_updated: SystemTime) -> Self {
+    pub fn from_relay_list(mut relay_list: RelayList, last_updated: SystemTime) -> Self {
+        for country in &mut relay_list.countries {
+            for city in &mut country.cities {
+                for relay in &mut city.relays {
+                    // The cleaned up relays are also kept in `locations`, so that frontends are
+                    // shown the same endpoints as the ones that are connected to
+                    Self::filter_invalid_relays(relay);
+
+                    // TODO: The WireGuard data is incorrectly modelled.
+                    // Using a vector here suggests that a relay may use multiple key pairs at a
+                    // time. This is incorrect and will never be the case.
+                    //
+                    // Currently, the `wireguard` vector will have 0 or 1 entries.
+                    // This should be changed into e.g. using an Option<_> instead.
+                    //
+
+                    if !relay.tunnels.wireguard.is_empty() {
+                        for port in UDP2TCP_PORTS {
+                            relay.obfuscators.udp2tcp.push(Udp2TcpEndpointData { port });
+                        }
+                    }
+                }
+            }
+        }
+
+        ParsedRelays {
+            last_updated,
+            relays: Self::relays_with_location(&relay_list, &[]),
+            locations: relay_list,
+        }
+    }
+
+    /// Replaces the addresses of the relays that are selected from with the ones in `overrides`.
+    /// The relays in `locations` are left as they are.
+    pub fn apply_overrides(&mut self, overrides: &[RelayOverride]) {
+        for relay_override in overrides {
+            if !self
+                .relays
+                .iter()
+                .any(|relay| relay_override.matches(relay))
+            {
+                log::warn!(
+                    "Relay {} is overridden, but is not in the relay list",
+                    relay_override.hostname
+                );
+            }
+        }
+        self.relays = Self::relays_with_location(&self.locations, overrides);
+    }
+
+    fn relays_with_location(relay_list: &RelayList, overrides: &[RelayOverride]) -> Vec<Relay> {
         let mut relays = Vec::new();
         for country in &relay_list.countries {
             let country_name = country.name.clone();
@@ -115,36 +169,16 @@ impl ParsedRelays {
                         latitude,
                         longitude,
                     });
-
-                    Self::filter_invalid_relays(&mut relay_with_location);
-
-                    // TODO: The WireGuard data is incorrectly modelled.
-                    // Using a vector here suggests that a relay may use multiple key pairs at a
-                    // time. This is incorrect and will never be the case.
-                    //
-                    // Currently, the `wireguard` vector will have 0 or 1 entries.
-                    // This should be changed into e.g. using an Option<_> instead.
-                    //
-
-                    if !relay.tunnels.wireguard.is_empty() {
-                        for port in UDP2TCP_PORTS {
-                            relay_with_location
-                                .obfuscators
-                                .udp2tcp
-                                .push(Udp2TcpEndpointData { port });
+                    for relay_override in overrides {
+                        if relay_override.matches(&relay_with_location) {
+                            relay_override.apply_to_relay(&mut relay_with_location);
                         }
                     }
-
                     relays.push(relay_with_location);
                 }
             }
         }
-
-        ParsedRelays {
-            last_updated,
-            locations: relay_list,
-            relays,
-        }
+        relays
     }
 
     fn filter_invalid_relays(relay: &mut Relay) {
@@ -222,6 +256,37 @@ pub struct SelectorConfig {
 pub struct RelaySelector {
     config: Arc<Mutex<SelectorConfig>>,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    custom_lists: Arc<Mutex<CustomListsSettings>>,
+    relay_overrides: Arc<Mutex<Vec<RelayOverride>>>,
+    avoided_relays: Arc<Mutex<HashSet<String>>>,
+    selection_strategy: Arc<Mutex<SelectionStrategy>>,
+    relay_latencies: Arc<Mutex<HashMap<String, Duration>>>,
+    last_selected: Arc<Mutex<HashMap<RelayRole, String>>>,
+    recent_failures: Arc<Mutex<RecentFailures>>,
+    working_openvpn_transport: Arc<Mutex<Option<TransportPort>>>,
+}
+
+/// Relays that recently failed to complete a handshake, and when they did.
+#[derive(Default)]
+struct RecentFailures {
+    cooldown: Duration,
+    failed_at: HashMap<String, Instant>,
+}
+
+impl RecentFailures {
+    fn has_failed(&self, hostname: &str) -> bool {
+        self.failed_at
+            .get(hostname)
+            .map(|failed_at| failed_at.elapsed() < self.cooldown)
+            .unwrap_or(false)
+    }
+}
+
+/// Whether a relay is selected as the entry relay of a multihop tunnel, or as the exit relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RelayRole {
+    Entry,
+    Exit,
 }
 
 impl RelaySelector {
@@ -247,6 +312,14 @@ impl RelaySelector {
         RelaySelector {
             config: Arc::new(Mutex::new(config)),
             parsed_relays: Arc::new(Mutex::new(unsynchronized_parsed_relays)),
+            custom_lists: Arc::new(Mutex::new(CustomListsSettings::default())),
+            relay_overrides: Arc::new(Mutex::new(Vec::new())),
+            avoided_relays: Arc::new(Mutex::new(HashSet::new())),
+            selection_strategy: Arc::new(Mutex::new(SelectionStrategy::default())),
+            relay_latencies: Arc::new(Mutex::new(HashMap::new())),
+            last_selected: Arc::new(Mutex::new(HashMap::new())),
+            recent_failures: Arc::new(Mutex::new(RecentFailures::default())),
+            working_openvpn_transport: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -254,12 +327,110 @@ impl RelaySelector {
         *self.config.lock() = config;
     }
 
+    /// Sets the custom lists that location constraints may refer to.
+    pub fn set_custom_lists(&self, custom_lists: CustomListsSettings) {
+        *self.custom_lists.lock() = custom_lists;
+    }
+
+    /// Sets the addresses to connect to relays on instead of the ones in the relay list. These
+    /// are also applied to relay lists that are downloaded later.
+    pub fn set_relay_overrides(&self, overrides: Vec<RelayOverride>) {
+        let mut parsed_relays = self.parsed_relays.lock();
+        parsed_relays.apply_overrides(&overrides);
+        *self.relay_overrides.lock() = overrides;
+    }
+
+    /// Sets the hostnames of relays that should not be selected, unless no other relay matches
+    /// the constraints.
+    pub fn set_avoided_relays(&self, hostnames: HashSet<String>) {
+        *self.avoided_relays.lock() = hostnames;
+    }
+
+    /// Sets how entry and exit relays are chosen among the ones that match the constraints.
+    pub fn set_selection_strategy(&self, strategy: SelectionStrategy) {
+        *self.selection_strategy.lock() = strategy;
+    }
+
+    /// Sets the time that it usually takes to connect to each relay, which is used by
+    /// [`SelectionStrategy::LowestLatency`].
+    pub fn set_relay_latencies(&self, latencies: HashMap<String, Duration>) {
+        *self.relay_latencies.lock() = latencies;
+    }
+
+    /// Sets for how long a relay that failed to complete a handshake is deprioritized. Zero
+    /// disables this.
+    pub fn set_failure_cooldown(&self, cooldown: Duration) {
+        self.recent_failures.lock().cooldown = cooldown;
+    }
+
+    /// Remembers that connecting to the relay failed, so that other relays are preferred until
+    /// the cooldown has passed.
+    pub fn report_failed_relay(&self, hostname: &str) {
+        let mut recent_failures = self.recent_failures.lock();
+        let cooldown = recent_failures.cooldown;
+        recent_failures
+            .failed_at
+            .retain(|_, failed_at| failed_at.elapsed() < cooldown);
+        if !cooldown.is_zero() {
+            recent_failures
+                .failed_at
+                .insert(hostname.to_owned(), Instant::now());
+        }
+    }
+
+    /// Sets the OpenVPN transport that last resulted in a working tunnel on the current network.
+    /// It is tried first, instead of UDP, unless the constraints specify the transport. `None`
+    /// should be set when the network changes.
+    pub fn set_working_openvpn_transport(&self, transport: Option<TransportPort>) {
+        *self.working_openvpn_transport.lock() = transport;
+    }
+
+    /// Removes the avoided relays, and then the relays that recently failed, from `relays`. Each
+    /// step is skipped if it would leave no relays.
+    fn without_deprioritized_relays(&self, relays: Vec<Relay>) -> Vec<Relay> {
+        let avoided_relays = self.avoided_relays.lock();
+        let relays =
+            Self::retain_unless_empty(relays, |relay| !avoided_relays.contains(&relay.hostname));
+        let recent_failures = self.recent_failures.lock();
+        Self::retain_unless_empty(relays, |relay| !recent_failures.has_failed(&relay.hostname))
+    }
+
+    fn retain_unless_empty(relays: Vec<Relay>, keep: impl Fn(&Relay) -> bool) -> Vec<Relay> {
+        if !relays.iter().any(&keep) {
+            return relays;
+        }
+        relays.into_iter().filter(|relay| keep(relay)).collect()
+    }
+
+    fn resolve_location(
+        &self,
+        location: &Constraint<LocationConstraint>,
+    ) -> Constraint<ResolvedLocationConstraint> {
+        ResolvedLocationConstraint::from_constraint(location.clone(), &self.custom_lists.lock())
+    }
+
+    fn resolve_location_set(
+        &self,
+        location_set: &ConstraintSet<LocationConstraint>,
+    ) -> ConstraintSet<ResolvedLocationConstraint> {
+        ResolvedLocationConstraint::from_set(location_set.clone(), &self.custom_lists.lock())
+    }
+
     /// Returns all countries and cities. The cities in the object returned does not have any
     /// relays in them.
     pub fn get_locations(&mut self) -> RelayList {
         self.parsed_relays.lock().locations().clone()
     }
 
+    /// Returns when the relay list was last updated, along with its version.
+    pub fn get_relay_list_info(&self) -> RelayListInfo {
+        let parsed_relays = self.parsed_relays.lock();
+        RelayListInfo {
+            last_updated: parsed_relays.last_updated(),
+            etag: parsed_relays.tag().map(str::to_owned),
+        }
+    }
+
     /// Returns a random relay and relay endpoint matching the current constraints.
     pub fn get_relay(
         &self,
@@ -275,7 +446,24 @@ impl RelaySelector {
         let config = self.config.lock();
         match &config.relay_settings {
             RelaySettings::CustomTunnelEndpoint(custom_relay) => {
-                Ok((SelectedRelay::Custom(custom_relay.clone()), None, None))
+                let entry_location = match &custom_relay.entry_location {
+                    Some(entry_location) => entry_location,
+                    None => return Ok((SelectedRelay::Custom(custom_relay.clone()), None, None)),
+                };
+                let (entry_relay, entry_endpoint) =
+                    self.get_custom_exit_entry_endpoint(entry_location, retry_attempt)?;
+                let obfuscator = self.get_obfuscator_inner(
+                    &config,
+                    &entry_relay,
+                    &entry_endpoint,
+                    retry_attempt,
+                )?;
+                let relay = CustomExitSelectedRelay {
+                    entry_relay,
+                    entry_endpoint,
+                    exit: custom_relay.clone(),
+                };
+                Ok((SelectedRelay::CustomExit(relay), None, obfuscator))
             }
             RelaySettings::Normal(constraints) => {
                 let relay =
@@ -284,12 +472,7 @@ impl RelaySelector {
                     MullvadEndpoint::OpenVpn(endpoint)
                         if endpoint.protocol == TransportProtocol::Tcp =>
                     {
-                        let location = relay
-                            .exit_relay
-                            .location
-                            .as_ref()
-                            .expect("Relay has no location set");
-                        self.get_bridge_for(&config, location, retry_attempt)?
+                        self.get_bridge_for(&config, &relay.exit_relay, retry_attempt)?
                     }
                     _ => None,
                 };
@@ -320,22 +503,13 @@ impl RelaySelector {
         retry_attempt: u32,
     ) -> Result<NormalSelectedRelay, Error> {
         match relay_constraints.tunnel_protocol {
-            Constraint::Only(TunnelType::OpenVpn) => self.get_openvpn_endpoint(
-                &relay_constraints.location,
-                &relay_constraints.providers,
-                &relay_constraints.ownership,
-                relay_constraints.openvpn_constraints,
-                bridge_state,
-                retry_attempt,
-            ),
+            Constraint::Only(TunnelType::OpenVpn) => {
+                self.get_openvpn_endpoint(relay_constraints, bridge_state, retry_attempt)
+            }
 
-            Constraint::Only(TunnelType::Wireguard) => self.get_wireguard_endpoint(
-                &relay_constraints.location,
-                &relay_constraints.providers,
-                &relay_constraints.ownership,
-                &relay_constraints.wireguard_constraints,
-                retry_attempt,
-            ),
+            Constraint::Only(TunnelType::Wireguard) => {
+                self.get_wireguard_endpoint(relay_constraints, retry_attempt)
+            }
             Constraint::Any => {
                 self.get_any_tunnel_endpoint(relay_constraints, bridge_state, retry_attempt)
             }
@@ -345,11 +519,11 @@ impl RelaySelector {
     /// Returns the average location of relays that match the given constraints.
     /// This returns none if the location is `any` or if no relays match the constraints.
     pub fn get_relay_midpoint(&self, relay_constraints: &RelayConstraints) -> Option<Coordinates> {
-        if relay_constraints.location.is_any() {
+        if relay_constraints.location.is_any() && relay_constraints.location_set.is_any() {
             return None;
         }
 
-        let matcher = RelayMatcher::from(relay_constraints.clone());
+        let matcher = RelayMatcher::new(relay_constraints.clone(), &self.custom_lists.lock());
         let mut matching_locations: Vec<Location> = self
             .parsed_relays
             .lock()
@@ -370,22 +544,48 @@ impl RelaySelector {
         Some(Coordinates::midpoint(&matching_locations))
     }
 
+    /// Returns the active relays that `filter` accepts, nearest to `location` first. The relays
+    /// are returned in the order of the relay list if no location is given.
+    pub fn get_nearest_relays(
+        &self,
+        location: Option<&Coordinates>,
+        filter: impl Fn(&Relay) -> bool,
+    ) -> Vec<Relay> {
+        let mut relays: Vec<Relay> = self
+            .parsed_relays
+            .lock()
+            .relays()
+            .iter()
+            .filter(|relay| relay.active && filter(relay))
+            .cloned()
+            .collect();
+        if let Some(location) = location {
+            relays.sort_by_cached_key(|relay| {
+                relay
+                    .location
+                    .as_ref()
+                    .map(|relay_location| relay_location.distance_from(location) as usize)
+                    .unwrap_or(usize::MAX)
+            });
+        }
+        relays
+    }
+
     /// Returns an OpenVpn endpoint, should only ever be used when the user has specified the tunnel
     /// protocol as only OpenVPN.
     fn get_openvpn_endpoint(
         &self,
-        location: &Constraint<LocationConstraint>,
-        providers: &Constraint<Providers>,
-        ownership: &Constraint<Ownership>,
-        openvpn_constraints: OpenVpnConstraints,
+        relay_constraints: &RelayConstraints,
         bridge_state: BridgeState,
         retry_attempt: u32,
     ) -> Result<NormalSelectedRelay, Error> {
         let mut relay_matcher = RelayMatcher {
-            location: location.clone(),
-            providers: providers.clone(),
-            ownership: *ownership,
-            tunnel: openvpn_constraints,
+            location: self.resolve_location(&relay_constraints.location),
+            location_set: self.resolve_location_set(&relay_constraints.location_set),
+            providers: relay_constraints.providers.clone(),
+            ownership: relay_constraints.ownership,
+            datacenter: relay_constraints.datacenter.clone(),
+            tunnel: relay_constraints.openvpn_constraints,
         };
 
         if relay_matcher.tunnel.port.is_any() && bridge_state == BridgeState::On {
@@ -400,7 +600,7 @@ impl RelaySelector {
         let mut preferred_relay_matcher = relay_matcher.clone();
 
         let (preferred_port, preferred_protocol) =
-            Self::preferred_openvpn_constraints(retry_attempt);
+            self.preferred_openvpn_constraints(retry_attempt);
         let should_try_preferred = match &mut preferred_relay_matcher.tunnel.port {
             any @ Constraint::Any => {
                 *any = Constraint::Only(TransportPort {
@@ -430,16 +630,25 @@ impl RelaySelector {
     fn get_wireguard_multi_hop_endpoint(
         &self,
         mut entry_matcher: RelayMatcher<WireguardMatcher>,
-        exit_location: Constraint<LocationConstraint>,
+        exit_location: Constraint<ResolvedLocationConstraint>,
+        exit_location_set: ConstraintSet<ResolvedLocationConstraint>,
+        exit_datacenter: Constraint<Datacenter>,
     ) -> Result<NormalSelectedRelay, Error> {
         let mut exit_matcher = RelayMatcher {
             location: exit_location,
+            location_set: exit_location_set,
+            datacenter: exit_datacenter,
             tunnel: WIREGUARD_EXIT_CONSTRAINTS.clone(),
             ..entry_matcher.clone()
         };
 
+        // Pick the exit relay first if it is limited to a datacenter or a set of locations, since
+        // the entry relay could otherwise take the only relay that is left for it.
         let (exit_relay, entry_relay, exit_endpoint, mut entry_endpoint) =
-            if entry_matcher.location.is_subset(&exit_matcher.location) {
+            if entry_matcher.location.is_subset(&exit_matcher.location)
+                && exit_matcher.location_set.is_any()
+                && exit_matcher.datacenter.is_any()
+            {
                 let (entry_relay, entry_endpoint) = self.get_entry_endpoint(&entry_matcher)?;
                 exit_matcher.set_peer(entry_relay.clone());
                 let exit_result = self.get_tunnel_endpoint_internal(&exit_matcher)?;
@@ -483,16 +692,16 @@ impl RelaySelector {
     /// tunnel protocol as only WireGuard.
     fn get_wireguard_endpoint(
         &self,
-        location: &Constraint<LocationConstraint>,
-        providers: &Constraint<Providers>,
-        ownership: &Constraint<Ownership>,
-        wireguard_constraints: &WireguardConstraints,
+        relay_constraints: &RelayConstraints,
         retry_attempt: u32,
     ) -> Result<NormalSelectedRelay, Error> {
+        let wireguard_constraints = &relay_constraints.wireguard_constraints;
         let mut entry_relay_matcher = RelayMatcher {
-            location: location.clone(),
-            providers: providers.clone(),
-            ownership: *ownership,
+            location: self.resolve_location(&relay_constraints.location),
+            location_set: self.resolve_location_set(&relay_constraints.location_set),
+            providers: relay_constraints.providers.clone(),
+            ownership: relay_constraints.ownership,
+            datacenter: relay_constraints.datacenter.clone(),
             tunnel: wireguard_constraints.clone().into(),
         };
 
@@ -508,12 +717,22 @@ impl RelaySelector {
                 .or_else(|_| self.get_tunnel_endpoint_internal(&entry_relay_matcher));
         }
 
-        entry_relay_matcher.location = wireguard_constraints.entry_location.clone();
+        // The location set and datacenter constraints only apply to the exit relay.
+        let exit_location = entry_relay_matcher.location.clone();
+        let exit_location_set = entry_relay_matcher.location_set.clone();
+        entry_relay_matcher.location = self.resolve_location(&wireguard_constraints.entry_location);
+        entry_relay_matcher.location_set = ConstraintSet::any();
+        entry_relay_matcher.datacenter = Constraint::Any;
         entry_relay_matcher.tunnel.port = entry_relay_matcher
             .tunnel
             .port
             .or(Self::preferred_wireguard_port(retry_attempt));
-        self.get_wireguard_multi_hop_endpoint(entry_relay_matcher, location.clone())
+        self.get_wireguard_multi_hop_endpoint(
+            entry_relay_matcher,
+            exit_location,
+            exit_location_set,
+            relay_constraints.datacenter.clone(),
+        )
     }
 
     /// Like [Self::get_tunnel_endpoint_internal] but also selects an entry endpoint if applicable.
@@ -521,27 +740,28 @@ impl RelaySelector {
         &self,
         relay_constraints: &RelayConstraints,
     ) -> Result<NormalSelectedRelay, Error> {
-        let mut matcher: RelayMatcher<AnyTunnelMatcher> = relay_constraints.clone().into();
+        let mut matcher = RelayMatcher::new(relay_constraints.clone(), &self.custom_lists.lock());
+        let entry_location =
+            self.resolve_location(&relay_constraints.wireguard_constraints.entry_location);
 
         let mut selected_entry_relay = None;
         let mut selected_entry_endpoint = None;
+        let pick_entry_first = entry_location.is_subset(&matcher.location)
+            && matcher.location_set.is_any()
+            && matcher.datacenter.is_any();
         let mut entry_matcher = RelayMatcher {
-            location: relay_constraints
-                .wireguard_constraints
-                .entry_location
-                .clone(),
+            location: entry_location,
+            location_set: ConstraintSet::any(),
+            datacenter: Constraint::Any,
             ..matcher.clone()
         }
         .into_wireguard_matcher();
 
-        // Pick the entry relay first if its location constraint is a subset of the exit location.
+        // Pick the entry relay first if its location constraint is a subset of the exit location,
+        // unless the exit relay is limited to a datacenter or a set of locations.
         if relay_constraints.wireguard_constraints.use_multihop {
             matcher.tunnel.wireguard = WIREGUARD_EXIT_CONSTRAINTS.clone();
-            if relay_constraints
-                .wireguard_constraints
-                .entry_location
-                .is_subset(&matcher.location)
-            {
+            if pick_entry_first {
                 if let Ok((entry_relay, entry_endpoint)) = self.get_entry_endpoint(&entry_matcher) {
                     matcher.tunnel.wireguard.peer = Some(entry_relay.clone());
                     selected_entry_relay = Some(entry_relay);
@@ -552,16 +772,11 @@ impl RelaySelector {
 
         let mut selected_relay = self.get_tunnel_endpoint_internal(&matcher)?;
 
-        // Pick the entry relay last if its location constraint is NOT a subset of the exit
-        // location.
+        // Otherwise, pick the entry relay last.
         if matches!(selected_relay.endpoint, MullvadEndpoint::Wireguard(..))
             && relay_constraints.wireguard_constraints.use_multihop
         {
-            if !relay_constraints
-                .wireguard_constraints
-                .entry_location
-                .is_subset(&matcher.location)
-            {
+            if !pick_entry_first {
                 entry_matcher.tunnel.peer = Some(selected_relay.exit_relay.clone());
                 if let Ok((entry_relay, entry_endpoint)) = self.get_entry_endpoint(&entry_matcher) {
                     selected_entry_relay = Some(entry_relay);
@@ -630,13 +845,8 @@ impl RelaySelector {
         bridge_state: BridgeState,
         retry_attempt: u32,
     ) -> RelayConstraints {
-        let (preferred_port, preferred_protocol, preferred_tunnel) = self
-            .preferred_tunnel_constraints(
-                retry_attempt,
-                &original_constraints.location,
-                &original_constraints.providers,
-                &original_constraints.ownership,
-            );
+        let (preferred_port, preferred_protocol, preferred_tunnel) =
+            self.preferred_tunnel_constraints(retry_attempt, original_constraints);
 
         let mut relay_constraints = original_constraints.clone();
         relay_constraints.openvpn_constraints = Default::default();
@@ -681,7 +891,7 @@ impl RelaySelector {
                     });
                 } else if openvpn_constraints.port.is_any() {
                     let (preferred_port, preferred_protocol) =
-                        Self::preferred_openvpn_constraints(retry_attempt);
+                        self.preferred_openvpn_constraints(retry_attempt);
                     openvpn_constraints.port = Constraint::Only(TransportPort {
                         protocol: preferred_protocol,
                         port: preferred_port,
@@ -713,9 +923,10 @@ impl RelaySelector {
             .filter(|relay| relay.active)
             .filter_map(|relay| matcher.filter_matching_relay(relay))
             .collect();
+        let matching_relays = self.without_deprioritized_relays(matching_relays);
 
         let relay = self
-            .pick_random_relay(&matching_relays)
+            .pick_relay(&matching_relays, RelayRole::Entry)
             .cloned()
             .ok_or(Error::NoRelay)?;
         let endpoint = matcher
@@ -727,6 +938,39 @@ impl RelaySelector {
         Ok((relay, endpoint))
     }
 
+    /// Returns a relay in `entry_location` to reach a custom WireGuard server through. The
+    /// allowed IPs of the peer are set once the host of the server has been resolved.
+    fn get_custom_exit_entry_endpoint(
+        &self,
+        entry_location: &Constraint<LocationConstraint>,
+        retry_attempt: u32,
+    ) -> Result<(Relay, MullvadWireguardEndpoint), Error> {
+        let matcher = RelayMatcher {
+            location: self.resolve_location(entry_location),
+            location_set: ConstraintSet::any(),
+            providers: Constraint::Any,
+            ownership: Constraint::Any,
+            datacenter: Constraint::Any,
+            tunnel: WireguardMatcher {
+                peer: None,
+                port: Constraint::Any,
+                ip_version: Constraint::Any,
+            },
+        };
+        let mut preferred_matcher = matcher.clone();
+        preferred_matcher.tunnel.port = Self::preferred_wireguard_port(retry_attempt);
+
+        let (relay, endpoint) = self
+            .get_entry_endpoint(&preferred_matcher)
+            .or_else(|_| self.get_entry_endpoint(&matcher))?;
+        log::info!(
+            "Selected entry relay {} at {} for the custom relay",
+            relay.hostname,
+            endpoint.peer.endpoint.ip(),
+        );
+        Ok((relay, endpoint))
+    }
+
     fn set_entry_peers(
         exit_peer: &wireguard::PeerConfig,
         entry_endpoint: &mut MullvadWireguardEndpoint,
@@ -735,25 +979,29 @@ impl RelaySelector {
         entry_endpoint.exit_peer = Some(exit_peer.clone());
     }
 
+    /// Returns a bridge for `exit_relay`. Bridges are picked near the exit relay within the
+    /// bridge location, but never on the exit relay itself.
     fn get_bridge_for(
         &self,
         config: &MutexGuard<'_, SelectorConfig>,
-        location: &mullvad_types::location::Location,
+        exit_relay: &Relay,
         retry_attempt: u32,
     ) -> Result<Option<SelectedBridge>, Error> {
+        let location = exit_relay
+            .location
+            .as_ref()
+            .expect("Relay has no location set");
         match &config.bridge_settings {
             BridgeSettings::Normal(settings) => {
-                let bridge_constraints = InternalBridgeConstraints {
-                    location: settings.location.clone(),
-                    providers: settings.providers.clone(),
-                    ownership: settings.ownership,
-                    // FIXME: This is temporary while talpid-core only supports TCP proxies
-                    transport_protocol: Constraint::Only(TransportProtocol::Tcp),
-                };
+                let bridge_constraints = Self::internal_bridge_constraints(settings);
                 match config.bridge_state {
                     BridgeState::On => {
                         let (settings, relay) = self
-                            .get_proxy_settings(&bridge_constraints, Some(location))
+                            .get_proxy_settings(
+                                &bridge_constraints,
+                                Some(location),
+                                Some(exit_relay),
+                            )
                             .ok_or(Error::NoBridge)?;
                         Ok(Some(SelectedBridge::Normal(NormalSelectedBridge {
                             settings,
@@ -761,7 +1009,7 @@ impl RelaySelector {
                         })))
                     }
                     BridgeState::Auto if Self::should_use_bridge(retry_attempt) => Ok(self
-                        .get_proxy_settings(&bridge_constraints, Some(location))
+                        .get_proxy_settings(&bridge_constraints, Some(location), Some(exit_relay))
                         .map(|(settings, relay)| {
                             SelectedBridge::Normal(NormalSelectedBridge { settings, relay })
                         })),
@@ -788,24 +1036,54 @@ impl RelaySelector {
         };
 
         let constraints = match &config.bridge_settings {
-            BridgeSettings::Normal(settings) => InternalBridgeConstraints {
-                location: settings.location.clone(),
-                providers: settings.providers.clone(),
-                ownership: settings.ownership,
-                transport_protocol: Constraint::Only(TransportProtocol::Tcp),
-            },
+            BridgeSettings::Normal(settings) => Self::internal_bridge_constraints(settings),
             BridgeSettings::Custom(_bridge_settings) => InternalBridgeConstraints {
                 location: Constraint::Any,
                 providers: Constraint::Any,
                 ownership: Constraint::Any,
                 transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+                port: Constraint::Any,
+                cipher: Constraint::Any,
             },
         };
 
-        self.get_proxy_settings(&constraints, near_location)
+        self.get_proxy_settings(&constraints, near_location, None)
             .map(|(settings, _relay)| settings)
     }
 
+    /// Returns an error if no bridge in the relay list matches `constraints`. Nothing is checked
+    /// before a relay list has been loaded.
+    pub fn validate_bridge_constraints(
+        &self,
+        constraints: &BridgeConstraints,
+    ) -> Result<(), Error> {
+        let constraints = Self::internal_bridge_constraints(constraints);
+        let parsed_relays = self.parsed_relays.lock();
+        let relays = parsed_relays.relays();
+        if relays.is_empty()
+            || relays
+                .iter()
+                .filter(|relay| relay.active)
+                .any(|relay| Self::matching_bridge_relay(relay, &constraints).is_some())
+        {
+            Ok(())
+        } else {
+            Err(Error::NoBridge)
+        }
+    }
+
+    fn internal_bridge_constraints(settings: &BridgeConstraints) -> InternalBridgeConstraints {
+        InternalBridgeConstraints {
+            location: settings.location.clone(),
+            providers: settings.providers.clone(),
+            ownership: settings.ownership,
+            // FIXME: This is temporary while talpid-core only supports TCP proxies
+            transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+            port: settings.port,
+            cipher: settings.cipher.clone(),
+        }
+    }
+
     fn should_use_bridge(retry_attempt: u32) -> bool {
         // shouldn't use a bridge for the first 3 times
         retry_attempt > 3 &&
@@ -816,10 +1094,13 @@ impl RelaySelector {
             (retry_attempt % 4) < 2
     }
 
+    /// Picks a bridge that matches `constraints`, preferring the ones that are close to
+    /// `location`. The bridge is never `exit_relay`, since it would not add another hop.
     fn get_proxy_settings<T: Into<Coordinates>>(
         &self,
         constraints: &InternalBridgeConstraints,
         location: Option<T>,
+        exit_relay: Option<&Relay>,
     ) -> Option<(ProxySettings, Relay)> {
         let mut matching_relays: Vec<Relay> = self
             .parsed_relays
@@ -827,6 +1108,7 @@ impl RelaySelector {
             .relays()
             .iter()
             .filter(|relay| relay.active)
+            .filter(|relay| exit_relay.map_or(true, |exit| exit.hostname != relay.hostname))
             .filter_map(|relay| Self::matching_bridge_relay(relay, constraints))
             .collect();
 
@@ -887,6 +1169,14 @@ impl RelaySelector {
                 )
                 .ok_or(Error::NoObfuscator)?,
             )),
+            SelectedObfuscation::Wss => Ok(Some(
+                self.get_wss_obfuscator(&config.obfuscation_settings.wss, relay, retry_attempt)
+                    .ok_or(Error::NoObfuscator)?,
+            )),
+            SelectedObfuscation::External => Ok(Some(
+                self.get_external_obfuscator(&config.obfuscation_settings, relay, retry_attempt)
+                    .ok_or(Error::NoObfuscator)?,
+            )),
         }
     }
 
@@ -931,6 +1221,69 @@ impl RelaySelector {
         _endpoint: &MullvadWireguardEndpoint,
         retry_attempt: u32,
     ) -> Option<SelectedObfuscator> {
+        Self::get_udp2tcp_endpoint(obfuscation_settings, relay, retry_attempt)
+            .map(|endpoint| ObfuscatorConfig::Udp2Tcp { endpoint })
+            .map(|config| SelectedObfuscator {
+                config,
+                relay: relay.clone(),
+            })
+    }
+
+    /// Selects one of the WebSocket over TLS endpoints of the relay. Not all relays have them.
+    fn get_wss_obfuscator(
+        &self,
+        obfuscation_settings: &WssObfuscationSettings,
+        relay: &Relay,
+        retry_attempt: u32,
+    ) -> Option<SelectedObfuscator> {
+        let endpoints = &relay.obfuscators.wss;
+        let wss_endpoint = if obfuscation_settings.port.is_only() {
+            endpoints
+                .iter()
+                .find(|&candidate| obfuscation_settings.port.matches_eq(&candidate.port))
+        } else if endpoints.is_empty() {
+            None
+        } else {
+            endpoints.get(retry_attempt as usize % endpoints.len())
+        };
+        wss_endpoint.map(|wss_endpoint| SelectedObfuscator {
+            config: ObfuscatorConfig::Wss {
+                endpoint: SocketAddr::new(relay.ipv4_addr_in.into(), wss_endpoint.port),
+                server_name: wss_endpoint.server_name.clone(),
+            },
+            relay: relay.clone(),
+        })
+    }
+
+    /// Uses the user supplied transport to reach either the configured bridge or the udp2tcp
+    /// endpoint of the relay.
+    fn get_external_obfuscator(
+        &self,
+        obfuscation_settings: &ObfuscationSettings,
+        relay: &Relay,
+        retry_attempt: u32,
+    ) -> Option<SelectedObfuscator> {
+        let transport = obfuscation_settings.external.transport.clone()?;
+        let endpoint = match obfuscation_settings.external.bridge {
+            Some(bridge) => bridge,
+            None => {
+                Self::get_udp2tcp_endpoint(&obfuscation_settings.udp2tcp, relay, retry_attempt)?
+            }
+        };
+        Some(SelectedObfuscator {
+            config: ObfuscatorConfig::External {
+                endpoint,
+                transport,
+            },
+            relay: relay.clone(),
+        })
+    }
+
+    fn get_udp2tcp_endpoint(
+        obfuscation_settings: &Udp2TcpObfuscationSettings,
+        relay: &Relay,
+        retry_attempt: u32,
+    ) -> Option<SocketAddr> {
         let udp2tcp_endpoint = if obfuscation_settings.port.is_only() {
             relay
                 .obfuscators
@@ -943,54 +1296,48 @@ impl RelaySelector {
                 .udp2tcp
                 .get(retry_attempt as usize % relay.obfuscators.udp2tcp.len())
         };
-        udp2tcp_endpoint
-            .map(|udp2tcp_endpoint| ObfuscatorConfig::Udp2Tcp {
-                endpoint: SocketAddr::new(relay.ipv4_addr_in.into(), udp2tcp_endpoint.port),
-            })
-            .map(|config| SelectedObfuscator {
-                config,
-                relay: relay.clone(),
-            })
+        udp2tcp_endpoint.map(|udp2tcp_endpoint| {
+            SocketAddr::new(relay.ipv4_addr_in.into(), udp2tcp_endpoint.port)
+        })
     }
 
     /// Returns preferred constraints
-    #[allow(unused_variables)]
     fn preferred_tunnel_constraints(
         &self,
         retry_attempt: u32,
-        location_constraint: &Constraint<LocationConstraint>,
-        providers_constraint: &Constraint<Providers>,
-        ownership_constraint: &Constraint<Ownership>,
+        constraints: &RelayConstraints,
     ) -> (Constraint<u16>, TransportProtocol, TunnelType) {
+        let location = self.resolve_location(&constraints.location);
+        let location_set = self.resolve_location_set(&constraints.location_set);
+        let matches_constraints = |relay: &Relay| {
+            location.matches(relay)
+                && location_set.matches(relay)
+                && constraints.providers.matches(relay)
+                && constraints.ownership.matches(relay)
+                && constraints.datacenter.matches(relay)
+        };
+
         #[cfg(target_os = "windows")]
         {
             let location_supports_openvpn =
                 self.parsed_relays.lock().relays().iter().any(|relay| {
-                    relay.active
-                        && !relay.tunnels.openvpn.is_empty()
-                        && location_constraint.matches(relay)
-                        && providers_constraint.matches(relay)
-                        && ownership_constraint.matches(relay)
+                    relay.active && !relay.tunnels.openvpn.is_empty() && matches_constraints(relay)
                 });
             if location_supports_openvpn {
                 let (preferred_port, preferred_protocol) =
-                    Self::preferred_openvpn_constraints(retry_attempt);
+                    self.preferred_openvpn_constraints(retry_attempt);
                 return (preferred_port, preferred_protocol, TunnelType::OpenVpn);
             }
         }
 
         let location_supports_wireguard = self.parsed_relays.lock().relays().iter().any(|relay| {
-            relay.active
-                && !relay.tunnels.wireguard.is_empty()
-                && location_constraint.matches(relay)
-                && providers_constraint.matches(relay)
-                && ownership_constraint.matches(relay)
+            relay.active && !relay.tunnels.wireguard.is_empty() && matches_constraints(relay)
         });
         // If location does not support WireGuard, defer to preferred OpenVPN tunnel
         // constraints
         if !location_supports_wireguard {
             let (preferred_port, preferred_protocol) =
-                Self::preferred_openvpn_constraints(retry_attempt);
+                self.preferred_openvpn_constraints(retry_attempt);
             return (preferred_port, preferred_protocol, TunnelType::OpenVpn);
         }
 
@@ -1010,7 +1357,7 @@ impl RelaySelector {
             ),
             _ => {
                 let (preferred_port, preferred_protocol) =
-                    Self::preferred_openvpn_constraints(retry_attempt - 2);
+                    self.preferred_openvpn_constraints(retry_attempt - 2);
                 (preferred_port, preferred_protocol, TunnelType::OpenVpn)
             }
         }
@@ -1026,17 +1373,23 @@ impl RelaySelector {
         }
     }
 
-    fn preferred_openvpn_constraints(retry_attempt: u32) -> (Constraint<u16>, TransportProtocol) {
-        // Prefer UDP by default. But if that has failed a couple of times, then try TCP port
-        // 443, which works for many with UDP problems. After that, just alternate
-        // between protocols.
+    fn preferred_openvpn_constraints(
+        &self,
+        retry_attempt: u32,
+    ) -> (Constraint<u16>, TransportProtocol) {
+        // Prefer UDP by default, or the transport that worked the last time on this network. But
+        // if that has failed a couple of times, then try TCP port 443, which works for many with
+        // UDP problems. After that, just alternate between protocols.
         // If the tunnel type constraint is set OpenVpn, from the 4th attempt onwards, the first
         // two retry attempts OpenVpn constraints should be set to TCP as a bridge will be used,
         // and to UDP or TCP for the next two attempts. If the tunnel type is specified to be _Any_
         // and on not-Windows, the first two tries are used for WireGuard and don't
         // affect counting here.
         match retry_attempt {
-            0 | 1 => (Constraint::Any, TransportProtocol::Udp),
+            0 | 1 => match *self.working_openvpn_transport.lock() {
+                Some(transport) => (transport.port, transport.protocol),
+                None => (Constraint::Any, TransportProtocol::Udp),
+            },
             2 | 3 => (Constraint::Only(443), TransportProtocol::Tcp),
             attempt if attempt % 4 < 2 => (Constraint::Any, TransportProtocol::Tcp),
             attempt if attempt % 4 == 2 => (Constraint::Any, TransportProtocol::Udp),
@@ -1057,8 +1410,9 @@ impl RelaySelector {
             .filter(|relay| relay.active)
             .filter_map(|relay| matcher.filter_matching_relay(relay))
             .collect();
+        let matching_relays = self.without_deprioritized_relays(matching_relays);
 
-        self.pick_random_relay(&matching_relays)
+        self.pick_relay(&matching_relays, RelayRole::Exit)
             .and_then(|selected_relay| {
                 let endpoint = matcher.mullvad_endpoint(selected_relay);
                 let addr_in = endpoint
@@ -1083,10 +1437,11 @@ impl RelaySelector {
         }
 
         let mut filtered_relay = relay.clone();
-        filtered_relay
-            .bridges
-            .shadowsocks
-            .retain(|bridge| constraints.transport_protocol.matches_eq(&bridge.protocol));
+        filtered_relay.bridges.shadowsocks.retain(|bridge| {
+            constraints.transport_protocol.matches_eq(&bridge.protocol)
+                && constraints.port.matches_eq(&bridge.port)
+                && constraints.cipher.matches_eq(&bridge.cipher)
+        });
         if filtered_relay.bridges.shadowsocks.is_empty() {
             return None;
         }
@@ -1094,6 +1449,47 @@ impl RelaySelector {
         Some(filtered_relay)
     }
 
+    /// Picks an entry or exit relay according to the selection strategy.
+    fn pick_relay<'a>(&self, relays: &'a [Relay], role: RelayRole) -> Option<&'a Relay> {
+        let strategy = *self.selection_strategy.lock();
+        let relay = match strategy {
+            SelectionStrategy::Random => relays.choose(&mut rand::thread_rng()),
+            SelectionStrategy::Weighted => self.pick_random_relay(relays),
+            SelectionStrategy::LowestLatency => {
+                let latencies = self.relay_latencies.lock();
+                relays
+                    .iter()
+                    .filter_map(|relay| {
+                        latencies
+                            .get(&relay.hostname)
+                            .map(|latency| (latency, relay))
+                    })
+                    .min_by(|(latency_a, relay_a), (latency_b, relay_b)| {
+                        latency_a
+                            .cmp(latency_b)
+                            .then_with(|| relay_a.hostname.cmp(&relay_b.hostname))
+                    })
+                    .map(|(_, relay)| relay)
+                    .or_else(|| self.pick_random_relay(relays))
+            }
+            SelectionStrategy::RoundRobin => {
+                let last_selected = self.last_selected.lock().get(&role).cloned();
+                let by_hostname = |a: &&Relay, b: &&Relay| a.hostname.cmp(&b.hostname);
+                relays
+                    .iter()
+                    .filter(|relay| Some(&relay.hostname) > last_selected.as_ref())
+                    .min_by(by_hostname)
+                    .or_else(|| relays.iter().min_by(by_hostname))
+            }
+        };
+        if let Some(relay) = relay {
+            self.last_selected
+                .lock()
+                .insert(role, relay.hostname.clone());
+        }
+        relay
+    }
+
     /// Picks a relay using [Self::pick_random_relay_fn], using the `weight` member of each relay
     /// as the weight function.
     fn pick_random_relay<'a>(&self, relays: &'a [Relay]) -> Option<&'a Relay> {
@@ -1199,6 +1595,7 @@ pub struct NormalSelectedBridge {
 pub enum SelectedRelay {
     Normal(NormalSelectedRelay),
     Custom(CustomTunnelEndpoint),
+    CustomExit(CustomExitSelectedRelay),
 }
 
 #[derive(Debug)]
@@ -1208,6 +1605,14 @@ pub struct NormalSelectedRelay {
     pub entry_relay: Option<Relay>,
 }
 
+/// A custom WireGuard server that is reached through a Mullvad relay.
+#[derive(Debug)]
+pub struct CustomExitSelectedRelay {
+    pub entry_relay: Relay,
+    pub entry_endpoint: MullvadWireguardEndpoint,
+    pub exit: CustomTunnelEndpoint,
+}
+
 #[derive(Debug)]
 pub struct SelectedObfuscator {
     pub config: ObfuscatorConfig,
@@ -1240,13 +1645,17 @@ impl NormalSelectedRelay {
 mod test {
     use super::*;
     use mullvad_types::{
-        relay_constraints::{BridgeConstraints, RelayConstraints},
+        relay_constraints::{
+            BridgeConstraints, ExternalObfuscationSettings, Ownership, RelayConstraints,
+            WireguardConstraints,
+        },
         relay_list::{
             OpenVpnEndpointData, Relay, RelayBridges, RelayListCity, RelayListCountry,
-            RelayObfuscators, RelayTunnels, WireguardEndpointData,
+            RelayObfuscators, RelayTunnels, ShadowsocksEndpointData, WireguardEndpointData,
+            WssEndpointData,
         },
     };
-    use talpid_types::net::wireguard::PublicKey;
+    use talpid_types::net::{obfuscation::ExternalTransportConfig, wireguard::PublicKey};
 
     lazy_static::lazy_static! {
         static ref RELAYS: RelayList = RelayList {
@@ -1270,6 +1679,8 @@ mod test {
                                     active: true,
                                     owned: true,
                                     provider: "31173".to_string(),
+                                    provider_name: None,
+                                    datacenter: None,
                                     weight: 1,
                                     tunnels: RelayTunnels {
                                         openvpn: vec![],
@@ -1287,6 +1698,10 @@ mod test {
                                     },
                                     obfuscators: RelayObfuscators {
                                         udp2tcp: vec![],
+                                        wss: vec![WssEndpointData {
+                                            port: 443,
+                                            server_name: "se9-wireguard.relays.mullvad.net".to_string(),
+                                        }],
                                     },
                                     location: None,
                                 },
@@ -1298,6 +1713,8 @@ mod test {
                                     active: true,
                                     owned: false,
                                     provider: "31173".to_string(),
+                                    provider_name: None,
+                                    datacenter: Some("got-dc2".to_string()),
                                     weight: 1,
                                     tunnels: RelayTunnels {
                                         openvpn: vec![],
@@ -1315,6 +1732,7 @@ mod test {
                                     },
                                     obfuscators: RelayObfuscators {
                                         udp2tcp: vec![],
+                                        wss: vec![],
                                     },
                                     location: None,
                                 },
@@ -1326,6 +1744,8 @@ mod test {
                                     active: true,
                                     owned: true,
                                     provider: "31173".to_string(),
+                                    provider_name: None,
+                                    datacenter: None,
                                     weight: 1,
                                     tunnels: RelayTunnels {
                                         openvpn: vec![
@@ -1349,6 +1769,7 @@ mod test {
                                     },
                                     obfuscators: RelayObfuscators {
                                         udp2tcp: vec![],
+                                        wss: vec![],
                                     },
                                     location: None,
                                 },
@@ -1360,6 +1781,8 @@ mod test {
                                     active: true,
                                     owned: true,
                                     provider: "31173".to_string(),
+                                    provider_name: None,
+                                    datacenter: None,
                                     weight: 1,
                                     tunnels: RelayTunnels {
                                         openvpn: vec![],
@@ -1377,6 +1800,7 @@ mod test {
                                     },
                                     obfuscators: RelayObfuscators {
                                         udp2tcp: vec![],
+                                        wss: vec![],
                                     },
                                     location: None,
                                 },
@@ -1388,6 +1812,8 @@ mod test {
                                     active: true,
                                     owned: true,
                                     provider: "31173".to_string(),
+                                    provider_name: None,
+                                    datacenter: None,
                                     weight: 1,
                                     tunnels: RelayTunnels {
                                         openvpn: vec![OpenVpnEndpointData{
@@ -1401,6 +1827,7 @@ mod test {
                                     },
                                     obfuscators: RelayObfuscators {
                                         udp2tcp: vec![],
+                                        wss: vec![],
                                     },
                                     location: None,
                                 }
@@ -1430,6 +1857,14 @@ mod test {
                 },
                 bridge_state: BridgeState::Auto,
             })),
+            custom_lists: Arc::new(Mutex::new(CustomListsSettings::default())),
+            relay_overrides: Arc::new(Mutex::new(Vec::new())),
+            avoided_relays: Arc::new(Mutex::new(HashSet::new())),
+            selection_strategy: Arc::new(Mutex::new(SelectionStrategy::default())),
+            relay_latencies: Arc::new(Mutex::new(HashMap::new())),
+            last_selected: Arc::new(Mutex::new(HashMap::new())),
+            recent_failures: Arc::new(Mutex::new(RecentFailures::default())),
+            working_openvpn_transport: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -1694,44 +2129,170 @@ mod test {
     }
 
     #[test]
-    fn test_selecting_any_relay_will_consider_multihop() {
-        let relay_constraints = RelayConstraints {
-            wireguard_constraints: WireguardConstraints {
-                use_multihop: true,
-                ..WireguardConstraints::default()
-            },
-            // This has to be explicit otherwise Android will chose WireGuard when default
-            // constructing.
-            tunnel_protocol: Constraint::Any,
-            ..RelayConstraints::default()
+    fn test_bridge_port_and_cipher() {
+        let shadowsocks = |port: u16, cipher: &str| ShadowsocksEndpointData {
+            port,
+            cipher: cipher.to_string(),
+            password: "mullvad".to_string(),
+            protocol: TransportProtocol::Tcp,
+        };
+        let mut relay = RELAYS.countries[0].cities[0].relays[2].clone();
+        relay.bridges.shadowsocks = vec![
+            shadowsocks(443, "aes-256-gcm"),
+            shadowsocks(1234, "chacha20"),
+        ];
+        let mut constraints = InternalBridgeConstraints {
+            location: Constraint::Any,
+            providers: Constraint::Any,
+            ownership: Constraint::Any,
+            transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+            port: Constraint::Any,
+            cipher: Constraint::Any,
+        };
+        let bridges = |constraints: &InternalBridgeConstraints| {
+            RelaySelector::matching_bridge_relay(&relay, constraints)
+                .map(|relay| relay.bridges.shadowsocks)
         };
 
-        let relay_selector = new_relay_selector();
+        assert_eq!(bridges(&constraints).unwrap().len(), 2);
 
-        let result = relay_selector.get_tunnel_endpoint(&relay_constraints, BridgeState::Off, 0)
-            .expect("Failed to get relay when tunnel constraints are set to Any and retrying the selection");
-        // Windows will ignore WireGuard until WireGuard is supported well enough
-        // TODO: Remove this caveat once Windows defaults to using WireGuard
-        #[cfg(target_os = "windows")]
-        assert!(
-            matches!(result.endpoint, MullvadEndpoint::OpenVpn(_)) && result.entry_relay.is_none()
+        constraints.port = Constraint::Only(1234);
+        assert_eq!(
+            bridges(&constraints),
+            Some(vec![shadowsocks(1234, "chacha20")])
         );
 
-        #[cfg(not(target_os = "windows"))]
-        assert!(
-            matches!(result.endpoint, MullvadEndpoint::Wireguard(_))
-                && result.entry_relay.is_some()
+        constraints.cipher = Constraint::Only("aes-256-gcm".to_string());
+        assert_eq!(bridges(&constraints), None);
+
+        constraints.port = Constraint::Any;
+        assert_eq!(
+            bridges(&constraints),
+            Some(vec![shadowsocks(443, "aes-256-gcm")])
         );
     }
 
-    const WIREGUARD_MULTIHOP_CONSTRAINTS: RelayConstraints = RelayConstraints {
-        location: Constraint::Any,
-        providers: Constraint::Any,
-        ownership: Constraint::Any,
-        wireguard_constraints: WireguardConstraints {
-            use_multihop: true,
-            port: Constraint::Any,
-            ip_version: Constraint::Any,
+    #[test]
+    fn test_bridge_is_not_exit_relay() {
+        let shadowsocks = ShadowsocksEndpointData {
+            port: 443,
+            cipher: "aes-256-gcm".to_string(),
+            password: "mullvad".to_string(),
+            protocol: TransportProtocol::Tcp,
+        };
+        let mut relays = RELAYS.clone();
+        relays.countries[0].cities[0].relays[2].bridges.shadowsocks = vec![shadowsocks.clone()];
+        let relay_selector = new_relay_selector();
+        *relay_selector.parsed_relays.lock() =
+            ParsedRelays::from_relay_list(relays.clone(), SystemTime::now());
+        {
+            let mut config = relay_selector.config.lock();
+            config.relay_settings = RelaySettings::Normal(RelayConstraints {
+                location: Constraint::Only(LocationConstraint::Hostname(
+                    "se".to_string(),
+                    "got".to_string(),
+                    "se-got-001".to_string(),
+                )),
+                tunnel_protocol: Constraint::Only(TunnelType::OpenVpn),
+                openvpn_constraints: OpenVpnConstraints {
+                    port: Constraint::Only(TransportPort {
+                        protocol: TransportProtocol::Tcp,
+                        port: Constraint::Any,
+                    }),
+                },
+                ..RelayConstraints::default()
+            });
+            config.bridge_state = BridgeState::On;
+        }
+
+        // The only bridge is on the exit relay, so it adds no hop
+        assert!(matches!(relay_selector.get_relay(0), Err(Error::NoBridge)));
+
+        relays.countries[0].cities[0].relays[0].bridges.shadowsocks = vec![shadowsocks];
+        *relay_selector.parsed_relays.lock() =
+            ParsedRelays::from_relay_list(relays, SystemTime::now());
+        match relay_selector.get_relay(0).unwrap() {
+            (SelectedRelay::Normal(relay), Some(SelectedBridge::Normal(bridge)), None) => {
+                assert_eq!(relay.exit_relay.hostname, "se-got-001");
+                assert_eq!(bridge.relay.hostname, "se9-wireguard");
+            }
+            result => panic!("Expected a bridged relay, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_validate_bridge_constraints() {
+        let relay_selector = new_relay_selector();
+        let mut relays = RELAYS.clone();
+        relays.countries[0].cities[0].relays[2].bridges.shadowsocks =
+            vec![ShadowsocksEndpointData {
+                port: 443,
+                cipher: "aes-256-gcm".to_string(),
+                password: "mullvad".to_string(),
+                protocol: TransportProtocol::Tcp,
+            }];
+        *relay_selector.parsed_relays.lock() =
+            ParsedRelays::from_relay_list(relays, SystemTime::now());
+
+        let mut constraints = BridgeConstraints {
+            location: Constraint::Only(LocationConstraint::City(
+                "se".to_string(),
+                "got".to_string(),
+            )),
+            ..BridgeConstraints::default()
+        };
+        assert!(relay_selector
+            .validate_bridge_constraints(&constraints)
+            .is_ok());
+
+        constraints.location = Constraint::Only(LocationConstraint::Country("de".to_string()));
+        assert!(matches!(
+            relay_selector.validate_bridge_constraints(&constraints),
+            Err(Error::NoBridge)
+        ));
+    }
+
+    #[test]
+    fn test_selecting_any_relay_will_consider_multihop() {
+        let relay_constraints = RelayConstraints {
+            wireguard_constraints: WireguardConstraints {
+                use_multihop: true,
+                ..WireguardConstraints::default()
+            },
+            // This has to be explicit otherwise Android will chose WireGuard when default
+            // constructing.
+            tunnel_protocol: Constraint::Any,
+            ..RelayConstraints::default()
+        };
+
+        let relay_selector = new_relay_selector();
+
+        let result = relay_selector.get_tunnel_endpoint(&relay_constraints, BridgeState::Off, 0)
+            .expect("Failed to get relay when tunnel constraints are set to Any and retrying the selection");
+        // Windows will ignore WireGuard until WireGuard is supported well enough
+        // TODO: Remove this caveat once Windows defaults to using WireGuard
+        #[cfg(target_os = "windows")]
+        assert!(
+            matches!(result.endpoint, MullvadEndpoint::OpenVpn(_)) && result.entry_relay.is_none()
+        );
+
+        #[cfg(not(target_os = "windows"))]
+        assert!(
+            matches!(result.endpoint, MullvadEndpoint::Wireguard(_))
+                && result.entry_relay.is_some()
+        );
+    }
+
+    const WIREGUARD_MULTIHOP_CONSTRAINTS: RelayConstraints = RelayConstraints {
+        location: Constraint::Any,
+        location_set: ConstraintSet::any(),
+        providers: Constraint::Any,
+        ownership: Constraint::Any,
+        datacenter: Constraint::Any,
+        wireguard_constraints: WireguardConstraints {
+            use_multihop: true,
+            port: Constraint::Any,
+            ip_version: Constraint::Any,
             entry_location: Constraint::Any,
         },
         tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
@@ -1742,8 +2303,10 @@ mod test {
 
     const WIREGUARD_SINGLEHOP_CONSTRAINTS: RelayConstraints = RelayConstraints {
         location: Constraint::Any,
+        location_set: ConstraintSet::any(),
         providers: Constraint::Any,
         ownership: Constraint::Any,
+        datacenter: Constraint::Any,
         wireguard_constraints: WireguardConstraints {
             use_multihop: false,
             port: Constraint::Any,
@@ -1827,6 +2390,100 @@ mod test {
             .is_some());
     }
 
+    #[test]
+    fn test_selecting_wg_endpoint_with_external_obfuscation() {
+        let relay_selector = new_relay_selector();
+
+        let result = relay_selector
+            .get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, 0)
+            .expect("Failed to select a WireGuard relay");
+
+        relay_selector.config.lock().obfuscation_settings = ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::External,
+            ..ObfuscationSettings::default()
+        };
+
+        // No transport has been configured
+        assert!(matches!(
+            relay_selector.get_obfuscator(
+                &result.exit_relay,
+                result.endpoint.unwrap_wireguard(),
+                0
+            ),
+            Err(Error::NoObfuscator)
+        ));
+
+        let transport = ExternalTransportConfig {
+            path: "/usr/bin/lyrebird".into(),
+            args: vec![],
+            method: "obfs4".to_owned(),
+            method_args: String::new(),
+        };
+        let bridge: SocketAddr = "192.0.2.1:443".parse().unwrap();
+        relay_selector.config.lock().obfuscation_settings.external = ExternalObfuscationSettings {
+            transport: Some(transport.clone()),
+            bridge: Some(bridge),
+        };
+
+        let obfs_config = relay_selector
+            .get_obfuscator(&result.exit_relay, result.endpoint.unwrap_wireguard(), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            obfs_config.config,
+            ObfuscatorConfig::External {
+                endpoint: bridge,
+                transport,
+            }
+        );
+    }
+
+    #[test]
+    fn test_selecting_wg_endpoint_with_wss_obfuscation() {
+        let relay_selector = new_relay_selector();
+        relay_selector.config.lock().obfuscation_settings = ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::Wss,
+            ..ObfuscationSettings::default()
+        };
+
+        let relays = relay_selector.parsed_relays.lock().relays().clone();
+        let with_wss = relays
+            .iter()
+            .find(|relay| relay.hostname == "se9-wireguard")
+            .unwrap();
+        let without_wss = relays
+            .iter()
+            .find(|relay| relay.hostname == "se10-wireguard")
+            .unwrap();
+        let endpoint = relay_selector
+            .get_tunnel_endpoint(&WIREGUARD_SINGLEHOP_CONSTRAINTS, BridgeState::Off, 0)
+            .expect("Failed to select a WireGuard relay")
+            .endpoint;
+
+        let obfs_config = relay_selector
+            .get_obfuscator(with_wss, endpoint.unwrap_wireguard(), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            obfs_config.config,
+            ObfuscatorConfig::Wss {
+                endpoint: SocketAddr::new(with_wss.ipv4_addr_in.into(), 443),
+                server_name: "se9-wireguard.relays.mullvad.net".to_owned(),
+            }
+        );
+
+        assert!(matches!(
+            relay_selector.get_obfuscator(without_wss, endpoint.unwrap_wireguard(), 0),
+            Err(Error::NoObfuscator)
+        ));
+
+        relay_selector.config.lock().obfuscation_settings.wss.port = Constraint::Only(8443);
+        assert!(matches!(
+            relay_selector.get_obfuscator(with_wss, endpoint.unwrap_wireguard(), 0),
+            Err(Error::NoObfuscator)
+        ));
+    }
+
     #[test]
     fn test_selected_endpoints_use_correct_port_ranges() {
         let relay_selector = new_relay_selector();
@@ -1861,10 +2518,10 @@ mod test {
                 }
             ));
 
-            let SelectedObfuscator {
-                config: ObfuscatorConfig::Udp2Tcp { endpoint },
-                ..
-            } = obfs_config;
+            let endpoint = match obfs_config.config {
+                ObfuscatorConfig::Udp2Tcp { endpoint } => endpoint,
+                config => panic!("unexpected obfuscator: {:?}", config),
+            };
             assert!(TCP2UDP_PORTS.contains(&endpoint.port()));
         }
     }
@@ -1895,6 +2552,32 @@ mod test {
             .expect_err("Successfully selected a relay that should be filtered");
     }
 
+    #[test]
+    fn test_locations_match_parsed_relays() {
+        let mut relay_selector = new_relay_selector();
+        let locations = relay_selector.get_locations();
+        let relays: Vec<_> = locations
+            .countries
+            .iter()
+            .flat_map(|country| country.cities.iter())
+            .flat_map(|city| city.relays.iter())
+            .collect();
+        let find = |hostname: &str| {
+            relays
+                .iter()
+                .find(|relay| relay.hostname == hostname)
+                .unwrap()
+        };
+
+        assert_eq!(
+            find("se9-wireguard").obfuscators.udp2tcp.len(),
+            UDP2TCP_PORTS.len()
+        );
+        assert!(find("se-got-001").obfuscators.udp2tcp.is_empty());
+        assert!(find("se11-wireguard-filtered").tunnels.wireguard.is_empty());
+        assert!(find("se-got-010-filtered").tunnels.openvpn.is_empty());
+    }
+
     #[test]
     fn test_ownership() {
         let relay_selector = new_relay_selector();
@@ -1925,4 +2608,388 @@ mod test {
             ));
         }
     }
+
+    #[test]
+    fn test_datacenter() {
+        let relay_selector = new_relay_selector();
+        let mut constraints = RelayConstraints {
+            datacenter: Constraint::Only(Datacenter("GOT-DC2".to_string())),
+            ..RelayConstraints::default()
+        };
+        for i in 0..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Auto, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        }
+
+        // Only the exit relay has to be in the datacenter
+        constraints.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
+        constraints.wireguard_constraints.use_multihop = true;
+        let relay = relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .unwrap();
+        assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        assert_eq!(
+            relay.entry_relay.map(|relay| relay.hostname),
+            Some("se9-wireguard".to_string())
+        );
+
+        constraints.datacenter = Constraint::Only(Datacenter("got-dc1".to_string()));
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_location_set() {
+        let relay_selector = new_relay_selector();
+        let mut constraints = RelayConstraints {
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            location_set: ConstraintSet {
+                include: vec![
+                    LocationConstraint::Country("no".to_string()),
+                    LocationConstraint::Country("se".to_string()),
+                ],
+                exclude: vec![LocationConstraint::Hostname(
+                    "se".to_string(),
+                    "got".to_string(),
+                    "se9-wireguard".to_string(),
+                )],
+            },
+            ..RelayConstraints::default()
+        };
+        for i in 0..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        }
+
+        constraints.location_set.include = vec![LocationConstraint::Country("no".to_string())];
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_hostname_pattern() {
+        let relay_selector = new_relay_selector();
+        let mut constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::Hostname(
+                "se".to_string(),
+                "got".to_string(),
+                "se1*-wireguard".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+        for i in 0..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        }
+
+        constraints.location = Constraint::Only(LocationConstraint::Hostname(
+            "se".to_string(),
+            "got".to_string(),
+            "se2*".to_string(),
+        ));
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_avoided_relays() {
+        let relay_selector = new_relay_selector();
+        let constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::City(
+                "se".to_string(),
+                "got".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+        relay_selector.set_avoided_relays(HashSet::from(["se9-wireguard".to_string()]));
+        for i in 0..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        }
+
+        // Avoided relays are still used when no other relay matches
+        relay_selector.set_avoided_relays(HashSet::from([
+            "se9-wireguard".to_string(),
+            "se10-wireguard".to_string(),
+        ]));
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_relay_overrides() {
+        let mut relay_selector = new_relay_selector();
+        let constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::Hostname(
+                "se".to_string(),
+                "got".to_string(),
+                "se9-wireguard".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+        let override_address = "192.0.2.1".parse().unwrap();
+        relay_selector.set_relay_overrides(vec![RelayOverride {
+            hostname: "SE9-WIREGUARD".to_string(),
+            ipv4_addr_in: Some(override_address),
+            ipv6_addr_in: None,
+        }]);
+
+        let relay = relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .unwrap();
+        assert_eq!(relay.exit_relay.ipv4_addr_in, override_address);
+        assert_eq!(
+            relay.endpoint.unwrap_wireguard().peer.endpoint.ip(),
+            IpAddr::V4(override_address)
+        );
+        // Frontends are still shown the addresses in the relay list
+        assert_ne!(
+            relay_selector.get_locations().countries[0].cities[0].relays[0].ipv4_addr_in,
+            override_address
+        );
+
+        relay_selector.set_relay_overrides(vec![]);
+        let relay = relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .unwrap();
+        assert_ne!(relay.exit_relay.ipv4_addr_in, override_address);
+    }
+
+    #[test]
+    fn test_selection_strategy() {
+        let relay_selector = new_relay_selector();
+        let constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::City(
+                "se".to_string(),
+                "got".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+        let select = |retry_attempt| {
+            relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, retry_attempt)
+                .unwrap()
+                .exit_relay
+                .hostname
+        };
+
+        relay_selector.set_selection_strategy(SelectionStrategy::RoundRobin);
+        let first = select(0);
+        let second = select(1);
+        assert_ne!(first, second);
+        assert_eq!(select(2), first);
+        assert_eq!(select(3), second);
+
+        // Relays without a known latency are picked by weight
+        relay_selector.set_selection_strategy(SelectionStrategy::LowestLatency);
+        select(0);
+        relay_selector.set_relay_latencies(HashMap::from([
+            ("se9-wireguard".to_string(), Duration::from_millis(300)),
+            ("se10-wireguard".to_string(), Duration::from_millis(100)),
+        ]));
+        for i in 0..10 {
+            assert_eq!(select(i), "se10-wireguard");
+        }
+
+        relay_selector.set_selection_strategy(SelectionStrategy::Random);
+        select(0);
+    }
+
+    #[test]
+    fn test_recent_failures() {
+        let relay_selector = new_relay_selector();
+        let constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::City(
+                "se".to_string(),
+                "got".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+
+        // Failures are not remembered while the cooldown is zero
+        relay_selector.report_failed_relay("se9-wireguard");
+        assert!(relay_selector.recent_failures.lock().failed_at.is_empty());
+
+        relay_selector.set_failure_cooldown(Duration::from_secs(60));
+        relay_selector.report_failed_relay("se9-wireguard");
+        for i in 1..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se10-wireguard");
+        }
+
+        // Relays that failed are still used when no other relay matches
+        relay_selector.report_failed_relay("se10-wireguard");
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_working_openvpn_transport() {
+        let relay_selector = new_relay_selector();
+        let mut constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::Hostname(
+                "se".to_string(),
+                "got".to_string(),
+                "se-got-001".to_string(),
+            )),
+            tunnel_protocol: Constraint::Only(TunnelType::OpenVpn),
+            ..RelayConstraints::default()
+        };
+        let selected_transport = |constraints: &RelayConstraints, attempt| match relay_selector
+            .get_tunnel_endpoint(constraints, BridgeState::Off, attempt)
+            .unwrap()
+            .endpoint
+        {
+            MullvadEndpoint::OpenVpn(endpoint) => (endpoint.protocol, endpoint.address.port()),
+            _ => panic!("OpenVPN endpoint was not selected"),
+        };
+
+        assert_eq!(
+            selected_transport(&constraints, 0),
+            (TransportProtocol::Udp, 1194)
+        );
+
+        relay_selector.set_working_openvpn_transport(Some(TransportPort {
+            protocol: TransportProtocol::Tcp,
+            port: Constraint::Only(443),
+        }));
+        assert_eq!(
+            selected_transport(&constraints, 0),
+            (TransportProtocol::Tcp, 443)
+        );
+
+        // An explicit transport constraint takes precedence
+        constraints.openvpn_constraints.port = Constraint::Only(TransportPort {
+            protocol: TransportProtocol::Udp,
+            port: Constraint::Any,
+        });
+        assert_eq!(
+            selected_transport(&constraints, 0),
+            (TransportProtocol::Udp, 1194)
+        );
+
+        relay_selector.set_working_openvpn_transport(None);
+        constraints.openvpn_constraints.port = Constraint::Any;
+        assert_eq!(
+            selected_transport(&constraints, 0),
+            (TransportProtocol::Udp, 1194)
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn test_custom_list() {
+        use mullvad_types::custom_list::CustomList;
+
+        let relay_selector = new_relay_selector();
+        let mut custom_lists = CustomListsSettings::default();
+        custom_lists
+            .create(CustomList {
+                name: "fast".to_string(),
+                locations: vec![LocationConstraint::Hostname(
+                    "se".to_string(),
+                    "got".to_string(),
+                    "se9-wireguard".to_string(),
+                )],
+            })
+            .unwrap();
+        relay_selector.set_custom_lists(custom_lists.clone());
+
+        let constraints = RelayConstraints {
+            location: Constraint::Only(LocationConstraint::CustomList("fast".to_string())),
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        };
+        for i in 0..10 {
+            let relay = relay_selector
+                .get_tunnel_endpoint(&constraints, BridgeState::Off, i)
+                .unwrap();
+            assert_eq!(relay.exit_relay.hostname, "se9-wireguard");
+        }
+
+        // A list that does not exist, or is empty, matches no relays
+        custom_lists
+            .update(CustomList {
+                name: "fast".to_string(),
+                locations: vec![],
+            })
+            .unwrap();
+        relay_selector.set_custom_lists(custom_lists);
+        assert!(relay_selector
+            .get_tunnel_endpoint(&constraints, BridgeState::Off, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_custom_exit_entry_relay() {
+        let relay_selector = new_relay_selector();
+        let exit = CustomTunnelEndpoint {
+            host: "192.0.2.1".to_string(),
+            config: mullvad_types::ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+                tunnel: wireguard::TunnelConfig {
+                    private_key: wireguard::PrivateKey::new_from_random(),
+                    addresses: vec!["10.0.0.2".parse().unwrap()],
+                },
+                peer: wireguard::PeerConfig {
+                    public_key: PublicKey::from_base64(
+                        "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
+                    )
+                    .unwrap(),
+                    allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                    endpoint: "0.0.0.0:51820".parse().unwrap(),
+                    psk: None,
+                },
+                exit_peer: None,
+                ipv4_gateway: "10.0.0.1".parse().unwrap(),
+                ipv6_gateway: None,
+            }),
+            entry_location: Some(Constraint::Only(LocationConstraint::Hostname(
+                "se".to_string(),
+                "got".to_string(),
+                "se10-wireguard".to_string(),
+            ))),
+        };
+        relay_selector.config.lock().relay_settings =
+            RelaySettings::CustomTunnelEndpoint(exit.clone());
+
+        match relay_selector.get_relay(0).unwrap() {
+            (SelectedRelay::CustomExit(relay), None, None) => {
+                assert_eq!(relay.entry_relay.hostname, "se10-wireguard");
+                assert_eq!(relay.exit, exit);
+            }
+            result => panic!("Expected a custom exit relay, got {:?}", result),
+        }
+
+        // Without an entry location, the custom relay is used directly
+        let exit = CustomTunnelEndpoint {
+            entry_location: None,
+            ..exit
+        };
+        relay_selector.config.lock().relay_settings =
+            RelaySettings::CustomTunnelEndpoint(exit.clone());
+        assert!(matches!(
+            relay_selector.get_relay(0).unwrap(),
+            (SelectedRelay::Custom(relay), None, None) if relay == exit
+        ));
+    }
 }
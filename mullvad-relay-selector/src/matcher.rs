@@ -1,8 +1,9 @@
 use mullvad_types::{
+    custom_list::CustomListsSettings,
     endpoint::{MullvadEndpoint, MullvadWireguardEndpoint},
     relay_constraints::{
-        Constraint, LocationConstraint, Match, OpenVpnConstraints, Ownership, Providers,
-        RelayConstraints, WireguardConstraints,
+        Constraint, ConstraintSet, Datacenter, LocationConstraint, Match, OpenVpnConstraints,
+        Ownership, Providers, RelayConstraints, Set, WireguardConstraints,
     },
     relay_list::{Relay, RelayTunnels, WireguardEndpointData},
 };
@@ -10,20 +11,87 @@ use rand::{seq::SliceRandom, Rng};
 use std::net::{IpAddr, SocketAddr};
 use talpid_types::net::{all_of_the_internet, wireguard, IpVersion, TunnelType};
 
+/// A location constraint with any custom list replaced by the locations in it. A relay matches if
+/// it is in any of the locations.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResolvedLocationConstraint(Vec<LocationConstraint>);
+
+impl ResolvedLocationConstraint {
+    pub fn from_constraint(
+        location: Constraint<LocationConstraint>,
+        custom_lists: &CustomListsSettings,
+    ) -> Constraint<Self> {
+        location.map(|location| Self::from_location(location, custom_lists))
+    }
+
+    pub fn from_set(
+        set: ConstraintSet<LocationConstraint>,
+        custom_lists: &CustomListsSettings,
+    ) -> ConstraintSet<Self> {
+        let resolve = |locations: Vec<LocationConstraint>| {
+            locations
+                .into_iter()
+                .map(|location| Self::from_location(location, custom_lists))
+                .collect()
+        };
+        ConstraintSet {
+            include: resolve(set.include),
+            exclude: resolve(set.exclude),
+        }
+    }
+
+    #[cfg_attr(target_os = "android", allow(unused_variables))]
+    fn from_location(location: LocationConstraint, custom_lists: &CustomListsSettings) -> Self {
+        #[cfg(not(target_os = "android"))]
+        if let LocationConstraint::CustomList(name) = &location {
+            return Self(custom_lists.locations(name).to_vec());
+        }
+        Self(vec![location])
+    }
+}
+
+impl Match<Relay> for ResolvedLocationConstraint {
+    fn matches(&self, relay: &Relay) -> bool {
+        self.0.iter().any(|location| location.matches(relay))
+    }
+}
+
+impl Set<ResolvedLocationConstraint> for ResolvedLocationConstraint {
+    /// Returns whether every location in `self` is within a location in `other`.
+    fn is_subset(&self, other: &Self) -> bool {
+        self.0.iter().all(|location| {
+            other
+                .0
+                .iter()
+                .any(|other_location| location.is_subset(other_location))
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct RelayMatcher<T: TunnelMatcher> {
-    pub location: Constraint<LocationConstraint>,
+    pub location: Constraint<ResolvedLocationConstraint>,
+    pub location_set: ConstraintSet<ResolvedLocationConstraint>,
     pub providers: Constraint<Providers>,
     pub ownership: Constraint<Ownership>,
+    pub datacenter: Constraint<Datacenter>,
     pub tunnel: T,
 }
 
-impl From<RelayConstraints> for RelayMatcher<AnyTunnelMatcher> {
-    fn from(constraints: RelayConstraints) -> Self {
+impl RelayMatcher<AnyTunnelMatcher> {
+    pub fn new(constraints: RelayConstraints, custom_lists: &CustomListsSettings) -> Self {
         Self {
-            location: constraints.location,
+            location: ResolvedLocationConstraint::from_constraint(
+                constraints.location,
+                custom_lists,
+            ),
+            location_set: ResolvedLocationConstraint::from_set(
+                constraints.location_set,
+                custom_lists,
+            ),
             providers: constraints.providers,
             ownership: constraints.ownership,
+            datacenter: constraints.datacenter,
             tunnel: AnyTunnelMatcher {
                 wireguard: constraints.wireguard_constraints.into(),
                 openvpn: constraints.openvpn_constraints,
@@ -31,15 +99,15 @@ impl From<RelayConstraints> for RelayMatcher<AnyTunnelMatcher> {
             },
         }
     }
-}
 
-impl RelayMatcher<AnyTunnelMatcher> {
     pub fn into_wireguard_matcher(self) -> RelayMatcher<WireguardMatcher> {
         RelayMatcher {
             tunnel: self.tunnel.wireguard,
             location: self.location,
+            location_set: self.location_set,
             providers: self.providers,
             ownership: self.ownership,
+            datacenter: self.datacenter,
         }
     }
 }
@@ -55,8 +123,10 @@ impl<T: TunnelMatcher> RelayMatcher<T> {
     /// Only matching endpoints are included in the returned Relay.
     pub fn filter_matching_relay(&self, relay: &Relay) -> Option<Relay> {
         if !self.location.matches(relay)
+            || !self.location_set.matches(relay)
             || !self.providers.matches(relay)
             || !self.ownership.matches(relay)
+            || !self.datacenter.matches(relay)
         {
             return None;
         }
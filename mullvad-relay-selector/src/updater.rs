@@ -1,11 +1,15 @@
 use super::{Error, ParsedRelays};
 use futures::{
-    channel::mpsc,
-    future::{Fuse, FusedFuture},
+    channel::{mpsc, oneshot},
+    future::{Either, Fuse, FusedFuture},
     Future, FutureExt, SinkExt, StreamExt,
 };
-use mullvad_api::{availability::ApiAvailabilityHandle, rest::MullvadRestHandle, RelayListProxy};
-use mullvad_types::relay_list::RelayList;
+use mullvad_api::{
+    availability::ApiAvailabilityHandle,
+    rest::{MullvadRestHandle, RequestServiceHandle},
+    RelayListProxy,
+};
+use mullvad_types::relay_list::{RelayList, RelayListMirror, RelayOverride};
 use parking_lot::Mutex;
 use std::{
     path::{Path, PathBuf},
@@ -20,28 +24,60 @@ use tokio::fs::File;
 /// This check is very cheap. The only reason to not have it very often is because if downloading
 /// constantly fails it will try very often and fill the logs etc.
 const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 15);
-/// How old the cached relays need to be to trigger an update
-const UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 const EXPONENTIAL_BACKOFF_INITIAL: Duration = Duration::from_secs(16);
 const EXPONENTIAL_BACKOFF_FACTOR: u32 = 8;
 
+enum UpdaterCommand {
+    /// Download a new relay list. If a sender is given, the outcome of the download is sent to it.
+    Update(Option<oneshot::Sender<Result<(), Error>>>),
+    /// Change how old the relay list may get before it is updated.
+    SetUpdateInterval(Duration),
+    /// Change where the relay list is downloaded from, and download it from there.
+    SetMirror(Option<RelayListMirror>),
+}
+
 #[derive(Clone)]
 pub struct RelayListUpdaterHandle {
-    tx: mpsc::Sender<()>,
+    tx: mpsc::Sender<UpdaterCommand>,
 }
 
 impl RelayListUpdaterHandle {
     pub async fn update(&mut self) {
+        self.send_command(UpdaterCommand::Update(None)).await
+    }
+
+    /// Downloads a new relay list and waits until it has been applied. Unlike
+    /// [`RelayListUpdaterHandle::update`], a failed download is not retried, but the reason is
+    /// returned instead.
+    pub async fn update_and_wait(&mut self) -> Result<(), Error> {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.tx
+            .send(UpdaterCommand::Update(Some(result_tx)))
+            .await
+            .map_err(|_| Error::DownloaderShutDown)?;
+        result_rx.await.map_err(|_| Error::DownloaderShutDown)?
+    }
+
+    pub async fn set_update_interval(&mut self, interval: Duration) {
+        self.send_command(UpdaterCommand::SetUpdateInterval(interval))
+            .await
+    }
+
+    pub async fn set_mirror(&mut self, mirror: Option<RelayListMirror>) {
+        self.send_command(UpdaterCommand::SetMirror(mirror)).await
+    }
+
+    async fn send_command(&mut self, command: UpdaterCommand) {
         if let Err(error) = self
             .tx
-            .send(())
+            .send(command)
             .await
             .map_err(|_| Error::DownloaderShutDown)
         {
             log::error!(
                 "{}",
-                error.display_chain_with_msg("Unable to send update command to relay list updater")
+                error.display_chain_with_msg("Unable to send command to relay list updater")
             );
         }
     }
@@ -49,10 +85,20 @@ impl RelayListUpdaterHandle {
 
 pub struct RelayListUpdater {
     api_client: RelayListProxy,
+    /// Mirror to download the relay list from instead of the API.
+    mirror: Option<RelayListMirror>,
+    /// Used to reach the mirror, since `api_client` can only reach the API.
+    mirror_service: RequestServiceHandle,
     cache_path: PathBuf,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    /// Addresses to use instead of the ones in downloaded relay lists.
+    relay_overrides: Arc<Mutex<Vec<RelayOverride>>>,
     on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
     last_check: SystemTime,
+    /// How old the cached relays need to be to trigger an update
+    update_interval: Duration,
+    /// Callers waiting for the current download to finish.
+    waiters: Vec<oneshot::Sender<Result<(), Error>>>,
     api_availability: ApiAvailabilityHandle,
 }
 
@@ -60,7 +106,10 @@ impl RelayListUpdater {
     pub fn spawn(
         selector: super::RelaySelector,
         api_handle: MullvadRestHandle,
+        mirror_service: RequestServiceHandle,
         cache_dir: &Path,
+        update_interval: Duration,
+        mirror: Option<RelayListMirror>,
         on_update: impl Fn(&RelayList) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
@@ -68,10 +117,15 @@ impl RelayListUpdater {
         let api_client = RelayListProxy::new(api_handle);
         let updater = RelayListUpdater {
             api_client,
+            mirror,
+            mirror_service,
             cache_path: cache_dir.join(super::RELAYS_FILENAME),
             parsed_relays: selector.parsed_relays,
+            relay_overrides: selector.relay_overrides,
             on_update: Box::new(on_update),
             last_check: UNIX_EPOCH,
+            update_interval,
+            waiters: vec![],
             api_availability,
         };
 
@@ -80,30 +134,46 @@ impl RelayListUpdater {
         RelayListUpdaterHandle { tx }
     }
 
-    async fn run(mut self, mut cmd_rx: mpsc::Receiver<()>) {
+    async fn run(mut self, mut cmd_rx: mpsc::Receiver<UpdaterCommand>) {
         let mut download_future = Box::pin(Fuse::terminated());
         loop {
-            let next_check = tokio::time::sleep(UPDATE_CHECK_INTERVAL).fuse();
+            let next_check =
+                tokio::time::sleep(std::cmp::min(UPDATE_CHECK_INTERVAL, self.update_interval))
+                    .fuse();
             tokio::pin!(next_check);
 
             futures::select! {
                 _check_update = next_check => {
                     if download_future.is_terminated() && self.should_update() {
-                        let tag = self.parsed_relays.lock().tag().map(|tag| tag.to_string());
-                        download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
+                        download_future = Box::pin(self.download_relay_list(true).fuse());
                         self.last_check = SystemTime::now();
                     }
                 },
 
                 new_relay_list = download_future => {
-                    self.consume_new_relay_list(new_relay_list).await;
+                    let result = self.consume_new_relay_list(new_relay_list).await;
+                    for waiter in self.waiters.drain(..) {
+                        let _ = waiter.send(result.clone().map_err(Error::DownloadRelayList));
+                    }
                 },
 
                 cmd = cmd_rx.next() => {
                     match cmd {
-                        Some(()) => {
-                            let tag = self.parsed_relays.lock().tag().map(|tag| tag.to_string());
-                            download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
+                        Some(UpdaterCommand::Update(waiter)) => {
+                            self.waiters.extend(waiter);
+                            // Retrying would keep the waiters from learning why a download failed
+                            let retry = self.waiters.is_empty();
+                            download_future = Box::pin(self.download_relay_list(retry).fuse());
+                            if retry {
+                                self.last_check = SystemTime::now();
+                            }
+                        },
+                        Some(UpdaterCommand::SetUpdateInterval(interval)) => {
+                            self.update_interval = interval;
+                        },
+                        Some(UpdaterCommand::SetMirror(mirror)) => {
+                            self.mirror = mirror;
+                            download_future = Box::pin(self.download_relay_list(true).fuse());
                             self.last_check = SystemTime::now();
                         },
                         None => {
@@ -117,29 +187,37 @@ impl RelayListUpdater {
         }
     }
 
+    /// Applies the result of a download. If the download failed, the reason is returned.
     async fn consume_new_relay_list(
         &mut self,
         result: Result<Option<RelayList>, mullvad_api::Error>,
-    ) {
+    ) -> Result<(), String> {
         match result {
             Ok(Some(relay_list)) => {
                 if let Err(err) = self.update_cache(relay_list).await {
                     log::error!("Failed to update relay list cache: {}", err);
                 }
+                Ok(())
+            }
+            Ok(None) => {
+                log::debug!("Relay list is up-to-date");
+                Ok(())
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to fetch new relay list")
+                );
+                Err(download_error_reason(&error))
             }
-            Ok(None) => log::debug!("Relay list is up-to-date"),
-            Err(error) => log::error!(
-                "{}",
-                error.display_chain_with_msg("Failed to fetch new relay list")
-            ),
         }
     }
 
-    /// Returns true if the current parsed_relays is older than UPDATE_INTERVAL
+    /// Returns true if the current parsed_relays is older than `update_interval`
     fn should_update(&mut self) -> bool {
         let last_check = std::cmp::max(self.parsed_relays.lock().last_updated(), self.last_check);
         match SystemTime::now().duration_since(last_check) {
-            Ok(duration) => duration >= UPDATE_INTERVAL,
+            Ok(duration) => duration >= self.update_interval,
             // If the clock is skewed we have no idea by how much or when the last update
             // actually was, better download again to get in sync and get a `last_updated`
             // timestamp corresponding to the new time.
@@ -147,14 +225,35 @@ impl RelayListUpdater {
         }
     }
 
+    /// Downloads the relay list if it has changed, from the mirror if one is set and from the API
+    /// otherwise. A background download waits for background
+    /// requests to be allowed and is retried until it succeeds, while a single attempt is made
+    /// right away otherwise.
     fn download_relay_list(
-        api_handle: ApiAvailabilityHandle,
-        proxy: RelayListProxy,
-        tag: Option<String>,
+        &self,
+        retry: bool,
     ) -> impl Future<Output = Result<Option<RelayList>, mullvad_api::Error>> + 'static {
+        let tag = self.parsed_relays.lock().tag().map(|tag| tag.to_string());
+        let proxy = self.api_client.clone();
+        let mirror = self.mirror.clone();
+        let mirror_service = self.mirror_service.clone();
+        let fetch = move |tag: Option<String>| match mirror.clone() {
+            Some(mirror) => Either::Left(RelayListProxy::relay_list_from_mirror(
+                mirror_service.clone(),
+                mirror,
+                tag,
+            )),
+            None => Either::Right(proxy.relay_list(tag)),
+        };
+
+        if !retry {
+            return Either::Left(fetch(tag).map(|result| result.map_err(mullvad_api::Error::from)));
+        }
+
+        let api_handle = self.api_availability.clone();
         let download_futures = move || {
             let available = api_handle.wait_background();
-            let req = proxy.relay_list(tag.clone());
+            let req = fetch(tag.clone());
             async move {
                 available.await?;
                 req.await.map_err(mullvad_api::Error::from)
@@ -163,13 +262,13 @@ impl RelayListUpdater {
 
         let exponential_backoff =
             ExponentialBackoff::new(EXPONENTIAL_BACKOFF_INITIAL, EXPONENTIAL_BACKOFF_FACTOR)
-                .max_delay(UPDATE_INTERVAL * 2);
+                .max_delay(self.update_interval * 2);
 
-        retry_future(
+        Either::Right(retry_future(
             download_futures,
             |result| result.is_err(),
             Jittered::jitter(exponential_backoff),
-        )
+        ))
     }
 
     async fn update_cache(&mut self, new_relay_list: RelayList) -> Result<(), Error> {
@@ -180,13 +279,17 @@ impl RelayListUpdater {
             );
         }
 
-        let new_parsed_relays = ParsedRelays::from_relay_list(new_relay_list, SystemTime::now());
+        let mut new_parsed_relays =
+            ParsedRelays::from_relay_list(new_relay_list, SystemTime::now());
         log::info!(
             "Downloaded relay inventory has {} relays",
             new_parsed_relays.relays().len()
         );
 
+        // Lock the relays first, so that the overrides cannot change before the new relays are
+        // stored
         let mut parsed_relays = self.parsed_relays.lock();
+        new_parsed_relays.apply_overrides(&self.relay_overrides.lock());
         *parsed_relays = new_parsed_relays;
         (self.on_update)(parsed_relays.locations());
         Ok(())
@@ -206,3 +309,20 @@ impl RelayListUpdater {
         Ok(())
     }
 }
+
+/// Describes why a download failed, without the generic message of the outermost error.
+fn download_error_reason(error: &mullvad_api::Error) -> String {
+    let mut reason = String::new();
+    let mut source: Option<&dyn std::error::Error> = match error {
+        mullvad_api::Error::RestError(error) => Some(error),
+        error => Some(error),
+    };
+    while let Some(error) = source {
+        if !reason.is_empty() {
+            reason.push_str(": ");
+        }
+        reason.push_str(&error.to_string());
+        source = error.source();
+    }
+    reason
+}
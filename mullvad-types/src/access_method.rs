@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use talpid_types::net::openvpn::{RemoteProxySettings, ShadowsocksProxySettings};
+
+/// The ways in which the daemon may reach the API. When the API cannot be reached, the daemon
+/// moves on to the next enabled method, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApiAccessMethodSettings {
+    pub methods: Vec<ApiAccessMethod>,
+}
+
+impl ApiAccessMethodSettings {
+    pub fn get(&self, name: &str) -> Option<&ApiAccessMethod> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ApiAccessMethod> {
+        self.methods.iter_mut().find(|method| method.name == name)
+    }
+
+    /// Returns the enabled methods, in the order that they are tried.
+    pub fn enabled(&self) -> impl Iterator<Item = &ApiAccessMethod> {
+        self.methods.iter().filter(|method| method.enabled)
+    }
+
+    /// Enables the method named `name` and disables all others, so that the API is only reached
+    /// through it. Returns `false` and changes nothing if there is no such method.
+    pub fn use_only(&mut self, name: &str) -> bool {
+        if self.get(name).is_none() {
+            return false;
+        }
+        for method in &mut self.methods {
+            method.enabled = method.name == name;
+        }
+        true
+    }
+}
+
+impl Default for ApiAccessMethodSettings {
+    fn default() -> Self {
+        ApiAccessMethodSettings {
+            methods: vec![
+                ApiAccessMethod {
+                    name: "direct".to_owned(),
+                    enabled: true,
+                    access_method: AccessMethod::Direct,
+                },
+                ApiAccessMethod {
+                    name: "bridges".to_owned(),
+                    enabled: true,
+                    access_method: AccessMethod::Bridges,
+                },
+            ],
+        }
+    }
+}
+
+/// A named way of reaching the API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApiAccessMethod {
+    pub name: String,
+    pub enabled: bool,
+    pub access_method: AccessMethod,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessMethod {
+    /// Connect to the API without a proxy.
+    Direct,
+    /// Connect through a Mullvad bridge that matches the bridge constraints.
+    Bridges,
+    /// Connect through a Shadowsocks server supplied by the user.
+    Shadowsocks(ShadowsocksProxySettings),
+    /// Connect through a SOCKS5 server supplied by the user.
+    Socks5(RemoteProxySettings),
+    /// Connect through an HTTP proxy supplied by the user, using the `CONNECT` method.
+    Http(RemoteProxySettings),
+}
+
+impl fmt::Display for AccessMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessMethod::Direct => "Direct".fmt(f),
+            AccessMethod::Bridges => "Mullvad bridges".fmt(f),
+            AccessMethod::Shadowsocks(settings) => write!(
+                f,
+                "Shadowsocks {} (cipher: {})",
+                settings.peer, settings.cipher
+            ),
+            AccessMethod::Socks5(settings) => {
+                write!(f, "SOCKS5 {}", settings.address)?;
+                if let Some(auth) = &settings.auth {
+                    write!(f, " (username: {})", auth.username)?;
+                }
+                Ok(())
+            }
+            AccessMethod::Http(settings) => {
+                write!(f, "HTTP {}", settings.address)?;
+                if let Some(auth) = &settings.auth {
+                    write!(f, " (username: {})", auth.username)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::openvpn::ProxyAuth;
+
+    #[test]
+    fn test_use_only() {
+        let mut settings = ApiAccessMethodSettings::default();
+        settings.methods.push(ApiAccessMethod {
+            name: "proxy".to_owned(),
+            enabled: false,
+            access_method: AccessMethod::Socks5(RemoteProxySettings {
+                address: "192.0.2.1:1080".parse().unwrap(),
+                auth: Some(ProxyAuth {
+                    username: "user".to_owned(),
+                    password: "pass".to_owned(),
+                }),
+            }),
+        });
+
+        assert!(!settings.use_only("missing"));
+        assert_eq!(settings.enabled().count(), 2);
+
+        assert!(settings.use_only("proxy"));
+        let enabled: Vec<_> = settings
+            .enabled()
+            .map(|method| method.name.as_str())
+            .collect();
+        assert_eq!(enabled, vec!["proxy"]);
+    }
+}
@@ -2,6 +2,7 @@ use chrono::{offset::Utc, DateTime};
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Identifier used to identify a Mullvad account.
 pub type AccountToken = String;
@@ -25,6 +26,15 @@ impl AccountData {
     }
 }
 
+/// Sent when the time left on the account drops below one of the configured notification
+/// thresholds.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccountExpiryEvent {
+    pub expiry: DateTime<Utc>,
+    /// The smallest threshold that the time left has dropped below.
+    pub threshold: Duration,
+}
+
 /// Data structure that's returned from successful invocation of the mullvad API's
 /// `/v1/submit-voucher` RPC.
 #[derive(Deserialize, Serialize, Debug)]
@@ -39,6 +49,22 @@ pub struct VoucherSubmission {
     pub new_expiry: DateTime<Utc>,
 }
 
+/// A pending login that completes once someone who knows the account number approves `code`
+/// at `url`, e.g. in a browser on another device.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LoginLink {
+    pub code: String,
+    pub url: String,
+    pub expiry: DateTime<Utc>,
+}
+
+impl LoginLink {
+    /// Return true if the code can no longer be approved.
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expiry
+    }
+}
+
 /// Token used for authentication in the API.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct AccessTokenData {
@@ -0,0 +1,155 @@
+//! Named lists of locations, such as "My fast servers". Relay constraints refer to a list by its
+//! name, using `LocationConstraint::CustomList`, and match the relays in any of its locations.
+
+use crate::relay_constraints::LocationConstraint;
+use serde::{Deserialize, Serialize};
+
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error(display = "Custom list names must not be empty")]
+    EmptyName,
+
+    #[error(display = "A custom list named \"{}\" already exists", _0)]
+    ListExists(String),
+
+    #[error(display = "There is no custom list named \"{}\"", _0)]
+    ListNotFound(String),
+
+    #[error(display = "Custom lists cannot contain other custom lists")]
+    NestedList,
+
+    #[error(display = "The custom list \"{}\" is used by the relay settings", _0)]
+    ListInUse(String),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomListsSettings {
+    pub custom_lists: Vec<CustomList>,
+}
+
+impl CustomListsSettings {
+    pub fn get(&self, name: &str) -> Option<&CustomList> {
+        self.custom_lists.iter().find(|list| list.name == name)
+    }
+
+    /// Adds a new list. It is an error if a list with the same name exists.
+    pub fn create(&mut self, list: CustomList) -> Result<(), Error> {
+        list.validate()?;
+        if self.get(&list.name).is_some() {
+            return Err(Error::ListExists(list.name));
+        }
+        self.custom_lists.push(list);
+        Ok(())
+    }
+
+    /// Replaces the locations of the list with the same name as `list`.
+    pub fn update(&mut self, list: CustomList) -> Result<(), Error> {
+        list.validate()?;
+        match self
+            .custom_lists
+            .iter_mut()
+            .find(|existing| existing.name == list.name)
+        {
+            Some(existing) => {
+                *existing = list;
+                Ok(())
+            }
+            None => Err(Error::ListNotFound(list.name)),
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<CustomList, Error> {
+        match self.custom_lists.iter().position(|list| list.name == name) {
+            Some(index) => Ok(self.custom_lists.remove(index)),
+            None => Err(Error::ListNotFound(name.to_owned())),
+        }
+    }
+
+    /// Returns the locations of the list named `name`. A list that does not exist is treated as
+    /// an empty list, which matches no relays.
+    pub fn locations(&self, name: &str) -> &[LocationConstraint] {
+        self.get(name)
+            .map(|list| list.locations.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomList {
+    pub name: String,
+    pub locations: Vec<LocationConstraint>,
+}
+
+impl CustomList {
+    fn validate(&self) -> Result<(), Error> {
+        if self.name.trim().is_empty() {
+            return Err(Error::EmptyName);
+        }
+        #[cfg(not(target_os = "android"))]
+        if self
+            .locations
+            .iter()
+            .any(|location| matches!(location, LocationConstraint::CustomList(_)))
+        {
+            return Err(Error::NestedList);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn list(name: &str, countries: &[&str]) -> CustomList {
+        CustomList {
+            name: name.to_owned(),
+            locations: countries
+                .iter()
+                .map(|country| LocationConstraint::Country(country.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_create_update_delete() {
+        let mut settings = CustomListsSettings::default();
+        settings.create(list("fast", &["se", "no"])).unwrap();
+        assert_eq!(
+            settings.create(list("fast", &["fi"])),
+            Err(Error::ListExists("fast".to_owned()))
+        );
+        assert_eq!(settings.locations("fast").len(), 2);
+
+        settings.update(list("fast", &["fi"])).unwrap();
+        assert_eq!(
+            settings.locations("fast"),
+            &[LocationConstraint::Country("fi".to_owned())]
+        );
+        assert_eq!(
+            settings.update(list("slow", &["fi"])),
+            Err(Error::ListNotFound("slow".to_owned()))
+        );
+
+        assert_eq!(settings.delete("fast"), Ok(list("fast", &["fi"])));
+        assert!(settings.locations("fast").is_empty());
+        assert!(settings.delete("fast").is_err());
+    }
+
+    #[test]
+    fn test_empty_name() {
+        let mut settings = CustomListsSettings::default();
+        assert_eq!(settings.create(list(" ", &["se"])), Err(Error::EmptyName));
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn test_nested_list() {
+        let mut settings = CustomListsSettings::default();
+        let mut nested = list("nested", &["se"]);
+        nested
+            .locations
+            .push(LocationConstraint::CustomList("fast".to_owned()));
+        assert_eq!(settings.create(nested), Err(Error::NestedList));
+    }
+}
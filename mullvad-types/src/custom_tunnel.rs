@@ -1,4 +1,7 @@
-use crate::settings::TunnelOptions;
+use crate::{
+    relay_constraints::{Constraint, LocationConstraint},
+    settings::TunnelOptions,
+};
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Serialize};
@@ -6,7 +9,9 @@ use std::{
     fmt, io,
     net::{IpAddr, SocketAddr, ToSocketAddrs},
 };
-use talpid_types::net::{openvpn, wireguard, Endpoint, TunnelParameters};
+use talpid_types::net::{
+    obfuscation::ObfuscatorConfig, openvpn, wireguard, Endpoint, TunnelParameters,
+};
 
 #[derive(err_derive::Error, Debug)]
 pub enum Error {
@@ -15,6 +20,9 @@ pub enum Error {
 
     #[error(display = "Host has no IPv4 address: {}", _0)]
     HostHasNoIpv4(String),
+
+    #[error(display = "Only WireGuard servers can be reached through an entry relay")]
+    EntryRelayNotSupported,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -25,11 +33,19 @@ pub enum Error {
 pub struct CustomTunnelEndpoint {
     pub host: String,
     pub config: ConnectionConfig,
+    /// Location of a Mullvad relay to reach the server through, if any. This is only supported
+    /// for WireGuard servers.
+    #[serde(default)]
+    pub entry_location: Option<Constraint<LocationConstraint>>,
 }
 
 impl CustomTunnelEndpoint {
     pub fn new(host: String, config: ConnectionConfig) -> Self {
-        Self { host, config }
+        Self {
+            host,
+            config,
+            entry_location: None,
+        }
     }
 
     pub fn endpoint(&self) -> Endpoint {
@@ -66,6 +82,41 @@ impl CustomTunnelEndpoint {
         };
         Ok(parameters)
     }
+
+    /// Returns the parameters of a tunnel to this WireGuard server that goes through
+    /// `entry_peer`, a Mullvad relay. Both hops use the same interface, so the key and addresses
+    /// in `tunnel` must be accepted by the server, rather than the ones in `config`.
+    pub fn to_multihop_tunnel_parameters(
+        &self,
+        tunnel: wireguard::TunnelConfig,
+        mut entry_peer: wireguard::PeerConfig,
+        tunnel_options: TunnelOptions,
+        obfuscation: Option<ObfuscatorConfig>,
+    ) -> Result<TunnelParameters, Error> {
+        let config = match &self.config {
+            ConnectionConfig::Wireguard(config) => config,
+            ConnectionConfig::OpenVpn(_) => return Err(Error::EntryRelayNotSupported),
+        };
+        let ip = resolve_to_ip(&self.host)?;
+        let mut exit_peer = config.peer.clone();
+        exit_peer.endpoint = SocketAddr::new(ip, exit_peer.endpoint.port());
+        entry_peer.allowed_ips = vec![ip.into()];
+
+        Ok(wireguard::TunnelParameters {
+            connection: wireguard::ConnectionConfig {
+                tunnel,
+                peer: entry_peer,
+                exit_peer: Some(exit_peer),
+                // DNS and the pings of the connectivity monitor are answered by the server
+                ipv4_gateway: config.ipv4_gateway,
+                ipv6_gateway: config.ipv6_gateway,
+            },
+            options: tunnel_options.wireguard.options,
+            generic_options: tunnel_options.generic,
+            obfuscation,
+        }
+        .into())
+    }
 }
 
 impl fmt::Display for CustomTunnelEndpoint {
@@ -77,12 +128,17 @@ impl fmt::Display for CustomTunnelEndpoint {
                 self.host,
                 config.endpoint.address.port(),
                 config.endpoint.protocol
-            ),
+            )?,
             ConnectionConfig::Wireguard(connection) => write!(
                 f,
                 "WireGuard relay - {} with public key {}",
                 connection.peer.endpoint, connection.peer.public_key
-            ),
+            )?,
+        }
+        match &self.entry_location {
+            Some(Constraint::Any) => write!(f, " (via any location)"),
+            Some(Constraint::Only(location)) => write!(f, " (via {})", location),
+            None => Ok(()),
         }
     }
 }
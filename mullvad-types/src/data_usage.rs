@@ -0,0 +1,44 @@
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Number of bytes that have passed through the tunnel in each direction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficCounters {
+    pub received: u64,
+    pub sent: u64,
+}
+
+impl TrafficCounters {
+    pub fn add(&mut self, other: TrafficCounters) {
+        self.received = self.received.saturating_add(other.received);
+        self.sent = self.sent.saturating_add(other.sent);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.received.saturating_add(self.sent)
+    }
+}
+
+/// The traffic of a calendar month, in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonthlyDataUsage {
+    pub year: i32,
+    /// From 1 to 12.
+    pub month: u32,
+    pub traffic: TrafficCounters,
+}
+
+/// The traffic since the tunnel was connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionDataUsage {
+    pub started: DateTime<Utc>,
+    pub traffic: TrafficCounters,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUsage {
+    /// `None` while not connected.
+    pub session: Option<SessionDataUsage>,
+    /// The months that any traffic was recorded for, oldest first.
+    pub months: Vec<MonthlyDataUsage>,
+}
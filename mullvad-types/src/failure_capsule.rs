@@ -0,0 +1,38 @@
+use chrono::{offset::Utc, DateTime};
+use std::fmt;
+
+/// Context about a connection attempt that failed, in a form that is suitable for attaching to a
+/// bug report. Addresses and other sensitive information are redacted, but the relays are
+/// identified by hostname.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureCapsule {
+    pub started: DateTime<Utc>,
+    pub failed: DateTime<Utc>,
+    /// Why the attempt failed.
+    pub cause: String,
+    /// The tunnel endpoint that was connected to.
+    pub endpoint: String,
+    /// Names and values of the relays and settings that the attempt was made with.
+    pub parameters: Vec<(String, String)>,
+    /// The lines that were logged during the attempt.
+    pub log: Vec<String>,
+}
+
+impl fmt::Display for FailureCapsule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Failed connection attempt")?;
+        writeln!(f, "Started: {}", self.started.to_rfc3339())?;
+        writeln!(f, "Failed: {}", self.failed.to_rfc3339())?;
+        writeln!(f, "Cause: {}", self.cause)?;
+        writeln!(f, "Endpoint: {}", self.endpoint)?;
+        writeln!(f, "\nParameters:")?;
+        for (name, value) in &self.parameters {
+            writeln!(f, "  {}: {}", name, value)?;
+        }
+        writeln!(f, "\nLog:")?;
+        for line in &self.log {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{offset::Utc, DateTime};
+use std::fmt;
+
+/// The results of a set of checks for signs that the network blocks or tampers with VPN and API
+/// traffic, along with suggestions on which circumvention settings to try.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterferenceReport {
+    pub created: DateTime<Utc>,
+    /// Number of connection attempts in a row that had failed when the report was made.
+    pub connection_failures: u32,
+    pub checks: Vec<InterferenceCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterferenceCheck {
+    pub kind: CheckKind,
+    pub outcome: CheckOutcome,
+    /// What was observed, in a form that is suitable for sharing.
+    pub details: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// Whether the API hostname resolves to the addresses that the app knows about.
+    ApiDns,
+    /// Whether WireGuard handshakes over plain UDP go through.
+    WireguardUdp,
+    /// Whether TCP connections to port 443 of the API go through.
+    ApiTcp,
+    /// Whether the API presents a certificate from the expected authority.
+    ApiTls,
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckKind::ApiDns => "DNS lookup of the API".fmt(f),
+            CheckKind::WireguardUdp => "WireGuard over UDP".fmt(f),
+            CheckKind::ApiTcp => "TCP port 443 of the API".fmt(f),
+            CheckKind::ApiTls => "TLS certificate of the API".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    /// The check found signs of interference.
+    Failed,
+    /// The check could not tell whether there is interference.
+    Inconclusive,
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckOutcome::Passed => "PASS".fmt(f),
+            CheckOutcome::Failed => "FAIL".fmt(f),
+            CheckOutcome::Inconclusive => "????".fmt(f),
+        }
+    }
+}
+
+impl InterferenceReport {
+    pub fn outcome(&self, kind: CheckKind) -> Option<CheckOutcome> {
+        self.checks
+            .iter()
+            .find(|check| check.kind == kind)
+            .map(|check| check.outcome)
+    }
+
+    /// Returns the settings that are likely to get around the interference that was found.
+    pub fn suggestions(&self) -> Vec<&'static str> {
+        let failed = |kind| self.outcome(kind) == Some(CheckOutcome::Failed);
+        let mut suggestions = vec![];
+        if failed(CheckKind::WireguardUdp) {
+            suggestions.push(
+                "WireGuard over UDP appears to be blocked. Tunnel it over TCP with \
+                 `mullvad obfuscation set mode udp2tcp`, or use OpenVPN over TCP port 443 with \
+                 `mullvad relay set tunnel-protocol openvpn` and \
+                 `mullvad relay set tunnel openvpn --protocol tcp --port 443`.",
+            );
+        }
+        if failed(CheckKind::ApiTcp) || failed(CheckKind::ApiTls) {
+            suggestions.push(
+                "The API cannot be reached directly, or the connection to it is intercepted. \
+                 Make sure that the \"bridges\" API access method is enabled, or add a proxy \
+                 with `mullvad api-access add`.",
+            );
+        }
+        if failed(CheckKind::ApiTls) {
+            suggestions.push(
+                "TLS connections appear to be intercepted, e.g. by a corporate proxy or antivirus \
+                 software. Traffic inside the tunnel is not affected once it has connected.",
+            );
+        }
+        if failed(CheckKind::ApiDns) {
+            suggestions.push(
+                "DNS responses appear to be tampered with. The app does not rely on DNS to reach \
+                 the API or the relays, but other programs may be affected while disconnected.",
+            );
+        }
+        suggestions
+    }
+}
+
+impl fmt::Display for InterferenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Network interference report")?;
+        writeln!(f, "Created: {}", self.created.to_rfc3339())?;
+        writeln!(
+            f,
+            "Failed connection attempts in a row: {}",
+            self.connection_failures
+        )?;
+        writeln!(f)?;
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.outcome, check.kind, check.details)?;
+        }
+
+        let suggestions = self.suggestions();
+        if !suggestions.is_empty() {
+            writeln!(f, "\nSuggestions:")?;
+            for suggestion in suggestions {
+                writeln!(f, "  - {}", suggestion)?;
+            }
+        }
+        Ok(())
+    }
+}
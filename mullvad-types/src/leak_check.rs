@@ -0,0 +1,72 @@
+use crate::interference::CheckOutcome;
+use chrono::{offset::Utc, DateTime};
+use std::fmt;
+
+/// The results of a set of checks that traffic, DNS queries and addresses do not leak outside
+/// of the tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    pub created: DateTime<Utc>,
+    /// Whether the tunnel was connected when the checks were run. Most checks are inconclusive
+    /// otherwise.
+    pub connected: bool,
+    pub checks: Vec<LeakCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakCheck {
+    pub kind: LeakCheckKind,
+    pub outcome: CheckOutcome,
+    /// What was observed.
+    pub details: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeakCheckKind {
+    /// Whether IPv4 traffic exits from a Mullvad relay.
+    ExitIp,
+    /// Whether DNS queries are resolved by Mullvad's resolvers.
+    Dns,
+    /// Whether IPv6 traffic is either sent through the tunnel or blocked.
+    Ipv6,
+    /// Whether local interfaces have public addresses that a browser can reveal through WebRTC.
+    WebRtc,
+}
+
+impl fmt::Display for LeakCheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeakCheckKind::ExitIp => "Exit IP".fmt(f),
+            LeakCheckKind::Dns => "DNS resolver".fmt(f),
+            LeakCheckKind::Ipv6 => "IPv6 leaks".fmt(f),
+            LeakCheckKind::WebRtc => "WebRTC addresses".fmt(f),
+        }
+    }
+}
+
+impl LeakReport {
+    /// Returns whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome == CheckOutcome::Passed)
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Leak check report")?;
+        writeln!(f, "Created: {}", self.created.to_rfc3339())?;
+        if !self.connected {
+            writeln!(
+                f,
+                "The tunnel is not connected, so most checks cannot tell whether anything leaks"
+            )?;
+        }
+        writeln!(f)?;
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.outcome, check.kind, check.details)?;
+        }
+        Ok(())
+    }
+}
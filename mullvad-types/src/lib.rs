@@ -1,12 +1,20 @@
 #![deny(rust_2018_idioms)]
 
+pub mod access_method;
 pub mod account;
 pub mod auth_failed;
+pub mod custom_list;
+pub mod data_usage;
 pub mod device;
 pub mod endpoint;
+pub mod failure_capsule;
+pub mod interference;
+pub mod leak_check;
 pub mod location;
+pub mod protocol_probe;
 pub mod relay_constraints;
 pub mod relay_list;
+pub mod relay_stats;
 pub mod settings;
 pub mod states;
 pub mod version;
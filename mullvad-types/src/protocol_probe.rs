@@ -0,0 +1,217 @@
+use chrono::{offset::Utc, DateTime};
+use std::{fmt, time::Duration};
+
+/// Which ways of reaching a relay got through the current network, as found by sending probes
+/// to nearby relays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReport {
+    pub created: DateTime<Utc>,
+    pub results: Vec<ProbeResult>,
+}
+
+/// The outcome of probing one relay with one protocol and port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub protocol: ProbeProtocol,
+    pub port: u16,
+    pub hostname: String,
+    pub outcome: ProbeOutcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeProtocol {
+    /// A WireGuard handshake over UDP.
+    WireguardUdp,
+    /// A TCP connection to the udp2tcp obfuscation endpoint of a WireGuard relay.
+    Udp2Tcp,
+    /// An OpenVPN session over UDP.
+    OpenVpnUdp,
+    /// An OpenVPN session over TCP.
+    OpenVpnTcp,
+    /// A TCP connection to a Shadowsocks bridge.
+    Bridge,
+}
+
+impl ProbeProtocol {
+    const ALL: [ProbeProtocol; 5] = [
+        ProbeProtocol::WireguardUdp,
+        ProbeProtocol::Udp2Tcp,
+        ProbeProtocol::OpenVpnUdp,
+        ProbeProtocol::OpenVpnTcp,
+        ProbeProtocol::Bridge,
+    ];
+
+    /// Returns the command that makes the daemon connect this way.
+    fn suggestion(&self, port: u16) -> String {
+        match self {
+            ProbeProtocol::WireguardUdp => format!(
+                "mullvad relay set tunnel-protocol wireguard && mullvad relay set tunnel wireguard \
+                 --port {}",
+                port
+            ),
+            ProbeProtocol::Udp2Tcp => format!(
+                "mullvad obfuscation set mode udp2tcp && mullvad obfuscation set udp2tcp --port {}",
+                port
+            ),
+            ProbeProtocol::OpenVpnUdp => format!(
+                "mullvad relay set tunnel-protocol openvpn && mullvad relay set tunnel openvpn \
+                 --protocol udp --port {}",
+                port
+            ),
+            ProbeProtocol::OpenVpnTcp => format!(
+                "mullvad relay set tunnel-protocol openvpn && mullvad relay set tunnel openvpn \
+                 --protocol tcp --port {}",
+                port
+            ),
+            ProbeProtocol::Bridge => {
+                "mullvad relay set tunnel-protocol openvpn && mullvad bridge set state on"
+                    .to_owned()
+            }
+        }
+    }
+}
+
+impl fmt::Display for ProbeProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeProtocol::WireguardUdp => "WireGuard/UDP".fmt(f),
+            ProbeProtocol::Udp2Tcp => "WireGuard/udp2tcp".fmt(f),
+            ProbeProtocol::OpenVpnUdp => "OpenVPN/UDP".fmt(f),
+            ProbeProtocol::OpenVpnTcp => "OpenVPN/TCP".fmt(f),
+            ProbeProtocol::Bridge => "Bridge".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// The relay replied after this long.
+    Reachable(Duration),
+    /// The relay did not reply in time, or the connection was refused or reset.
+    Unreachable(String),
+    /// No probe was sent, for example because it requires a device that is not there.
+    Skipped(String),
+}
+
+impl ProbeOutcome {
+    fn cell(&self) -> String {
+        match self {
+            ProbeOutcome::Reachable(latency) => format!("ok ({} ms)", latency.as_millis()),
+            ProbeOutcome::Unreachable(_) => "blocked".to_owned(),
+            ProbeOutcome::Skipped(_) => "skipped".to_owned(),
+        }
+    }
+}
+
+impl ProbeReport {
+    /// Returns the protocol and port combinations that reached at least one relay, in the order
+    /// that they were probed.
+    pub fn working(&self) -> Vec<(ProbeProtocol, u16)> {
+        let mut working = vec![];
+        for result in &self.results {
+            let key = (result.protocol, result.port);
+            if matches!(result.outcome, ProbeOutcome::Reachable(_)) && !working.contains(&key) {
+                working.push(key);
+            }
+        }
+        working
+    }
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Protocol probe report")?;
+        writeln!(f, "Created: {}", self.created.to_rfc3339())?;
+
+        for protocol in ProbeProtocol::ALL {
+            let results: Vec<&ProbeResult> = self
+                .results
+                .iter()
+                .filter(|result| result.protocol == protocol)
+                .collect();
+            if results.is_empty() {
+                continue;
+            }
+
+            let mut hostnames: Vec<&str> = vec![];
+            let mut ports: Vec<u16> = vec![];
+            for result in &results {
+                if !hostnames.contains(&result.hostname.as_str()) {
+                    hostnames.push(&result.hostname);
+                }
+                if !ports.contains(&result.port) {
+                    ports.push(result.port);
+                }
+            }
+            let width = hostnames
+                .iter()
+                .map(|hostname| hostname.len())
+                .max()
+                .unwrap_or(0)
+                .max("ok (1000 ms)".len());
+
+            writeln!(f)?;
+            write!(f, "{:<20}", protocol.to_string())?;
+            for hostname in &hostnames {
+                write!(f, "  {:<width$}", hostname, width = width)?;
+            }
+            writeln!(f)?;
+            for port in &ports {
+                write!(f, "{:<20}", format!("  port {}", port))?;
+                for hostname in &hostnames {
+                    let cell = results
+                        .iter()
+                        .find(|result| result.port == *port && result.hostname == *hostname)
+                        .map(|result| result.outcome.cell())
+                        .unwrap_or_else(|| "-".to_owned());
+                    write!(f, "  {:<width$}", cell, width = width)?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        let mut notes: Vec<String> = vec![];
+        for result in &self.results {
+            if let ProbeOutcome::Skipped(reason) = &result.outcome {
+                let note = format!("{} was skipped: {}", result.protocol, reason);
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+            }
+        }
+        if !notes.is_empty() {
+            writeln!(f)?;
+            for note in notes {
+                writeln!(f, "{}", note)?;
+            }
+        }
+
+        writeln!(f)?;
+        let working = self.working();
+        if working
+            .iter()
+            .any(|(protocol, _)| *protocol == ProbeProtocol::WireguardUdp)
+        {
+            return writeln!(
+                f,
+                "WireGuard over UDP works, so no constraints should be needed on this network"
+            );
+        }
+        match working.first() {
+            Some((protocol, port)) => {
+                writeln!(
+                    f,
+                    "WireGuard over UDP did not get through, but {} on port {} did. To use it, \
+                     run:",
+                    protocol, port
+                )?;
+                writeln!(f, "  {}", protocol.suggestion(*port))
+            }
+            None => writeln!(
+                f,
+                "No probes got through. The network may block all VPN traffic, or not be \
+                 connected to the internet at all"
+            ),
+        }
+    }
+}
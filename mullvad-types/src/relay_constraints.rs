@@ -9,8 +9,11 @@ use crate::{
 #[cfg(target_os = "android")]
 use jnix::{FromJava, IntoJava};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
-use talpid_types::net::{openvpn::ProxySettings, IpVersion, TransportProtocol, TunnelType};
+use std::{collections::HashSet, fmt, net::SocketAddr};
+use talpid_types::net::{
+    obfuscation::ExternalTransportConfig, openvpn::ProxySettings, IpVersion, TransportProtocol,
+    TunnelType,
+};
 
 pub trait Match<T> {
     fn matches(&self, other: &T) -> bool;
@@ -127,6 +130,70 @@ impl<T: fmt::Debug + Clone + Eq + Set<U>, U: fmt::Debug + Clone + Eq> Set<Constr
     }
 }
 
+/// Limits a value to a set of alternatives, for constraints that a single [`Constraint`] cannot
+/// express, such as "Sweden or Norway, but not Stockholm". A value matches if it matches any of
+/// `include`, or if `include` is empty, and if it matches none of `exclude`.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConstraintSet<T> {
+    pub include: Vec<T>,
+    pub exclude: Vec<T>,
+}
+
+impl<T> ConstraintSet<T> {
+    /// Returns a set that matches everything.
+    pub const fn any() -> Self {
+        ConstraintSet {
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+impl<T> Default for ConstraintSet<T> {
+    fn default() -> Self {
+        Self::any()
+    }
+}
+
+impl<T: Match<U>, U> Match<U> for ConstraintSet<T> {
+    fn matches(&self, other: &U) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|value| value.matches(other)))
+            && !self.exclude.iter().any(|value| value.matches(other))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for ConstraintSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fn write_list<T: fmt::Display>(
+            f: &mut fmt::Formatter<'_>,
+            values: &[T],
+        ) -> Result<(), fmt::Error> {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " or ")?;
+                }
+                value.fmt(f)?;
+            }
+            Ok(())
+        }
+        if self.include.is_empty() {
+            write!(f, "anywhere")?;
+        } else {
+            write_list(f, &self.include)?;
+        }
+        if !self.exclude.is_empty() {
+            write!(f, ", except ")?;
+            write_list(f, &self.exclude)?;
+        }
+        Ok(())
+    }
+}
+
 impl<T: fmt::Debug + Clone + Eq + PartialEq> From<Option<T>> for Constraint<T> {
     fn from(value: Option<T>) -> Self {
         match value {
@@ -182,11 +249,16 @@ impl RelaySettings {
 #[cfg_attr(target_os = "android", jnix(package = "net.mullvad.mullvadvpn.model"))]
 pub struct RelayConstraints {
     pub location: Constraint<LocationConstraint>,
+    /// Further limits the locations, in addition to `location`.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub location_set: ConstraintSet<LocationConstraint>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub providers: Constraint<Providers>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub ownership: Constraint<Ownership>,
     #[cfg_attr(target_os = "android", jnix(skip))]
+    pub datacenter: Constraint<Datacenter>,
+    #[cfg_attr(target_os = "android", jnix(skip))]
     pub tunnel_protocol: Constraint<TunnelType>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub wireguard_constraints: WireguardConstraints,
@@ -200,8 +272,10 @@ impl Default for RelayConstraints {
         RelayConstraints {
             tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
             location: Constraint::default(),
+            location_set: ConstraintSet::default(),
             providers: Constraint::default(),
             ownership: Constraint::default(),
+            datacenter: Constraint::default(),
             wireguard_constraints: WireguardConstraints::default(),
             openvpn_constraints: OpenVpnConstraints::default(),
         }
@@ -212,8 +286,12 @@ impl RelayConstraints {
     pub fn merge(&self, update: RelayConstraintsUpdate) -> Self {
         RelayConstraints {
             location: update.location.unwrap_or_else(|| self.location.clone()),
+            location_set: update
+                .location_set
+                .unwrap_or_else(|| self.location_set.clone()),
             providers: update.providers.unwrap_or_else(|| self.providers.clone()),
             ownership: update.ownership.unwrap_or(self.ownership),
+            datacenter: update.datacenter.unwrap_or_else(|| self.datacenter.clone()),
             tunnel_protocol: update.tunnel_protocol.unwrap_or(self.tunnel_protocol),
             wireguard_constraints: update
                 .wireguard_constraints
@@ -223,6 +301,17 @@ impl RelayConstraints {
                 .unwrap_or(self.openvpn_constraints),
         }
     }
+
+    /// Returns whether any of the location constraints refer to the custom list named `name`.
+    #[cfg(not(target_os = "android"))]
+    pub fn references_custom_list(&self, name: &str) -> bool {
+        let is_list = |location: &LocationConstraint| matches!(location, LocationConstraint::CustomList(list) if list == name);
+        let is_only_list = |constraint: &Constraint<LocationConstraint>| matches!(constraint, Constraint::Only(location) if is_list(location));
+        is_only_list(&self.location)
+            || self.location_set.include.iter().any(is_list)
+            || self.location_set.exclude.iter().any(is_list)
+            || is_only_list(&self.wireguard_constraints.entry_location)
+    }
 }
 
 impl fmt::Display for RelayConstraints {
@@ -247,18 +336,32 @@ impl fmt::Display for RelayConstraints {
         }
         write!(f, " in ")?;
         match self.location {
-            Constraint::Any => write!(f, "any location")?,
-            Constraint::Only(ref location_constraint) => location_constraint.fmt(f)?,
+            Constraint::Any if !self.location_set.include.is_empty() => self.location_set.fmt(f)?,
+            Constraint::Any => {
+                write!(f, "any location")?;
+                if !self.location_set.is_any() {
+                    write!(f, " limited to {}", self.location_set)?;
+                }
+            }
+            Constraint::Only(ref location_constraint) => {
+                location_constraint.fmt(f)?;
+                if !self.location_set.is_any() {
+                    write!(f, " limited to {}", self.location_set)?;
+                }
+            }
         }
         write!(f, " using ")?;
         match self.providers {
             Constraint::Any => write!(f, "any provider")?,
             Constraint::Only(ref constraint) => constraint.fmt(f)?,
         }
-        match self.ownership {
+        if let Constraint::Only(ref constraint) = self.ownership {
+            write!(f, " and {}", constraint)?;
+        }
+        match self.datacenter {
             Constraint::Any => Ok(()),
             Constraint::Only(ref constraint) => {
-                write!(f, " and {}", constraint)
+                write!(f, " in {}", constraint)
             }
         }
     }
@@ -275,8 +378,13 @@ pub enum LocationConstraint {
     Country(CountryCode),
     /// A city is composed of a country code and a city code.
     City(CountryCode, CityCode),
-    /// An single hostname in a given city.
+    /// An single hostname in a given city. The hostname may contain `*`, which matches any
+    /// sequence of characters, such as in `se-got-wg-*`.
     Hostname(CountryCode, CityCode, Hostname),
+    /// The locations in the [`crate::custom_list::CustomList`] with the given name. It must be
+    /// resolved into those locations before it can match any relay.
+    #[cfg(not(target_os = "android"))]
+    CustomList(String),
 }
 
 impl Match<Relay> for LocationConstraint {
@@ -298,9 +406,11 @@ impl Match<Relay> for LocationConstraint {
                 relay.location.as_ref().map_or(false, |loc| {
                     loc.country_code == *country
                         && loc.city_code == *city
-                        && relay.hostname == *hostname
+                        && hostname_matches(hostname, &relay.hostname)
                 })
             }
+            #[cfg(not(target_os = "android"))]
+            LocationConstraint::CustomList(_) => false,
         }
     }
 }
@@ -315,15 +425,95 @@ impl Set<LocationConstraint> for LocationConstraint {
                 LocationConstraint::City(..) => self == other,
                 _ => false,
             },
-            LocationConstraint::Hostname(ref country, ref city, ref _hostname) => match other {
+            LocationConstraint::Hostname(ref country, ref city, ref hostname) => match other {
                 LocationConstraint::Country(ref other_country) => country == other_country,
                 LocationConstraint::City(ref other_country, ref other_city) => {
                     country == other_country && city == other_city
                 }
-                LocationConstraint::Hostname(..) => self == other,
+                // A pattern in `self` is treated as a literal, so it is only a subset of patterns
+                // that match it, such as `se-got-*` for `se-got-wg-*`
+                LocationConstraint::Hostname(
+                    ref other_country,
+                    ref other_city,
+                    ref other_hostname,
+                ) => {
+                    country == other_country
+                        && city == other_city
+                        && hostname_matches(other_hostname, hostname)
+                }
+                #[cfg(not(target_os = "android"))]
+                LocationConstraint::CustomList(_) => false,
             },
+            #[cfg(not(target_os = "android"))]
+            LocationConstraint::CustomList(_) => self == other,
+        }
+    }
+}
+
+/// Returns whether `hostname` matches `pattern`, in which `*` matches any sequence of characters,
+/// including none.
+pub fn hostname_matches(pattern: &str, hostname: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == hostname;
+    }
+    let pattern = pattern.as_bytes();
+    let hostname = hostname.as_bytes();
+    let (mut p, mut h) = (0, 0);
+    // Where the last `*` was seen, and where in the hostname its match currently ends
+    let mut last_star: Option<(usize, usize)> = None;
+    while h < hostname.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            last_star = Some((p, h));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == hostname[h] {
+            p += 1;
+            h += 1;
+        } else if let Some((star, star_end)) = last_star {
+            // Let the last `*` match one more character and try again
+            p = star + 1;
+            h = star_end + 1;
+            last_star = Some((star, star_end + 1));
+        } else {
+            return false;
         }
     }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// How a relay is chosen among the relays that match the constraints.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// Every matching relay is equally likely to be chosen.
+    Random,
+    /// Relays are chosen at random, in proportion to their `weight`.
+    Weighted,
+    /// The relay that has been fastest to connect to is chosen. Relays that have not been
+    /// connected to are only chosen, by weight, if that is the case for all matching relays.
+    LowestLatency,
+    /// The matching relays are chosen in turn, ordered by hostname.
+    RoundRobin,
+}
+
+impl Default for SelectionStrategy {
+    fn default() -> Self {
+        SelectionStrategy::Weighted
+    }
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SelectionStrategy::Random => "random",
+                SelectionStrategy::Weighted => "weighted",
+                SelectionStrategy::LowestLatency => "lowest latency",
+                SelectionStrategy::RoundRobin => "round-robin",
+            }
+        )
+    }
 }
 
 /// Limits the set of servers to choose based on ownership.
@@ -401,6 +591,27 @@ impl fmt::Display for Providers {
     }
 }
 
+/// Limits the set of [`crate::relay_list::Relay`]s used by a `RelaySelector` to those hosted in
+/// a single datacenter.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Datacenter(pub String);
+
+impl Match<Relay> for Datacenter {
+    fn matches(&self, relay: &Relay) -> bool {
+        relay
+            .datacenter
+            .as_ref()
+            .map_or(false, |datacenter| datacenter.eq_ignore_ascii_case(&self.0))
+    }
+}
+
+impl fmt::Display for Datacenter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "datacenter {}", self.0)
+    }
+}
+
 impl fmt::Display for LocationConstraint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
@@ -409,6 +620,8 @@ impl fmt::Display for LocationConstraint {
             LocationConstraint::Hostname(country, city, hostname) => {
                 write!(f, "city {}, {}, hostname {}", city, country, hostname)
             }
+            #[cfg(not(target_os = "android"))]
+            LocationConstraint::CustomList(name) => write!(f, "custom list {}", name),
         }
     }
 }
@@ -503,6 +716,8 @@ pub enum SelectedObfuscation {
     Auto,
     Off,
     Udp2Tcp,
+    Wss,
+    External,
 }
 
 impl Default for SelectedObfuscation {
@@ -517,6 +732,8 @@ impl fmt::Display for SelectedObfuscation {
             SelectedObfuscation::Auto => "auto".fmt(f),
             SelectedObfuscation::Off => "off".fmt(f),
             SelectedObfuscation::Udp2Tcp => "udp2tcp".fmt(f),
+            SelectedObfuscation::Wss => "wss".fmt(f),
+            SelectedObfuscation::External => "external".fmt(f),
         }
     }
 }
@@ -536,6 +753,46 @@ impl fmt::Display for Udp2TcpObfuscationSettings {
     }
 }
 
+/// Settings for sending WireGuard traffic through a WebSocket over TLS.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WssObfuscationSettings {
+    pub port: Constraint<u16>,
+}
+
+impl fmt::Display for WssObfuscationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Constraint::Any => write!(f, "any port"),
+            Constraint::Only(port) => write!(f, "port {}", port),
+        }
+    }
+}
+
+/// Settings for sending WireGuard traffic through a pluggable transport supplied by the user.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(default)]
+pub struct ExternalObfuscationSettings {
+    pub transport: Option<ExternalTransportConfig>,
+    /// Server that the transport connects to. If unset, the TCP obfuscation endpoint of the
+    /// selected relay is used.
+    pub bridge: Option<SocketAddr>,
+}
+
+impl fmt::Display for ExternalObfuscationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.transport {
+            Some(transport) => write!(f, "{}", transport)?,
+            None => write!(f, "no transport configured")?,
+        }
+        if let Some(bridge) = self.bridge {
+            write!(f, " via {}", bridge)?;
+        }
+        Ok(())
+    }
+}
+
 /// Contains obfuscation settings
 #[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -543,6 +800,8 @@ impl fmt::Display for Udp2TcpObfuscationSettings {
 pub struct ObfuscationSettings {
     pub selected_obfuscation: SelectedObfuscation,
     pub udp2tcp: Udp2TcpObfuscationSettings,
+    pub wss: WssObfuscationSettings,
+    pub external: ExternalObfuscationSettings,
 }
 
 /// Limits the set of bridge servers to use in `mullvad-daemon`.
@@ -553,6 +812,10 @@ pub struct BridgeConstraints {
     pub location: Constraint<LocationConstraint>,
     pub providers: Constraint<Providers>,
     pub ownership: Constraint<Ownership>,
+    /// Port of the Shadowsocks server on the bridge.
+    pub port: Constraint<u16>,
+    /// Cipher used by the Shadowsocks server on the bridge.
+    pub cipher: Constraint<String>,
 }
 
 impl fmt::Display for BridgeConstraints {
@@ -566,12 +829,16 @@ impl fmt::Display for BridgeConstraints {
             Constraint::Any => write!(f, "any provider")?,
             Constraint::Only(ref constraint) => constraint.fmt(f)?,
         }
-        match self.ownership {
-            Constraint::Any => Ok(()),
-            Constraint::Only(ref constraint) => {
-                write!(f, " and {}", constraint)
-            }
+        if let Constraint::Only(ref constraint) = self.ownership {
+            write!(f, " and {}", constraint)?;
+        }
+        if let Constraint::Only(port) = self.port {
+            write!(f, " on port {}", port)?;
+        }
+        if let Constraint::Only(ref cipher) = self.cipher {
+            write!(f, " with cipher {}", cipher)?;
         }
+        Ok(())
     }
 }
 
@@ -604,6 +871,8 @@ pub struct InternalBridgeConstraints {
     pub providers: Constraint<Providers>,
     pub ownership: Constraint<Ownership>,
     pub transport_protocol: Constraint<TransportProtocol>,
+    pub port: Constraint<u16>,
+    pub cipher: Constraint<String>,
 }
 
 /// Used to update the [`RelaySettings`] used in `mullvad-daemon`.
@@ -638,6 +907,29 @@ impl RelaySettingsUpdate {
     }
 }
 
+impl From<RelaySettings> for RelaySettingsUpdate {
+    /// Returns an update that replaces all relay settings with `settings`.
+    fn from(settings: RelaySettings) -> Self {
+        match settings {
+            RelaySettings::CustomTunnelEndpoint(endpoint) => {
+                RelaySettingsUpdate::CustomTunnelEndpoint(endpoint)
+            }
+            RelaySettings::Normal(constraints) => {
+                RelaySettingsUpdate::Normal(RelayConstraintsUpdate {
+                    location: Some(constraints.location),
+                    location_set: Some(constraints.location_set),
+                    providers: Some(constraints.providers),
+                    ownership: Some(constraints.ownership),
+                    datacenter: Some(constraints.datacenter),
+                    tunnel_protocol: Some(constraints.tunnel_protocol),
+                    wireguard_constraints: Some(constraints.wireguard_constraints),
+                    openvpn_constraints: Some(constraints.openvpn_constraints),
+                })
+            }
+        }
+    }
+}
+
 /// Used in [`RelaySettings`] to change relay constraints in the daemon.
 #[derive(Debug, Default, Deserialize, Serialize)]
 #[cfg_attr(target_os = "android", derive(FromJava))]
@@ -646,13 +938,332 @@ impl RelaySettingsUpdate {
 pub struct RelayConstraintsUpdate {
     pub location: Option<Constraint<LocationConstraint>>,
     #[cfg_attr(target_os = "android", jnix(default))]
+    pub location_set: Option<ConstraintSet<LocationConstraint>>,
+    #[cfg_attr(target_os = "android", jnix(default))]
     pub providers: Option<Constraint<Providers>>,
     #[cfg_attr(target_os = "android", jnix(default))]
     pub ownership: Option<Constraint<Ownership>>,
     #[cfg_attr(target_os = "android", jnix(default))]
+    pub datacenter: Option<Constraint<Datacenter>>,
+    #[cfg_attr(target_os = "android", jnix(default))]
     pub tunnel_protocol: Option<Constraint<TunnelType>>,
     #[cfg_attr(target_os = "android", jnix(default))]
     pub wireguard_constraints: Option<WireguardConstraints>,
     #[cfg_attr(target_os = "android", jnix(default))]
     pub openvpn_constraints: Option<OpenVpnConstraints>,
 }
+
+impl RelayConstraintsUpdate {
+    /// Returns a builder for an update that changes nothing until its setters are called.
+    pub fn builder() -> RelayConstraintsUpdateBuilder {
+        RelayConstraintsUpdateBuilder::default()
+    }
+}
+
+/// Returned by [`RelayConstraintsUpdateBuilder::build`] if the update is invalid.
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum RelayConstraintsUpdateError {
+    #[error(display = "At least one provider must be given")]
+    NoProviders,
+
+    #[error(display = "The datacenter name must not be empty")]
+    EmptyDatacenter,
+
+    #[error(display = "Port 0 is not a valid port")]
+    InvalidPort,
+
+    #[error(
+        display = "Constraints for {} were given, but the tunnel protocol is limited to {}",
+        _0,
+        _1
+    )]
+    ConflictingTunnelProtocol(TunnelType, TunnelType),
+
+    #[error(display = "The entry location can only be set when multihop is used")]
+    EntryLocationWithoutMultihop,
+}
+
+/// Builds a [`RelayConstraintsUpdate`] one constraint at a time. Constraints that are not set are
+/// left unchanged by the update. The WireGuard and OpenVPN constraints are each replaced as a
+/// whole, so the setters for them start from the defaults unless the current constraints are
+/// given with [`Self::wireguard_constraints`] or [`Self::openvpn_constraints`] first.
+#[derive(Debug, Default)]
+pub struct RelayConstraintsUpdateBuilder {
+    location: Option<Constraint<LocationConstraint>>,
+    location_set: Option<ConstraintSet<LocationConstraint>>,
+    providers: Option<Constraint<Vec<Provider>>>,
+    ownership: Option<Constraint<Ownership>>,
+    datacenter: Option<Constraint<Datacenter>>,
+    tunnel_protocol: Option<Constraint<TunnelType>>,
+    wireguard_constraints: Option<WireguardConstraints>,
+    openvpn_constraints: Option<OpenVpnConstraints>,
+}
+
+impl RelayConstraintsUpdateBuilder {
+    pub fn location(mut self, location: Constraint<LocationConstraint>) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    pub fn location_set(mut self, location_set: ConstraintSet<LocationConstraint>) -> Self {
+        self.location_set = Some(location_set);
+        self
+    }
+
+    pub fn providers(mut self, providers: Constraint<Vec<Provider>>) -> Self {
+        self.providers = Some(providers);
+        self
+    }
+
+    pub fn ownership(mut self, ownership: Constraint<Ownership>) -> Self {
+        self.ownership = Some(ownership);
+        self
+    }
+
+    pub fn datacenter(mut self, datacenter: Constraint<Datacenter>) -> Self {
+        self.datacenter = Some(datacenter);
+        self
+    }
+
+    pub fn tunnel_protocol(mut self, tunnel_protocol: Constraint<TunnelType>) -> Self {
+        self.tunnel_protocol = Some(tunnel_protocol);
+        self
+    }
+
+    pub fn wireguard_constraints(mut self, constraints: WireguardConstraints) -> Self {
+        self.wireguard_constraints = Some(constraints);
+        self
+    }
+
+    pub fn wireguard_port(mut self, port: Constraint<u16>) -> Self {
+        self.wireguard_constraints
+            .get_or_insert_with(Default::default)
+            .port = port;
+        self
+    }
+
+    pub fn wireguard_ip_version(mut self, ip_version: Constraint<IpVersion>) -> Self {
+        self.wireguard_constraints
+            .get_or_insert_with(Default::default)
+            .ip_version = ip_version;
+        self
+    }
+
+    /// Enables multihop with the given entry location, or disables it if `entry_location` is
+    /// `None`.
+    pub fn multihop(mut self, entry_location: Option<Constraint<LocationConstraint>>) -> Self {
+        let constraints = self
+            .wireguard_constraints
+            .get_or_insert_with(Default::default);
+        constraints.use_multihop = entry_location.is_some();
+        constraints.entry_location = entry_location.unwrap_or(Constraint::Any);
+        self
+    }
+
+    pub fn openvpn_constraints(mut self, constraints: OpenVpnConstraints) -> Self {
+        self.openvpn_constraints = Some(constraints);
+        self
+    }
+
+    /// Sets the OpenVPN transport protocol and port. `port` is ignored if `protocol` is
+    /// `Constraint::Any`, since a port can only be selected together with a protocol.
+    pub fn openvpn_port(
+        mut self,
+        protocol: Constraint<TransportProtocol>,
+        port: Constraint<u16>,
+    ) -> Self {
+        self.openvpn_constraints = Some(OpenVpnConstraints {
+            port: match protocol {
+                Constraint::Any => Constraint::Any,
+                Constraint::Only(protocol) => Constraint::Only(TransportPort { protocol, port }),
+            },
+        });
+        self
+    }
+
+    /// Checks that the constraints are consistent and returns the update.
+    pub fn build(self) -> Result<RelayConstraintsUpdate, RelayConstraintsUpdateError> {
+        let providers = match self.providers {
+            Some(Constraint::Only(providers)) => Some(Constraint::Only(
+                Providers::new(providers.into_iter())
+                    .map_err(|_| RelayConstraintsUpdateError::NoProviders)?,
+            )),
+            Some(Constraint::Any) => Some(Constraint::Any),
+            None => None,
+        };
+
+        if let Some(Constraint::Only(Datacenter(name))) = &self.datacenter {
+            if name.trim().is_empty() {
+                return Err(RelayConstraintsUpdateError::EmptyDatacenter);
+            }
+        }
+
+        if let Some(wireguard) = &self.wireguard_constraints {
+            if wireguard.port == Constraint::Only(0) {
+                return Err(RelayConstraintsUpdateError::InvalidPort);
+            }
+            if !wireguard.use_multihop && wireguard.entry_location.is_only() {
+                return Err(RelayConstraintsUpdateError::EntryLocationWithoutMultihop);
+            }
+        }
+        if let Some(OpenVpnConstraints {
+            port: Constraint::Only(transport_port),
+        }) = &self.openvpn_constraints
+        {
+            if transport_port.port == Constraint::Only(0) {
+                return Err(RelayConstraintsUpdateError::InvalidPort);
+            }
+        }
+
+        if let Some(Constraint::Only(tunnel_protocol)) = self.tunnel_protocol {
+            let conflict = match tunnel_protocol {
+                TunnelType::Wireguard => self
+                    .openvpn_constraints
+                    .map(|constraints| constraints.port.is_only())
+                    .unwrap_or(false)
+                    .then(|| TunnelType::OpenVpn),
+                TunnelType::OpenVpn => self
+                    .wireguard_constraints
+                    .as_ref()
+                    .map(|constraints| *constraints != WireguardConstraints::default())
+                    .unwrap_or(false)
+                    .then(|| TunnelType::Wireguard),
+            };
+            if let Some(conflict) = conflict {
+                return Err(RelayConstraintsUpdateError::ConflictingTunnelProtocol(
+                    conflict,
+                    tunnel_protocol,
+                ));
+            }
+        }
+
+        Ok(RelayConstraintsUpdate {
+            location: self.location,
+            location_set: self.location_set,
+            providers,
+            ownership: self.ownership,
+            datacenter: self.datacenter,
+            tunnel_protocol: self.tunnel_protocol,
+            wireguard_constraints: self.wireguard_constraints,
+            openvpn_constraints: self.openvpn_constraints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hostname_matches() {
+        assert!(hostname_matches("se-got-wg-001", "se-got-wg-001"));
+        assert!(!hostname_matches("se-got-wg-001", "se-got-wg-002"));
+        assert!(hostname_matches("se-got-wg-*", "se-got-wg-001"));
+        assert!(hostname_matches("se-got-wg-*", "se-got-wg-"));
+        assert!(!hostname_matches("se-got-wg-*", "se-got-ovpn-001"));
+        assert!(hostname_matches("*-wg-*", "se-got-wg-001"));
+        assert!(hostname_matches("se-*-wg-0*1", "se-got-wg-0101"));
+        assert!(!hostname_matches("se-*-wg-0*1", "se-got-wg-0102"));
+        assert!(hostname_matches("*", "se-got-wg-001"));
+
+        let pattern = |hostname: &str| {
+            LocationConstraint::Hostname("se".to_owned(), "got".to_owned(), hostname.to_owned())
+        };
+        assert!(pattern("se-got-wg-001").is_subset(&pattern("se-got-wg-*")));
+        assert!(pattern("se-got-wg-*").is_subset(&pattern("se-got-*")));
+        assert!(!pattern("se-got-*").is_subset(&pattern("se-got-wg-*")));
+    }
+
+    #[test]
+    fn test_constraint_set() {
+        struct DivisibleBy(u32);
+
+        impl Match<u32> for DivisibleBy {
+            fn matches(&self, value: &u32) -> bool {
+                value % self.0 == 0
+            }
+        }
+
+        let set = ConstraintSet {
+            include: vec![DivisibleBy(2), DivisibleBy(3)],
+            exclude: vec![DivisibleBy(4)],
+        };
+        assert!(set.matches(&2));
+        assert!(set.matches(&9));
+        assert!(!set.matches(&8));
+        assert!(!set.matches(&5));
+
+        let set = ConstraintSet {
+            include: vec![],
+            exclude: vec![DivisibleBy(4)],
+        };
+        assert!(set.matches(&5));
+        assert!(!set.matches(&8));
+
+        assert!(ConstraintSet::<DivisibleBy>::any().matches(&5));
+    }
+
+    #[test]
+    fn test_builder() {
+        let update = RelayConstraintsUpdate::builder()
+            .location(Constraint::Only(LocationConstraint::Country(
+                "se".to_owned(),
+            )))
+            .providers(Constraint::Only(vec!["31173".to_owned()]))
+            .tunnel_protocol(Constraint::Only(TunnelType::Wireguard))
+            .wireguard_port(Constraint::Only(51820))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            update.location,
+            Some(Constraint::Only(LocationConstraint::Country(
+                "se".to_owned()
+            )))
+        );
+        assert!(update.providers.unwrap().is_only());
+        assert_eq!(
+            update.wireguard_constraints.unwrap().port,
+            Constraint::Only(51820)
+        );
+        assert_eq!(update.ownership, None);
+        assert_eq!(update.openvpn_constraints, None);
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        assert_eq!(
+            RelayConstraintsUpdate::builder()
+                .providers(Constraint::Only(vec![]))
+                .build()
+                .unwrap_err(),
+            RelayConstraintsUpdateError::NoProviders
+        );
+        assert_eq!(
+            RelayConstraintsUpdate::builder()
+                .wireguard_port(Constraint::Only(0))
+                .build()
+                .unwrap_err(),
+            RelayConstraintsUpdateError::InvalidPort
+        );
+        assert_eq!(
+            RelayConstraintsUpdate::builder()
+                .tunnel_protocol(Constraint::Only(TunnelType::Wireguard))
+                .openvpn_port(
+                    Constraint::Only(TransportProtocol::Tcp),
+                    Constraint::Only(443)
+                )
+                .build()
+                .unwrap_err(),
+            RelayConstraintsUpdateError::ConflictingTunnelProtocol(
+                TunnelType::OpenVpn,
+                TunnelType::Wireguard
+            )
+        );
+        assert!(RelayConstraintsUpdate::builder()
+            .tunnel_protocol(Constraint::Only(TunnelType::OpenVpn))
+            .multihop(Some(Constraint::Any))
+            .build()
+            .is_err());
+    }
+}
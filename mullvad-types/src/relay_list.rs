@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, SystemTime},
 };
 use talpid_types::net::{
     openvpn::{ProxySettings, ShadowsocksProxySettings},
@@ -32,6 +33,21 @@ impl RelayList {
             countries: Vec::new(),
         }
     }
+
+    /// Looks up a relay by hostname, along with the country and city that it is listed under.
+    pub fn find_relay(
+        &self,
+        hostname: &str,
+    ) -> Option<(&RelayListCountry, &RelayListCity, &Relay)> {
+        self.countries.iter().find_map(|country| {
+            country.cities.iter().find_map(|city| {
+                city.relays
+                    .iter()
+                    .find(|relay| relay.hostname == hostname)
+                    .map(|relay| (country, city, relay))
+            })
+        })
+    }
 }
 
 /// A list of [`RelayListCity`]s within a country. Used by [`RelayList`].
@@ -76,6 +92,14 @@ pub struct Relay {
     pub owned: bool,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub provider: String,
+    /// Name of the hosting provider suitable for showing to users. Falls back on `provider`.
+    #[serde(default)]
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub provider_name: Option<String>,
+    /// Identifier of the datacenter that the relay is hosted in.
+    #[serde(default)]
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub datacenter: Option<String>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub weight: u64,
     #[serde(skip_serializing_if = "RelayTunnels::is_empty", default)]
@@ -90,6 +114,68 @@ pub struct Relay {
     pub location: Option<Location>,
 }
 
+impl Relay {
+    /// Returns the name of the hosting provider to show to users.
+    pub fn provider_label(&self) -> &str {
+        self.provider_name.as_deref().unwrap_or(&self.provider)
+    }
+}
+
+/// Addresses to connect to a relay on instead of the ones in the relay list, for networks where
+/// the relay is only reachable through other addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayOverride {
+    /// Hostname of the relay, such as `se-got-wg-001`.
+    pub hostname: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv4_addr_in: Option<Ipv4Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6_addr_in: Option<Ipv6Addr>,
+}
+
+impl RelayOverride {
+    /// Returns whether the override does not replace any address.
+    pub fn is_empty(&self) -> bool {
+        self.ipv4_addr_in.is_none() && self.ipv6_addr_in.is_none()
+    }
+
+    /// Returns whether the override applies to `relay`. Hostnames are compared without regard
+    /// to case.
+    pub fn matches(&self, relay: &Relay) -> bool {
+        self.hostname.eq_ignore_ascii_case(&relay.hostname)
+    }
+
+    /// Replaces the addresses of `relay` with the ones of the override.
+    pub fn apply_to_relay(&self, relay: &mut Relay) {
+        if let Some(ipv4_addr_in) = self.ipv4_addr_in {
+            relay.ipv4_addr_in = ipv4_addr_in;
+        }
+        if let Some(ipv6_addr_in) = self.ipv6_addr_in {
+            relay.ipv6_addr_in = Some(ipv6_addr_in);
+        }
+    }
+}
+
+/// Returns `current` with `imported` merged into it. An imported override replaces any existing
+/// one for the same relay, and one without addresses removes it.
+pub fn merge_relay_overrides(
+    current: &[RelayOverride],
+    imported: Vec<RelayOverride>,
+) -> Vec<RelayOverride> {
+    let mut overrides = current.to_vec();
+    for new_override in imported {
+        overrides.retain(|existing| {
+            !existing
+                .hostname
+                .eq_ignore_ascii_case(&new_override.hostname)
+        });
+        if !new_override.is_empty() {
+            overrides.push(new_override);
+        }
+    }
+    overrides
+}
+
 /// Provides protocol-specific information about a [`Relay`].
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 #[serde(default)]
@@ -203,15 +289,17 @@ impl ShadowsocksEndpointData {
 #[serde(default)]
 pub struct RelayObfuscators {
     pub udp2tcp: Vec<Udp2TcpEndpointData>,
+    pub wss: Vec<WssEndpointData>,
 }
 
 impl RelayObfuscators {
     pub fn is_empty(&self) -> bool {
-        self.udp2tcp.is_empty()
+        self.udp2tcp.is_empty() && self.wss.is_empty()
     }
 
     pub fn clear(&mut self) {
         self.udp2tcp.clear();
+        self.wss.clear();
     }
 }
 
@@ -219,3 +307,306 @@ impl RelayObfuscators {
 pub struct Udp2TcpEndpointData {
     pub port: u16,
 }
+
+/// A WebSocket over TLS endpoint on a relay.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct WssEndpointData {
+    pub port: u16,
+    /// Name that the relay expects in the TLS server name indication.
+    pub server_name: String,
+}
+
+/// Describes how a relay that the tunnel exits through differs in a newer relay list.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitRelayChange {
+    /// The relay is now listed under a different location.
+    Moved { country: String, city: String },
+    /// The relay is no longer considered to be part of its country.
+    ExcludedFromCountry,
+    /// The relay has been deactivated.
+    Deactivated,
+    /// The relay is no longer in the relay list.
+    Removed,
+}
+
+impl ExitRelayChange {
+    /// Compares `relay`, as it was when it was selected, to its entry in `relay_list`.
+    pub fn detect(relay: &Relay, relay_list: &RelayList) -> Option<Self> {
+        let (country, city, new_relay) = match relay_list.find_relay(&relay.hostname) {
+            Some(entry) => entry,
+            None => return Some(ExitRelayChange::Removed),
+        };
+        let moved = relay
+            .location
+            .as_ref()
+            .map(|location| {
+                location.country_code != country.code || location.city_code != city.code
+            })
+            .unwrap_or(false);
+        if moved {
+            Some(ExitRelayChange::Moved {
+                country: country.name.clone(),
+                city: city.name.clone(),
+            })
+        } else if relay.include_in_country && !new_relay.include_in_country {
+            Some(ExitRelayChange::ExcludedFromCountry)
+        } else if !new_relay.active {
+            Some(ExitRelayChange::Deactivated)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ExitRelayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitRelayChange::Moved { country, city } => write!(f, "moved to {}, {}", city, country),
+            ExitRelayChange::ExcludedFromCountry => {
+                "no longer counted as part of its country".fmt(f)
+            }
+            ExitRelayChange::Deactivated => "deactivated".fmt(f),
+            ExitRelayChange::Removed => "removed from the relay list".fmt(f),
+        }
+    }
+}
+
+/// Sent when a relay list update shows that the relay that the tunnel exits through has changed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExitRelayChangeEvent {
+    pub hostname: String,
+    pub change: ExitRelayChange,
+    /// Whether the daemon reconnects the tunnel in response to the change.
+    pub reconnecting: bool,
+}
+
+pub const MIN_RELAY_LIST_UPDATE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+pub const MAX_RELAY_LIST_UPDATE_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+pub const DEFAULT_RELAY_LIST_UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub enum RelayListUpdateIntervalError {
+    TooSmall,
+    TooLarge,
+}
+
+impl fmt::Display for RelayListUpdateIntervalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RelayListUpdateIntervalError::TooSmall => write!(
+                f,
+                "The relay list update interval must be at least {} minutes",
+                MIN_RELAY_LIST_UPDATE_INTERVAL.as_secs() / 60
+            ),
+            RelayListUpdateIntervalError::TooLarge => write!(
+                f,
+                "The relay list update interval must be at most {} days",
+                MAX_RELAY_LIST_UPDATE_INTERVAL.as_secs() / 60 / 60 / 24
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RelayListUpdateIntervalError {}
+
+/// How old the relay list may get before the daemon downloads a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "Duration", into = "Duration")]
+pub struct RelayListUpdateInterval(Duration);
+
+impl RelayListUpdateInterval {
+    pub fn new(interval: Duration) -> Result<Self, RelayListUpdateIntervalError> {
+        if interval < MIN_RELAY_LIST_UPDATE_INTERVAL {
+            Err(RelayListUpdateIntervalError::TooSmall)
+        } else if interval > MAX_RELAY_LIST_UPDATE_INTERVAL {
+            Err(RelayListUpdateIntervalError::TooLarge)
+        } else {
+            Ok(RelayListUpdateInterval(interval))
+        }
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl TryFrom<Duration> for RelayListUpdateInterval {
+    type Error = RelayListUpdateIntervalError;
+
+    fn try_from(duration: Duration) -> Result<Self, RelayListUpdateIntervalError> {
+        RelayListUpdateInterval::new(duration)
+    }
+}
+
+impl From<RelayListUpdateInterval> for Duration {
+    fn from(interval: RelayListUpdateInterval) -> Duration {
+        interval.0
+    }
+}
+
+impl Default for RelayListUpdateInterval {
+    fn default() -> Self {
+        RelayListUpdateInterval(DEFAULT_RELAY_LIST_UPDATE_INTERVAL)
+    }
+}
+
+/// A server that the relay list is downloaded from instead of the API, such as a self-hosted
+/// mirror. The mirror serves the relay list in the same format as the API, and an Ed25519
+/// signature of it, which is checked against `public_key` before the list is used.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayListMirror {
+    /// HTTPS URL of the relay list.
+    pub url: String,
+    /// Ed25519 key that the relay list must be signed with.
+    pub public_key: [u8; 32],
+}
+
+/// Describes how fresh the relay list that the daemon uses is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayListInfo {
+    /// When the relay list was downloaded, or when the bundled list was built if no list has been
+    /// downloaded yet.
+    pub last_updated: SystemTime,
+    /// Version of the relay list, as reported by the API.
+    pub etag: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::location::Location;
+
+    fn relay(hostname: &str, location: Option<Location>) -> Relay {
+        Relay {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: Ipv4Addr::new(10, 0, 0, 1),
+            ipv6_addr_in: None,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider".to_owned(),
+            provider_name: None,
+            datacenter: None,
+            weight: 1,
+            tunnels: RelayTunnels::default(),
+            bridges: RelayBridges::default(),
+            obfuscators: RelayObfuscators::default(),
+            location,
+        }
+    }
+
+    fn relay_list(country: &str, city: &str, relays: Vec<Relay>) -> RelayList {
+        RelayList {
+            etag: None,
+            countries: vec![RelayListCountry {
+                name: country.to_uppercase(),
+                code: country.to_owned(),
+                cities: vec![RelayListCity {
+                    name: city.to_uppercase(),
+                    code: city.to_owned(),
+                    latitude: 0.0,
+                    longitude: 0.0,
+                    relays,
+                }],
+            }],
+        }
+    }
+
+    fn selected_relay() -> Relay {
+        relay(
+            "se-got-001",
+            Some(Location {
+                country: "SE".to_owned(),
+                country_code: "se".to_owned(),
+                city: "GOT".to_owned(),
+                city_code: "got".to_owned(),
+                latitude: 0.0,
+                longitude: 0.0,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_unchanged_exit_relay() {
+        let list = relay_list("se", "got", vec![relay("se-got-001", None)]);
+        assert_eq!(ExitRelayChange::detect(&selected_relay(), &list), None);
+    }
+
+    #[test]
+    fn test_moved_exit_relay() {
+        let list = relay_list("de", "ber", vec![relay("se-got-001", None)]);
+        assert_eq!(
+            ExitRelayChange::detect(&selected_relay(), &list),
+            Some(ExitRelayChange::Moved {
+                country: "DE".to_owned(),
+                city: "BER".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_reclassified_exit_relay() {
+        let mut new_relay = relay("se-got-001", None);
+        new_relay.include_in_country = false;
+        let list = relay_list("se", "got", vec![new_relay]);
+        assert_eq!(
+            ExitRelayChange::detect(&selected_relay(), &list),
+            Some(ExitRelayChange::ExcludedFromCountry)
+        );
+    }
+
+    #[test]
+    fn test_removed_exit_relay() {
+        let list = relay_list("se", "got", vec![relay("se-got-002", None)]);
+        assert_eq!(
+            ExitRelayChange::detect(&selected_relay(), &list),
+            Some(ExitRelayChange::Removed)
+        );
+    }
+
+    #[test]
+    fn test_relay_overrides() {
+        let ipv4_override = |hostname: &str, address: [u8; 4]| RelayOverride {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: Some(Ipv4Addr::from(address)),
+            ipv6_addr_in: None,
+        };
+        let current = vec![
+            ipv4_override("se-got-001", [192, 0, 2, 1]),
+            ipv4_override("se-got-002", [192, 0, 2, 2]),
+        ];
+        let merged = merge_relay_overrides(
+            &current,
+            vec![
+                ipv4_override("SE-GOT-001", [192, 0, 2, 3]),
+                RelayOverride {
+                    hostname: "se-got-002".to_owned(),
+                    ipv4_addr_in: None,
+                    ipv6_addr_in: None,
+                },
+            ],
+        );
+        assert_eq!(merged, vec![ipv4_override("SE-GOT-001", [192, 0, 2, 3])]);
+
+        let mut relay = relay("se-got-001", None);
+        assert!(merged[0].matches(&relay));
+        merged[0].apply_to_relay(&mut relay);
+        assert_eq!(relay.ipv4_addr_in, Ipv4Addr::new(192, 0, 2, 3));
+        assert_eq!(relay.ipv6_addr_in, None);
+    }
+
+    #[test]
+    fn test_update_interval_bounds() {
+        assert!(RelayListUpdateInterval::new(MIN_RELAY_LIST_UPDATE_INTERVAL).is_ok());
+        assert!(RelayListUpdateInterval::new(MAX_RELAY_LIST_UPDATE_INTERVAL).is_ok());
+        assert!(matches!(
+            RelayListUpdateInterval::new(Duration::from_secs(60)),
+            Err(RelayListUpdateIntervalError::TooSmall)
+        ));
+        assert!(matches!(
+            RelayListUpdateInterval::new(MAX_RELAY_LIST_UPDATE_INTERVAL + Duration::from_secs(1)),
+            Err(RelayListUpdateIntervalError::TooLarge)
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Number of connection attempts to a relay before its success rate is trusted.
+pub const MIN_ATTEMPTS: u32 = 5;
+
+/// Relays that are connected to successfully less often than this are considered bad.
+pub const MIN_SUCCESS_RATE: f64 = 0.5;
+
+/// How well connections to a single relay have worked out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayStats {
+    pub hostname: String,
+    /// Number of times that connecting to the relay was attempted.
+    pub attempts: u32,
+    /// Number of attempts that resulted in a working tunnel.
+    pub successes: u32,
+    /// Sum of the time it took to establish the tunnel, over all successful attempts.
+    pub total_handshake_time: Duration,
+    /// Number of connections during which any traffic was observed.
+    pub throughput_samples: u32,
+    /// Sum of the highest throughput of each of those connections, in bytes per second.
+    pub total_throughput: u64,
+    pub last_used: DateTime<Utc>,
+}
+
+impl RelayStats {
+    pub fn new(hostname: String) -> Self {
+        RelayStats {
+            hostname,
+            attempts: 0,
+            successes: 0,
+            total_handshake_time: Duration::ZERO,
+            throughput_samples: 0,
+            total_throughput: 0,
+            last_used: Utc::now(),
+        }
+    }
+
+    /// Returns the share of attempts that succeeded, or `None` if there have been none.
+    pub fn success_rate(&self) -> Option<f64> {
+        if self.attempts == 0 {
+            return None;
+        }
+        Some(f64::from(self.successes) / f64::from(self.attempts))
+    }
+
+    pub fn average_handshake_time(&self) -> Option<Duration> {
+        if self.successes == 0 {
+            return None;
+        }
+        Some(self.total_handshake_time / self.successes)
+    }
+
+    /// Returns the average of the highest throughput of each connection, in bytes per second.
+    pub fn average_throughput(&self) -> Option<u64> {
+        if self.throughput_samples == 0 {
+            return None;
+        }
+        Some(self.total_throughput / u64::from(self.throughput_samples))
+    }
+
+    /// Returns whether connecting to the relay has failed consistently enough that it should be
+    /// avoided.
+    pub fn is_bad(&self) -> bool {
+        self.attempts >= MIN_ATTEMPTS
+            && self
+                .success_rate()
+                .map(|rate| rate < MIN_SUCCESS_RATE)
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_bad() {
+        let mut stats = RelayStats::new("se-got-wg-001".to_owned());
+        assert_eq!(stats.success_rate(), None);
+        assert!(!stats.is_bad());
+
+        stats.attempts = MIN_ATTEMPTS - 1;
+        assert!(!stats.is_bad());
+
+        stats.attempts = MIN_ATTEMPTS;
+        stats.successes = 1;
+        assert!(stats.is_bad());
+
+        stats.successes = MIN_ATTEMPTS;
+        assert!(!stats.is_bad());
+    }
+
+    #[test]
+    fn test_averages() {
+        let mut stats = RelayStats::new("se-got-wg-001".to_owned());
+        assert_eq!(stats.average_handshake_time(), None);
+        assert_eq!(stats.average_throughput(), None);
+
+        stats.attempts = 3;
+        stats.successes = 2;
+        stats.total_handshake_time = Duration::from_millis(300);
+        stats.throughput_samples = 2;
+        stats.total_throughput = 3000;
+        assert_eq!(
+            stats.average_handshake_time(),
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(stats.average_throughput(), Some(1500));
+    }
+}
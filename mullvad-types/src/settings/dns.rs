@@ -2,6 +2,7 @@
 use jnix::{jni::objects::JObject, FromJava, IntoJava, JnixEnv};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+use talpid_types::net::dns::{DnsFilterRule, SplitDnsRule};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -28,6 +29,23 @@ pub struct DnsOptions {
     pub default_options: DefaultDnsOptions,
     #[cfg_attr(target_os = "android", jnix(map = "|opts| opts.addresses"))]
     pub custom_options: CustomDnsOptions,
+    /// Domains whose queries are sent to other resolvers than the ones above.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub split_rules: Vec<SplitDnsRule>,
+    /// Domains that are appended to short names, such as `intranet`, while connected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub search_domains: Vec<String>,
+    /// Block DNS over TLS, and DNS over HTTPS to well-known resolvers, unless it is sent to the
+    /// resolvers in use. Plain DNS to other resolvers is always blocked while connected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub strict_leak_prevention: bool,
+    /// Domains whose queries are answered locally, by being blocked or rewritten, while
+    /// connected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub filter_rules: Vec<DnsFilterRule>,
+    /// Record the queries in memory while connected. The log is never written to disk.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub query_log: bool,
 }
 
 #[cfg(target_os = "android")]
@@ -55,6 +73,11 @@ impl From<AndroidDnsOptions> for DnsOptions {
             custom_options: CustomDnsOptions {
                 addresses: options.addresses,
             },
+            split_rules: vec![],
+            search_domains: vec![],
+            strict_leak_prevention: false,
+            filter_rules: vec![],
+            query_log: false,
         }
     }
 }
@@ -85,5 +108,7 @@ pub struct DefaultDnsOptions {
 /// Custom DNS config
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CustomDnsOptions {
+    /// Resolvers in order of preference. While connected, the ones that do not respond are tried
+    /// after the ones that do.
     pub addresses: Vec<IpAddr>,
 }
@@ -1,17 +1,22 @@
 use crate::{
+    access_method::ApiAccessMethodSettings,
+    custom_list::CustomListsSettings,
     relay_constraints::{
         BridgeConstraints, BridgeSettings, BridgeState, Constraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelaySettings, RelaySettingsUpdate,
-        SelectedObfuscation,
+        SelectedObfuscation, SelectionStrategy,
     },
+    relay_list::{RelayListMirror, RelayListUpdateInterval, RelayOverride},
     wireguard,
 };
+use ipnetwork::IpNetwork;
 #[cfg(target_os = "android")]
-use jnix::IntoJava;
+use jnix::{FromJava, IntoJava};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(target_os = "windows")]
-use std::{collections::HashSet, path::PathBuf};
-use talpid_types::net::{self, openvpn, GenericTunnelOptions};
+use std::collections::HashSet;
+use std::{fmt, path::PathBuf, time::Duration};
+use talpid_types::net::{self, openvpn, GenericTunnelOptions, RetryPolicy};
 
 mod dns;
 
@@ -21,6 +26,9 @@ mod dns;
 /// being added to `mullvad-daemon`.
 pub const CURRENT_SETTINGS_VERSION: SettingsVersion = SettingsVersion::V6;
 
+/// How long a relay that failed to complete a handshake is deprioritized by default.
+pub const DEFAULT_RELAY_FAILURE_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 #[repr(u32)]
 pub enum SettingsVersion {
@@ -74,20 +82,102 @@ pub struct Settings {
     bridge_state: BridgeState,
     /// If the daemon should allow communication with private (LAN) networks.
     pub allow_lan: bool,
+    /// Private networks to allow when `allow_lan` is set. If `None`, all private networks are
+    /// allowed.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub allowed_lan_nets: Option<Vec<IpNetwork>>,
+    /// Ports that the firewall keeps open towards the local network, even while blocking.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub firewall_exceptions: Vec<net::FirewallException>,
+    /// Local ports that accept unsolicited inbound connections from inside the tunnel while
+    /// connected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub inbound_tunnel_ports: Vec<net::InboundTunnelPort>,
+    /// If the daemon should allow mDNS and SSDP discovery on the local network, independently of
+    /// `allow_lan`.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub allow_multicast: bool,
     /// Extra level of kill switch. When this setting is on, the disconnected state will block
     /// the firewall to not allow any traffic in or out.
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub block_when_disconnected: bool,
+    /// Block all traffic when any part of the DNS config cannot be applied while connecting,
+    /// instead of connecting with the parts that succeeded.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub strict_enforcement: bool,
     /// If the daemon should connect the VPN tunnel directly on start or not.
     pub auto_connect: bool,
+    /// Whether `auto_connect` is managed per user by the GUI, or is a machine-wide policy that
+    /// applies before any user has logged on.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub auto_connect_scope: AutoConnectScope,
+    /// What to do when a relay list update shows that the current exit relay has changed.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub exit_relay_change_policy: ExitRelayChangePolicy,
+    /// When to notify that the account is about to run out of time.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub expiry_notifications: ExpiryNotificationSettings,
+    /// How often the relay list is refreshed.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub relay_list_update_interval: RelayListUpdateInterval,
+    /// Server to download the relay list from instead of the API.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub relay_list_mirror: Option<RelayListMirror>,
+    /// Addresses to connect to relays on instead of the ones in the relay list.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub relay_overrides: Vec<RelayOverride>,
+    /// How the daemon may reach the API.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub api_access_methods: ApiAccessMethodSettings,
+    /// Named lists of locations that the relay constraints can refer to.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub custom_lists: CustomListsSettings,
+    /// Whether to change circumvention settings automatically when interference is detected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub circumvention_assistant: bool,
+    /// Whether to avoid relays that connecting to has failed consistently for, as long as other
+    /// relays match the constraints.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub avoid_bad_relays: bool,
+    /// How relays are chosen among the ones that match the constraints.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub relay_selection_strategy: SelectionStrategy,
+    /// For how long other relays are preferred over one that failed to complete a handshake.
+    /// Zero disables this.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub relay_failure_cooldown: Duration,
+    /// Scripts to run when the tunnel state changes.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub tunnel_hooks: TunnelHookSettings,
+    /// Warn instead of disconnecting or reconnecting when asked to while more than this many bytes
+    /// per second pass through the tunnel.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub disconnect_warning_threshold: Option<u64>,
+    /// How long to wait for the network to settle before reconnecting after it has changed.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub reconnect_debounce: net::ReconnectDebounce,
+    /// When to consider the host to be offline.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub offline_detection: net::OfflineDetection,
     /// Options that should be applied to tunnels of a specific type regardless of where the relays
     /// might be located.
     pub tunnel_options: TunnelOptions,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
+    /// Android apps to exclude from the tunnel.
+    pub app_exclusions: AppExclusionSettings,
     /// Split tunneling settings
     #[cfg(windows)]
     pub split_tunnel: SplitTunnelSettings,
+    /// Link layer protocols that the firewall permits on physical adapters while blocking.
+    #[cfg(windows)]
+    pub link_layer_exemptions: net::LinkLayerExemptions,
+    /// How DNS is configured on the host.
+    #[cfg(target_os = "linux")]
+    pub dns_backend: net::DnsBackend,
+    /// The firewall mark and routing table that are used to route traffic outside the tunnel.
+    #[cfg(target_os = "linux")]
+    pub policy_routing: net::PolicyRouting,
     /// Specifies settings schema version
     #[cfg_attr(target_os = "android", jnix(skip))]
     settings_version: SettingsVersion,
@@ -98,10 +188,123 @@ pub struct Settings {
 pub struct SplitTunnelSettings {
     /// Toggles split tunneling on or off
     pub enable_exclusions: bool,
-    /// List of applications to exclude from the tunnel.
+    /// List of applications to exclude from the tunnel. Besides absolute paths, an application
+    /// can be given as a path containing the wildcards `*` and `?`, or as the file name of its
+    /// executable.
     pub apps: HashSet<PathBuf>,
 }
 
+/// Apps to exclude from the tunnel on Android, identified by their package names. These are kept
+/// on every platform, so that they survive when settings are moved between devices.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(target_os = "android", derive(FromJava, IntoJava))]
+#[cfg_attr(target_os = "android", jnix(package = "net.mullvad.mullvadvpn.model"))]
+pub struct AppExclusionSettings {
+    /// Whether the apps in `excluded_packages` are excluded.
+    pub enabled: bool,
+    pub excluded_packages: Vec<String>,
+}
+
+/// Who owns the auto-connect setting.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoConnectScope {
+    /// Auto-connect follows the preferences of whichever user runs the GUI, and the GUI disconnects
+    /// the tunnel when it quits.
+    User,
+    /// Auto-connect is a machine-wide policy. The tunnel is brought up when the daemon starts,
+    /// whether or not a user has logged on, and clients leave it in place when they quit. Since
+    /// no one may be logged on to connect manually, the daemon never stops retrying a connection
+    /// made by auto-connect that fails.
+    Machine,
+}
+
+impl Default for AutoConnectScope {
+    fn default() -> Self {
+        AutoConnectScope::User
+    }
+}
+
+impl fmt::Display for AutoConnectScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AutoConnectScope::User => "user",
+                AutoConnectScope::Machine => "machine",
+            }
+        )
+    }
+}
+
+/// How the daemon responds when the relay that the tunnel exits through is moved or reclassified
+/// while connected.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitRelayChangePolicy {
+    /// Only notify clients of the change.
+    Notify,
+    /// Notify clients and reconnect, so that a relay matching the constraints is selected again.
+    Reconnect,
+}
+
+impl Default for ExitRelayChangePolicy {
+    fn default() -> Self {
+        ExitRelayChangePolicy::Notify
+    }
+}
+
+impl fmt::Display for ExitRelayChangePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ExitRelayChangePolicy::Notify => "notify",
+                ExitRelayChangePolicy::Reconnect => "reconnect",
+            }
+        )
+    }
+}
+
+/// When and how to notify that the account is about to run out of time.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExpiryNotificationSettings {
+    /// Notify once when the time left on the account drops below each of these.
+    pub thresholds: Vec<Duration>,
+    /// Absolute path to a program that is run for each notification.
+    pub script: Option<PathBuf>,
+}
+
+impl Default for ExpiryNotificationSettings {
+    fn default() -> Self {
+        ExpiryNotificationSettings {
+            thresholds: vec![
+                Duration::from_secs(7 * 24 * 60 * 60),
+                Duration::from_secs(24 * 60 * 60),
+            ],
+            script: None,
+        }
+    }
+}
+
+/// Scripts that are run when the tunnel enters a state, like the up and down scripts of OpenVPN.
+/// The daemon runs them with its own privileges, so they must be placed in a directory that only
+/// administrators can write to.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TunnelHookSettings {
+    /// Run when the tunnel has connected.
+    pub connected: Option<PathBuf>,
+    /// Run when the tunnel has disconnected.
+    pub disconnected: Option<PathBuf>,
+    /// Run when the tunnel has entered the error state.
+    pub error: Option<PathBuf>,
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -116,12 +319,40 @@ impl Default for Settings {
             },
             bridge_state: BridgeState::Auto,
             allow_lan: false,
+            allowed_lan_nets: None,
+            firewall_exceptions: vec![],
+            inbound_tunnel_ports: vec![],
+            allow_multicast: false,
             block_when_disconnected: false,
+            strict_enforcement: false,
             auto_connect: false,
+            auto_connect_scope: AutoConnectScope::default(),
+            exit_relay_change_policy: ExitRelayChangePolicy::default(),
+            expiry_notifications: ExpiryNotificationSettings::default(),
+            relay_list_update_interval: RelayListUpdateInterval::default(),
+            relay_list_mirror: None,
+            relay_overrides: vec![],
+            api_access_methods: ApiAccessMethodSettings::default(),
+            custom_lists: CustomListsSettings::default(),
+            circumvention_assistant: false,
+            avoid_bad_relays: false,
+            relay_selection_strategy: SelectionStrategy::default(),
+            relay_failure_cooldown: DEFAULT_RELAY_FAILURE_COOLDOWN,
+            tunnel_hooks: TunnelHookSettings::default(),
+            disconnect_warning_threshold: None,
+            reconnect_debounce: net::ReconnectDebounce::default(),
+            offline_detection: net::OfflineDetection::default(),
             tunnel_options: TunnelOptions::default(),
             show_beta_releases: false,
+            app_exclusions: AppExclusionSettings::default(),
             #[cfg(windows)]
             split_tunnel: SplitTunnelSettings::default(),
+            #[cfg(windows)]
+            link_layer_exemptions: net::LinkLayerExemptions::default(),
+            #[cfg(target_os = "linux")]
+            dns_backend: net::DnsBackend::default(),
+            #[cfg(target_os = "linux")]
+            policy_routing: net::PolicyRouting::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
         }
     }
@@ -132,23 +363,26 @@ impl Settings {
         self.relay_settings.clone()
     }
 
-    pub fn update_relay_settings(&mut self, update: RelaySettingsUpdate) -> bool {
+    /// Applies `update` to the relay settings. If the new relay settings cannot be used with a
+    /// bridge, a bridge state of `On` is changed to `Auto`.
+    pub fn update_relay_settings(&mut self, update: RelaySettingsUpdate) -> SettingsChanged {
         let update_supports_bridge = update.supports_bridge();
         let new_settings = self.relay_settings.merge(update);
-        if self.relay_settings != new_settings {
-            if !update_supports_bridge && BridgeState::On == self.bridge_state {
-                self.bridge_state = BridgeState::Auto;
-            }
-            log::debug!(
-                "Changing relay settings:\n\tfrom: {}\n\tto: {}",
-                self.relay_settings,
-                new_settings
-            );
-
-            self.relay_settings = new_settings;
-            true
+        if self.relay_settings == new_settings {
+            return SettingsChanged::Unchanged;
+        }
+        log::debug!(
+            "Changing relay settings:\n\tfrom: {}\n\tto: {}",
+            self.relay_settings,
+            new_settings
+        );
+        self.relay_settings = new_settings;
+
+        if !update_supports_bridge && BridgeState::On == self.bridge_state {
+            self.bridge_state = BridgeState::Auto;
+            SettingsChanged::RelaySettingsAndBridgeState
         } else {
-            false
+            SettingsChanged::RelaySettings
         }
     }
 
@@ -156,13 +390,22 @@ impl Settings {
         self.bridge_state
     }
 
-    pub fn set_bridge_state(&mut self, bridge_state: BridgeState) -> bool {
-        if self.bridge_state != bridge_state {
-            self.bridge_state = bridge_state;
-            true
-        } else {
-            false
+    /// Changes the bridge state. A bridge cannot be required while the relay settings only allow
+    /// tunnels over UDP.
+    pub fn set_bridge_state(
+        &mut self,
+        bridge_state: BridgeState,
+    ) -> Result<SettingsChanged, SettingsChangeError> {
+        if self.bridge_state == bridge_state {
+            return Ok(SettingsChanged::Unchanged);
         }
+        if bridge_state == BridgeState::On
+            && !RelaySettingsUpdate::from(self.relay_settings.clone()).supports_bridge()
+        {
+            return Err(SettingsChangeError::BridgeNotSupported);
+        }
+        self.bridge_state = bridge_state;
+        Ok(SettingsChanged::BridgeState)
     }
 
     pub fn get_settings_version(&self) -> SettingsVersion {
@@ -170,6 +413,40 @@ impl Settings {
     }
 }
 
+/// Describes what a change to the [`Settings`] affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsChanged {
+    /// The settings already had the requested values.
+    Unchanged,
+    /// The relay settings were changed.
+    RelaySettings,
+    /// The relay settings were changed, and the bridge state was changed from `On` to `Auto`,
+    /// since a bridge cannot be used with the new relay settings.
+    RelaySettingsAndBridgeState,
+    /// The bridge state was changed.
+    BridgeState,
+}
+
+impl SettingsChanged {
+    /// Returns whether anything was changed.
+    pub fn is_changed(&self) -> bool {
+        *self != SettingsChanged::Unchanged
+    }
+}
+
+/// Explains why a change to the [`Settings`] was rejected.
+#[derive(err_derive::Error, Debug, Clone, PartialEq, Eq)]
+pub enum SettingsChangeError {
+    #[error(
+        display = "A bridge cannot be required, since the relay settings only allow tunnels \
+                   over UDP"
+    )]
+    BridgeNotSupported,
+
+    #[error(display = "No bridges match the bridge constraints")]
+    NoMatchingBridge,
+}
+
 /// TunnelOptions holds configuration data that applies to all kinds of tunnels.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default)]
@@ -204,6 +481,9 @@ impl Default for TunnelOptions {
             generic: GenericTunnelOptions {
                 // Enable IPv6 be default on Android
                 enable_ipv6: cfg!(target_os = "android"),
+                retry_policy: RetryPolicy::default(),
+                #[cfg(target_os = "linux")]
+                rate_limit: None,
             },
             dns_options: DnsOptions::default(),
         }
@@ -27,6 +27,40 @@ impl fmt::Display for TargetState {
     }
 }
 
+/// Sent when auto-connecting at startup kept ending up in the error state, and the daemon has
+/// stopped retrying.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AutoConnectFailedEvent {
+    /// Number of times connecting was retried.
+    pub retries: u32,
+}
+
+/// A user-initiated change that would interrupt the tunnel.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectAction {
+    Disconnect,
+    Reconnect,
+}
+
+impl fmt::Display for DisconnectAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisconnectAction::Disconnect => "disconnect".fmt(f),
+            DisconnectAction::Reconnect => "reconnect".fmt(f),
+        }
+    }
+}
+
+/// Sent when a disconnect or reconnect was held back because the tunnel is busy transferring
+/// data. The action is carried out if it is confirmed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DisconnectWarningEvent {
+    pub action: DisconnectAction,
+    /// Bytes per second that passed through the tunnel, in both directions.
+    pub throughput: u64,
+}
+
 /// Represents the state the client tunnel is in.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
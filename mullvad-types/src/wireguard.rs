@@ -6,14 +6,25 @@ use serde::{Deserialize, Deserializer, Serialize};
 use std::{convert::TryFrom, fmt, time::Duration};
 use talpid_types::net::wireguard;
 
-pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(1 * 24 * 60 * 60);
-pub const MAX_ROTATION_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(1 * 60 * 60);
+pub const MAX_ROTATION_INTERVAL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
 pub const DEFAULT_ROTATION_INTERVAL: Duration = if cfg!(target_os = "android") {
     Duration::from_secs(4 * 24 * 60 * 60)
 } else {
     Duration::from_secs(7 * 24 * 60 * 60)
 };
 
+/// The smallest tunnel MTU that can be set. IPv6 does not work below this.
+pub const MIN_MTU: u16 = 1280;
+/// The largest tunnel MTU that can be set. Larger packets do not fit in a 1500 byte link MTU once
+/// the WireGuard and IPv6 headers have been added.
+pub const MAX_MTU: u16 = 1420;
+
+/// The shortest interval between keepalive packets that can be set, in seconds.
+pub const MIN_PERSISTENT_KEEPALIVE: u16 = 10;
+/// The longest interval between keepalive packets that can be set, in seconds.
+pub const MAX_PERSISTENT_KEEPALIVE: u16 = 60 * 60;
+
 /// Contains account specific wireguard data
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct WireguardData {
@@ -43,23 +54,88 @@ impl fmt::Display for RotationIntervalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use RotationIntervalError::*;
 
-        match *self {
-            TooSmall => write!(
-                f,
-                "Rotation interval must be at least {} hours",
-                MIN_ROTATION_INTERVAL.as_secs() / 60 / 60
-            ),
-            TooLarge => write!(
-                f,
-                "Rotation interval must be at most {} hours",
-                MAX_ROTATION_INTERVAL.as_secs() / 60 / 60
-            ),
-        }
+        let (bound, interval) = match *self {
+            TooSmall => ("at least", MIN_ROTATION_INTERVAL),
+            TooLarge => ("at most", MAX_ROTATION_INTERVAL),
+        };
+        let hours = interval.as_secs() / 60 / 60;
+        write!(
+            f,
+            "The rotation interval must be {} {} hour{}",
+            bound,
+            hours,
+            if hours == 1 { "" } else { "s" }
+        )
     }
 }
 
 impl std::error::Error for RotationIntervalError {}
 
+#[derive(Debug, Clone)]
+pub enum MtuError {
+    TooSmall,
+    TooLarge,
+}
+
+impl fmt::Display for MtuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (bound, mtu) = match *self {
+            MtuError::TooSmall => ("at least", MIN_MTU),
+            MtuError::TooLarge => ("at most", MAX_MTU),
+        };
+        write!(f, "The MTU must be {} {}", bound, mtu)
+    }
+}
+
+impl std::error::Error for MtuError {}
+
+#[derive(Debug, Clone)]
+pub enum PersistentKeepaliveError {
+    TooSmall,
+    TooLarge,
+}
+
+impl fmt::Display for PersistentKeepaliveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (bound, interval) = match *self {
+            PersistentKeepaliveError::TooSmall => ("at least", MIN_PERSISTENT_KEEPALIVE),
+            PersistentKeepaliveError::TooLarge => ("at most", MAX_PERSISTENT_KEEPALIVE),
+        };
+        write!(
+            f,
+            "The keepalive interval must be 0 or {} {} seconds",
+            bound, interval
+        )
+    }
+}
+
+impl std::error::Error for PersistentKeepaliveError {}
+
+/// Checks that `interval` is 0, which disables keepalive packets, or within the range that can be
+/// set for the tunnel.
+pub fn validate_persistent_keepalive(interval: u16) -> Result<u16, PersistentKeepaliveError> {
+    if interval == 0 {
+        Ok(interval)
+    } else if interval < MIN_PERSISTENT_KEEPALIVE {
+        Err(PersistentKeepaliveError::TooSmall)
+    } else if interval > MAX_PERSISTENT_KEEPALIVE {
+        Err(PersistentKeepaliveError::TooLarge)
+    } else {
+        Ok(interval)
+    }
+}
+
+/// Checks that `mtu` is within the range that can be set for the tunnel.
+pub fn validate_mtu(mtu: u16) -> Result<u16, MtuError> {
+    if mtu < MIN_MTU {
+        Err(MtuError::TooSmall)
+    } else if mtu > MAX_MTU {
+        Err(MtuError::TooLarge)
+    } else {
+        Ok(mtu)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct RotationInterval(Duration);
 
@@ -0,0 +1,587 @@
+//! A local DNS resolver that is used as the system resolver while split DNS rules or a DNS filter
+//! are in effect. Queries are first passed to the filter, which may answer them locally. Queries
+//! for the domains of the rules are forwarded to the resolvers of those rules, and all other
+//! queries to the resolvers that would otherwise have been used. Only UDP is supported.
+//!
+//! While the query log is enabled, the forwarder also runs without rules or a filter, so that
+//! every query can be recorded. The log is only kept in memory.
+
+use std::{
+    collections::VecDeque,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use talpid_types::net::dns::{
+    find_dns_filter_rule, find_split_dns_rule, DnsFilterAction, DnsFilterRule, DnsQueryLogEntry,
+    DnsQueryOutcome, SplitDnsRule,
+};
+use tokio::net::UdpSocket;
+
+/// Address that the forwarder listens on. Other programs commonly occupy port 53 on
+/// `127.0.0.1` on Linux and Windows, so a separate loopback address is used there. Only
+/// `127.0.0.1` is assigned to the loopback interface by default on macOS.
+#[cfg(target_os = "macos")]
+pub const FORWARDER_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+#[cfg(not(target_os = "macos"))]
+pub const FORWARDER_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 86));
+
+const DNS_PORT: u16 = 53;
+
+/// How long to wait for each upstream resolver to respond before trying the next one.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long the filter may take to decide on a query. Queries that it does not decide on in time
+/// are forwarded as if there was no filter, so that a slow filter cannot stall name resolution.
+const FILTER_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Time to live of the records in responses from the filter.
+const FILTER_ANSWER_TTL: u32 = 60;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Number of queries that the query log keeps. The oldest ones are discarded first.
+const QUERY_LOG_CAPACITY: usize = 1000;
+
+/// Largest message that is forwarded in either direction. This covers EDNS payload sizes that
+/// are used in practice.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Size of the fixed DNS message header.
+const HEADER_SIZE: usize = 12;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to bind the DNS forwarder to {}", _0)]
+    Bind(SocketAddr, #[error(source)] io::Error),
+}
+
+/// Decides how queries are answered before they are forwarded. The daemon installs a
+/// [`RuleFilter`] for the filter rules in the settings, and builds that need other filtering can
+/// install their own implementation instead.
+#[async_trait::async_trait]
+pub trait DnsFilter: Send + Sync {
+    /// Returns how to answer a query for `name` of the record type `query_type`, or `None` if
+    /// the query should be forwarded. This must return within [`FILTER_TIMEOUT`].
+    async fn filter(&self, name: &str, query_type: u16) -> Option<DnsFilterAction>;
+}
+
+/// Filter that answers the queries that match a [`DnsFilterRule`].
+pub struct RuleFilter {
+    rules: Vec<DnsFilterRule>,
+}
+
+impl RuleFilter {
+    pub fn new(rules: Vec<DnsFilterRule>) -> Self {
+        RuleFilter { rules }
+    }
+}
+
+#[async_trait::async_trait]
+impl DnsFilter for RuleFilter {
+    async fn filter(&self, name: &str, _query_type: u16) -> Option<DnsFilterAction> {
+        find_dns_filter_rule(&self.rules, name).map(|rule| rule.action.clone())
+    }
+}
+
+/// The most recent queries handled by the forwarder, with how they were answered.
+#[derive(Default)]
+pub struct DnsQueryLog {
+    inner: Mutex<QueryLogInner>,
+}
+
+#[derive(Default)]
+struct QueryLogInner {
+    entries: VecDeque<DnsQueryLogEntry>,
+    last_sequence: u64,
+}
+
+impl DnsQueryLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, name: String, query_type: u16, outcome: DnsQueryOutcome) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.len() >= QUERY_LOG_CAPACITY {
+            inner.entries.pop_front();
+        }
+        inner.last_sequence += 1;
+        let entry = DnsQueryLogEntry {
+            sequence: inner.last_sequence,
+            time: SystemTime::now(),
+            name,
+            query_type,
+            outcome,
+        };
+        inner.entries.push_back(entry);
+    }
+
+    /// Returns the entries that come after the one numbered `sequence`, oldest first.
+    pub fn entries_after(&self, sequence: u64) -> Vec<DnsQueryLogEntry> {
+        self.inner
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|entry| entry.sequence > sequence)
+            .cloned()
+            .collect()
+    }
+
+    /// Discards all entries. Sequence numbers are not reused.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+}
+
+struct Upstreams {
+    rules: Vec<SplitDnsRule>,
+    default_servers: Vec<IpAddr>,
+    filter: Option<Arc<dyn DnsFilter>>,
+    query_log: Option<Arc<DnsQueryLog>>,
+}
+
+impl Upstreams {
+    fn servers_for(&self, query: &[u8]) -> Vec<IpAddr> {
+        parse_question(query)
+            .and_then(|question| find_split_dns_rule(&self.rules, &question.name))
+            .map(|rule| rule.servers.clone())
+            .unwrap_or_else(|| self.default_servers.clone())
+    }
+}
+
+/// A running forwarder. It stops when this is dropped.
+pub struct DnsForwarder {
+    upstreams: Arc<Mutex<Upstreams>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl DnsForwarder {
+    /// Starts listening on [`FORWARDER_ADDRESS`]. This must be called from within a Tokio
+    /// runtime.
+    pub fn start(
+        rules: Vec<SplitDnsRule>,
+        default_servers: Vec<IpAddr>,
+        filter: Option<Arc<dyn DnsFilter>>,
+        query_log: Option<Arc<DnsQueryLog>>,
+    ) -> Result<Self, Error> {
+        let address = SocketAddr::new(FORWARDER_ADDRESS, DNS_PORT);
+        let socket = std::net::UdpSocket::bind(address)
+            .and_then(|socket| {
+                socket.set_nonblocking(true)?;
+                UdpSocket::from_std(socket)
+            })
+            .map_err(|error| Error::Bind(address, error))?;
+
+        let upstreams = Arc::new(Mutex::new(Upstreams {
+            rules,
+            default_servers,
+            filter,
+            query_log,
+        }));
+        let task = tokio::spawn(serve(Arc::new(socket), upstreams.clone()));
+        log::debug!("Started DNS forwarder on {}", address);
+
+        Ok(DnsForwarder { upstreams, task })
+    }
+
+    /// Replaces the rules and default resolvers. Queries that are already being forwarded are
+    /// not affected.
+    pub fn set_upstreams(&self, rules: Vec<SplitDnsRule>, default_servers: Vec<IpAddr>) {
+        let mut upstreams = self.upstreams.lock().unwrap();
+        upstreams.rules = rules;
+        upstreams.default_servers = default_servers;
+    }
+
+    /// Replaces the filter. Queries that are already being filtered are not affected.
+    pub fn set_filter(&self, filter: Option<Arc<dyn DnsFilter>>) {
+        self.upstreams.lock().unwrap().filter = filter;
+    }
+
+    /// Starts or stops recording queries in `query_log`.
+    pub fn set_query_log(&self, query_log: Option<Arc<DnsQueryLog>>) {
+        self.upstreams.lock().unwrap().query_log = query_log;
+    }
+}
+
+impl Drop for DnsForwarder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(socket: Arc<UdpSocket>, upstreams: Arc<Mutex<Upstreams>>) {
+    let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+    loop {
+        let (length, client) = match socket.recv_from(&mut buffer).await {
+            Ok(result) => result,
+            // Windows reports ICMP errors for earlier responses when receiving
+            Err(error) => {
+                log::trace!("DNS forwarder failed to receive a query: {}", error);
+                continue;
+            }
+        };
+        if !client.ip().is_loopback() {
+            log::error!("Dropping a DNS query from outside: {}", client);
+            continue;
+        }
+
+        let query = buffer[..length].to_vec();
+        let (servers, filter, query_log) = {
+            let upstreams = upstreams.lock().unwrap();
+            (
+                upstreams.servers_for(&query),
+                upstreams.filter.clone(),
+                upstreams.query_log.clone(),
+            )
+        };
+        let socket = socket.clone();
+        tokio::spawn(async move {
+            let response = handle_query(&query, &servers, filter, query_log).await;
+            if let Some(response) = response {
+                if let Err(error) = socket.send_to(&response, client).await {
+                    log::debug!("DNS forwarder failed to send a response: {}", error);
+                }
+            }
+        });
+    }
+}
+
+/// Returns the response to `query`, from the filter or from one of `servers`, and records the
+/// outcome in `query_log`.
+async fn handle_query(
+    query: &[u8],
+    servers: &[IpAddr],
+    filter: Option<Arc<dyn DnsFilter>>,
+    query_log: Option<Arc<DnsQueryLog>>,
+) -> Option<Vec<u8>> {
+    let question = parse_question(query);
+    let action = match (&filter, &question) {
+        (Some(filter), Some(question)) => apply_filter(&**filter, question).await,
+        _ => None,
+    };
+
+    let (response, outcome) = match (action, &question) {
+        (Some(action), Some(question)) => {
+            let outcome = match action {
+                DnsFilterAction::Block => DnsQueryOutcome::Blocked,
+                DnsFilterAction::Rewrite(_) => DnsQueryOutcome::Rewritten,
+            };
+            (Some(filter_response(query, question, &action)), outcome)
+        }
+        _ => match forward(query, servers).await {
+            Some((response, server)) => (Some(response), DnsQueryOutcome::Resolved(server)),
+            None => {
+                log::debug!("No DNS response from {:?}", servers);
+                (None, DnsQueryOutcome::NoResponse)
+            }
+        },
+    };
+
+    if let (Some(query_log), Some(question)) = (query_log, question) {
+        query_log.push(question.name, question.query_type, outcome);
+    }
+    response
+}
+
+/// Returns how the filter answers the query, if it decides on it in time.
+async fn apply_filter(filter: &dyn DnsFilter, question: &Question) -> Option<DnsFilterAction> {
+    match tokio::time::timeout(
+        FILTER_TIMEOUT,
+        filter.filter(&question.name, question.query_type),
+    )
+    .await
+    {
+        Ok(action) => action,
+        Err(_) => {
+            log::warn!("DNS filter timed out, so the query is forwarded");
+            None
+        }
+    }
+}
+
+/// Sends `query` to each server in turn, and returns the first response and the server that sent
+/// it.
+async fn forward(query: &[u8], servers: &[IpAddr]) -> Option<(Vec<u8>, IpAddr)> {
+    for server in servers {
+        match tokio::time::timeout(UPSTREAM_TIMEOUT, forward_to(query, *server)).await {
+            Ok(Ok(response)) => return Some((response, *server)),
+            Ok(Err(error)) => log::debug!("Failed to forward DNS query to {}: {}", server, error),
+            Err(_) => log::debug!("Timed out waiting for DNS response from {}", server),
+        }
+    }
+    None
+}
+
+async fn forward_to(query: &[u8], server: IpAddr) -> io::Result<Vec<u8>> {
+    let bind_address = match server {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(bind_address, 0)).await?;
+    socket.connect(SocketAddr::new(server, DNS_PORT)).await?;
+    socket.send(query).await?;
+
+    let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+    loop {
+        let length = socket.recv(&mut buffer).await?;
+        // Ignore responses to other queries, such as late responses from the same port
+        if length >= HEADER_SIZE && query.len() >= 2 && buffer[..2] == query[..2] {
+            buffer.truncate(length);
+            return Ok(buffer);
+        }
+    }
+}
+
+/// The first question in a DNS query.
+#[derive(Debug, PartialEq)]
+struct Question {
+    name: String,
+    query_type: u16,
+    /// Offset of the end of the question in the query.
+    end: usize,
+}
+
+/// Returns the first question in a DNS query, if it can be parsed.
+fn parse_question(message: &[u8]) -> Option<Question> {
+    let question_count = u16::from_be_bytes([*message.get(4)?, *message.get(5)?]);
+    if question_count == 0 {
+        return None;
+    }
+
+    let mut labels = vec![];
+    let mut offset = HEADER_SIZE;
+    loop {
+        let length = usize::from(*message.get(offset)?);
+        offset += 1;
+        if length == 0 {
+            break;
+        }
+        // Names in questions are not compressed, and longer labels are invalid
+        if length > 63 {
+            return None;
+        }
+        let label = message.get(offset..offset + length)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += length;
+    }
+    let query_type = u16::from_be_bytes([*message.get(offset)?, *message.get(offset + 1)?]);
+    // Skip the type and class
+    let end = offset + 4;
+    if message.len() < end {
+        return None;
+    }
+    Some(Question {
+        name: labels.join("."),
+        query_type,
+        end,
+    })
+}
+
+/// Builds the response to `query` for the action of the filter. Only the first question is
+/// answered.
+fn filter_response(query: &[u8], question: &Question, action: &DnsFilterAction) -> Vec<u8> {
+    let mut response = query[..question.end].to_vec();
+    // Set QR and keep the opcode and RD. Set RA and clear the response code.
+    response[2] = 0x80 | (query[2] & 0x79);
+    response[3] = 0x80;
+    // One question, and no authority or additional records
+    response[4..HEADER_SIZE].copy_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    let addresses = match action {
+        DnsFilterAction::Block => {
+            response[3] |= RCODE_NXDOMAIN;
+            return response;
+        }
+        DnsFilterAction::Rewrite(addresses) => addresses,
+    };
+    let mut answer_count: u16 = 0;
+    for address in addresses {
+        let (record_type, data) = match address {
+            IpAddr::V4(address) => (TYPE_A, address.octets().to_vec()),
+            IpAddr::V6(address) => (TYPE_AAAA, address.octets().to_vec()),
+        };
+        if record_type != question.query_type {
+            continue;
+        }
+        // Pointer to the name in the question
+        response.extend([0xc0, HEADER_SIZE as u8]);
+        response.extend(record_type.to_be_bytes());
+        response.extend(CLASS_IN.to_be_bytes());
+        response.extend(FILTER_ANSWER_TTL.to_be_bytes());
+        response.extend((data.len() as u16).to_be_bytes());
+        response.extend(data);
+        answer_count += 1;
+    }
+    response[6..8].copy_from_slice(&answer_count.to_be_bytes());
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn query(name: &str) -> Vec<u8> {
+        let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            message.push(label.len() as u8);
+            message.extend(label.as_bytes());
+        }
+        // Terminator, then type A and class IN
+        message.extend([0, 0, 1, 0, 1]);
+        message
+    }
+
+    #[test]
+    fn test_parse_question() {
+        let message = query("mail.corp.example");
+        assert_eq!(
+            parse_question(&message),
+            Some(Question {
+                name: "mail.corp.example".to_owned(),
+                query_type: TYPE_A,
+                end: message.len(),
+            })
+        );
+        assert_eq!(parse_question(&message[..16]), None);
+        assert_eq!(parse_question(&message[..message.len() - 1]), None);
+
+        let mut no_questions = query("corp.example");
+        no_questions[5] = 0;
+        assert_eq!(parse_question(&no_questions), None);
+    }
+
+    #[test]
+    fn test_filter_response() {
+        let message = query("ads.example");
+        let question = parse_question(&message).unwrap();
+
+        let blocked = filter_response(&message, &question, &DnsFilterAction::Block);
+        assert_eq!(blocked[..2], message[..2]);
+        assert_eq!(blocked[2] & 0x80, 0x80);
+        assert_eq!(blocked[3] & 0x0f, RCODE_NXDOMAIN);
+        assert_eq!(blocked[6..8], [0, 0]);
+        assert_eq!(blocked.len(), message.len());
+
+        let rewritten = filter_response(
+            &message,
+            &question,
+            &DnsFilterAction::Rewrite(vec![
+                "2001:db8::1".parse().unwrap(),
+                "192.168.1.1".parse().unwrap(),
+            ]),
+        );
+        assert_eq!(rewritten[3] & 0x0f, 0);
+        assert_eq!(rewritten[6..8], [0, 1]);
+        assert_eq!(rewritten[rewritten.len() - 4..], [192, 168, 1, 1]);
+    }
+
+    #[test]
+    fn test_rule_filter() {
+        let filter = RuleFilter::new(vec![DnsFilterRule {
+            domain: "*.ads.example".to_owned(),
+            action: DnsFilterAction::Block,
+        }]);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(
+            runtime.block_on(filter.filter("tracker.ads.example", TYPE_A)),
+            Some(DnsFilterAction::Block)
+        );
+        assert_eq!(runtime.block_on(filter.filter("ads.example", TYPE_A)), None);
+    }
+
+    #[test]
+    fn test_query_log_capacity() {
+        let query_log = DnsQueryLog::new();
+        for _ in 0..QUERY_LOG_CAPACITY + 10 {
+            query_log.push("mullvad.net".to_owned(), TYPE_A, DnsQueryOutcome::Blocked);
+        }
+
+        let entries = query_log.entries_after(0);
+        assert_eq!(entries.len(), QUERY_LOG_CAPACITY);
+        assert_eq!(entries[0].sequence, 11);
+        assert_eq!(
+            entries.last().unwrap().sequence,
+            QUERY_LOG_CAPACITY as u64 + 10
+        );
+        assert_eq!(
+            query_log.entries_after(QUERY_LOG_CAPACITY as u64 + 5).len(),
+            5
+        );
+
+        query_log.clear();
+        assert!(query_log.entries_after(0).is_empty());
+        query_log.push("mullvad.net".to_owned(), TYPE_A, DnsQueryOutcome::Blocked);
+        assert_eq!(
+            query_log.entries_after(0)[0].sequence,
+            QUERY_LOG_CAPACITY as u64 + 11
+        );
+    }
+
+    #[test]
+    fn test_handle_query_is_logged() {
+        let filter: Arc<dyn DnsFilter> = Arc::new(RuleFilter::new(vec![DnsFilterRule {
+            domain: "ads.example".to_owned(),
+            action: DnsFilterAction::Block,
+        }]));
+        let query_log = Arc::new(DnsQueryLog::new());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        let blocked = runtime.block_on(handle_query(
+            &query("ads.example"),
+            &[],
+            Some(filter.clone()),
+            Some(query_log.clone()),
+        ));
+        assert!(blocked.is_some());
+        let unanswered = runtime.block_on(handle_query(
+            &query("mullvad.net"),
+            &[],
+            Some(filter),
+            Some(query_log.clone()),
+        ));
+        assert!(unanswered.is_none());
+
+        let entries = query_log.entries_after(0);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "ads.example");
+        assert_eq!(entries[0].outcome, DnsQueryOutcome::Blocked);
+        assert_eq!(entries[1].name, "mullvad.net");
+        assert_eq!(entries[1].outcome, DnsQueryOutcome::NoResponse);
+    }
+
+    #[test]
+    fn test_servers_for() {
+        let corp_resolver: IpAddr = "192.168.1.1".parse().unwrap();
+        let default_resolver: IpAddr = "10.64.0.1".parse().unwrap();
+        let upstreams = Upstreams {
+            rules: vec![SplitDnsRule {
+                domain: "*.corp.example".to_owned(),
+                servers: vec![corp_resolver],
+            }],
+            default_servers: vec![default_resolver],
+            filter: None,
+            query_log: None,
+        };
+        assert_eq!(
+            upstreams.servers_for(&query("mail.corp.example")),
+            vec![corp_resolver]
+        );
+        assert_eq!(
+            upstreams.servers_for(&query("mullvad.net")),
+            vec![default_resolver]
+        );
+        assert_eq!(upstreams.servers_for(&[]), vec![default_resolver]);
+    }
+}
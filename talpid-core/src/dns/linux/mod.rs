@@ -8,10 +8,22 @@ use self::{
     systemd_resolved::SystemdResolved,
 };
 use crate::routing::RouteManagerHandle;
-use std::{env, fmt, net::IpAddr};
+use std::{env, fmt, net::IpAddr, sync::Mutex};
+use talpid_types::net::DnsBackend;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+lazy_static::lazy_static! {
+    /// The backend selected in the settings. It is global since it also decides how WireGuard
+    /// tunnels are created.
+    static ref SELECTED_BACKEND: Mutex<DnsBackend> = Mutex::new(DnsBackend::Auto);
+}
+
+/// Selects how DNS is configured. This takes effect the next time DNS is set.
+pub fn set_backend(backend: DnsBackend) {
+    *SELECTED_BACKEND.lock().unwrap() = backend;
+}
+
 /// Errors that can happen in the Linux DNS monitor
 #[derive(err_derive::Error, Debug)]
 pub enum Error {
@@ -40,6 +52,8 @@ pub struct DnsMonitor {
     route_manager: RouteManagerHandle,
     handle: tokio::runtime::Handle,
     inner: Option<DnsMonitorHolder>,
+    strict: bool,
+    search_domains: Vec<String>,
 }
 
 impl super::DnsMonitorT for DnsMonitor {
@@ -50,6 +64,8 @@ impl super::DnsMonitorT for DnsMonitor {
             route_manager,
             handle,
             inner: None,
+            strict: false,
+            search_domains: vec![],
         })
     }
 
@@ -58,7 +74,14 @@ impl super::DnsMonitorT for DnsMonitor {
         // Creating a new DNS monitor for each set, in case the system changed how it manages DNS.
         let mut inner = DnsMonitorHolder::new(&self.handle)?;
         if !servers.is_empty() {
-            inner.set(&self.handle, &self.route_manager, interface, servers)?;
+            inner.set(
+                &self.handle,
+                &self.route_manager,
+                interface,
+                servers,
+                &self.search_domains,
+                self.strict,
+            )?;
             self.inner = Some(inner);
         }
         Ok(())
@@ -70,6 +93,23 @@ impl super::DnsMonitorT for DnsMonitor {
         }
         Ok(())
     }
+
+    fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn set_search_domains(&mut self, domains: Vec<String>) {
+        self.search_domains = domains;
+    }
+}
+
+impl DnsMonitor {
+    /// Restores `/etc/resolv.conf` if a backup of it was left behind. Changes made with the other
+    /// backends are tied to the tunnel interface, and are removed along with it.
+    pub fn reset_leftover(&mut self) -> Result<()> {
+        // Nothing has been set by this instance yet, so there is nothing else to reset.
+        static_resolv_conf::restore_from_backup().map_err(Error::StaticResolvConf)
+    }
 }
 
 pub enum DnsMonitorHolder {
@@ -103,7 +143,12 @@ impl DnsMonitorHolder {
             Some("resolvconf") => DnsMonitorHolder::Resolvconf(Resolvconf::new()?),
             Some("systemd") => DnsMonitorHolder::SystemdResolved(SystemdResolved::new()?),
             Some("network-manager") => DnsMonitorHolder::NetworkManager(NetworkManager::new()?),
-            Some(_) | None => Self::with_detected_dns_manager(handle)?,
+            Some(_) | None => match *SELECTED_BACKEND.lock().unwrap() {
+                DnsBackend::Auto => Self::with_detected_dns_manager(handle)?,
+                DnsBackend::SystemdResolved { dnssec } => {
+                    DnsMonitorHolder::SystemdResolved(SystemdResolved::selected(dnssec)?)
+                }
+            },
         };
         log::debug!("Managing DNS via {}", manager);
         Ok(manager)
@@ -138,17 +183,28 @@ impl DnsMonitorHolder {
         route_manager: &RouteManagerHandle,
         interface: &str,
         servers: &[IpAddr],
+        search_domains: &[String],
+        strict: bool,
     ) -> Result<()> {
         use self::DnsMonitorHolder::*;
         match self {
-            Resolvconf(ref mut resolvconf) => resolvconf.set_dns(interface, servers)?,
+            Resolvconf(ref mut resolvconf) => {
+                resolvconf.set_dns(interface, servers, search_domains)?
+            }
             StaticResolvConf(ref mut static_resolv_conf) => {
-                static_resolv_conf.set_dns(servers.to_vec())?
+                static_resolv_conf.set_dns(servers.to_vec(), search_domains.to_vec())?
+            }
+            SystemdResolved(ref mut systemd_resolved) => {
+                handle.block_on(systemd_resolved.set_dns(
+                    route_manager.clone(),
+                    interface,
+                    servers,
+                    search_domains,
+                    strict,
+                ))?
             }
-            SystemdResolved(ref mut systemd_resolved) => handle
-                .block_on(systemd_resolved.set_dns(route_manager.clone(), interface, servers))?,
             NetworkManager(ref mut network_manager) => {
-                network_manager.set_dns(interface, servers)?
+                network_manager.set_dns(interface, servers, search_domains)?
             }
         }
         Ok(())
@@ -170,6 +226,9 @@ impl DnsMonitorHolder {
 
 /// Returns true if DnsMonitor will use NetworkManager to manage DNS.
 pub fn will_use_nm() -> bool {
+    if let DnsBackend::SystemdResolved { .. } = *SELECTED_BACKEND.lock().unwrap() {
+        return false;
+    }
     crate::dns::imp::SystemdResolved::new().is_err()
         && crate::dns::imp::NetworkManager::new().is_ok()
 }
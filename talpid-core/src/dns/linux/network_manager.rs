@@ -24,8 +24,15 @@ impl NetworkManager {
         Ok(manager)
     }
 
-    pub fn set_dns(&mut self, interface_name: &str, servers: &[IpAddr]) -> Result<()> {
-        let old_settings = self.connection.set_dns(interface_name, servers)?;
+    pub fn set_dns(
+        &mut self,
+        interface_name: &str,
+        servers: &[IpAddr],
+        search_domains: &[String],
+    ) -> Result<()> {
+        let old_settings = self
+            .connection
+            .set_dns(interface_name, servers, search_domains)?;
         self.settings_backup = Some(old_settings);
         self.device = Some(interface_name.to_string());
         Ok(())
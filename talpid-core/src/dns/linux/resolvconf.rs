@@ -75,10 +75,21 @@ impl Resolvconf {
             .unwrap_or_else(|_| false)
     }
 
-    pub fn set_dns(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()> {
+    pub fn set_dns(
+        &mut self,
+        interface: &str,
+        servers: &[IpAddr],
+        search_domains: &[String],
+    ) -> Result<()> {
         let record_name = format!("{}.mullvad", interface);
         let mut record_contents = String::new();
 
+        if !search_domains.is_empty() {
+            record_contents.push_str("search ");
+            record_contents.push_str(&search_domains.join(" "));
+            record_contents.push('\n');
+        }
+
         for address in servers {
             record_contents.push_str("nameserver ");
             record_contents.push_str(&address.to_string());
@@ -47,7 +47,7 @@ impl StaticResolvConf {
         })
     }
 
-    pub fn set_dns(&mut self, servers: Vec<IpAddr>) -> Result<()> {
+    pub fn set_dns(&mut self, servers: Vec<IpAddr>, search_domains: Vec<String>) -> Result<()> {
         let mut state = self.state.lock();
         let new_state = match state.take() {
             None => {
@@ -57,11 +57,13 @@ impl StaticResolvConf {
                 State {
                     backup,
                     desired_dns: servers,
+                    search_domains,
                 }
             }
             Some(previous_state) => State {
                 backup: previous_state.backup,
                 desired_dns: servers,
+                search_domains,
             },
         };
 
@@ -85,6 +87,7 @@ impl StaticResolvConf {
 struct State {
     backup: Config,
     desired_dns: Vec<IpAddr>,
+    search_domains: Vec<String>,
 }
 
 impl State {
@@ -97,6 +100,15 @@ impl State {
             .map(|&address| ScopedIp::from(address))
             .collect();
 
+        if !self.search_domains.is_empty() {
+            // Our domains are searched before the ones that were already configured
+            let mut search = self.search_domains.clone();
+            if let Some(existing) = self.backup.get_search() {
+                search.extend(existing.iter().cloned());
+            }
+            config.set_search(search);
+        }
+
         config
     }
 }
@@ -176,10 +188,9 @@ impl DnsWatcher {
                 .collect();
 
             if new_config.nameservers != desired_nameservers {
-                state.backup = new_config.clone();
-                new_config.nameservers = desired_nameservers;
+                state.backup = new_config;
 
-                write_config(&new_config)
+                write_config(&state.desired_config())
             } else {
                 new_config.nameservers.clear();
                 new_config.nameservers.append(&mut state.backup.nameservers);
@@ -215,7 +226,7 @@ fn write_backup(backup: &Config) -> Result<()> {
         .map_err(|e| Error::WriteResolvConf(RESOLV_CONF_BACKUP_PATH, e))
 }
 
-fn restore_from_backup() -> Result<()> {
+pub fn restore_from_backup() -> Result<()> {
     match fs::read_to_string(RESOLV_CONF_BACKUP_PATH) {
         Ok(backup) => {
             log::info!("Restoring DNS state from backup");
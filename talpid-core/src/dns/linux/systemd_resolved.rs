@@ -4,7 +4,7 @@ use crate::{
 };
 use std::net::IpAddr;
 use talpid_dbus::systemd_resolved::{AsyncHandle, SystemdResolved as DbusInterface};
-use talpid_types::ErrorExt;
+use talpid_types::{net::DnssecMode, ErrorExt};
 
 pub(crate) use talpid_dbus::systemd_resolved::Error as SystemdDbusError;
 
@@ -17,11 +17,18 @@ pub enum Error {
 
     #[error(display = "Failed to resolve interface index with error {}", _0)]
     InterfaceNameError(#[error(source)] IfaceIndexLookupError),
+
+    /// Part of the DNS config could not be set, and strict enforcement is enabled.
+    #[error(display = "{}", _0)]
+    IncompleteConfig(&'static str, #[error(source)] SystemdDbusError),
 }
 
 pub struct SystemdResolved {
     pub dbus_interface: AsyncHandle,
     tunnel_index: u32,
+    /// Whether systemd-resolved was selected in the settings, rather than detected.
+    selected: bool,
+    dnssec: Option<DnssecMode>,
 }
 
 impl SystemdResolved {
@@ -31,30 +38,71 @@ impl SystemdResolved {
         let systemd_resolved = SystemdResolved {
             dbus_interface,
             tunnel_index: 0,
+            selected: false,
+            dnssec: None,
+        };
+
+        Ok(systemd_resolved)
+    }
+
+    /// Uses systemd-resolved regardless of how `/etc/resolv.conf` is managed. The tunnel
+    /// interface is also made the default route for DNS queries.
+    pub fn selected(dnssec: Option<DnssecMode>) -> Result<Self> {
+        let dbus_interface = DbusInterface::new_ignoring_resolv_conf()?.async_handle();
+
+        let systemd_resolved = SystemdResolved {
+            dbus_interface,
+            tunnel_index: 0,
+            selected: true,
+            dnssec,
         };
 
         Ok(systemd_resolved)
     }
 
+    /// Sets the DNS servers of the tunnel interface. Parts of the config that only make leaks
+    /// less likely are skipped if they fail, unless `strict` is set.
     pub async fn set_dns(
         &mut self,
         _route_manager: RouteManagerHandle,
         interface_name: &str,
         servers: &[IpAddr],
+        search_domains: &[String],
+        strict: bool,
     ) -> Result<()> {
         let tunnel_index = iface_index(interface_name)?;
         self.tunnel_index = tunnel_index;
 
-        if let Err(error) = self.dbus_interface.disable_dot(self.tunnel_index).await {
-            log::error!("Failed to disable DoT: {}", error.display_chain());
-        }
-
-        if let Err(error) = self
-            .dbus_interface
-            .set_domains(tunnel_index, &[(".", true)])
-            .await
-        {
-            log::error!("Failed to set search domains: {}", error.display_chain());
+        check_incomplete(
+            self.dbus_interface.disable_dot(self.tunnel_index).await,
+            "Failed to disable DoT",
+            strict,
+        )?;
+
+        // Route all queries to the tunnel, and search the given domains
+        let mut domains = vec![(".", true)];
+        domains.extend(search_domains.iter().map(|domain| (domain.as_str(), false)));
+        check_incomplete(
+            self.dbus_interface
+                .set_domains(tunnel_index, &domains)
+                .await,
+            "Failed to set search domains",
+            strict,
+        )?;
+
+        if self.selected {
+            check_incomplete(
+                self.dbus_interface
+                    .set_default_route(tunnel_index, true)
+                    .await,
+                "Failed to set default DNS route",
+                strict,
+            )?;
+            if let Some(dnssec) = self.dnssec {
+                self.dbus_interface
+                    .set_dnssec(tunnel_index, dnssec.as_str())
+                    .await?;
+            }
         }
 
         let _ = self
@@ -74,6 +122,12 @@ impl SystemdResolved {
             log::error!("Failed to set search domains: {}", error.display_chain());
         }
 
+        if self.dnssec.is_some() {
+            if let Err(error) = self.dbus_interface.set_dnssec(self.tunnel_index, "").await {
+                log::error!("Failed to reset DNSSEC mode: {}", error.display_chain());
+            }
+        }
+
         let _ = self
             .dbus_interface
             .set_dns(self.tunnel_index, vec![])
@@ -82,3 +136,19 @@ impl SystemdResolved {
         Ok(())
     }
 }
+
+/// Logs a failure to set part of the DNS config, or returns it if `strict` is set.
+fn check_incomplete(
+    result: std::result::Result<(), SystemdDbusError>,
+    message: &'static str,
+    strict: bool,
+) -> Result<()> {
+    match result {
+        Err(error) if strict => Err(Error::IncompleteConfig(message, error)),
+        Err(error) => {
+            log::error!("{}: {}", message, error.display_chain());
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
@@ -18,7 +18,9 @@ use system_configuration::{
         string::CFString,
     },
     dynamic_store::{SCDynamicStore, SCDynamicStoreBuilder, SCDynamicStoreCallBackContext},
-    sys::schema_definitions::{kSCPropNetDNSServerAddresses, kSCPropNetInterfaceDeviceName},
+    sys::schema_definitions::{
+        kSCPropNetDNSSearchDomains, kSCPropNetDNSServerAddresses, kSCPropNetInterfaceDeviceName,
+    },
 };
 use talpid_time::Instant;
 use talpid_types::tunnel::ErrorStateCause;
@@ -70,6 +72,10 @@ struct State {
     dns_settings: Option<DnsSettings>,
     /// The backup of all DNS settings. These are being applied back on reset.
     backup: HashMap<ServicePath, Option<DnsSettings>>,
+    /// Whether failing to restore the DNS of a service should block the tunnel.
+    strict: bool,
+    /// Domains that are appended to short names.
+    search_domains: Vec<String>,
 }
 
 impl State {
@@ -79,6 +85,8 @@ impl State {
             dns_settings: None,
             change_counter: ChangeCounter::new(),
             backup: HashMap::new(),
+            strict: false,
+            search_domains: vec![],
         }
     }
 
@@ -89,7 +97,11 @@ impl State {
         servers: &[IpAddr],
     ) -> Result<()> {
         let servers: Vec<DnsServer> = servers.iter().map(|ip| ip.to_string()).collect();
-        let new_settings = DnsSettings::from_server_addresses(&servers, interface.to_string());
+        let new_settings = DnsSettings::from_server_addresses(
+            &servers,
+            &self.search_domains,
+            interface.to_string(),
+        );
         match &self.dns_settings {
             None => {
                 let backup = read_all_dns(&store);
@@ -101,7 +113,7 @@ impl State {
                 self.backup = backup;
             }
             Some(old_settings) => {
-                if new_settings.address_set() != old_settings.address_set() {
+                if !new_settings.same_config(old_settings) {
                     for service_path in self.backup.keys() {
                         new_settings.save(store, service_path.as_str())?;
                     }
@@ -124,7 +136,7 @@ impl State {
                         true
                     }
                     Some(new_settings) => {
-                        if new_settings.address_set() != expected_settings.address_set() {
+                        if !new_settings.same_config(expected_settings) {
                             let servers = new_settings.server_addresses().join(",");
                             log::debug!("Detected DNS change [{}] for {}", servers, *path);
                             self.backup.insert(path.to_string(), Some(new_settings));
@@ -150,6 +162,10 @@ impl State {
                     }
                     if let Err(e) = expected_settings.save(&store, path.clone()) {
                         log::error!("Failed changing DNS for {}: {}", *path, e);
+                        if self.strict {
+                            self.block_incomplete(&store, format!("{} for {}", e, *path));
+                            return;
+                        }
                     }
                     // If we changed a "state" entry, also set the corresponding "setup" entry.
                     if let Some(setup_path_str) = state_to_setup_path(&path.to_string()) {
@@ -162,6 +178,10 @@ impl State {
                         }
                         if let Err(e) = expected_settings.save(&store, setup_path.clone()) {
                             log::error!("Failed changing DNS for {}: {}", setup_path, e);
+                            if self.strict {
+                                self.block_incomplete(&store, format!("{} for {}", e, setup_path));
+                                return;
+                            }
                         }
                     }
                 }
@@ -169,6 +189,19 @@ impl State {
         }
     }
 
+    /// Blocks the tunnel since the DNS of a service could not be restored, and strict enforcement
+    /// is enabled.
+    fn block_incomplete(&mut self, store: &SCDynamicStore, failure: String) {
+        if let Some(tx) = self.tsm_tx.upgrade() {
+            let _ = tx.unbounded_send(TunnelCommand::Block(ErrorStateCause::StrictEnforcement(
+                failure,
+            )));
+        }
+        if let Err(err) = self.reset(store) {
+            log::error!("Failed to reset DNS after failing to restore it: {}", err);
+        }
+    }
+
     fn reset(&mut self, store: &SCDynamicStore) -> Result<()> {
         log::trace!("Restoring DNS settings to: {:#?}", self.backup);
         let old_backup = std::mem::take(&mut self.backup);
@@ -197,7 +230,11 @@ struct DnsSettings {
 unsafe impl Send for DnsSettings {}
 
 impl DnsSettings {
-    pub fn from_server_addresses(server_addresses: &[DnsServer], name: String) -> Self {
+    pub fn from_server_addresses(
+        server_addresses: &[DnsServer],
+        search_domains: &[String],
+        name: String,
+    ) -> Self {
         let mut mut_dict = CFMutableDictionary::new();
         if !server_addresses.is_empty() {
             let cf_string_servers: Vec<CFString> =
@@ -210,6 +247,17 @@ impl DnsSettings {
                 &server_addresses_value.to_void(),
             );
         }
+        if !search_domains.is_empty() {
+            let cf_string_domains: Vec<CFString> =
+                search_domains.iter().map(|s| CFString::new(s)).collect();
+            let search_domains_value = CFArray::from_CFTypes(&cf_string_domains).into_untyped();
+            let search_domains_key =
+                unsafe { CFString::wrap_under_get_rule(kSCPropNetDNSSearchDomains) };
+            mut_dict.add(
+                &search_domains_key.to_void(),
+                &search_domains_value.to_void(),
+            );
+        }
         let dict = mut_dict.to_immutable();
         DnsSettings { dict, name }
     }
@@ -260,6 +308,20 @@ impl DnsSettings {
         BTreeSet::from_iter(self.server_addresses().into_iter())
     }
 
+    pub fn search_domains(&self) -> Vec<String> {
+        self.dict
+            .find(unsafe { kSCPropNetDNSSearchDomains }.to_void())
+            .map(|array_ptr| unsafe { CFType::wrap_under_get_rule(*array_ptr) })
+            .and_then(|array| array.downcast::<CFArray>())
+            .and_then(Self::parse_cf_array_to_strings)
+            .unwrap_or(Vec::new())
+    }
+
+    /// Returns whether both settings use the same servers, in any order, and search domains.
+    pub fn same_config(&self, other: &DnsSettings) -> bool {
+        self.address_set() == other.address_set() && self.search_domains() == other.search_domains()
+    }
+
     pub fn interface_config(&self, interface_path: &str) -> Result<Vec<IpAddr>> {
         let addresses = self
             .server_addresses()
@@ -361,6 +423,14 @@ impl super::DnsMonitorT for DnsMonitor {
     fn reset(&mut self) -> Result<()> {
         self.state.lock().reset(&self.store)
     }
+
+    fn set_strict(&mut self, strict: bool) {
+        self.state.lock().strict = strict;
+    }
+
+    fn set_search_domains(&mut self, domains: Vec<String>) {
+        self.state.lock().search_domains = domains;
+    }
 }
 
 impl DnsMonitor {
@@ -8,6 +8,10 @@ use {
     std::sync::Weak,
 };
 
+/// Local resolver that applies split DNS rules and DNS filters
+#[cfg(not(target_os = "android"))]
+pub mod forwarder;
+
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
 mod imp;
@@ -17,7 +21,7 @@ mod imp;
 mod imp;
 
 #[cfg(target_os = "linux")]
-pub use imp::will_use_nm;
+pub use imp::{set_backend, will_use_nm};
 
 #[cfg(windows)]
 #[path = "windows/mod.rs"]
@@ -79,6 +83,26 @@ impl DnsMonitor {
         log::info!("Resetting DNS");
         self.inner.reset()
     }
+
+    /// Reset DNS changes that a previous instance did not undo, because it did not exit cleanly.
+    #[cfg(target_os = "linux")]
+    pub fn reset_leftover(&mut self) -> Result<(), Error> {
+        self.inner.reset_leftover()
+    }
+
+    /// Whether parts of the DNS config that fail to be set should be treated as errors, rather
+    /// than being skipped. This takes effect the next time DNS is set.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.inner.set_strict(strict)
+    }
+
+    /// Sets the domains that are appended to short names by the resolver of the tunnel
+    /// interface. This takes effect the next time DNS is set.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_search_domains(&mut self, domains: Vec<String>) {
+        self.inner.set_search_domains(domains)
+    }
 }
 
 trait DnsMonitorT: Sized {
@@ -93,4 +117,10 @@ trait DnsMonitorT: Sized {
     fn set(&mut self, interface: &str, servers: &[IpAddr]) -> Result<(), Self::Error>;
 
     fn reset(&mut self) -> Result<(), Self::Error>;
+
+    #[cfg(not(target_os = "android"))]
+    fn set_strict(&mut self, strict: bool);
+
+    #[cfg(not(target_os = "android"))]
+    fn set_search_domains(&mut self, domains: Vec<String>);
 }
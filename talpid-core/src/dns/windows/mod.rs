@@ -43,19 +43,25 @@ pub enum Error {
 
 pub struct DnsMonitor {
     current_guid: Option<GUID>,
+    strict: bool,
+    search_domains: Vec<String>,
 }
 
 impl super::DnsMonitorT for DnsMonitor {
     type Error = Error;
 
     fn new() -> Result<Self, Error> {
-        Ok(DnsMonitor { current_guid: None })
+        Ok(DnsMonitor {
+            current_guid: None,
+            strict: false,
+            search_domains: vec![],
+        })
     }
 
     fn set(&mut self, interface: &str, servers: &[IpAddr]) -> Result<(), Error> {
         let guid = guid_from_luid(&luid_from_alias(interface).map_err(Error::InterfaceLuidError)?)
             .map_err(Error::InterfaceGuidError)?;
-        set_dns(&guid, servers)?;
+        set_dns(&guid, servers, &self.search_domains, self.strict)?;
         self.current_guid = Some(guid);
         flush_dns_cache()?;
         Ok(())
@@ -63,15 +69,30 @@ impl super::DnsMonitorT for DnsMonitor {
 
     fn reset(&mut self) -> Result<(), Error> {
         if let Some(guid) = self.current_guid.take() {
-            return set_dns(&guid, &[]).and(flush_dns_cache());
+            return set_dns(&guid, &[], &[], false).and(flush_dns_cache());
         }
         Ok(())
     }
+
+    fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn set_search_domains(&mut self, domains: Vec<String>) {
+        self.search_domains = domains;
+    }
 }
 
-fn set_dns(interface: &GUID, servers: &[IpAddr]) -> Result<(), Error> {
+/// Sets the DNS servers and search domains of an interface. Failing to disable LLMNR on the
+/// interface is only an error if `strict` is set.
+fn set_dns(
+    interface: &GUID,
+    servers: &[IpAddr],
+    search_domains: &[String],
+    strict: bool,
+) -> Result<(), Error> {
     let transaction = Transaction::new().map_err(Error::SetResolversError)?;
-    let result = match set_dns_inner(&transaction, interface, servers) {
+    let result = match set_dns_inner(&transaction, interface, servers, search_domains, strict) {
         Ok(()) => transaction.commit(),
         Err(error) => transaction.rollback().and(Err(error)),
     };
@@ -82,6 +103,8 @@ fn set_dns_inner(
     transaction: &Transaction,
     interface: &GUID,
     servers: &[IpAddr],
+    search_domains: &[String],
+    strict: bool,
 ) -> io::Result<()> {
     let guid_str = string_from_guid(interface);
 
@@ -90,6 +113,8 @@ fn set_dns_inner(
         &guid_str,
         "Tcpip",
         servers.iter().filter(|addr| addr.is_ipv4()),
+        search_domains,
+        strict,
     )?;
 
     config_interface(
@@ -97,6 +122,8 @@ fn set_dns_inner(
         &guid_str,
         "Tcpip6",
         servers.iter().filter(|addr| addr.is_ipv6()),
+        search_domains,
+        strict,
     )?;
 
     Ok(())
@@ -107,6 +134,8 @@ fn config_interface<'a>(
     guid: &str,
     service: &str,
     nameservers: impl Iterator<Item = &'a IpAddr>,
+    search_domains: &[String],
+    strict: bool,
 ) -> io::Result<()> {
     let nameservers = nameservers
         .map(|addr| addr.to_string())
@@ -121,27 +150,27 @@ fn config_interface<'a>(
     ) {
         Ok(adapter_key) => Ok(adapter_key),
         Err(error) => {
-            if nameservers.is_empty() && error.kind() == io::ErrorKind::NotFound {
+            if nameservers.is_empty()
+                && search_domains.is_empty()
+                && error.kind() == io::ErrorKind::NotFound
+            {
                 return Ok(());
             }
             Err(error)
         }
     }?;
 
-    if !nameservers.is_empty() {
-        adapter_key.set_value("NameServer", &nameservers.join(","))?;
-    } else {
-        adapter_key.delete_value("NameServer").or_else(|error| {
-            if error.kind() == io::ErrorKind::NotFound {
-                Ok(())
-            } else {
-                Err(error)
-            }
-        })?;
-    }
+    set_list_value(&adapter_key, "NameServer", &nameservers)?;
+    set_list_value(&adapter_key, "SearchList", search_domains)?;
 
     // Try to disable LLMNR on the interface
     if let Err(error) = adapter_key.set_value("EnableMulticast", &0u32) {
+        if strict {
+            return Err(io::Error::new(
+                error.kind(),
+                format!("Failed to disable LLMNR on the tunnel interface ({service}): {error}"),
+            ));
+        }
         log::error!(
             "{}\nService: {service}",
             error.display_chain_with_msg("Failed to disable LLMNR on the tunnel interface")
@@ -151,6 +180,21 @@ fn config_interface<'a>(
     Ok(())
 }
 
+/// Sets a comma-separated registry value, or removes it if `values` is empty.
+fn set_list_value(key: &RegKey, name: &str, values: &[String]) -> io::Result<()> {
+    if !values.is_empty() {
+        key.set_value(name, &values.join(","))
+    } else {
+        key.delete_value(name).or_else(|error| {
+            if error.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(error)
+            }
+        })
+    }
+}
+
 fn flush_dns_cache() -> Result<(), Error> {
     let sysdir = get_system_dir().map_err(Error::SystemDirError)?;
     Command::new(sysdir.join("ipconfig.exe"))
@@ -14,7 +14,10 @@ use std::{
     io,
     net::{IpAddr, Ipv4Addr},
 };
-use talpid_types::net::{AllowedTunnelTraffic, Endpoint, TransportProtocol};
+use talpid_types::net::{
+    AllowedTunnelTraffic, CaptivePortal, Endpoint, FirewallException, FirewallExceptionDirection,
+    TransportProtocol,
+};
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
 const MANGLE_CHAIN_PRIORITY: i32 = libc::NF_IP_PRI_MANGLE;
@@ -346,6 +349,7 @@ impl<'a> PolicyBatch<'a> {
                     &tunnel.interface,
                     TransportProtocol::Udp,
                     *server,
+                    53,
                 )?;
                 self.batch.add(&allow_rule, nftnl::MsgType::Add);
                 let allow_rule = allow_tunnel_dns_rule(
@@ -353,6 +357,7 @@ impl<'a> PolicyBatch<'a> {
                     &tunnel.interface,
                     TransportProtocol::Tcp,
                     *server,
+                    53,
                 )?;
                 self.batch.add(&allow_rule, nftnl::MsgType::Add);
             }
@@ -365,7 +370,7 @@ impl<'a> PolicyBatch<'a> {
             rule.add_expr(&nft_expr!(cmp == split_tunnel::NET_CLS_CLASSID));
             rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
             rule.add_expr(&nft_expr!(ct mark set));
-            rule.add_expr(&nft_expr!(immediate data crate::linux::TUNNEL_FW_MARK));
+            rule.add_expr(&nft_expr!(immediate data crate::linux::tunnel_fw_mark()));
             rule.add_expr(&nft_expr!(meta mark set));
             self.batch.add(&rule, nftnl::MsgType::Add);
         }
@@ -416,7 +421,7 @@ impl<'a> PolicyBatch<'a> {
             check_not_iface(&mut prerouting_rule, Direction::In, &tunnel.interface)?;
             prerouting_rule.add_expr(&nft_expr!(ct mark));
             prerouting_rule.add_expr(&nft_expr!(cmp == split_tunnel::MARK));
-            prerouting_rule.add_expr(&nft_expr!(immediate data crate::linux::TUNNEL_FW_MARK));
+            prerouting_rule.add_expr(&nft_expr!(immediate data crate::linux::tunnel_fw_mark()));
             prerouting_rule.add_expr(&nft_expr!(meta mark set));
             if *ADD_COUNTERS {
                 prerouting_rule.add_expr(&nft_expr!(counter));
@@ -552,13 +557,15 @@ impl<'a> PolicyBatch<'a> {
     }
 
     fn add_policy_specific_rules(&mut self, policy: &FirewallPolicy) -> Result<()> {
-        let allow_lan = match policy {
+        let allowed_lan_nets = match policy {
             FirewallPolicy::Connecting {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                ..
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint);
                 self.add_allow_endpoint_rules(&allowed_endpoint.endpoint);
@@ -581,40 +588,66 @@ impl<'a> PolicyBatch<'a> {
                         self.add_block_cve_2019_14899(tunnel);
                     }
                 }
-                *allow_lan
+                allow_lan.then(|| allowed_lan_nets)
             }
             FirewallPolicy::Connected {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 dns_servers,
+                strict_dns_leak_prevention,
+                excluded_networks,
+                ..
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint);
-                self.add_allow_dns_rules(tunnel, dns_servers, TransportProtocol::Udp)?;
-                self.add_allow_dns_rules(tunnel, dns_servers, TransportProtocol::Tcp)?;
+                for protocol in [TransportProtocol::Udp, TransportProtocol::Tcp] {
+                    self.add_allow_dns_rules(tunnel, dns_servers, protocol, 53)?;
+                    if *strict_dns_leak_prevention {
+                        self.add_allow_dns_rules(
+                            tunnel,
+                            dns_servers,
+                            protocol,
+                            super::DNS_OVER_TLS_PORT,
+                        )?;
+                    }
+                }
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
                 self.add_drop_dns_rule();
+                if *strict_dns_leak_prevention {
+                    self.add_drop_encrypted_dns_rules(dns_servers);
+                }
                 self.add_allow_tunnel_rules(&tunnel.interface)?;
+                self.add_allow_excluded_networks_rules(excluded_networks);
                 if *allow_lan {
                     self.add_block_cve_2019_14899(tunnel);
                 }
-                *allow_lan
+                allow_lan.then(|| allowed_lan_nets)
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
+                ..
             } => {
                 self.add_allow_endpoint_rules(&allowed_endpoint.endpoint);
 
                 // Important to drop DNS before allowing LAN (to stop DNS leaking to the LAN)
                 self.add_drop_dns_rule();
-                *allow_lan
+                allow_lan.then(|| allowed_lan_nets)
             }
         };
 
-        if allow_lan {
-            self.add_allow_lan_rules();
+        if let Some(allowed_lan_nets) = allowed_lan_nets {
+            self.add_allow_lan_rules(allowed_lan_nets);
+        }
+        if policy.allow_multicast() {
+            self.add_allow_discovery_rules();
+        }
+        self.add_firewall_exception_rules(policy.firewall_exceptions(), policy.allowed_lan_nets());
+        if let Some(captive_portal) = policy.captive_portal() {
+            self.add_captive_portal_rules(captive_portal);
         }
 
         // Reject any remaining outgoing traffic
@@ -633,7 +666,7 @@ impl<'a> PolicyBatch<'a> {
     fn add_allow_tunnel_endpoint_rules(&mut self, endpoint: &Endpoint) {
         let mut prerouting_rule = Rule::new(&self.prerouting_chain);
         check_endpoint(&mut prerouting_rule, End::Src, endpoint);
-        prerouting_rule.add_expr(&nft_expr!(immediate data crate::linux::TUNNEL_FW_MARK));
+        prerouting_rule.add_expr(&nft_expr!(immediate data crate::linux::tunnel_fw_mark()));
         prerouting_rule.add_expr(&nft_expr!(meta mark set));
 
         if *ADD_COUNTERS {
@@ -656,7 +689,7 @@ impl<'a> PolicyBatch<'a> {
         let mut out_rule = Rule::new(&self.out_chain);
         check_endpoint(&mut out_rule, End::Dst, endpoint);
         out_rule.add_expr(&nft_expr!(meta mark));
-        out_rule.add_expr(&nft_expr!(cmp == crate::linux::TUNNEL_FW_MARK));
+        out_rule.add_expr(&nft_expr!(cmp == crate::linux::tunnel_fw_mark()));
         add_verdict(&mut out_rule, &Verdict::Accept);
 
         self.batch.add(&out_rule, nftnl::MsgType::Add);
@@ -692,17 +725,18 @@ impl<'a> PolicyBatch<'a> {
         tunnel: &tunnel::TunnelMetadata,
         dns_servers: &[IpAddr],
         protocol: TransportProtocol,
+        port: u16,
     ) -> Result<()> {
         let (local_resolvers, remote_resolvers): (Vec<IpAddr>, Vec<IpAddr>) = dns_servers
             .iter()
             .partition(|server| is_local_dns_address(tunnel, server));
 
         for resolver in &local_resolvers {
-            self.add_allow_local_dns_rule(&tunnel.interface, protocol, *resolver)?;
+            self.add_allow_local_dns_rule(&tunnel.interface, protocol, *resolver, port)?;
         }
 
         for resolver in &remote_resolvers {
-            self.add_allow_tunnel_dns_rule(&tunnel.interface, protocol, *resolver)?;
+            self.add_allow_tunnel_dns_rule(&tunnel.interface, protocol, *resolver, port)?;
         }
 
         Ok(())
@@ -713,9 +747,10 @@ impl<'a> PolicyBatch<'a> {
         interface: &str,
         protocol: TransportProtocol,
         host: IpAddr,
+        port: u16,
     ) -> Result<()> {
         for chain in &[&self.out_chain, &self.forward_chain] {
-            let allow_rule = allow_tunnel_dns_rule(chain, interface, protocol, host)?;
+            let allow_rule = allow_tunnel_dns_rule(chain, interface, protocol, host, port)?;
             self.batch.add(&allow_rule, nftnl::MsgType::Add);
         }
         Ok(())
@@ -726,6 +761,7 @@ impl<'a> PolicyBatch<'a> {
         tunnel_interface: &str,
         protocol: TransportProtocol,
         host: IpAddr,
+        port: u16,
     ) -> Result<()> {
         let chains = [
             (&self.out_chain, Direction::Out),
@@ -749,7 +785,7 @@ impl<'a> PolicyBatch<'a> {
             };
 
             check_not_iface(&mut allow_rule, *direction, tunnel_interface)?;
-            check_port(&mut allow_rule, protocol, port_dir, 53);
+            check_port(&mut allow_rule, protocol, port_dir, port);
             check_l3proto(&mut allow_rule, host);
 
             allow_rule.add_expr(&addr);
@@ -780,6 +816,64 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
+    /// Blocks all DNS over TLS, and DNS over HTTPS to well-known resolvers other than
+    /// `dns_servers`. DNS over TLS to `dns_servers` must be allowed before this.
+    fn add_drop_encrypted_dns_rules(&mut self, dns_servers: &[IpAddr]) {
+        let doh_resolvers = super::blocked_doh_resolvers(dns_servers);
+        for chain in &[&self.out_chain, &self.forward_chain] {
+            let mut block_udp_rule = Rule::new(chain);
+            check_port(
+                &mut block_udp_rule,
+                TransportProtocol::Udp,
+                End::Dst,
+                super::DNS_OVER_TLS_PORT,
+            );
+            add_verdict(
+                &mut block_udp_rule,
+                &Verdict::Reject(RejectionType::Icmp(IcmpCode::PortUnreach)),
+            );
+            self.batch.add(&block_udp_rule, nftnl::MsgType::Add);
+
+            let mut block_tcp_rule = Rule::new(chain);
+            check_port(
+                &mut block_tcp_rule,
+                TransportProtocol::Tcp,
+                End::Dst,
+                super::DNS_OVER_TLS_PORT,
+            );
+            add_verdict(&mut block_tcp_rule, &Verdict::Reject(RejectionType::TcpRst));
+            self.batch.add(&block_tcp_rule, nftnl::MsgType::Add);
+
+            for resolver in &doh_resolvers {
+                // HTTP/3 runs over UDP
+                let mut block_udp_rule = Rule::new(chain);
+                check_ip(&mut block_udp_rule, End::Dst, *resolver);
+                check_port(
+                    &mut block_udp_rule,
+                    TransportProtocol::Udp,
+                    End::Dst,
+                    super::HTTPS_PORT,
+                );
+                add_verdict(
+                    &mut block_udp_rule,
+                    &Verdict::Reject(RejectionType::Icmp(IcmpCode::PortUnreach)),
+                );
+                self.batch.add(&block_udp_rule, nftnl::MsgType::Add);
+
+                let mut block_tcp_rule = Rule::new(chain);
+                check_ip(&mut block_tcp_rule, End::Dst, *resolver);
+                check_port(
+                    &mut block_tcp_rule,
+                    TransportProtocol::Tcp,
+                    End::Dst,
+                    super::HTTPS_PORT,
+                );
+                add_verdict(&mut block_tcp_rule, &Verdict::Reject(RejectionType::TcpRst));
+                self.batch.add(&block_tcp_rule, nftnl::MsgType::Add);
+            }
+        }
+    }
+
     fn add_allow_in_tunnel_endpoint_rules(
         &mut self,
         tunnel_interface: &str,
@@ -825,6 +919,28 @@ impl<'a> PolicyBatch<'a> {
         Ok(())
     }
 
+    /// Adds rules that allow connections to networks that are routed outside the tunnel. These
+    /// are added after the rule that drops DNS, so DNS stays blocked.
+    fn add_allow_excluded_networks_rules(&mut self, excluded_networks: &[IpNetwork]) {
+        for net in excluded_networks {
+            for chain in &[&self.out_chain, &self.forward_chain] {
+                let mut out_rule = Rule::new(chain);
+                check_net(&mut out_rule, End::Dst, *net);
+                add_verdict(&mut out_rule, &Verdict::Accept);
+                self.batch.add(&out_rule, nftnl::MsgType::Add);
+            }
+
+            let mut in_rule = Rule::new(&self.in_chain);
+            check_net(&mut in_rule, End::Src, *net);
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            in_rule.add_expr(&nft_expr!(ct state));
+            in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            in_rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(&mut in_rule, &Verdict::Accept);
+            self.batch.add(&in_rule, nftnl::MsgType::Add);
+        }
+    }
+
     /// Adds rules for stopping [CVE-2019-14899](https://seclists.org/oss-sec/2019/q4/122).
     /// An attacker on the same local network as the VPN connected device could figure out
     /// the tunnel IP the device used if the device was set to not filter reverse path (rp_filter.)
@@ -839,11 +955,11 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
-    fn add_allow_lan_rules(&mut self) {
+    fn add_allow_lan_rules(&mut self, allowed_lan_nets: &[IpNetwork]) {
         // Output and forward chains
         for chain in &[&self.out_chain, &self.forward_chain] {
             // LAN -> LAN
-            for net in &*super::ALLOWED_LAN_NETS {
+            for net in allowed_lan_nets {
                 let mut out_rule = Rule::new(chain);
                 check_net(&mut out_rule, End::Dst, *net);
                 add_verdict(&mut out_rule, &Verdict::Accept);
@@ -861,7 +977,7 @@ impl<'a> PolicyBatch<'a> {
 
         // Input chain
         // LAN -> LAN
-        for net in &*super::ALLOWED_LAN_NETS {
+        for net in allowed_lan_nets {
             let mut in_rule = Rule::new(&self.in_chain);
             check_net(&mut in_rule, End::Src, *net);
             add_verdict(&mut in_rule, &Verdict::Accept);
@@ -870,6 +986,94 @@ impl<'a> PolicyBatch<'a> {
         self.add_dhcp_server_rules();
     }
 
+    /// Adds rules that permit mDNS and SSDP discovery on the local network without allowing any
+    /// other LAN traffic.
+    fn add_allow_discovery_rules(&mut self) {
+        for net in &*super::ALLOWED_DISCOVERY_MULTICAST_NETS {
+            let mut rule = Rule::new(&self.out_chain);
+            check_net(&mut rule, End::Dst, *net);
+            add_verdict(&mut rule, &Verdict::Accept);
+            self.batch.add(&rule, nftnl::MsgType::Add);
+        }
+
+        for net in &*super::ALLOWED_LAN_NETS {
+            for port in &super::DISCOVERY_PORTS {
+                // Unicast responses to queries from other hosts
+                let mut out_rule = Rule::new(&self.out_chain);
+                check_net(&mut out_rule, End::Dst, *net);
+                check_port(&mut out_rule, TransportProtocol::Udp, End::Src, *port);
+                add_verdict(&mut out_rule, &Verdict::Accept);
+                self.batch.add(&out_rule, nftnl::MsgType::Add);
+
+                for end in &[End::Src, End::Dst] {
+                    let mut in_rule = Rule::new(&self.in_chain);
+                    check_net(&mut in_rule, End::Src, *net);
+                    check_port(&mut in_rule, TransportProtocol::Udp, *end, *port);
+                    add_verdict(&mut in_rule, &Verdict::Accept);
+                    self.batch.add(&in_rule, nftnl::MsgType::Add);
+                }
+            }
+        }
+    }
+
+    /// Adds rules that keep the given ports open towards the allowed LAN networks. These are added
+    /// after the rule that drops DNS, so an exception for port 53 cannot leak DNS to the LAN.
+    fn add_firewall_exception_rules(
+        &mut self,
+        exceptions: &[FirewallException],
+        allowed_lan_nets: &[IpNetwork],
+    ) {
+        for exception in exceptions {
+            let (in_port_end, out_port_end) = match exception.direction {
+                FirewallExceptionDirection::Inbound => (End::Dst, End::Src),
+                FirewallExceptionDirection::Outbound => (End::Src, End::Dst),
+            };
+            for net in allowed_lan_nets {
+                let mut in_rule = Rule::new(&self.in_chain);
+                check_net(&mut in_rule, End::Src, *net);
+                check_port(
+                    &mut in_rule,
+                    exception.protocol,
+                    in_port_end,
+                    exception.port,
+                );
+                add_verdict(&mut in_rule, &Verdict::Accept);
+                self.batch.add(&in_rule, nftnl::MsgType::Add);
+
+                let mut out_rule = Rule::new(&self.out_chain);
+                check_net(&mut out_rule, End::Dst, *net);
+                check_port(
+                    &mut out_rule,
+                    exception.protocol,
+                    out_port_end,
+                    exception.port,
+                );
+                add_verdict(&mut out_rule, &Verdict::Accept);
+                self.batch.add(&out_rule, nftnl::MsgType::Add);
+            }
+        }
+    }
+
+    /// Adds rules that permit connections outside the tunnel while signing in to a captive portal.
+    /// These are added after the rule that drops DNS, so DNS stays blocked.
+    fn add_captive_portal_rules(&mut self, captive_portal: CaptivePortal) {
+        let mut out_rule = Rule::new(&self.out_chain);
+        let mut in_rule = Rule::new(&self.in_chain);
+        if let CaptivePortal::Address(address) = captive_portal {
+            check_ip(&mut out_rule, End::Dst, address);
+            check_ip(&mut in_rule, End::Src, address);
+        }
+        add_verdict(&mut out_rule, &Verdict::Accept);
+        self.batch.add(&out_rule, nftnl::MsgType::Add);
+
+        let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+        in_rule.add_expr(&nft_expr!(ct state));
+        in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+        in_rule.add_expr(&nft_expr!(cmp != 0u32));
+        add_verdict(&mut in_rule, &Verdict::Accept);
+        self.batch.add(&in_rule, nftnl::MsgType::Add);
+    }
+
     fn add_dhcp_server_rules(&mut self) {
         use TransportProtocol::Udp;
         // Outgoing DHCPv4 response
@@ -906,10 +1110,11 @@ fn allow_tunnel_dns_rule<'a>(
     iface: &str,
     protocol: TransportProtocol,
     host: IpAddr,
+    port: u16,
 ) -> Result<Rule<'a>> {
     let mut rule = Rule::new(chain);
     check_iface(&mut rule, Direction::Out, iface)?;
-    check_port(&mut rule, protocol, End::Dst, 53);
+    check_port(&mut rule, protocol, End::Dst, port);
 
     let daddr = match host {
         IpAddr::V4(_) => nft_expr!(payload ipv4 daddr),
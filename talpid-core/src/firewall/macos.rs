@@ -68,6 +68,16 @@ impl Firewall {
         new_filter_rules.append(&mut self.get_allow_dhcp_client_rules()?);
         new_filter_rules.append(&mut self.get_allow_ndp_rules()?);
         new_filter_rules.append(&mut self.get_policy_specific_rules(&policy)?);
+        if policy.allow_multicast() {
+            new_filter_rules.append(&mut self.get_allow_discovery_rules()?);
+        }
+        new_filter_rules.append(&mut self.get_firewall_exception_rules(
+            policy.firewall_exceptions(),
+            policy.allowed_lan_nets(),
+        )?);
+        if let Some(captive_portal) = policy.captive_portal() {
+            new_filter_rules.append(&mut self.get_captive_portal_rules(captive_portal)?);
+        }
 
         let return_out_rule = self
             .create_rule_builder(FilterRuleAction::Drop(DropAction::Return))
@@ -118,8 +128,10 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                ..
             } => {
                 let mut rules = vec![self.get_allow_relay_rule(*peer_endpoint)?];
                 rules.push(self.get_allowed_endpoint_rule(allowed_endpoint.endpoint)?);
@@ -136,7 +148,7 @@ impl Firewall {
                 }
 
                 if *allow_lan {
-                    rules.append(&mut self.get_allow_lan_rules()?);
+                    rules.append(&mut self.get_allow_lan_rules(allowed_lan_nets)?);
                 }
                 Ok(rules)
             }
@@ -144,12 +156,25 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 dns_servers,
+                strict_dns_leak_prevention,
+                excluded_networks,
+                ..
             } => {
                 let mut rules = vec![];
 
                 for server in dns_servers.iter() {
-                    rules.append(&mut self.get_allow_dns_rules_when_connected(&tunnel, *server)?);
+                    rules.append(
+                        &mut self.get_allow_dns_rules_when_connected(&tunnel, *server, 53)?,
+                    );
+                    if *strict_dns_leak_prevention {
+                        rules.append(&mut self.get_allow_dns_rules_when_connected(
+                            &tunnel,
+                            *server,
+                            super::DNS_OVER_TLS_PORT,
+                        )?);
+                    }
                 }
 
                 rules.push(self.get_allow_relay_rule(*peer_endpoint)?);
@@ -157,6 +182,9 @@ impl Firewall {
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
                 rules.append(&mut self.get_block_dns_rules()?);
+                if *strict_dns_leak_prevention {
+                    rules.append(&mut self.get_block_encrypted_dns_rules(dns_servers)?);
+                }
 
                 rules.extend(
                     self.get_allow_tunnel_rule(
@@ -165,15 +193,17 @@ impl Firewall {
                     )?
                     .into_iter(),
                 );
+                rules.append(&mut self.get_allow_excluded_networks_rules(excluded_networks)?);
 
                 if *allow_lan {
-                    rules.append(&mut self.get_allow_lan_rules()?);
+                    rules.append(&mut self.get_allow_lan_rules(allowed_lan_nets)?);
                 }
 
                 Ok(rules)
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
                 ..
             } => {
@@ -183,7 +213,7 @@ impl Firewall {
                 if *allow_lan {
                     // Important to block DNS before allow LAN (so DNS does not leak to the LAN)
                     rules.append(&mut self.get_block_dns_rules()?);
-                    rules.append(&mut self.get_allow_lan_rules()?);
+                    rules.append(&mut self.get_allow_lan_rules(allowed_lan_nets)?);
                 }
 
                 Ok(rules)
@@ -195,6 +225,7 @@ impl Firewall {
         &self,
         tunnel: &crate::tunnel::TunnelMetadata,
         server: IpAddr,
+        port: u16,
     ) -> Result<Vec<pfctl::FilterRule>> {
         let mut rules = Vec::with_capacity(4);
 
@@ -214,7 +245,7 @@ impl Firewall {
                 .interface(&tunnel.interface)
                 .proto(pfctl::Proto::Tcp)
                 .keep_state(pfctl::StatePolicy::None)
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(block_tunnel_tcp);
             let block_tunnel_udp = self
@@ -224,7 +255,7 @@ impl Firewall {
                 .interface(&tunnel.interface)
                 .proto(pfctl::Proto::Udp)
                 .keep_state(pfctl::StatePolicy::None)
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(block_tunnel_udp);
 
@@ -236,7 +267,7 @@ impl Firewall {
                 .proto(pfctl::Proto::Tcp)
                 .keep_state(pfctl::StatePolicy::Keep)
                 .tcp_flags(Self::get_tcp_flags())
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(allow_nontunnel_tcp);
             let allow_nontunnel_udp = self
@@ -245,7 +276,7 @@ impl Firewall {
                 .quick(true)
                 .proto(pfctl::Proto::Udp)
                 .keep_state(pfctl::StatePolicy::Keep)
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(allow_nontunnel_udp);
         } else {
@@ -258,7 +289,7 @@ impl Firewall {
                 .proto(pfctl::Proto::Tcp)
                 .keep_state(pfctl::StatePolicy::Keep)
                 .tcp_flags(Self::get_tcp_flags())
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(allow_tunnel_tcp);
             let allow_tunnel_udp = self
@@ -267,7 +298,7 @@ impl Firewall {
                 .quick(true)
                 .interface(&tunnel.interface)
                 .proto(pfctl::Proto::Udp)
-                .to(pfctl::Endpoint::new(server, 53))
+                .to(pfctl::Endpoint::new(server, port))
                 .build()?;
             rules.push(allow_tunnel_udp);
         };
@@ -328,6 +359,37 @@ impl Firewall {
         Ok(vec![block_tcp_dns_rule, block_udp_dns_rule])
     }
 
+    /// Blocks all DNS over TLS, and DNS over HTTPS to well-known resolvers other than
+    /// `dns_servers`. DNS over TLS to `dns_servers` must be allowed before this.
+    fn get_block_encrypted_dns_rules(
+        &self,
+        dns_servers: &[IpAddr],
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for proto in [pfctl::Proto::Tcp, pfctl::Proto::Udp] {
+            rules.push(
+                self.create_rule_builder(FilterRuleAction::Drop(DropAction::Return))
+                    .direction(pfctl::Direction::Out)
+                    .quick(true)
+                    .proto(proto)
+                    .to(pfctl::Port::from(super::DNS_OVER_TLS_PORT))
+                    .build()?,
+            );
+            // DNS over HTTPS may use HTTP/3, which runs over UDP
+            for resolver in super::blocked_doh_resolvers(dns_servers) {
+                rules.push(
+                    self.create_rule_builder(FilterRuleAction::Drop(DropAction::Return))
+                        .direction(pfctl::Direction::Out)
+                        .quick(true)
+                        .proto(proto)
+                        .to(pfctl::Endpoint::new(resolver, super::HTTPS_PORT))
+                        .build()?,
+                );
+            }
+        }
+        Ok(rules)
+    }
+
     fn get_allow_tunnel_rule(
         &self,
         tunnel_interface: &str,
@@ -362,9 +424,12 @@ impl Firewall {
         Ok(vec![lo0_rule])
     }
 
-    fn get_allow_lan_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
+    fn get_allow_lan_rules(
+        &self,
+        allowed_lan_nets: &[IpNetwork],
+    ) -> Result<Vec<pfctl::FilterRule>> {
         let mut rules = vec![];
-        for net in &*super::ALLOWED_LAN_NETS {
+        for net in allowed_lan_nets {
             let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
             rule_builder.quick(true);
             let allow_out = rule_builder
@@ -416,6 +481,124 @@ impl Firewall {
         Ok(rules)
     }
 
+    fn get_allow_discovery_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for multicast_net in &*super::ALLOWED_DISCOVERY_MULTICAST_NETS {
+            let allow_multicast_out = self
+                .create_rule_builder(FilterRuleAction::Pass)
+                .quick(true)
+                .direction(pfctl::Direction::Out)
+                .to(pfctl::Ip::from(*multicast_net))
+                .build()?;
+            rules.push(allow_multicast_out);
+        }
+        for net in &*super::ALLOWED_LAN_NETS {
+            for port in &super::DISCOVERY_PORTS {
+                // Unicast responses to queries from other hosts
+                let allow_response_out = self
+                    .create_rule_builder(FilterRuleAction::Pass)
+                    .quick(true)
+                    .direction(pfctl::Direction::Out)
+                    .proto(pfctl::Proto::Udp)
+                    .from(pfctl::Port::from(*port))
+                    .to(pfctl::Ip::from(*net))
+                    .build()?;
+                let allow_from_port_in = self
+                    .create_rule_builder(FilterRuleAction::Pass)
+                    .quick(true)
+                    .direction(pfctl::Direction::In)
+                    .proto(pfctl::Proto::Udp)
+                    .from(pfctl::Endpoint::new(
+                        pfctl::Ip::from(*net),
+                        pfctl::Port::from(*port),
+                    ))
+                    .build()?;
+                let allow_to_port_in = self
+                    .create_rule_builder(FilterRuleAction::Pass)
+                    .quick(true)
+                    .direction(pfctl::Direction::In)
+                    .proto(pfctl::Proto::Udp)
+                    .from(pfctl::Ip::from(*net))
+                    .to(pfctl::Port::from(*port))
+                    .build()?;
+                rules.push(allow_response_out);
+                rules.push(allow_from_port_in);
+                rules.push(allow_to_port_in);
+            }
+        }
+        Ok(rules)
+    }
+
+    fn get_firewall_exception_rules(
+        &self,
+        exceptions: &[net::FirewallException],
+        allowed_lan_nets: &[IpNetwork],
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for exception in exceptions {
+            for net in allowed_lan_nets {
+                let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
+                rule_builder
+                    .quick(true)
+                    .proto(as_pfctl_proto(exception.protocol))
+                    .keep_state(pfctl::StatePolicy::Keep);
+                match exception.direction {
+                    net::FirewallExceptionDirection::Inbound => rule_builder
+                        .direction(pfctl::Direction::In)
+                        .from(pfctl::Ip::from(*net))
+                        .to(pfctl::Port::from(exception.port)),
+                    net::FirewallExceptionDirection::Outbound => rule_builder
+                        .direction(pfctl::Direction::Out)
+                        .to(pfctl::Endpoint::new(
+                            pfctl::Ip::from(*net),
+                            pfctl::Port::from(exception.port),
+                        )),
+                };
+                rules.push(rule_builder.build()?);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Returns rules that permit connections to networks that are routed outside the tunnel.
+    /// These must come after the rules that block DNS.
+    fn get_allow_excluded_networks_rules(
+        &self,
+        excluded_networks: &[IpNetwork],
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for net in excluded_networks {
+            rules.push(
+                self.create_rule_builder(FilterRuleAction::Pass)
+                    .direction(pfctl::Direction::Out)
+                    .to(pfctl::Ip::from(*net))
+                    .quick(true)
+                    .keep_state(pfctl::StatePolicy::Keep)
+                    .build()?,
+            );
+        }
+        Ok(rules)
+    }
+
+    /// Returns rules that permit connections outside the tunnel while signing in to a captive
+    /// portal. DNS is blocked first, since the blocked policy only does so when LAN is allowed.
+    fn get_captive_portal_rules(
+        &self,
+        captive_portal: net::CaptivePortal,
+    ) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = self.get_block_dns_rules()?;
+        let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
+        rule_builder
+            .direction(pfctl::Direction::Out)
+            .quick(true)
+            .keep_state(pfctl::StatePolicy::Keep);
+        if let net::CaptivePortal::Address(address) = captive_portal {
+            rule_builder.to(pfctl::Ip::from(address));
+        }
+        rules.push(rule_builder.build()?);
+        Ok(rules)
+    }
+
     fn get_allow_dhcp_client_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
         let mut dhcp_rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
         dhcp_rule_builder.quick(true).proto(pfctl::Proto::Udp);
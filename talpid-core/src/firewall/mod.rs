@@ -8,7 +8,12 @@ use std::{
     fmt,
     net::{Ipv4Addr, Ipv6Addr},
 };
-use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, Endpoint};
+#[cfg(windows)]
+use talpid_types::net::LinkLayerExemptions;
+use talpid_types::net::{
+    AllowedEndpoint, AllowedTunnelTraffic, CaptivePortal, Endpoint, FirewallException,
+    InboundTunnelPort,
+};
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -57,6 +62,16 @@ lazy_static! {
         // Site-local IPv6 multicast.
         IpNetwork::V6(Ipv6Network::new(Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 0), 16).unwrap()),
     ];
+    /// When "allow multicast" is enabled the app will allow traffic to these networks, which
+    /// covers mDNS and SSDP discovery, without allowing the LAN as a whole.
+    pub(crate) static ref ALLOWED_DISCOVERY_MULTICAST_NETS: [IpNetwork; 3] = [
+        // Local subnetwork multicast, including mDNS (224.0.0.251)
+        IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(224, 0, 0, 0), 24).unwrap()),
+        // SSDP
+        IpNetwork::V4(Ipv4Network::new(Ipv4Addr::new(239, 255, 255, 250), 32).unwrap()),
+        // Link-local IPv6 multicast, including mDNS (ff02::fb) and SSDP (ff02::c)
+        IpNetwork::V6(Ipv6Network::new(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0), 16).unwrap()),
+    ];
     static ref IPV6_LINK_LOCAL: Ipv6Network = Ipv6Network::new(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10).unwrap();
     /// The allowed target addresses of outbound DHCPv6 requests
     static ref DHCPV6_SERVER_ADDRS: [Ipv6Addr; 2] = [
@@ -70,6 +85,44 @@ lazy_static! {
         IpNetwork::V6(ipnetwork::Ipv6Network::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 128).unwrap()),
     ];
 }
+
+/// Well-known public DNS over HTTPS resolvers. HTTPS traffic to these is blocked by strict DNS
+/// leak prevention, unless they are among the DNS servers in use.
+#[cfg(not(target_os = "android"))]
+const PUBLIC_DOH_RESOLVERS: [IpAddr; 20] = [
+    // Cloudflare
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+    IpAddr::V4(Ipv4Addr::new(1, 0, 0, 1)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1001)),
+    // Google
+    IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)),
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)),
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8844)),
+    // Quad9
+    IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+    IpAddr::V4(Ipv4Addr::new(149, 112, 112, 112)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0xfe)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0x9)),
+    // OpenDNS
+    IpAddr::V4(Ipv4Addr::new(208, 67, 222, 222)),
+    IpAddr::V4(Ipv4Addr::new(208, 67, 220, 220)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0x119, 0x53, 0, 0, 0, 0, 0x53)),
+    // AdGuard
+    IpAddr::V4(Ipv4Addr::new(94, 140, 14, 14)),
+    IpAddr::V4(Ipv4Addr::new(94, 140, 15, 15)),
+    // Mullvad
+    IpAddr::V4(Ipv4Addr::new(194, 242, 2, 2)),
+    IpAddr::V6(Ipv6Addr::new(0x2a07, 0xe340, 0, 0, 0, 0, 0, 0x2)),
+];
+/// Port used by DNS over TLS.
+#[cfg(all(unix, not(target_os = "android")))]
+const DNS_OVER_TLS_PORT: u16 = 853;
+/// Port used by DNS over HTTPS, and all other HTTPS traffic.
+#[cfg(all(unix, not(target_os = "android")))]
+const HTTPS_PORT: u16 = 443;
 #[cfg(all(unix, not(target_os = "android")))]
 const DHCPV4_SERVER_PORT: u16 = 67;
 #[cfg(all(unix, not(target_os = "android")))]
@@ -80,6 +133,20 @@ const DHCPV6_SERVER_PORT: u16 = 547;
 const DHCPV6_CLIENT_PORT: u16 = 546;
 #[cfg(all(unix, not(target_os = "android")))]
 const ROOT_UID: u32 = 0;
+/// UDP ports used by mDNS and SSDP, permitted when "allow multicast" is enabled.
+#[cfg(all(unix, not(target_os = "android")))]
+const DISCOVERY_PORTS: [u16; 2] = [5353, 1900];
+
+/// Returns the public DNS over HTTPS resolvers to block with strict DNS leak prevention, which are
+/// the ones not in `dns_servers`.
+#[cfg(not(target_os = "android"))]
+fn blocked_doh_resolvers(dns_servers: &[IpAddr]) -> Vec<IpAddr> {
+    PUBLIC_DOH_RESOLVERS
+        .iter()
+        .filter(|resolver| !dns_servers.contains(resolver))
+        .copied()
+        .collect()
+}
 
 #[cfg(any(all(unix, not(target_os = "android")), target_os = "windows"))]
 /// Returns whether an address belongs to a private subnet.
@@ -91,6 +158,22 @@ pub fn is_local_address(address: &IpAddr) -> bool {
         .any(|net| net.contains(address))
 }
 
+/// Returns the networks that are reachable when LAN access is allowed, unless the user has
+/// narrowed them down.
+pub fn default_allowed_lan_nets() -> Vec<IpNetwork> {
+    ALLOWED_LAN_NETS.to_vec()
+}
+
+/// Returns whether a network lies entirely within the private address ranges that may be allowed
+/// as LAN networks.
+pub fn is_local_network(network: &IpNetwork) -> bool {
+    ALLOWED_LAN_NETS.iter().any(|lan_net| {
+        lan_net.is_ipv4() == network.is_ipv4()
+            && lan_net.prefix() <= network.prefix()
+            && lan_net.contains(network.ip())
+    })
+}
+
 /// A enum that describes network security strategy
 ///
 /// # Firewall block/allow specification.
@@ -107,13 +190,25 @@ pub enum FirewallPolicy {
         tunnel: Option<crate::tunnel::TunnelMetadata>,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// The networks that are reachable when `allow_lan` is set, and that `firewall_exceptions`
+        /// apply to.
+        allowed_lan_nets: Vec<IpNetwork>,
+        /// Ports to keep open towards the local network.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Flag setting if mDNS and SSDP discovery on the local network should be possible.
+        allow_multicast: bool,
         /// Host that should be reachable while connecting.
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+        /// Traffic to permit outside the tunnel while signing in to a captive portal.
+        captive_portal: Option<CaptivePortal>,
         /// A process that is allowed to send packets to the relay.
         #[cfg(windows)]
         relay_client: PathBuf,
+        /// Link layer protocols to permit on physical adapters.
+        #[cfg(windows)]
+        link_layer_exemptions: LinkLayerExemptions,
     },
 
     /// Allow traffic only to server and over tunnel interface
@@ -124,27 +219,120 @@ pub enum FirewallPolicy {
         tunnel: crate::tunnel::TunnelMetadata,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// The networks that are reachable when `allow_lan` is set, and that `firewall_exceptions`
+        /// apply to.
+        allowed_lan_nets: Vec<IpNetwork>,
+        /// Ports to keep open towards the local network.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Local ports that accept inbound connections from inside the tunnel. This is only
+        /// enforced on Windows, since the other platforms accept all inbound tunnel traffic.
+        inbound_tunnel_ports: Vec<InboundTunnelPort>,
+        /// Flag setting if mDNS and SSDP discovery on the local network should be possible.
+        allow_multicast: bool,
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_servers: Vec<IpAddr>,
+        /// Also block DNS over TLS, and DNS over HTTPS to well-known resolvers, unless it is sent
+        /// to one of `dns_servers`.
+        #[cfg(not(target_os = "android"))]
+        strict_dns_leak_prevention: bool,
+        /// Networks that are routed outside the tunnel, because the allowed IPs of the tunnel
+        /// have been overridden. Traffic to these is allowed on any interface, except DNS.
+        #[cfg(not(target_os = "android"))]
+        excluded_networks: Vec<IpNetwork>,
         /// A process that is allowed to send packets to the relay.
         #[cfg(windows)]
         relay_client: PathBuf,
+        /// Link layer protocols to permit on physical adapters.
+        #[cfg(windows)]
+        link_layer_exemptions: LinkLayerExemptions,
     },
 
     /// Block all network traffic in and out from the computer.
     Blocked {
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// The networks that are reachable when `allow_lan` is set, and that `firewall_exceptions`
+        /// apply to.
+        allowed_lan_nets: Vec<IpNetwork>,
+        /// Ports to keep open towards the local network.
+        firewall_exceptions: Vec<FirewallException>,
+        /// Flag setting if mDNS and SSDP discovery on the local network should be possible.
+        allow_multicast: bool,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: AllowedEndpoint,
+        /// Traffic to permit outside the tunnel while signing in to a captive portal.
+        captive_portal: Option<CaptivePortal>,
         /// Desination port for DNS traffic redirection. Traffic destined to `127.0.0.1:53` will be
         /// redirected to `127.0.0.1:$dns_redirect_port`.
         #[cfg(target_os = "macos")]
         dns_redirect_port: u16,
+        /// Link layer protocols to permit on physical adapters.
+        #[cfg(windows)]
+        link_layer_exemptions: LinkLayerExemptions,
     },
 }
 
+impl FirewallPolicy {
+    /// Returns the ports to keep open towards the local network.
+    pub fn firewall_exceptions(&self) -> &[FirewallException] {
+        match self {
+            FirewallPolicy::Connecting {
+                firewall_exceptions,
+                ..
+            }
+            | FirewallPolicy::Connected {
+                firewall_exceptions,
+                ..
+            }
+            | FirewallPolicy::Blocked {
+                firewall_exceptions,
+                ..
+            } => firewall_exceptions,
+        }
+    }
+
+    /// Returns the networks that are reachable when LAN traffic is allowed, and that firewall
+    /// exceptions apply to.
+    pub fn allowed_lan_nets(&self) -> &[IpNetwork] {
+        match self {
+            FirewallPolicy::Connecting {
+                allowed_lan_nets, ..
+            }
+            | FirewallPolicy::Connected {
+                allowed_lan_nets, ..
+            }
+            | FirewallPolicy::Blocked {
+                allowed_lan_nets, ..
+            } => allowed_lan_nets,
+        }
+    }
+
+    /// Returns whether mDNS and SSDP discovery on the local network is allowed.
+    pub fn allow_multicast(&self) -> bool {
+        match self {
+            FirewallPolicy::Connecting {
+                allow_multicast, ..
+            }
+            | FirewallPolicy::Connected {
+                allow_multicast, ..
+            }
+            | FirewallPolicy::Blocked {
+                allow_multicast, ..
+            } => *allow_multicast,
+        }
+    }
+
+    /// Returns the traffic to permit outside the tunnel while signing in to a captive portal.
+    pub fn captive_portal(&self) -> Option<CaptivePortal> {
+        match self {
+            FirewallPolicy::Connecting { captive_portal, .. }
+            | FirewallPolicy::Blocked { captive_portal, .. } => *captive_portal,
+            FirewallPolicy::Connected { .. } => None,
+        }
+    }
+}
+
 impl fmt::Display for FirewallPolicy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -214,7 +402,11 @@ impl fmt::Display for FirewallPolicy {
                 if *allow_lan { "Allowing" } else { "Blocking" },
                 allowed_endpoint,
             ),
+        }?;
+        if let Some(captive_portal) = self.captive_portal() {
+            write!(f, ". Allowing {} for a captive portal", captive_portal)?;
         }
+        Ok(())
     }
 }
 
@@ -230,6 +422,15 @@ pub struct FirewallArguments {
     pub initial_state: InitialFirewallState,
     /// This argument is required for the blocked state to configure the firewall correctly.
     pub allow_lan: bool,
+    /// The networks that are reachable in the initial blocked state when `allow_lan` is set.
+    pub allowed_lan_nets: Vec<IpNetwork>,
+    /// Ports to keep open towards the local network in the initial blocked state.
+    pub firewall_exceptions: Vec<FirewallException>,
+    /// Whether to allow mDNS and SSDP discovery in the initial blocked state.
+    pub allow_multicast: bool,
+    /// Link layer protocols to permit while in the initial blocked state.
+    #[cfg(windows)]
+    pub link_layer_exemptions: LinkLayerExemptions,
 }
 
 /// State to enter during firewall init.
@@ -269,3 +470,47 @@ impl Firewall {
         self.inner.reset_policy()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::{FirewallExceptionDirection, TransportProtocol};
+
+    #[test]
+    fn test_firewall_exceptions_apply_to_allowed_lan_nets() {
+        let allowed_lan_nets: Vec<IpNetwork> = vec!["192.168.1.0/24".parse().unwrap()];
+        let policy = FirewallPolicy::Blocked {
+            allow_lan: false,
+            allowed_lan_nets: allowed_lan_nets.clone(),
+            firewall_exceptions: vec![FirewallException {
+                direction: FirewallExceptionDirection::Inbound,
+                protocol: TransportProtocol::Tcp,
+                port: 8765,
+            }],
+            allow_multicast: false,
+            allowed_endpoint: AllowedEndpoint {
+                #[cfg(windows)]
+                clients: vec![],
+                endpoint: Endpoint::new(Ipv4Addr::new(1, 2, 3, 4), 443, TransportProtocol::Tcp),
+            },
+            captive_portal: None,
+            #[cfg(target_os = "macos")]
+            dns_redirect_port: 0,
+            #[cfg(windows)]
+            link_layer_exemptions: LinkLayerExemptions::default(),
+        };
+
+        // The networks apply to firewall exceptions even when LAN traffic is blocked
+        assert_eq!(policy.allowed_lan_nets(), &allowed_lan_nets[..]);
+        assert_eq!(policy.firewall_exceptions().len(), 1);
+    }
+
+    #[test]
+    fn test_is_local_network() {
+        assert!(is_local_network(&"192.168.1.0/24".parse().unwrap()));
+        assert!(is_local_network(&"fe80::/64".parse().unwrap()));
+        assert!(!is_local_network(&"10.0.0.0/7".parse().unwrap()));
+        assert!(!is_local_network(&"1.2.3.0/24".parse().unwrap()));
+        assert!(default_allowed_lan_nets().iter().all(is_local_network));
+    }
+}
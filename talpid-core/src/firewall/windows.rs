@@ -1,12 +1,16 @@
 use crate::{logging::windows::log_sink, tunnel::TunnelMetadata};
 
+use ipnetwork::IpNetwork;
 use std::{net::IpAddr, path::Path, ptr};
 
 use self::winfw::*;
 use super::{FirewallArguments, FirewallPolicy, InitialFirewallState};
 use crate::winnet;
 use talpid_types::{
-    net::{AllowedEndpoint, AllowedTunnelTraffic, Endpoint},
+    net::{
+        AllowedEndpoint, AllowedTunnelTraffic, CaptivePortal, Endpoint, FirewallException,
+        InboundTunnelPort, LinkLayerExemptions,
+    },
     tunnel::FirewallPolicyError,
 };
 use widestring::WideCString;
@@ -55,7 +59,14 @@ pub struct Firewall(());
 impl Firewall {
     pub fn from_args(args: FirewallArguments) -> Result<Self, Error> {
         if let InitialFirewallState::Blocked(allowed_endpoint) = args.initial_state {
-            Self::initialize_blocked(allowed_endpoint, args.allow_lan)
+            Self::initialize_blocked(
+                allowed_endpoint,
+                args.allow_lan,
+                args.allow_multicast,
+                &args.allowed_lan_nets,
+                &args.firewall_exceptions,
+                args.link_layer_exemptions,
+            )
         } else {
             Self::new()
         }
@@ -78,8 +89,20 @@ impl Firewall {
     fn initialize_blocked(
         allowed_endpoint: AllowedEndpoint,
         allow_lan: bool,
+        allow_multicast: bool,
+        allowed_lan_nets: &[IpNetwork],
+        firewall_exceptions: &[FirewallException],
+        link_layer_exemptions: LinkLayerExemptions,
     ) -> Result<Self, Error> {
-        let cfg = &WinFwSettings::new(allow_lan);
+        let settings = WinFwSettingsContainer::new(
+            allow_lan,
+            allow_multicast,
+            allowed_lan_nets,
+            firewall_exceptions,
+            None,
+            link_layer_exemptions,
+        );
+        let cfg = &settings.as_settings();
         let allowed_endpoint = WinFwAllowedEndpointContainer::from(allowed_endpoint);
         unsafe {
             WinFw_InitializeBlocked(
@@ -101,11 +124,24 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
+                firewall_exceptions,
+                allow_multicast,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                captive_portal,
                 relay_client,
+                link_layer_exemptions,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
+                let settings = WinFwSettingsContainer::new(
+                    allow_lan,
+                    allow_multicast,
+                    &allowed_lan_nets,
+                    &firewall_exceptions,
+                    captive_portal,
+                    link_layer_exemptions,
+                );
+                let cfg = &settings.as_settings();
 
                 self.set_connecting_state(
                     &peer_endpoint,
@@ -120,17 +156,60 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
+                firewall_exceptions,
+                inbound_tunnel_ports,
+                allow_multicast,
                 dns_servers,
                 relay_client,
+                link_layer_exemptions,
+                strict_dns_leak_prevention,
+                excluded_networks,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
-                self.set_connected_state(&peer_endpoint, &cfg, &tunnel, &dns_servers, &relay_client)
+                let settings = WinFwSettingsContainer::new(
+                    allow_lan,
+                    allow_multicast,
+                    &allowed_lan_nets,
+                    &firewall_exceptions,
+                    None,
+                    link_layer_exemptions,
+                );
+                let cfg = &settings.as_settings();
+                let doh_servers = if strict_dns_leak_prevention {
+                    super::blocked_doh_resolvers(&dns_servers)
+                } else {
+                    vec![]
+                };
+                self.set_connected_state(
+                    &peer_endpoint,
+                    &cfg,
+                    &tunnel,
+                    &dns_servers,
+                    strict_dns_leak_prevention,
+                    &doh_servers,
+                    &inbound_tunnel_ports,
+                    &excluded_networks,
+                    &relay_client,
+                )
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                allowed_lan_nets,
+                firewall_exceptions,
+                allow_multicast,
                 allowed_endpoint,
+                captive_portal,
+                link_layer_exemptions,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
+                let settings = WinFwSettingsContainer::new(
+                    allow_lan,
+                    allow_multicast,
+                    &allowed_lan_nets,
+                    &firewall_exceptions,
+                    captive_portal,
+                    link_layer_exemptions,
+                );
+                let cfg = &settings.as_settings();
                 self.set_blocked_state(
                     &cfg,
                     &WinFwAllowedEndpointContainer::from(allowed_endpoint).as_endpoint(),
@@ -147,7 +226,7 @@ impl Firewall {
     fn set_connecting_state(
         &mut self,
         endpoint: &Endpoint,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         tunnel_metadata: &Option<TunnelMetadata>,
         allowed_endpoint: &WinFwAllowedEndpoint<'_>,
         allowed_tunnel_traffic: &AllowedTunnelTraffic,
@@ -209,9 +288,13 @@ impl Firewall {
     fn set_connected_state(
         &mut self,
         endpoint: &Endpoint,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         tunnel_metadata: &TunnelMetadata,
         dns_servers: &[IpAddr],
+        block_encrypted_dns: bool,
+        doh_servers: &[IpAddr],
+        inbound_tunnel_ports: &[InboundTunnelPort],
+        excluded_networks: &[IpNetwork],
         relay_client: &Path,
     ) -> Result<(), Error> {
         log::trace!("Applying 'connected' firewall policy");
@@ -250,6 +333,18 @@ impl Firewall {
             dns_servers.iter().cloned().map(widestring_ip).collect();
         let dns_servers: Vec<*const u16> = dns_servers.iter().map(|ip| ip.as_ptr()).collect();
 
+        let doh_servers: Vec<WideCString> =
+            doh_servers.iter().cloned().map(widestring_ip).collect();
+        let doh_servers: Vec<*const u16> = doh_servers.iter().map(|ip| ip.as_ptr()).collect();
+
+        let inbound_ports: Vec<WinFwInboundPort> = inbound_tunnel_ports
+            .iter()
+            .map(WinFwInboundPort::from)
+            .collect();
+
+        let excluded_networks = WinFwNetworksContainer::new(excluded_networks);
+        let excluded_networks = excluded_networks.as_slice();
+
         unsafe {
             WinFw_ApplyPolicyConnected(
                 winfw_settings,
@@ -260,6 +355,13 @@ impl Firewall {
                 v6_gateway_ptr,
                 dns_servers.as_ptr(),
                 dns_servers.len(),
+                block_encrypted_dns,
+                doh_servers.as_ptr(),
+                doh_servers.len(),
+                inbound_ports.as_ptr(),
+                inbound_ports.len(),
+                excluded_networks.as_ptr(),
+                excluded_networks.len(),
             )
             .into_result()
             .map_err(Error::ApplyingConnectedPolicy)
@@ -268,7 +370,7 @@ impl Firewall {
 
     fn set_blocked_state(
         &mut self,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         allowed_endpoint: &WinFwAllowedEndpoint<'_>,
     ) -> Result<(), Error> {
         log::trace!("Applying 'blocked' firewall policy");
@@ -300,10 +402,14 @@ fn widestring_ip(ip: IpAddr) -> WideCString {
 
 #[allow(non_snake_case)]
 mod winfw {
-    use super::{widestring_ip, AllowedEndpoint, AllowedTunnelTraffic, Error, WideCString};
+    use super::{
+        widestring_ip, AllowedEndpoint, AllowedTunnelTraffic, CaptivePortal, Error,
+        FirewallException, InboundTunnelPort, IpNetwork, LinkLayerExemptions, WideCString,
+    };
     use crate::logging::windows::LogSink;
     use libc;
-    use talpid_types::net::TransportProtocol;
+    use std::ptr;
+    use talpid_types::net::{FirewallExceptionDirection, TransportProtocol};
 
     pub struct WinFwAllowedEndpointContainer {
         _clients: Box<[WideCString]>,
@@ -408,21 +514,168 @@ mod winfw {
         }
     }
 
-    #[repr(C)]
-    pub struct WinFwSettings {
-        permitDhcp: bool,
-        permitLan: bool,
+    /// Networks as passed to the firewall module. The addresses have to outlive the networks,
+    /// which point to them.
+    pub struct WinFwNetworksContainer {
+        _addresses: Box<[WideCString]>,
+        networks: Box<[WinFwAllowedLanNet]>,
     }
 
-    impl WinFwSettings {
-        pub fn new(permit_lan: bool) -> WinFwSettings {
+    impl WinFwNetworksContainer {
+        pub fn new(networks: &[IpNetwork]) -> Self {
+            let addresses = networks
+                .iter()
+                .map(|net| widestring_ip(net.ip()))
+                .collect::<Box<_>>();
+            let networks = addresses
+                .iter()
+                .zip(networks)
+                .map(|(address, net)| WinFwAllowedLanNet {
+                    address: address.as_ptr(),
+                    prefix_length: net.prefix(),
+                })
+                .collect::<Box<_>>();
+            WinFwNetworksContainer {
+                _addresses: addresses,
+                networks,
+            }
+        }
+
+        pub fn as_slice(&self) -> &[WinFwAllowedLanNet] {
+            &self.networks
+        }
+    }
+
+    pub struct WinFwSettingsContainer {
+        lan_nets: WinFwNetworksContainer,
+        firewall_exceptions: Box<[WinFwFirewallException]>,
+        captive_portal: Option<CaptivePortal>,
+        captive_portal_address: Option<WideCString>,
+        permit_dhcp: bool,
+        permit_ndp: bool,
+        permit_lan: bool,
+        permit_multicast: bool,
+    }
+
+    impl WinFwSettingsContainer {
+        pub fn new(
+            permit_lan: bool,
+            permit_multicast: bool,
+            allowed_lan_nets: &[IpNetwork],
+            firewall_exceptions: &[FirewallException],
+            captive_portal: Option<CaptivePortal>,
+            exemptions: LinkLayerExemptions,
+        ) -> Self {
+            let lan_nets = WinFwNetworksContainer::new(allowed_lan_nets);
+            let firewall_exceptions = firewall_exceptions
+                .iter()
+                .map(WinFwFirewallException::from)
+                .collect::<Box<_>>();
+            let captive_portal_address = match captive_portal {
+                Some(CaptivePortal::Address(address)) => Some(widestring_ip(address)),
+                _ => None,
+            };
+
+            WinFwSettingsContainer {
+                lan_nets,
+                firewall_exceptions,
+                captive_portal,
+                captive_portal_address,
+                permit_dhcp: exemptions.permit_dhcp,
+                permit_ndp: exemptions.permit_ndp,
+                permit_lan,
+                permit_multicast,
+            }
+        }
+
+        pub fn as_settings(&self) -> WinFwSettings<'_> {
             WinFwSettings {
-                permitDhcp: true,
-                permitLan: permit_lan,
+                permitDhcp: self.permit_dhcp,
+                permitNdp: self.permit_ndp,
+                permitLan: self.permit_lan,
+                permitMulticast: self.permit_multicast,
+                allowedLanNets: self.lan_nets.as_slice().as_ptr(),
+                numAllowedLanNets: self.lan_nets.as_slice().len() as u32,
+                firewallExceptions: self.firewall_exceptions.as_ptr(),
+                numFirewallExceptions: self.firewall_exceptions.len() as u32,
+                permitCaptivePortal: self.captive_portal.is_some(),
+                captivePortalAddress: self
+                    .captive_portal_address
+                    .as_ref()
+                    .map(|address| address.as_ptr())
+                    .unwrap_or(ptr::null()),
+
+                _phantom: std::marker::PhantomData,
             }
         }
     }
 
+    #[repr(C)]
+    pub struct WinFwAllowedLanNet {
+        address: *const libc::wchar_t,
+        prefix_length: u8,
+    }
+
+    #[repr(u8)]
+    #[derive(Clone, Copy)]
+    pub enum WinFwFirewallExceptionDirection {
+        Inbound = 0u8,
+        Outbound = 1u8,
+    }
+
+    #[repr(C)]
+    pub struct WinFwFirewallException {
+        direction: WinFwFirewallExceptionDirection,
+        protocol: WinFwProt,
+        port: u16,
+    }
+
+    impl From<&FirewallException> for WinFwFirewallException {
+        fn from(exception: &FirewallException) -> Self {
+            WinFwFirewallException {
+                direction: match exception.direction {
+                    FirewallExceptionDirection::Inbound => WinFwFirewallExceptionDirection::Inbound,
+                    FirewallExceptionDirection::Outbound => {
+                        WinFwFirewallExceptionDirection::Outbound
+                    }
+                },
+                protocol: WinFwProt::from(exception.protocol),
+                port: exception.port,
+            }
+        }
+    }
+
+    #[repr(C)]
+    pub struct WinFwInboundPort {
+        protocol: WinFwProt,
+        port: u16,
+    }
+
+    impl From<&InboundTunnelPort> for WinFwInboundPort {
+        fn from(port: &InboundTunnelPort) -> Self {
+            WinFwInboundPort {
+                protocol: WinFwProt::from(port.protocol),
+                port: port.port,
+            }
+        }
+    }
+
+    #[repr(C)]
+    pub struct WinFwSettings<'a> {
+        permitDhcp: bool,
+        permitNdp: bool,
+        permitLan: bool,
+        permitMulticast: bool,
+        allowedLanNets: *const WinFwAllowedLanNet,
+        numAllowedLanNets: u32,
+        firewallExceptions: *const WinFwFirewallException,
+        numFirewallExceptions: u32,
+        permitCaptivePortal: bool,
+        captivePortalAddress: *const libc::wchar_t,
+
+        _phantom: std::marker::PhantomData<&'a WinFwSettingsContainer>,
+    }
+
     #[allow(dead_code)]
     #[repr(u32)]
     #[derive(Clone, Copy)]
@@ -473,7 +726,7 @@ mod winfw {
         #[link_name = "WinFw_InitializeBlocked"]
         pub fn WinFw_InitializeBlocked(
             timeout: libc::c_uint,
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             allowed_endpoint: *const WinFwAllowedEndpoint<'_>,
             sink: Option<LogSink>,
             sink_context: *const u8,
@@ -484,7 +737,7 @@ mod winfw {
 
         #[link_name = "WinFw_ApplyPolicyConnecting"]
         pub fn WinFw_ApplyPolicyConnecting(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             relay: &WinFwEndpoint,
             relayClient: *const libc::wchar_t,
             tunnelIfaceAlias: *const libc::wchar_t,
@@ -494,7 +747,7 @@ mod winfw {
 
         #[link_name = "WinFw_ApplyPolicyConnected"]
         pub fn WinFw_ApplyPolicyConnected(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             relay: &WinFwEndpoint,
             relayClient: *const libc::wchar_t,
             tunnelIfaceAlias: *const libc::wchar_t,
@@ -502,15 +755,114 @@ mod winfw {
             v6Gateway: *const libc::wchar_t,
             dnsServers: *const *const libc::wchar_t,
             numDnsServers: usize,
+            blockEncryptedDns: bool,
+            dohServers: *const *const libc::wchar_t,
+            numDohServers: usize,
+            inboundPorts: *const WinFwInboundPort,
+            numInboundPorts: usize,
+            excludedNetworks: *const WinFwAllowedLanNet,
+            numExcludedNetworks: usize,
         ) -> WinFwPolicyStatus;
 
         #[link_name = "WinFw_ApplyPolicyBlocked"]
         pub fn WinFw_ApplyPolicyBlocked(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             allowed_endpoint: *const WinFwAllowedEndpoint<'_>,
         ) -> WinFwPolicyStatus;
 
         #[link_name = "WinFw_Reset"]
         pub fn WinFw_Reset() -> WinFwPolicyStatus;
     }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_link_layer_exemptions() {
+            let exemptions = |permit_dhcp, permit_ndp| {
+                let container = WinFwSettingsContainer::new(
+                    false,
+                    false,
+                    &[],
+                    &[],
+                    None,
+                    LinkLayerExemptions {
+                        permit_dhcp,
+                        permit_ndp,
+                    },
+                );
+                let settings = container.as_settings();
+                (settings.permitDhcp, settings.permitNdp)
+            };
+
+            assert_eq!(exemptions(true, true), (true, true));
+            assert_eq!(exemptions(true, false), (true, false));
+            assert_eq!(exemptions(false, true), (false, true));
+            assert_eq!(exemptions(false, false), (false, false));
+
+            let default = LinkLayerExemptions::default();
+            assert!(default.permit_dhcp && default.permit_ndp);
+        }
+
+        #[test]
+        fn test_allowed_lan_nets() {
+            let allowed_lan_nets: Vec<IpNetwork> = vec![
+                "192.168.1.0/24".parse().unwrap(),
+                "fd00::/8".parse().unwrap(),
+            ];
+            let container = WinFwSettingsContainer::new(
+                true,
+                false,
+                &allowed_lan_nets,
+                &[],
+                None,
+                LinkLayerExemptions::default(),
+            );
+            let settings = container.as_settings();
+
+            assert!(settings.permitLan);
+            assert_eq!(settings.numAllowedLanNets, 2);
+            let nets = unsafe { std::slice::from_raw_parts(settings.allowedLanNets, 2) };
+            let address = |net: &WinFwAllowedLanNet| {
+                unsafe { WideCString::from_ptr_str(net.address) }.to_string_lossy()
+            };
+            assert_eq!(address(&nets[0]), "192.168.1.0");
+            assert_eq!(nets[0].prefix_length, 24);
+            assert_eq!(address(&nets[1]), "fd00::");
+            assert_eq!(nets[1].prefix_length, 8);
+        }
+
+        #[test]
+        fn test_firewall_exceptions_are_passed_with_allowed_lan_nets() {
+            let allowed_lan_nets: Vec<IpNetwork> = vec!["192.168.1.0/24".parse().unwrap()];
+            let exceptions = [FirewallException {
+                direction: FirewallExceptionDirection::Inbound,
+                protocol: TransportProtocol::Tcp,
+                port: 8765,
+            }];
+            let container = WinFwSettingsContainer::new(
+                false,
+                false,
+                &allowed_lan_nets,
+                &exceptions,
+                None,
+                LinkLayerExemptions::default(),
+            );
+            let settings = container.as_settings();
+
+            // The exceptions apply to the allowed networks, so they are passed even when LAN
+            // traffic is blocked
+            assert!(!settings.permitLan);
+            assert_eq!(settings.numAllowedLanNets, 1);
+            assert_eq!(settings.numFirewallExceptions, 1);
+            let exception = unsafe { &*settings.firewallExceptions };
+            assert!(matches!(
+                exception.direction,
+                WinFwFirewallExceptionDirection::Inbound
+            ));
+            assert!(matches!(exception.protocol, WinFwProt::Tcp));
+            assert_eq!(exception.port, 8765);
+        }
+    }
 }
@@ -1,7 +1,12 @@
 use std::{
     ffi::{self, CString},
     fs, io,
+    sync::atomic::{AtomicU32, Ordering},
 };
+use talpid_types::net::PolicyRouting;
+
+pub mod netns;
+pub mod rate_limit;
 
 const PROC_SYS_NET_IPV4_CONF_SRC_VALID_MARK: &str = "/proc/sys/net/ipv4/conf/all/src_valid_mark";
 
@@ -28,9 +33,26 @@ pub enum IfaceIndexLookupError {
     InterfaceLookupError(String, #[error(source)] io::Error),
 }
 
-// b"mole" is [ 0x6d, 0x6f 0x6c, 0x65 ]
-pub const TUNNEL_FW_MARK: u32 = 0x6d6f6c65;
-pub const TUNNEL_TABLE_ID: u32 = 0x6d6f6c65;
+static TUNNEL_FW_MARK: AtomicU32 = AtomicU32::new(PolicyRouting::DEFAULT_FWMARK);
+static TUNNEL_TABLE_ID: AtomicU32 = AtomicU32::new(PolicyRouting::DEFAULT_TABLE_ID);
+
+/// Sets the firewall mark and routing table that are used to route traffic outside the tunnel.
+/// This must be done before any route manager, firewall or tunnel is created, since the values
+/// are not updated for those that exist.
+pub fn set_policy_routing(routing: PolicyRouting) {
+    TUNNEL_FW_MARK.store(routing.fwmark, Ordering::Relaxed);
+    TUNNEL_TABLE_ID.store(routing.table_id, Ordering::Relaxed);
+}
+
+/// Firewall mark of traffic that should bypass the tunnel.
+pub fn tunnel_fw_mark() -> u32 {
+    TUNNEL_FW_MARK.load(Ordering::Relaxed)
+}
+
+/// ID of the routing table that routes traffic through the tunnel.
+pub fn tunnel_table_id() -> u32 {
+    TUNNEL_TABLE_ID.load(Ordering::Relaxed)
+}
 
 pub fn set_src_valid_mark_sysctl() -> io::Result<()> {
     fs::write(PROC_SYS_NET_IPV4_CONF_SRC_VALID_MARK, b"1")
@@ -0,0 +1,104 @@
+//! Named network namespaces, as managed by `ip netns`. The WireGuard interface can be moved into
+//! one of these, so that only processes that run in the namespace use the tunnel.
+
+use std::{
+    fs::{self, File},
+    io,
+    net::IpAddr,
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+    thread,
+};
+use talpid_types::net::wireguard::is_valid_namespace_name;
+
+/// Directory in which `ip netns` keeps a bind mount of each named namespace.
+const NETNS_RUN_DIR: &str = "/run/netns";
+
+/// Files in `/etc/netns/<name>/` are bind mounted over the ones in `/etc/` by `ip netns exec`.
+const NETNS_ETC_DIR: &str = "/etc/netns";
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Invalid network namespace name: {}", _0)]
+    InvalidName(String),
+
+    #[error(display = "Failed to open network namespace {}", _0)]
+    Open(String, #[error(source)] io::Error),
+
+    #[error(display = "Failed to enter network namespace")]
+    Enter(#[error(source)] nix::Error),
+
+    #[error(display = "Failed to write resolv.conf for network namespace")]
+    WriteResolvConf(#[error(source)] io::Error),
+
+    #[error(display = "Thread running in network namespace panicked")]
+    ThreadPanicked,
+}
+
+/// An existing named network namespace.
+pub struct Namespace {
+    name: String,
+    file: File,
+}
+
+impl Namespace {
+    /// Opens the namespace that was created by `ip netns add <name>`.
+    pub fn open(name: &str) -> Result<Self, Error> {
+        if !is_valid_namespace_name(name) {
+            return Err(Error::InvalidName(name.to_owned()));
+        }
+        let file = File::open(Path::new(NETNS_RUN_DIR).join(name))
+            .map_err(|error| Error::Open(name.to_owned(), error))?;
+        Ok(Self {
+            name: name.to_owned(),
+            file,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs `f` on a new thread that has entered the namespace, and returns its result. Sockets
+    /// that are created by `f` keep operating in the namespace once it returns.
+    pub fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let file = self
+            .file
+            .try_clone()
+            .map_err(|error| Error::Open(self.name.clone(), error))?;
+        thread::spawn(move || {
+            nix::sched::setns(file.as_raw_fd(), nix::sched::CloneFlags::CLONE_NEWNET)
+                .map_err(Error::Enter)?;
+            Ok(f())
+        })
+        .join()
+        .map_err(|_| Error::ThreadPanicked)?
+    }
+
+    /// Sets the DNS servers and search domains of processes that are started using
+    /// `ip netns exec`. The file is left in place when the tunnel goes down, since nothing in the
+    /// namespace is reachable then.
+    pub fn set_dns(&self, servers: &[IpAddr], search_domains: &[String]) -> Result<(), Error> {
+        let dir = Path::new(NETNS_ETC_DIR).join(&self.name);
+        fs::create_dir_all(&dir).map_err(Error::WriteResolvConf)?;
+        let mut content: String = servers
+            .iter()
+            .map(|server| format!("nameserver {}\n", server))
+            .collect();
+        if !search_domains.is_empty() {
+            content.push_str(&format!("search {}\n", search_domains.join(" ")));
+        }
+        fs::write(dir.join("resolv.conf"), content).map_err(Error::WriteResolvConf)
+    }
+}
+
+impl AsRawFd for Namespace {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
@@ -0,0 +1,84 @@
+//! Caps the throughput of the tunnel interface using `tc`. Outgoing traffic is shaped by a token
+//! bucket filter, and incoming traffic above the rate is dropped by a policer, which makes the
+//! remote end back off. The rules are removed together with the interface.
+
+use std::io;
+
+/// Milliseconds of traffic at the full rate that may be sent in a burst.
+const BURST_MILLIS: u64 = 100;
+
+/// Smallest burst, in bytes. Smaller buckets cannot hold a full packet at low rates.
+const MIN_BURST: u64 = 16 * 1024;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    #[error(display = "Failed to limit outgoing traffic on {}", _0)]
+    Egress(String, #[error(source)] io::Error),
+
+    #[error(display = "Failed to limit incoming traffic on {}", _0)]
+    Ingress(String, #[error(source)] io::Error),
+}
+
+/// Limits the traffic on `interface` to `rate` bits per second in each direction. `namespace`
+/// is the network namespace that the interface is in, if it is not in the root namespace.
+pub fn apply(interface: &str, namespace: Option<&str>, rate: u64) -> Result<(), Error> {
+    let rate_arg = format!("{}bit", rate);
+    let burst_arg = burst(rate).to_string();
+
+    tc(
+        namespace,
+        &[
+            "qdisc", "replace", "dev", interface, "root", "tbf", "rate", &rate_arg, "burst",
+            &burst_arg, "latency", "50ms",
+        ],
+    )
+    .map_err(|error| Error::Egress(interface.to_owned(), error))?;
+
+    tc(
+        namespace,
+        &[
+            "qdisc", "replace", "dev", interface, "handle", "ffff:", "ingress",
+        ],
+    )
+    .and_then(|_| {
+        tc(
+            namespace,
+            &[
+                "filter", "replace", "dev", interface, "parent", "ffff:", "protocol", "all",
+                "prio", "1", "u32", "match", "u32", "0", "0", "police", "rate", &rate_arg, "burst",
+                &burst_arg, "drop", "flowid", ":1",
+            ],
+        )
+    })
+    .map_err(|error| Error::Ingress(interface.to_owned(), error))
+}
+
+/// Returns the size of the token bucket for `rate` bits per second, in bytes.
+fn burst(rate: u64) -> u64 {
+    (rate / 8 * BURST_MILLIS / 1000).max(MIN_BURST)
+}
+
+fn tc(namespace: Option<&str>, args: &[&str]) -> io::Result<()> {
+    let mut all_args = vec![];
+    if let Some(namespace) = namespace {
+        all_args.extend(["-n", namespace]);
+    }
+    all_args.extend(args);
+    duct::cmd("tc", all_args)
+        .stdout_null()
+        .stderr_capture()
+        .run()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_burst() {
+        assert_eq!(burst(1_000_000), MIN_BURST);
+        assert_eq!(burst(100_000_000), 1_250_000);
+    }
+}
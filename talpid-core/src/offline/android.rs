@@ -39,6 +39,8 @@ pub enum Error {
     InvalidMethodResult(&'static str, &'static str, String),
 }
 
+pub const DETECTION_METHOD: &str = "the system reports that there is no network connection";
+
 pub struct MonitorHandle {
     jvm: Arc<JavaVM>,
     class: GlobalRef,
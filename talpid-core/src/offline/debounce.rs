@@ -0,0 +1,226 @@
+//! Holds back changes in connectivity until they have settled, so that a flapping network does not
+//! make the tunnel reconnect over and over. The offline detection settings are applied here too.
+
+use super::Detection;
+use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use talpid_types::net::ReconnectDebounce;
+
+/// The window that `ReconnectDebounce::max_reconnects_per_minute` applies to.
+const RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Decides when a change in connectivity may be passed on.
+struct Debouncer {
+    settings: Arc<Mutex<ReconnectDebounce>>,
+    /// Holds the connectivity that was last passed on.
+    detection: Arc<Mutex<Detection>>,
+    /// When the host came back online, within the last `RECONNECT_WINDOW`.
+    reconnects: VecDeque<Instant>,
+}
+
+impl Debouncer {
+    fn new(settings: Arc<Mutex<ReconnectDebounce>>, detection: Arc<Mutex<Detection>>) -> Self {
+        Debouncer {
+            settings,
+            detection,
+            reconnects: VecDeque::new(),
+        }
+    }
+
+    fn reported_offline(&self) -> bool {
+        self.detection.lock().unwrap().is_offline
+    }
+
+    /// Records what the offline monitor reported. Returns the connectivity to pass on.
+    fn monitor_offline(&mut self, monitor_offline: bool) -> bool {
+        let mut detection = self.detection.lock().unwrap();
+        detection.monitor_offline = monitor_offline;
+        detection.apply(monitor_offline)
+    }
+
+    /// Returns when `offline` may be passed on, if it is still the connectivity by then.
+    fn deadline(&mut self, offline: bool, now: Instant) -> Instant {
+        let settings = *self.settings.lock().unwrap();
+        let mut deadline = now + settings.min_stable_interval;
+
+        if offline && !self.reported_offline() {
+            let grace_period = self.detection.lock().unwrap().settings.grace_period;
+            deadline = deadline.max(now + grace_period);
+        }
+
+        while let Some(reconnect) = self.reconnects.front() {
+            if now.saturating_duration_since(*reconnect) < RECONNECT_WINDOW {
+                break;
+            }
+            self.reconnects.pop_front();
+        }
+
+        let max_reconnects = settings
+            .max_reconnects_per_minute
+            .filter(|max| *max > 0)
+            .map(|max| max as usize);
+        if let Some(max_reconnects) = max_reconnects {
+            if self.is_reconnect(offline) && self.reconnects.len() >= max_reconnects {
+                let oldest = self.reconnects[self.reconnects.len() - max_reconnects];
+                deadline = deadline.max(oldest + RECONNECT_WINDOW);
+            }
+        }
+        deadline
+    }
+
+    /// Records that `offline` has been stable until `now`. Returns whether it should be passed on.
+    fn settled(&mut self, offline: bool, now: Instant) -> bool {
+        if offline == self.reported_offline() {
+            return false;
+        }
+        if self.is_reconnect(offline) {
+            self.reconnects.push_back(now);
+        }
+        self.detection.lock().unwrap().is_offline = offline;
+        true
+    }
+
+    fn is_reconnect(&self, offline: bool) -> bool {
+        self.reported_offline() && !offline
+    }
+}
+
+/// Passes the connectivity received on `offline_rx` on to `forward` once it has stayed unchanged
+/// for as long as `settings` and the offline detection settings require. Changes that are undone
+/// before then are dropped. Returns when `offline_rx` is closed or `forward` returns `false`.
+pub async fn forward(
+    mut offline_rx: UnboundedReceiver<bool>,
+    settings: Arc<Mutex<ReconnectDebounce>>,
+    detection: Arc<Mutex<Detection>>,
+    mut forward: impl FnMut(bool) -> bool,
+) {
+    let mut debouncer = Debouncer::new(settings, detection);
+    let mut pending = None;
+
+    loop {
+        let next = match pending {
+            None => offline_rx.next().await,
+            Some(offline) => {
+                let deadline = debouncer.deadline(offline, Instant::now());
+                let deadline = tokio::time::Instant::from_std(deadline);
+                match tokio::time::timeout_at(deadline, offline_rx.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        pending = None;
+                        if debouncer.settled(offline, Instant::now()) && !forward(offline) {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+        match next {
+            Some(monitor_offline) => {
+                let offline = debouncer.monitor_offline(monitor_offline);
+                if pending.is_none() && offline != debouncer.reported_offline() {
+                    log::trace!("Waiting for connectivity to settle");
+                }
+                pending = Some(offline);
+            }
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::OfflineDetection;
+
+    fn debouncer(min_stable_interval: Duration, max: Option<u32>) -> Debouncer {
+        let settings = ReconnectDebounce {
+            min_stable_interval,
+            max_reconnects_per_minute: max,
+        };
+        let detection = Detection {
+            is_offline: true,
+            ..Detection::default()
+        };
+        Debouncer::new(
+            Arc::new(Mutex::new(settings)),
+            Arc::new(Mutex::new(detection)),
+        )
+    }
+
+    #[test]
+    fn test_min_stable_interval() {
+        let interval = Duration::from_secs(5);
+        let mut debouncer = debouncer(interval, None);
+        let now = Instant::now();
+
+        assert_eq!(debouncer.deadline(false, now), now + interval);
+        assert!(debouncer.settled(false, now + interval));
+        assert!(!debouncer.settled(false, now + interval));
+        assert_eq!(debouncer.deadline(true, now + interval), now + 2 * interval);
+    }
+
+    #[test]
+    fn test_max_reconnects_per_minute() {
+        let mut debouncer = debouncer(Duration::ZERO, Some(2));
+        let start = Instant::now();
+
+        let first = start;
+        assert_eq!(debouncer.deadline(false, first), first);
+        assert!(debouncer.settled(false, first));
+        assert!(debouncer.settled(true, first));
+
+        let second = start + Duration::from_secs(10);
+        assert_eq!(debouncer.deadline(false, second), second);
+        assert!(debouncer.settled(false, second));
+        assert!(debouncer.settled(true, second));
+
+        // The third reconnect within a minute has to wait for the first to fall out of the window.
+        let third = start + Duration::from_secs(20);
+        assert_eq!(debouncer.deadline(false, third), first + RECONNECT_WINDOW);
+        // Going offline is never held back by the limit.
+        assert_eq!(debouncer.deadline(true, third), third);
+
+        let later = first + RECONNECT_WINDOW;
+        assert_eq!(debouncer.deadline(false, later), later);
+    }
+
+    #[test]
+    fn test_no_limit_when_zero() {
+        let mut debouncer = debouncer(Duration::ZERO, Some(0));
+        let now = Instant::now();
+        assert!(debouncer.settled(false, now));
+        assert!(debouncer.settled(true, now));
+        assert_eq!(debouncer.deadline(false, now), now);
+    }
+
+    #[test]
+    fn test_grace_period() {
+        let grace_period = Duration::from_secs(30);
+        let mut debouncer = debouncer(Duration::from_secs(5), None);
+        debouncer.detection.lock().unwrap().settings = OfflineDetection {
+            enabled: true,
+            grace_period,
+        };
+        let now = Instant::now();
+
+        // Coming online is not affected by the grace period.
+        assert_eq!(debouncer.deadline(false, now), now + Duration::from_secs(5));
+        assert!(debouncer.settled(false, now));
+        assert_eq!(debouncer.deadline(true, now), now + grace_period);
+    }
+
+    #[test]
+    fn test_detection_disabled() {
+        let mut debouncer = debouncer(Duration::ZERO, None);
+        assert!(debouncer.monitor_offline(true));
+
+        debouncer.detection.lock().unwrap().settings.enabled = false;
+        assert!(!debouncer.monitor_offline(true));
+        assert!(debouncer.detection.lock().unwrap().monitor_offline);
+    }
+}
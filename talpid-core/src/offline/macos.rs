@@ -30,6 +30,8 @@ pub enum Error {
     StartMonitorError(#[error(source)] crate::routing::PlatformError),
 }
 
+pub const DETECTION_METHOD: &str = "there is no default route that does not use a tunnel";
+
 pub struct MonitorHandle {
     _notify_tx: Arc<UnboundedSender<bool>>,
 }
@@ -5,6 +5,9 @@ use crate::windows::window::PowerManagementListener;
 use futures::channel::mpsc::UnboundedSender;
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+use talpid_types::net::OfflineDetection;
+
+pub mod debounce;
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -31,6 +34,33 @@ lazy_static::lazy_static! {
 
 pub use self::imp::Error;
 
+/// The connectivity of the host, shared between the tunnel state machine and its handle.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Detection {
+    pub settings: OfflineDetection,
+    /// Whether the offline monitor last found the host to be offline.
+    pub monitor_offline: bool,
+    /// Whether the host was last reported to be offline, after applying `settings`.
+    pub is_offline: bool,
+}
+
+impl Detection {
+    /// Returns whether the host should be considered offline when the offline monitor finds it to
+    /// be `monitor_offline`.
+    pub fn apply(&self, monitor_offline: bool) -> bool {
+        monitor_offline && self.settings.enabled
+    }
+}
+
+/// Returns a description of when the host is considered to be offline.
+pub fn detection_method() -> &'static str {
+    if *FORCE_DISABLE_OFFLINE_MONITOR {
+        "never, since the offline monitor is disabled by TALPID_DISABLE_OFFLINE_MONITOR"
+    } else {
+        imp::DETECTION_METHOD
+    }
+}
+
 pub struct MonitorHandle(Option<imp::MonitorHandle>);
 
 impl MonitorHandle {
@@ -20,6 +20,9 @@ pub enum Error {
     ConnectivityMonitorError(#[error(source)] winnet::DefaultRouteCallbackError),
 }
 
+pub const DETECTION_METHOD: &str =
+    "there is no IPv4 or IPv6 connectivity, or the machine is suspended";
+
 pub struct BroadcastListener {
     system_state: Arc<Mutex<SystemState>>,
     _callback_handle: winnet::WinNetCallbackHandle,
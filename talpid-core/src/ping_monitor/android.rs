@@ -43,7 +43,17 @@ impl super::Pinger for Pinger {
     fn send_icmp(&mut self) -> Result<(), Error> {
         self.try_deplete_process_list();
 
-        let cmd = ping_cmd(self.addr, 1);
+        let cmd = ping_cmd(self.addr, 1, None);
+        let handle = cmd.start().map_err(Error::PingError)?;
+        self.processes.push(handle);
+        Ok(())
+    }
+
+    fn send_icmp_sized(&mut self, size: u16) -> Result<(), Error> {
+        self.try_deplete_process_list();
+
+        // `ping` takes the size of the payload, which excludes the IP and ICMP headers
+        let cmd = ping_cmd(self.addr, 1, Some(size.saturating_sub(28)));
         let handle = cmd.start().map_err(Error::PingError)?;
         self.processes.push(handle);
         Ok(())
@@ -75,10 +85,20 @@ impl Drop for Pinger {
     }
 }
 
-fn ping_cmd(ip: Ipv4Addr, timeout_secs: u16) -> duct::Expression {
+fn ping_cmd(ip: Ipv4Addr, timeout_secs: u16, payload_size: Option<u16>) -> duct::Expression {
     let timeout_secs = timeout_secs.to_string();
-    let ip = ip.to_string();
-    let args = ["-n", "-i", "1", "-w", &timeout_secs, &ip];
+    let mut args = vec![
+        "-n".to_owned(),
+        "-i".to_owned(),
+        "1".to_owned(),
+        "-w".to_owned(),
+        timeout_secs,
+    ];
+    if let Some(payload_size) = payload_size {
+        args.push("-s".to_owned());
+        args.push(payload_size.to_string());
+    }
+    args.push(ip.to_string());
 
     duct::cmd("ping", args)
         .stdin_null()
@@ -9,6 +9,8 @@ use std::{
 };
 
 const SEND_RETRY_ATTEMPTS: u32 = 10;
+/// Size of the IPv4 header that the OS adds to each ICMP message
+const IPV4_HEADER_SIZE: u16 = 20;
 
 /// Pinger errors
 #[derive(err_derive::Error, Debug)]
@@ -130,6 +132,12 @@ impl super::Pinger for Pinger {
         self.construct_icmpv4_packet(&mut message)?;
         self.send_ping_request(&message, self.addr)
     }
+
+    fn send_icmp_sized(&mut self, size: u16) -> Result<()> {
+        let mut message = vec![0u8; usize::from(size.saturating_sub(IPV4_HEADER_SIZE))];
+        self.construct_icmpv4_packet(&mut message)?;
+        self.send_ping_request(&message, self.addr)
+    }
 }
 
 trait PayloadWriter {
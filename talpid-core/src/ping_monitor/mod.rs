@@ -12,6 +12,8 @@ pub use imp::Error;
 pub trait Pinger: Send {
     /// Sends an ICMP packet
     fn send_icmp(&mut self) -> Result<(), Error>;
+    /// Sends an ICMP packet that is `size` bytes long, including the IP header
+    fn send_icmp_sized(&mut self, size: u16) -> Result<(), Error>;
     /// Clears all resources used by the pinger.
     fn reset(&mut self) {}
 }
@@ -288,7 +288,7 @@ impl OpenVpnCommand {
 
         #[cfg(target_os = "linux")]
         args.extend(
-            ["--mark", &crate::linux::TUNNEL_FW_MARK.to_string()]
+            ["--mark", &crate::linux::tunnel_fw_mark().to_string()]
                 .iter()
                 .map(OsString::from),
         );
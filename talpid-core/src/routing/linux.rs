@@ -63,8 +63,8 @@ lazy_static! {
             ..RuleHeader::default()
         },
         nlas: vec![
-            RuleNla::FwMark(crate::linux::TUNNEL_FW_MARK),
-            RuleNla::Table(crate::linux::TUNNEL_TABLE_ID),
+            RuleNla::FwMark(crate::linux::tunnel_fw_mark()),
+            RuleNla::Table(crate::linux::tunnel_table_id()),
         ],
     };
     static ref NO_FWMARK_RULE_V6: RuleMessage = {
@@ -798,7 +798,7 @@ impl RouteManagerImpl {
         if set_mark {
             message
                 .nlas
-                .push(RouteNla::Mark(crate::linux::TUNNEL_FW_MARK));
+                .push(RouteNla::Mark(crate::linux::tunnel_fw_mark()));
         }
         message.header.destination_prefix_length = 8u8 * (octets.len() as u8);
         message.header.flags = RouteFlags::RTM_F_FIB_MATCH;
@@ -22,6 +22,9 @@ pub use imp::{Error, RouteManager};
 
 pub use imp::RouteManagerHandle;
 
+#[cfg(target_os = "linux")]
+pub use crate::linux::set_policy_routing;
+
 /// A network route with a specific network node, destinaiton and an optional metric.
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct Route {
@@ -86,7 +89,7 @@ impl RequiredRoute {
             node: node.into(),
             prefix,
             #[cfg(target_os = "linux")]
-            table_id: crate::linux::TUNNEL_TABLE_ID,
+            table_id: crate::linux::tunnel_table_id(),
         }
     }
 
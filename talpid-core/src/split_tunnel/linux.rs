@@ -46,6 +46,10 @@ pub enum Error {
     /// Unable to read /proc/mounts
     #[error(display = "Failed to read /proc/mounts")]
     ListMounts(#[error(source)] io::Error),
+
+    /// Unable to find the child processes of a process
+    #[error(display = "Failed to list processes")]
+    ListProcesses(#[error(source)] io::Error),
 }
 
 /// Manages PIDs in the Linux Cgroup excluded from the VPN tunnel.
@@ -121,6 +125,20 @@ impl PidManager {
             .map_err(Error::AddCGroupPid)
     }
 
+    /// Add a process and all of its descendants to the Cgroup. Processes that are started later
+    /// are excluded as well, since children inherit the Cgroup of their parent.
+    pub fn add_tree(&self, pid: i32) -> Result<(), Error> {
+        // The parent is added first, so that children that it starts meanwhile are included
+        self.add(pid)?;
+        for descendant in descendants(pid).map_err(Error::ListProcesses)? {
+            // Processes may exit before they are added
+            if let Err(error) = self.add(descendant) {
+                log::debug!("Failed to exclude PID {}: {}", descendant, error);
+            }
+        }
+        Ok(())
+    }
+
     /// Remove a PID from the Cgroup to have it included in the tunnel.
     pub fn remove(&self, pid: i32) -> Result<(), Error> {
         // FIXME: We remove PIDs from our cgroup here by adding
@@ -170,3 +188,64 @@ impl PidManager {
         Ok(())
     }
 }
+
+/// Returns the PIDs of all descendants of a process, parents before their children.
+fn descendants(pid: i32) -> io::Result<Vec<i32>> {
+    let mut parents = vec![];
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let child: i32 = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(child) => child,
+            None => continue,
+        };
+        // Processes may exit while they are being listed
+        if let Ok(stat) = fs::read_to_string(entry.path().join("stat")) {
+            if let Some(parent) = parse_parent_pid(&stat) {
+                parents.push((child, parent));
+            }
+        }
+    }
+
+    let children = |pid: i32| {
+        parents
+            .iter()
+            .filter(move |(_, parent)| *parent == pid)
+            .map(|(child, _)| *child)
+    };
+    let mut descendants: Vec<i32> = children(pid).collect();
+    let mut index = 0;
+    while let Some(&descendant) = descendants.get(index) {
+        descendants.extend(children(descendant));
+        index += 1;
+    }
+    Ok(descendants)
+}
+
+/// Returns the parent PID from the contents of `/proc/<pid>/stat`.
+fn parse_parent_pid(stat: &str) -> Option<i32> {
+    // The command name is within parentheses and may contain spaces or parentheses itself
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_parent_pid;
+
+    #[test]
+    fn test_parse_parent_pid() {
+        assert_eq!(
+            parse_parent_pid("1234 (bash) S 1000 1234 1234 0"),
+            Some(1000)
+        );
+        assert_eq!(
+            parse_parent_pid("1234 (Web Content (x)) S 42 1234 1234 0"),
+            Some(42)
+        );
+        assert_eq!(parse_parent_pid("1234 (bash"), None);
+    }
+}
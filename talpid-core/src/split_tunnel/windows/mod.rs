@@ -1,5 +1,6 @@
 mod driver;
 mod path_monitor;
+mod pattern;
 mod volume_monitor;
 mod windows;
 
@@ -435,27 +436,63 @@ impl SplitTunnel {
             };
 
             let mut previous_addresses = InterfaceAddresses::default();
+            let mut resolver = pattern::AppResolver::default();
 
-            while let Ok((request, response_tx)) = rx.recv() {
-                let response = match request {
-                    Request::SetPaths(paths) => {
-                        let mut monitored_paths_guard = monitored_paths.lock().unwrap();
+            let set_paths = |paths: &[OsString]| {
+                let result = if paths.len() > 0 {
+                    handle.set_config(paths).map_err(Error::SetConfiguration)
+                } else {
+                    handle.clear_config().map_err(Error::SetConfiguration)
+                };
 
-                        let result = if paths.len() > 0 {
-                            handle.set_config(&paths).map_err(Error::SetConfiguration)
-                        } else {
-                            handle.clear_config().map_err(Error::SetConfiguration)
-                        };
+                if result.is_ok() {
+                    if let Err(error) = path_monitor.set_paths(paths) {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to update path monitor")
+                        );
+                    }
+                }
+                result
+            };
 
-                        if result.is_ok() {
-                            if let Err(error) = path_monitor.set_paths(&paths) {
+            loop {
+                // Patterns must be resolved again now and then, since the executables that
+                // match them change.
+                let next_request = if resolver.has_patterns() {
+                    rx.recv_timeout(pattern::REFRESH_INTERVAL)
+                } else {
+                    rx.recv()
+                        .map_err(|_| sync_mpsc::RecvTimeoutError::Disconnected)
+                };
+                let (request, response_tx) = match next_request {
+                    Ok(request) => request,
+                    Err(sync_mpsc::RecvTimeoutError::Timeout) => {
+                        let paths = resolver.resolve();
+                        let mut monitored_paths_guard = monitored_paths.lock().unwrap();
+                        if *monitored_paths_guard != paths {
+                            log::debug!("Updating excluded paths that match a pattern");
+                            if let Err(error) = set_paths(&paths) {
                                 log::error!(
                                     "{}",
-                                    error.display_chain_with_msg("Failed to update path monitor")
+                                    error.display_chain_with_msg("Failed to update excluded paths")
                                 );
                             }
+                            *monitored_paths_guard = paths;
                         }
-                        *monitored_paths_guard = paths.to_vec();
+                        continue;
+                    }
+                    Err(sync_mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+
+                let response = match request {
+                    Request::SetPaths(apps) => {
+                        let mut monitored_paths_guard = monitored_paths.lock().unwrap();
+
+                        resolver.set_apps(&apps);
+                        let paths = resolver.resolve();
+                        let result = set_paths(&paths);
+                        *monitored_paths_guard = paths;
 
                         result
                     }
@@ -0,0 +1,222 @@
+//! Resolves excluded applications that are given as patterns into the paths of executables.
+//!
+//! The driver only matches processes against exact paths. Apps that update themselves often move
+//! to a new, versioned directory, so an app can instead be given as a path containing the
+//! wildcards `*` and `?` (e.g. `C:\Program Files\App\*\app.exe`), or as the name of its executable
+//! (e.g. `app.exe`). Path patterns are expanded against the file system. Executable names are
+//! matched against running processes, and each executable that is found is kept, so that the app
+//! is excluded as soon as it is started again.
+
+use super::windows::{get_process_image_path, open_process, ProcessAccess, ProcessSnapshot};
+use std::{
+    collections::BTreeSet,
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use winapi::um::tlhelp32::TH32CS_SNAPPROCESS;
+
+/// How often patterns are resolved again to pick up new executables.
+pub const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Returns whether `app` is a pattern rather than the path of a single executable.
+pub fn is_pattern(app: &OsStr) -> bool {
+    let app = app.to_string_lossy();
+    has_wildcards(&app) || !app.contains(&['\\', '/'][..])
+}
+
+fn has_wildcards(s: &str) -> bool {
+    s.contains(&['*', '?'][..])
+}
+
+/// Turns a list of excluded applications, some of which may be patterns, into paths.
+#[derive(Default)]
+pub struct AppResolver {
+    paths: Vec<OsString>,
+    path_patterns: Vec<PathBuf>,
+    name_patterns: Vec<String>,
+    /// Executables that have been seen running with a name that matches one of `name_patterns`.
+    seen: BTreeSet<PathBuf>,
+}
+
+impl AppResolver {
+    /// Replaces the list of excluded applications.
+    pub fn set_apps(&mut self, apps: &[OsString]) {
+        self.paths.clear();
+        self.path_patterns.clear();
+        self.name_patterns.clear();
+        self.seen.clear();
+
+        for app in apps {
+            if !is_pattern(app) {
+                self.paths.push(app.clone());
+            } else if Path::new(app).components().count() > 1 {
+                self.path_patterns.push(PathBuf::from(app));
+            } else {
+                self.name_patterns.push(app.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    /// Returns whether any of the applications is a pattern, meaning that the result of
+    /// `resolve` may change over time.
+    pub fn has_patterns(&self) -> bool {
+        !self.path_patterns.is_empty() || !self.name_patterns.is_empty()
+    }
+
+    /// Returns the paths of all executables to exclude.
+    pub fn resolve(&mut self) -> Vec<OsString> {
+        let mut resolved: BTreeSet<OsString> = self.paths.iter().cloned().collect();
+
+        for pattern in &self.path_patterns {
+            resolved.extend(
+                expand_path(pattern)
+                    .into_iter()
+                    .map(PathBuf::into_os_string),
+            );
+        }
+
+        if !self.name_patterns.is_empty() {
+            self.seen.retain(|path| path.is_file());
+            for path in self.running_matches() {
+                if self.seen.insert(path.clone()) {
+                    log::debug!("Found excluded app {}", path.display());
+                }
+            }
+            resolved.extend(self.seen.iter().cloned().map(PathBuf::into_os_string));
+        }
+
+        resolved.into_iter().collect()
+    }
+
+    /// Returns the paths of running processes whose executable matches a name pattern.
+    fn running_matches(&self) -> Vec<PathBuf> {
+        let snapshot = match ProcessSnapshot::new(TH32CS_SNAPPROCESS, 0) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to list running processes")
+                );
+                return vec![];
+            }
+        };
+
+        let mut matches = vec![];
+        for entry in snapshot.entries() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => break,
+            };
+            let exe_name = entry.exe_name.to_string_lossy();
+            if !self
+                .name_patterns
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &exe_name))
+            {
+                continue;
+            }
+            // Processes that cannot be opened are skipped. They may have exited already.
+            let path = open_process(ProcessAccess::QueryLimitedInformation, false, entry.pid)
+                .and_then(|process| get_process_image_path(process.get_raw()));
+            if let Ok(path) = path {
+                matches.push(PathBuf::from(path));
+            }
+        }
+        matches
+    }
+}
+
+/// Returns all files that match `pattern`, where any component of the path may contain
+/// wildcards.
+fn expand_path(pattern: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let part = match component {
+            Component::Normal(part) => part.to_string_lossy(),
+            other => {
+                for candidate in &mut candidates {
+                    candidate.push(other);
+                }
+                continue;
+            }
+        };
+        if !has_wildcards(&part) {
+            for candidate in &mut candidates {
+                candidate.push(&*part);
+            }
+            continue;
+        }
+
+        candidates = candidates
+            .into_iter()
+            .flat_map(|dir| fs::read_dir(dir).into_iter().flatten())
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| wildcard_match(&part, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+    }
+
+    candidates.retain(|path| path.is_file());
+    candidates
+}
+
+/// Matches `text` against `pattern`, where `*` matches any number of characters and `?` matches
+/// exactly one. Like paths on Windows, the comparison is case-insensitive.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern, and the position in the text that it was tried at.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` consume one more character.
+                Some((star, star_t)) => {
+                    backtrack = Some((star, star_t + 1));
+                    p = star + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern(OsStr::new("firefox.exe")));
+        assert!(is_pattern(OsStr::new(r"C:\Program Files\App\*\app.exe")));
+        assert!(!is_pattern(OsStr::new(r"C:\Program Files\App\app.exe")));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("firefox.exe", "Firefox.exe"));
+        assert!(wildcard_match("*.exe", "app.exe"));
+        assert!(wildcard_match("app-?.?.exe", "app-1.2.exe"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("app?.exe", "app.exe"));
+        assert!(!wildcard_match("*.exe", "app.exe.bak"));
+        assert!(!wildcard_match("app.exe", "myapp.exe"));
+    }
+}
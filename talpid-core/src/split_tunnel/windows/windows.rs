@@ -25,6 +25,7 @@ use winapi::{
         psapi::K32GetProcessImageFileNameW,
         synchapi::{CreateEventW, SetEvent},
         tlhelp32::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W},
+        winbase::QueryFullProcessImageNameW,
         winnt::{HANDLE, PROCESS_QUERY_LIMITED_INFORMATION},
     },
 };
@@ -74,6 +75,8 @@ impl Drop for ProcessSnapshot {
 pub struct ProcessEntry {
     pub pid: u32,
     pub parent_pid: u32,
+    /// File name of the executable, without its directory.
+    pub exe_name: OsString,
 }
 
 pub struct ProcessSnapshotEntries<'a> {
@@ -103,9 +106,16 @@ impl Iterator for ProcessSnapshotEntries<'_> {
             self.iter_started = true;
         }
 
+        let exe_name = &self.temp_entry.szExeFile;
+        let exe_name_len = exe_name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(exe_name.len());
+
         Some(Ok(ProcessEntry {
             pid: self.temp_entry.th32ProcessID,
             parent_pid: self.temp_entry.th32ParentProcessID,
+            exe_name: OsStringExt::from_wide(&exe_name[..exe_name_len]),
         }))
     }
 }
@@ -316,6 +326,28 @@ fn get_process_device_path_inner(
     Ok(OsStringExt::from_wide(&buffer))
 }
 
+/// Returns the path of the image of a running process, in the same format as a path that a user
+/// would enter. The driver configuration expects device paths, which `get_device_path` returns.
+pub fn get_process_image_path(handle: RawHandle) -> Result<OsString, io::Error> {
+    let mut buffer = vec![0u16; 512];
+    loop {
+        let mut written = buffer.len() as u32;
+        if unsafe { QueryFullProcessImageNameW(handle, 0, buffer.as_mut_ptr(), &mut written) } != 0
+        {
+            // `written` does not include a null terminator
+            buffer.truncate(written as usize);
+            return Ok(OsStringExt::from_wide(&buffer));
+        }
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(ERROR_INSUFFICIENT_BUFFER as i32) {
+            return Err(error);
+        }
+        // Try again with a larger buffer capacity.
+        let capacity = buffer.len() * 2;
+        buffer.resize(capacity, 0);
+    }
+}
+
 /// Abstraction over `OVERLAPPED`, which is used for async I/O.
 pub struct Overlapped {
     overlapped: OVERLAPPED,
@@ -6,6 +6,8 @@ use std::{
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
+#[cfg(windows)]
+use talpid_types::net::obfuscation::ObfuscatorConfig;
 #[cfg(not(target_os = "android"))]
 use talpid_types::net::openvpn as openvpn_types;
 use talpid_types::net::{wireguard as wireguard_types, AllowedTunnelTraffic, TunnelParameters};
@@ -91,6 +93,13 @@ pub struct TunnelMetadata {
     pub ipv4_gateway: Ipv4Addr,
     /// The IP to the IPv6 default gateway on the tunnel interface.
     pub ipv6_gateway: Option<Ipv6Addr>,
+    /// The MTU of the tunnel interface, if it is known.
+    pub mtu: Option<u16>,
+    /// The WireGuard implementation that the tunnel runs on, for WireGuard tunnels.
+    pub wireguard_implementation: Option<wireguard_types::WireguardImplementation>,
+    /// The network namespace that the tunnel interface is in, if not the root namespace.
+    #[cfg(target_os = "linux")]
+    pub namespace: Option<String>,
 }
 
 /// Abstraction for monitoring a generic VPN tunnel.
@@ -177,7 +186,12 @@ impl TunnelMonitor {
                     "openvpn.exe"
                 }
             }
-            _ => return std::env::current_exe().unwrap(),
+            TunnelParameters::Wireguard(params) => {
+                if let Some(ObfuscatorConfig::External { transport, .. }) = &params.obfuscation {
+                    return transport.path.clone();
+                }
+                return std::env::current_exe().unwrap();
+            }
         };
         resource_dir.join(process_string)
     }
@@ -194,10 +208,13 @@ impl TunnelMonitor {
             + Clone
             + 'static,
     {
+        // The MTU is only detected if it has not been set explicitly
+        let detect_mtu = params.options.mtu.is_none();
         #[cfg(target_os = "linux")]
         args.runtime
             .block_on(Self::assign_mtu(&args.route_manager, params));
-        let config = wireguard::config::Config::from_parameters(params)?;
+        let mut config = wireguard::config::Config::from_parameters(params)?;
+        config.detect_mtu = detect_mtu;
         let monitor = wireguard::WireguardMonitor::start(
             config,
             if params.options.use_pq_safe_psk {
@@ -999,6 +999,10 @@ mod event_server {
                 ips,
                 ipv4_gateway,
                 ipv6_gateway,
+                mtu: None,
+                wireguard_implementation: None,
+                #[cfg(target_os = "linux")]
+                namespace: None,
             })
         }
     }
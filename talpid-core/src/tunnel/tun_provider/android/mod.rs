@@ -64,6 +64,7 @@ pub struct AndroidTunProvider {
     object: GlobalRef,
     last_tun_config: TunConfig,
     allow_lan: bool,
+    allowed_lan_nets: Vec<IpNetwork>,
     custom_dns_servers: Option<Vec<IpAddr>>,
 }
 
@@ -72,6 +73,7 @@ impl AndroidTunProvider {
     pub fn new(
         context: AndroidContext,
         allow_lan: bool,
+        allowed_lan_nets: Vec<IpNetwork>,
         custom_dns_servers: Option<Vec<IpAddr>>,
     ) -> Self {
         let env = JnixEnv::from(
@@ -88,6 +90,7 @@ impl AndroidTunProvider {
             object: context.vpn_service,
             last_tun_config: TunConfig::default(),
             allow_lan,
+            allowed_lan_nets,
             custom_dns_servers,
         }
     }
@@ -101,6 +104,17 @@ impl AndroidTunProvider {
         Ok(())
     }
 
+    pub fn set_allowed_lan_nets(&mut self, allowed_lan_nets: Vec<IpNetwork>) -> Result<(), Error> {
+        if self.allowed_lan_nets != allowed_lan_nets {
+            self.allowed_lan_nets = allowed_lan_nets;
+            if self.allow_lan {
+                self.recreate_tun_if_open()?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn set_dns_servers(&mut self, servers: Option<Vec<IpAddr>>) -> Result<(), Error> {
         if self.custom_dns_servers != servers {
             self.custom_dns_servers = servers;
@@ -234,12 +248,12 @@ impl AndroidTunProvider {
                 .cloned()
                 .partition::<Vec<_>, _>(|route| route.is_ipv4());
 
-            let (original_lan_ipv4_networks, original_lan_ipv6_networks) =
-                crate::firewall::ALLOWED_LAN_NETS
-                    .iter()
-                    .chain(crate::firewall::ALLOWED_LAN_MULTICAST_NETS.iter())
-                    .cloned()
-                    .partition::<Vec<_>, _>(|network| network.is_ipv4());
+            let (original_lan_ipv4_networks, original_lan_ipv6_networks) = self
+                .allowed_lan_nets
+                .iter()
+                .chain(crate::firewall::ALLOWED_LAN_MULTICAST_NETS.iter())
+                .cloned()
+                .partition::<Vec<_>, _>(|network| network.is_ipv4());
 
             let lan_ipv4_networks = original_lan_ipv4_networks
                 .into_iter()
@@ -1,3 +1,5 @@
+#[cfg(not(target_os = "android"))]
+use std::net::IpAddr;
 use std::{
     borrow::Cow,
     ffi::CString,
@@ -18,17 +20,26 @@ pub struct Config {
     pub ipv6_gateway: Option<Ipv6Addr>,
     /// Maximum transmission unit for the tunnel
     pub mtu: u16,
+    /// Whether to lower the MTU once the tunnel is up, if larger packets do not get through
+    pub detect_mtu: bool,
     /// Firewall mark
     #[cfg(target_os = "linux")]
     pub fwmark: u32,
     /// Enable IPv6 routing rules
     #[cfg(target_os = "linux")]
     pub enable_ipv6: bool,
+    /// Network namespace to move the tunnel interface into
+    #[cfg(target_os = "linux")]
+    pub namespace: Option<String>,
     /// Temporary switch for wireguard-nt
     #[cfg(target_os = "windows")]
     pub use_wireguard_nt: bool,
     /// Obfuscator config to be used for reaching the relay.
     pub obfuscator_config: Option<ObfuscatorConfig>,
+    /// How to detect that the tunnel has stopped working
+    pub liveness: wireguard::LivenessOptions,
+    /// Seconds between keepalive packets sent to each peer, or 0 if disabled
+    pub persistent_keepalive: u16,
 }
 
 #[cfg(not(target_os = "android"))]
@@ -86,6 +97,20 @@ impl Config {
             return Err(Error::NoPeersSuppliedError);
         }
         let mtu = wg_options.mtu.unwrap_or(DEFAULT_MTU);
+
+        // Only the last peer carries traffic to the internet. With multihop, the allowed IPs of
+        // the entry peer are just the exit relay.
+        #[cfg(not(target_os = "android"))]
+        if let Some(allowed_ips) = &wg_options.allowed_ips {
+            let peer = peers.last_mut().unwrap();
+            peer.allowed_ips = allowed_ips.clone();
+            // The gateway serves DNS and answers the pings of the connectivity monitor
+            peer.allowed_ips.push(IpAddr::from(connection_config.ipv4_gateway).into());
+            if let Some(ipv6_gateway) = connection_config.ipv6_gateway {
+                peer.allowed_ips.push(IpAddr::from(ipv6_gateway).into());
+            }
+        }
+
         for peer in &mut peers {
             peer.allowed_ips = peer
                 .allowed_ips
@@ -113,19 +138,30 @@ impl Config {
             None
         };
 
+        // The probes are sent from outside the namespace, where the tunnel cannot be reached
+        #[cfg(target_os = "linux")]
+        let detect_mtu = wg_options.mtu.is_none() && wg_options.namespace.is_none();
+        #[cfg(not(target_os = "linux"))]
+        let detect_mtu = wg_options.mtu.is_none();
+
         Ok(Config {
             tunnel,
             peers,
             ipv4_gateway: connection_config.ipv4_gateway,
             ipv6_gateway,
             mtu,
+            detect_mtu,
             #[cfg(target_os = "linux")]
-            fwmark: crate::linux::TUNNEL_FW_MARK,
+            fwmark: crate::linux::tunnel_fw_mark(),
             #[cfg(target_os = "linux")]
             enable_ipv6: generic_options.enable_ipv6,
+            #[cfg(target_os = "linux")]
+            namespace: wg_options.namespace.clone(),
             #[cfg(target_os = "windows")]
             use_wireguard_nt: wg_options.use_wireguard_nt,
             obfuscator_config,
+            liveness: wg_options.liveness,
+            persistent_keepalive: wg_options.persistent_keepalive,
         })
     }
 
@@ -151,6 +187,10 @@ impl Config {
             if let Some(ref psk) = peer.psk {
                 wg_conf.add("preshared_key", psk.as_bytes().as_ref());
             }
+            wg_conf.add(
+                "persistent_keepalive_interval",
+                self.persistent_keepalive.to_string().as_str(),
+            );
             for addr in &peer.allowed_ips {
                 wg_conf.add("allowed_ip", addr.to_string().as_str());
             }
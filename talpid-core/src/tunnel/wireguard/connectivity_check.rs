@@ -8,6 +8,7 @@ use std::{
     sync::{mpsc, Mutex, Weak},
     time::{Duration, Instant},
 };
+use talpid_types::net::wireguard::LivenessOptions;
 
 use super::{Tunnel, TunnelError};
 
@@ -17,14 +18,14 @@ const DELAY_ON_INITIAL_SETUP: Duration = Duration::from_millis(50);
 const REGULAR_LOOP_SLEEP: Duration = Duration::from_secs(1);
 
 /// Timeout for waiting on receiving traffic after sending outgoing traffic.  Once this timeout is
-/// hit, a ping will be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached, or traffic
-/// is received.
+/// hit, a ping will be sent every `LivenessOptions::probe_interval` until the failure timeout is
+/// reached, or traffic is received.
 const BYTES_RX_TIMEOUT: Duration = Duration::from_secs(5);
 /// Timeout for waiting on receiving or sending any traffic.  Once this timeout is hit, a ping will
-/// be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached or traffic is received.
+/// be sent every `LivenessOptions::probe_interval` until the failure timeout is reached or traffic
+/// is received.
 const TRAFFIC_TIMEOUT: Duration = Duration::from_secs(120);
-/// Timeout for waiting on receiving traffic after sending the first ICMP packet.  Once this
-/// timeout is reached, it is assumed that the connection is lost.
+/// Failure timeout with the default liveness options.
 const PING_TIMEOUT: Duration = Duration::from_secs(15);
 /// Timeout for receiving traffic when establishing a connection.
 const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(4);
@@ -32,8 +33,6 @@ const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(4);
 const ESTABLISH_TIMEOUT_MULTIPLIER: u32 = 2;
 /// Maximum timeout for establishing a connection.
 const MAX_ESTABLISH_TIMEOUT: Duration = PING_TIMEOUT;
-/// Number of seconds to wait between sending ICMP packets
-const SECONDS_PER_PING: Duration = Duration::from_secs(3);
 
 /// Connectivity monitor errors
 #[derive(err_derive::Error, Debug)]
@@ -60,18 +59,23 @@ pub enum Error {
 /// timeout. A connection is considered to be established the first time an increase in incoming
 /// traffic is observed.
 ///
-/// The connectivity monitor will start sending pings and start the countdown to `PING_TIMEOUT` in
-/// the following cases:
+/// The connectivity monitor will start sending pings and start the countdown to the failure timeout
+/// of its `LivenessOptions` in the following cases:
 /// - In case that we have observed a bump in the outgoing traffic but no coressponding incoming
 /// traffic for longer than `BYTES_RX_TIMEOUT`, then the monitor will start pinging.
 /// - In case that no increase in outgoing or incoming traffic has been observed for longer than
 /// `TRAFFIC_TIMEOUT`, then the monitor will start pinging as well.
 ///
 /// Once a connection established, a connection is only considered broken once the connectivity
-/// monitor has started pinging and no traffic has been received for a duration of `PING_TIMEOUT`.
+/// monitor has started pinging and no traffic has been received for the duration of the failure
+/// timeout.
+///
+/// If active probing is disabled, no pings are sent once the connection is established. The
+/// countdown then only starts in the first case, so an idle tunnel is never considered broken.
 pub struct ConnectivityMonitor {
     tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
     conn_state: ConnState,
+    liveness: LivenessOptions,
     initial_ping_timestamp: Option<Instant>,
     num_pings_sent: u32,
     pinger: Box<dyn Pinger>,
@@ -84,6 +88,7 @@ impl ConnectivityMonitor {
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
         close_receiver: mpsc::Receiver<()>,
+        liveness: LivenessOptions,
     ) -> Result<Self, Error> {
         let pinger = new_pinger(
             addr,
@@ -97,6 +102,7 @@ impl ConnectivityMonitor {
         Ok(Self {
             tunnel_handle,
             conn_state: ConnState::new(now, Default::default()),
+            liveness,
             initial_ping_timestamp: None,
             num_pings_sent: 0,
             pinger,
@@ -184,7 +190,7 @@ impl ConnectivityMonitor {
 
     /// Returns true if connection is established
     fn check_connectivity(&mut self, now: Instant) -> Result<bool, Error> {
-        self.check_connectivity_interval(now, PING_TIMEOUT)
+        self.check_connectivity_interval(now, self.liveness.failure_timeout())
     }
 
     /// Returns true if connection is established
@@ -221,14 +227,23 @@ impl ConnectivityMonitor {
     }
 
     fn maybe_send_ping(&mut self, now: Instant) -> Result<(), Error> {
+        if !self.liveness.active_probing {
+            // Only wait for a response to traffic that was sent anyway
+            if self.conn_state.rx_timed_out() && self.initial_ping_timestamp.is_none() {
+                self.initial_ping_timestamp = Some(now);
+            }
+            return Ok(());
+        }
+
         // Only send out a ping if we haven't received a byte in a while or no traffic has flowed
         // in the last 2 minutes, but if a ping already has been sent out, only send one out every
-        // 3 seconds.
+        // probe interval.
         if (self.conn_state.rx_timed_out() || self.conn_state.traffic_timed_out())
             && self
                 .initial_ping_timestamp
                 .map(|initial_ping_timestamp| {
-                    initial_ping_timestamp.elapsed() / self.num_pings_sent < SECONDS_PER_PING
+                    initial_ping_timestamp.elapsed() / self.num_pings_sent
+                        >= self.liveness.probe_interval
                 })
                 .unwrap_or(true)
         {
@@ -521,6 +536,10 @@ mod test {
             }
             Ok(())
         }
+
+        fn send_icmp_sized(&mut self, _size: u16) -> Result<(), crate::ping_monitor::Error> {
+            self.send_icmp()
+        }
     }
 
     struct MockTunnel {
@@ -616,6 +635,7 @@ mod test {
     ) -> ConnectivityMonitor {
         ConnectivityMonitor {
             conn_state: ConnState::new(now, Default::default()),
+            liveness: LivenessOptions::default(),
             initial_ping_timestamp: None,
             num_pings_sent: 0,
             pinger,
@@ -659,6 +679,38 @@ mod test {
         assert!(!monitor.check_connectivity(now).unwrap())
     }
 
+    #[test]
+    /// Verify that an idle tunnel is neither probed nor considered broken if active probing is
+    /// disabled.
+    fn test_probing_disabled() {
+        let (_tunnel_anchor, tunnel) = MockTunnel::never_incrementing().into_locked();
+        let (_tx, rx) = mpsc::channel();
+        let pings_sent = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let pings_sent_copy = pings_sent.clone();
+        let pinger = MockPinger {
+            on_send_ping: Some(Box::new(move || {
+                pings_sent_copy.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            })),
+        };
+        let now = Instant::now();
+        let start = now - (TRAFFIC_TIMEOUT + Duration::from_secs(10));
+        let mut monitor = mock_monitor(start, Box::new(pinger), tunnel, rx);
+        monitor.liveness.active_probing = false;
+
+        // Mock the state - traffic was last received after it was last sent
+        monitor.conn_state = connected_state(start);
+        if let ConnState::Connected { rx_timestamp, .. } = &mut monitor.conn_state {
+            *rx_timestamp = start + Duration::from_secs(1);
+        }
+        assert!(monitor.conn_state.traffic_timed_out());
+        assert!(monitor.check_connectivity(now).unwrap());
+        assert_eq!(pings_sent.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        monitor.liveness.active_probing = true;
+        monitor.check_connectivity(now).unwrap();
+        assert_eq!(pings_sent.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
     #[test]
     /// Verify that `check_connectivity()` returns `true` if the tunnel is connected and traffic is
     /// flowing constantly.
@@ -27,19 +27,24 @@ use std::{
 use talpid_types::BoxedError;
 use talpid_types::{
     net::{
-        obfuscation::ObfuscatorConfig, wireguard::PublicKey, AllowedTunnelTraffic, Endpoint,
-        TransportProtocol,
+        obfuscation::ObfuscatorConfig,
+        wireguard::{PublicKey, WireguardImplementation},
+        AllowedTunnelTraffic, Endpoint, TransportProtocol,
     },
     ErrorExt,
 };
 use tunnel_obfuscation::{
-    create_obfuscator, Error as ObfuscationError, Settings as ObfuscationSettings, Udp2TcpSettings,
+    create_obfuscator, Error as ObfuscationError, ExternalTransport, ExternalTransportOptions,
+    Settings as ObfuscationSettings, Transport, TransportOptions, Udp2TcpTransport, WssTransport,
+    WssTransportOptions,
 };
 
 /// WireGuard config data-types
 pub mod config;
 mod connectivity_check;
 mod logging;
+#[cfg(not(target_os = "android"))]
+mod mtu_detection;
 mod stats;
 mod wireguard_go;
 #[cfg(target_os = "linux")]
@@ -76,6 +81,11 @@ pub enum Error {
     #[error(display = "Tunnel obfuscator failed")]
     ObfuscatorError(#[error(source)] ObfuscationError),
 
+    /// Failed to exclude the obfuscation process from the tunnel
+    #[cfg(target_os = "linux")]
+    #[error(display = "Failed to exclude the obfuscation process from the tunnel")]
+    ExcludeObfuscatorError(#[error(source)] crate::split_tunnel::Error),
+
     /// Failed to set up connectivity monitor
     #[error(display = "Connectivity monitor failed")]
     ConnectivityMonitorError(#[error(source)] connectivity_check::Error),
@@ -93,6 +103,21 @@ pub enum Error {
     #[cfg(target_os = "windows")]
     #[error(display = "Failed to set IP addresses on WireGuard interface")]
     SetIpAddressesError,
+
+    /// Failed to set up the tunnel in a network namespace
+    #[cfg(target_os = "linux")]
+    #[error(display = "Failed to set up the tunnel in the network namespace")]
+    NamespaceTunnelError(#[error(source)] wireguard_kernel::Error),
+
+    /// Failed to enter the network namespace of the tunnel
+    #[cfg(target_os = "linux")]
+    #[error(display = "Failed to enter the network namespace of the tunnel")]
+    NamespaceError(#[error(source)] crate::linux::netns::Error),
+
+    /// Quantum-resistant tunnels cannot be used in a network namespace
+    #[cfg(target_os = "linux")]
+    #[error(display = "Quantum-resistant tunnels are not supported in a network namespace")]
+    NamespacePskNegotiationError,
 }
 
 /// Spawns and monitors a wireguard tunnel
@@ -149,42 +174,93 @@ fn maybe_create_obfuscator(
     // The first one is always the entry relay.
     let mut first_peer = config.peers.get_mut(0).expect("missing peer");
 
-    if let Some(ref obfuscator_config) = config.obfuscator_config {
-        match obfuscator_config {
-            ObfuscatorConfig::Udp2Tcp { endpoint } => {
-                log::trace!("Connecting to Udp2Tcp endpoint {:?}", *endpoint);
-                let settings = Udp2TcpSettings {
-                    peer: *endpoint,
-                    #[cfg(target_os = "linux")]
-                    fwmark: Some(crate::linux::TUNNEL_FW_MARK),
-                };
-                let obfuscator = runtime
-                    .block_on(create_obfuscator(&ObfuscationSettings::Udp2Tcp(settings)))
-                    .map_err(Error::CreateObfuscatorError)?;
-                let endpoint = obfuscator.endpoint();
-                log::trace!("Patching first WireGuard peer to become {:?}", endpoint);
-                first_peer.endpoint = endpoint;
-                let (runner, abort_handle) = abortable(async move {
-                    match obfuscator.run().await {
-                        Ok(_) => {
-                            let _ = close_msg_sender.send(CloseMsg::ObfuscatorExpired);
-                        }
-                        Err(error) => {
-                            log::error!(
-                                "{}",
-                                error.display_chain_with_msg("Obfuscation controller failed")
-                            );
-                            let _ = close_msg_sender
-                                .send(CloseMsg::ObfuscatorFailed(Error::ObfuscatorError(error)));
-                        }
-                    }
-                });
-                runtime.spawn(runner);
-                return Ok(Some(ObfuscatorHandle::new(abort_handle)));
+    let settings = match config.obfuscator_config {
+        Some(ObfuscatorConfig::Udp2Tcp { endpoint }) => {
+            log::trace!("Connecting to Udp2Tcp endpoint {:?}", endpoint);
+            ObfuscationSettings {
+                transport: Udp2TcpTransport.name().to_owned(),
+                peer: endpoint,
+                #[cfg(target_os = "linux")]
+                fwmark: Some(crate::linux::tunnel_fw_mark()),
+                options: TransportOptions::Null,
             }
         }
+        Some(ObfuscatorConfig::Wss {
+            endpoint,
+            ref server_name,
+        }) => {
+            log::trace!("Connecting to WebSocket endpoint {:?}", endpoint);
+            ObfuscationSettings {
+                transport: WssTransport.name().to_owned(),
+                peer: endpoint,
+                #[cfg(target_os = "linux")]
+                fwmark: Some(crate::linux::tunnel_fw_mark()),
+                options: WssTransportOptions {
+                    server_name: server_name.clone(),
+                    path: "/".to_owned(),
+                }
+                .to_options(),
+            }
+        }
+        Some(ObfuscatorConfig::External {
+            endpoint,
+            ref transport,
+        }) => {
+            log::trace!(
+                "Connecting to {:?} through external transport {}",
+                endpoint,
+                transport
+            );
+            ObfuscationSettings {
+                transport: ExternalTransport.name().to_owned(),
+                peer: endpoint,
+                #[cfg(target_os = "linux")]
+                fwmark: None,
+                options: ExternalTransportOptions {
+                    path: transport.path.clone(),
+                    args: transport.args.clone(),
+                    method: transport.method.clone(),
+                    method_args: transport.method_args.clone(),
+                }
+                .to_options(),
+            }
+        }
+        None => return Ok(None),
+    };
+
+    let obfuscator = runtime
+        .block_on(create_obfuscator(&settings))
+        .map_err(Error::CreateObfuscatorError)?;
+
+    // Traffic from a separate obfuscation process cannot be marked, so the process is excluded
+    // from the tunnel instead.
+    #[cfg(target_os = "linux")]
+    if let Some(pid) = obfuscator.process_id() {
+        crate::split_tunnel::PidManager::new()
+            .and_then(|pid_manager| pid_manager.add(pid as i32))
+            .map_err(Error::ExcludeObfuscatorError)?;
     }
-    Ok(None)
+
+    let endpoint = obfuscator.endpoint();
+    log::trace!("Patching first WireGuard peer to become {:?}", endpoint);
+    first_peer.endpoint = endpoint;
+    let (runner, abort_handle) = abortable(async move {
+        match obfuscator.run().await {
+            Ok(_) => {
+                let _ = close_msg_sender.send(CloseMsg::ObfuscatorExpired);
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Obfuscation controller failed")
+                );
+                let _ = close_msg_sender
+                    .send(CloseMsg::ObfuscatorFailed(Error::ObfuscatorError(error)));
+            }
+        }
+    });
+    runtime.spawn(runner);
+    Ok(Some(ObfuscatorHandle::new(abort_handle)))
 }
 
 impl WireguardMonitor {
@@ -203,6 +279,12 @@ impl WireguardMonitor {
     ) -> Result<WireguardMonitor> {
         let on_event = args.on_event;
 
+        // The PSK is negotiated through the tunnel, which is unreachable outside the namespace
+        #[cfg(target_os = "linux")]
+        if config.namespace.is_some() && psk_negotiation.is_some() {
+            return Err(Error::NamespacePskNegotiationError);
+        }
+
         let endpoint_addrs: Vec<IpAddr> =
             config.peers.iter().map(|peer| peer.endpoint.ip()).collect();
         let (close_msg_sender, close_msg_receiver) = sync_mpsc::channel();
@@ -213,7 +295,7 @@ impl WireguardMonitor {
         #[cfg(target_os = "windows")]
         let (setup_done_tx, setup_done_rx) = mpsc::channel(0);
 
-        let tunnel = Self::open_tunnel(
+        let (tunnel, implementation) = Self::open_tunnel(
             args.runtime.clone(),
             &Self::patch_allowed_ips(&config, psk_negotiation.is_some()),
             log_path,
@@ -236,17 +318,38 @@ impl WireguardMonitor {
         };
 
         let gateway = config.ipv4_gateway;
-        let mut connectivity_monitor = connectivity_check::ConnectivityMonitor::new(
-            gateway,
-            #[cfg(any(target_os = "macos", target_os = "linux"))]
-            iface_name.clone(),
-            Arc::downgrade(&monitor.tunnel),
-            pinger_rx,
-        )
-        .map_err(Error::ConnectivityMonitorError)?;
+        let new_connectivity_monitor = {
+            let iface_name = iface_name.clone();
+            let tunnel = Arc::downgrade(&monitor.tunnel);
+            let liveness = config.liveness;
+            move || {
+                connectivity_check::ConnectivityMonitor::new(
+                    gateway,
+                    #[cfg(any(target_os = "macos", target_os = "linux"))]
+                    iface_name,
+                    tunnel,
+                    pinger_rx,
+                    liveness,
+                )
+            }
+        };
+        // The pinger must be created in the namespace in order to reach the tunnel
+        #[cfg(target_os = "linux")]
+        let connectivity_monitor = match &config.namespace {
+            Some(namespace) => crate::linux::netns::Namespace::open(namespace)
+                .and_then(|namespace| namespace.run(new_connectivity_monitor))
+                .map_err(Error::NamespaceError)?,
+            None => new_connectivity_monitor(),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let connectivity_monitor = new_connectivity_monitor();
+        let mut connectivity_monitor =
+            connectivity_monitor.map_err(Error::ConnectivityMonitorError)?;
 
-        let metadata = Self::tunnel_metadata(&iface_name, &config);
+        let metadata = Self::tunnel_metadata(&iface_name, &config, implementation);
         let tunnel = monitor.tunnel.clone();
+        #[cfg(not(target_os = "android"))]
+        let weak_tunnel = Arc::downgrade(&monitor.tunnel);
 
         let tunnel_fut = async move {
             #[cfg(windows)]
@@ -264,22 +367,32 @@ impl WireguardMonitor {
             };
             (on_event)(TunnelEvent::InterfaceUp(metadata.clone(), allowed_traffic)).await;
 
+            // Routes in a network namespace are set up along with the tunnel interface.
+            #[cfg(target_os = "linux")]
+            let manage_routes = config.namespace.is_none();
+            #[cfg(not(target_os = "linux"))]
+            let manage_routes = true;
+
             // Add non-default routes before establishing the tunnel.
             #[cfg(target_os = "linux")]
-            args.route_manager
-                .create_routing_rules(config.enable_ipv6)
-                .await
-                .map_err(Error::SetupRoutingError)
-                .map_err(CloseMsg::SetupError)?;
+            if manage_routes {
+                args.route_manager
+                    .create_routing_rules(config.enable_ipv6)
+                    .await
+                    .map_err(Error::SetupRoutingError)
+                    .map_err(CloseMsg::SetupError)?;
+            }
 
-            let routes = Self::get_pre_tunnel_routes(&iface_name, &config)
-                .chain(Self::get_endpoint_routes(&endpoint_addrs))
-                .collect();
-            args.route_manager
-                .add_routes(routes)
-                .await
-                .map_err(Error::SetupRoutingError)
-                .map_err(CloseMsg::SetupError)?;
+            if manage_routes {
+                let routes = Self::get_pre_tunnel_routes(&iface_name, &config)
+                    .chain(Self::get_endpoint_routes(&endpoint_addrs))
+                    .collect();
+                args.route_manager
+                    .add_routes(routes)
+                    .await
+                    .map_err(Error::SetupRoutingError)
+                    .map_err(CloseMsg::SetupError)?;
+            }
 
             if let Some(pubkey) = psk_negotiation {
                 Self::perform_psk_negotiation(tunnel, args.retry_attempt, pubkey, &mut config)
@@ -310,12 +423,34 @@ impl WireguardMonitor {
             .await
             .unwrap()?;
 
+            // Probe before the default routes are added, so that other traffic is not counted as
+            // replies to the probes.
+            #[cfg(not(target_os = "android"))]
+            let metadata = if config.detect_mtu {
+                let mtu = mtu_detection::detect_mtu(
+                    weak_tunnel,
+                    config.ipv4_gateway,
+                    iface_name.clone(),
+                    config.mtu,
+                    config.ipv6_gateway.is_some(),
+                )
+                .await;
+                TunnelMetadata {
+                    mtu: Some(mtu),
+                    ..metadata
+                }
+            } else {
+                metadata
+            };
+
             // Add any default route(s) that may exist.
-            args.route_manager
-                .add_routes(Self::get_post_tunnel_routes(&iface_name, &config).collect())
-                .await
-                .map_err(Error::SetupRoutingError)
-                .map_err(CloseMsg::SetupError)?;
+            if manage_routes {
+                args.route_manager
+                    .add_routes(Self::get_post_tunnel_routes(&iface_name, &config).collect())
+                    .await
+                    .map_err(Error::SetupRoutingError)
+                    .map_err(CloseMsg::SetupError)?;
+            }
 
             (on_event)(TunnelEvent::Up(metadata)).await;
 
@@ -467,6 +602,8 @@ impl WireguardMonitor {
         Ok(())
     }
 
+    /// Starts the tunnel using the preferred WireGuard implementation that works, and returns
+    /// which one that is.
     #[allow(unused_variables)]
     fn open_tunnel(
         runtime: tokio::runtime::Handle,
@@ -475,14 +612,23 @@ impl WireguardMonitor {
         resource_dir: &Path,
         tun_provider: Arc<Mutex<TunProvider>>,
         #[cfg(windows)] setup_done_tx: mpsc::Sender<std::result::Result<(), BoxedError>>,
-    ) -> Result<Box<dyn Tunnel>> {
+    ) -> Result<(Box<dyn Tunnel>, WireguardImplementation)> {
+        // Only the kernel implementation supports moving the interface into a namespace
+        #[cfg(target_os = "linux")]
+        if config.namespace.is_some() {
+            let tunnel = wireguard_kernel::NetlinkTunnel::new(runtime, config)
+                .map_err(Error::NamespaceTunnelError)?;
+            log::debug!("Using kernel WireGuard implementation in a network namespace");
+            return Ok((Box::new(tunnel), WireguardImplementation::Kernel));
+        }
+
         #[cfg(target_os = "linux")]
         if !*FORCE_USERSPACE_WIREGUARD {
             if crate::dns::will_use_nm() {
                 match wireguard_kernel::NetworkManagerTunnel::new(runtime, config) {
                     Ok(tunnel) => {
                         log::debug!("Using NetworkManager to use kernel WireGuard implementation");
-                        return Ok(Box::new(tunnel));
+                        return Ok((Box::new(tunnel), WireguardImplementation::NetworkManager));
                     }
                     Err(err) => {
                         log::error!(
@@ -497,7 +643,7 @@ impl WireguardMonitor {
                 match wireguard_kernel::NetlinkTunnel::new(runtime, config) {
                     Ok(tunnel) => {
                         log::debug!("Using kernel WireGuard implementation");
-                        return Ok(Box::new(tunnel));
+                        return Ok((Box::new(tunnel), WireguardImplementation::Kernel));
                     }
                     Err(error) => {
                         log::error!(
@@ -521,7 +667,7 @@ impl WireguardMonitor {
             ) {
                 Ok(tunnel) => {
                     log::debug!("Using WireGuardNT");
-                    return Ok(Box::new(tunnel));
+                    return Ok((Box::new(tunnel), WireguardImplementation::WireguardNt));
                 }
                 Err(error) => {
                     log::error!(
@@ -534,19 +680,18 @@ impl WireguardMonitor {
 
         #[cfg(any(target_os = "linux", windows))]
         log::debug!("Using userspace WireGuard implementation");
-        Ok(Box::new(
-            WgGoTunnel::start_tunnel(
-                config,
-                log_path,
-                #[cfg(not(windows))]
-                tun_provider,
-                #[cfg(not(windows))]
-                Self::get_tunnel_destinations(config).flat_map(Self::replace_default_prefixes),
-                #[cfg(windows)]
-                setup_done_tx,
-            )
-            .map_err(Error::TunnelError)?,
-        ))
+        let tunnel = WgGoTunnel::start_tunnel(
+            config,
+            log_path,
+            #[cfg(not(windows))]
+            tun_provider,
+            #[cfg(not(windows))]
+            Self::get_tunnel_destinations(config).flat_map(Self::replace_default_prefixes),
+            #[cfg(windows)]
+            setup_done_tx,
+        )
+        .map_err(Error::TunnelError)?;
+        Ok((Box::new(tunnel), WireguardImplementation::Userspace))
     }
 
     /// Blocks the current thread until tunnel disconnects
@@ -634,9 +779,21 @@ impl WireguardMonitor {
 
         let (node_v4, node_v6) = Self::get_tunnel_nodes(iface_name, config);
 
+        let gateway_networks: Vec<ipnetwork::IpNetwork> =
+            std::iter::once(ipnetwork::Ipv4Network::from(config.ipv4_gateway).into())
+                .chain(
+                    config
+                        .ipv6_gateway
+                        .map(|gateway| ipnetwork::Ipv6Network::from(gateway).into()),
+                )
+                .collect();
+
         let routes = gateway_routes.chain(
             Self::get_tunnel_destinations(config)
-                .filter(|allowed_ip| allowed_ip.prefix() != 0)
+                // The gateways are among the allowed IPs when these are overridden
+                .filter(move |allowed_ip| {
+                    allowed_ip.prefix() != 0 && !gateway_networks.contains(allowed_ip)
+                })
                 .map(move |allowed_ip| {
                     if allowed_ip.is_ipv4() {
                         RequiredRoute::new(allowed_ip, node_v4.clone())
@@ -698,12 +855,20 @@ impl WireguardMonitor {
         vec![network]
     }
 
-    fn tunnel_metadata(interface_name: &str, config: &Config) -> TunnelMetadata {
+    fn tunnel_metadata(
+        interface_name: &str,
+        config: &Config,
+        implementation: WireguardImplementation,
+    ) -> TunnelMetadata {
         TunnelMetadata {
             interface: interface_name.to_string(),
             ips: config.tunnel.addresses.clone(),
             ipv4_gateway: config.ipv4_gateway,
             ipv6_gateway: config.ipv6_gateway,
+            mtu: Some(config.mtu),
+            wireguard_implementation: Some(implementation),
+            #[cfg(target_os = "linux")]
+            namespace: config.namespace.clone(),
         }
     }
 }
@@ -0,0 +1,265 @@
+//! Finds the largest packets that get through the tunnel. On some links, packets that are too
+//! large are dropped without an ICMP "fragmentation needed" message being sent back, which breaks
+//! path MTU discovery and makes connections stall once they start sending full-sized packets.
+
+use super::{Tunnel, TunnelError};
+use crate::ping_monitor::{self, new_pinger, Pinger};
+use std::{
+    io,
+    net::Ipv4Addr,
+    sync::{Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+use talpid_types::ErrorExt;
+
+/// Time to wait for the reply to a probe.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Number of times a probe is sent before its size is considered not to get through.
+const PROBE_ATTEMPTS: u32 = 2;
+/// How often the tunnel statistics are read while waiting for a reply.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The search stops once the largest working size is known to within this many bytes.
+const PRECISION: u16 = 8;
+
+/// The minimum MTU that IPv6 requires.
+const MIN_IPV6_MTU: u16 = 1280;
+/// The minimum MTU that IPv4 requires.
+const MIN_IPV4_MTU: u16 = 576;
+
+/// MTU detection errors
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    /// Failed to send a probe
+    #[error(display = "Failed to send MTU probe")]
+    PingError(#[error(source)] ping_monitor::Error),
+
+    /// Failed to read the tunnel statistics
+    #[error(display = "Failed to read tunnel statistics")]
+    StatsError(#[error(source)] TunnelError),
+
+    /// The tunnel was closed while probing
+    #[error(display = "The tunnel was closed")]
+    TunnelClosed,
+
+    /// Failed to set the MTU of the tunnel interface
+    #[error(display = "Failed to set the MTU of the tunnel interface")]
+    SetMtuError(#[error(source)] io::Error),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ProbeResult {
+    /// Packets of the current MTU get through.
+    Working,
+    /// Packets larger than this are dropped.
+    Lowered(u16),
+    /// Not even the smallest probe got through, so nothing can be concluded.
+    Inconclusive,
+}
+
+/// Probes the tunnel for the largest MTU that works, and lowers the MTU of the tunnel interface
+/// if `current_mtu` is too large. Returns the MTU that the interface has afterwards.
+pub(super) async fn detect_mtu(
+    tunnel: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+    gateway: Ipv4Addr,
+    interface: String,
+    current_mtu: u16,
+    ipv6: bool,
+) -> u16 {
+    let min_mtu = if ipv6 { MIN_IPV6_MTU } else { MIN_IPV4_MTU };
+    let result = tokio::task::spawn_blocking(move || {
+        let mut pinger = new_pinger(
+            gateway,
+            #[cfg(any(target_os = "macos", target_os = "linux"))]
+            interface.clone(),
+        )
+        .map_err(Error::PingError)?;
+        let result = search(current_mtu, min_mtu, |size| {
+            probe(pinger.as_mut(), &tunnel, size)
+        })?;
+        if let ProbeResult::Lowered(mtu) = result {
+            set_mtu(&interface, mtu, ipv6).map_err(Error::SetMtuError)?;
+        }
+        Ok(result)
+    })
+    .await
+    .unwrap();
+
+    match result {
+        Ok(ProbeResult::Working) => {
+            log::debug!("Packets of {} bytes get through the tunnel", current_mtu);
+            current_mtu
+        }
+        Ok(ProbeResult::Lowered(mtu)) => {
+            log::warn!(
+                "Packets larger than {} bytes are dropped without notice, which suggests that \
+                 path MTU discovery is broken. Lowered the tunnel MTU from {} to {}",
+                mtu,
+                current_mtu,
+                mtu
+            );
+            mtu
+        }
+        Ok(ProbeResult::Inconclusive) => {
+            log::warn!(
+                "No MTU probes got through the tunnel, keeping the MTU at {}",
+                current_mtu
+            );
+            current_mtu
+        }
+        Err(error) => {
+            log::error!("{}", error.display_chain_with_msg("Failed to detect MTU"));
+            current_mtu
+        }
+    }
+}
+
+/// Searches for the largest MTU between `min_mtu` and `max_mtu` for which `probe` succeeds.
+fn search(
+    max_mtu: u16,
+    min_mtu: u16,
+    mut probe: impl FnMut(u16) -> Result<bool, Error>,
+) -> Result<ProbeResult, Error> {
+    if probe(max_mtu)? {
+        return Ok(ProbeResult::Working);
+    }
+    if max_mtu <= min_mtu || !probe(min_mtu)? {
+        return Ok(ProbeResult::Inconclusive);
+    }
+
+    let mut working = min_mtu;
+    let mut failing = max_mtu;
+    while failing - working > PRECISION {
+        let size = working + (failing - working) / 2;
+        if probe(size)? {
+            working = size;
+        } else {
+            failing = size;
+        }
+    }
+    Ok(ProbeResult::Lowered(working))
+}
+
+/// Sends a ping of `size` bytes to the gateway, and waits for the received byte count of the
+/// tunnel to grow by at least as much. The replies are the same size as the requests.
+fn probe(
+    pinger: &mut dyn Pinger,
+    tunnel: &Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+    size: u16,
+) -> Result<bool, Error> {
+    for _ in 0..PROBE_ATTEMPTS {
+        let rx_bytes_before = rx_bytes(tunnel)?;
+        pinger.send_icmp_sized(size).map_err(Error::PingError)?;
+
+        let deadline = Instant::now() + PROBE_TIMEOUT;
+        while Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+            if rx_bytes(tunnel)? >= rx_bytes_before + u64::from(size) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn rx_bytes(tunnel: &Weak<Mutex<Option<Box<dyn Tunnel>>>>) -> Result<u64, Error> {
+    let tunnel = tunnel.upgrade().ok_or(Error::TunnelClosed)?;
+    let tunnel = tunnel.lock().unwrap();
+    let stats = tunnel
+        .as_ref()
+        .ok_or(Error::TunnelClosed)?
+        .get_tunnel_stats()
+        .map_err(Error::StatsError)?;
+    Ok(stats.values().map(|stats| stats.rx_bytes).sum())
+}
+
+#[cfg(target_os = "linux")]
+fn set_mtu(interface: &str, mtu: u16, _ipv6: bool) -> io::Result<()> {
+    duct::cmd!(
+        "ip",
+        "link",
+        "set",
+        "dev",
+        interface,
+        "mtu",
+        mtu.to_string()
+    )
+    .stdout_null()
+    .run()
+    .map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn set_mtu(interface: &str, mtu: u16, _ipv6: bool) -> io::Result<()> {
+    duct::cmd!("ifconfig", interface, "mtu", mtu.to_string())
+        .stdout_null()
+        .run()
+        .map(|_| ())
+}
+
+#[cfg(windows)]
+fn set_mtu(interface: &str, mtu: u16, ipv6: bool) -> io::Result<()> {
+    use crate::windows::{self, AddressFamily};
+
+    let luid = windows::luid_from_alias(interface)?;
+    let families: &[AddressFamily] = if ipv6 {
+        &[AddressFamily::Ipv4, AddressFamily::Ipv6]
+    } else {
+        &[AddressFamily::Ipv4]
+    };
+    for family in families {
+        let mut row = windows::get_ip_interface_entry(*family, &luid)?;
+        row.NlMtu = u32::from(mtu);
+        windows::set_ip_interface_entry(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn search_path(max_mtu: u16, min_mtu: u16, path_mtu: Option<u16>) -> ProbeResult {
+        search(max_mtu, min_mtu, |size| {
+            Ok(path_mtu.map(|path_mtu| size <= path_mtu).unwrap_or(false))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_search_working() {
+        assert_eq!(
+            search_path(1380, MIN_IPV6_MTU, Some(1420)),
+            ProbeResult::Working
+        );
+        assert_eq!(
+            search_path(1380, MIN_IPV6_MTU, Some(1380)),
+            ProbeResult::Working
+        );
+    }
+
+    #[test]
+    fn test_search_lowered() {
+        match search_path(1380, MIN_IPV4_MTU, Some(1300)) {
+            ProbeResult::Lowered(mtu) => assert!(mtu <= 1300 && mtu > 1300 - PRECISION),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            search_path(1380, MIN_IPV6_MTU, Some(MIN_IPV6_MTU)),
+            ProbeResult::Lowered(MIN_IPV6_MTU)
+        );
+    }
+
+    #[test]
+    fn test_search_inconclusive() {
+        assert_eq!(
+            search_path(1380, MIN_IPV6_MTU, None),
+            ProbeResult::Inconclusive
+        );
+        assert_eq!(
+            search_path(1380, MIN_IPV6_MTU, Some(1000)),
+            ProbeResult::Inconclusive
+        );
+    }
+}
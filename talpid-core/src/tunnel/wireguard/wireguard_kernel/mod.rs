@@ -1,5 +1,6 @@
 use super::{Config, Tunnel, TunnelError};
 use futures::future::{abortable, AbortHandle};
+use ipnetwork::IpNetwork;
 use netlink_packet_core::{constants::*, NetlinkDeserializable};
 use netlink_packet_route::{
     rtnl::{
@@ -14,7 +15,7 @@ use netlink_proto::{
     sys::{protocols::NETLINK_GENERIC, SocketAddr},
     ConnectionHandle, Error as NetlinkError,
 };
-use std::{ffi::CString, net::IpAddr};
+use std::{ffi::CString, net::IpAddr, os::unix::io::RawFd};
 use tokio_stream::StreamExt;
 
 mod parsers;
@@ -80,6 +81,18 @@ pub enum Error {
     #[error(display = "Failed to delete device")]
     DeleteDevice(#[error(source)] rtnetlink::Error),
 
+    #[error(display = "Failed to move device into network namespace")]
+    SetNamespace(#[error(source)] rtnetlink::Error),
+
+    #[error(display = "Failed to bring up link")]
+    SetLinkUp(#[error(source)] rtnetlink::Error),
+
+    #[error(display = "Failed to add route")]
+    AddRoute(#[error(source)] rtnetlink::Error),
+
+    #[error(display = "Network namespace error")]
+    Namespace(#[error(source)] crate::linux::netns::Error),
+
     #[error(display = "NetworkManager error")]
     NetworkManager(#[error(source)] nm_tunnel::Error),
 }
@@ -190,8 +203,12 @@ impl Handle {
         }
 
         // fetch interface index of new device
-        let new_device = self.wg_handle.get_by_name(name).await?;
-        for nla in new_device.nlas {
+        self.get_device_index(name).await
+    }
+
+    pub async fn get_device_index(&mut self, name: String) -> Result<u32, Error> {
+        let device = self.wg_handle.get_by_name(name).await?;
+        for nla in device.nlas {
             if let DeviceNla::IfIndex(index) = nla {
                 return Ok(index);
             }
@@ -200,6 +217,52 @@ impl Handle {
         Err(Error::NoDevice)
     }
 
+    /// Moves a device into the network namespace referred to by `namespace_fd`. The device is
+    /// brought down, and may get a different index in the new namespace.
+    pub async fn set_namespace(&mut self, index: u32, namespace_fd: RawFd) -> Result<(), Error> {
+        self.route_handle
+            .link()
+            .set(index)
+            .setns_by_fd(namespace_fd)
+            .execute()
+            .await
+            .map_err(Error::SetNamespace)
+    }
+
+    pub async fn set_link_up(&mut self, index: u32) -> Result<(), Error> {
+        self.route_handle
+            .link()
+            .set(index)
+            .up()
+            .execute()
+            .await
+            .map_err(Error::SetLinkUp)
+    }
+
+    /// Routes `destination` through the device in the main routing table.
+    pub async fn add_route(&mut self, index: u32, destination: IpNetwork) -> Result<(), Error> {
+        let request = self.route_handle.route().add();
+        match destination {
+            IpNetwork::V4(destination) => {
+                request
+                    .v4()
+                    .destination_prefix(destination.ip(), destination.prefix())
+                    .output_interface(index)
+                    .execute()
+                    .await
+            }
+            IpNetwork::V6(destination) => {
+                request
+                    .v6()
+                    .destination_prefix(destination.ip(), destination.prefix())
+                    .output_interface(index)
+                    .execute()
+                    .await
+            }
+        }
+        .map_err(Error::AddRoute)
+    }
+
     pub async fn set_ip_address(&mut self, index: u32, addr: IpAddr) -> Result<(), Error> {
         let address_message = add_ip_addr_message(index, addr);
         let mut request = NetlinkMessage::from(RtnlMessage::NewAddress(address_message));
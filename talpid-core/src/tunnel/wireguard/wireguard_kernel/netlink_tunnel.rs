@@ -1,4 +1,4 @@
-use std::pin::Pin;
+use std::{os::unix::io::AsRawFd, pin::Pin};
 
 use futures::Future;
 
@@ -7,6 +7,10 @@ use super::{
     wg_message::DeviceNla,
     Config, Error, Handle, Tunnel, TunnelError, MULLVAD_INTERFACE_NAME,
 };
+use crate::linux::netns::Namespace;
+
+/// The loopback interface has this index in every network namespace.
+const LOOPBACK_INTERFACE_INDEX: u32 = 1;
 
 pub struct NetlinkTunnel {
     interface_index: u32,
@@ -16,19 +20,54 @@ pub struct NetlinkTunnel {
 
 impl NetlinkTunnel {
     pub fn new(tokio_handle: tokio::runtime::Handle, config: &Config) -> Result<Self, Error> {
+        let namespace = config
+            .namespace
+            .as_deref()
+            .map(Namespace::open)
+            .transpose()
+            .map_err(Error::Namespace)?;
+
         tokio_handle.clone().block_on(async {
             let mut netlink_connections = Handle::connect().await?;
-            let interface_index = netlink_connections
+            let mut interface_index = netlink_connections
                 .create_device(MULLVAD_INTERFACE_NAME.to_string(), config.mtu as u32)
                 .await?;
 
+            if let Some(namespace) = &namespace {
+                match Self::move_to_namespace(
+                    &tokio_handle,
+                    &mut netlink_connections,
+                    interface_index,
+                    namespace,
+                )
+                .await
+                {
+                    Ok((namespace_connections, namespace_index)) => {
+                        netlink_connections = namespace_connections;
+                        interface_index = namespace_index;
+                    }
+                    Err(err) => {
+                        if let Err(teardown_err) =
+                            netlink_connections.delete_device(interface_index).await
+                        {
+                            log::error!(
+                                "Failed to tear down WireGuard interface after failing to move it \
+                                into the network namespace: {}",
+                                teardown_err
+                            );
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+
             let mut tunnel = Self {
                 interface_index,
                 netlink_connections,
                 tokio_handle,
             };
 
-            if let Err(err) = tunnel.setup(config).await {
+            if let Err(err) = tunnel.setup(config, namespace.is_some()).await {
                 if let Err(teardown_err) = tunnel
                     .netlink_connections
                     .delete_device(interface_index)
@@ -46,7 +85,34 @@ impl NetlinkTunnel {
         })
     }
 
-    async fn setup(&mut self, config: &Config) -> Result<(), Error> {
+    /// Moves the device into `namespace`, and returns connections that operate in it along with
+    /// the new index of the device. Encrypted traffic is still sent from the namespace that the
+    /// device was created in.
+    async fn move_to_namespace(
+        tokio_handle: &tokio::runtime::Handle,
+        netlink_connections: &mut Handle,
+        interface_index: u32,
+        namespace: &Namespace,
+    ) -> Result<(Handle, u32), Error> {
+        netlink_connections
+            .set_namespace(interface_index, namespace.as_raw_fd())
+            .await?;
+        log::debug!(
+            "Moved WireGuard interface into network namespace {}",
+            namespace.name()
+        );
+
+        let tokio_handle = tokio_handle.clone();
+        let mut namespace_connections = namespace
+            .run(move || tokio_handle.block_on(Handle::connect()))
+            .map_err(Error::Namespace)??;
+        let namespace_index = namespace_connections
+            .get_device_index(MULLVAD_INTERFACE_NAME.to_string())
+            .await?;
+        Ok((namespace_connections, namespace_index))
+    }
+
+    async fn setup(&mut self, config: &Config, in_namespace: bool) -> Result<(), Error> {
         self.netlink_connections
             .wg_handle
             .set_config(self.interface_index, config)
@@ -58,6 +124,34 @@ impl NetlinkTunnel {
                 .await?;
         }
 
+        // The route manager only manages the root namespace, so the routes of the namespace are
+        // set up here. The tunnel is the only way out of the namespace.
+        if in_namespace {
+            self.netlink_connections
+                .set_link_up(LOOPBACK_INTERFACE_INDEX)
+                .await?;
+            self.netlink_connections
+                .set_link_up(self.interface_index)
+                .await?;
+
+            let mut destinations = vec![];
+            for destination in config.peers.iter().flat_map(|peer| peer.allowed_ips.iter()) {
+                let has_address = config
+                    .tunnel
+                    .addresses
+                    .iter()
+                    .any(|address| address.is_ipv4() == destination.is_ipv4());
+                if has_address && !destinations.contains(destination) {
+                    destinations.push(*destination);
+                }
+            }
+            for destination in destinations {
+                self.netlink_connections
+                    .add_route(self.interface_index, destination)
+                    .await?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -145,6 +145,10 @@ fn convert_config_to_dbus(config: &Config) -> DeviceConfig {
             "public-key".into(),
             Variant(Box::new(peer.public_key.to_base64())),
         );
+        peer_config.insert(
+            "persistent-keepalive".into(),
+            Variant(Box::new(u32::from(config.persistent_keepalive))),
+        );
 
         peer_configs.push(peer_config);
     }
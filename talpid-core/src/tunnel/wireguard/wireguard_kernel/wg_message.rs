@@ -86,6 +86,7 @@ impl DeviceMessage {
                 PeerNla::Endpoint(peer_endpoint),
                 PeerNla::AllowedIps(allowed_ips),
                 PeerNla::Flags(WGPEER_F_REPLACE_ALLOWEDIPS),
+                PeerNla::PersistentKeepaliveInterval(config.persistent_keepalive),
             ];
             if let Some(psk) = peer.psk.as_ref() {
                 peer_nlas.push(PeerNla::PresharedKey(*psk.as_bytes()));
@@ -96,7 +97,7 @@ impl DeviceMessage {
         let nlas = vec![
             DeviceNla::IfIndex(interface_index),
             DeviceNla::ListenPort(0),
-            DeviceNla::Fwmark(crate::linux::TUNNEL_FW_MARK),
+            DeviceNla::Fwmark(crate::linux::tunnel_fw_mark()),
             DeviceNla::PrivateKey(config.tunnel.private_key.to_bytes()),
             DeviceNla::Flags(WGDEVICE_F_REPLACE_PEERS),
             DeviceNla::Peers(peers),
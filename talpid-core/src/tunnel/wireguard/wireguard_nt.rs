@@ -836,11 +836,14 @@ fn serialize_config(config: &Config) -> Result<Vec<MaybeUninit<u8>>> {
     buffer.extend(windows::as_uninit_byte_slice(&header));
 
     for peer in &config.peers {
-        let flags = if peer.psk.is_some() {
+        let mut flags = if peer.psk.is_some() {
             WgPeerFlag::HAS_PRESHARED_KEY | WgPeerFlag::HAS_PUBLIC_KEY | WgPeerFlag::HAS_ENDPOINT
         } else {
             WgPeerFlag::HAS_PUBLIC_KEY | WgPeerFlag::HAS_ENDPOINT
         };
+        if config.persistent_keepalive != 0 {
+            flags |= WgPeerFlag::HAS_PERSISTENT_KEEPALIVE;
+        }
         let wg_peer = WgPeer {
             flags,
             reserved: 0,
@@ -850,7 +853,7 @@ fn serialize_config(config: &Config) -> Result<Vec<MaybeUninit<u8>>> {
                 .as_ref()
                 .map(|psk| psk.as_bytes().clone())
                 .unwrap_or([0u8; WIREGUARD_KEY_LENGTH]),
-            persistent_keepalive: 0,
+            persistent_keepalive: config.persistent_keepalive,
             endpoint: windows::inet_sockaddr_from_socketaddr(peer.endpoint).into(),
             tx_bytes: 0,
             rx_bytes: 0,
@@ -1041,8 +1044,11 @@ mod tests {
                 ipv4_gateway: "0.0.0.0".parse().unwrap(),
                 ipv6_gateway: None,
                 mtu: 0,
+                detect_mtu: false,
                 use_wireguard_nt: true,
                 obfuscator_config: None,
+                liveness: wireguard::LivenessOptions::default(),
+                persistent_keepalive: 0,
             }
         };
         static ref WG_STRUCT_CONFIG: Interface = Interface {
@@ -3,6 +3,8 @@ use super::{
     EventResult, SharedTunnelStateValues, TunnelCommand, TunnelCommandReceiver, TunnelState,
     TunnelStateTransition, TunnelStateWrapper,
 };
+#[cfg(not(target_os = "android"))]
+use crate::dns::forwarder::{DnsForwarder, FORWARDER_ADDRESS};
 use crate::{
     firewall::FirewallPolicy,
     tunnel::{TunnelEvent, TunnelMetadata},
@@ -16,7 +18,7 @@ use futures::{
 use std::net::IpAddr;
 use talpid_types::{
     net::TunnelParameters,
-    tunnel::{ErrorStateCause, FirewallPolicyError},
+    tunnel::{ErrorStateCause, FirewallPolicyError, TunnelInterface},
     BoxedError, ErrorExt,
 };
 
@@ -104,24 +106,90 @@ impl ConnectedState {
         }
     }
 
+    /// Returns the networks that are routed outside the tunnel, if the allowed IPs of the tunnel
+    /// have been overridden.
+    #[cfg(not(target_os = "android"))]
+    fn get_excluded_networks(&self) -> Vec<ipnetwork::IpNetwork> {
+        match &self.tunnel_parameters {
+            TunnelParameters::Wireguard(params) => params
+                .options
+                .allowed_ips
+                .as_deref()
+                .map(talpid_types::net::excluded_networks)
+                .unwrap_or_default(),
+            TunnelParameters::OpenVpn(_) => vec![],
+        }
+    }
+
+    /// Returns the DNS servers that may be reached. These include the resolvers of the split DNS
+    /// rules, if the forwarder is used.
+    #[cfg(not(target_os = "android"))]
+    fn get_allowed_dns_servers(&self, shared_values: &SharedTunnelStateValues) -> Vec<IpAddr> {
+        let mut servers = self.get_dns_servers(shared_values);
+        if self.use_dns_forwarder(shared_values) {
+            servers.push(FORWARDER_ADDRESS);
+            for server in shared_values
+                .split_dns_rules
+                .iter()
+                .flat_map(|rule| &rule.servers)
+            {
+                if !servers.contains(server) {
+                    servers.push(*server);
+                }
+            }
+        }
+        servers
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn use_dns_forwarder(&self, shared_values: &SharedTunnelStateValues) -> bool {
+        // The forwarder cannot be reached from inside a network namespace
+        #[cfg(target_os = "linux")]
+        if self.metadata.namespace.is_some() {
+            return false;
+        }
+        !shared_values.split_dns_rules.is_empty()
+            || shared_values.dns_filter.is_some()
+            || shared_values.dns_query_log.is_some()
+    }
+
     fn get_firewall_policy(&self, shared_values: &SharedTunnelStateValues) -> FirewallPolicy {
         FirewallPolicy::Connected {
             peer_endpoint: self.tunnel_parameters.get_next_hop_endpoint(),
             tunnel: self.metadata.clone(),
             allow_lan: shared_values.allow_lan,
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            inbound_tunnel_ports: shared_values.inbound_tunnel_ports.clone(),
+            allow_multicast: shared_values.allow_multicast,
             #[cfg(not(target_os = "android"))]
-            dns_servers: self.get_dns_servers(shared_values),
+            dns_servers: self.get_allowed_dns_servers(shared_values),
             #[cfg(windows)]
             relay_client: TunnelMonitor::get_relay_client(
                 &shared_values.resource_dir,
                 &self.tunnel_parameters,
             ),
+            #[cfg(windows)]
+            link_layer_exemptions: shared_values.link_layer_exemptions,
+            #[cfg(not(target_os = "android"))]
+            strict_dns_leak_prevention: shared_values.strict_dns_leak_prevention,
+            #[cfg(not(target_os = "android"))]
+            excluded_networks: self.get_excluded_networks(),
         }
     }
 
     fn set_dns(&self, shared_values: &mut SharedTunnelStateValues) -> Result<(), BoxedError> {
         let dns_ips = self.get_dns_servers(shared_values);
 
+        #[cfg(not(target_os = "android"))]
+        if self.use_dns_forwarder(shared_values) {
+            return self.set_dns_forwarder(shared_values, dns_ips);
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            shared_values.dns_forwarder = None;
+        }
+
         #[cfg(any(target_os = "linux", target_os = "windows"))]
         let dns_ips = dns_ips
             .into_iter()
@@ -132,6 +200,14 @@ impl ConnectedState {
             })
             .collect::<Vec<_>>();
 
+        // Processes in the namespace are the only ones that can reach the tunnel
+        #[cfg(target_os = "linux")]
+        if let Some(namespace) = &self.metadata.namespace {
+            return crate::linux::netns::Namespace::open(namespace)
+                .and_then(|namespace| namespace.set_dns(&dns_ips, &shared_values.search_domains))
+                .map_err(BoxedError::new);
+        }
+
         shared_values
             .dns_monitor
             .set(&self.metadata.interface, &dns_ips)
@@ -140,10 +216,63 @@ impl ConnectedState {
         Ok(())
     }
 
+    /// Points the system resolver at the local forwarder, which answers queries that the filter
+    /// decides on, sends queries that match a split DNS rule to the resolvers of the rule, and
+    /// other queries to `default_servers`. Queries are recorded in the query log, if enabled.
+    #[cfg(not(target_os = "android"))]
+    fn set_dns_forwarder(
+        &self,
+        shared_values: &mut SharedTunnelStateValues,
+        default_servers: Vec<IpAddr>,
+    ) -> Result<(), BoxedError> {
+        let rules = shared_values.split_dns_rules.clone();
+        let filter = shared_values.dns_filter.clone();
+        let query_log = shared_values.dns_query_log.clone();
+        match &shared_values.dns_forwarder {
+            Some(forwarder) => {
+                forwarder.set_upstreams(rules, default_servers);
+                forwarder.set_filter(filter);
+                forwarder.set_query_log(query_log);
+            }
+            None => {
+                let _guard = shared_values.runtime.enter();
+                let forwarder = DnsForwarder::start(rules, default_servers, filter, query_log)
+                    .map_err(BoxedError::new)?;
+                shared_values.dns_forwarder = Some(forwarder);
+            }
+        }
+
+        shared_values
+            .dns_monitor
+            .set(&self.metadata.interface, &[FORWARDER_ADDRESS])
+            .map_err(BoxedError::new)
+    }
+
+    /// Failing to limit the throughput is not considered serious enough to block the tunnel.
+    #[cfg(target_os = "linux")]
+    fn set_rate_limit(&self) {
+        if let Some(rate) = self.tunnel_parameters.get_generic_options().rate_limit {
+            if let Err(error) = crate::linux::rate_limit::apply(
+                &self.metadata.interface,
+                self.metadata.namespace.as_deref(),
+                rate,
+            ) {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to set rate limit")
+                );
+            }
+        }
+    }
+
     fn reset_dns(shared_values: &mut SharedTunnelStateValues) {
         if let Err(error) = shared_values.dns_monitor.reset() {
             log::error!("{}", error.display_chain_with_msg("Unable to reset DNS"));
         }
+        #[cfg(not(target_os = "android"))]
+        {
+            shared_values.dns_forwarder = None;
+        }
     }
 
     fn reset_routes(shared_values: &mut SharedTunnelStateValues) {
@@ -162,6 +291,37 @@ impl ConnectedState {
         }
     }
 
+    /// Passes a new filter or query log to the running forwarder. If the forwarder has to be
+    /// started or stopped, the firewall policy and DNS config are updated instead.
+    #[cfg(not(target_os = "android"))]
+    fn update_dns_forwarder(
+        self,
+        shared_values: &mut SharedTunnelStateValues,
+        used_dns_forwarder: bool,
+    ) -> EventConsequence {
+        if used_dns_forwarder == self.use_dns_forwarder(shared_values) {
+            if let Some(forwarder) = &shared_values.dns_forwarder {
+                forwarder.set_filter(shared_values.dns_filter.clone());
+                forwarder.set_query_log(shared_values.dns_query_log.clone());
+            }
+            return EventConsequence::SameState(self.into());
+        }
+        if let Err(error) = self.set_firewall_policy(shared_values) {
+            return self.disconnect(
+                shared_values,
+                AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+            );
+        }
+        match self.set_dns(shared_values) {
+            Ok(()) => EventConsequence::SameState(self.into()),
+            Err(error) => {
+                log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+                let cause = shared_values.dns_error_cause(&error);
+                self.disconnect(shared_values, AfterDisconnect::Block(cause))
+            }
+        }
+    }
+
     fn disconnect(
         self,
         shared_values: &mut SharedTunnelStateValues,
@@ -209,6 +369,81 @@ impl ConnectedState {
                     }
                 }
             }
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                match shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    Ok(true) => match self.set_firewall_policy(shared_values) {
+                        Ok(()) => {
+                            cfg_if! {
+                                if #[cfg(target_os = "android")] {
+                                    self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
+                                } else {
+                                    SameState(self.into())
+                                }
+                            }
+                        }
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    },
+                    Ok(false) => SameState(self.into()),
+                    Err(error_cause) => {
+                        self.disconnect(shared_values, AfterDisconnect::Block(error_cause))
+                    }
+                }
+            }
+            Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                if shared_values.allow_multicast == allow_multicast {
+                    return SameState(self.into());
+                }
+                shared_values.allow_multicast = allow_multicast;
+                match self.set_firewall_policy(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    ),
+                }
+            }
+            Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                shared_values.firewall_exceptions = exceptions;
+                match self.set_firewall_policy(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    ),
+                }
+            }
+            Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                // The tunnel is up, so there is nothing to permit until it goes down
+                shared_values.captive_portal = captive_portal;
+                SameState(self.into())
+            }
+            Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                if shared_values.inbound_tunnel_ports == ports {
+                    return SameState(self.into());
+                }
+                shared_values.inbound_tunnel_ports = ports;
+                match self.set_firewall_policy(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    ),
+                }
+            }
+            #[cfg(windows)]
+            Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                shared_values.link_layer_exemptions = exemptions;
+                match self.set_firewall_policy(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    ),
+                }
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 shared_values.allowed_endpoint = endpoint;
                 let _ = tx.send(());
@@ -230,10 +465,8 @@ impl ConnectedState {
                         Ok(()) => SameState(self.into()),
                         Err(error) => {
                             log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
-                            self.disconnect(
-                                shared_values,
-                                AfterDisconnect::Block(ErrorStateCause::SetDnsError),
-                            )
+                            let cause = shared_values.dns_error_cause(&error);
+                            self.disconnect(shared_values, AfterDisconnect::Block(cause))
                         }
                     }
                 }
@@ -242,10 +475,78 @@ impl ConnectedState {
                     self.disconnect(shared_values, AfterDisconnect::Block(error_cause))
                 }
             },
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SearchDomains(domains)) => {
+                if !shared_values.set_search_domains(domains) {
+                    return SameState(self.into());
+                }
+                match self.set_dns(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => {
+                        log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+                        let cause = shared_values.dns_error_cause(&error);
+                        self.disconnect(shared_values, AfterDisconnect::Block(cause))
+                    }
+                }
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                if !shared_values.set_strict_dns_leak_prevention(enabled) {
+                    return SameState(self.into());
+                }
+                match self.set_firewall_policy(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    ),
+                }
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SplitDns(rules)) => {
+                if !shared_values.set_split_dns_rules(rules) {
+                    return SameState(self.into());
+                }
+                if let Err(error) = self.set_firewall_policy(shared_values) {
+                    return self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    );
+                }
+                match self.set_dns(shared_values) {
+                    Ok(()) => SameState(self.into()),
+                    Err(error) => {
+                        log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+                        let cause = shared_values.dns_error_cause(&error);
+                        self.disconnect(shared_values, AfterDisconnect::Block(cause))
+                    }
+                }
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsFilter(filter)) => {
+                let used_dns_forwarder = self.use_dns_forwarder(shared_values);
+                if !shared_values.set_dns_filter(filter) {
+                    return SameState(self.into());
+                }
+                self.update_dns_forwarder(shared_values, used_dns_forwarder)
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                let used_dns_forwarder = self.use_dns_forwarder(shared_values);
+                if !shared_values.set_dns_query_log(query_log) {
+                    return SameState(self.into());
+                }
+                self.update_dns_forwarder(shared_values, used_dns_forwarder)
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                 shared_values.block_when_disconnected = block_when_disconnected;
                 SameState(self.into())
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                shared_values.set_strict_enforcement(strict_enforcement);
+                SameState(self.into())
+            }
             Some(TunnelCommand::IsOffline(is_offline)) => {
                 shared_values.is_offline = is_offline;
                 if is_offline {
@@ -323,7 +624,14 @@ impl TunnelState for ConnectedState {
         bootstrap: Self::Bootstrap,
     ) -> (TunnelStateWrapper, TunnelStateTransition) {
         let connected_state = ConnectedState::from(bootstrap);
-        let tunnel_endpoint = connected_state.tunnel_parameters.get_tunnel_endpoint();
+        let mut tunnel_endpoint = connected_state.tunnel_parameters.get_tunnel_endpoint();
+        tunnel_endpoint.mtu = connected_state.metadata.mtu;
+        tunnel_endpoint.wireguard_implementation =
+            connected_state.metadata.wireguard_implementation;
+        let tunnel_interface = TunnelInterface {
+            name: connected_state.metadata.interface.clone(),
+            ips: connected_state.metadata.ips.clone(),
+        };
 
         if let Err(error) = connected_state.set_firewall_policy(shared_values) {
             DisconnectingState::enter(
@@ -336,18 +644,21 @@ impl TunnelState for ConnectedState {
             )
         } else if let Err(error) = connected_state.set_dns(shared_values) {
             log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+            let cause = shared_values.dns_error_cause(&error);
             DisconnectingState::enter(
                 shared_values,
                 (
                     connected_state.tunnel_close_tx,
                     connected_state.tunnel_close_event,
-                    AfterDisconnect::Block(ErrorStateCause::SetDnsError),
+                    AfterDisconnect::Block(cause),
                 ),
             )
         } else {
+            #[cfg(target_os = "linux")]
+            connected_state.set_rate_limit();
             (
                 TunnelStateWrapper::from(connected_state),
-                TunnelStateTransition::Connected(tunnel_endpoint),
+                TunnelStateTransition::Connected(tunnel_endpoint, tunnel_interface),
             )
         }
     }
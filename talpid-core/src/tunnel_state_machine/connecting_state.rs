@@ -71,10 +71,16 @@ impl ConnectingState {
             peer_endpoint,
             tunnel: tunnel_metadata.clone(),
             allow_lan: shared_values.allow_lan,
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            allow_multicast: shared_values.allow_multicast,
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
+            captive_portal: shared_values.captive_portal,
             #[cfg(windows)]
             relay_client: TunnelMonitor::get_relay_client(&shared_values.resource_dir, &params),
+            #[cfg(windows)]
+            link_layer_exemptions: shared_values.link_layer_exemptions,
         };
         shared_values
             .firewall
@@ -117,12 +123,26 @@ impl ConnectingState {
         let log_dir = log_dir.clone();
         let resource_dir = resource_dir.to_path_buf();
 
-        let (tunnel_close_tx, tunnel_close_rx) = oneshot::channel();
+        let (tunnel_close_tx, mut tunnel_close_rx) = oneshot::channel();
         let (tunnel_close_event_tx, tunnel_close_event_rx) = oneshot::channel();
 
         let mut tunnel_parameters = parameters.clone();
+        let retry_delay = parameters
+            .get_generic_options()
+            .retry_policy
+            .delay(retry_attempt);
 
         tokio::task::spawn_blocking(move || {
+            if !retry_delay.is_zero() {
+                log::debug!("Waiting {:?} before retrying to connect", retry_delay);
+                let closed =
+                    runtime.block_on(tokio::time::timeout(retry_delay, &mut tunnel_close_rx));
+                if closed.is_ok() {
+                    let _ = tunnel_close_event_tx.send(None);
+                    return;
+                }
+            }
+
             let start = Instant::now();
 
             let route_manager_handle = match route_manager_handle {
@@ -333,6 +353,40 @@ impl ConnectingState {
                     self.reset_firewall(shared_values)
                 }
             }
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                match shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    Ok(true) => self.reset_firewall(shared_values),
+                    Ok(false) => SameState(self.into()),
+                    Err(error_cause) => {
+                        self.disconnect(shared_values, AfterDisconnect::Block(error_cause))
+                    }
+                }
+            }
+            Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                if shared_values.allow_multicast != allow_multicast {
+                    shared_values.allow_multicast = allow_multicast;
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self.into())
+                }
+            }
+            Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                shared_values.firewall_exceptions = exceptions;
+                self.reset_firewall(shared_values)
+            }
+            Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                shared_values.captive_portal = captive_portal;
+                self.reset_firewall(shared_values)
+            }
+            Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                shared_values.inbound_tunnel_ports = ports;
+                SameState(self.into())
+            }
+            #[cfg(windows)]
+            Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                shared_values.link_layer_exemptions = exemptions;
+                self.reset_firewall(shared_values)
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -358,10 +412,40 @@ impl ConnectingState {
                 Ok(_) => SameState(self.into()),
                 Err(cause) => self.disconnect(shared_values, AfterDisconnect::Block(cause)),
             },
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SplitDns(rules)) => {
+                shared_values.set_split_dns_rules(rules);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsFilter(filter)) => {
+                shared_values.set_dns_filter(filter);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                shared_values.set_dns_query_log(query_log);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SearchDomains(domains)) => {
+                shared_values.set_search_domains(domains);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                shared_values.set_strict_dns_leak_prevention(enabled);
+                SameState(self.into())
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                 shared_values.block_when_disconnected = block_when_disconnected;
                 SameState(self.into())
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                shared_values.set_strict_enforcement(strict_enforcement);
+                SameState(self.into())
+            }
             Some(TunnelCommand::IsOffline(is_offline)) => {
                 shared_values.is_offline = is_offline;
                 if is_offline {
@@ -553,6 +637,19 @@ impl TunnelState for ConnectingState {
                 ErrorState::enter(shared_values, ErrorStateCause::TunnelParameterError(err))
             }
             Ok(tunnel_parameters) => {
+                let retry_policy = tunnel_parameters.get_generic_options().retry_policy;
+                if retry_policy.exceeds_max_attempts(retry_attempt) {
+                    log::error!(
+                        "Giving up after {} failed attempts at connecting",
+                        retry_attempt
+                    );
+                    #[cfg(not(target_os = "android"))]
+                    let cause = ErrorStateCause::RetryLimitReached(retry_attempt);
+                    #[cfg(target_os = "android")]
+                    let cause = ErrorStateCause::StartTunnelError;
+                    return ErrorState::enter(shared_values, cause);
+                }
+
                 #[cfg(windows)]
                 if let Err(error) = shared_values.split_tunnel.set_tunnel_addresses(None) {
                     log::error!(
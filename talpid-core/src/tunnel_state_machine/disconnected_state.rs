@@ -23,9 +23,15 @@ impl DisconnectedState {
         let result = if shared_values.block_when_disconnected {
             let policy = FirewallPolicy::Blocked {
                 allow_lan: shared_values.allow_lan,
+                allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
+                firewall_exceptions: shared_values.firewall_exceptions.clone(),
+                allow_multicast: shared_values.allow_multicast,
                 allowed_endpoint: shared_values.allowed_endpoint.clone(),
+                captive_portal: shared_values.captive_portal,
                 #[cfg(target_os = "macos")]
                 dns_redirect_port: shared_values.filtering_resolver.listening_port(),
+                #[cfg(windows)]
+                link_layer_exemptions: shared_values.link_layer_exemptions,
             };
 
             shared_values.firewall.apply_policy(policy).map_err(|e| {
@@ -147,6 +153,49 @@ impl TunnelState for DisconnectedState {
                 }
                 SameState(self.into())
             }
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                // Same situation as allow LAN above.
+                if shared_values
+                    .set_allowed_lan_nets(allowed_lan_nets)
+                    .expect("Failed to set allowed LAN networks")
+                {
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                SameState(self.into())
+            }
+            Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                if shared_values.allow_multicast != allow_multicast {
+                    shared_values.allow_multicast = allow_multicast;
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                SameState(self.into())
+            }
+            Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                if shared_values.firewall_exceptions != exceptions {
+                    shared_values.firewall_exceptions = exceptions;
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                SameState(self.into())
+            }
+            Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                if shared_values.captive_portal != captive_portal {
+                    shared_values.captive_portal = captive_portal;
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                SameState(self.into())
+            }
+            Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                shared_values.inbound_tunnel_ports = ports;
+                SameState(self.into())
+            }
+            #[cfg(windows)]
+            Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                if shared_values.link_layer_exemptions != exemptions {
+                    shared_values.link_layer_exemptions = exemptions;
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                SameState(self.into())
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -163,6 +212,31 @@ impl TunnelState for DisconnectedState {
 
                 SameState(self.into())
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SplitDns(rules)) => {
+                shared_values.set_split_dns_rules(rules);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsFilter(filter)) => {
+                shared_values.set_dns_filter(filter);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                shared_values.set_dns_query_log(query_log);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SearchDomains(domains)) => {
+                shared_values.set_search_domains(domains);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                shared_values.set_strict_dns_leak_prevention(enabled);
+                SameState(self.into())
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                 if shared_values.block_when_disconnected != block_when_disconnected {
                     shared_values.block_when_disconnected = block_when_disconnected;
@@ -187,6 +261,11 @@ impl TunnelState for DisconnectedState {
                 }
                 SameState(self.into())
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                shared_values.set_strict_enforcement(strict_enforcement);
+                SameState(self.into())
+            }
             Some(TunnelCommand::IsOffline(is_offline)) => {
                 shared_values.is_offline = is_offline;
                 SameState(self.into())
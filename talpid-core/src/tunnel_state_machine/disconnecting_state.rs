@@ -4,8 +4,13 @@ use super::{
     TunnelState, TunnelStateTransition, TunnelStateWrapper,
 };
 use futures::{channel::oneshot, future::FusedFuture, StreamExt};
+use std::time::Duration;
 use talpid_types::tunnel::{ActionAfterDisconnect, ErrorStateCause};
 
+/// How long to wait for the tunnel to close once the state machine is shutting down. This must be
+/// shorter than `TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT`, so that the remaining teardown can finish.
+const SHUTDOWN_TUNNEL_CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
+
 /// This state is active from when we manually trigger a tunnel kill until the tunnel wait
 /// operation (TunnelExit) returned.
 pub struct DisconnectingState {
@@ -27,6 +32,10 @@ impl DisconnectingState {
                     let _ = shared_values.set_allow_lan(allow_lan);
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                    let _ = shared_values.set_allowed_lan_nets(allowed_lan_nets);
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -36,10 +45,40 @@ impl DisconnectingState {
                     let _ = shared_values.set_dns_servers(servers);
                     AfterDisconnect::Nothing
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SplitDns(rules)) => {
+                    shared_values.set_split_dns_rules(rules);
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsFilter(filter)) => {
+                    shared_values.set_dns_filter(filter);
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                    shared_values.set_dns_query_log(query_log);
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SearchDomains(domains)) => {
+                    shared_values.set_search_domains(domains);
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                    shared_values.set_strict_dns_leak_prevention(enabled);
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                     shared_values.block_when_disconnected = block_when_disconnected;
                     AfterDisconnect::Nothing
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                    shared_values.set_strict_enforcement(strict_enforcement);
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::IsOffline(is_offline)) => {
                     shared_values.is_offline = is_offline;
                     AfterDisconnect::Nothing
@@ -57,12 +96,37 @@ impl DisconnectingState {
                     shared_values.split_tunnel.set_paths(&paths, result_tx);
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                    shared_values.allow_multicast = allow_multicast;
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                    shared_values.firewall_exceptions = exceptions;
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                    shared_values.captive_portal = captive_portal;
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                    shared_values.inbound_tunnel_ports = ports;
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(windows)]
+                Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                    shared_values.link_layer_exemptions = exemptions;
+                    AfterDisconnect::Nothing
+                }
             },
             AfterDisconnect::Block(reason) => match command {
                 Some(TunnelCommand::AllowLan(allow_lan)) => {
                     let _ = shared_values.set_allow_lan(allow_lan);
                     AfterDisconnect::Block(reason)
                 }
+                Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                    let _ = shared_values.set_allowed_lan_nets(allowed_lan_nets);
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -72,10 +136,40 @@ impl DisconnectingState {
                     let _ = shared_values.set_dns_servers(servers);
                     AfterDisconnect::Block(reason)
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SplitDns(rules)) => {
+                    shared_values.set_split_dns_rules(rules);
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsFilter(filter)) => {
+                    shared_values.set_dns_filter(filter);
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                    shared_values.set_dns_query_log(query_log);
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SearchDomains(domains)) => {
+                    shared_values.set_search_domains(domains);
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                    shared_values.set_strict_dns_leak_prevention(enabled);
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                     shared_values.block_when_disconnected = block_when_disconnected;
                     AfterDisconnect::Block(reason)
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                    shared_values.set_strict_enforcement(strict_enforcement);
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::IsOffline(is_offline)) => {
                     shared_values.is_offline = is_offline;
                     if !is_offline && reason == ErrorStateCause::IsOffline {
@@ -97,6 +191,27 @@ impl DisconnectingState {
                     shared_values.split_tunnel.set_paths(&paths, result_tx);
                     AfterDisconnect::Block(reason)
                 }
+                Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                    shared_values.allow_multicast = allow_multicast;
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                    shared_values.firewall_exceptions = exceptions;
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                    shared_values.captive_portal = captive_portal;
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                    shared_values.inbound_tunnel_ports = ports;
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(windows)]
+                Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                    shared_values.link_layer_exemptions = exemptions;
+                    AfterDisconnect::Block(reason)
+                }
                 None => AfterDisconnect::Block(reason),
             },
             AfterDisconnect::Reconnect(retry_attempt) => match command {
@@ -104,6 +219,10 @@ impl DisconnectingState {
                     let _ = shared_values.set_allow_lan(allow_lan);
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                    let _ = shared_values.set_allowed_lan_nets(allowed_lan_nets);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
                     let _ = tx.send(());
@@ -113,10 +232,40 @@ impl DisconnectingState {
                     let _ = shared_values.set_dns_servers(servers);
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SplitDns(rules)) => {
+                    shared_values.set_split_dns_rules(rules);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsFilter(filter)) => {
+                    shared_values.set_dns_filter(filter);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                    shared_values.set_dns_query_log(query_log);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::SearchDomains(domains)) => {
+                    shared_values.set_search_domains(domains);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                    shared_values.set_strict_dns_leak_prevention(enabled);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                     shared_values.block_when_disconnected = block_when_disconnected;
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                #[cfg(not(target_os = "android"))]
+                Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                    shared_values.set_strict_enforcement(strict_enforcement);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::IsOffline(is_offline)) => {
                     shared_values.is_offline = is_offline;
                     if is_offline {
@@ -138,6 +287,27 @@ impl DisconnectingState {
                     shared_values.split_tunnel.set_paths(&paths, result_tx);
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                    shared_values.allow_multicast = allow_multicast;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                    shared_values.firewall_exceptions = exceptions;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                    shared_values.captive_portal = captive_portal;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                    shared_values.inbound_tunnel_ports = ports;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(windows)]
+                Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                    shared_values.link_layer_exemptions = exemptions;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
             },
         };
 
@@ -198,6 +368,22 @@ impl TunnelState for DisconnectingState {
             } else {
                 EventResult::Close(Ok(None))
             }
+        } else if commands.is_done() {
+            // Don't let a tunnel that fails to close prevent firewall and DNS teardown
+            runtime.block_on(async {
+                match tokio::time::timeout(
+                    SHUTDOWN_TUNNEL_CLOSE_TIMEOUT,
+                    &mut self.tunnel_close_event,
+                )
+                .await
+                {
+                    Ok(result) => EventResult::Close(result),
+                    Err(_) => {
+                        log::error!("Tunnel did not close in time. Proceeding with shutdown");
+                        EventResult::Close(Ok(None))
+                    }
+                }
+            })
         } else {
             runtime.block_on(async {
                 futures::select! {
@@ -22,9 +22,15 @@ impl ErrorState {
     ) -> Result<(), FirewallPolicyError> {
         let policy = FirewallPolicy::Blocked {
             allow_lan: shared_values.allow_lan,
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
+            firewall_exceptions: shared_values.firewall_exceptions.clone(),
+            allow_multicast: shared_values.allow_multicast,
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
+            captive_portal: shared_values.captive_portal,
             #[cfg(target_os = "macos")]
             dns_redirect_port: shared_values.filtering_resolver.listening_port(),
+            #[cfg(windows)]
+            link_layer_exemptions: shared_values.link_layer_exemptions,
         };
 
         #[cfg(target_os = "linux")]
@@ -148,6 +154,45 @@ impl TunnelState for ErrorState {
                     SameState(self.into())
                 }
             }
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets)) => {
+                match shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    Ok(true) => {
+                        let _ = Self::set_firewall_policy(shared_values);
+                        SameState(self.into())
+                    }
+                    Ok(false) => SameState(self.into()),
+                    Err(error_state_cause) => {
+                        NewState(Self::enter(shared_values, error_state_cause))
+                    }
+                }
+            }
+            Some(TunnelCommand::AllowMulticast(allow_multicast)) => {
+                if shared_values.allow_multicast != allow_multicast {
+                    shared_values.allow_multicast = allow_multicast;
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                SameState(self.into())
+            }
+            Some(TunnelCommand::FirewallExceptions(exceptions)) => {
+                shared_values.firewall_exceptions = exceptions;
+                let _ = Self::set_firewall_policy(shared_values);
+                SameState(self.into())
+            }
+            Some(TunnelCommand::CaptivePortal(captive_portal)) => {
+                shared_values.captive_portal = captive_portal;
+                let _ = Self::set_firewall_policy(shared_values);
+                SameState(self.into())
+            }
+            Some(TunnelCommand::InboundTunnelPorts(ports)) => {
+                shared_values.inbound_tunnel_ports = ports;
+                SameState(self.into())
+            }
+            #[cfg(windows)]
+            Some(TunnelCommand::LinkLayerExemptions(exemptions)) => {
+                shared_values.link_layer_exemptions = exemptions;
+                let _ = Self::set_firewall_policy(shared_values);
+                SameState(self.into())
+            }
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -172,10 +217,40 @@ impl TunnelState for ErrorState {
                     SameState(self.into())
                 }
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SplitDns(rules)) => {
+                shared_values.set_split_dns_rules(rules);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsFilter(filter)) => {
+                shared_values.set_dns_filter(filter);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::DnsQueryLog(query_log)) => {
+                shared_values.set_dns_query_log(query_log);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::SearchDomains(domains)) => {
+                shared_values.set_search_domains(domains);
+                SameState(self.into())
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictDnsLeakPrevention(enabled)) => {
+                shared_values.set_strict_dns_leak_prevention(enabled);
+                SameState(self.into())
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected)) => {
                 shared_values.block_when_disconnected = block_when_disconnected;
                 SameState(self.into())
             }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::StrictEnforcement(strict_enforcement)) => {
+                shared_values.set_strict_enforcement(strict_enforcement);
+                SameState(self.into())
+            }
             Some(TunnelCommand::IsOffline(is_offline)) => {
                 shared_values.is_offline = is_offline;
                 if !is_offline && self.block_reason == ErrorStateCause::IsOffline {
@@ -24,10 +24,13 @@ use crate::{
 #[cfg(windows)]
 use std::ffi::OsString;
 
+#[cfg(not(target_os = "android"))]
+use crate::dns::forwarder::{DnsFilter, DnsQueryLog};
 use futures::{
     channel::{mpsc, oneshot},
     stream, StreamExt,
 };
+use ipnetwork::IpNetwork;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
@@ -41,10 +44,18 @@ use std::{
     time::Duration,
 };
 #[cfg(target_os = "android")]
-use talpid_types::{android::AndroidContext, ErrorExt};
+use talpid_types::android::AndroidContext;
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::dns::SplitDnsRule;
+#[cfg(windows)]
+use talpid_types::net::LinkLayerExemptions;
 use talpid_types::{
-    net::{AllowedEndpoint, TunnelParameters},
+    net::{
+        AllowedEndpoint, CaptivePortal, FirewallException, InboundTunnelPort, OfflineDetection,
+        OfflineStatus, ReconnectDebounce, TunnelParameters,
+    },
     tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
+    BoxedError, ErrorExt,
 };
 
 const TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
@@ -91,18 +102,68 @@ pub enum Error {
 pub struct InitialTunnelState {
     /// Whether to allow LAN traffic when not in the (non-blocking) disconnected state.
     pub allow_lan: bool,
+    /// Networks to allow when LAN traffic is allowed. If `None`, all private networks are
+    /// allowed.
+    pub allowed_lan_nets: Option<Vec<IpNetwork>>,
+    /// Ports to keep open towards the local network, regardless of the tunnel state.
+    pub firewall_exceptions: Vec<FirewallException>,
+    /// Ports that accept unsolicited inbound connections from inside the tunnel.
+    pub inbound_tunnel_ports: Vec<InboundTunnelPort>,
+    /// Whether to allow mDNS and SSDP discovery when not in the (non-blocking) disconnected state.
+    pub allow_multicast: bool,
     /// Block traffic unless connected to the VPN.
     pub block_when_disconnected: bool,
+    /// Block traffic rather than connect with a partially applied DNS config.
+    #[cfg(not(target_os = "android"))]
+    pub strict_enforcement: bool,
     /// DNS servers to use. If `None`, the tunnel gateway is used.
     pub dns_servers: Option<Vec<IpAddr>>,
+    /// Domains whose DNS queries are sent to other resolvers than `dns_servers`.
+    #[cfg(not(target_os = "android"))]
+    pub split_dns_rules: Vec<SplitDnsRule>,
+    /// Filter that DNS queries pass through before they are forwarded while connected.
+    #[cfg(not(target_os = "android"))]
+    pub dns_filter: Option<Arc<dyn DnsFilter>>,
+    /// Log that DNS queries are recorded in while connected, if enabled.
+    #[cfg(not(target_os = "android"))]
+    pub dns_query_log: Option<Arc<DnsQueryLog>>,
+    /// Domains that are appended to short names by the system resolver while connected.
+    #[cfg(not(target_os = "android"))]
+    pub search_domains: Vec<String>,
+    /// Whether to block encrypted DNS to other resolvers than `dns_servers` while connected.
+    #[cfg(not(target_os = "android"))]
+    pub strict_dns_leak_prevention: bool,
     /// A single endpoint that is allowed to communicate outside the tunnel, i.e.
     /// in any of the blocking states.
     pub allowed_endpoint: AllowedEndpoint,
     /// Whether to reset any existing firewall rules when initializing the disconnected state.
     pub reset_firewall: bool,
+    /// How long to wait for the connectivity of the host to settle before reacting to it.
+    pub reconnect_debounce: ReconnectDebounce,
+    /// When to consider the host to be offline.
+    pub offline_detection: OfflineDetection,
     /// Programs to exclude from the tunnel using the split tunnel driver.
     #[cfg(windows)]
     pub exclude_paths: Vec<OsString>,
+    /// Link layer protocols to permit on physical adapters in the blocking states.
+    #[cfg(windows)]
+    pub link_layer_exemptions: LinkLayerExemptions,
+    /// Changes that a previous instance did not undo because it did not exit cleanly.
+    pub leftover_state: LeftoverState,
+}
+
+/// System changes that a previous instance may have left behind. These are undone before the
+/// initial state is entered.
+///
+/// A leftover firewall policy is replaced or reset by the initial state, depending on
+/// `block_when_disconnected` and `reset_firewall`. On Windows and macOS, routes and DNS servers
+/// are bound to the tunnel interface, and disappear with it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LeftoverState {
+    /// The system resolvers were changed.
+    pub dns: bool,
+    /// Routes or routing rules were added.
+    pub routes: bool,
 }
 
 /// Spawn the tunnel state machine thread, returning a channel for sending tunnel commands.
@@ -126,16 +187,31 @@ pub async fn spawn(
         #[cfg(target_os = "android")]
         initial_settings.allow_lan,
         #[cfg(target_os = "android")]
+        initial_settings
+            .allowed_lan_nets
+            .clone()
+            .unwrap_or_else(crate::firewall::default_allowed_lan_nets),
+        #[cfg(target_os = "android")]
         initial_settings.dns_servers.clone(),
     );
 
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
     let weak_command_tx = Arc::downgrade(&command_tx);
+    let reconnect_debounce = Arc::new(Mutex::new(initial_settings.reconnect_debounce));
+    let offline_detection = Arc::new(Mutex::new(offline::Detection {
+        settings: initial_settings.offline_detection,
+        ..offline::Detection::default()
+    }));
+    let (offline_tx, offline_rx) = mpsc::unbounded();
 
     let init_args = TunnelStateMachineInitArgs {
         settings: initial_settings,
         command_tx: weak_command_tx,
+        reconnect_debounce: reconnect_debounce.clone(),
+        offline_detection: offline_detection.clone(),
+        offline_tx: offline_tx.clone(),
+        offline_rx,
         offline_state_tx: offline_state_listener,
         tunnel_parameters_generator,
         tun_provider,
@@ -165,6 +241,9 @@ pub async fn spawn(
     Ok(TunnelStateMachineHandle {
         command_tx,
         shutdown_rx,
+        reconnect_debounce,
+        offline_detection,
+        offline_tx,
         #[cfg(windows)]
         split_tunnel,
     })
@@ -174,14 +253,44 @@ pub async fn spawn(
 pub enum TunnelCommand {
     /// Enable or disable LAN access in the firewall.
     AllowLan(bool),
+    /// Set the networks to allow when LAN access is enabled. If `None`, all private networks
+    /// are allowed.
+    AllowedLanNets(Option<Vec<IpNetwork>>),
+    /// Set the ports to keep open towards the local network.
+    FirewallExceptions(Vec<FirewallException>),
+    /// Set the ports that accept inbound connections from inside the tunnel.
+    InboundTunnelPorts(Vec<InboundTunnelPort>),
+    /// Temporarily permit traffic outside the tunnel so that a captive portal can be signed in
+    /// to, or stop doing so if `None`. This only affects the connecting and blocked states.
+    CaptivePortal(Option<CaptivePortal>),
+    /// Enable or disable mDNS and SSDP discovery in the firewall.
+    AllowMulticast(bool),
     /// Endpoint that should never be blocked. `()` is sent to the
     /// channel after attempting to set the firewall policy, regardless
     /// of whether it succeeded.
     AllowEndpoint(AllowedEndpoint, oneshot::Sender<()>),
     /// Set DNS servers to use.
     Dns(Option<Vec<IpAddr>>),
+    /// Set the domains whose DNS queries are sent to other resolvers.
+    #[cfg(not(target_os = "android"))]
+    SplitDns(Vec<SplitDnsRule>),
+    /// Set the filter that DNS queries pass through while connected.
+    #[cfg(not(target_os = "android"))]
+    DnsFilter(Option<Arc<dyn DnsFilter>>),
+    /// Start recording DNS queries in a log while connected, or stop if `None`.
+    #[cfg(not(target_os = "android"))]
+    DnsQueryLog(Option<Arc<DnsQueryLog>>),
+    /// Set the domains that are appended to short names while connected.
+    #[cfg(not(target_os = "android"))]
+    SearchDomains(Vec<String>),
+    /// Enable or disable blocking of encrypted DNS to other resolvers while connected.
+    #[cfg(not(target_os = "android"))]
+    StrictDnsLeakPrevention(bool),
     /// Enable or disable the block_when_disconnected feature.
     BlockWhenDisconnected(bool),
+    /// Enable or disable strict enforcement of the DNS config.
+    #[cfg(not(target_os = "android"))]
+    StrictEnforcement(bool),
     /// Notify the state machine of the connectivity of the device.
     IsOffline(bool),
     /// Open tunnel connection.
@@ -199,6 +308,9 @@ pub enum TunnelCommand {
         oneshot::Sender<Result<(), split_tunnel::Error>>,
         Vec<OsString>,
     ),
+    /// Set which link layer protocols the firewall should permit on physical adapters.
+    #[cfg(windows)]
+    LinkLayerExemptions(LinkLayerExemptions),
 }
 
 type TunnelCommandReceiver = stream::Fuse<mpsc::UnboundedReceiver<TunnelCommand>>;
@@ -225,6 +337,10 @@ struct TunnelStateMachine {
 struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     settings: InitialTunnelState,
     command_tx: std::sync::Weak<mpsc::UnboundedSender<TunnelCommand>>,
+    reconnect_debounce: Arc<Mutex<ReconnectDebounce>>,
+    offline_detection: Arc<Mutex<offline::Detection>>,
+    offline_tx: mpsc::UnboundedSender<bool>,
+    offline_rx: mpsc::UnboundedReceiver<bool>,
     offline_state_tx: mpsc::UnboundedSender<bool>,
     tunnel_parameters_generator: G,
     tun_provider: TunProvider,
@@ -267,6 +383,12 @@ impl TunnelStateMachine {
         )
         .map_err(Error::InitSplitTunneling)?;
 
+        let allowed_lan_nets = args
+            .settings
+            .allowed_lan_nets
+            .clone()
+            .unwrap_or_else(crate::firewall::default_allowed_lan_nets);
+
         let fw_args = FirewallArguments {
             initial_state: if args.settings.block_when_disconnected || !args.settings.reset_firewall
             {
@@ -275,13 +397,19 @@ impl TunnelStateMachine {
                 InitialFirewallState::None
             },
             allow_lan: args.settings.allow_lan,
+            allowed_lan_nets: allowed_lan_nets.clone(),
+            firewall_exceptions: args.settings.firewall_exceptions.clone(),
+            allow_multicast: args.settings.allow_multicast,
+            #[cfg(windows)]
+            link_layer_exemptions: args.settings.link_layer_exemptions,
         };
 
         let firewall = Firewall::from_args(fw_args).map_err(Error::InitFirewallError)?;
         let route_manager = RouteManager::new(HashSet::new())
             .await
             .map_err(Error::InitRouteManagerError)?;
-        let dns_monitor = DnsMonitor::new(
+        #[cfg_attr(target_os = "android", allow(unused_mut))]
+        let mut dns_monitor = DnsMonitor::new(
             #[cfg(target_os = "linux")]
             runtime.clone(),
             #[cfg(target_os = "linux")]
@@ -292,21 +420,13 @@ impl TunnelStateMachine {
             args.command_tx.clone(),
         )
         .map_err(Error::InitDnsMonitorError)?;
+        #[cfg(not(target_os = "android"))]
+        dns_monitor.set_strict(args.settings.strict_enforcement);
+        #[cfg(not(target_os = "android"))]
+        dns_monitor.set_search_domains(args.settings.search_domains.clone());
 
-        let (offline_tx, mut offline_rx) = mpsc::unbounded();
-        let initial_offline_state_tx = args.offline_state_tx.clone();
-        tokio::spawn(async move {
-            while let Some(offline) = offline_rx.next().await {
-                if let Some(tx) = args.command_tx.upgrade() {
-                    let _ = tx.unbounded_send(TunnelCommand::IsOffline(offline));
-                } else {
-                    break;
-                }
-                let _ = args.offline_state_tx.unbounded_send(offline);
-            }
-        });
         let offline_monitor = offline::spawn_monitor(
-            offline_tx,
+            args.offline_tx,
             #[cfg(target_os = "linux")]
             route_manager
                 .handle()
@@ -318,8 +438,30 @@ impl TunnelStateMachine {
         )
         .await
         .map_err(Error::OfflineMonitorError)?;
-        let is_offline = offline_monitor.host_is_offline().await;
-        let _ = initial_offline_state_tx.unbounded_send(is_offline);
+        let monitor_offline = offline_monitor.host_is_offline().await;
+        let is_offline = {
+            let mut detection = args.offline_detection.lock().unwrap();
+            detection.monitor_offline = monitor_offline;
+            detection.is_offline = detection.apply(monitor_offline);
+            detection.is_offline
+        };
+        let _ = args.offline_state_tx.unbounded_send(is_offline);
+        let command_tx = args.command_tx.clone();
+        let offline_state_tx = args.offline_state_tx;
+        tokio::spawn(offline::debounce::forward(
+            args.offline_rx,
+            args.reconnect_debounce,
+            args.offline_detection,
+            move |offline| {
+                if let Some(tx) = command_tx.upgrade() {
+                    let _ = tx.unbounded_send(TunnelCommand::IsOffline(offline));
+                } else {
+                    return false;
+                }
+                let _ = offline_state_tx.unbounded_send(offline);
+                true
+            },
+        ));
 
         #[cfg(windows)]
         split_tunnel
@@ -335,10 +477,31 @@ impl TunnelStateMachine {
             route_manager,
             _offline_monitor: offline_monitor,
             allow_lan: args.settings.allow_lan,
+            allowed_lan_nets,
+            firewall_exceptions: args.settings.firewall_exceptions,
+            inbound_tunnel_ports: args.settings.inbound_tunnel_ports,
+            captive_portal: None,
+            allow_multicast: args.settings.allow_multicast,
             block_when_disconnected: args.settings.block_when_disconnected,
+            #[cfg(not(target_os = "android"))]
+            strict_enforcement: args.settings.strict_enforcement,
             is_offline,
             dns_servers: args.settings.dns_servers,
+            #[cfg(not(target_os = "android"))]
+            split_dns_rules: args.settings.split_dns_rules,
+            #[cfg(not(target_os = "android"))]
+            dns_filter: args.settings.dns_filter,
+            #[cfg(not(target_os = "android"))]
+            dns_query_log: args.settings.dns_query_log,
+            #[cfg(not(target_os = "android"))]
+            search_domains: args.settings.search_domains,
+            #[cfg(not(target_os = "android"))]
+            strict_dns_leak_prevention: args.settings.strict_dns_leak_prevention,
+            #[cfg(not(target_os = "android"))]
+            dns_forwarder: None,
             allowed_endpoint: args.settings.allowed_endpoint,
+            #[cfg(windows)]
+            link_layer_exemptions: args.settings.link_layer_exemptions,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
             log_dir: args.log_dir,
@@ -352,6 +515,7 @@ impl TunnelStateMachine {
         };
 
         tokio::task::spawn_blocking(move || {
+            shared_values.reset_leftover_state(args.settings.leftover_state);
             let (initial_state, _) =
                 DisconnectedState::enter(&mut shared_values, args.settings.reset_firewall);
 
@@ -420,14 +584,49 @@ struct SharedTunnelStateValues {
     _offline_monitor: offline::MonitorHandle,
     /// Should LAN access be allowed outside the tunnel.
     allow_lan: bool,
+    /// Networks that are reachable outside the tunnel when LAN access is allowed.
+    allowed_lan_nets: Vec<IpNetwork>,
+    /// Ports that are reachable on the local network in every state.
+    firewall_exceptions: Vec<FirewallException>,
+    /// Ports that are reachable from inside the tunnel while connected.
+    inbound_tunnel_ports: Vec<InboundTunnelPort>,
+    /// Traffic that is permitted outside the tunnel while signing in to a captive portal.
+    captive_portal: Option<CaptivePortal>,
+    /// Should mDNS and SSDP discovery be allowed outside the tunnel.
+    allow_multicast: bool,
     /// Should network access be allowed when in the disconnected state.
     block_when_disconnected: bool,
+    /// Should partial failures to set DNS block the tunnel.
+    #[cfg(not(target_os = "android"))]
+    strict_enforcement: bool,
     /// True when the computer is known to be offline.
     is_offline: bool,
     /// DNS servers to use (overriding default).
     dns_servers: Option<Vec<IpAddr>>,
+    /// Domains whose DNS queries are sent to other resolvers.
+    #[cfg(not(target_os = "android"))]
+    split_dns_rules: Vec<SplitDnsRule>,
+    /// Filter that DNS queries pass through while connected.
+    #[cfg(not(target_os = "android"))]
+    dns_filter: Option<Arc<dyn DnsFilter>>,
+    /// Log that DNS queries are recorded in while connected.
+    #[cfg(not(target_os = "android"))]
+    dns_query_log: Option<Arc<DnsQueryLog>>,
+    /// Local resolver that applies `split_dns_rules` and `dns_filter`, and records queries in
+    /// `dns_query_log`, while connected.
+    #[cfg(not(target_os = "android"))]
+    dns_forwarder: Option<crate::dns::forwarder::DnsForwarder>,
+    /// Domains that are appended to short names while connected.
+    #[cfg(not(target_os = "android"))]
+    search_domains: Vec<String>,
+    /// Block encrypted DNS to other resolvers than the ones in use while connected.
+    #[cfg(not(target_os = "android"))]
+    strict_dns_leak_prevention: bool,
     /// Endpoint that should not be blocked by the firewall.
     allowed_endpoint: AllowedEndpoint,
+    /// Link layer protocols that should not be blocked by the firewall.
+    #[cfg(windows)]
+    link_layer_exemptions: LinkLayerExemptions,
     /// The generator of new `TunnelParameter`s
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
     /// The provider of tunnel devices.
@@ -473,6 +672,40 @@ impl SharedTunnelStateValues {
         Ok(())
     }
 
+    pub fn set_allowed_lan_nets(
+        &mut self,
+        allowed_lan_nets: Option<Vec<IpNetwork>>,
+    ) -> Result<bool, ErrorStateCause> {
+        let allowed_lan_nets =
+            allowed_lan_nets.unwrap_or_else(crate::firewall::default_allowed_lan_nets);
+
+        if self.allowed_lan_nets != allowed_lan_nets {
+            self.allowed_lan_nets = allowed_lan_nets;
+
+            #[cfg(target_os = "android")]
+            {
+                if let Err(error) = self
+                    .tun_provider
+                    .lock()
+                    .unwrap()
+                    .set_allowed_lan_nets(self.allowed_lan_nets.clone())
+                {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg(
+                            "Failed to restart tunnel after changing allowed LAN networks",
+                        )
+                    );
+                    return Err(ErrorStateCause::StartTunnelError);
+                }
+            }
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     pub fn set_dns_servers(
         &mut self,
         dns_servers: Option<Vec<IpAddr>>,
@@ -504,6 +737,77 @@ impl SharedTunnelStateValues {
         }
     }
 
+    /// Returns whether the rules changed.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_split_dns_rules(&mut self, rules: Vec<SplitDnsRule>) -> bool {
+        if self.split_dns_rules != rules {
+            self.split_dns_rules = rules;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether there was or is a filter. Filters cannot be compared, so replacing one
+    /// always counts as a change.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_dns_filter(&mut self, filter: Option<Arc<dyn DnsFilter>>) -> bool {
+        let changed = self.dns_filter.is_some() || filter.is_some();
+        self.dns_filter = filter;
+        changed
+    }
+
+    /// Returns whether the log was enabled or disabled.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_dns_query_log(&mut self, query_log: Option<Arc<DnsQueryLog>>) -> bool {
+        let changed = self.dns_query_log.is_some() != query_log.is_some();
+        self.dns_query_log = query_log;
+        changed
+    }
+
+    /// Returns whether the domains changed. They take effect the next time DNS is set.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_search_domains(&mut self, domains: Vec<String>) -> bool {
+        if self.search_domains != domains {
+            self.dns_monitor.set_search_domains(domains.clone());
+            self.search_domains = domains;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether the setting changed.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_strict_dns_leak_prevention(&mut self, enabled: bool) -> bool {
+        if self.strict_dns_leak_prevention != enabled {
+            self.strict_dns_leak_prevention = enabled;
+            true
+        } else {
+            false
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub fn set_strict_enforcement(&mut self, strict_enforcement: bool) {
+        self.strict_enforcement = strict_enforcement;
+        self.dns_monitor.set_strict(strict_enforcement);
+    }
+
+    /// Returns the reason to block with after failing to set DNS. With strict enforcement, the
+    /// reason describes what failed, since it may be a part of the config that is otherwise
+    /// skipped.
+    #[cfg_attr(target_os = "android", allow(unused_variables))]
+    pub fn dns_error_cause(&self, error: &BoxedError) -> ErrorStateCause {
+        #[cfg(not(target_os = "android"))]
+        if self.strict_enforcement {
+            return ErrorStateCause::StrictEnforcement(
+                error.display_chain_with_msg("Failed to set DNS"),
+            );
+        }
+        ErrorStateCause::SetDnsError
+    }
+
     /// NetworkManager's connectivity check can get hung when DNS requests fail, thus the TSM
     /// should always disable it before applying firewall rules. The connectivity check should be
     /// reset whenever the firewall is cleared.
@@ -518,6 +822,39 @@ impl SharedTunnelStateValues {
         }
     }
 
+    /// Undoes the changes in `leftover` that the initial state does not take care of.
+    fn reset_leftover_state(&mut self, leftover: LeftoverState) {
+        if leftover == LeftoverState::default() {
+            return;
+        }
+        log::info!(
+            "Resetting state left by a previous instance: {:?}",
+            leftover
+        );
+
+        #[cfg(target_os = "linux")]
+        if leftover.routes {
+            if let Err(error) = self
+                .runtime
+                .block_on(self.route_manager.clear_routing_rules())
+            {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to clear leftover routing rules")
+                );
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if leftover.dns {
+            if let Err(error) = self.dns_monitor.reset_leftover() {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to reset leftover DNS config")
+                );
+            }
+        }
+    }
+
     /// Reset NetworkManager's connectivity check if it was disabled.
     #[cfg(target_os = "linux")]
     pub fn reset_connectivity_check(&mut self) {
@@ -629,19 +966,30 @@ state_wrapper! {
 pub struct TunnelStateMachineHandle {
     command_tx: Arc<mpsc::UnboundedSender<TunnelCommand>>,
     shutdown_rx: oneshot::Receiver<()>,
+    reconnect_debounce: Arc<Mutex<ReconnectDebounce>>,
+    offline_detection: Arc<Mutex<offline::Detection>>,
+    /// Used to make the offline detection settings apply to the last result of the offline monitor.
+    offline_tx: mpsc::UnboundedSender<bool>,
     #[cfg(windows)]
     split_tunnel: split_tunnel::SplitTunnelHandle,
 }
 
 impl TunnelStateMachineHandle {
     /// Waits for the tunnel state machine to shut down.
-    /// This may fail after a timeout of `TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT`.
-    pub async fn try_join(self) {
+    /// This may fail after a timeout of `TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT`. Returns whether
+    /// the state machine shut down in time.
+    pub async fn try_join(self) -> bool {
         drop(self.command_tx);
 
         match tokio::time::timeout(TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT, self.shutdown_rx).await {
-            Ok(_) => log::info!("Tunnel state machine shut down"),
-            Err(_) => log::error!("Tunnel state machine did not shut down gracefully"),
+            Ok(_) => {
+                log::info!("Tunnel state machine shut down");
+                true
+            }
+            Err(_) => {
+                log::error!("Tunnel state machine did not shut down gracefully");
+                false
+            }
         }
     }
 
@@ -650,6 +998,33 @@ impl TunnelStateMachineHandle {
         &self.command_tx
     }
 
+    /// Sets how long to wait for the connectivity of the host to settle before reacting to it.
+    /// This takes effect from the next change in connectivity.
+    pub fn set_reconnect_debounce(&self, reconnect_debounce: ReconnectDebounce) {
+        *self.reconnect_debounce.lock().unwrap() = reconnect_debounce;
+    }
+
+    /// Sets when to consider the host to be offline. The new settings are applied to the current
+    /// connectivity right away, subject to the reconnect debounce settings.
+    pub fn set_offline_detection(&self, offline_detection: OfflineDetection) {
+        let monitor_offline = {
+            let mut detection = self.offline_detection.lock().unwrap();
+            detection.settings = offline_detection;
+            detection.monitor_offline
+        };
+        let _ = self.offline_tx.unbounded_send(monitor_offline);
+    }
+
+    /// Returns whether the host is considered to be offline, and why.
+    pub fn offline_status(&self) -> OfflineStatus {
+        let detection = *self.offline_detection.lock().unwrap();
+        OfflineStatus {
+            is_offline: detection.is_offline,
+            monitor_offline: detection.monitor_offline,
+            detection_method: offline::detection_method().to_owned(),
+        }
+    }
+
     /// Returns split tunnel object handle.
     #[cfg(windows)]
     pub fn split_tunnel(&self) -> &split_tunnel::SplitTunnelHandle {
@@ -22,9 +22,9 @@ use winapi::{
         inaddr::IN_ADDR,
         netioapi::{
             CancelMibChangeNotify2, ConvertInterfaceAliasToLuid, ConvertInterfaceLuidToAlias,
-            ConvertInterfaceLuidToGuid, FreeMibTable, GetIpInterfaceEntry,
+            ConvertInterfaceLuidToGuid, FreeMibTable, GetIfEntry2, GetIpInterfaceEntry,
             GetUnicastIpAddressEntry, GetUnicastIpAddressTable, MibAddInstance,
-            NotifyIpInterfaceChange, SetIpInterfaceEntry, MIB_IPINTERFACE_ROW,
+            NotifyIpInterfaceChange, SetIpInterfaceEntry, MIB_IF_ROW2, MIB_IPINTERFACE_ROW,
             MIB_UNICASTIPADDRESS_ROW, MIB_UNICASTIPADDRESS_TABLE,
         },
         nldef::{IpDadStatePreferred, IpDadStateTentative, NL_DAD_STATE},
@@ -193,6 +193,19 @@ pub fn get_ip_interface_entry(
     }
 }
 
+/// Returns information about a network interface, including its traffic counters.
+pub fn get_if_entry(luid: &NET_LUID) -> io::Result<MIB_IF_ROW2> {
+    let mut row: MIB_IF_ROW2 = unsafe { mem::zeroed() };
+    row.InterfaceLuid = *luid;
+
+    let result = unsafe { GetIfEntry2(&mut row) };
+    if result == NO_ERROR {
+        Ok(row)
+    } else {
+        Err(io::Error::from_raw_os_error(result as i32))
+    }
+}
+
 /// Set the properties of an IP interface.
 pub fn set_ip_interface_entry(row: &MIB_IPINTERFACE_ROW) -> io::Result<()> {
     let result = unsafe { SetIpInterfaceEntry(row as *const _ as *mut _) };
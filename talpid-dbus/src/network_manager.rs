@@ -442,7 +442,12 @@ impl NetworkManager {
         Proxy::new(NM_BUS, device, RPC_TIMEOUT, &*self.connection)
     }
 
-    pub fn set_dns(&mut self, interface_name: &str, servers: &[IpAddr]) -> Result<DeviceConfig> {
+    pub fn set_dns(
+        &mut self,
+        interface_name: &str,
+        servers: &[IpAddr],
+        search_domains: &[String],
+    ) -> Result<DeviceConfig> {
         let device_path = self.fetch_device(interface_name)?;
         self.wait_until_device_is_ready(&device_path)?;
 
@@ -539,7 +544,7 @@ impl NetworkManager {
             })
             .collect();
         if !v4_dns.is_empty() {
-            Self::update_dns_config(&mut settings, "ipv4", v4_dns);
+            Self::update_dns_config(&mut settings, "ipv4", v4_dns, search_domains);
         }
 
         let v6_dns: Vec<Vec<u8>> = servers
@@ -550,7 +555,7 @@ impl NetworkManager {
             })
             .collect();
         if !v6_dns.is_empty() {
-            Self::update_dns_config(&mut settings, "ipv6", v6_dns);
+            Self::update_dns_config(&mut settings, "ipv6", v6_dns, search_domains);
         }
 
         if let Some(wg_config) = settings.get_mut("wireguard") {
@@ -578,6 +583,7 @@ impl NetworkManager {
         settings: &mut NetworkSettings<'a>,
         ip_protocol: &'static str,
         servers: T,
+        search_domains: &[String],
     ) where
         T: RefArg + 'a,
     {
@@ -598,10 +604,10 @@ impl NetworkManager {
             Variant(Box::new(DNS_FIRST_PRIORITY)),
         );
         settings.insert("dns".to_string(), Variant(Box::new(servers)));
-        settings.insert(
-            "dns-search".to_string(),
-            Variant(Box::new(vec!["~.".to_string()])),
-        );
+        // "~." routes all queries to this connection
+        let mut dns_search = vec!["~.".to_string()];
+        dns_search.extend_from_slice(search_domains);
+        settings.insert("dns-search".to_string(), Variant(Box::new(dns_search)));
     }
 
     pub fn fetch_device(&self, interface_name: &str) -> Result<dbus::Path<'static>> {
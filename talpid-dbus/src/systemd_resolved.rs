@@ -83,6 +83,8 @@ const DNS_SERVERS: &str = "DNS";
 const GET_LINK_METHOD: &str = "GetLink";
 const SET_DNS_METHOD: &str = "SetDNS";
 const SET_DNS_OVER_TLS_METHOD: &str = "SetDNSOverTLS";
+const SET_DNSSEC_METHOD: &str = "SetDNSSEC";
+const SET_DEFAULT_ROUTE_METHOD: &str = "SetDefaultRoute";
 const SET_DOMAINS_METHOD: &str = "SetDomains";
 const REVERT_METHOD: &str = "Revert";
 
@@ -114,6 +116,17 @@ impl SystemdResolved {
         Ok(systemd_resolved)
     }
 
+    /// Connects to systemd-resolved without checking that `/etc/resolv.conf` points to it. DNS set
+    /// through the returned instance is only used by programs that query systemd-resolved.
+    pub fn new_ignoring_resolv_conf() -> Result<Self> {
+        let dbus_connection = crate::get_connection().map_err(Error::ConnectDBus)?;
+
+        let systemd_resolved = SystemdResolved { dbus_connection };
+
+        systemd_resolved.ensure_resolved_exists()?;
+        Ok(systemd_resolved)
+    }
+
     pub fn new_connection() -> Result<Self> {
         let dbus_connection = SyncConnection::new_system().map_err(Error::ConnectDBus)?;
         let systemd_resolved = SystemdResolved {
@@ -369,6 +382,40 @@ impl SystemdResolved {
         }).map_err(Error::DBusRpcError)
     }
 
+    /// Sets the DNSSEC mode of the link. An empty mode reverts to the global setting.
+    fn link_set_dnssec(&self, interface_index: u32, mode: &str) -> Result<()> {
+        let link_object_path = self
+            .fetch_link(interface_index)
+            .map_err(|e| Error::GetLinkError(Box::new(e)))?;
+
+        self.as_link_object(link_object_path)
+            .method_call(LINK_INTERFACE, SET_DNSSEC_METHOD, (mode,))
+            .map_err(Error::DBusRpcError)
+    }
+
+    /// Sets whether the link is used for domains that no link has a matching search or routing
+    /// domain for.
+    fn link_set_default_route(&self, interface_index: u32, default_route: bool) -> Result<()> {
+        let link_object_path = self
+            .fetch_link(interface_index)
+            .map_err(|e| Error::GetLinkError(Box::new(e)))?;
+
+        self.as_link_object(link_object_path)
+            .method_call(LINK_INTERFACE, SET_DEFAULT_ROUTE_METHOD, (default_route,))
+            .or_else(|error| {
+                if error.name() == Some("org.freedesktop.DBus.Error.UnknownMethod") {
+                    log::debug!(
+                        "Didn't set DefaultRoute because systemd-resolved doesn't have 'SetDefaultRoute' method. {}",
+                        error
+                    );
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            })
+            .map_err(Error::DBusRpcError)
+    }
+
     fn get_link_dns_domains<'a, 'b: 'a>(
         &'a self,
         link_object_path: &'b dbus::Path<'static>,
@@ -560,18 +607,39 @@ impl AsyncHandle {
             .map_err(Error::AsyncTaskError)?
     }
 
-    pub async fn set_domains(
-        &self,
-        interface_index: u32,
-        domains: &[(&'static str, bool)],
-    ) -> Result<()> {
+    pub async fn set_dnssec(&self, interface_index: u32, mode: &'static str) -> Result<()> {
         let interface = self.dbus_interface.clone();
-        let domains = domains.to_vec();
-        tokio::task::spawn_blocking(move || interface.set_domains(interface_index, &domains))
+        tokio::task::spawn_blocking(move || interface.link_set_dnssec(interface_index, mode))
             .await
             .map_err(Error::AsyncTaskError)?
     }
 
+    pub async fn set_default_route(&self, interface_index: u32, default_route: bool) -> Result<()> {
+        let interface = self.dbus_interface.clone();
+        tokio::task::spawn_blocking(move || {
+            interface.link_set_default_route(interface_index, default_route)
+        })
+        .await
+        .map_err(Error::AsyncTaskError)?
+    }
+
+    pub async fn set_domains(&self, interface_index: u32, domains: &[(&str, bool)]) -> Result<()> {
+        let interface = self.dbus_interface.clone();
+        let domains: Vec<(String, bool)> = domains
+            .iter()
+            .map(|(domain, routing_only)| (domain.to_string(), *routing_only))
+            .collect();
+        tokio::task::spawn_blocking(move || {
+            let domains: Vec<(&str, bool)> = domains
+                .iter()
+                .map(|(domain, routing_only)| (domain.as_str(), *routing_only))
+                .collect();
+            interface.set_domains(interface_index, &domains)
+        })
+        .await
+        .map_err(Error::AsyncTaskError)?
+    }
+
     pub async fn revert_link(&self, state: DnsState) -> Result<()> {
         let mut interface = self.dbus_interface.clone();
         tokio::task::spawn_blocking(move || interface.revert_link(&state))
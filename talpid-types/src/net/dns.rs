@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::SystemTime,
+};
+
+/// Name that DNS64 resolvers return synthesized IPv6 addresses for, which reveal the NAT64 prefix
+/// of the network (RFC 7050).
+pub const NAT64_DISCOVERY_NAME: &str = "ipv4only.arpa";
+
+/// The IPv4 addresses of [`NAT64_DISCOVERY_NAME`].
+const NAT64_DISCOVERY_ADDRESSES: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// Sends DNS queries for a domain, and all of its subdomains, to a specific set of resolvers
+/// instead of the ones used for everything else. Resolvers on the local network are reached
+/// outside the tunnel, and all other resolvers are reached through it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SplitDnsRule {
+    /// Domain that the rule applies to, such as `corp.example`. A leading `*.` only matches
+    /// subdomains, and not the domain itself.
+    pub domain: String,
+    /// Resolvers to send the queries to, in order of preference.
+    pub servers: Vec<IpAddr>,
+}
+
+impl SplitDnsRule {
+    /// Returns whether queries for `name` should be sent to the resolvers of this rule. Names
+    /// are compared without regard to case or a trailing dot.
+    pub fn matches(&self, name: &str) -> bool {
+        domain_matches(&self.domain, name)
+    }
+
+    /// Returns the number of labels in the domain. Where several rules match a name, the most
+    /// specific one is used.
+    pub fn specificity(&self) -> usize {
+        domain_specificity(&self.domain)
+    }
+
+    /// Returns whether the rule names a valid domain and at least one resolver.
+    pub fn is_valid(&self) -> bool {
+        !self.servers.is_empty() && is_valid_domain_pattern(&self.domain)
+    }
+}
+
+/// Answers DNS queries for a domain, and all of its subdomains, locally instead of forwarding
+/// them to a resolver.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DnsFilterRule {
+    /// Domain that the rule applies to. This is matched like the domain of a [`SplitDnsRule`].
+    pub domain: String,
+    pub action: DnsFilterAction,
+}
+
+/// How queries that match a [`DnsFilterRule`] are answered.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsFilterAction {
+    /// Respond that the name does not exist.
+    Block,
+    /// Respond with these addresses. Queries for a type of address that is not in the list get
+    /// an empty response.
+    Rewrite(Vec<IpAddr>),
+}
+
+impl DnsFilterRule {
+    /// Returns whether queries for `name` are answered by this rule.
+    pub fn matches(&self, name: &str) -> bool {
+        domain_matches(&self.domain, name)
+    }
+
+    /// Returns the specificity of the domain, as for [`SplitDnsRule::specificity`].
+    pub fn specificity(&self) -> usize {
+        domain_specificity(&self.domain)
+    }
+
+    /// Returns whether the rule names a valid domain, and at least one address if it rewrites.
+    pub fn is_valid(&self) -> bool {
+        let has_addresses = match &self.action {
+            DnsFilterAction::Block => true,
+            DnsFilterAction::Rewrite(addresses) => !addresses.is_empty(),
+        };
+        has_addresses && is_valid_domain_pattern(&self.domain)
+    }
+}
+
+impl fmt::Display for DnsFilterRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.action {
+            DnsFilterAction::Block => write!(f, "{} blocked", self.domain),
+            DnsFilterAction::Rewrite(addresses) => {
+                write!(f, "{} ->", self.domain)?;
+                for address in addresses {
+                    write!(f, " {}", address)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A query that was handled by the local forwarder while the query log was enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQueryLogEntry {
+    /// Increases by one for each query, so that clients can ask for the queries after the last
+    /// one they have seen.
+    pub sequence: u64,
+    pub time: SystemTime,
+    pub name: String,
+    /// Record type, such as 1 for `A` and 28 for `AAAA`.
+    pub query_type: u16,
+    pub outcome: DnsQueryOutcome,
+}
+
+/// How a logged query was answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsQueryOutcome {
+    /// Blocked by the DNS filter.
+    Blocked,
+    /// Answered with other addresses by the DNS filter.
+    Rewritten,
+    /// Forwarded to and answered by this resolver.
+    Resolved(IpAddr),
+    /// Forwarded, but none of the resolvers responded.
+    NoResponse,
+}
+
+impl fmt::Display for DnsQueryOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsQueryOutcome::Blocked => write!(f, "blocked"),
+            DnsQueryOutcome::Rewritten => write!(f, "rewritten"),
+            DnsQueryOutcome::Resolved(server) => write!(f, "resolved by {}", server),
+            DnsQueryOutcome::NoResponse => write!(f, "no response"),
+        }
+    }
+}
+
+/// Returns the filter rule that applies to `name`, if any.
+pub fn find_dns_filter_rule<'a>(
+    rules: &'a [DnsFilterRule],
+    name: &str,
+) -> Option<&'a DnsFilterRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(name))
+        .max_by_key(|rule| rule.specificity())
+}
+
+/// Returns whether `domain` is a valid domain name, such as `corp.example`. A trailing dot is
+/// allowed.
+pub fn is_valid_domain(domain: &str) -> bool {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+impl fmt::Display for SplitDnsRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ->", self.domain)?;
+        for server in &self.servers {
+            write!(f, " {}", server)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the rule that applies to `name`, if any.
+pub fn find_split_dns_rule<'a>(rules: &'a [SplitDnsRule], name: &str) -> Option<&'a SplitDnsRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(name))
+        .max_by_key(|rule| rule.specificity())
+}
+
+/// Returns the NAT64 prefix that `address`, an IPv6 address of [`NAT64_DISCOVERY_NAME`], was
+/// synthesized with. Only /96 prefixes are recognized, which is what nearly all networks use.
+pub fn nat64_prefix(address: Ipv6Addr) -> Option<Ipv6Addr> {
+    let octets = address.octets();
+    let embedded = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    if !NAT64_DISCOVERY_ADDRESSES.contains(&embedded) {
+        return None;
+    }
+    let mut prefix = octets;
+    prefix[12..].fill(0);
+    Some(Ipv6Addr::from(prefix))
+}
+
+fn domain_matches(pattern: &str, name: &str) -> bool {
+    let name = normalize(name);
+    match pattern.strip_prefix("*.") {
+        Some(parent) => is_subdomain(&name, &normalize(parent)),
+        None => {
+            let domain = normalize(pattern);
+            name == domain || is_subdomain(&name, &domain)
+        }
+    }
+}
+
+fn domain_specificity(pattern: &str) -> usize {
+    let labels = normalize(pattern).split('.').count();
+    if pattern.starts_with("*.") {
+        // Count the wildcard as half a label, so that `a.b` beats `*.b` for `a.b`
+        labels * 2 - 1
+    } else {
+        labels * 2
+    }
+}
+
+fn is_valid_domain_pattern(pattern: &str) -> bool {
+    is_valid_domain(pattern.strip_prefix("*.").unwrap_or(pattern))
+}
+
+fn normalize(name: &str) -> String {
+    name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase()
+}
+
+fn is_subdomain(name: &str, parent: &str) -> bool {
+    name.len() > parent.len() + 1
+        && name.ends_with(parent)
+        && name.as_bytes()[name.len() - parent.len() - 1] == b'.'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(domain: &str) -> SplitDnsRule {
+        SplitDnsRule {
+            domain: domain.to_owned(),
+            servers: vec!["192.168.1.1".parse().unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let domain = rule("corp.example");
+        assert!(domain.matches("corp.example"));
+        assert!(domain.matches("Mail.Corp.Example."));
+        assert!(!domain.matches("notcorp.example"));
+        assert!(!domain.matches("example"));
+
+        let wildcard = rule("*.corp.example");
+        assert!(wildcard.matches("mail.corp.example"));
+        assert!(!wildcard.matches("corp.example"));
+    }
+
+    #[test]
+    fn test_most_specific_rule() {
+        let rules = [
+            rule("*.example"),
+            rule("corp.example"),
+            rule("*.corp.example"),
+        ];
+        assert_eq!(
+            find_split_dns_rule(&rules, "mail.corp.example")
+                .unwrap()
+                .domain,
+            "*.corp.example"
+        );
+        assert_eq!(
+            find_split_dns_rule(&rules, "corp.example").unwrap().domain,
+            "corp.example"
+        );
+        assert_eq!(
+            find_split_dns_rule(&rules, "www.example").unwrap().domain,
+            "*.example"
+        );
+        assert!(find_split_dns_rule(&rules, "example.org").is_none());
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(rule("corp.example").is_valid());
+        assert!(rule("*.corp.example.").is_valid());
+        assert!(!rule("").is_valid());
+        assert!(!rule("*.").is_valid());
+        assert!(!rule("corp..example").is_valid());
+        assert!(!rule("-corp.example").is_valid());
+        assert!(!rule("*.*.example").is_valid());
+        assert!(!SplitDnsRule {
+            domain: "corp.example".to_owned(),
+            servers: vec![],
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_filter_rule_is_valid() {
+        let filter_rule = |domain: &str, action| DnsFilterRule {
+            domain: domain.to_owned(),
+            action,
+        };
+        assert!(filter_rule("*.ads.example", DnsFilterAction::Block).is_valid());
+        assert!(filter_rule(
+            "router.home",
+            DnsFilterAction::Rewrite(vec!["192.168.1.1".parse().unwrap()])
+        )
+        .is_valid());
+        assert!(!filter_rule("router.home", DnsFilterAction::Rewrite(vec![])).is_valid());
+        assert!(!filter_rule("*.", DnsFilterAction::Block).is_valid());
+    }
+
+    #[test]
+    fn test_is_valid_domain() {
+        assert!(is_valid_domain("corp"));
+        assert!(is_valid_domain("corp.example."));
+        assert!(!is_valid_domain("*.corp.example"));
+        assert!(!is_valid_domain("corp example"));
+        assert!(!is_valid_domain(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn test_nat64_prefix() {
+        assert_eq!(
+            nat64_prefix("64:ff9b::c000:aa".parse().unwrap()),
+            Some("64:ff9b::".parse().unwrap())
+        );
+        assert_eq!(
+            nat64_prefix("2001:db8:64::192.0.0.171".parse().unwrap()),
+            Some("2001:db8:64::".parse().unwrap())
+        );
+        assert_eq!(nat64_prefix("2001:db8::1".parse().unwrap()), None);
+    }
+}
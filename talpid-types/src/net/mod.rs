@@ -8,8 +8,10 @@ use std::{
     fmt,
     net::{IpAddr, SocketAddr},
     str::FromStr,
+    time::Duration,
 };
 
+pub mod dns;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
@@ -34,6 +36,8 @@ impl TunnelParameters {
                 proxy: params.proxy.as_ref().map(|proxy| proxy.get_endpoint()),
                 obfuscation: None,
                 entry_endpoint: None,
+                mtu: None,
+                wireguard_implementation: None,
             },
             TunnelParameters::Wireguard(params) => TunnelEndpoint {
                 tunnel_type: TunnelType::Wireguard,
@@ -48,6 +52,8 @@ impl TunnelParameters {
                     .connection
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
+                mtu: None,
+                wireguard_implementation: None,
             },
         }
     }
@@ -70,7 +76,9 @@ impl TunnelParameters {
 
     fn get_obfuscator_endpoint(obfuscator: &ObfuscatorConfig) -> Endpoint {
         match obfuscator {
-            ObfuscatorConfig::Udp2Tcp { endpoint } => Endpoint {
+            ObfuscatorConfig::Udp2Tcp { endpoint }
+            | ObfuscatorConfig::Wss { endpoint, .. }
+            | ObfuscatorConfig::External { endpoint, .. } => Endpoint {
                 address: *endpoint,
                 protocol: TransportProtocol::Tcp,
             },
@@ -142,6 +150,15 @@ pub struct TunnelEndpoint {
     pub obfuscation: Option<ObfuscationEndpoint>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub entry_endpoint: Option<Endpoint>,
+    /// MTU of the tunnel interface. This is only known once the tunnel is up.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    #[serde(default)]
+    pub mtu: Option<u16>,
+    /// WireGuard implementation that the tunnel runs on. This is only known once the tunnel is
+    /// up, and is `None` for OpenVPN.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    #[serde(default)]
+    pub wireguard_implementation: Option<wireguard::WireguardImplementation>,
 }
 
 impl fmt::Display for TunnelEndpoint {
@@ -175,12 +192,18 @@ impl fmt::Display for TunnelEndpoint {
 pub enum ObfuscationType {
     #[serde(rename = "udp2tcp")]
     Udp2Tcp,
+    #[serde(rename = "wss")]
+    Wss,
+    #[serde(rename = "external")]
+    External,
 }
 
 impl fmt::Display for ObfuscationType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         let obfuscation = match self {
             ObfuscationType::Udp2Tcp => "Udp2Tcp",
+            ObfuscationType::Wss => "WebSocket over TLS",
+            ObfuscationType::External => "external transport",
         };
         write!(f, "{}", obfuscation)
     }
@@ -203,6 +226,20 @@ impl From<&ObfuscatorConfig> for ObfuscationEndpoint {
                 },
                 ObfuscationType::Udp2Tcp,
             ),
+            ObfuscatorConfig::Wss { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Tcp,
+                },
+                ObfuscationType::Wss,
+            ),
+            ObfuscatorConfig::External { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Tcp,
+                },
+                ObfuscationType::External,
+            ),
         };
 
         ObfuscationEndpoint {
@@ -372,6 +409,307 @@ pub struct GenericTunnelOptions {
     /// Enable configuration of IPv6 on the tunnel interface, allowing IPv6 communication to be
     /// forwarded through the tunnel.
     pub enable_ipv6: bool,
+    /// How quickly to retry after failing to connect.
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// Maximum throughput of the tunnel in each direction, in bits per second.
+    #[cfg(target_os = "linux")]
+    #[serde(default)]
+    pub rate_limit: Option<u64>,
+}
+
+/// How long to wait before each new attempt at connecting after an attempt has failed, and when
+/// to give up. By default, connecting is retried immediately and indefinitely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Factor that the delay is multiplied by for each subsequent retry.
+    pub multiplier: u32,
+    /// Upper bound for the delay. The delay grows without bound if this is `None`.
+    pub max_delay: Option<Duration>,
+    /// Number of attempts after which the tunnel enters the error state instead of retrying.
+    /// Connecting is retried indefinitely if this is `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_delay: Duration::ZERO,
+            multiplier: 1,
+            max_delay: None,
+            max_attempts: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns how long to wait before attempt number `retry_attempt`, where zero is the first
+    /// attempt.
+    pub fn delay(&self, retry_attempt: u32) -> Duration {
+        if retry_attempt == 0 {
+            return Duration::ZERO;
+        }
+        let factor = self.multiplier.saturating_pow(retry_attempt - 1);
+        let delay = self
+            .initial_delay
+            .checked_mul(factor)
+            .unwrap_or(Duration::MAX);
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+
+    /// Returns whether attempt number `retry_attempt`, where zero is the first attempt, exceeds
+    /// the maximum number of attempts.
+    pub fn exceeds_max_attempts(&self, retry_attempt: u32) -> bool {
+        self.max_attempts
+            .map(|max_attempts| retry_attempt >= max_attempts)
+            .unwrap_or(false)
+    }
+}
+
+/// Network protocols below the tunnel that the firewall lets through on physical adapters even
+/// when all other traffic is blocked. Some network access control setups require these to work
+/// before a machine is admitted to the network.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(default)]
+pub struct LinkLayerExemptions {
+    /// Permit DHCPv4 and DHCPv6 requests to go out and responses to come in.
+    pub permit_dhcp: bool,
+    /// Permit the subset of NDP needed for router and neighbor discovery.
+    pub permit_ndp: bool,
+}
+
+#[cfg(windows)]
+impl Default for LinkLayerExemptions {
+    fn default() -> Self {
+        LinkLayerExemptions {
+            permit_dhcp: true,
+            permit_ndp: true,
+        }
+    }
+}
+
+/// Limits how quickly the tunnel reacts when the host goes offline and comes back online, so that
+/// a flapping network connection does not cause a reconnect storm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectDebounce {
+    /// How long the connectivity must stay unchanged before the tunnel reacts to it.
+    pub min_stable_interval: Duration,
+    /// Maximum number of times per minute that coming back online may trigger a reconnect.
+    pub max_reconnects_per_minute: Option<u32>,
+}
+
+/// Controls when the host is considered to be offline. No tunnel is connected while it is
+/// offline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OfflineDetection {
+    /// Whether to react to the host appearing to be offline. If disabled, the host is always
+    /// assumed to be online.
+    pub enabled: bool,
+    /// How long the host must appear to be offline before it is considered to be.
+    pub grace_period: Duration,
+}
+
+impl Default for OfflineDetection {
+    fn default() -> Self {
+        OfflineDetection {
+            enabled: true,
+            grace_period: Duration::ZERO,
+        }
+    }
+}
+
+/// Whether the host is considered to be offline, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineStatus {
+    /// Whether the host is considered to be offline.
+    pub is_offline: bool,
+    /// Whether the offline monitor finds the host to be offline, regardless of the offline
+    /// detection settings.
+    pub monitor_offline: bool,
+    /// The condition that the offline monitor uses to decide that the host is offline.
+    pub detection_method: String,
+}
+
+/// Selects how DNS is configured on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsBackend {
+    /// Detect how the host manages DNS and configure it the same way.
+    Auto,
+    /// Configure DNS for the tunnel interface through the D-Bus API of systemd-resolved, even if
+    /// `/etc/resolv.conf` is not managed by it. This leaves other resolv.conf managers alone.
+    SystemdResolved {
+        /// DNSSEC mode to use for the tunnel interface. If `None`, the global setting of
+        /// systemd-resolved is used.
+        dnssec: Option<DnssecMode>,
+    },
+}
+
+impl Default for DnsBackend {
+    fn default() -> Self {
+        DnsBackend::Auto
+    }
+}
+
+impl fmt::Display for DnsBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsBackend::Auto => f.write_str("auto"),
+            DnsBackend::SystemdResolved { dnssec: None } => f.write_str("systemd-resolved"),
+            DnsBackend::SystemdResolved {
+                dnssec: Some(dnssec),
+            } => write!(f, "systemd-resolved with DNSSEC {}", dnssec),
+        }
+    }
+}
+
+/// DNSSEC validation modes of systemd-resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnssecMode {
+    /// Do not validate responses.
+    No,
+    /// Validate responses if the resolver supports DNSSEC.
+    AllowDowngrade,
+    /// Reject responses that cannot be validated.
+    Yes,
+}
+
+impl DnssecMode {
+    /// Returns the name that systemd-resolved uses for the mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnssecMode::No => "no",
+            DnssecMode::AllowDowngrade => "allow-downgrade",
+            DnssecMode::Yes => "yes",
+        }
+    }
+}
+
+impl fmt::Display for DnssecMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The firewall mark and routing table that are used to route traffic on Linux. These can be
+/// changed to coexist with other software that uses policy routing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyRouting {
+    /// Firewall mark of traffic that bypasses the tunnel, such as the tunnel traffic itself.
+    pub fwmark: u32,
+    /// ID of the routing table that routes all other traffic through the tunnel.
+    pub table_id: u32,
+}
+
+impl PolicyRouting {
+    // b"mole" is [ 0x6d, 0x6f 0x6c, 0x65 ]
+    pub const DEFAULT_FWMARK: u32 = 0x6d6f6c65;
+    pub const DEFAULT_TABLE_ID: u32 = 0x6d6f6c65;
+
+    /// Returns whether the values can be used. The mark must be set, and the table must not be
+    /// one of the tables that are reserved by the kernel.
+    pub fn is_valid(&self) -> bool {
+        // RT_TABLE_UNSPEC, RT_TABLE_COMPAT, RT_TABLE_DEFAULT, RT_TABLE_MAIN and RT_TABLE_LOCAL
+        const RESERVED_TABLES: [u32; 5] = [0, 252, 253, 254, 255];
+        self.fwmark != 0 && !RESERVED_TABLES.contains(&self.table_id)
+    }
+}
+
+impl Default for PolicyRouting {
+    fn default() -> Self {
+        PolicyRouting {
+            fwmark: Self::DEFAULT_FWMARK,
+            table_id: Self::DEFAULT_TABLE_ID,
+        }
+    }
+}
+
+impl fmt::Display for PolicyRouting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fwmark {:#x}, table {:#x}", self.fwmark, self.table_id)
+    }
+}
+
+/// A port that the firewall keeps open towards the local network in every tunnel state, including
+/// the blocking ones, regardless of the allow LAN setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct FirewallException {
+    /// Whether the port is opened for connections from, or to, the local network.
+    pub direction: FirewallExceptionDirection,
+    pub protocol: TransportProtocol,
+    /// A local port for inbound exceptions, or a remote port for outbound exceptions.
+    pub port: u16,
+}
+
+impl fmt::Display for FirewallException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.direction, self.protocol, self.port)
+    }
+}
+
+/// Direction of the connections that a [`FirewallException`] permits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallExceptionDirection {
+    /// Hosts on the local network may connect to the port on this machine.
+    Inbound,
+    /// This machine may connect to the port on hosts on the local network.
+    Outbound,
+}
+
+impl fmt::Display for FirewallExceptionDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FirewallExceptionDirection::Inbound => "inbound".fmt(f),
+            FirewallExceptionDirection::Outbound => "outbound".fmt(f),
+        }
+    }
+}
+
+/// Traffic that the firewall permits outside the tunnel, in the states that otherwise block it,
+/// so that the user can sign in to a captive portal. DNS is never permitted, so that it stays
+/// pinned to the resolvers used by the tunnel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptivePortal {
+    /// Permit traffic to the portal.
+    Address(IpAddr),
+    /// Permit all traffic except DNS.
+    AllTraffic,
+}
+
+impl fmt::Display for CaptivePortal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptivePortal::Address(address) => write!(f, "traffic to {}", address),
+            CaptivePortal::AllTraffic => "all traffic".fmt(f),
+        }
+    }
+}
+
+/// A local port that accepts unsolicited inbound connections from inside the tunnel, such as a
+/// port forwarded by the relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct InboundTunnelPort {
+    pub protocol: TransportProtocol,
+    pub port: u16,
+}
+
+impl fmt::Display for InboundTunnelPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.protocol, self.port)
+    }
 }
 
 /// Returns a vector of IP networks representing all of the internet, 0.0.0.0/0.
@@ -383,3 +721,126 @@ pub fn all_of_the_internet() -> Vec<ipnetwork::IpNetwork> {
         "::0/0".parse().expect("Failed to parse ipv6 network"),
     ]
 }
+
+/// Returns the networks that are not covered by any of `networks`, as few as possible. Together
+/// with `networks`, these make up all of the internet.
+pub fn excluded_networks(networks: &[ipnetwork::IpNetwork]) -> Vec<ipnetwork::IpNetwork> {
+    all_of_the_internet()
+        .into_iter()
+        .flat_map(|net| subtract_networks(net, networks))
+        .collect()
+}
+
+fn subtract_networks(
+    net: ipnetwork::IpNetwork,
+    networks: &[ipnetwork::IpNetwork],
+) -> Vec<ipnetwork::IpNetwork> {
+    if networks.iter().any(|other| contains_network(*other, net)) {
+        return vec![];
+    }
+    if !networks.iter().any(|other| contains_network(net, *other)) {
+        return vec![net];
+    }
+    // Some of the networks are within `net`, so keep what is left of each half
+    let (lower, upper) = split_network(net);
+    let mut remaining = subtract_networks(lower, networks);
+    remaining.extend(subtract_networks(upper, networks));
+    remaining
+}
+
+fn contains_network(net: ipnetwork::IpNetwork, other: ipnetwork::IpNetwork) -> bool {
+    net.prefix() <= other.prefix() && net.contains(other.network())
+}
+
+/// Splits a network into its two halves. Networks of a single address cannot be split, and are
+/// never passed here, since they either contain another network entirely or not at all.
+fn split_network(net: ipnetwork::IpNetwork) -> (ipnetwork::IpNetwork, ipnetwork::IpNetwork) {
+    use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
+
+    let prefix = net.prefix() + 1;
+    match net {
+        IpNetwork::V4(net) => {
+            let lower = u32::from(net.network());
+            let upper = lower | (1 << (32 - prefix));
+            (
+                Ipv4Network::new(lower.into(), prefix).unwrap().into(),
+                Ipv4Network::new(upper.into(), prefix).unwrap().into(),
+            )
+        }
+        IpNetwork::V6(net) => {
+            let lower = u128::from(net.network());
+            let upper = lower | (1 << (128 - prefix));
+            (
+                Ipv6Network::new(lower.into(), prefix).unwrap().into(),
+                Ipv6Network::new(upper.into(), prefix).unwrap().into(),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(10), Duration::ZERO);
+        assert!(!policy.exceeds_max_attempts(u32::MAX));
+
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_secs(2),
+            multiplier: 3,
+            max_delay: Some(Duration::from_secs(60)),
+            max_attempts: Some(5),
+        };
+        assert_eq!(policy.delay(0), Duration::ZERO);
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(6));
+        assert_eq!(policy.delay(3), Duration::from_secs(18));
+        assert_eq!(policy.delay(4), Duration::from_secs(54));
+        assert_eq!(policy.delay(5), Duration::from_secs(60));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(60));
+        assert!(!policy.exceeds_max_attempts(4));
+        assert!(policy.exceeds_max_attempts(5));
+    }
+
+    #[test]
+    fn test_excluded_networks() {
+        let parse = |nets: &[&str]| -> Vec<ipnetwork::IpNetwork> {
+            nets.iter().map(|net| net.parse().unwrap()).collect()
+        };
+        assert_eq!(
+            excluded_networks(&parse(&["10.0.0.0/8"])),
+            parse(&[
+                "0.0.0.0/5",
+                "8.0.0.0/7",
+                "11.0.0.0/8",
+                "12.0.0.0/6",
+                "16.0.0.0/4",
+                "32.0.0.0/3",
+                "64.0.0.0/2",
+                "128.0.0.0/1",
+                "::/0",
+            ])
+        );
+        assert_eq!(
+            excluded_networks(&parse(&["0.0.0.0/1", "128.0.0.0/1", "::/0"])),
+            vec![]
+        );
+        assert_eq!(
+            excluded_networks(&parse(&["128.0.0.0/1", "192.168.1.1/32", "2000::/3"])),
+            parse(&["0.0.0.0/1", "::/3", "4000::/2", "8000::/1"])
+        );
+    }
+
+    #[test]
+    fn test_policy_routing_validity() {
+        assert!(PolicyRouting::default().is_valid());
+        let routing = |fwmark, table_id| PolicyRouting { fwmark, table_id };
+        assert!(routing(1, 100).is_valid());
+        assert!(!routing(0, 100).is_valid());
+        assert!(!routing(1, 254).is_valid());
+        assert!(!routing(1, 0).is_valid());
+    }
+}
@@ -1,7 +1,44 @@
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::{fmt, net::SocketAddr, path::PathBuf};
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Serialize, Debug)]
 pub enum ObfuscatorConfig {
-    Udp2Tcp { endpoint: SocketAddr },
+    Udp2Tcp {
+        endpoint: SocketAddr,
+    },
+    /// Sends the WireGuard traffic as WebSocket messages over a TLS connection to `endpoint`.
+    /// `server_name` is used for the server name indication and the `Host` header.
+    Wss {
+        endpoint: SocketAddr,
+        server_name: String,
+    },
+    /// Runs a user supplied pluggable transport executable and sends the WireGuard traffic,
+    /// framed as by udp2tcp, through it to `endpoint`.
+    External {
+        endpoint: SocketAddr,
+        transport: ExternalTransportConfig,
+    },
+}
+
+/// Describes a pluggable transport client executable that speaks the managed proxy protocol of
+/// the Tor pluggable transport specification, version 1.
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Debug)]
+pub struct ExternalTransportConfig {
+    /// Absolute path to the executable.
+    pub path: PathBuf,
+    /// Extra command line arguments passed to the executable.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Name of the transport method to request, e.g. `obfs4`.
+    pub method: String,
+    /// Per-connection arguments, e.g. `cert=...;iat-mode=0`. They are handed to the transport
+    /// through the SOCKS authentication fields.
+    #[serde(default)]
+    pub method_args: String,
+}
+
+impl fmt::Display for ExternalTransportConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.method, self.path.display())
+    }
 }
@@ -8,6 +8,7 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
 };
 
 /// Tunnel parameters required to start a `WireguardMonitor`.
@@ -88,6 +89,36 @@ pub struct TunnelOptions {
     #[serde(default = "default_wgnt_setting")]
     #[serde(rename = "wireguard_nt")]
     pub use_wireguard_nt: bool,
+    /// How to detect that an established tunnel has stopped working.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub liveness: LivenessOptions,
+    /// Named network namespace to move the tunnel interface into. Only processes in the
+    /// namespace use the tunnel when this is set.
+    #[cfg(target_os = "linux")]
+    pub namespace: Option<String>,
+    /// Seconds between keepalive packets sent to the relay, or 0 to not send any. These keep NAT
+    /// mappings open while the tunnel is idle.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    #[serde(default = "default_persistent_keepalive")]
+    pub persistent_keepalive: u16,
+    /// Networks to route through the tunnel, instead of all traffic. Traffic to other
+    /// destinations is sent outside the tunnel, and is not blocked by the firewall.
+    #[cfg(not(target_os = "android"))]
+    #[serde(default)]
+    pub allowed_ips: Option<Vec<IpNetwork>>,
+}
+
+/// Default number of seconds between keepalive packets. This is short enough to keep the
+/// mappings of most NATs open.
+pub const DEFAULT_PERSISTENT_KEEPALIVE: u16 = 25;
+
+fn default_persistent_keepalive() -> u16 {
+    DEFAULT_PERSISTENT_KEEPALIVE
+}
+
+/// Returns whether `name` can be the name of a network namespace created by `ip netns add`.
+pub fn is_valid_namespace_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\0')
 }
 
 #[cfg(windows)]
@@ -103,10 +134,76 @@ impl Default for TunnelOptions {
             use_pq_safe_psk: false,
             #[cfg(windows)]
             use_wireguard_nt: default_wgnt_setting(),
+            liveness: LivenessOptions::default(),
+            #[cfg(target_os = "linux")]
+            namespace: None,
+            persistent_keepalive: DEFAULT_PERSISTENT_KEEPALIVE,
+            #[cfg(not(target_os = "android"))]
+            allowed_ips: None,
         }
     }
 }
 
+/// The WireGuard implementation that a tunnel runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireguardImplementation {
+    /// The in-kernel implementation on Linux, configured over netlink.
+    Kernel,
+    /// The in-kernel implementation on Linux, configured through NetworkManager.
+    NetworkManager,
+    /// The WireGuardNT driver on Windows.
+    WireguardNt,
+    /// wireguard-go, running in userspace.
+    Userspace,
+}
+
+impl fmt::Display for WireguardImplementation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireguardImplementation::Kernel => "kernel".fmt(f),
+            WireguardImplementation::NetworkManager => "kernel (NetworkManager)".fmt(f),
+            WireguardImplementation::WireguardNt => "WireGuardNT".fmt(f),
+            WireguardImplementation::Userspace => "userspace (wireguard-go)".fmt(f),
+        }
+    }
+}
+
+/// How the connectivity of a WireGuard tunnel is monitored. When no traffic has been received
+/// for a while, the relay is probed with pings, and the tunnel is reconnected if none of them are
+/// answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LivenessOptions {
+    /// Time between probes.
+    pub probe_interval: Duration,
+    /// How long to wait for traffic after each probe.
+    pub probe_timeout: Duration,
+    /// Number of probes in a row that may go unanswered before the tunnel is reconnected.
+    pub max_failures: u32,
+    /// Whether to send probes at all. If disabled, the tunnel is only reconnected when traffic is
+    /// sent but nothing is received, so that no extra traffic is used on metered links.
+    pub active_probing: bool,
+}
+
+impl Default for LivenessOptions {
+    fn default() -> Self {
+        LivenessOptions {
+            probe_interval: Duration::from_secs(3),
+            probe_timeout: Duration::from_secs(3),
+            max_failures: 5,
+            active_probing: true,
+        }
+    }
+}
+
+impl LivenessOptions {
+    /// Returns how long traffic may go unanswered before the tunnel is considered broken.
+    pub fn failure_timeout(&self) -> Duration {
+        self.probe_timeout.saturating_mul(self.max_failures)
+    }
+}
+
 /// Wireguard x25519 private key
 #[derive(Clone)]
 pub struct PrivateKey(x25519_dalek::StaticSecret);
@@ -129,6 +226,11 @@ impl PrivateKey {
     pub fn to_base64(&self) -> String {
         base64::encode(self.0.to_bytes())
     }
+
+    /// Compute the x25519 shared secret between this key and a public key
+    pub fn diffie_hellman(&self, public_key: &PublicKey) -> [u8; 32] {
+        *self.0.diffie_hellman(&public_key.0).as_bytes()
+    }
 }
 
 impl From<[u8; 32]> for PrivateKey {
@@ -2,9 +2,7 @@ use crate::net::TunnelEndpoint;
 #[cfg(target_os = "android")]
 use jnix::IntoJava;
 use serde::{Deserialize, Serialize};
-use std::fmt;
-#[cfg(target_os = "android")]
-use std::net::IpAddr;
+use std::{fmt, net::IpAddr};
 
 /// Event emitted from the states in `talpid_core::tunnel_state_machine` when the tunnel state
 /// machine enters a new state.
@@ -15,13 +13,22 @@ pub enum TunnelStateTransition {
     /// Network is secured but tunnel is still connecting.
     Connecting(TunnelEndpoint),
     /// Tunnel is connected.
-    Connected(TunnelEndpoint),
+    Connected(TunnelEndpoint, TunnelInterface),
     /// Disconnecting tunnel.
     Disconnecting(ActionAfterDisconnect),
     /// Tunnel is disconnected but usually secured by blocking all connections.
     Error(ErrorState),
 }
 
+/// The network interface that a connected tunnel runs on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TunnelInterface {
+    /// Name of the tunnel interface.
+    pub name: String,
+    /// Local IPs on the tunnel interface.
+    pub ips: Vec<IpAddr>,
+}
+
 /// Action that will be taken after disconnection is complete.
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -104,13 +111,21 @@ pub enum ErrorStateCause {
     /// Error reported by split tunnel module.
     #[cfg(target_os = "windows")]
     SplitTunnelError,
+    /// Part of the DNS, route or firewall config could not be applied, and strict enforcement is
+    /// enabled. Contains a description of what failed.
+    #[cfg(not(target_os = "android"))]
+    StrictEnforcement(String),
+    /// Connecting failed as many times in a row as the retry policy allows. Contains the number of
+    /// attempts that were made.
+    #[cfg(not(target_os = "android"))]
+    RetryLimitReached(u32),
 }
 
 impl ErrorStateCause {
     #[cfg(target_os = "macos")]
     pub fn prevents_filtering_resolver(&self) -> bool {
         match self {
-            Self::SetDnsError => true,
+            Self::SetDnsError | Self::StrictEnforcement(_) => true,
             _ => false,
         }
     }
@@ -206,6 +221,23 @@ impl fmt::Display for ErrorStateCause {
             VpnPermissionDenied => "The Android VPN permission was denied when creating the tunnel",
             #[cfg(target_os = "windows")]
             SplitTunnelError => "The split tunneling module reported an error",
+            #[cfg(not(target_os = "android"))]
+            StrictEnforcement(ref failure) => {
+                return write!(
+                    f,
+                    "Blocking since strict enforcement is enabled and the tunnel could not be \
+                    fully set up: {}",
+                    failure
+                );
+            }
+            #[cfg(not(target_os = "android"))]
+            RetryLimitReached(attempts) => {
+                return write!(
+                    f,
+                    "Gave up after {} failed attempts at connecting",
+                    attempts
+                );
+            }
         };
 
         write!(f, "{}", description)
@@ -0,0 +1,498 @@
+//! Runs pluggable transport client executables that implement the managed proxy protocol from
+//! the Tor pluggable transport specification, version 1. The transport is launched as a child
+//! process, asked to open a SOCKS5 listener for a single method, and WireGuard datagrams are
+//! then sent through it using the same length-prefixed framing as udp2tcp.
+
+use crate::{Obfuscator, Settings, Transport};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{TcpStream, UdpSocket},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+/// How long the transport may take to report its SOCKS listener.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest datagram that fits in the two byte length prefix.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+/// The user and group that transports are run as when the daemon runs as root.
+#[cfg(target_os = "linux")]
+const UNPRIVILEGED_ID: u32 = 65534;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    /// The transport executable path is not absolute
+    #[error(display = "The transport executable path must be absolute: {}", _0)]
+    RelativePath(String),
+
+    /// Failed to prepare the state directory of the transport
+    #[error(display = "Failed to prepare the transport state directory")]
+    StateDirectory(#[error(source)] io::Error),
+
+    /// Failed to start the transport process
+    #[error(display = "Failed to start the transport process")]
+    Spawn(#[error(source)] io::Error),
+
+    /// Failed to read from the transport process
+    #[error(display = "Failed to read from the transport process")]
+    ReadOutput(#[error(source)] io::Error),
+
+    /// The transport exited during setup
+    #[error(display = "The transport exited before opening a SOCKS listener")]
+    UnexpectedEof,
+
+    /// The transport did not finish its setup in time
+    #[error(display = "Timed out waiting for the transport to open a SOCKS listener")]
+    LaunchTimeout,
+
+    /// The transport rejected the environment or the protocol version
+    #[error(display = "The transport refused to start: {}", _0)]
+    Refused(String),
+
+    /// The transport does not provide the requested method
+    #[error(display = "The transport failed to provide method \"{}\": {}", _0, _1)]
+    MethodError(String, String),
+
+    /// The transport sent a line that could not be understood
+    #[error(display = "Invalid message from transport: {}", _0)]
+    InvalidMessage(String),
+
+    /// The SOCKS handshake with the transport failed
+    #[error(display = "SOCKS handshake with the transport failed")]
+    SocksHandshake(#[error(source)] io::Error),
+
+    /// Failed to bind the local UDP socket
+    #[error(display = "Failed to bind local UDP socket")]
+    BindUdp(#[error(source)] io::Error),
+
+    /// Forwarding traffic through the transport failed
+    #[error(display = "Failed to forward traffic through the transport")]
+    Forward(#[error(source)] io::Error),
+
+    /// The transport process exited
+    #[error(display = "The transport process exited: {}", _0)]
+    Exited(ExitStatus),
+
+    /// Failed to wait for the transport process
+    #[error(display = "Failed to wait for the transport process")]
+    Wait(#[error(source)] io::Error),
+}
+
+/// Options of the `external` transport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalTransportOptions {
+    /// Absolute path to the pluggable transport client.
+    pub path: PathBuf,
+    /// Extra command line arguments.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Transport method to request, e.g. `obfs4`.
+    pub method: String,
+    /// Per-connection arguments, passed in the SOCKS authentication fields.
+    #[serde(default)]
+    pub method_args: String,
+}
+
+impl ExternalTransportOptions {
+    /// Returns the options as a settings blob for [`ExternalTransport`].
+    pub fn to_options(&self) -> crate::TransportOptions {
+        serde_json::to_value(self).expect("failed to serialize transport options")
+    }
+}
+
+struct External {
+    local_addr: SocketAddr,
+    udp_socket: UdpSocket,
+    stream: TcpStream,
+    child: Child,
+    // Dropping stdin tells the transport to exit.
+    _stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl External {
+    async fn new(settings: &Settings, options: ExternalTransportOptions) -> Result<Self> {
+        if !options.path.is_absolute() {
+            return Err(Error::RelativePath(options.path.display().to_string()));
+        }
+
+        let state_dir = prepare_state_dir(&options.method).map_err(Error::StateDirectory)?;
+        let mut child = spawn(&options, &state_dir)?;
+        let stdin = child.stdin.take().expect("missing stdin");
+        let mut stdout = BufReader::new(child.stdout.take().expect("missing stdout")).lines();
+
+        let proxy_addr = tokio::time::timeout(
+            LAUNCH_TIMEOUT,
+            read_client_method(&mut stdout, &options.method),
+        )
+        .await
+        .map_err(|_| Error::LaunchTimeout)??;
+
+        let stream = socks5_connect(proxy_addr, settings.peer, &options.method_args)
+            .await
+            .map_err(Error::SocksHandshake)?;
+        let _ = stream.set_nodelay(true);
+
+        let listen_addr = if settings.peer.is_ipv4() {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+        } else {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0)
+        };
+        let udp_socket = UdpSocket::bind(listen_addr).await.map_err(Error::BindUdp)?;
+        let local_addr = udp_socket.local_addr().map_err(Error::BindUdp)?;
+
+        Ok(Self {
+            local_addr,
+            udp_socket,
+            stream,
+            child,
+            _stdin: stdin,
+            stdout,
+        })
+    }
+}
+
+#[async_trait]
+impl Obfuscator for External {
+    fn endpoint(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        let External {
+            udp_socket,
+            stream,
+            mut child,
+            _stdin,
+            mut stdout,
+            ..
+        } = *self;
+
+        let (tcp_read, tcp_write) = stream.into_split();
+        let forward = async {
+            tokio::try_join!(
+                udp_to_tcp(&udp_socket, tcp_write),
+                tcp_to_udp(&udp_socket, tcp_read)
+            )
+        };
+        // Keep draining the transport's output so that it never blocks on a full pipe. The exit
+        // of the process is reported by `child.wait()`, so this never completes.
+        let drain_output = async {
+            while let Ok(Some(_line)) = stdout.next_line().await {}
+            futures::future::pending::<()>().await
+        };
+
+        let result = tokio::select! {
+            result = forward => result.map(|_| ()).map_err(Error::Forward),
+            status = child.wait() => match status {
+                Ok(status) => Err(Error::Exited(status)),
+                Err(error) => Err(Error::Wait(error)),
+            },
+            _ = drain_output => unreachable!("transport output is drained forever"),
+        };
+        result.map_err(crate::Error::RunExternalObfuscator)
+    }
+}
+
+/// Starts the transport with an empty environment except for the variables defined by the
+/// pluggable transport specification.
+fn spawn(options: &ExternalTransportOptions, state_dir: &Path) -> Result<Child> {
+    let mut command = std::process::Command::new(&options.path);
+    command
+        .args(&options.args)
+        .env_clear()
+        .env("TOR_PT_MANAGED_TRANSPORT_VER", "1")
+        .env("TOR_PT_CLIENT_TRANSPORTS", &options.method)
+        .env("TOR_PT_STATE_LOCATION", state_dir)
+        .env("TOR_PT_EXIT_ON_STDIN_CLOSE", "1")
+        .current_dir(state_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    #[cfg(windows)]
+    if let Some(system_root) = std::env::var_os("SystemRoot") {
+        command.env("SystemRoot", system_root);
+    }
+
+    // Traffic from the transport is excluded from the tunnel based on its process, not on the
+    // user it runs as, so there is no reason to keep root privileges.
+    // On macOS the firewall only lets root reach the relay, so privileges cannot be dropped.
+    #[cfg(target_os = "linux")]
+    if is_root() {
+        use std::os::unix::process::CommandExt;
+        command.uid(UNPRIVILEGED_ID).gid(UNPRIVILEGED_ID);
+    }
+
+    Command::from(command)
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(Error::Spawn)
+}
+
+/// Returns a directory that only the transport can write to.
+fn prepare_state_dir(method: &str) -> io::Result<PathBuf> {
+    let name: String = method
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    let state_dir = std::env::temp_dir().join(format!("mullvad-pt-{}", name));
+
+    match std::fs::symlink_metadata(&state_dir) {
+        Ok(metadata) => {
+            if !metadata.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "state path exists and is not a directory",
+                ));
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let owner = metadata.uid();
+                #[cfg(target_os = "linux")]
+                let expected_owner =
+                    owner == unsafe { libc::geteuid() } || (is_root() && owner == UNPRIVILEGED_ID);
+                #[cfg(not(target_os = "linux"))]
+                let expected_owner = owner == unsafe { libc::geteuid() };
+                if !expected_owner {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "state directory is owned by another user",
+                    ));
+                }
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            std::fs::create_dir(&state_dir)?;
+        }
+        Err(error) => return Err(error),
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&state_dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(target_os = "linux")]
+    if is_root() {
+        chown(&state_dir, UNPRIVILEGED_ID)?;
+    }
+
+    Ok(state_dir)
+}
+
+#[cfg(target_os = "linux")]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(target_os = "linux")]
+fn chown(path: &Path, id: u32) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    if unsafe { libc::lchown(path.as_ptr(), id, id) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Reads the transport's configuration messages until it has reported the SOCKS listener for
+/// `method`.
+async fn read_client_method<R: AsyncRead + Unpin>(
+    stdout: &mut Lines<BufReader<R>>,
+    method: &str,
+) -> Result<SocketAddr> {
+    let mut proxy_addr = None;
+    loop {
+        let line = stdout
+            .next_line()
+            .await
+            .map_err(Error::ReadOutput)?
+            .ok_or(Error::UnexpectedEof)?;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("VERSION") => {
+                if words.next() != Some("1") {
+                    return Err(Error::InvalidMessage(line));
+                }
+            }
+            Some("VERSION-ERROR") | Some("ENV-ERROR") => {
+                return Err(Error::Refused(words.collect::<Vec<_>>().join(" ")));
+            }
+            Some("CMETHOD") => {
+                let name = words.next();
+                let protocol = words.next();
+                let address = words.next().and_then(|address| address.parse().ok());
+                match (name, protocol, address) {
+                    (Some(name), Some("socks5"), Some(address)) if name == method => {
+                        proxy_addr = Some(address)
+                    }
+                    (Some(name), ..) if name != method => (),
+                    _ => return Err(Error::InvalidMessage(line)),
+                }
+            }
+            Some("CMETHOD-ERROR") => {
+                let name = words.next().unwrap_or_default().to_owned();
+                return Err(Error::MethodError(
+                    name,
+                    words.collect::<Vec<_>>().join(" "),
+                ));
+            }
+            Some("CMETHODS") if words.next() == Some("DONE") => {
+                return proxy_addr.ok_or_else(|| {
+                    Error::MethodError(method.to_owned(), "method not provided".to_owned())
+                });
+            }
+            // LOG, STATUS and unknown keywords are ignored, as required by the specification.
+            _ => (),
+        }
+    }
+}
+
+/// Connects to `target` through the SOCKS5 proxy of the transport. Method arguments are passed
+/// through username/password authentication, as described by the specification.
+async fn socks5_connect(
+    proxy: SocketAddr,
+    target: SocketAddr,
+    method_args: &str,
+) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy).await?;
+
+    let auth_method = if method_args.is_empty() { 0x00 } else { 0x02 };
+    stream.write_all(&[0x05, 0x01, auth_method]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x05 || reply[1] != auth_method {
+        return Err(socks_error("authentication method rejected"));
+    }
+
+    if auth_method == 0x02 {
+        let args = method_args.as_bytes();
+        if args.len() > 2 * 255 {
+            return Err(socks_error("method arguments are too long"));
+        }
+        let (username, password) = if args.len() > 255 {
+            args.split_at(255)
+        } else {
+            (args, &[0u8][..])
+        };
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend_from_slice(username);
+        request.push(password.len() as u8);
+        request.extend_from_slice(password);
+        stream.write_all(&request).await?;
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(socks_error("method arguments rejected"));
+        }
+    }
+
+    let mut request = vec![0x05, 0x01, 0x00];
+    match target.ip() {
+        IpAddr::V4(ip) => {
+            request.push(0x01);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(0x04);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    if header[1] != 0x00 {
+        return Err(socks_error("connection refused by transport"));
+    }
+    let address_len = match header[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await? as usize,
+        _ => return Err(socks_error("invalid address type")),
+    };
+    let mut bound_address = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound_address).await?;
+
+    Ok(stream)
+}
+
+fn socks_error(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+async fn udp_to_tcp(
+    udp_socket: &UdpSocket,
+    mut tcp_write: tokio::net::tcp::OwnedWriteHalf,
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; 2 + MAX_DATAGRAM_SIZE];
+    let mut connected = false;
+    loop {
+        let (len, peer) = udp_socket.recv_from(&mut buffer[2..]).await?;
+        if !connected {
+            udp_socket.connect(peer).await?;
+            connected = true;
+        }
+        buffer[..2].copy_from_slice(&(len as u16).to_be_bytes());
+        tcp_write.write_all(&buffer[..2 + len]).await?;
+    }
+}
+
+async fn tcp_to_udp(
+    udp_socket: &UdpSocket,
+    tcp_read: tokio::net::tcp::OwnedReadHalf,
+) -> io::Result<()> {
+    let mut tcp_read = BufReader::new(tcp_read);
+    let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let len = tcp_read.read_u16().await? as usize;
+        tcp_read.read_exact(&mut buffer[..len]).await?;
+        // Datagrams that arrive before WireGuard has sent anything have nowhere to go, so
+        // failing to deliver one is not fatal.
+        let _ = udp_socket.send(&buffer[..len]).await;
+    }
+}
+
+/// Runs a pluggable transport executable chosen by the user. See [`ExternalTransportOptions`].
+pub struct ExternalTransport;
+
+#[async_trait]
+impl Transport for ExternalTransport {
+    fn name(&self) -> &'static str {
+        "external"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sends WireGuard traffic through a user supplied pluggable transport"
+    }
+
+    async fn create_obfuscator(&self, settings: &Settings) -> crate::Result<Box<dyn Obfuscator>> {
+        let options: ExternalTransportOptions = serde_json::from_value(settings.options.clone())
+            .map_err(|error| crate::Error::InvalidTransportSettings(self.name(), error))?;
+        Ok(Box::new(
+            External::new(settings, options)
+                .await
+                .map_err(crate::Error::CreateExternalObfuscator)?,
+        ))
+    }
+}
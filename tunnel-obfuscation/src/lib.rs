@@ -1,8 +1,12 @@
 use async_trait::async_trait;
 use std::net::SocketAddr;
 
+mod external;
 mod udp2tcp;
-pub use udp2tcp::Udp2TcpSettings;
+mod wss;
+pub use external::{ExternalTransport, ExternalTransportOptions};
+pub use udp2tcp::Udp2TcpTransport;
+pub use wss::{WssTransport, WssTransportOptions};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -14,22 +18,91 @@ pub enum Error {
 
     #[error(display = "Failed to run Udp2Tcp obfuscator")]
     RunUdp2TcpObfuscator(#[error(source)] udp2tcp::Error),
+
+    #[error(display = "Failed to create external obfuscator")]
+    CreateExternalObfuscator(#[error(source)] external::Error),
+
+    #[error(display = "Failed to run external obfuscator")]
+    RunExternalObfuscator(#[error(source)] external::Error),
+
+    #[error(display = "Failed to create WebSocket obfuscator")]
+    CreateWssObfuscator(#[error(source)] wss::Error),
+
+    #[error(display = "Failed to run WebSocket obfuscator")]
+    RunWssObfuscator(#[error(source)] wss::Error),
+
+    #[error(display = "No obfuscation transport named \"{}\" is registered", _0)]
+    UnknownTransport(String),
+
+    #[error(display = "Invalid settings for obfuscation transport \"{}\"", _0)]
+    InvalidTransportSettings(&'static str, #[error(source)] serde_json::Error),
+
+    /// Used by transports that are implemented outside of this crate.
+    #[error(display = "Obfuscation transport \"{}\" failed: {}", _0, _1)]
+    Transport(&'static str, String),
 }
 
 #[async_trait]
 pub trait Obfuscator: Send {
     fn endpoint(&self) -> SocketAddr;
+
+    /// Returns the ID of the process that sends the obfuscated traffic, if it is not sent by the
+    /// current process.
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+
     async fn run(self: Box<Self>) -> Result<()>;
 }
 
-pub enum Settings {
-    Udp2Tcp(Udp2TcpSettings),
+/// A kind of obfuscation that can be layered on top of WireGuard. Each transport is responsible
+/// for interpreting its own settings blob.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Unique identifier of the transport, e.g. `udp2tcp`.
+    fn name(&self) -> &'static str;
+
+    /// Short human readable description of the transport.
+    fn description(&self) -> &'static str;
+
+    /// Creates an obfuscator that forwards WireGuard traffic to the peer in `settings`.
+    async fn create_obfuscator(&self, settings: &Settings) -> Result<Box<dyn Obfuscator>>;
+}
+
+/// All transports that are compiled into this build, in order of preference.
+static TRANSPORTS: &[&dyn Transport] = &[&Udp2TcpTransport, &WssTransport, &ExternalTransport];
+
+/// Returns the transports that are compiled into this build.
+pub fn transports() -> &'static [&'static dyn Transport] {
+    TRANSPORTS
+}
+
+/// Looks up a transport by name.
+pub fn find_transport(name: &str) -> Option<&'static dyn Transport> {
+    TRANSPORTS
+        .iter()
+        .find(|transport| transport.name() == name)
+        .copied()
+}
+
+/// Transport specific settings blob.
+pub type TransportOptions = serde_json::Value;
+
+/// Settings used to create an obfuscator.
+pub struct Settings {
+    /// Name of the transport to use.
+    pub transport: String,
+    /// Address of the obfuscation server on the relay.
+    pub peer: SocketAddr,
+    /// Firewall mark to apply to the outgoing traffic of the obfuscator.
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+    /// Transport specific settings. `Null` selects the defaults of the transport.
+    pub options: TransportOptions,
 }
 
 pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator>> {
-    match settings {
-        Settings::Udp2Tcp(s) => udp2tcp::create_obfuscator(s)
-            .await
-            .map_err(Error::CreateUdp2TcpObfuscator),
-    }
+    let transport = find_transport(&settings.transport)
+        .ok_or_else(|| Error::UnknownTransport(settings.transport.clone()))?;
+    transport.create_obfuscator(settings).await
 }
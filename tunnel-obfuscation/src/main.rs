@@ -1,5 +1,7 @@
 use std::{env::args, net::SocketAddr};
-use tunnel_obfuscation::{create_obfuscator, Obfuscator, Settings, Udp2TcpSettings};
+use tunnel_obfuscation::{
+    create_obfuscator, find_transport, transports, Obfuscator, Settings, TransportOptions,
+};
 
 #[tokio::main]
 async fn main() {
@@ -17,20 +19,26 @@ async fn main() {
 }
 
 async fn instantiate_requested(obfuscator_type: &str) -> Box<dyn Obfuscator> {
-    match obfuscator_type {
-        "udp2tcp" => {
-            let settings = Udp2TcpSettings {
-                peer: SocketAddr::new("127.0.0.1".parse().unwrap(), 3030),
-                #[cfg(target_os = "linux")]
-                fwmark: Some(1337),
-            };
-
-            create_obfuscator(&Settings::Udp2Tcp(settings))
-                .await
-                .expect("Creating obfuscator failed")
-        }
-        _ => {
-            unimplemented!()
-        }
+    if find_transport(obfuscator_type).is_none() {
+        let names = transports()
+            .iter()
+            .map(|transport| transport.name())
+            .collect::<Vec<_>>();
+        panic!(
+            "Unknown transport. Available transports: {}",
+            names.join(", ")
+        );
     }
+
+    let settings = Settings {
+        transport: obfuscator_type.to_owned(),
+        peer: SocketAddr::new("127.0.0.1".parse().unwrap(), 3030),
+        #[cfg(target_os = "linux")]
+        fwmark: Some(1337),
+        options: TransportOptions::Null,
+    };
+
+    create_obfuscator(&settings)
+        .await
+        .expect("Creating obfuscator failed")
 }
@@ -1,4 +1,4 @@
-use crate::Obfuscator;
+use crate::{Obfuscator, Settings, Transport};
 use async_trait::async_trait;
 use std::net::SocketAddr;
 use udp_over_tcp::{
@@ -6,12 +6,6 @@ use udp_over_tcp::{
     TcpOptions,
 };
 
-pub struct Udp2TcpSettings {
-    pub peer: SocketAddr,
-    #[cfg(target_os = "linux")]
-    pub fwmark: Option<u32>,
-}
-
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(err_derive::Error, Debug)]
@@ -36,7 +30,7 @@ struct Udp2Tcp {
 }
 
 impl Udp2Tcp {
-    pub async fn new(settings: &Udp2TcpSettings) -> Result<Self> {
+    pub async fn new(settings: &Settings) -> Result<Self> {
         let listen_addr = if settings.peer.is_ipv4() {
             SocketAddr::new("127.0.0.1".parse().unwrap(), 0)
         } else {
@@ -80,6 +74,24 @@ impl Obfuscator for Udp2Tcp {
     }
 }
 
-pub async fn create_obfuscator(settings: &Udp2TcpSettings) -> Result<Box<dyn Obfuscator>> {
-    Ok(Box::new(Udp2Tcp::new(settings).await?))
+/// Tunnels WireGuard's UDP traffic inside a TCP connection. Takes no options.
+pub struct Udp2TcpTransport;
+
+#[async_trait]
+impl Transport for Udp2TcpTransport {
+    fn name(&self) -> &'static str {
+        "udp2tcp"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sends WireGuard traffic over TCP"
+    }
+
+    async fn create_obfuscator(&self, settings: &Settings) -> crate::Result<Box<dyn Obfuscator>> {
+        Ok(Box::new(
+            Udp2Tcp::new(settings)
+                .await
+                .map_err(crate::Error::CreateUdp2TcpObfuscator)?,
+        ))
+    }
 }
@@ -0,0 +1,424 @@
+//! Sends WireGuard datagrams as binary WebSocket messages over a TLS connection, usually on TCP
+//! port 443. To anything watching the network this looks like an ordinary HTTPS connection that
+//! has been upgraded to a WebSocket, which gets through networks that recognize and block the
+//! udp2tcp framing.
+
+use crate::{Obfuscator, Settings, Transport};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    net::{TcpStream, UdpSocket},
+    sync::Mutex,
+};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        self,
+        client::{ServerCertVerified, ServerCertVerifier},
+        Certificate, ClientConfig, ServerName,
+    },
+    TlsConnector,
+};
+
+/// How long the TCP connection and the TLS and WebSocket handshakes may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Largest datagram that WireGuard sends.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+/// Upper bound of the size of the HTTP response to the upgrade request.
+const MAX_RESPONSE_SIZE: usize = 8 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(err_derive::Error, Debug)]
+#[error(no_from)]
+pub enum Error {
+    /// The server name cannot be used for TLS
+    #[error(display = "Invalid TLS server name: {}", _0)]
+    InvalidServerName(String),
+
+    /// Failed to bind the local UDP socket
+    #[error(display = "Failed to bind local UDP socket")]
+    BindUdp(#[error(source)] io::Error),
+
+    /// Failed to connect to the relay
+    #[error(display = "Failed to connect to the relay")]
+    Connect(#[error(source)] io::Error),
+
+    /// The TLS handshake failed
+    #[error(display = "TLS handshake with the relay failed")]
+    TlsHandshake(#[error(source)] io::Error),
+
+    /// The WebSocket upgrade request could not be sent or the response could not be read
+    #[error(display = "WebSocket handshake with the relay failed")]
+    WebSocketHandshake(#[error(source)] io::Error),
+
+    /// The relay did not agree to upgrade the connection
+    #[error(display = "The relay rejected the WebSocket upgrade: {}", _0)]
+    UpgradeRejected(String),
+
+    /// The handshakes did not finish in time
+    #[error(display = "Timed out connecting to the relay")]
+    ConnectTimeout,
+
+    /// Forwarding traffic to the relay failed
+    #[error(display = "Failed to forward traffic to the relay")]
+    Forward(#[error(source)] io::Error),
+}
+
+/// Options of the `wss` transport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WssTransportOptions {
+    /// Name sent in the TLS server name indication and the `Host` header.
+    pub server_name: String,
+    /// Path of the WebSocket endpoint on the relay.
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_path() -> String {
+    "/".to_owned()
+}
+
+impl WssTransportOptions {
+    /// Returns the options as a settings blob for [`WssTransport`].
+    pub fn to_options(&self) -> crate::TransportOptions {
+        serde_json::to_value(self).expect("failed to serialize transport options")
+    }
+}
+
+struct Wss {
+    local_addr: SocketAddr,
+    udp_socket: UdpSocket,
+    stream: TlsStream<TcpStream>,
+}
+
+impl Wss {
+    async fn new(settings: &Settings, options: WssTransportOptions) -> Result<Self> {
+        let server_name = ServerName::try_from(options.server_name.as_str())
+            .map_err(|_| Error::InvalidServerName(options.server_name.clone()))?;
+
+        let listen_addr = if settings.peer.is_ipv4() {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+        } else {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0)
+        };
+        let udp_socket = UdpSocket::bind(listen_addr).await.map_err(Error::BindUdp)?;
+        let local_addr = udp_socket.local_addr().map_err(Error::BindUdp)?;
+
+        let stream =
+            tokio::time::timeout(CONNECT_TIMEOUT, connect(settings, server_name, &options))
+                .await
+                .map_err(|_| Error::ConnectTimeout)??;
+
+        Ok(Self {
+            local_addr,
+            udp_socket,
+            stream,
+        })
+    }
+}
+
+#[async_trait]
+impl Obfuscator for Wss {
+    fn endpoint(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        let Wss {
+            udp_socket, stream, ..
+        } = *self;
+
+        let (tls_read, tls_write) = tokio::io::split(stream);
+        let tls_write = Mutex::new(tls_write);
+        tokio::try_join!(
+            udp_to_wss(&udp_socket, &tls_write),
+            wss_to_udp(&udp_socket, tls_read, &tls_write)
+        )
+        .map(|_| ())
+        .map_err(Error::Forward)
+        .map_err(crate::Error::RunWssObfuscator)
+    }
+}
+
+/// Connects to the relay and performs the TLS and WebSocket handshakes.
+async fn connect(
+    settings: &Settings,
+    server_name: ServerName,
+    options: &WssTransportOptions,
+) -> Result<TlsStream<TcpStream>> {
+    let stream = connect_tcp(settings).await.map_err(Error::Connect)?;
+    let _ = stream.set_nodelay(true);
+
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+        .with_no_client_auth();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await
+        .map_err(Error::TlsHandshake)?;
+
+    websocket_handshake(&mut stream, &options.server_name, &options.path).await?;
+    Ok(stream)
+}
+
+#[cfg(target_os = "linux")]
+async fn connect_tcp(settings: &Settings) -> io::Result<TcpStream> {
+    use std::os::unix::io::AsRawFd;
+
+    let socket = if settings.peer.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    if let Some(fwmark) = settings.fwmark {
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_MARK,
+                &fwmark as *const u32 as *const libc::c_void,
+                std::mem::size_of_val(&fwmark) as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    socket.connect(settings.peer).await
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn connect_tcp(settings: &Settings) -> io::Result<TcpStream> {
+    TcpStream::connect(settings.peer).await
+}
+
+/// Sends the HTTP upgrade request and waits for the relay to switch protocols.
+async fn websocket_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    path: &str,
+) -> Result<()> {
+    let key = base64::encode(rand::random::<[u8; 16]>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        path, host, key
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(Error::WebSocketHandshake)?;
+    stream.flush().await.map_err(Error::WebSocketHandshake)?;
+
+    // Read one byte at a time so that no part of the first frame is consumed.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_SIZE {
+            return Err(Error::UpgradeRejected(
+                "the response is too large".to_owned(),
+            ));
+        }
+        response.push(stream.read_u8().await.map_err(Error::WebSocketHandshake)?);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let mut status = status_line.split_whitespace();
+    match (status.next(), status.next()) {
+        (Some(version), Some("101")) if version.starts_with("HTTP/1.") => Ok(()),
+        _ => Err(Error::UpgradeRejected(status_line.to_owned())),
+    }
+}
+
+async fn udp_to_wss(
+    udp_socket: &UdpSocket,
+    tls_write: &Mutex<WriteHalf<TlsStream<TcpStream>>>,
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+    let mut connected = false;
+    loop {
+        let (len, peer) = udp_socket.recv_from(&mut buffer).await?;
+        if !connected {
+            udp_socket.connect(peer).await?;
+            connected = true;
+        }
+        write_frame(&mut *tls_write.lock().await, OPCODE_BINARY, &buffer[..len]).await?;
+    }
+}
+
+async fn wss_to_udp(
+    udp_socket: &UdpSocket,
+    mut tls_read: ReadHalf<TlsStream<TcpStream>>,
+    tls_write: &Mutex<WriteHalf<TlsStream<TcpStream>>>,
+) -> io::Result<()> {
+    let mut message = Vec::new();
+    loop {
+        let frame = read_frame(&mut tls_read).await?;
+        match frame.opcode {
+            OPCODE_PING => {
+                write_frame(&mut *tls_write.lock().await, OPCODE_PONG, &frame.payload).await?;
+            }
+            OPCODE_PONG => (),
+            OPCODE_CLOSE => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "the relay closed the WebSocket",
+                ))
+            }
+            OPCODE_BINARY | OPCODE_CONTINUATION => {
+                if message.len() + frame.payload.len() > MAX_DATAGRAM_SIZE {
+                    return Err(invalid_data("message is too large"));
+                }
+                message.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    // Datagrams that arrive before WireGuard has sent anything have nowhere to
+                    // go, so failing to deliver one is not fatal.
+                    let _ = udp_socket.send(&message).await;
+                    message.clear();
+                }
+            }
+            _ => return Err(invalid_data("unexpected frame type")),
+        }
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Frame> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7f {
+        126 => u64::from(reader.read_u16().await?),
+        127 => reader.read_u64().await?,
+        len => u64::from(len),
+    };
+    if len > MAX_DATAGRAM_SIZE as u64 {
+        return Err(invalid_data("frame is too large"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        apply_mask(&mut payload, mask);
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Writes a single unfragmented frame. Frames sent by a client must be masked.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= usize::from(u16::MAX) => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let mask = rand::random::<[u8; 4]>();
+    frame.extend_from_slice(&mask);
+    let payload_start = frame.len();
+    frame.extend_from_slice(payload);
+    apply_mask(&mut frame[payload_start..], mask);
+
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Accepts the certificate of any relay. The TLS layer only exists to make the traffic look like
+/// HTTPS, and the relay is authenticated by WireGuard inside of it, so there is nothing to gain
+/// from verifying the certificate.
+struct AcceptAnyCertificate;
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Tunnels WireGuard traffic through a WebSocket over TLS. See [`WssTransportOptions`].
+pub struct WssTransport;
+
+#[async_trait]
+impl Transport for WssTransport {
+    fn name(&self) -> &'static str {
+        "wss"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sends WireGuard traffic through a WebSocket over TLS"
+    }
+
+    async fn create_obfuscator(&self, settings: &Settings) -> crate::Result<Box<dyn Obfuscator>> {
+        let options: WssTransportOptions = serde_json::from_value(settings.options.clone())
+            .map_err(|error| crate::Error::InvalidTransportSettings(self.name(), error))?;
+        Ok(Box::new(
+            Wss::new(settings, options)
+                .await
+                .map_err(crate::Error::CreateWssObfuscator)?,
+        ))
+    }
+}
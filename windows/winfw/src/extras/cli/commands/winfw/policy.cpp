@@ -4,6 +4,7 @@
 #include <libcommon/error.h>
 #include "winfw/winfw.h"
 #include <functional>
+#include <iterator>
 
 namespace commands::winfw
 {
@@ -11,12 +12,30 @@ namespace commands::winfw
 namespace detail
 {
 
+const WinFwAllowedLanNet DefaultAllowedLanNets[] =
+{
+	{ L"10.0.0.0", 8 },
+	{ L"172.16.0.0", 12 },
+	{ L"192.168.0.0", 16 },
+	{ L"169.254.0.0", 16 },
+	{ L"fe80::", 10 },
+	{ L"fc00::", 7 },
+};
+
 WinFwSettings CreateSettings(const std::wstring &dhcp, const std::wstring &lan)
 {
 	WinFwSettings s;
 
 	s.permitDhcp = (0 == _wcsicmp(dhcp.c_str(), L"yes"));
+	s.permitNdp = s.permitDhcp;
 	s.permitLan = (0 == _wcsicmp(lan.c_str(), L"yes"));
+	s.permitMulticast = false;
+	s.allowedLanNets = DefaultAllowedLanNets;
+	s.numAllowedLanNets = static_cast<uint32_t>(std::size(DefaultAllowedLanNets));
+	s.firewallExceptions = nullptr;
+	s.numFirewallExceptions = 0;
+	s.permitCaptivePortal = false;
+	s.captivePortalAddress = nullptr;
 
 	return s;
 }
@@ -152,7 +171,14 @@ void Policy::processConnected(const KeyValuePairs &arguments)
 		dnsCstr,
 		nullptr,
 		&dnsCstr,
-		1
+		1,
+		false,
+		nullptr,
+		0,
+		nullptr,
+		0,
+		nullptr,
+		0
 	);
 
 	m_messageSink((success
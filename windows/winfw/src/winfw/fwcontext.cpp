@@ -8,14 +8,20 @@
 #include "rules/baseline/permitdhcp.h"
 #include "rules/baseline/permitndp.h"
 #include "rules/baseline/permitdhcpserver.h"
+#include "rules/baseline/permitdiscovery.h"
+#include "rules/baseline/permitexcludednetworks.h"
 #include "rules/baseline/permitlan.h"
 #include "rules/baseline/permitlanservice.h"
 #include "rules/baseline/permitloopback.h"
+#include "rules/baseline/permitportexceptions.h"
+#include "rules/baseline/permitcaptiveportal.h"
 #include "rules/baseline/permitvpntunnel.h"
+#include "rules/baseline/permitvpntunnelinbound.h"
 #include "rules/baseline/permitvpntunnelservice.h"
 #include "rules/baseline/permitdns.h"
 #include "rules/baseline/permitendpoint.h"
 #include "rules/dns/blockall.h"
+#include "rules/dns/blockencrypted.h"
 #include "rules/dns/permittunnel.h"
 #include "rules/dns/permitnontunnel.h"
 #include "rules/multi/permitvpnrelay.h"
@@ -54,16 +60,63 @@ void AppendSettingsRules
 	if (settings.permitDhcp)
 	{
 		ruleset.emplace_back(std::make_unique<baseline::PermitDhcp>());
+	}
+
+	if (settings.permitNdp)
+	{
 		ruleset.emplace_back(std::make_unique<baseline::PermitNdp>());
 	}
 
+	//
+	// The allowed LAN networks are also the networks that firewall exceptions apply to.
+	//
+
+	std::vector<wfp::IpNetwork> ipv4Networks;
+	std::vector<wfp::IpNetwork> ipv6Networks;
+
+	for (uint32_t i = 0; i < settings.numAllowedLanNets; ++i)
+	{
+		const auto &net = settings.allowedLanNets[i];
+		const auto address = wfp::IpAddress(net.address);
+
+		auto &networks = (address.type() == wfp::IpAddress::Type::Ipv4 ? ipv4Networks : ipv6Networks);
+		networks.emplace_back(address, net.prefixLength);
+	}
+
 	if (settings.permitLan)
 	{
-		ruleset.emplace_back(std::make_unique<baseline::PermitLan>());
-		ruleset.emplace_back(std::make_unique<baseline::PermitLanService>());
+		ruleset.emplace_back(std::make_unique<baseline::PermitLan>(ipv4Networks, ipv6Networks));
+		ruleset.emplace_back(std::make_unique<baseline::PermitLanService>(ipv4Networks, ipv6Networks));
 		ruleset.emplace_back(baseline::PermitDhcpServer::WithExtent(baseline::PermitDhcpServer::Extent::IPv4Only));
 	}
 
+	if (settings.permitMulticast)
+	{
+		ruleset.emplace_back(std::make_unique<baseline::PermitDiscovery>());
+	}
+
+	if (0 != settings.numFirewallExceptions)
+	{
+		const std::vector<WinFwFirewallException> exceptions(
+			settings.firewallExceptions,
+			settings.firewallExceptions + settings.numFirewallExceptions
+		);
+
+		ruleset.emplace_back(std::make_unique<baseline::PermitPortExceptions>(exceptions, ipv4Networks, ipv6Networks));
+	}
+
+	if (settings.permitCaptivePortal)
+	{
+		std::optional<wfp::IpAddress> address;
+
+		if (nullptr != settings.captivePortalAddress)
+		{
+			address = wfp::IpAddress(settings.captivePortalAddress);
+		}
+
+		ruleset.emplace_back(std::make_unique<baseline::PermitCaptivePortal>(address));
+	}
+
 	//
 	// DNS management
 	//
@@ -254,7 +307,11 @@ bool FwContext::applyPolicyConnected
 	const std::wstring &relayClient,
 	const std::wstring &tunnelInterfaceAlias,
 	const std::vector<wfp::IpAddress> &tunnelDnsServers,
-	const std::vector<wfp::IpAddress> &nonTunnelDnsServers
+	const std::vector<wfp::IpAddress> &nonTunnelDnsServers,
+	bool blockEncryptedDns,
+	const std::vector<wfp::IpAddress> &dohServers,
+	const std::vector<WinFwInboundPort> &inboundPorts,
+	const std::vector<WinFwAllowedLanNet> &excludedNetworks
 )
 {
 	Ruleset ruleset;
@@ -263,6 +320,11 @@ bool FwContext::applyPolicyConnected
 	AppendSettingsRules(ruleset, settings);
 	AppendRelayRules(ruleset, relay, relayClient);
 
+	if (blockEncryptedDns)
+	{
+		ruleset.emplace_back(std::make_unique<dns::BlockEncrypted>(dohServers));
+	}
+
 	if (!tunnelDnsServers.empty())
 	{
 		ruleset.emplace_back(std::make_unique<dns::PermitTunnel>(
@@ -286,6 +348,30 @@ bool FwContext::applyPolicyConnected
 		std::nullopt
 	));
 
+	if (!inboundPorts.empty())
+	{
+		ruleset.emplace_back(std::make_unique<baseline::PermitVpnTunnelInbound>(
+			tunnelInterfaceAlias,
+			inboundPorts
+		));
+	}
+
+	if (!excludedNetworks.empty())
+	{
+		std::vector<wfp::IpNetwork> ipv4Networks;
+		std::vector<wfp::IpNetwork> ipv6Networks;
+
+		for (const auto &net : excludedNetworks)
+		{
+			const auto address = wfp::IpAddress(net.address);
+
+			auto &networks = (address.type() == wfp::IpAddress::Type::Ipv4 ? ipv4Networks : ipv6Networks);
+			networks.emplace_back(address, net.prefixLength);
+		}
+
+		ruleset.emplace_back(std::make_unique<baseline::PermitExcludedNetworks>(ipv4Networks, ipv6Networks));
+	}
+
 	const auto status = applyRuleset(ruleset);
 
 	if (status)
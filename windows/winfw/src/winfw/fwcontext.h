@@ -41,7 +41,11 @@ public:
 		const std::wstring &relayClient,
 		const std::wstring &tunnelInterfaceAlias,
 		const std::vector<wfp::IpAddress> &tunnelDnsServers,
-		const std::vector<wfp::IpAddress> &nonTunnelDnsServers
+		const std::vector<wfp::IpAddress> &nonTunnelDnsServers,
+		bool blockEncryptedDns,
+		const std::vector<wfp::IpAddress> &dohServers,
+		const std::vector<WinFwInboundPort> &inboundPorts,
+		const std::vector<WinFwAllowedLanNet> &excludedNetworks
 	);
 
 	bool applyPolicyBlocked(
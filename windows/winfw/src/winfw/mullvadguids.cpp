@@ -118,6 +118,24 @@ MullvadGuids::DetailedIdentityRegistry MullvadGuids::DetailedRegistry(IdentityQu
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLan_Outbound_Multicast_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLanService_Inbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLanService_Inbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Inbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Inbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Inbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Outbound_Ipv6()));
@@ -132,6 +150,10 @@ MullvadGuids::DetailedIdentityRegistry MullvadGuids::DetailedRegistry(IdentityQu
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitEndpoint()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnel_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnel_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelService_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitVpnTunnelService_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitNdp_Outbound_Router_Solicitation()));
@@ -145,6 +167,10 @@ MullvadGuids::DetailedIdentityRegistry MullvadGuids::DetailedRegistry(IdentityQu
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitDns_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockAll_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockAll_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockDnsOverTls_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockDnsOverTls_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockDnsOverHttps_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_BlockDnsOverHttps_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_PermitNonTunnel_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_PermitNonTunnel_Outbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Dns_PermitTunnel_Outbound_Ipv4()));
@@ -492,6 +518,258 @@ const GUID &MullvadGuids::Filter_Baseline_PermitLanService_Inbound_Ipv6()
 	return g;
 }
 
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0xf2cac0b1,
+		0xc8bb,
+		0x46c7,
+		{ 0xa2, 0x7b, 0x75, 0x93, 0xb4, 0xa8, 0x20, 0x9a }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0x87f88813,
+		0x04e0,
+		0x4261,
+		{ 0xa5, 0x5b, 0x69, 0x8, 0xdc, 0x1d, 0x42, 0x80 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0xac08be3c,
+		0x6481,
+		0x4831,
+		{ 0x8a, 0x5d, 0xc6, 0x6c, 0xcb, 0xe4, 0x6f, 0x14 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xd7b03b4f,
+		0xb092,
+		0x4932,
+		{ 0x8b, 0xd0, 0xd5, 0x49, 0xd2, 0x97, 0x88, 0xbf }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0x7ee1c3b,
+		0x1f21,
+		0x4513,
+		{ 0x8f, 0x14, 0xaa, 0xba, 0xaa, 0xee, 0x8d, 0xbd }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xedc72ed7,
+		0xb055,
+		0x4f96,
+		{ 0x96, 0x54, 0x96, 0x39, 0xe, 0x4f, 0x98, 0xc2 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv4()
+{
+	static const GUID g =
+	{
+		0xbe3017f5,
+		0xab3b,
+		0x4628,
+		{ 0xb0, 0xe8, 0x42, 0x8b, 0xa0, 0x10, 0x71, 0x22 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv6()
+{
+	static const GUID g =
+	{
+		0xef6e1ba,
+		0x815,
+		0x4ea0,
+		{ 0xa0, 0xed, 0xaa, 0xc2, 0xa0, 0xb, 0xf8, 0x46 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv4()
+{
+	static const GUID g =
+	{
+		0xc833bbe6,
+		0xe8b3,
+		0x48c9,
+		{ 0xbc, 0x4e, 0xff, 0x54, 0x78, 0xae, 0xb0, 0xef }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv6()
+{
+	static const GUID g =
+	{
+		0xc7c65583,
+		0x669b,
+		0x434f,
+		{ 0xb5, 0xe6, 0x2f, 0x3a, 0x67, 0x25, 0x73, 0xa4 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv4()
+{
+	static const GUID g =
+	{
+		0x77f70601,
+		0x9337,
+		0x4094,
+		{ 0x8d, 0x7a, 0xcc, 0xf5, 0xcb, 0x3, 0x4c, 0x8f }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv6()
+{
+	static const GUID g =
+	{
+		0xe77f60b5,
+		0x2323,
+		0x4faa,
+		{ 0xb0, 0xa0, 0x35, 0x43, 0x6b, 0x75, 0x12, 0x10 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv4()
+{
+	static const GUID g =
+	{
+		0x20ede770,
+		0x421c,
+		0x4c53,
+		{ 0xa6, 0x40, 0xe0, 0xdf, 0x37, 0xb1, 0xa, 0x9b }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv6()
+{
+	static const GUID g =
+	{
+		0x3a721f27,
+		0x2ec4,
+		0x45c3,
+		{ 0xbf, 0x9b, 0x61, 0xcd, 0x54, 0x16, 0x8c, 0xee }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv4()
+{
+	static const GUID g =
+	{
+		0xbbebcbd3,
+		0xf1a9,
+		0x4591,
+		{ 0x8f, 0x33, 0xfb, 0xaf, 0x47, 0x41, 0xf3, 0x74 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv6()
+{
+	static const GUID g =
+	{
+		0x2c3b2c76,
+		0x9ee8,
+		0x4a34,
+		{ 0x87, 0x90, 0xbd, 0x32, 0x2a, 0x72, 0xd9, 0x8 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0x5d8e1f3b,
+		0x7c42,
+		0x4e9a,
+		{ 0x9b, 0x61, 0x2f, 0xd4, 0x83, 0x0e, 0xa7, 0x15 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xe1a4c962,
+		0x3b0d,
+		0x4f57,
+		{ 0xa8, 0x2c, 0x71, 0x9e, 0x05, 0xb3, 0xd6, 0x4f }
+	};
+
+	return g;
+}
+
 //static
 const GUID &MullvadGuids::Filter_Baseline_PermitLoopback_Outbound_Ipv4()
 {
@@ -688,6 +966,62 @@ const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnel_Outbound_Ipv6()
 	return g;
 }
 
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv4()
+{
+	static const GUID g =
+	{
+		0xb435b246,
+		0x7cd0,
+		0x4c8e,
+		{ 0x9d, 0xeb, 0x8b, 0x9e, 0x89, 0xfd, 0xa2, 0x17 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv6()
+{
+	static const GUID g =
+	{
+		0x4f1a7c3b,
+		0xa349,
+		0x4d38,
+		{ 0xb9, 0x20, 0x58, 0xe1, 0x45, 0xad, 0x82, 0x64 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv4()
+{
+	static const GUID g =
+	{
+		0xbe0bf484,
+		0x2c23,
+		0x4642,
+		{ 0xa4, 0x51, 0x5c, 0xd6, 0xba, 0x7d, 0x54, 0x7a }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv6()
+{
+	static const GUID g =
+	{
+		0x349e763a,
+		0x947d,
+		0x44c7,
+		{ 0xa4, 0x59, 0x93, 0xe4, 0x8f, 0x4e, 0x6d, 0x4c }
+	};
+
+	return g;
+}
+
 //static
 const GUID &MullvadGuids::Filter_Baseline_PermitVpnTunnelService_Ipv4()
 {
@@ -870,6 +1204,62 @@ const GUID &MullvadGuids::Filter_Dns_BlockAll_Outbound_Ipv6()
 	return g;
 }
 
+//static
+const GUID &MullvadGuids::Filter_Dns_BlockDnsOverTls_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0xc389caf4,
+		0xc8c9,
+		0x44c9,
+		{ 0xa1, 0xb, 0x34, 0xb0, 0x5a, 0xdf, 0xb, 0x98 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Dns_BlockDnsOverTls_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0x7ba1f296,
+		0x2e50,
+		0x4223,
+		{ 0xb2, 0x8d, 0xc4, 0x7c, 0x4c, 0x29, 0x5c, 0xf5 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Dns_BlockDnsOverHttps_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0x0cd03332,
+		0xe2e3,
+		0x4f0d,
+		{ 0xbc, 0x4f, 0xfd, 0x11, 0x6c, 0x74, 0xac, 0xf }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Dns_BlockDnsOverHttps_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0x72f0a96c,
+		0x005b,
+		0x41ba,
+		{ 0xbb, 0x41, 0xc1, 0xe, 0x2b, 0xc4, 0x81, 0x3 }
+	};
+
+	return g;
+}
+
 //static
 const GUID &MullvadGuids::Filter_Dns_PermitNonTunnel_Outbound_Ipv4()
 {
@@ -54,6 +54,28 @@ public:
 	static const GUID &Filter_Baseline_PermitLanService_Inbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLanService_Inbound_Ipv6();
 
+	static const GUID &Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv6();
+
+	static const GUID &Filter_Baseline_PermitDiscovery_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitDiscovery_Outbound_Ipv6();
+	static const GUID &Filter_Baseline_PermitDiscovery_Inbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitDiscovery_Inbound_Ipv6();
+	static const GUID &Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv4();
+	static const GUID &Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv6();
+
+	static const GUID &Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv4();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv6();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv4();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv6();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv4();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv6();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv4();
+	static const GUID &Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv6();
+
+	static const GUID &Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6();
+
 	static const GUID &Filter_Baseline_PermitLoopback_Outbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLoopback_Inbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLoopback_Outbound_Ipv6();
@@ -74,6 +96,11 @@ public:
 	static const GUID &Filter_Baseline_PermitVpnTunnel_Outbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitVpnTunnel_Outbound_Ipv6();
 
+	static const GUID &Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv4();
+	static const GUID &Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv6();
+	static const GUID &Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv4();
+	static const GUID &Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv6();
+
 	static const GUID &Filter_Baseline_PermitVpnTunnelService_Ipv4();
 	static const GUID &Filter_Baseline_PermitVpnTunnelService_Ipv6();
 
@@ -90,6 +117,10 @@ public:
 
 	static const GUID &Filter_Dns_BlockAll_Outbound_Ipv4();
 	static const GUID &Filter_Dns_BlockAll_Outbound_Ipv6();
+	static const GUID &Filter_Dns_BlockDnsOverTls_Outbound_Ipv4();
+	static const GUID &Filter_Dns_BlockDnsOverTls_Outbound_Ipv6();
+	static const GUID &Filter_Dns_BlockDnsOverHttps_Outbound_Ipv4();
+	static const GUID &Filter_Dns_BlockDnsOverHttps_Outbound_Ipv6();
 	static const GUID &Filter_Dns_PermitNonTunnel_Outbound_Ipv4();
 	static const GUID &Filter_Dns_PermitNonTunnel_Outbound_Ipv6();
 	static const GUID &Filter_Dns_PermitTunnel_Outbound_Ipv4();
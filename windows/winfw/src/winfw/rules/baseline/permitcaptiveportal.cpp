@@ -0,0 +1,62 @@
+#include "stdafx.h"
+#include "permitcaptiveportal.h"
+#include <winfw/mullvadguids.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+PermitCaptivePortal::PermitCaptivePortal(const std::optional<wfp::IpAddress> &address)
+	: m_address(address)
+{
+}
+
+bool PermitCaptivePortal::apply(IObjectInstaller &objectInstaller)
+{
+	if (m_address.has_value())
+	{
+		return applyFilter(objectInstaller, wfp::IpAddress::Type::Ipv6 == m_address->type());
+	}
+
+	return applyFilter(objectInstaller, false)
+		&& applyFilter(objectInstaller, true);
+}
+
+bool PermitCaptivePortal::applyFilter(IObjectInstaller &objectInstaller, bool ipv6) const
+{
+	const auto &layer = (ipv6 ? FWPM_LAYER_ALE_AUTH_CONNECT_V6 : FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+	//
+	// DNS is not affected by this filter, since all DNS traffic is lifted out of the
+	// baseline sublayer and blocked in the DNS sublayer.
+	//
+
+	wfp::FilterBuilder filterBuilder;
+
+	filterBuilder
+		.key(ipv6
+			? MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv6()
+			: MullvadGuids::Filter_Baseline_PermitCaptivePortal_Outbound_Ipv4())
+		.name(L"Permit outbound connections while signing in to a captive portal")
+		.description(L"This filter is part of a rule that temporarily permits traffic to a captive portal")
+		.provider(MullvadGuids::Provider())
+		.layer(layer)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	wfp::ConditionBuilder conditionBuilder(layer);
+
+	if (m_address.has_value())
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(*m_address));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
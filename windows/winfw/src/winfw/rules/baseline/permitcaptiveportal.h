@@ -0,0 +1,29 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <libwfp/ipaddress.h>
+#include <optional>
+
+namespace rules::baseline
+{
+
+class PermitCaptivePortal : public IFirewallRule
+{
+public:
+
+	//
+	// Permits outbound connections to `address`, or to any address if it is not set.
+	//
+	PermitCaptivePortal(const std::optional<wfp::IpAddress> &address);
+	~PermitCaptivePortal() = default;
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	bool applyFilter(IObjectInstaller &objectInstaller, bool ipv6) const;
+
+	const std::optional<wfp::IpAddress> m_address;
+};
+
+}
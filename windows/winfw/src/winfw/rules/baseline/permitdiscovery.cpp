@@ -0,0 +1,190 @@
+#include "stdafx.h"
+#include "permitdiscovery.h"
+#include <winfw/mullvadguids.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/ipaddress.h>
+#include <libwfp/ipnetwork.h>
+#include <libwfp/conditions/conditionprotocol.h>
+#include <libwfp/conditions/conditionport.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+namespace
+{
+
+constexpr uint16_t MDNS_PORT = 5353;
+constexpr uint16_t SSDP_PORT = 1900;
+
+void AddRemotePrivateNetworks(wfp::ConditionBuilder &conditionBuilder, bool ipv6)
+{
+	if (ipv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFE80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 10)));
+		conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFC00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 7)));
+		return;
+	}
+
+	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 10, 0, 0, 0 }), 8)));
+	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 172, 16, 0, 0 }), 12)));
+	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 192, 168, 0, 0 }), 16)));
+	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 169, 254, 0, 0 }), 16)));
+}
+
+} // anonymous namespace
+
+bool PermitDiscovery::apply(IObjectInstaller &objectInstaller)
+{
+	return applyIpv4(objectInstaller) && applyIpv6(objectInstaller);
+}
+
+bool PermitDiscovery::applyIpv4(IObjectInstaller &objectInstaller) const
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound mDNS and SSDP multicast.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Outbound_Ipv4())
+		.name(L"Permit outbound mDNS and SSDP multicast (IPv4)")
+		.description(L"This filter is part of a rule that permits discovery of local network services")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		// Local subnet multicast, including mDNS.
+		conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 224, 0, 0, 0 }), 24)));
+
+		// SSDP.
+		conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 239, 255, 255, 250 }), 32)));
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #2 Permit inbound mDNS and SSDP traffic from the local network.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Ipv4())
+		.name(L"Permit inbound mDNS and SSDP traffic (IPv4)")
+		.layer(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
+
+		conditionBuilder.add_condition(ConditionProtocol::Udp());
+		AddRemotePrivateNetworks(conditionBuilder, false);
+		conditionBuilder.add_condition(ConditionPort::Local(MDNS_PORT));
+		conditionBuilder.add_condition(ConditionPort::Local(SSDP_PORT));
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #3 Permit unicast responses to queries sent from this machine.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv4())
+		.name(L"Permit inbound mDNS and SSDP responses (IPv4)");
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
+
+	conditionBuilder.add_condition(ConditionProtocol::Udp());
+	AddRemotePrivateNetworks(conditionBuilder, false);
+	conditionBuilder.add_condition(ConditionPort::Remote(MDNS_PORT));
+	conditionBuilder.add_condition(ConditionPort::Remote(SSDP_PORT));
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+bool PermitDiscovery::applyIpv6(IObjectInstaller &objectInstaller) const
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound link-local multicast, which includes mDNS and SSDP.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Outbound_Ipv6())
+		.name(L"Permit outbound mDNS and SSDP multicast (IPv6)")
+		.description(L"This filter is part of a rule that permits discovery of local network services")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+		const wfp::IpNetwork linkLocalMulticast(wfp::IpAddress::Literal6({ 0xFF02, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 16);
+		conditionBuilder.add_condition(ConditionIp::Remote(linkLocalMulticast));
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #2 Permit inbound mDNS and SSDP traffic from the local network.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Ipv6())
+		.name(L"Permit inbound mDNS and SSDP traffic (IPv6)")
+		.layer(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
+
+		conditionBuilder.add_condition(ConditionProtocol::Udp());
+		AddRemotePrivateNetworks(conditionBuilder, true);
+		conditionBuilder.add_condition(ConditionPort::Local(MDNS_PORT));
+		conditionBuilder.add_condition(ConditionPort::Local(SSDP_PORT));
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #3 Permit unicast responses to queries sent from this machine.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitDiscovery_Inbound_Response_Ipv6())
+		.name(L"Permit inbound mDNS and SSDP responses (IPv6)");
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
+
+	conditionBuilder.add_condition(ConditionProtocol::Udp());
+	AddRemotePrivateNetworks(conditionBuilder, true);
+	conditionBuilder.add_condition(ConditionPort::Remote(MDNS_PORT));
+	conditionBuilder.add_condition(ConditionPort::Remote(SSDP_PORT));
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
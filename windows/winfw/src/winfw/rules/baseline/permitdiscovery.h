@@ -0,0 +1,24 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+
+namespace rules::baseline
+{
+
+//
+// Permits mDNS and SSDP discovery on the local network,
+// without permitting any other LAN traffic.
+//
+class PermitDiscovery : public IFirewallRule
+{
+public:
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	bool applyIpv4(IObjectInstaller &objectInstaller) const;
+	bool applyIpv6(IObjectInstaller &objectInstaller) const;
+};
+
+}
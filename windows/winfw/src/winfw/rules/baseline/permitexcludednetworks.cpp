@@ -0,0 +1,92 @@
+#include "stdafx.h"
+#include "permitexcludednetworks.h"
+#include <winfw/mullvadguids.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/ipnetwork.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+PermitExcludedNetworks::PermitExcludedNetworks
+(
+	const std::vector<wfp::IpNetwork> &ipv4Networks,
+	const std::vector<wfp::IpNetwork> &ipv6Networks
+)
+	: m_ipv4Networks(ipv4Networks)
+	, m_ipv6Networks(ipv6Networks)
+{
+}
+
+bool PermitExcludedNetworks::apply(IObjectInstaller &objectInstaller)
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound connections to excluded IPv4 networks.
+	//
+	// A filter without conditions would match all traffic,
+	// so skip it if no IPv4 networks are excluded.
+	//
+
+	if (!m_ipv4Networks.empty())
+	{
+		filterBuilder
+			.key(MullvadGuids::Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv4())
+			.name(L"Permit outbound connections to networks outside the tunnel (IPv4)")
+			.description(L"This filter is part of a rule that permits traffic that is not routed through the tunnel")
+			.provider(MullvadGuids::Provider())
+			.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+			.sublayer(MullvadGuids::SublayerBaseline())
+			.weight(wfp::FilterBuilder::WeightClass::Medium)
+			.permit();
+
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		for (const auto &network : m_ipv4Networks)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #2 Permit outbound connections to excluded IPv6 networks.
+	//
+
+	if (!m_ipv6Networks.empty())
+	{
+		filterBuilder
+			.key(MullvadGuids::Filter_Baseline_PermitExcludedNetworks_Outbound_Ipv6())
+			.name(L"Permit outbound connections to networks outside the tunnel (IPv6)")
+			.description(L"This filter is part of a rule that permits traffic that is not routed through the tunnel")
+			.provider(MullvadGuids::Provider())
+			.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6)
+			.sublayer(MullvadGuids::SublayerBaseline())
+			.weight(wfp::FilterBuilder::WeightClass::Medium)
+			.permit();
+
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+		for (const auto &network : m_ipv6Networks)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	return true;
+}
+
+}
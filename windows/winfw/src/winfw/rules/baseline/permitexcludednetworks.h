@@ -0,0 +1,32 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <libwfp/ipnetwork.h>
+#include <vector>
+
+namespace rules::baseline
+{
+
+//
+// Permits outbound connections to networks that are routed outside the VPN tunnel
+// because they are not among the allowed IPs of the tunnel.
+//
+class PermitExcludedNetworks : public IFirewallRule
+{
+public:
+
+	PermitExcludedNetworks(
+		const std::vector<wfp::IpNetwork> &ipv4Networks,
+		const std::vector<wfp::IpNetwork> &ipv6Networks
+	);
+	~PermitExcludedNetworks() = default;
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	const std::vector<wfp::IpNetwork> m_ipv4Networks;
+	const std::vector<wfp::IpNetwork> m_ipv6Networks;
+};
+
+}
@@ -0,0 +1,148 @@
+#include "stdafx.h"
+#include "permitportexceptions.h"
+#include <winfw/mullvadguids.h>
+#include <winfw/rules/shared.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/ipaddress.h>
+#include <libwfp/ipnetwork.h>
+#include <libwfp/conditions/conditionip.h>
+#include <libwfp/conditions/conditionport.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+namespace
+{
+
+const GUID &FilterKey(WinFwFirewallExceptionDirection direction, WinFwProtocol protocol, bool ipv6)
+{
+	if (WinFwFirewallExceptionDirection::Inbound == direction)
+	{
+		if (WinFwProtocol::Tcp == protocol)
+		{
+			return ipv6
+				? MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv6()
+				: MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Tcp_Ipv4();
+		}
+
+		return ipv6
+			? MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv6()
+			: MullvadGuids::Filter_Baseline_PermitPortExceptions_Inbound_Udp_Ipv4();
+	}
+
+	if (WinFwProtocol::Tcp == protocol)
+	{
+		return ipv6
+			? MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv6()
+			: MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Tcp_Ipv4();
+	}
+
+	return ipv6
+		? MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv6()
+		: MullvadGuids::Filter_Baseline_PermitPortExceptions_Outbound_Udp_Ipv4();
+}
+
+} // anonymous namespace
+
+PermitPortExceptions::PermitPortExceptions
+(
+	const std::vector<WinFwFirewallException> &exceptions,
+	const std::vector<wfp::IpNetwork> &ipv4Networks,
+	const std::vector<wfp::IpNetwork> &ipv6Networks
+)
+	: m_exceptions(exceptions)
+	, m_ipv4Networks(ipv4Networks)
+	, m_ipv6Networks(ipv6Networks)
+{
+}
+
+bool PermitPortExceptions::apply(IObjectInstaller &objectInstaller)
+{
+	for (const auto direction : { WinFwFirewallExceptionDirection::Inbound, WinFwFirewallExceptionDirection::Outbound })
+	{
+		for (const auto protocol : { WinFwProtocol::Tcp, WinFwProtocol::Udp })
+		{
+			if (!applyFilter(objectInstaller, direction, protocol, false)
+				|| !applyFilter(objectInstaller, direction, protocol, true))
+			{
+				return false;
+			}
+		}
+	}
+
+	return true;
+}
+
+bool PermitPortExceptions::applyFilter
+(
+	IObjectInstaller &objectInstaller,
+	WinFwFirewallExceptionDirection direction,
+	WinFwProtocol protocol,
+	bool ipv6
+) const
+{
+	const bool inbound = (WinFwFirewallExceptionDirection::Inbound == direction);
+
+	std::vector<uint16_t> ports;
+
+	for (const auto &exception : m_exceptions)
+	{
+		if (exception.direction == direction && exception.protocol == protocol)
+		{
+			ports.push_back(exception.port);
+		}
+	}
+
+	//
+	// Conditions on the same field are OR'ed together, so a single filter
+	// can cover all ports for a given direction, protocol and address family.
+	// A filter without port conditions would match all ports, so skip it.
+	// The same goes for a filter without network conditions.
+	//
+
+	const auto &networks = ipv6 ? m_ipv6Networks : m_ipv4Networks;
+
+	if (ports.empty() || networks.empty())
+	{
+		return true;
+	}
+
+	const auto &layer = inbound
+		? (ipv6 ? FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6 : FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4)
+		: (ipv6 ? FWPM_LAYER_ALE_AUTH_CONNECT_V6 : FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+	wfp::FilterBuilder filterBuilder;
+
+	filterBuilder
+		.key(FilterKey(direction, protocol, ipv6))
+		.name(inbound
+			? L"Permit inbound connections to user-defined ports on LAN"
+			: L"Permit outbound connections to user-defined ports on LAN")
+		.description(L"This filter is part of a rule that permits user-defined exceptions to the firewall")
+		.provider(MullvadGuids::Provider())
+		.layer(layer)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	wfp::ConditionBuilder conditionBuilder(layer);
+
+	conditionBuilder.add_condition(CreateProtocolCondition(protocol));
+
+	for (const auto &network : networks)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
+
+	for (const auto port : ports)
+	{
+		conditionBuilder.add_condition(inbound ? ConditionPort::Local(port) : ConditionPort::Remote(port));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
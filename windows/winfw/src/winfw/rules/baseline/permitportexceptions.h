@@ -0,0 +1,38 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <winfw/winfw.h>
+#include <libwfp/ipnetwork.h>
+#include <vector>
+
+namespace rules::baseline
+{
+
+class PermitPortExceptions : public IFirewallRule
+{
+public:
+
+	PermitPortExceptions(
+		const std::vector<WinFwFirewallException> &exceptions,
+		const std::vector<wfp::IpNetwork> &ipv4Networks,
+		const std::vector<wfp::IpNetwork> &ipv6Networks
+	);
+	~PermitPortExceptions() = default;
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	bool applyFilter(
+		IObjectInstaller &objectInstaller,
+		WinFwFirewallExceptionDirection direction,
+		WinFwProtocol protocol,
+		bool ipv6
+	) const;
+
+	const std::vector<WinFwFirewallException> m_exceptions;
+	const std::vector<wfp::IpNetwork> m_ipv4Networks;
+	const std::vector<wfp::IpNetwork> m_ipv6Networks;
+};
+
+}
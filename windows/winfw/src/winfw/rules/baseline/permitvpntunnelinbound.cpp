@@ -0,0 +1,107 @@
+#include "stdafx.h"
+#include "permitvpntunnelinbound.h"
+#include <winfw/mullvadguids.h>
+#include <winfw/rules/shared.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/conditions/conditioninterface.h>
+#include <libwfp/conditions/conditionport.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+namespace
+{
+
+const GUID &FilterKey(WinFwProtocol protocol, bool ipv6)
+{
+	if (WinFwProtocol::Tcp == protocol)
+	{
+		return ipv6
+			? MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv6()
+			: MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Tcp_Ipv4();
+	}
+
+	return ipv6
+		? MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv6()
+		: MullvadGuids::Filter_Baseline_PermitVpnTunnelInbound_Udp_Ipv4();
+}
+
+} // anonymous namespace
+
+PermitVpnTunnelInbound::PermitVpnTunnelInbound(
+	const std::wstring &tunnelInterfaceAlias,
+	const std::vector<WinFwInboundPort> &ports
+)
+	: m_tunnelInterfaceAlias(tunnelInterfaceAlias)
+	, m_ports(ports)
+{
+}
+
+bool PermitVpnTunnelInbound::apply(IObjectInstaller &objectInstaller)
+{
+	for (const auto protocol : { WinFwProtocol::Tcp, WinFwProtocol::Udp })
+	{
+		if (!applyFilter(objectInstaller, protocol, false)
+			|| !applyFilter(objectInstaller, protocol, true))
+		{
+			return false;
+		}
+	}
+
+	return true;
+}
+
+bool PermitVpnTunnelInbound::applyFilter(IObjectInstaller &objectInstaller, WinFwProtocol protocol, bool ipv6) const
+{
+	std::vector<uint16_t> ports;
+
+	for (const auto &port : m_ports)
+	{
+		if (port.protocol == protocol)
+		{
+			ports.push_back(port.port);
+		}
+	}
+
+	//
+	// A filter without port conditions would match all ports, so skip it.
+	//
+
+	if (ports.empty())
+	{
+		return true;
+	}
+
+	const auto &layer = ipv6 ? FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6 : FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4;
+
+	wfp::FilterBuilder filterBuilder;
+
+	filterBuilder
+		.key(FilterKey(protocol, ipv6))
+		.name(ipv6
+			? L"Permit inbound connections to user-defined ports on tunnel interface (IPv6)"
+			: L"Permit inbound connections to user-defined ports on tunnel interface (IPv4)")
+		.description(L"This filter is part of a rule that permits inbound connections inside the VPN tunnel")
+		.provider(MullvadGuids::Provider())
+		.layer(layer)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	wfp::ConditionBuilder conditionBuilder(layer);
+
+	conditionBuilder.add_condition(ConditionInterface::Alias(m_tunnelInterfaceAlias));
+	conditionBuilder.add_condition(CreateProtocolCondition(protocol));
+
+	for (const auto port : ports)
+	{
+		conditionBuilder.add_condition(ConditionPort::Local(port));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
@@ -0,0 +1,30 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <winfw/winfw.h>
+#include <string>
+#include <vector>
+
+namespace rules::baseline
+{
+
+class PermitVpnTunnelInbound : public IFirewallRule
+{
+public:
+
+	PermitVpnTunnelInbound(
+		const std::wstring &tunnelInterfaceAlias,
+		const std::vector<WinFwInboundPort> &ports
+	);
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	bool applyFilter(IObjectInstaller &objectInstaller, WinFwProtocol protocol, bool ipv6) const;
+
+	const std::wstring m_tunnelInterfaceAlias;
+	const std::vector<WinFwInboundPort> m_ports;
+};
+
+}
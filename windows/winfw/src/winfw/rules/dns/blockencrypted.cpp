@@ -0,0 +1,118 @@
+#include "stdafx.h"
+#include "blockencrypted.h"
+#include <winfw/mullvadguids.h>
+#include <winfw/rules/ports.h>
+#include <winfw/rules/shared.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/conditions/conditionport.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::dns
+{
+
+BlockEncrypted::BlockEncrypted(const std::vector<wfp::IpAddress> &dohServers)
+{
+	SplitAddresses(dohServers, m_dohServersIpv4, m_dohServersIpv6);
+}
+
+bool BlockEncrypted::apply(IObjectInstaller &objectInstaller)
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Block outbound DNS over TLS, IPv4.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Dns_BlockDnsOverTls_Outbound_Ipv4())
+		.name(L"Block outbound DNS over TLS (IPv4)")
+		.description(L"This filter is part of a rule that blocks encrypted DNS requests")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+		.sublayer(MullvadGuids::SublayerDns())
+		.weight(wfp::FilterBuilder::WeightClass::Min)
+		.block();
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+	conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
+
+	if (false == objectInstaller.addFilter(filterBuilder, conditionBuilder))
+	{
+		return false;
+	}
+
+	//
+	// #2 Block outbound DNS over TLS, IPv6.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Dns_BlockDnsOverTls_Outbound_Ipv6())
+		.name(L"Block outbound DNS over TLS (IPv6)")
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	conditionBuilder.reset(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
+
+	if (false == objectInstaller.addFilter(filterBuilder, conditionBuilder))
+	{
+		return false;
+	}
+
+	//
+	// #3 Block outbound DNS over HTTPS, IPv4.
+	//
+
+	if (false == m_dohServersIpv4.empty())
+	{
+		filterBuilder
+			.key(MullvadGuids::Filter_Dns_BlockDnsOverHttps_Outbound_Ipv4())
+			.name(L"Block outbound DNS over HTTPS (IPv4)")
+			.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		conditionBuilder.reset(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		conditionBuilder.add_condition(ConditionPort::Remote(HTTPS_PORT));
+
+		for (const auto &server : m_dohServersIpv4)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(server));
+		}
+
+		if (false == objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	if (m_dohServersIpv6.empty())
+	{
+		return true;
+	}
+
+	//
+	// #4 Block outbound DNS over HTTPS, IPv6.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Dns_BlockDnsOverHttps_Outbound_Ipv6())
+		.name(L"Block outbound DNS over HTTPS (IPv6)")
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	conditionBuilder.reset(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	conditionBuilder.add_condition(ConditionPort::Remote(HTTPS_PORT));
+
+	for (const auto &server : m_dohServersIpv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(server));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
@@ -0,0 +1,24 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <libwfp/ipaddress.h>
+#include <vector>
+
+namespace rules::dns
+{
+
+class BlockEncrypted : public IFirewallRule
+{
+public:
+
+	BlockEncrypted(const std::vector<wfp::IpAddress> &dohServers);
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	std::vector<wfp::IpAddress> m_dohServersIpv4;
+	std::vector<wfp::IpAddress> m_dohServersIpv6;
+};
+
+}
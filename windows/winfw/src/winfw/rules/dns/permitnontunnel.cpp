@@ -44,6 +44,7 @@ bool PermitNonTunnel::apply(IObjectInstaller &objectInstaller)
 		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
 
 		conditionBuilder.add_condition(ConditionPort::Remote(DNS_SERVER_PORT));
+		conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
 
 		for (const auto &host : m_hostsIpv4)
 		{
@@ -83,6 +84,7 @@ bool PermitNonTunnel::apply(IObjectInstaller &objectInstaller)
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
 
 	conditionBuilder.add_condition(ConditionPort::Remote(DNS_SERVER_PORT));
+	conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
 
 	for (const auto &host : m_hostsIpv6)
 	{
@@ -44,6 +44,7 @@ bool PermitTunnel::apply(IObjectInstaller &objectInstaller)
 		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
 
 		conditionBuilder.add_condition(ConditionPort::Remote(DNS_SERVER_PORT));
+		conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
 		conditionBuilder.add_condition(ConditionInterface::Alias(m_tunnelInterfaceAlias));
 
 		for (const auto &host : m_hostsIpv4)
@@ -79,6 +80,7 @@ bool PermitTunnel::apply(IObjectInstaller &objectInstaller)
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
 
 	conditionBuilder.add_condition(ConditionPort::Remote(DNS_SERVER_PORT));
+	conditionBuilder.add_condition(ConditionPort::Remote(DNS_OVER_TLS_PORT));
 	conditionBuilder.add_condition(ConditionInterface::Alias(m_tunnelInterfaceAlias));
 
 	for (const auto &host : m_hostsIpv6)
@@ -14,6 +14,9 @@ enum Ports : uint16_t
 	DHCPV6_SERVER_PORT = 547,
 
 	DNS_SERVER_PORT = 53,
+	DNS_OVER_TLS_PORT = 853,
+
+	HTTPS_PORT = 443,
 };
 
 }
@@ -303,7 +303,14 @@ WinFw_ApplyPolicyConnected(
 	const wchar_t *v4Gateway,
 	const wchar_t *v6Gateway,
 	const wchar_t * const *dnsServers,
-	size_t numDnsServers
+	size_t numDnsServers,
+	bool blockEncryptedDns,
+	const wchar_t * const *dohServers,
+	size_t numDohServers,
+	const WinFwInboundPort *inboundPorts,
+	size_t numInboundPorts,
+	const WinFwAllowedLanNet *excludedNetworks,
+	size_t numExcludedNetworks
 )
 {
 	if (nullptr == g_fwContext)
@@ -343,6 +350,21 @@ WinFw_ApplyPolicyConnected(
 			THROW_ERROR("Invalid argument: dnsServers");
 		}
 
+		if (nullptr == dohServers && 0 != numDohServers)
+		{
+			THROW_ERROR("Invalid argument: dohServers");
+		}
+
+		if (nullptr == inboundPorts && 0 != numInboundPorts)
+		{
+			THROW_ERROR("Invalid argument: inboundPorts");
+		}
+
+		if (nullptr == excludedNetworks && 0 != numExcludedNetworks)
+		{
+			THROW_ERROR("Invalid argument: excludedNetworks");
+		}
+
 		std::vector<wfp::IpAddress> tunnelDnsServers;
 		std::vector<wfp::IpAddress> nonTunnelDnsServers;
 
@@ -382,6 +404,13 @@ WinFw_ApplyPolicyConnected(
 			addToDnsCollection(ip.type() == wfp::IpAddress::Type::Ipv4 ? v4GatewayIp : v6GatewayIp, std::move(ip));
 		}
 
+		std::vector<wfp::IpAddress> encryptedDnsServers;
+
+		for (size_t i = 0; i < numDohServers; i++)
+		{
+			encryptedDnsServers.emplace_back(wfp::IpAddress(dohServers[i]));
+		}
+
 		if (nullptr != g_logSink)
 		{
 			std::stringstream ss;
@@ -413,7 +442,11 @@ WinFw_ApplyPolicyConnected(
 			relayClient,
 			tunnelInterfaceAlias,
 			tunnelDnsServers,
-			nonTunnelDnsServers
+			nonTunnelDnsServers,
+			blockEncryptedDns,
+			encryptedDnsServers,
+			std::vector<WinFwInboundPort>(inboundPorts, inboundPorts + numInboundPorts),
+			std::vector<WinFwAllowedLanNet>(excludedNetworks, excludedNetworks + numExcludedNetworks)
 		) ? WINFW_POLICY_STATUS_SUCCESS : WINFW_POLICY_STATUS_GENERAL_FAILURE;
 	}
 	catch (common::error::WindowsException &err)
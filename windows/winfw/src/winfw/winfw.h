@@ -19,15 +19,12 @@
 // Structures
 ///////////////////////////////////////////////////////////////////////////////
 
-typedef struct tag_WinFwSettings
+typedef struct tag_WinFwAllowedLanNet
 {
-	// Permit outbound DHCP requests and inbound DHCP responses on all interfaces.
-	bool permitDhcp;
-
-	// Permit all traffic to and from private address ranges.
-	bool permitLan;
+	const wchar_t *address;
+	uint8_t prefixLength;
 }
-WinFwSettings;
+WinFwAllowedLanNet;
 
 enum WinFwProtocol : uint8_t
 {
@@ -35,6 +32,56 @@ enum WinFwProtocol : uint8_t
 	Udp = 1,
 };
 
+enum WinFwFirewallExceptionDirection : uint8_t
+{
+	Inbound = 0,
+	Outbound = 1,
+};
+
+typedef struct tag_WinFwFirewallException
+{
+	WinFwFirewallExceptionDirection direction;
+	WinFwProtocol protocol;
+	uint16_t port;
+}
+WinFwFirewallException;
+
+typedef struct tag_WinFwInboundPort
+{
+	WinFwProtocol protocol;
+	uint16_t port;
+}
+WinFwInboundPort;
+
+typedef struct tag_WinFwSettings
+{
+	// Permit outbound DHCP requests and inbound DHCP responses on all interfaces.
+	bool permitDhcp;
+
+	// Permit the subset of NDP required for router and neighbor discovery on all interfaces.
+	bool permitNdp;
+
+	// Permit all traffic to and from the networks in `allowedLanNets`.
+	bool permitLan;
+
+	// Permit mDNS and SSDP discovery on the local network, even if `permitLan` is not set.
+	bool permitMulticast;
+
+	// Private networks that are reachable when `permitLan` is set.
+	const WinFwAllowedLanNet *allowedLanNets;
+	uint32_t numAllowedLanNets;
+
+	// Ports that are reachable on private networks regardless of `permitLan`.
+	const WinFwFirewallException *firewallExceptions;
+	uint32_t numFirewallExceptions;
+
+	// Permit outbound connections to `captivePortalAddress`, or to any address if it is null,
+	// so that the user can sign in to a captive portal. DNS remains blocked.
+	bool permitCaptivePortal;
+	const wchar_t *captivePortalAddress;
+}
+WinFwSettings;
+
 typedef struct tag_WinFwEndpoint
 {
 	const wchar_t *ip;
@@ -177,6 +224,8 @@ WinFw_ApplyPolicyConnecting(
 // - Non-DNS traffic inside the VPN tunnel
 // - DNS requests inside the VPN tunnel to any specified remote DNS server
 // - DNS requests outside the VPN tunnel to any specified local DNS servers
+// - Inbound connections inside the VPN tunnel to any specified local ports
+// - Connections outside the VPN tunnel to any specified excluded networks
 //
 // Parameters:
 //
@@ -184,6 +233,14 @@ WinFw_ApplyPolicyConnecting(
 //   Friendly name of VPN tunnel interface
 // dnsServers:
 //   Array of string-encoded IP addresses of DNS servers to use
+// blockEncryptedDns:
+//   Whether to block DNS over TLS, except to the DNS servers, and DNS over HTTPS to dohServers
+// dohServers:
+//   Array of string-encoded IP addresses of DNS over HTTPS resolvers to block
+// inboundPorts:
+//   Array of local ports that accept inbound connections inside the VPN tunnel
+// excludedNetworks:
+//   Array of networks that are routed outside the VPN tunnel
 //
 extern "C"
 WINFW_LINKAGE
@@ -197,7 +254,14 @@ WinFw_ApplyPolicyConnected(
 	const wchar_t *v4Gateway,
 	const wchar_t *v6Gateway,
 	const wchar_t * const *dnsServers,
-	size_t numDnsServers
+	size_t numDnsServers,
+	bool blockEncryptedDns,
+	const wchar_t * const *dohServers,
+	size_t numDohServers,
+	const WinFwInboundPort *inboundPorts,
+	size_t numInboundPorts,
+	const WinFwAllowedLanNet *excludedNetworks,
+	size_t numExcludedNetworks
 );
 
 //